@@ -7,7 +7,7 @@ use crate::convertors::ufo::load_font_info;
 use crate::convertors::ufo::load_glyphs;
 use crate::convertors::ufo::load_master_info;
 use crate::convertors::ufo::norad_glyph_to_babelfont_layer;
-use crate::{Axis, BabelfontError, Font, Location, Master};
+use crate::{Axis, BabelfontError, Font, Instance, Location, Master};
 
 use designspace::{Axis as DSAxis, Designspace, Instance as DSInstance};
 
@@ -25,7 +25,7 @@ pub fn load(path: PathBuf) -> Result<Font, BabelfontError> {
     let mut font = Font::new();
     load_axes(&mut font, &ds.axes.axis);
     if let Some(instances) = &ds.instances {
-        load_instances(&mut font, &instances.instance);
+        load_instances(&mut font, &ds, &instances.instance);
     }
     let default_master = ds
         .default_master()
@@ -43,6 +43,7 @@ pub fn load(path: PathBuf) -> Result<Font, BabelfontError> {
     })?;
     load_glyphs(&mut font, &default_ufo);
     load_masters(&mut font, &ds, relative)?;
+    font.glyphs.reindex();
     let info = default_ufo.font_info;
     load_font_info(&mut font, &info);
     font.features = Some(default_ufo.features);
@@ -62,8 +63,22 @@ fn load_axes(font: &mut Font, axes: &[DSAxis]) {
     }
 }
 
-pub(crate) fn load_instances(_font: &mut Font, _instances: &[DSInstance]) {
-    // unimplemented!()
+pub(crate) fn load_instances(font: &mut Font, ds: &Designspace, instances: &[DSInstance]) {
+    for instance in instances {
+        let location = Location(
+            ds.axes
+                .axis
+                .iter()
+                .map(|x| x.tag.clone())
+                .zip(ds.location_to_tuple(&instance.location))
+                .collect(),
+        );
+        font.instances.push(Instance {
+            name: instance.name.as_ref().unwrap_or(&instance.stylename).into(),
+            location,
+            style_name: (&instance.stylename).into(),
+        });
+    }
 }
 
 fn load_masters(
@@ -615,6 +615,13 @@ lazy_static! {
         ("isFixedPitch", "post", "isFixedPitch"),
         ("postscriptIsFixedPitch", "post", "isFixedPitch"),
     ];
+    // The last item is the largest value allowed in the list
+    static ref BITFIELD_CP: Vec<(&'static str, &'static str, &'static str, u8)> = vec![
+        ("panose", "OS2", "panose", 63),
+        ("openTypeOS2Panose", "OS2", "panose", 63),
+        ("unicodeRanges", "OS2", "unicodeRanges", 127),
+        ("openTypeOS2UnicodeRanges", "OS2", "unicodeRanges", 127),
+    ];
 
     // XXX fsType
 }
@@ -647,6 +654,25 @@ fn load_custom_parameters(ot_values: &mut Vec<OTValue>, params: HashMap<String,
             });
         }
     }
+    for (key, table, field, max) in BITFIELD_CP.iter() {
+        if let Some(v) = params.get(*key).and_then(|v| v.as_array()) {
+            ot_values.push(OTValue {
+                table: table.to_string(),
+                field: field.to_string(),
+                value: OTScalar::BitField(
+                    v.iter()
+                        .filter_map(|x| match x.as_i64() {
+                            Some(bit) if (0..=*max as i64).contains(&bit) => Some(bit as u8),
+                            _ => {
+                                log::warn!("Ignoring invalid value {:?} in {:?}", x, key);
+                                None
+                            }
+                        })
+                        .collect(),
+                ),
+            });
+        }
+    }
 }
 
 fn load_instance(font: &mut Font, plist: &Plist) {
@@ -707,6 +733,26 @@ fn fixup_axis_mappings(font: &mut Font) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_bitfield_parameters() {
+        let panose = Plist::parse("(2, 11, 64, \"x\")").unwrap();
+        let ranges = Plist::parse("(0, 122, 128, -1)").unwrap();
+        let mut params = HashMap::new();
+        params.insert("panose".to_string(), &panose);
+        params.insert("unicodeRanges".to_string(), &ranges);
+        let mut ot_values = vec![];
+        load_custom_parameters(&mut ot_values, params);
+        let value = |field: &str| match ot_values.iter().find(|v| v.field == field) {
+            Some(OTValue {
+                value: OTScalar::BitField(bits),
+                ..
+            }) => bits.clone(),
+            _ => panic!("No bitfield for {}", field),
+        };
+        assert_eq!(value("panose"), vec![2, 11]);
+        assert_eq!(value("unicodeRanges"), vec![0, 122]);
+    }
+
     #[test]
     fn do_something() {
         let f = load("data/Nunito3.glyphs".into()).unwrap();
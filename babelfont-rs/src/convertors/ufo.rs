@@ -38,6 +38,7 @@ pub fn load(path: PathBuf) -> Result<Font, BabelfontError> {
             }
         }
     }
+    font.glyphs.reindex();
     font.features = Some(ufo.features);
     font.masters.push(master);
     Ok(font)
@@ -123,6 +124,31 @@ pub(crate) fn load_font_info(font: &mut Font, info: &norad::FontInfo) {
     if let Some(v) = info.open_type_head_lowest_rec_ppem {
         font.set_ot_value("head", "lowestRecPPEM", OTScalar::Unsigned(v))
     }
+    if let Some(p) = &info.open_type_os2_panose {
+        let digits = [
+            p.family_type,
+            p.serif_style,
+            p.weight,
+            p.proportion,
+            p.contrast,
+            p.stroke_variation,
+            p.arm_style,
+            p.letterform,
+            p.midline,
+            p.x_height,
+        ];
+        font.set_ot_value(
+            "OS2",
+            "panose",
+            OTScalar::BitField(digits.iter().map(|&d| d as u8).collect()),
+        )
+    }
+    if let Some(v) = &info.open_type_os2_unicode_ranges {
+        font.set_ot_value("OS2", "unicodeRanges", OTScalar::BitField(v.to_vec()))
+    }
+    if let Some(v) = &info.open_type_os2_code_page_ranges {
+        font.set_ot_value("OS2", "codePageRanges", OTScalar::BitField(v.to_vec()))
+    }
     // XXX and much more
     if let Some(v) = &info.trademark {
         font.names.trademark = v.into();
@@ -54,7 +54,7 @@ impl Font {
             axes: vec![],
             instances: vec![],
             masters: vec![],
-            glyphs: GlyphList(vec![]),
+            glyphs: GlyphList::default(),
             note: None,
             date: chrono::Local::now(),
             names: Names::new(),
@@ -99,14 +99,7 @@ impl Font {
     }
 
     pub fn master_layer_for(&self, glyphname: &str, master: &Master) -> Option<&Layer> {
-        if let Some(glyph) = self.glyphs.get(glyphname) {
-            for layer in &glyph.layers {
-                if layer.id == Some(master.id.clone()) {
-                    return Some(layer);
-                }
-            }
-        }
-        None
+        self.glyphs.get_layer(glyphname, &master.id)
     }
 
     pub fn ot_value(
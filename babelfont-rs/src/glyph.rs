@@ -1,25 +1,101 @@
 use crate::common::Direction;
 use crate::layer::Layer;
+use std::collections::HashMap;
+
+/// A list of glyphs, indexed by name and by master layer.
+///
+/// The indices are kept up to date by `push`. Handing out mutable access
+/// through `iter_mut`/`get_mut` marks them as stale, since glyphs or layers
+/// may be renamed; until `reindex` is called, lookups which the index can't
+/// answer fall back to a linear scan. Where names are duplicated, the first
+/// glyph with the name is the one found.
+#[derive(Debug, Default, Shrinkwrap)]
+pub struct GlyphList {
+    #[shrinkwrap(main_field)]
+    glyphs: Vec<Glyph>,
+    name_index: HashMap<String, usize>,
+    layer_index: Vec<HashMap<String, usize>>,
+    stale: bool,
+}
 
-#[derive(Debug, Shrinkwrap)]
-#[shrinkwrap(mutable)]
-pub struct GlyphList(pub Vec<Glyph>);
 impl GlyphList {
-    pub fn get(&self, g: &str) -> Option<&Glyph> {
-        for glyph in self.0.iter() {
-            if glyph.name == g {
-                return Some(glyph);
+    pub fn new(glyphs: Vec<Glyph>) -> Self {
+        let mut list = GlyphList {
+            glyphs,
+            ..Default::default()
+        };
+        list.reindex();
+        list
+    }
+
+    pub fn push(&mut self, glyph: Glyph) {
+        self.name_index
+            .entry(glyph.name.clone())
+            .or_insert(self.glyphs.len());
+        self.layer_index.push(Self::layer_ids(&glyph));
+        self.glyphs.push(glyph);
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Glyph> {
+        self.stale = true;
+        self.glyphs.iter_mut()
+    }
+
+    /// Rebuilds the name and layer indices from scratch.
+    pub fn reindex(&mut self) {
+        self.name_index = HashMap::new();
+        for (ix, g) in self.glyphs.iter().enumerate() {
+            self.name_index.entry(g.name.clone()).or_insert(ix);
+        }
+        self.layer_index = self.glyphs.iter().map(Self::layer_ids).collect();
+        self.stale = false;
+    }
+
+    fn layer_ids(glyph: &Glyph) -> HashMap<String, usize> {
+        glyph
+            .layers
+            .iter()
+            .enumerate()
+            .filter_map(|(ix, l)| l.id.as_ref().map(|id| (id.clone(), ix)))
+            .collect()
+    }
+
+    fn index_of(&self, g: &str) -> Option<usize> {
+        if let Some(&ix) = self.name_index.get(g) {
+            if self.glyphs.get(ix).is_some_and(|glyph| glyph.name == g) {
+                return Some(ix);
             }
         }
-        None
+        // An up to date index knows every name, so a miss means there is no
+        // such glyph
+        if !self.stale {
+            return None;
+        }
+        self.glyphs.iter().position(|glyph| glyph.name == g)
     }
-    pub fn get_mut(&mut self, g: &str) -> Option<&Glyph> {
-        for glyph in self.0.iter_mut() {
-            if glyph.name == g {
-                return Some(glyph);
+
+    pub fn get(&self, g: &str) -> Option<&Glyph> {
+        self.index_of(g).map(|ix| &self.glyphs[ix])
+    }
+
+    pub fn get_mut(&mut self, g: &str) -> Option<&mut Glyph> {
+        let ix = self.index_of(g)?;
+        self.stale = true;
+        Some(&mut self.glyphs[ix])
+    }
+
+    /// Returns the layer of glyph `g` whose id is `layer_id` (usually a master id).
+    pub fn get_layer(&self, g: &str, layer_id: &str) -> Option<&Layer> {
+        let ix = self.index_of(g)?;
+        let glyph = &self.glyphs[ix];
+        if let Some(&layer_ix) = self.layer_index.get(ix).and_then(|m| m.get(layer_id)) {
+            if let Some(layer) = glyph.layers.get(layer_ix) {
+                if layer.id.as_deref() == Some(layer_id) {
+                    return Some(layer);
+                }
             }
         }
-        None
+        glyph.get_layer(layer_id)
     }
 }
 
@@ -50,3 +126,63 @@ impl Glyph {
         self.layers.iter_mut().find(|l| l.id.as_deref() == Some(id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph(name: &str, layer_ids: &[&str]) -> Glyph {
+        Glyph {
+            name: name.to_string(),
+            production_name: None,
+            category: GlyphCategory::Base,
+            codepoints: vec![],
+            layers: layer_ids
+                .iter()
+                .map(|id| {
+                    let mut l = Layer::new(500);
+                    l.id = Some(id.to_string());
+                    l
+                })
+                .collect(),
+            exported: true,
+            direction: None,
+        }
+    }
+
+    #[test]
+    fn test_glyphlist_index() {
+        let mut list = GlyphList::new(vec![glyph("a", &["m1", "m2"])]);
+        list.push(glyph("b", &["m2"]));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.get("b").unwrap().name, "b");
+        assert!(list.get("c").is_none());
+        assert!(list.get_layer("a", "m2").is_some());
+        assert!(list.get_layer("b", "m1").is_none());
+
+        // Mutations behind the index's back are still found
+        list.get_mut("b")
+            .unwrap()
+            .layers
+            .insert(0, glyph("x", &["m1"]).layers.remove(0));
+        assert_eq!(list.get_layer("b", "m2").unwrap().id.as_deref(), Some("m2"));
+        list.get_mut("a").unwrap().name = "c".to_string();
+        assert!(list.get("a").is_none());
+        assert_eq!(list.get("c").unwrap().name, "c");
+        list.reindex();
+        assert!(!list.stale);
+        assert_eq!(list.get("c").unwrap().name, "c");
+        assert!(list.get("a").is_none());
+    }
+
+    #[test]
+    fn test_glyphlist_duplicate_names() {
+        let mut first = glyph("a", &["m1"]);
+        first.codepoints = vec![0x61];
+        let mut list = GlyphList::new(vec![first, glyph("a", &["m2"])]);
+        assert_eq!(list.get("a").unwrap().codepoints, vec![0x61]);
+        list.push(glyph("a", &["m3"]));
+        assert_eq!(list.get("a").unwrap().codepoints, vec![0x61]);
+        assert!(list.get_layer("a", "m2").is_none());
+    }
+}
@@ -23,7 +23,7 @@ pub use crate::common::{Location, Position};
 pub use crate::common::{Node, NodeType, OTScalar};
 pub use crate::error::BabelfontError;
 pub use crate::font::Font;
-pub use crate::glyph::{Glyph, GlyphList};
+pub use crate::glyph::{Glyph, GlyphCategory, GlyphList};
 pub use crate::guide::Guide;
 pub use crate::instance::Instance;
 pub use crate::layer::Layer;
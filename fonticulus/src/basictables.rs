@@ -6,28 +6,31 @@ use fonttools::tables::head::head;
 use fonttools::tables::name::{name, NameRecord, NameRecordID};
 use fonttools::tables::os2::os2;
 use fonttools::tables::post::post;
-use fonttools::tables::{cmap, glyf, hhea, hmtx};
+use fonttools::tables::{cmap, hhea, hmtx};
 use fonttools::tag;
 use fonttools::types::Tag;
+use kurbo::Rect;
 use std::cmp::{max, min};
 
 use std::collections::BTreeMap;
 
 // This takes a babelfont font, and creates most of the output fonttools-rs font.
+// The outline tables (and maxp, which depends on them) are left to the caller.
 pub fn fill_tables(
     input: &babelfont::Font,
-    glyf_table: glyf::glyf,
+    sfnt_version: font::SfntVersion,
+    bounds: &[Rect],
     metrics: Vec<hmtx::Metric>,
     glyph_names: Vec<String>,
     codepoint_to_gid_mapping: BTreeMap<u32, u16>,
 ) -> Font {
-    let mut font = Font::new(font::SfntVersion::TrueType);
-    let head_table = compile_head(input, &glyf_table);
+    let mut font = Font::new(sfnt_version);
+    let head_table = compile_head(input, bounds);
     let post_table = compile_post(input, &glyph_names);
-    let os2_table = compile_os2(input, &metrics, &glyf_table, &codepoint_to_gid_mapping);
+    let os2_table = compile_os2(input, &metrics, bounds, &codepoint_to_gid_mapping);
     let cmap_table = compile_cmap(input, &glyph_names, codepoint_to_gid_mapping);
     let name_table = compile_name(input);
-    let mut hhea_table = compile_hhea(input, &metrics, &glyf_table);
+    let mut hhea_table = compile_hhea(input, &metrics, bounds);
 
     // Serializing the hmtx table determines the number of "long" horizontal metrics,
     // (as sum glyphs can be stored *without* an advance width, only an LSB)
@@ -37,25 +40,18 @@ pub fn fill_tables(
     let (hmtx_bytes, num_h_metrics) = hmtx_table.to_bytes();
     hhea_table.numberOfHMetrics = num_h_metrics;
 
-    let maxp_table = glyf_table.as_maxp10();
-
     font.tables.insert(head_table);
     font.tables.insert(hhea_table);
-    font.tables.insert(maxp_table);
     font.tables.insert(os2_table);
     font.tables.insert_raw(tag!("hmtx"), hmtx_bytes);
     font.tables.insert(cmap_table);
-    font.tables.insert(glyf_table);
     font.tables.insert(name_table);
     font.tables.insert(post_table);
 
-    // Don't worry, this will get filled in on `font.save`.
-    font.tables.insert_raw(tag!("loca"), vec![0]);
-
     font
 }
 
-pub fn compile_head(font: &babelfont::Font, glyf: &glyf::glyf) -> head {
+pub fn compile_head(font: &babelfont::Font, bounds: &[Rect]) -> head {
     let mut minor = font.version.1;
     while minor > 999 {
         minor /= 10;
@@ -67,11 +63,11 @@ pub fn compile_head(font: &babelfont::Font, glyf: &glyf::glyf) -> head {
     let mut x_max: i16 = 0;
     let mut y_min: i16 = 0;
     let mut y_max: i16 = 0;
-    for glyph in &glyf.glyphs {
-        x_min = min(x_min, glyph.xMin);
-        x_max = max(x_max, glyph.xMax);
-        y_min = min(y_min, glyph.yMin);
-        y_max = max(y_max, glyph.yMax);
+    for bound in bounds {
+        x_min = min(x_min, bound.x0 as i16);
+        x_max = max(x_max, bound.x1 as i16);
+        y_min = min(y_min, bound.y0 as i16);
+        y_max = max(y_max, bound.y1 as i16);
     }
 
     let created_date = font.date.naive_local();
@@ -200,7 +196,7 @@ pub fn compile_cmap(
 pub fn compile_hhea(
     input: &babelfont::Font,
     metrics: &[hmtx::Metric],
-    glyf: &glyf::glyf,
+    bounds: &[Rect],
 ) -> hhea::hhea {
     let lineGap = input
         .ot_value("hhea", "lineGap", true)
@@ -224,11 +220,11 @@ pub fn compile_hhea(
     let minRightSideBearing = metrics
         .iter()
         .map(|x| x.advanceWidth as i16)
-        .zip(glyf.glyphs.iter().map(|g| g.xMax))
+        .zip(bounds.iter().map(|b| b.x1 as i16))
         .map(|t| t.0 - t.1)
         .min()
         .unwrap_or(0);
-    let xMaxExtent = glyf.glyphs.iter().map(|g| g.xMax).max().unwrap_or(0);
+    let xMaxExtent = bounds.iter().map(|b| b.x1 as i16).max().unwrap_or(0);
     hhea::hhea {
         majorVersion: 1,
         minorVersion: 0,
@@ -255,7 +251,7 @@ pub fn compile_hhea(
 pub fn compile_os2(
     input: &babelfont::Font,
     metrics: &[hmtx::Metric],
-    _glyf: &glyf::glyf,
+    bounds: &[Rect],
     mapping: &BTreeMap<u32, u16>,
 ) -> os2 {
     let upm = input.upm as f64;
@@ -280,7 +276,8 @@ pub fn compile_os2(
     let sTypoLineGap = input
         .ot_value("OS2", "sTypoLineGap", true)
         .map(i32::from)
-        .unwrap_or((upm * 1.2) as i32 + (font_ascender - font_descender) as i32)
+        // As in ufo2ft, the line gap makes up the rest of 1.2em, if there is any
+        .unwrap_or(0.max((upm * 1.2) as i32 - (font_ascender - font_descender) as i32))
         as i16;
 
     let ySubscriptYOffset = input
@@ -320,9 +317,16 @@ pub fn compile_os2(
         .ot_value("OS2", "ySuperscriptYSize", true)
         .map_or((upm * 0.6).round() as i16, i16::from);
 
-    let xAvgCharWidth = (metrics.iter().map(|m| m.advanceWidth as f32).sum::<f32>()
-        / metrics.iter().filter(|m| m.advanceWidth != 0).count() as f32)
-        .round() as i16;
+    let widths: Vec<f32> = metrics
+        .iter()
+        .filter(|m| m.advanceWidth != 0)
+        .map(|m| m.advanceWidth as f32)
+        .collect();
+    let xAvgCharWidth = if widths.is_empty() {
+        0
+    } else {
+        (widths.iter().sum::<f32>() / widths.len() as f32).round() as i16
+    };
     let yStrikeoutSize = input
         .ot_value("OS2", "yStrikeoutSize", true)
         .map_or_else(|| postscript_underline_thickness(input).into(), i32::from)
@@ -332,7 +336,8 @@ pub fn compile_os2(
         .map_or((x_height as f32 * 0.22) as i16, i16::from);
     let achVendID = input
         .ot_value("OS2", "achVendID", true)
-        .map_or(tag!("NONE"), |x| Tag::from_raw(String::from(x)).unwrap());
+        .and_then(|x| Tag::from_raw(String::from(x)).ok())
+        .unwrap_or(tag!("NONE"));
     let usWeightClass = input
         .ot_value("OS2", "usWeightClass", true)
         .map_or(400, i16::from) as u16;
@@ -346,12 +351,17 @@ pub fn compile_os2(
     );
     let usFirstCharIndex = *mapping.keys().min().unwrap_or(&0xFFFF) as u16;
     let usLastCharIndex = *mapping.keys().max().unwrap_or(&0xFFFF) as u16;
+    // Windows clips anything outside the win metrics, so by default they
+    // cover both the vertical metrics and the font's bounding box.
+    let y_max = bounds.iter().map(|b| b.y1).fold(0.0, f64::max) as i32;
+    let y_min = bounds.iter().map(|b| b.y0).fold(0.0, f64::min) as i32;
     let usWinAscent = input
         .ot_value("OS2", "usWinAscent", true)
-        .map_or(font_ascender + sTypoLineGap, i16::from) as u16;
+        .map_or_else(|| max(font_ascender as i32, y_max), i32::from) as u16;
     let usWinDescent = input
         .ot_value("OS2", "usWinDescent", true)
-        .map_or(font_descender.abs(), i16::from) as u16;
+        .map_or_else(|| max(-(font_descender as i32), -y_min), i32::from)
+        as u16;
     let sFamilyClass = input
         .ot_value("OS2", "familyClass", true)
         .map(i16::from)
@@ -383,7 +393,7 @@ pub fn compile_os2(
         sTypoLineGap,
         usWinAscent,
         usWinDescent,
-        usMaxContext: Some(0), // This should be changed later by the feature compiler
+        usMaxContext: Some(0), // This is set once the layout tables have been built
         usBreakChar: Some(32), // Yes, these are constants
         usDefaultChar: Some(0), // this too
         // sFamilyClass: input.open_type_os2_family_class... (not public)
@@ -391,16 +401,21 @@ pub fn compile_os2(
         panose: get_panose(input),
         ulCodePageRange1: Some(0),
         ulCodePageRange2: Some(0),
-        ulUnicodeRange1: 0b10100001000000000000000011111111, // XXX
-        ulUnicodeRange2: 0,                                  // XXX
-        ulUnicodeRange3: 0,                                  // XXX
-        ulUnicodeRange4: 0,                                  // XXX
+        ulUnicodeRange1: 0,
+        ulUnicodeRange2: 0,
+        ulUnicodeRange3: 0,
+        ulUnicodeRange4: 0,
         usFirstCharIndex,
         usLastCharIndex,
         usLowerOpticalPointSize: None,
         usUpperOpticalPointSize: None,
         fsSelection: get_selection(input),
     };
+    if let Some(OTScalar::BitField(unicode_ranges)) = input.ot_value("OS2", "unicodeRanges", true) {
+        table.int_list_to_unicode_ranges(&unicode_ranges);
+    } else {
+        table.calc_unicode_ranges(mapping);
+    }
     if let Some(OTScalar::BitField(page_ranges)) = input.ot_value("OS2", "codePageRanges", true) {
         table.int_list_to_code_page_ranges(&page_ranges);
     } else {
@@ -1,10 +1,16 @@
 use crate::basictables::fill_tables;
+use crate::cache::GlyphCache;
+use crate::cff::{build_cff, build_cff2, layers_to_outlines, GlyphOutlines};
+use crate::diagnostics::{BuildError, Diagnostics, Problem};
+use crate::gdef::build_gdef;
 use crate::glyph::layers_to_glyph;
 use crate::kerning::build_kerning;
 use babelfont::{Component, Font, Layer, Node, Path};
 use fonttools::tables::gvar::GlyphVariationData;
-use fonttools::tables::{glyf, hmtx};
+use fonttools::tables::{glyf, hmtx, maxp};
+use fonttools::types::Tag;
 use fonttools::{font, tag};
+use kurbo::Rect;
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::collections::{BTreeMap, HashSet};
@@ -43,122 +49,369 @@ pub fn build_font(
     input: &mut babelfont::Font,
     subset: &Option<HashSet<String>>,
     just_one_master: Option<usize>,
-) -> font::Font {
-    decompose_mixed_glyphs(input);
+    otf: bool,
+    kern: bool,
+    gdef: bool,
+    cache_dir: Option<&std::path::Path>,
+    diagnostics: &Diagnostics,
+) -> Result<font::Font, BuildError> {
+    check_source(input, subset, just_one_master, diagnostics)?;
+    decompose_mixed_glyphs(input, diagnostics);
+    let input: &babelfont::Font = input;
 
     // First, find the glyphs we're dealing with
     let mut codepoint_to_gid: BTreeMap<u32, u16> = BTreeMap::new();
     let mut name_to_id: BTreeMap<String, u16> = BTreeMap::new();
     let names = get_glyph_names_and_mapping(input, &mut codepoint_to_gid, &mut name_to_id, subset);
 
-    let true_model = input
-        .variation_model()
-        .expect("Couldn't get variation model");
-
+    let true_model;
     let default_master_ix;
     let base_master;
     let variation_model;
+    // Problems with a static font's glyphs belong to the master it was built from
+    let static_master_name;
 
     if let Some(master_ix) = just_one_master {
         // Oh, actually, we're not building a variable font. Just pick a master
         // and pretend that's the only thing in the font.
         default_master_ix = 0;
-        base_master = input.masters.get(master_ix).unwrap();
+        base_master = &input.masters[master_ix];
         variation_model = None;
+        static_master_name = Some(master_name(base_master));
     } else {
         default_master_ix = input
             .default_master_index()
-            .expect("Couldn't find default master");
+            .ok_or(BuildError::NoDefaultMaster)?;
 
         // Unused, but needs to have the same type...
-        base_master = input.masters.get(default_master_ix).unwrap();
+        base_master = &input.masters[default_master_ix];
 
+        true_model = input.variation_model()?;
         variation_model = Some(&true_model);
+        static_master_name = None;
     }
+    let add_master = |problems: Vec<Problem>| -> Vec<Problem> {
+        match &static_master_name {
+            Some(name) => problems
+                .into_iter()
+                .map(|p| {
+                    if p.master.is_none() {
+                        p.in_master(name)
+                    } else {
+                        p
+                    }
+                })
+                .collect(),
+            None => problems,
+        }
+    };
 
-    // The guts of this thing is the big, parallel babelfont::Glyph to glyf::Glyph convertor.
-    let result: Vec<(glyf::Glyph, hmtx::Metric, Option<GlyphVariationData>)> = input
+    let glyphs: Vec<&babelfont::Glyph> = input
         .glyphs
-        .par_iter()
-        .map(|glif| {
-            // If we are subsetting, check if we are included in the subset
-            if subset.is_some() && !subset.as_ref().unwrap().contains(&glif.name.to_string()) {
-                return None;
+        .iter()
+        // If we are subsetting, check if we are included in the subset
+        .filter(|glif| {
+            subset.is_none() || subset.as_ref().unwrap().contains(&glif.name.to_string())
+        })
+        .collect();
+
+    let layer_masters: Vec<&babelfont::Master> = if just_one_master.is_none() {
+        // Find all layers for each glyph across the designspace
+        input.masters.iter().collect()
+    } else {
+        // Nobody here but us chickens
+        vec![base_master]
+    };
+    let all_layers = |glif: &babelfont::Glyph| -> Vec<Option<&Layer>> {
+        layer_masters
+            .iter()
+            .map(|master| input.master_layer_for(&glif.name, master))
+            .collect()
+    };
+
+    // Build a basic hmtx entry (check_source has reported any missing layers)
+    let basic_metric = |glif: &babelfont::Glyph| -> hmtx::Metric {
+        let advance_width = input
+            .master_layer_for(&glif.name, base_master)
+            .map_or(0, |layer| layer.width as u16);
+        hmtx::Metric {
+            advanceWidth: advance_width,
+            lsb: 0, // Dummy LSB because we will recalculate it later
+        }
+    };
+
+    let mut font = if otf {
+        // Keep the cubic outlines and make CFF/CFF2
+        let result: Vec<(GlyphOutlines, hmtx::Metric)> = glyphs
+            .par_iter()
+            .map(|glif| {
+                let mut problems = vec![];
+                let outlines = layers_to_outlines(
+                    input,
+                    &layer_masters,
+                    &all_layers(glif),
+                    default_master_ix,
+                    &glif.name,
+                    &mut problems,
+                );
+                diagnostics.extend(add_master(problems));
+                (outlines, basic_metric(glif))
+            })
+            .collect();
+        let (outlines, mut metrics): (Vec<GlyphOutlines>, Vec<hmtx::Metric>) =
+            result.into_iter().unzip();
+        let bounds: Vec<Rect> = outlines.iter().map(|o| o.bounds).collect();
+        for (metric, bound) in metrics.iter_mut().zip(bounds.iter()) {
+            metric.lsb = bound.x0 as i16;
+        }
+
+        let mut font = fill_tables(
+            input,
+            font::SfntVersion::OpenType,
+            &bounds,
+            metrics.clone(),
+            names.clone(),
+            codepoint_to_gid,
+        );
+        font.tables.insert(maxp::maxp::new05(outlines.len() as u16));
+        if let Some(model) = variation_model {
+            let (cff2, hvar) = build_cff2(&outlines, default_master_ix, model)?;
+            font.tables.insert(cff2);
+            if let Some(hvar) = hvar {
+                font.tables.insert_raw(tag!("HVAR"), hvar);
             }
+        } else {
+            font.tables
+                .insert(build_cff(input, &outlines, &metrics, &names));
+        }
+        font
+    } else {
+        // Glyphs which haven't changed since the last build can come out of the cache
+        let cache = cache_dir.and_then(|dir| {
+            GlyphCache::new(dir, input, default_master_ix, just_one_master.is_none())
+                .map_err(|e| log::warn!("Could not use glyph cache {}: {}", dir.display(), e))
+                .ok()
+        });
 
-            let all_layers: Vec<Option<&Layer>> = if just_one_master.is_none() {
-                // Find all layers for this glyph across the designspace
-                input
-                    .masters
-                    .iter()
-                    .map(|master| input.master_layer_for(&glif.name, master))
-                    .collect()
-            } else {
-                // Nobody here but us chickens
-                vec![input.master_layer_for(&glif.name, base_master)]
-            };
+        // The guts of this thing is the big, parallel babelfont::Glyph to glyf::Glyph convertor.
+        let result: Vec<(glyf::Glyph, hmtx::Metric, Option<GlyphVariationData>)> = glyphs
+            .par_iter()
+            .map(|glif| {
+                let layers = all_layers(glif);
+                let key = cache
+                    .as_ref()
+                    .map(|cache| cache.key(&glif.name, &layers, &name_to_id));
+                let cached = cache.as_ref().zip(key.as_ref()).and_then(|(c, k)| c.get(k));
 
-            // Convert them to OT glyph objects, plus variation data
-            let (glyph, variation) = layers_to_glyph(
-                default_master_ix,
-                &name_to_id,
-                &all_layers,
-                variation_model,
-                &glif.name,
-            );
-
-            // Build a basic hmtx entry
-            let advance_width = input
-                .master_layer_for(&glif.name, input.default_master().unwrap())
-                .unwrap()
-                .width as u16;
-            let metric = hmtx::Metric {
-                advanceWidth: advance_width,
-                lsb: 0, // Dummy LSB because we will recalculate it later
-            };
+                // Convert them to OT glyph objects, plus variation data
+                let (glyph, variation) = cached.unwrap_or_else(|| {
+                    let mut problems = vec![];
+                    let converted = layers_to_glyph(
+                        default_master_ix,
+                        &name_to_id,
+                        &layers,
+                        variation_model,
+                        &glif.name,
+                        &mut problems,
+                    );
+                    // Glyphs with problems aren't cached, so that they are
+                    // reported again next time
+                    if let (Some(cache), Some(key), true) = (&cache, &key, problems.is_empty()) {
+                        cache.put(key, &converted);
+                    }
+                    diagnostics.extend(add_master(problems));
+                    converted
+                });
 
-            // Return them all together
-            Some((glyph, metric, variation))
-        })
-        .filter_map(|e| e)
-        .collect();
+                // Return them all together
+                (glyph, basic_metric(glif), variation)
+            })
+            .collect();
 
-    // We built the per-glyph data in parallel tuples, but now we want them
-    // split into individual font-level vecs
-    let (glyphs, mut metrics, variations) = result.into_iter().unzip_n_vec();
+        if let Some(cache) = &cache {
+            cache.report();
+        }
 
-    let mut glyf_table = glyf::glyf { glyphs };
+        // We built the per-glyph data in parallel tuples, but now we want them
+        // split into individual font-level vecs
+        let (glyphs, mut metrics, variations) = result.into_iter().unzip_n_vec();
 
-    // We built the glyphs in parallel (FOR SPEED) which means that some glyphs
-    // which used components may have been built before the component glyphs that
-    // they use. Obviously their glyph bounds will be undetermined until all the
-    // components are available. Now that we're done building the glyphs, we have
-    // to go over the whole glyf table again and recalculate the bounds.
-    glyf_table.recalc_bounds();
-    for (id, glyph) in glyf_table.glyphs.iter().enumerate() {
-        metrics[id].lsb = glyph.xMin;
-    }
+        let mut glyf_table = glyf::glyf { glyphs };
+
+        // We built the glyphs in parallel (FOR SPEED) which means that some glyphs
+        // which used components may have been built before the component glyphs that
+        // they use. Obviously their glyph bounds will be undetermined until all the
+        // components are available. Now that we're done building the glyphs, we have
+        // to go over the whole glyf table again and recalculate the bounds.
+        glyf_table.recalc_bounds();
+        for (id, glyph) in glyf_table.glyphs.iter().enumerate() {
+            metrics[id].lsb = glyph.xMin;
+        }
+        let bounds: Vec<Rect> = glyf_table
+            .glyphs
+            .iter()
+            .map(|g| Rect::new(g.xMin.into(), g.yMin.into(), g.xMax.into(), g.yMax.into()))
+            .collect();
+
+        // Build the font with glyf + static metadata tables
+        let mut font = fill_tables(
+            input,
+            font::SfntVersion::TrueType,
+            &bounds,
+            metrics,
+            names,
+            codepoint_to_gid,
+        );
+        font.tables.insert(glyf_table.as_maxp10());
+        font.tables.insert(glyf_table);
+        // Don't worry, this will get filled in on `font.save`.
+        font.tables.insert_raw(tag!("loca"), vec![0]);
 
-    // Build the font with glyf + static metadata tables
-    let mut font = fill_tables(input, glyf_table, metrics, names, codepoint_to_gid);
+        if just_one_master.is_none() && variations.iter().any(Option::is_some) {
+            // Put the gvar table in there (if nothing varies, which can
+            // happen when every glyph has a problem, we leave it out)
+            let gvar_table = fonttools::tables::gvar::gvar { variations };
+            font.tables
+                .insert_raw(tag!("gvar"), gvar_table.to_bytes(None));
+            // No gvar optimization by default (use ttf-optimize-gvar for IUP)
+        }
+        font
+    };
 
     // Feature writers (temporary hack)
-    let gpos_table = build_kerning(input, &name_to_id);
-    font.tables.insert(gpos_table);
-
-    if just_one_master.is_none() {
-        // Put the gvar table in there
-        let gvar_table = fonttools::tables::gvar::gvar { variations };
-        font.tables
-            .insert_raw(tag!("gvar"), gvar_table.to_bytes(None));
-        // No gvar optimization by default (use ttf-optimize-gvar for IUP)
+    if kern {
+        let gpos_table = build_kerning(input, base_master, &name_to_id, diagnostics);
+        font.tables.insert(gpos_table);
     }
+    if gdef {
+        font.tables.insert(build_gdef(input, &name_to_id));
+    }
+    set_max_context(&mut font)?;
+
+    Ok(font)
+}
+
+pub fn master_name(master: &babelfont::Master) -> String {
+    master.name.default().unwrap_or_else(|| master.id.clone())
+}
+
+// Problems with the source glyphs themselves are found here, before any
+// conversion, so that they are reported even when the converted glyphs come
+// out of the cache.
+fn check_source(
+    input: &babelfont::Font,
+    subset: &Option<HashSet<String>>,
+    just_one_master: Option<usize>,
+    diagnostics: &Diagnostics,
+) -> Result<(), BuildError> {
+    let (masters, base_master): (Vec<&babelfont::Master>, &babelfont::Master) =
+        match just_one_master {
+            Some(ix) => (vec![&input.masters[ix]], &input.masters[ix]),
+            None => (
+                input.masters.iter().collect(),
+                input.default_master().ok_or(BuildError::NoDefaultMaster)?,
+            ),
+        };
+    let problems: Vec<Problem> = input
+        .glyphs
+        .iter()
+        .filter(|glif| subset.as_ref().is_none_or(|s| s.contains(&glif.name)))
+        .collect::<Vec<&babelfont::Glyph>>()
+        .par_iter()
+        .flat_map_iter(|glif| {
+            let mut problems = vec![];
+            if input.master_layer_for(&glif.name, base_master).is_none() {
+                problems.push(
+                    Problem::new("no layer in this master; the glyph will be empty")
+                        .in_glyph(&glif.name)
+                        .in_master(&master_name(base_master)),
+                );
+            }
+            for master in &masters {
+                let layer = match input.master_layer_for(&glif.name, master) {
+                    Some(layer) => layer,
+                    None => continue,
+                };
+                let problem = |message: String| {
+                    Problem::new(message)
+                        .in_glyph(&glif.name)
+                        .in_master(&master_name(master))
+                };
+                for (ix, path) in layer.paths().enumerate() {
+                    if let Err(e) = path.to_kurbo() {
+                        problems.push(problem(format!("path {} is left out: {}", ix, e)));
+                    }
+                }
+                for component in layer.components() {
+                    if input.glyphs.get(&component.reference).is_none() {
+                        problems.push(problem(format!(
+                            "component refers to unknown glyph {}",
+                            component.reference
+                        )));
+                    }
+                }
+            }
+            problems
+        })
+        .collect();
+    diagnostics.extend(problems);
+    // compile_os2 falls back to NONE for a vendor ID that isn't a valid tag
+    if let Some(vendor) = input.ot_value("OS2", "achVendID", true) {
+        let vendor = String::from(vendor);
+        if let Err(e) = Tag::from_raw(&vendor) {
+            diagnostics.report(Problem::new(format!(
+                "vendor ID {:?} is not a valid tag ({}); using NONE",
+                vendor, e
+            )));
+        }
+    }
+    Ok(())
+}
 
-    font
+// usMaxContext can only be known once the layout tables have been built
+fn set_max_context(font: &mut font::Font) -> Result<(), BuildError> {
+    let table_error = |e: otspec::DeserializationError| BuildError::Font(e.to_string());
+    let gsub_context = font
+        .tables
+        .GSUB()
+        .map_err(table_error)?
+        .map_or(0, |gsub| gsub.max_context());
+    let gpos_context = font
+        .tables
+        .GPOS()
+        .map_err(table_error)?
+        .map_or(0, |gpos| gpos.max_context());
+    if let Some(mut os2) = font.tables.os2().map_err(table_error)? {
+        os2.usMaxContext = Some(gsub_context.max(gpos_context));
+        font.tables.insert(os2);
+    }
+    Ok(())
 }
 
-fn decomposed_components(layer: &Layer, font: &Font) -> Vec<Path> {
+// The outlines of a layer's components, transformed into place. Components
+// come from the same layer of the glyphs they refer to, so those of a layer
+// without an id are left out.
+pub fn decomposed_components(
+    glif_name: &str,
+    master: &babelfont::Master,
+    layer: &Layer,
+    font: &Font,
+    problems: &mut Vec<Problem>,
+) -> Vec<Path> {
     let mut contours = Vec::new();
+    let layer_id = match layer.id.as_deref() {
+        Some(id) => id,
+        None => {
+            if layer.has_components() {
+                problems.push(
+                    Problem::new("layer has no id, so its components are left out")
+                        .in_glyph(glif_name)
+                        .in_master(&master_name(master)),
+                );
+            }
+            return contours;
+        }
+    };
 
     let mut stack: Vec<(&Component, kurbo::Affine)> = Vec::new();
 
@@ -170,7 +423,7 @@ fn decomposed_components(layer: &Layer, font: &Font) -> Vec<Path> {
                 Some(g) => g,
                 None => continue,
             };
-            let new_outline = match referenced_glyph.get_layer(layer.id.as_ref().unwrap()) {
+            let new_outline = match referenced_glyph.get_layer(layer_id) {
                 Some(g) => g,
                 None => continue,
             };
@@ -200,14 +453,20 @@ fn decomposed_components(layer: &Layer, font: &Font) -> Vec<Path> {
     contours
 }
 
-fn decompose_mixed_glyphs(input: &mut babelfont::Font) {
+fn decompose_mixed_glyphs(input: &mut babelfont::Font, diagnostics: &Diagnostics) {
     for master in &input.masters {
         let mut decomposed: BTreeMap<String, Vec<babelfont::Path>> = BTreeMap::new();
+        let mut problems = vec![];
         for glif in input.glyphs.iter() {
             if let Some(layer) = glif.get_layer(&master.id) {
-                decomposed.insert(glif.name.to_string(), decomposed_components(layer, input));
+                if layer.has_components() && layer.has_paths() {
+                    let contours =
+                        decomposed_components(&glif.name, master, layer, input, &mut problems);
+                    decomposed.insert(glif.name.to_string(), contours);
+                }
             }
         }
+        diagnostics.extend(problems);
         for glif in input.glyphs.iter_mut() {
             let name = glif.name.to_string();
             if let Some(layer) = glif.get_layer_mut(&master.id) {
@@ -224,4 +483,5 @@ fn decompose_mixed_glyphs(input: &mut babelfont::Font) {
             }
         }
     }
+    input.glyphs.reindex();
 }
@@ -0,0 +1,384 @@
+use crate::glyph::{CU2QU_TOLERANCE, REVERSE_DIRECTION};
+use babelfont::{Layer, Shape};
+use fonttools::tables::glyf;
+use fonttools::tables::gvar::GlyphVariationData;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/*
+    Converting glyphs is most of the work of a build, and most glyphs don't
+    change from one build to the next. So we can keep the glyf and gvar
+    entries for each glyph in a cache directory, and reuse them next time.
+
+    Each entry lives in a file named after a SHA-256 hash of everything
+    which went into making it: the glyph's layers in every master (after
+    mixed glyphs have been decomposed), the glyph IDs its components
+    resolve to, the axes and master locations, which master is the default,
+    whether we are building a variable font, the curve conversion settings,
+    the cache format version and the fonticulus version. If any of those
+    change, the hash changes and the glyph is rebuilt; stale entries are
+    just left behind, so delete the directory to tidy up.
+
+    A cache which can't be read or written is never fatal; we just build
+    the glyph again.
+*/
+
+// Bump this when the serialized layout of a CachedGlyph changes, or when
+// the way fonttools converts glyphs changes, so that old entries are not
+// reused. (Releases change the fonticulus version anyway, but builds between
+// releases don't.)
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+// The glyf table entry and gvar variation data for one glyph
+pub type CachedGlyph = (glyf::Glyph, Option<GlyphVariationData>);
+
+pub struct GlyphCache {
+    dir: PathBuf,
+    // Hash of everything which is the same for every glyph in this build
+    font_hasher: Sha256,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl GlyphCache {
+    pub fn new(
+        dir: &Path,
+        input: &babelfont::Font,
+        default_master_ix: usize,
+        variable: bool,
+    ) -> std::io::Result<GlyphCache> {
+        std::fs::create_dir_all(dir)?;
+        let mut hasher = Sha256::new();
+        hash_str(&mut hasher, env!("CARGO_PKG_VERSION"));
+        hasher.update(CACHE_FORMAT_VERSION.to_le_bytes());
+        hasher.update(CU2QU_TOLERANCE.to_le_bytes());
+        hasher.update([REVERSE_DIRECTION as u8]);
+        hasher.update([variable as u8]);
+        hasher.update((default_master_ix as u32).to_le_bytes());
+        hash_len(&mut hasher, input.axes.len());
+        for axis in &input.axes {
+            hash_str(&mut hasher, &axis.tag);
+            for value in [axis.min, axis.default, axis.max] {
+                hash_f32(&mut hasher, value.unwrap_or(f32::NAN));
+            }
+            hash_len(&mut hasher, axis.map.as_ref().map_or(0, Vec::len));
+            for (user, design) in axis.map.iter().flatten() {
+                hash_f32(&mut hasher, *user);
+                hash_f32(&mut hasher, *design);
+            }
+        }
+        hash_len(&mut hasher, input.masters.len());
+        for master in &input.masters {
+            hash_str(&mut hasher, &master.id);
+            // The location is a HashMap, so put it in a predictable order
+            let location: BTreeMap<&String, &f32> = master.location.0.iter().collect();
+            hash_len(&mut hasher, location.len());
+            for (axis, value) in location {
+                hash_str(&mut hasher, axis);
+                hash_f32(&mut hasher, *value);
+            }
+        }
+        Ok(GlyphCache {
+            dir: dir.to_path_buf(),
+            font_hasher: hasher,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    // Works out the cache key for a glyph, given its layers (in master
+    // order) and the mapping used to resolve its components
+    pub fn key(
+        &self,
+        glif_name: &str,
+        layers: &[Option<&Layer>],
+        mapping: &BTreeMap<String, u16>,
+    ) -> String {
+        let mut hasher = self.font_hasher.clone();
+        hash_str(&mut hasher, glif_name);
+        hash_len(&mut hasher, layers.len());
+        for layer in layers {
+            let layer = match layer {
+                Some(layer) => layer,
+                None => {
+                    // A sparse master
+                    hasher.update([0]);
+                    continue;
+                }
+            };
+            hasher.update([1]);
+            hasher.update(layer.width.to_le_bytes());
+            hash_len(&mut hasher, layer.shapes.len());
+            for shape in &layer.shapes {
+                match shape {
+                    Shape::PathShape(path) => {
+                        hasher.update([b'P', path.closed as u8]);
+                        hash_len(&mut hasher, path.nodes.len());
+                        for node in &path.nodes {
+                            hash_f32(&mut hasher, node.x);
+                            hash_f32(&mut hasher, node.y);
+                            hash_str(&mut hasher, &format!("{:?}", node.nodetype));
+                        }
+                    }
+                    Shape::ComponentShape(component) => {
+                        hasher.update([b'C']);
+                        hash_str(&mut hasher, &component.reference);
+                        let glyph_id = mapping.get(&component.reference).map_or(-1, |&x| x as i32);
+                        hasher.update(glyph_id.to_le_bytes());
+                        for coefficient in component.transform.as_coeffs() {
+                            hasher.update(coefficient.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    // Entries are spread across subdirectories so that big fonts don't
+    // make for one enormous directory
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedGlyph> {
+        let entry = File::open(self.path_for(key))
+            .ok()
+            .and_then(|file| bincode::deserialize_from(BufReader::new(file)).ok());
+        if entry.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        entry
+    }
+
+    pub fn put(&self, key: &str, entry: &CachedGlyph) {
+        let path = self.path_for(key);
+        // Write to a temporary file and rename it into place, so that an
+        // interrupted build (or two builds at once) can't leave half an entry
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let result = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| File::create(&temp_path))
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                bincode::serialize_into(&mut writer, entry).map_err(std::io::Error::other)?;
+                writer.flush()
+            })
+            .and_then(|_| std::fs::rename(&temp_path, &path));
+        if let Err(e) = result {
+            log::warn!(
+                "Could not write glyph cache entry {}: {}",
+                path.display(),
+                e
+            );
+            let _ = std::fs::remove_file(&temp_path);
+        }
+    }
+
+    pub fn report(&self) {
+        log::info!(
+            "Glyph cache: {} glyphs reused, {} rebuilt",
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed)
+        );
+    }
+}
+
+fn hash_f32(hasher: &mut Sha256, value: f32) {
+    hasher.update(value.to_le_bytes());
+}
+
+// Strings and lists are preceded by their lengths, so that where one ends
+// and the next begins is part of the hash: ["ab", "c"] and ["a", "bc"] must
+// not look the same.
+fn hash_len(hasher: &mut Sha256, len: usize) {
+    hasher.update((len as u64).to_le_bytes());
+}
+
+fn hash_str(hasher: &mut Sha256, value: &str) {
+    hash_len(hasher, value.len());
+    hasher.update(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babelfont::{Axis, Component, Location, Master, Node, NodeType, Path};
+    use fonttools::tables::gvar::DeltaSet;
+    use std::collections::HashMap;
+
+    // A cache directory of its own for each test, removed when it is dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "fonticulus-cache-test-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn source(bold_weight: f32) -> babelfont::Font {
+        let mut font = babelfont::Font::new();
+        let mut axis = Axis::new("Weight", "wght".to_string());
+        axis.min = Some(400.0);
+        axis.default = Some(400.0);
+        axis.max = Some(700.0);
+        font.axes.push(axis);
+        for (id, wght) in [("Regular", 400.0), ("Bold", bold_weight)] {
+            let location = Location(HashMap::from([("wght".to_string(), wght)]));
+            font.masters.push(Master::new(id, id, location));
+        }
+        font
+    }
+
+    fn layer(x: f32, width: i32) -> Layer {
+        let mut layer = Layer::new(width);
+        layer.push_path(Path {
+            nodes: vec![
+                Node {
+                    x: 0.0,
+                    y: 0.0,
+                    nodetype: NodeType::Line,
+                },
+                Node {
+                    x,
+                    y: 0.0,
+                    nodetype: NodeType::Line,
+                },
+            ],
+            closed: true,
+            ..Default::default()
+        });
+        layer.push_component(Component {
+            reference: "b".to_string(),
+            transform: kurbo::Affine::IDENTITY,
+        });
+        layer
+    }
+
+    fn mapping(gid_of_b: u16) -> BTreeMap<String, u16> {
+        BTreeMap::from([("a".to_string(), 1), ("b".to_string(), gid_of_b)])
+    }
+
+    #[test]
+    fn test_key_changes() {
+        let dir = TestDir::new("key");
+        let cache = GlyphCache::new(&dir.0, &source(700.0), 0, true).unwrap();
+        let (regular, bold) = (layer(100.0, 500), layer(200.0, 600));
+        let key = cache.key("a", &[Some(&regular), Some(&bold)], &mapping(2));
+        assert_eq!(
+            key,
+            cache.key("a", &[Some(&regular), Some(&bold)], &mapping(2))
+        );
+
+        let moved = layer(150.0, 600);
+        assert_ne!(
+            key,
+            cache.key("a", &[Some(&regular), Some(&moved)], &mapping(2))
+        );
+        let wider = layer(200.0, 650);
+        assert_ne!(
+            key,
+            cache.key("a", &[Some(&regular), Some(&wider)], &mapping(2))
+        );
+        assert_ne!(
+            key,
+            cache.key("a", &[Some(&regular), Some(&bold)], &mapping(3))
+        );
+        assert_ne!(key, cache.key("a", &[Some(&regular), None], &mapping(2)));
+
+        let moved_master = GlyphCache::new(&dir.0, &source(900.0), 0, true).unwrap();
+        assert_ne!(
+            key,
+            moved_master.key("a", &[Some(&regular), Some(&bold)], &mapping(2))
+        );
+    }
+
+    #[test]
+    fn test_key_separates_strings() {
+        let dir = TestDir::new("separate");
+        let cache = GlyphCache::new(&dir.0, &source(700.0), 0, true).unwrap();
+        // Without lengths, the name "a" followed by a sparse master's 0 byte
+        // would hash the same as the name "a\0" with no layers at all
+        let mapping = BTreeMap::new();
+        assert_ne!(
+            cache.key("a", &[None], &mapping),
+            cache.key("a\0", &[], &mapping)
+        );
+    }
+
+    #[test]
+    fn test_put_get() {
+        let dir = TestDir::new("put-get");
+        let cache = GlyphCache::new(&dir.0, &source(700.0), 0, true).unwrap();
+        let glyph = glyf::Glyph {
+            xMin: 0,
+            xMax: 100,
+            yMin: 0,
+            yMax: 0,
+            contours: vec![vec![
+                glyf::Point {
+                    x: 0,
+                    y: 0,
+                    on_curve: true,
+                },
+                glyf::Point {
+                    x: 100,
+                    y: 0,
+                    on_curve: true,
+                },
+            ]],
+            instructions: vec![],
+            components: vec![],
+            overlap: false,
+        };
+        let variations = GlyphVariationData {
+            deltasets: vec![DeltaSet {
+                peak: vec![1.0],
+                start: vec![0.0],
+                end: vec![1.0],
+                deltas: vec![(0, 0), (100, 0), (0, 0), (100, 0), (0, 0), (0, 0)],
+            }],
+        };
+        let entry: CachedGlyph = (glyph, Some(variations));
+
+        let key = cache.key("a", &[Some(&layer(100.0, 500))], &mapping(2));
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, &entry);
+        assert_eq!(cache.get(&key), Some(entry));
+        assert_eq!(cache.hits.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_a_miss() {
+        let dir = TestDir::new("corrupt");
+        let cache = GlyphCache::new(&dir.0, &source(700.0), 0, true).unwrap();
+        let key = cache.key("a", &[Some(&layer(100.0, 500))], &mapping(2));
+        let path = cache.path_for(&key);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"not a glyph").unwrap();
+        assert_eq!(cache.get(&key), None);
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::buildbasic::decomposed_components;
+use crate::diagnostics::{BuildError, Problem};
+use crate::fontinfo::*;
+use fonttools::cff::charstring::{
+    blended_charstring_from_operations, charstring_from_operations, path_operations, PathOperation,
+};
+use fonttools::cff::dict::{self, Dict, DictOperand};
+use fonttools::cff::subroutinizer::subroutinize;
+use fonttools::cff::FontDict;
+use fonttools::otvar::{
+    ItemVariationData, ItemVariationStore, RegionAxisCoordinates, VariationModel,
+};
+use fonttools::tables::hmtx;
+use fonttools::tables::CFF::CFF;
+use fonttools::tables::CFF2::CFF2;
+use kurbo::{BezPath, Rect, Shape};
+use std::collections::{BTreeMap, HashMap};
+
+// In --otf mode, we keep the cubic outlines of each layer and turn them
+// into charstrings. For a static font this is straightforward; for a variable
+// font, the outlines of each master are turned into lists of path operations,
+// and the variation model gives us a set of deltas per region for each
+// argument, which we write using `blend` operators in a CFF2 table.
+
+/// The outlines of a glyph in each master, as charstring path operations
+pub struct GlyphOutlines {
+    /// Path operations for each master (`None` for sparse masters)
+    pub masters: Vec<Option<Vec<PathOperation>>>,
+    /// Advance widths for each master
+    pub widths: Vec<Option<i32>>,
+    /// The bounds of the glyph in the default master
+    pub bounds: Rect,
+}
+
+// Badly constructed paths have already been reported by buildbasic, so
+// they are left out here
+pub(crate) fn layer_to_kurbo(
+    glif_name: &str,
+    master: &babelfont::Master,
+    layer: &babelfont::Layer,
+    font: &babelfont::Font,
+    problems: &mut Vec<Problem>,
+) -> Vec<BezPath> {
+    layer
+        .paths()
+        .cloned()
+        .chain(decomposed_components(
+            glif_name, master, layer, font, problems,
+        ))
+        .filter_map(|x| x.to_kurbo().ok())
+        .collect()
+}
+
+pub fn layers_to_outlines(
+    font: &babelfont::Font,
+    // The master of each layer
+    masters: &[&babelfont::Master],
+    layers: &[Option<&babelfont::Layer>],
+    default_master: usize,
+    glif_name: &str,
+    problems: &mut Vec<Problem>,
+) -> GlyphOutlines {
+    let paths: Vec<Option<Vec<BezPath>>> = layers
+        .iter()
+        .zip(masters.iter())
+        .map(|(layer, master)| layer.map(|l| layer_to_kurbo(glif_name, master, l, font, problems)))
+        .collect();
+    let bounds = paths[default_master]
+        .iter()
+        .flatten()
+        .map(|p| p.bounding_box())
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO)
+        .round();
+    let mut masters: Vec<Option<Vec<PathOperation>>> = paths
+        .iter()
+        .map(|p| p.as_ref().map(|p| path_operations(p)))
+        .collect();
+
+    // CFF2 blends require every master to have the same operations
+    let default_ops: Vec<(u16, usize)> = masters[default_master]
+        .iter()
+        .flatten()
+        .map(|(op, args)| (*op, args.len()))
+        .collect();
+    let compatible = masters.iter().flatten().all(|ops| {
+        ops.len() == default_ops.len()
+            && ops
+                .iter()
+                .zip(default_ops.iter())
+                .all(|((op, args), (d_op, d_len))| op == d_op && args.len() == *d_len)
+    });
+    if !compatible {
+        problems
+            .push(Problem::new("masters are incompatible; it will not vary").in_glyph(glif_name));
+        for (ix, master) in masters.iter_mut().enumerate() {
+            if ix != default_master {
+                *master = None;
+            }
+        }
+    }
+    GlyphOutlines {
+        masters,
+        widths: layers.iter().map(|l| l.map(|l| l.width)).collect(),
+        bounds,
+    }
+}
+
+fn font_bbox(outlines: &[GlyphOutlines]) -> Vec<DictOperand> {
+    let bbox = outlines
+        .iter()
+        .map(|o| o.bounds)
+        .filter(|b| b.area() != 0.0)
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO);
+    [bbox.x0, bbox.y0, bbox.x1, bbox.y1]
+        .iter()
+        .map(|&x| (x as i32).into())
+        .collect()
+}
+
+/// The most common advance width, which can be omitted from charstrings
+fn default_width(metrics: &[hmtx::Metric]) -> i32 {
+    let mut counts: HashMap<u16, usize> = HashMap::new();
+    for m in metrics {
+        *counts.entry(m.advanceWidth).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(width, count)| (count, std::cmp::Reverse(width)))
+        .map(|(width, _)| width as i32)
+        .unwrap_or(0)
+}
+
+pub fn build_cff(
+    input: &babelfont::Font,
+    outlines: &[GlyphOutlines],
+    metrics: &[hmtx::Metric],
+    glyph_names: &[String],
+) -> CFF {
+    let default_width_x = default_width(metrics);
+    let charstrings: Vec<_> = outlines
+        .iter()
+        .zip(metrics.iter())
+        .map(|(outline, metric)| {
+            let ops = outline.masters.iter().flatten().next().cloned();
+            let width = metric.advanceWidth as i32;
+            charstring_from_operations(
+                &ops.unwrap_or_default(),
+                (width != default_width_x).then_some(width),
+            )
+        })
+        .collect();
+    let subroutinized = subroutinize(&charstrings, &[], 1, false);
+
+    let mut cff = CFF {
+        name: postscript_font_name(input),
+        glyph_names: glyph_names.to_vec(),
+        charstrings: subroutinized.charstrings,
+        global_subrs: subroutinized.global_subrs,
+        ..Default::default()
+    };
+    cff.set_top_dict_string(
+        dict::VERSION,
+        &format!("{}.{:03}", input.version.0, input.version.1),
+    );
+    if let Some(copyright) = input.names.copyright.default() {
+        cff.set_top_dict_string(dict::NOTICE, &copyright);
+    }
+    cff.set_top_dict_string(
+        dict::FULL_NAME,
+        &format!(
+            "{} {}",
+            preferred_family_name(input),
+            preferred_subfamily_name(input)
+        ),
+    );
+    cff.set_top_dict_string(dict::FAMILY_NAME, &preferred_family_name(input));
+    let italic_angle = input.default_metric("italic angle").unwrap_or(0);
+    if italic_angle != 0 {
+        cff.top_dict
+            .set(dict::ITALIC_ANGLE, vec![italic_angle.into()]);
+    }
+    cff.top_dict.set(
+        dict::UNDERLINE_THICKNESS,
+        vec![(postscript_underline_thickness(input) as i32).into()],
+    );
+    if input.upm != 1000 {
+        let scale = 1.0 / input.upm as f64;
+        cff.top_dict.set(
+            dict::FONT_MATRIX,
+            vec![
+                scale.into(),
+                0.into(),
+                0.into(),
+                scale.into(),
+                0.into(),
+                0.into(),
+            ],
+        );
+    }
+    cff.top_dict.set(dict::FONT_BBOX, font_bbox(outlines));
+
+    let mut private_dict = Dict::default();
+    if default_width_x != 0 {
+        private_dict.set(dict::DEFAULT_WIDTH_X, vec![default_width_x.into()]);
+    }
+    cff.font_dicts.push(FontDict {
+        font_dict: Dict::default(),
+        private_dict,
+        subrs: subroutinized
+            .local_subrs
+            .into_iter()
+            .next()
+            .unwrap_or_default(),
+    });
+    cff
+}
+
+/// Collects the regions of the variation model into a variation store region list
+struct RegionList<'a> {
+    model: &'a VariationModel,
+    regions: Vec<Vec<RegionAxisCoordinates>>,
+}
+
+impl<'a> RegionList<'a> {
+    fn index_of(&mut self, support: &fonttools::otvar::Support) -> u16 {
+        let region: Vec<RegionAxisCoordinates> = self
+            .model
+            .axis_order
+            .iter()
+            .map(|axis| {
+                let (start, peak, end) = support.get(axis).copied().unwrap_or((0.0, 0.0, 0.0));
+                RegionAxisCoordinates {
+                    startCoord: start,
+                    peakCoord: peak,
+                    endCoord: end,
+                }
+            })
+            .collect();
+        match self.regions.iter().position(|r| *r == region) {
+            Some(ix) => ix as u16,
+            None => {
+                self.regions.push(region);
+                (self.regions.len() - 1) as u16
+            }
+        }
+    }
+}
+
+/// Builds a CFF2 table, plus the HVAR table data for the advance widths
+pub fn build_cff2(
+    outlines: &[GlyphOutlines],
+    default_master: usize,
+    model: &VariationModel,
+) -> Result<(CFF2, Option<Vec<u8>>), BuildError> {
+    let mut regions = RegionList {
+        model,
+        regions: vec![],
+    };
+    let mut region_sets: Vec<Vec<u16>> = vec![];
+    let mut width_deltas: Vec<BTreeMap<u16, i16>> = vec![];
+    let mut charstrings = vec![];
+
+    for outline in outlines {
+        let default_ops = outline.masters[default_master].clone().unwrap_or_default();
+        // All the arguments of each master, with the advance width at the end
+        let values: Vec<Option<ndarray::Array1<f32>>> = outline
+            .masters
+            .iter()
+            .zip(outline.widths.iter())
+            .map(|(ops, width)| {
+                let width = (*width)?;
+                // Incompatible masters vary only in their advance width
+                let mut args: Vec<f32> = ops
+                    .as_ref()
+                    .unwrap_or(&default_ops)
+                    .iter()
+                    .flat_map(|(_, a)| a.iter().map(|&x| x as f32))
+                    .collect();
+                args.push(width as f32);
+                Some(ndarray::Array1::from(args))
+            })
+            .collect();
+        let arg_count = default_ops.iter().map(|(_, a)| a.len()).sum::<usize>();
+
+        let mut glyph_regions: BTreeMap<u16, Vec<i32>> = BTreeMap::new();
+        let mut glyph_width_deltas = BTreeMap::new();
+        for (delta, support) in model.get_deltas_and_supports(&values) {
+            if support.is_empty() || delta.len() != arg_count + 1 {
+                continue;
+            }
+            let region = regions.index_of(&support);
+            let rounded: Vec<i32> = delta.iter().map(|x| x.round() as i32).collect();
+            if rounded[arg_count] != 0 {
+                glyph_width_deltas.insert(region, rounded[arg_count] as i16);
+            }
+            if rounded[..arg_count].iter().any(|&x| x != 0) {
+                glyph_regions.insert(region, rounded[..arg_count].to_vec());
+            }
+        }
+        width_deltas.push(glyph_width_deltas);
+
+        let vsindex = if glyph_regions.is_empty() {
+            None
+        } else {
+            let set: Vec<u16> = glyph_regions.keys().copied().collect();
+            let ix = match region_sets.iter().position(|s| *s == set) {
+                Some(ix) => ix,
+                None => {
+                    region_sets.push(set);
+                    region_sets.len() - 1
+                }
+            };
+            Some(ix as u16)
+        };
+        let region_deltas: Vec<Vec<i32>> = glyph_regions.into_values().collect();
+        charstrings.push(blended_charstring_from_operations(
+            &default_ops,
+            &region_deltas,
+            vsindex.filter(|&ix| ix != 0),
+        ));
+    }
+
+    let subroutinized = subroutinize(&charstrings, &[], 1, true);
+    let axis_count = model.axis_order.len() as u16;
+    let variation_store = if region_sets.is_empty() {
+        None
+    } else {
+        Some(ItemVariationStore {
+            format: 1,
+            axisCount: axis_count,
+            variationRegions: regions.regions.clone(),
+            variationData: region_sets
+                .iter()
+                .map(|set| ItemVariationData {
+                    region_indexes: set.clone(),
+                    delta_values: vec![],
+                })
+                .collect(),
+        })
+    };
+    let cff2 = CFF2 {
+        top_dict: Dict::default(),
+        charstrings: subroutinized.charstrings,
+        global_subrs: subroutinized.global_subrs,
+        font_dicts: vec![FontDict {
+            font_dict: Dict::default(),
+            private_dict: Dict::default(),
+            subrs: subroutinized
+                .local_subrs
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+        }],
+        fd_select: vec![],
+        variation_store,
+    };
+
+    let hvar = if width_deltas.iter().all(|d| d.is_empty()) {
+        None
+    } else {
+        Some(build_hvar(&regions.regions, axis_count, &width_deltas)?)
+    };
+    Ok((cff2, hvar))
+}
+
+// We don't have an HVAR table implementation, so we write the simplest
+// possible one: no mappings, so glyph IDs index directly into a single
+// ItemVariationData covering all the regions.
+fn build_hvar(
+    regions: &[Vec<RegionAxisCoordinates>],
+    axis_count: u16,
+    width_deltas: &[BTreeMap<u16, i16>],
+) -> Result<Vec<u8>, BuildError> {
+    let store = ItemVariationStore {
+        format: 1,
+        axisCount: axis_count,
+        variationRegions: regions.to_vec(),
+        variationData: vec![ItemVariationData {
+            region_indexes: (0..regions.len() as u16).collect(),
+            delta_values: width_deltas
+                .iter()
+                .map(|deltas| {
+                    (0..regions.len() as u16)
+                        .map(|r| deltas.get(&r).copied().unwrap_or(0))
+                        .collect()
+                })
+                .collect(),
+        }],
+    };
+    let mut data: Vec<u8> = vec![0, 1, 0, 0];
+    data.extend(20_u32.to_be_bytes()); // itemVariationStoreOffset
+    data.extend([0; 12]); // no advance, LSB or RSB mappings
+    let store = otspec::ser::to_bytes(&store)
+        .map_err(|e| BuildError::Font(format!("Couldn't serialize HVAR: {}", e)))?;
+    data.extend(store);
+    Ok(data)
+}
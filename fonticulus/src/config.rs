@@ -0,0 +1,350 @@
+use crate::diagnostics::{BuildError, Diagnostics};
+use crate::instances::build_instance_fonts;
+use crate::{
+    build_master_fonts, build_variable_font, family_name, load_with_babelfont, save_font,
+    BuildOptions,
+};
+use fonttools::font::Font;
+use fonttools::tables::STAT::{AxisRecord, STAT};
+use fonttools::vmetrics::VerticalMetricsPolicy;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/*
+    A build config file describes a whole build, so that it can be repeated
+    (in CI, say) without a shell script around fonticulus. It is a TOML file
+    like this, where everything except `sources` is optional:
+
+        sources = ["Test.designspace", "Test-Italic.designspace"]
+        output_dir = "fonts"
+        formats = ["ttf", "otf", "woff2"]
+        variable = true
+        static = true
+        static_from_masters = false
+        variable_filename = "{family}[{axes}].{ext}"
+        static_filename = "{family}-{style}.{ext}"
+        features = ["kern", "gdef"]
+        autohint = true
+        vertical_metrics = "googlefonts"
+        axis_order = ["wght", "wdth"]
+        stat_axis_order = ["wdth", "wght"]
+        post_process = [["ttf-fix-non-hinted", "{path}", "{path}"]]
+        cache_dir = ".fonticulus-cache"
+
+    The feature writers are `kern`, which writes a kern feature from the
+    source's kerning, and `gdef`, which writes GDEF glyph classes from the
+    glyph categories. WOFF2 fonts have TrueType outlines.
+
+    Static fonts are built at the source's named instances, and named after
+    their style names; with `static_from_masters`, one is built from each
+    master instead, named after the master.
+
+    Paths are relative to the config file. Each post-processing step is a
+    command and its arguments (not run through a shell), in which `{path}`
+    is replaced by the path of the font which was just written.
+*/
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BuildConfig {
+    sources: Vec<PathBuf>,
+    #[serde(default = "default_output_dir")]
+    output_dir: PathBuf,
+    #[serde(default = "default_formats")]
+    formats: Vec<OutputFormat>,
+    // Build a variable font from all the masters
+    #[serde(default = "default_true")]
+    variable: bool,
+    // Build a static font at each named instance
+    #[serde(default, rename = "static")]
+    static_fonts: bool,
+    // Build the static fonts from the masters instead of the instances
+    #[serde(default)]
+    static_from_masters: bool,
+    #[serde(default = "default_variable_filename")]
+    variable_filename: String,
+    #[serde(default = "default_static_filename")]
+    static_filename: String,
+    #[serde(default = "default_features")]
+    features: Vec<FeatureWriter>,
+    #[serde(default)]
+    autohint: bool,
+    vertical_metrics: Option<String>,
+    subset: Option<HashSet<String>>,
+    // Reorders the axes of the sources (and so of fvar and gvar)
+    axis_order: Option<Vec<String>>,
+    // Writes a STAT table listing the design axes in this order
+    stat_axis_order: Option<Vec<String>>,
+    #[serde(default)]
+    post_process: Vec<Vec<String>>,
+    // Keeps converted glyphs between builds
+    cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    Ttf,
+    Otf,
+    Woff2,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Ttf => "ttf",
+            OutputFormat::Otf => "otf",
+            OutputFormat::Woff2 => "woff2",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum FeatureWriter {
+    Kern,
+    Gdef,
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+fn default_formats() -> Vec<OutputFormat> {
+    vec![OutputFormat::Ttf]
+}
+fn default_true() -> bool {
+    true
+}
+fn default_variable_filename() -> String {
+    "{family}[{axes}].{ext}".to_string()
+}
+fn default_static_filename() -> String {
+    "{family}-{style}.{ext}".to_string()
+}
+fn default_features() -> Vec<FeatureWriter> {
+    vec![FeatureWriter::Kern]
+}
+
+fn load_config(config_file: &Path) -> Result<BuildConfig, BuildError> {
+    let contents = std::fs::read_to_string(config_file).map_err(|e| {
+        BuildError::Options(format!("Could not read {}: {}", config_file.display(), e))
+    })?;
+    toml::from_str(&contents).map_err(|e| {
+        BuildError::Options(format!(
+            "Could not parse build config {}: {}",
+            config_file.display(),
+            e
+        ))
+    })
+}
+
+// Each source is built in turn; problems found in one source don't stop
+// the others being built, but the build fails at the end.
+pub fn build_from_config(config_file: &Path) -> Result<(), BuildError> {
+    let config = load_config(config_file)?;
+    let base_dir = config_file.parent().unwrap_or_else(|| Path::new("."));
+
+    if !config.variable && !config.static_fonts {
+        log::warn!("Neither variable nor static fonts were requested; nothing to build");
+    }
+    let vertical_metrics: Option<VerticalMetricsPolicy> = config
+        .vertical_metrics
+        .as_ref()
+        .map(|policy| policy.parse().map_err(BuildError::Options))
+        .transpose()?;
+
+    let output_dir = base_dir.join(&config.output_dir);
+    std::fs::create_dir_all(&output_dir).map_err(|e| {
+        BuildError::Options(format!("Could not create {}: {}", output_dir.display(), e))
+    })?;
+
+    let cache_dir = config.cache_dir.as_ref().map(|dir| base_dir.join(dir));
+
+    let mut problems = 0;
+    for source in &config.sources {
+        let source = base_dir.join(source);
+        let diagnostics = Diagnostics::new(&source);
+        let paths = BuildPaths {
+            source: &source,
+            output_dir: &output_dir,
+            cache_dir: cache_dir.as_deref(),
+        };
+        build_source(&config, &paths, vertical_metrics, &diagnostics)
+            .map_err(|e| BuildError::InSource(source.clone(), Box::new(e)))?;
+        problems += diagnostics.summarize();
+    }
+    match problems {
+        0 => Ok(()),
+        count => Err(BuildError::Problems(count)),
+    }
+}
+
+// Where a source's build comes from and goes to, relative to the current
+// directory rather than the config file
+struct BuildPaths<'a> {
+    source: &'a Path,
+    output_dir: &'a Path,
+    cache_dir: Option<&'a Path>,
+}
+
+fn build_source(
+    config: &BuildConfig,
+    paths: &BuildPaths,
+    vertical_metrics: Option<VerticalMetricsPolicy>,
+    diagnostics: &Diagnostics,
+) -> Result<(), BuildError> {
+    log::info!("Building {}", paths.source.display());
+    let mut in_font = load_with_babelfont(paths.source)?;
+    if let Some(order) = &config.axis_order {
+        reorder_axes(&mut in_font, order)?;
+    }
+    let family = family_name(&in_font);
+    let axes: Vec<String> = in_font.axes.iter().map(|axis| axis.tag.clone()).collect();
+
+    for &format in &config.formats {
+        let otf = format == OutputFormat::Otf;
+        let extension = format.extension();
+        let options = BuildOptions {
+            subset: config.subset.clone(),
+            otf,
+            // Autohinting only applies to TrueType outlines
+            autohint: config.autohint && !otf,
+            vertical_metrics,
+            kern: config.features.contains(&FeatureWriter::Kern),
+            gdef: config.features.contains(&FeatureWriter::Gdef),
+            cache_dir: paths.cache_dir.map(Path::to_path_buf),
+        };
+        let filename = |template: &str, style: &str| {
+            template
+                .replace("{family}", &family)
+                .replace("{style}", style)
+                .replace("{axes}", &axes.join(","))
+                .replace("{ext}", extension)
+        };
+
+        if config.variable {
+            let mut out_font = build_variable_font(&mut in_font, &options, diagnostics)?;
+            if let Some(order) = &config.stat_axis_order {
+                add_stat_table(&mut out_font, order)?;
+            }
+            let path = paths
+                .output_dir
+                .join(filename(&config.variable_filename, ""));
+            save_and_post_process(out_font, &path, format, &config.post_process)?;
+        }
+        if config.static_fonts {
+            let out_fonts = if config.static_from_masters {
+                build_master_fonts(&mut in_font, &options, diagnostics)?
+            } else {
+                build_instance_fonts(&mut in_font, &options, diagnostics)?
+            };
+            for (style, out_font) in out_fonts {
+                let path = paths
+                    .output_dir
+                    .join(filename(&config.static_filename, &style));
+                save_and_post_process(out_font, &path, format, &config.post_process)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Axes which aren't mentioned keep their relative order, after those which are
+fn reorder_axes(in_font: &mut babelfont::Font, order: &[String]) -> Result<(), BuildError> {
+    for tag in order {
+        if !in_font.axes.iter().any(|axis| &axis.tag == tag) {
+            return Err(BuildError::Options(format!(
+                "Axis {} in axis_order is not in the source",
+                tag
+            )));
+        }
+    }
+    in_font.axes.sort_by_key(|axis| {
+        order
+            .iter()
+            .position(|tag| tag == &axis.tag)
+            .unwrap_or(order.len())
+    });
+    Ok(())
+}
+
+// The design axes are listed in fvar order, each with its position in the
+// requested ordering; axes which aren't mentioned are ordered after those
+// which are. There are no axis value tables yet.
+fn add_stat_table(font: &mut Font, order: &[String]) -> Result<(), BuildError> {
+    let fvar = match font
+        .tables
+        .fvar()
+        .map_err(|e| BuildError::Font(format!("Couldn't read fvar table: {}", e)))?
+    {
+        Some(fvar) => fvar,
+        None => {
+            log::warn!("stat_axis_order only applies to variable fonts");
+            return Ok(());
+        }
+    };
+    let mut unlisted = order.len();
+    let design_axes = fvar
+        .axes
+        .iter()
+        .map(|axis| {
+            let ordering = order
+                .iter()
+                .position(|tag| axis.axisTag == tag.as_str())
+                .unwrap_or_else(|| {
+                    unlisted += 1;
+                    unlisted - 1
+                });
+            AxisRecord {
+                axisTag: axis.axisTag,
+                axisNameID: axis.axisNameID,
+                axisOrdering: ordering as u16,
+            }
+        })
+        .collect();
+    font.tables.insert(STAT {
+        elided_fallback_name_id: Some(2),
+        design_axes,
+        axis_values: vec![],
+    });
+    Ok(())
+}
+
+fn save_and_post_process(
+    mut font: Font,
+    path: &Path,
+    format: OutputFormat,
+    steps: &[Vec<String>],
+) -> Result<(), BuildError> {
+    if format == OutputFormat::Woff2 {
+        font.save_woff2(path)
+            .map_err(|e| BuildError::Write(path.to_path_buf(), e.to_string()))?;
+    } else {
+        save_font(&mut font, path)?;
+    }
+    log::info!("Wrote {}", path.display());
+    for step in steps {
+        let args: Vec<String> = step
+            .iter()
+            .map(|arg| arg.replace("{path}", &path.to_string_lossy()))
+            .collect();
+        let (command, args) = match args.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+        let status = Command::new(command)
+            .args(args)
+            .status()
+            .map_err(|e| BuildError::PostProcess(format!("Could not run {}: {}", command, e)))?;
+        if !status.success() {
+            return Err(BuildError::PostProcess(format!(
+                "{} failed on {}",
+                command,
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
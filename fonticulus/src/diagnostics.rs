@@ -0,0 +1,171 @@
+use babelfont::BabelfontError;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/*
+    There are two kinds of things which can go wrong in a build. Some of
+    them mean we can't go on: the source can't be loaded, there's no default
+    master, a font can't be written. Those are `BuildError`s, and they are
+    passed back up to `main`.
+
+    Others only affect part of the font: a glyph with no layer in the
+    default master, a path which doesn't make sense, a component or kern
+    pair referring to a glyph which isn't there, masters which aren't
+    compatible. For those we do the best we can and carry on, noting a
+    `Problem` so that everything wrong with the source can be reported at
+    once. The fonts are still written, but the build fails at the end.
+*/
+
+#[derive(Debug)]
+pub enum BuildError {
+    /// The source isn't a .designspace, .ufo or .glyphs file
+    UnknownSourceType(PathBuf),
+    /// babelfont couldn't load the source, or make sense of its design space
+    Babelfont(BabelfontError),
+    /// None of the masters is at the default location of the design space
+    NoDefaultMaster,
+    /// Something went wrong with a font we built
+    Font(String),
+    /// A font couldn't be written
+    Write(PathBuf, String),
+    /// Something is wrong with the command line or build config
+    Options(String),
+    /// A post-processing step in the build config didn't work
+    PostProcess(String),
+    /// An error building a particular source
+    InSource(PathBuf, Box<BuildError>),
+    /// The build finished, but problems were reported along the way
+    Problems(usize),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::UnknownSourceType(path) => {
+                write!(f, "Unknown file type {}", path.display())
+            }
+            BuildError::Babelfont(e) => write!(f, "{}", e),
+            BuildError::NoDefaultMaster => {
+                write!(
+                    f,
+                    "No master is at the default location of the design space"
+                )
+            }
+            BuildError::Font(message) => write!(f, "{}", message),
+            BuildError::Write(path, message) => {
+                write!(f, "Could not write {}: {}", path.display(), message)
+            }
+            BuildError::Options(message) => write!(f, "{}", message),
+            BuildError::PostProcess(message) => write!(f, "{}", message),
+            BuildError::InSource(path, e) => write!(f, "{}: {}", path.display(), e),
+            BuildError::Problems(1) => write!(f, "1 problem was found during the build"),
+            BuildError::Problems(count) => {
+                write!(f, "{} problems were found during the build", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<BabelfontError> for BuildError {
+    fn from(e: BabelfontError) -> Self {
+        BuildError::Babelfont(e)
+    }
+}
+
+/// Something wrong with part of the source, which we could build around
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Problem {
+    pub glyph: Option<String>,
+    pub master: Option<String>,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new(message: impl Into<String>) -> Self {
+        Problem {
+            glyph: None,
+            master: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn in_glyph(mut self, glyph: &str) -> Self {
+        self.glyph = Some(glyph.to_string());
+        self
+    }
+
+    pub fn in_master(mut self, master: &str) -> Self {
+        self.master = Some(master.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.glyph, &self.master) {
+            (Some(glyph), Some(master)) => write!(f, "glyph {}, master {}: ", glyph, master)?,
+            (Some(glyph), None) => write!(f, "glyph {}: ", glyph)?,
+            (None, Some(master)) => write!(f, "master {}: ", master)?,
+            (None, None) => {}
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The problems found while building one source. Glyphs are built in
+/// parallel, so this can be shared between threads.
+pub struct Diagnostics {
+    source: PathBuf,
+    problems: Mutex<Vec<Problem>>,
+}
+
+impl Diagnostics {
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        Diagnostics {
+            source: source.into(),
+            problems: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn report(&self, problem: Problem) {
+        self.problems.lock().unwrap().push(problem);
+    }
+
+    pub fn extend(&self, problems: impl IntoIterator<Item = Problem>) {
+        self.problems.lock().unwrap().extend(problems);
+    }
+
+    // The same problem can be found by more than one build of the same
+    // source (say, TTF and OTF), so it's only listed once. Glyphs are built
+    // in parallel, so we sort them to get the same report every time.
+    fn unique_problems(&self) -> BTreeSet<Problem> {
+        self.problems.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Logs a summary of the problems found, and returns how many there were
+    pub fn summarize(&self) -> usize {
+        let problems = self.unique_problems();
+        if !problems.is_empty() {
+            log::error!("Problems found in {}:", self.source.display());
+            for problem in &problems {
+                log::error!("    {}", problem);
+            }
+        }
+        problems.len()
+    }
+
+    /// Logs the summary, and turns any problems into an error
+    pub fn finish(&self) -> Result<(), BuildError> {
+        match self.summarize() {
+            0 => Ok(()),
+            count => Err(BuildError::InSource(
+                self.source.clone(),
+                Box::new(BuildError::Problems(count)),
+            )),
+        }
+    }
+}
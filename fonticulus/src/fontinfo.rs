@@ -129,21 +129,57 @@ pub fn postscript_underline_thickness(input: &babelfont::Font) -> i16 {
         .map_or_else(|| upm * 0.05, f32::from) as i16
 }
 
-pub fn get_panose(_input: &babelfont::Font) -> os2::Panose {
-    // XXX
+pub fn get_panose(input: &babelfont::Font) -> os2::Panose {
+    let digits = if let Some(OTScalar::BitField(mut digits)) = input.ot_value("OS2", "panose", true)
+    {
+        digits.resize(10, 0);
+        digits
+    } else {
+        infer_panose(input)
+    };
     os2::Panose {
-        panose0: 0,
-        panose1: 0,
-        panose2: 0,
-        panose3: 0,
-        panose4: 0,
-        panose5: 0,
-        panose6: 0,
-        panose7: 0,
-        panose8: 0,
-        panose9: 0,
+        panose0: digits[0],
+        panose1: digits[1],
+        panose2: digits[2],
+        panose3: digits[3],
+        panose4: digits[4],
+        panose5: digits[5],
+        panose6: digits[6],
+        panose7: digits[7],
+        panose8: digits[8],
+        panose9: digits[9],
     }
 }
+
+// Without any Panose information in the source, we can at least claim a
+// Latin Text font and fill in the weight and proportion digits; everything
+// else is left as "Any".
+fn infer_panose(input: &babelfont::Font) -> Vec<u8> {
+    let weight_class = input
+        .ot_value("OS2", "usWeightClass", true)
+        .map_or(400, u16::from);
+    let width_class = input
+        .ot_value("OS2", "usWidthClass", true)
+        .map_or(5, u16::from);
+    let is_fixed_pitch = input
+        .ot_value("post", "isFixedPitch", true)
+        .is_some_and(bool::from);
+    // 100 is "Very Light" (2), 900 is "Black" (10), and anything heavier is "Extra Black"
+    let weight = ((weight_class + 50) / 100 + 1).clamp(2, 11) as u8;
+    let proportion = if is_fixed_pitch {
+        9 // Monospaced
+    } else {
+        match width_class {
+            1 | 2 => 8, // Very Condensed
+            3 | 4 => 6, // Condensed
+            6 | 7 => 5, // Extended
+            8 | 9 => 7, // Very Extended
+            _ => 0,     // Any
+        }
+    };
+    vec![2, 0, weight, proportion, 0, 0, 0, 0, 0, 0]
+}
+
 pub fn get_selection(input: &babelfont::Font) -> u16 {
     let mut selection =
         if let Some(OTScalar::BitField(s)) = input.ot_value("OS2", "fsSelection", true) {
@@ -0,0 +1,71 @@
+use babelfont::{Font, GlyphCategory};
+use fonttools::tables::GDEF::{GlyphClass, GDEF};
+use std::collections::BTreeMap;
+
+// Builds a GDEF table classifying each glyph by its category in the source.
+// Glyphs whose category is unknown are left out of the glyph classes.
+pub fn build_gdef(font: &Font, mapping: &BTreeMap<String, u16>) -> GDEF {
+    let glyph_class = font
+        .glyphs
+        .iter()
+        .filter_map(|glyph| {
+            let class = match glyph.category {
+                GlyphCategory::Base => GlyphClass::BaseGlyph,
+                GlyphCategory::Ligature => GlyphClass::LigatureGlyph,
+                GlyphCategory::Mark => GlyphClass::MarkGlyph,
+                GlyphCategory::Unknown => return None,
+            };
+            mapping.get(&glyph.name).map(|&gid| (gid, class))
+        })
+        .collect();
+    GDEF {
+        glyph_class,
+        attachment_point_list: BTreeMap::new(),
+        ligature_caret_list: BTreeMap::new(),
+        mark_attachment_class: BTreeMap::new(),
+        mark_glyph_sets: None,
+        item_variation_store: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babelfont::Glyph;
+
+    #[test]
+    fn test_glyph_classes() {
+        let mut font = Font::new();
+        for (name, category) in [
+            ("a", GlyphCategory::Base),
+            ("f_i", GlyphCategory::Ligature),
+            ("acutecomb", GlyphCategory::Mark),
+            ("unknown", GlyphCategory::Unknown),
+            ("unmapped", GlyphCategory::Mark),
+        ] {
+            font.glyphs.push(Glyph {
+                name: name.to_string(),
+                production_name: None,
+                category,
+                codepoints: vec![],
+                layers: vec![],
+                exported: true,
+                direction: None,
+            });
+        }
+        let mapping = ["a", "f_i", "acutecomb", "unknown"]
+            .iter()
+            .enumerate()
+            .map(|(gid, name)| (name.to_string(), gid as u16))
+            .collect();
+        let gdef = build_gdef(&font, &mapping);
+        assert_eq!(
+            gdef.glyph_class,
+            BTreeMap::from([
+                (0, GlyphClass::BaseGlyph),
+                (1, GlyphClass::LigatureGlyph),
+                (2, GlyphClass::MarkGlyph),
+            ])
+        );
+    }
+}
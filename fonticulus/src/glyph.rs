@@ -1,8 +1,10 @@
+use crate::diagnostics::Problem;
 use crate::utils::is_all_same;
 use fonttools::otvar::VariationModel;
 use fonttools::tables::glyf;
+use fonttools::tables::glyf::contourutils::kurbo_contours_to_compatible_glyf_contours;
 use fonttools::tables::gvar::{DeltaSet, GlyphVariationData};
-use kurbo::{cubics_to_quadratic_splines, BezPath, CubicBez, PathEl, PathSeg};
+use kurbo::BezPath;
 use std::collections::BTreeMap;
 use unzip_n::unzip_n;
 
@@ -10,6 +12,12 @@ unzip_n!(3);
 
 type GlyphContour = Vec<Vec<glyf::Point>>;
 
+// How far (in font units) the quadratic curves may stray from the cubics,
+// and whether contours are reversed on the way. The glyph cache's keys
+// depend on these, since they change the converted glyphs.
+pub const CU2QU_TOLERANCE: f64 = 1.0;
+pub const REVERSE_DIRECTION: bool = false;
+
 // OK, this is the trickiest portion of the project to follow.
 //
 // We are going to be converting a set of layers representing a single glyph
@@ -28,8 +36,10 @@ pub fn layers_to_glyph(
     // A variation model, which tells us where all the layers live in the
     // design space
     model: Option<&VariationModel>,
-    // and the glyph's name, for debugging purposes
+    // the glyph's name, for debugging purposes,
     glif_name: &str,
+    // and somewhere to note anything which stopped us converting it properly.
+    problems: &mut Vec<Problem>,
 ) -> (glyf::Glyph, Option<GlyphVariationData>) {
     let mut glyph = glyf::Glyph {
         xMin: 0,
@@ -42,10 +52,12 @@ pub fn layers_to_glyph(
         overlap: false,
     };
 
-    let default_layer = layers
-        .get(default_master)
-        .expect("Couldn't index the default master into the layer list?!")
-        .expect("No glif in default master!");
+    /* A glyph with no default layer has already been reported by
+    buildbasic; all we can do is leave it empty. */
+    let default_layer = match layers.get(default_master) {
+        Some(Some(layer)) => *layer,
+        _ => return (glyph, None),
+    };
 
     /* Dispatch empty glyphs (space, etc.) straight away */
     if !default_layer.has_components() && !default_layer.has_paths() {
@@ -67,13 +79,9 @@ pub fn layers_to_glyph(
 
     /* Handle the simple case of a static font. */
     if model.is_none() {
-        for (contour_ix, contour) in default_layer.paths().enumerate() {
-            let glyph_contour =
-                babelfont_contours_to_glyf_contours(contour_ix, vec![contour], 0, glif_name)
-                    .first()
-                    .unwrap()
-                    .clone();
-            glyph.contours.push(glyph_contour);
+        if let Some(contours) = layers_to_quadratic_contours(&[default_layer], glif_name, problems)
+        {
+            glyph.contours = contours.into_iter().next().unwrap();
         }
         return (glyph, None);
     }
@@ -88,42 +96,33 @@ pub fn layers_to_glyph(
     let mut contours: Vec<Option<GlyphContour>> = vec![];
 
     for o in layers {
-        widths.push(o.and_then(|x| Some(x.width)));
-        contours.push(o.and_then(|_| Some(vec![])));
+        widths.push(o.map(|x| x.width));
+        contours.push(None);
     }
 
-    // Convert each contour in turn (across layers)
-    for (index, _) in default_layer.paths().enumerate() {
-        for o in layers {
-            // If this contour doesn't exist in a given layer, we have a problem
-            if o.is_some() && index >= o.unwrap().paths().count() {
-                log::error!("Incompatible contour count in glyph {:}", glif_name);
+    // Convert the contours of all *non-sparse* layers together, so that
+    // the curves are split compatibly across masters.
+    let nonsparse_layers: Vec<&babelfont::Layer> = layers.iter().flatten().copied().collect();
+    let all_glyf_contours =
+        match layers_to_quadratic_contours(&nonsparse_layers, glif_name, problems) {
+            Some(c) => c,
+            None => {
+                // Fall back to a static glyph from the default master
+                if let Some(contours) =
+                    layers_to_quadratic_contours(&[default_layer], glif_name, problems)
+                {
+                    glyph.contours = contours.into_iter().next().unwrap();
+                }
                 return (glyph, None);
             }
-        }
+        };
 
-        // List of all contours across *non-sparse* layers.
-        let all_contours: Vec<&babelfont::Path> = layers
-            .iter()
-            .filter(|g| g.is_some())
-            .map(|x| x.unwrap().paths().skip(index).next().unwrap())
-            .collect();
-
-        // Convert them together into OT contours
-        let all_glyf_contours =
-            babelfont_contours_to_glyf_contours(index, all_contours, default_master, glif_name);
-
-        // Now we put them into their respective master
-        for (finished_contour, &master_id) in all_glyf_contours
-            .iter()
-            .zip(indexes_of_nonsparse_masters.iter())
-        {
-            assert!(contours[master_id].is_some());
-            contours[master_id]
-                .as_mut()
-                .unwrap()
-                .push(finished_contour.clone());
-        }
+    // Now we put them into their respective master
+    for (finished_contours, &master_id) in all_glyf_contours
+        .into_iter()
+        .zip(indexes_of_nonsparse_masters.iter())
+    {
+        contours[master_id] = Some(finished_contours);
     }
 
     // Now generate variations
@@ -132,19 +131,28 @@ pub fn layers_to_glyph(
         && !contours[default_master].as_ref().unwrap().is_empty()
     {
         if !glyph.components.is_empty() {
-            log::warn!("Can't create gvar deltas for mixed glyph {:}", glif_name);
+            problems.push(
+                Problem::new("can't create gvar deltas for a glyph with both paths and components")
+                    .in_glyph(glif_name),
+            );
             return (glyph, None);
         }
 
         // Gather all contour lengths, ensure they are the same.
-        // XXX should be caught in babelfont_contours_to_glyf_contours?
+        // (This should already have been caught in layers_to_quadratic_contours.)
         let lengths: Vec<usize> = contours
             .iter()
             .filter(|x| x.is_some())
             .map(|g| g.as_ref().unwrap().iter().flatten().count())
             .collect();
         if !is_all_same(&lengths) {
-            log::warn!("Incompatible glyph: {:}, lengths: {:?}", glif_name, lengths);
+            problems.push(
+                Problem::new(format!(
+                    "masters are incompatible (point counts {:?}); it will not vary",
+                    lengths
+                ))
+                .in_glyph(glif_name),
+            );
             glyph.contours = contours[default_master].as_ref().unwrap().clone();
             return (glyph, None);
         }
@@ -158,108 +166,41 @@ pub fn layers_to_glyph(
     (glyph, None)
 }
 
-fn babelfont_contours_to_glyf_contours(
-    // Which path this is in the glyph (for error reporting)
-    path_index: usize,
-
-    // A (non-sparse) list of contours
-    paths: Vec<&babelfont::Path>,
-
-    // The index of the default master (used as the reference for curve construction)
-    default_master: usize,
-
+fn layers_to_quadratic_contours(
+    // A (non-sparse) list of layers
+    layers: &[&babelfont::Layer],
     // Which glyph this is (for error reporting)
     glif_name: &str,
-) -> Vec<Vec<glyf::Point>> {
-    // Let's first get them all to kurbo elements.
-    let kurbo_paths: Vec<BezPath> = paths
+    problems: &mut Vec<Problem>,
+) -> Option<Vec<GlyphContour>> {
+    // Let's first get them all to kurbo elements. Badly constructed paths
+    // have already been reported by buildbasic, so we just leave them out.
+    let kurbo_paths: Vec<Vec<BezPath>> = layers
         .iter()
-        .map(|x| x.to_kurbo().expect("Bad contour construction"))
+        .map(|layer| layer.paths().filter_map(|x| x.to_kurbo().ok()).collect())
         .collect();
 
-    // Ensure they are all the same size
-    let lengths: Vec<usize> = kurbo_paths.iter().map(|x| x.elements().len()).collect();
-
-    if !is_all_same(&lengths) {
-        log::error!(
-            "Incompatible contour {:} in glyph {:}: {:?}",
-            path_index,
-            glif_name,
-            lengths
-        );
-        return vec![];
-    }
-
-    // XXX ensure they are all compatible, type-wise.
-
-    // We're going to turn the list of cubic bezpaths into Vec<Point> expected by Glyf
-    let mut quadratic_paths: Vec<Vec<glyf::Point>> = paths.iter().map(|_| vec![]).collect();
-
-    let default_elements: &[PathEl] = kurbo_paths[default_master].elements();
-    for (el_ix, el) in default_elements.iter().enumerate() {
-        match el {
-            PathEl::CurveTo(_, _, _) => {
-                // Convert all the cubics to quadratics in one go, across layers
-                let all_curves: Vec<CubicBez> = kurbo_paths
-                    .iter()
-                    .filter_map(|x| match x.get_seg(el_ix).unwrap() {
-                        PathSeg::Cubic(c) => Some(c),
-                        _ => None,
-                    })
-                    .collect();
-                if let Some(all_quadratics) = cubics_to_quadratic_splines(&all_curves, 1.0) {
-                    for (c_ix, contour) in quadratic_paths.iter_mut().enumerate() {
-                        let spline_points = all_quadratics[c_ix].points();
-                        // Skip the spline start, because we already have a point for that
-                        for pt in spline_points.iter().skip(1) {
-                            contour.push(glyf::Point {
-                                x: pt.x as i16,
-                                y: pt.y as i16,
-                                on_curve: false,
-                            });
-                        }
-                        // Last one is on-curve
-                        if let Some(last) = contour.last_mut() {
-                            last.on_curve = true
-                        }
-                    }
-                } else {
-                    log::warn!("Could not compatibly interpolate {:}", glif_name)
-                }
-            }
-            _ => {
-                for (c_ix, contour) in quadratic_paths.iter_mut().enumerate() {
-                    let this_path_el = kurbo_paths[c_ix].elements()[el_ix];
-                    match this_path_el {
-                        PathEl::MoveTo(pt) | PathEl::LineTo(pt) => contour.push(glyf::Point {
-                            x: pt.x as i16,
-                            y: pt.y as i16,
-                            on_curve: true,
-                        }),
-                        PathEl::QuadTo(pt1, pt2) => {
-                            // This can happen with components we already converted
-                            contour.push(glyf::Point {
-                                x: pt1.x as i16,
-                                y: pt1.y as i16,
-                                on_curve: false,
-                            });
-                            contour.push(glyf::Point {
-                                x: pt2.x as i16,
-                                y: pt2.y as i16,
-                                on_curve: true,
-                            });
-                        }
-                        PathEl::CurveTo(_, _, _) => panic!("Incompatible contour"),
-                        PathEl::ClosePath => {
-                            contour.pop();
-                        }
-                    }
-                }
-            }
+    match kurbo_contours_to_compatible_glyf_contours(
+        &kurbo_paths,
+        CU2QU_TOLERANCE,
+        REVERSE_DIRECTION,
+    ) {
+        Ok(result) => {
+            log::debug!(
+                "Converted {:} to quadratics with max error {:.3}",
+                glif_name,
+                result.max_error
+            );
+            Some(result.contours)
+        }
+        Err(e) => {
+            problems.push(
+                Problem::new(format!("could not convert to quadratic curves: {}", e))
+                    .in_glyph(glif_name),
+            );
+            None
         }
     }
-
-    quadratic_paths
 }
 
 fn babelfont_component_to_glyf_component(
@@ -274,7 +215,9 @@ fn babelfont_component_to_glyf_component(
             transformation: component.transform,
         })
     } else {
-        log::warn!("Couldn't find component for {:?}", component.reference);
+        // Components of glyphs missing from the source have been reported by
+        // buildbasic; this one was probably left out of a subset
+        log::debug!("Couldn't find component for {:?}", component.reference);
         None
     }
 }
@@ -0,0 +1,425 @@
+use crate::buildbasic::{build_font, master_name};
+use crate::diagnostics::{BuildError, Diagnostics, Problem};
+use crate::{autohint_font, vmetrics, BuildOptions};
+use babelfont::{Anchor, Component, Instance, Layer, Master, Shape};
+use fonttools::otvar::{support_scalar, Location as OTVarLocation, VariationModel};
+use ndarray::Array1;
+
+/*
+    A static font for a named instance is built by interpolating the masters
+    at the instance's location into a new master, which is added to the
+    source just long enough to build it like any other master. This way the
+    outlines, advance widths, kerning and metrics all come from the instance
+    location, for CFF as well as TrueType outlines.
+*/
+
+// No source uses this as a master or layer id
+const INSTANCE_MASTER_ID: &str = "fonticulus-instance";
+
+// Builds a static font at each of the source's named instances, returning
+// them with their style names
+pub fn build_instance_fonts(
+    in_font: &mut babelfont::Font,
+    options: &BuildOptions,
+    diagnostics: &Diagnostics,
+) -> Result<Vec<(String, fonttools::font::Font)>, BuildError> {
+    if in_font.instances.is_empty() {
+        log::warn!("The source has no named instances, so no static fonts were built");
+        return Ok(vec![]);
+    }
+    let instances = std::mem::take(&mut in_font.instances);
+    let built: Result<Vec<_>, _> = instances
+        .iter()
+        .enumerate()
+        .map(|(ix, instance)| {
+            let style = instance
+                .style_name
+                .default()
+                .or_else(|| instance.name.default())
+                .unwrap_or_else(|| format!("Instance{}", ix));
+            log::info!("Building {}", style);
+            build_instance(in_font, instance, &style, options, diagnostics)
+                .map(|font| (style, font))
+        })
+        .collect();
+    in_font.instances = instances;
+    let (style_names, mut out_fonts): (Vec<String>, Vec<fonttools::font::Font>) =
+        built?.into_iter().unzip();
+
+    if let Some(policy) = options.vertical_metrics {
+        vmetrics::apply_vertical_metrics(in_font, &mut out_fonts, policy)?;
+    }
+    if options.autohint {
+        for out_font in out_fonts.iter_mut() {
+            autohint_font(out_font, diagnostics);
+        }
+    }
+    Ok(style_names.into_iter().zip(out_fonts).collect())
+}
+
+// The source is put back as it was afterwards, whether or not the build
+// succeeded
+fn build_instance(
+    in_font: &mut babelfont::Font,
+    instance: &Instance,
+    style: &str,
+    options: &BuildOptions,
+    diagnostics: &Diagnostics,
+) -> Result<fonttools::font::Font, BuildError> {
+    let master = add_instance_master(in_font, instance, style, diagnostics)?;
+    in_font.masters.push(master);
+
+    // The style map names describe the source's own style, if anything, so
+    // they are worked out again from the instance's style name
+    let subfamily = std::mem::replace(&mut in_font.names.typographic_subfamily, style.into());
+    let style_map_family: Vec<_> = in_font.names.style_map_family_name.0.drain().collect();
+    let style_map_style = in_font.names.style_map_style_name.take();
+
+    let result = build_font(
+        in_font,
+        &options.subset,
+        Some(in_font.masters.len() - 1),
+        options.otf,
+        options.kern,
+        options.gdef,
+        options.cache_dir.as_deref(),
+        diagnostics,
+    );
+
+    in_font.names.typographic_subfamily = subfamily;
+    in_font
+        .names
+        .style_map_family_name
+        .0
+        .extend(style_map_family);
+    in_font.names.style_map_style_name = style_map_style;
+    in_font.masters.pop();
+    for glyph in in_font.glyphs.iter_mut() {
+        glyph
+            .layers
+            .retain(|layer| layer.id.as_deref() != Some(INSTANCE_MASTER_ID));
+    }
+    in_font.glyphs.reindex();
+    result
+}
+
+// Interpolates the masters at the instance's location, adding the new
+// master's layer to each glyph which has a layer in the default master
+fn add_instance_master(
+    in_font: &mut babelfont::Font,
+    instance: &Instance,
+    style: &str,
+    diagnostics: &Diagnostics,
+) -> Result<Master, BuildError> {
+    let default_ix = in_font
+        .default_master_index()
+        .ok_or(BuildError::NoDefaultMaster)?;
+    let interpolator = Interpolator {
+        model: in_font.variation_model()?,
+        default_ix,
+        location: in_font
+            .axes
+            .iter()
+            .map(|axis| axis.tag_as_tag())
+            .zip(in_font.normalize_location(&instance.location)?.0)
+            .collect(),
+    };
+    let masters = &in_font.masters;
+    let default_master = &masters[default_ix];
+
+    let mut master = Master::new(style, INSTANCE_MASTER_ID, instance.location.clone());
+    for (name, &value) in &default_master.metrics {
+        let values: Vec<Option<Array1<f32>>> = masters
+            .iter()
+            .map(|m| m.metrics.get(name).map(|&v| Array1::from(vec![v as f32])))
+            .collect();
+        let value = interpolator
+            .interpolate(&values)
+            .map_or(value, |v| v[0].round() as i32);
+        master.metrics.insert(name.clone(), value);
+    }
+
+    // A pair which is missing from a master isn't kerned there
+    let mut pairs: Vec<&(String, String)> = masters.iter().flat_map(|m| m.kerning.keys()).collect();
+    pairs.sort();
+    pairs.dedup();
+    let values: Vec<Option<Array1<f32>>> = masters
+        .iter()
+        .map(|m| {
+            Some(
+                pairs
+                    .iter()
+                    .map(|&pair| *m.kerning.get(pair).unwrap_or(&0) as f32)
+                    .collect(),
+            )
+        })
+        .collect();
+    if let Some(kerning) = interpolator.interpolate(&values) {
+        for (&pair, value) in pairs.iter().zip(kerning.iter()) {
+            let value = value.round() as i16;
+            if value != 0 {
+                master.kerning.insert(pair.clone(), value);
+            }
+        }
+    }
+    for value in &default_master.custom_ot_values {
+        master.set_ot_value(&value.table, &value.field, value.value.clone());
+    }
+
+    let mut problems = vec![];
+    let layers: Vec<Option<Layer>> = in_font
+        .glyphs
+        .iter()
+        .map(|glyph| {
+            let default_layer = in_font.master_layer_for(&glyph.name, default_master)?;
+            let values: Vec<Option<Array1<f32>>> = masters
+                .iter()
+                .map(|m| {
+                    let layer = in_font.master_layer_for(&glyph.name, m)?;
+                    let values = layer_values(layer, default_layer);
+                    if values.is_none() {
+                        problems.push(
+                            Problem::new(format!(
+                                "layer is incompatible with the default master; left out of {}",
+                                style
+                            ))
+                            .in_glyph(&glyph.name)
+                            .in_master(&master_name(m)),
+                        );
+                    }
+                    values
+                })
+                .collect();
+            interpolator
+                .interpolate(&values)
+                .map(|values| layer_from_values(default_layer, &values))
+        })
+        .collect();
+    diagnostics.extend(problems);
+
+    for (glyph, layer) in in_font.glyphs.iter_mut().zip(layers) {
+        if let Some(layer) = layer {
+            glyph.layers.push(layer);
+        }
+    }
+    in_font.glyphs.reindex();
+    Ok(master)
+}
+
+struct Interpolator {
+    model: VariationModel,
+    default_ix: usize,
+    location: OTVarLocation,
+}
+
+impl Interpolator {
+    // Values are given for each master, or None where a master doesn't have
+    // one; there is nothing to interpolate without a value in the default
+    // master.
+    fn interpolate(&self, values: &[Option<Array1<f32>>]) -> Option<Array1<f32>> {
+        values[self.default_ix].as_ref()?;
+        self.model
+            .get_deltas_and_supports(values)
+            .into_iter()
+            .map(|(delta, support)| delta * support_scalar(&self.location, &support))
+            .reduce(|a, b| a + b)
+    }
+}
+
+// The numbers which vary between masters, in the order of the default
+// master's layer, or None if the layer can't be interpolated with it
+fn layer_values(layer: &Layer, default_layer: &Layer) -> Option<Array1<f32>> {
+    if layer.shapes.len() != default_layer.shapes.len() {
+        return None;
+    }
+    let mut values = vec![layer.width as f32];
+    for (shape, default_shape) in layer.shapes.iter().zip(default_layer.shapes.iter()) {
+        match (shape, default_shape) {
+            (Shape::PathShape(path), Shape::PathShape(default_path)) => {
+                if path.nodes.len() != default_path.nodes.len()
+                    || path
+                        .nodes
+                        .iter()
+                        .zip(default_path.nodes.iter())
+                        .any(|(node, default_node)| node.nodetype != default_node.nodetype)
+                {
+                    return None;
+                }
+                values.extend(path.nodes.iter().flat_map(|node| [node.x, node.y]));
+            }
+            (Shape::ComponentShape(component), Shape::ComponentShape(default_component))
+                if component.reference == default_component.reference =>
+            {
+                values.extend(component.transform.as_coeffs().iter().map(|&c| c as f32));
+            }
+            _ => return None,
+        }
+    }
+    for default_anchor in &default_layer.anchors {
+        let anchor = layer
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == default_anchor.name)?;
+        values.extend([anchor.x as f32, anchor.y as f32]);
+    }
+    Some(Array1::from(values))
+}
+
+fn layer_from_values(default_layer: &Layer, values: &Array1<f32>) -> Layer {
+    let mut values = values.iter().copied();
+    let mut next = || values.next().expect("Too few values for layer");
+    let mut layer = Layer::new(next().round() as i32);
+    layer.id = Some(INSTANCE_MASTER_ID.to_string());
+    for shape in &default_layer.shapes {
+        match shape {
+            Shape::PathShape(path) => {
+                let mut path = path.clone();
+                for node in path.nodes.iter_mut() {
+                    node.x = next();
+                    node.y = next();
+                }
+                layer.push_path(path);
+            }
+            Shape::ComponentShape(component) => layer.push_component(Component {
+                reference: component.reference.clone(),
+                transform: kurbo::Affine::new([(); 6].map(|_| next() as f64)),
+            }),
+        }
+    }
+    for anchor in &default_layer.anchors {
+        layer.anchors.push(Anchor {
+            x: next().round() as i32,
+            y: next().round() as i32,
+            name: anchor.name.clone(),
+        });
+    }
+    layer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babelfont::{Axis, Glyph, GlyphCategory, Location, Node, NodeType, Path};
+    use std::collections::HashMap;
+
+    // A glyph with a line and an anchor, in a light master at wght=400 and
+    // a bold one at wght=700
+    fn source() -> babelfont::Font {
+        let mut font = babelfont::Font::new();
+        let mut axis = Axis::new("Weight", "wght".to_string());
+        axis.min = Some(400.0);
+        axis.default = Some(400.0);
+        axis.max = Some(700.0);
+        font.axes.push(axis);
+        let mut glyph = Glyph {
+            name: "a".to_string(),
+            production_name: None,
+            category: GlyphCategory::Base,
+            codepoints: vec![0x61],
+            layers: vec![],
+            exported: true,
+            direction: None,
+        };
+        for (id, wght, width, x, kern) in [
+            ("Light", 400.0, 500, 100.0, -50),
+            ("Bold", 700.0, 800, 400.0, -80),
+        ] {
+            let location = Location(HashMap::from([("wght".to_string(), wght)]));
+            let mut master = Master::new(id, id, location);
+            master.metrics.insert("xHeight".to_string(), width + 100);
+            master
+                .kerning
+                .insert(("a".to_string(), "a".to_string()), kern);
+            font.masters.push(master);
+
+            let mut layer = Layer::new(width);
+            layer.id = Some(id.to_string());
+            layer.push_path(Path {
+                nodes: vec![
+                    Node {
+                        x: 0.0,
+                        y: 0.0,
+                        nodetype: NodeType::Line,
+                    },
+                    Node {
+                        x,
+                        y: 0.0,
+                        nodetype: NodeType::Line,
+                    },
+                ],
+                closed: true,
+                ..Default::default()
+            });
+            layer.anchors.push(Anchor {
+                x: width / 2,
+                y: 0,
+                name: "top".to_string(),
+            });
+            glyph.layers.push(layer);
+        }
+        font.glyphs.push(glyph);
+        font.instances.push(Instance {
+            name: "Semibold".into(),
+            location: Location(HashMap::from([("wght".to_string(), 600.0)])),
+            style_name: "Semibold".into(),
+        });
+        font
+    }
+
+    #[test]
+    fn test_add_instance_master() {
+        let mut font = source();
+        let instance = font.instances.remove(0);
+        let diagnostics = Diagnostics::new("test.glyphs");
+        let master = add_instance_master(&mut font, &instance, "Semibold", &diagnostics).unwrap();
+
+        // wght=600 is two thirds of the way from the light master to the bold
+        assert_eq!(master.metrics["xHeight"], 800);
+        assert_eq!(master.kerning[&("a".to_string(), "a".to_string())], -70);
+        let layer = font.glyphs.get_layer("a", INSTANCE_MASTER_ID).unwrap();
+        assert_eq!(layer.width, 700);
+        let nodes: Vec<(f32, f32)> = layer
+            .paths()
+            .flat_map(|p| p.nodes.iter().map(|n| (n.x, n.y)))
+            .collect();
+        assert_eq!(nodes, vec![(0.0, 0.0), (300.0, 0.0)]);
+        assert_eq!(layer.anchors[0].x, 350);
+    }
+
+    #[test]
+    fn test_incompatible_layer_is_left_out() {
+        let mut font = source();
+        let instance = font.instances.remove(0);
+        font.glyphs.get_mut("a").unwrap().layers[1].shapes.clear();
+        font.glyphs.reindex();
+        let diagnostics = Diagnostics::new("test.glyphs");
+        add_instance_master(&mut font, &instance, "Semibold", &diagnostics).unwrap();
+
+        // Without the bold layer, the instance is the same as the light master
+        let layer = font.glyphs.get_layer("a", INSTANCE_MASTER_ID).unwrap();
+        assert_eq!(layer.width, 500);
+        assert_eq!(diagnostics.summarize(), 1);
+    }
+
+    #[test]
+    fn test_build_instance_fonts_restores_source() {
+        let mut font = source();
+        let options = BuildOptions {
+            subset: None,
+            otf: false,
+            autohint: false,
+            vertical_metrics: None,
+            kern: true,
+            gdef: false,
+            cache_dir: None,
+        };
+        let diagnostics = Diagnostics::new("test.glyphs");
+        let fonts = build_instance_fonts(&mut font, &options, &diagnostics).unwrap();
+        let styles: Vec<&str> = fonts.iter().map(|(style, _)| style.as_str()).collect();
+        assert_eq!(styles, vec!["Semibold"]);
+        assert_eq!(font.masters.len(), 2);
+        assert_eq!(font.instances.len(), 1);
+        assert_eq!(font.glyphs.get("a").unwrap().layers.len(), 2);
+        assert!(font.names.typographic_subfamily.default().is_none());
+    }
+}
@@ -1,4 +1,6 @@
-use babelfont::Font;
+use crate::buildbasic::master_name;
+use crate::diagnostics::{Diagnostics, Problem};
+use babelfont::{Font, Master};
 use fonttools::layout::common::{
     FeatureList, LanguageSystem, Lookup, LookupFlags, Script, ScriptList,
 };
@@ -17,38 +19,69 @@ macro_rules! hashmap {
         };
     }
 
-pub fn build_kerning(font: &Font, mapping: &BTreeMap<String, u16>) -> GPOS {
-    let master = font.default_master().unwrap();
-    let mut kerntable: PairPositioningMap = BTreeMap::new();
-    for ((l, r), value) in master.kerning.iter() {
-        let l_array: Vec<String> = if let Some(stripped) = l.strip_prefix('@') {
-            font.kern_groups.get(stripped).unwrap_or(&vec![]).to_vec()
-        } else {
-            vec![l.clone()]
-        };
-        let r_array: Vec<String> = if let Some(stripped) = r.strip_prefix('@') {
-            font.kern_groups.get(stripped).unwrap_or(&vec![]).to_vec()
+// Builds a kern feature from the kerning of the given master
+pub fn build_kerning(
+    font: &Font,
+    master: &Master,
+    mapping: &BTreeMap<String, u16>,
+    diagnostics: &Diagnostics,
+) -> GPOS {
+    let master_name = master_name(master);
+    let mut problems: Vec<Problem> = vec![];
+    let mut expand = |side: &String| -> Vec<String> {
+        if let Some(stripped) = side.strip_prefix('@') {
+            font.kern_groups.get(stripped).cloned().unwrap_or_else(|| {
+                problems.push(
+                    Problem::new(format!("kerning refers to unknown group {}", side))
+                        .in_master(&master_name),
+                );
+                vec![]
+            })
         } else {
-            vec![r.clone()]
-        };
+            vec![side.clone()]
+        }
+    };
+    let mut pairs: Vec<(Vec<String>, Vec<String>, i16)> = vec![];
+    for ((l, r), value) in master.kerning.iter() {
+        pairs.push((expand(l), expand(r), *value));
+    }
 
+    let mut kerntable: PairPositioningMap = BTreeMap::new();
+    for (l_array, r_array, value) in pairs {
         for l in &l_array {
             for r in &r_array {
-                add_single_kern(
-                    &mut kerntable,
-                    l.to_string(),
-                    r.to_string(),
-                    *value,
-                    mapping,
-                );
+                add_single_kern(&mut kerntable, l, r, value, mapping);
+            }
+        }
+    }
+
+    // Glyphs which aren't in the mapping may just have been left out of
+    // a subset; it's only a problem if they aren't in the source at all
+    for glyph in master.kerning.keys().flat_map(|(l, r)| [l, r]) {
+        if !glyph.starts_with('@') && font.glyphs.get(glyph).is_none() {
+            problems.push(
+                Problem::new(format!("kerning refers to unknown glyph {}", glyph))
+                    .in_master(&master_name),
+            );
+        }
+    }
+    for (group, members) in &font.kern_groups {
+        for glyph in members {
+            if font.glyphs.get(glyph).is_none() {
+                problems.push(Problem::new(format!(
+                    "kern group @{} contains unknown glyph {}",
+                    group, glyph
+                )));
             }
         }
     }
+    diagnostics.extend(problems);
     let pairpos = PairPos { mapping: kerntable };
     GPOS {
         lookups: vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Pair(vec![pairpos]),
         }],
         scripts: ScriptList {
@@ -68,25 +101,19 @@ pub fn build_kerning(font: &Font, mapping: &BTreeMap<String, u16>) -> GPOS {
     }
 }
 
+// Glyphs which aren't in the mapping are skipped; build_kerning reports
+// them if need be
 fn add_single_kern(
     kerntable: &mut PairPositioningMap,
-    l: String,
-    r: String,
+    l: &str,
+    r: &str,
     value: i16,
     mapping: &BTreeMap<String, u16>,
 ) {
-    let l_gid = mapping.get(&l);
-    let r_gid = mapping.get(&r);
-    if l_gid.is_none() {
-        // println!("Unknown glyph {:?} in kerning table", l);
-        return;
-    }
-    let l_gid = l_gid.unwrap();
-    if r_gid.is_none() {
-        // println!("Unknown glyph {:?} in kerning table", r);
-        return;
-    }
-    let r_gid = r_gid.unwrap();
+    let (l_gid, r_gid) = match (mapping.get(l), mapping.get(r)) {
+        (Some(l_gid), Some(r_gid)) => (l_gid, r_gid),
+        _ => return,
+    };
     kerntable.insert(
         (*l_gid, *r_gid),
         (valuerecord!(xAdvance = value), valuerecord!()),
@@ -1,34 +1,73 @@
 //! A fonticulously fast variable font builder
 mod basictables;
 mod buildbasic;
+mod cache;
+mod cff;
+mod config;
+mod diagnostics;
 mod fontinfo;
+mod gdef;
 mod glyph;
+mod instances;
 mod kerning;
 mod utils;
+mod vmetrics;
 
 use buildbasic::build_font;
 use clap::{App, Arg, ArgMatches};
+use diagnostics::{BuildError, Diagnostics};
+use fonttools::vmetrics::VerticalMetricsPolicy;
 
 // use rayon::prelude::*;
 use std::collections::HashSet;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /*
     OK, here is the basic plan:
 
-    1) This function handles command line stuff, uses babelfont-rs to load
-       the source file(s) into memory, and calls into buildbasic::build_font.
+    1) This function handles command line stuff (or config.rs reads a build
+       config file), uses babelfont-rs to load the source file(s) into memory,
+       and calls into buildbasic::build_font.
     2) The build_font function in buildbasic.rs coordinates the build.
     3) basictables.rs creates the non-glyph, non-layout, non-variable metadata tables
        (that is: head, hhea, maxp, OS/2, hmtx, cmap, glyf, name, post, loca).
     3a) fontinfo.rs works out what some of the stuff in those tables should be.
     4) glyph.rs handles Babelfont->OT glyph conversion, creating the glyf and gvar
-       table entries for each glyph.
+       table entries for each glyph. With --cache, cache.rs keeps those entries
+       on disk so that unchanged glyphs needn't be converted again next time.
+    4a) In --otf mode, cff.rs does the same job, producing a CFF (static) or
+       CFF2 (variable) table instead.
     5) babelfont-rs creates the variable metadata tables (fvar,avar).
-    6) We come back here and save the files at the end.
+    5a) Static fonts at the source's named instances are built from a master
+       which instances.rs interpolates at each instance's location.
+    6) We come back here, optionally make the vertical metrics consistent
+       across the family (vmetrics.rs), optionally autohint the TrueType
+       outlines, and save the files at the end.
+    7) Anything which goes wrong along the way is either a BuildError, which
+       stops the build, or a Problem, which is noted so that they can all be
+       reported together at the end (diagnostics.rs).
 */
 
+// What to build, whether it was asked for on the command line or in a
+// build config file.
+pub struct BuildOptions {
+    // Only convert these glyphs
+    pub subset: Option<HashSet<String>>,
+    // Produce CFF/CFF2 outlines instead of TrueType
+    pub otf: bool,
+    // Generate TrueType hinting instructions
+    pub autohint: bool,
+    // Make the vertical metrics consistent across the family
+    pub vertical_metrics: Option<VerticalMetricsPolicy>,
+    // Write a kern feature from the source's kerning
+    pub kern: bool,
+    // Write GDEF glyph classes from the source's glyph categories
+    pub gdef: bool,
+    // Reuse glyf/gvar entries from earlier builds kept in this directory
+    pub cache_dir: Option<PathBuf>,
+}
+
 fn main() {
     // Command line handling
     env_logger::init_from_env(
@@ -36,7 +75,20 @@ fn main() {
     );
     let matches = parse_command_line();
 
-    let filename = matches.value_of("INPUT").unwrap();
+    // --config means everything else comes from the build config file
+    let result = match matches.value_of("config") {
+        Some(config_file) => config::build_from_config(Path::new(config_file)),
+        None => build_from_command_line(&matches),
+    };
+    if let Err(e) = result {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn build_from_command_line(matches: &ArgMatches<'static>) -> Result<(), BuildError> {
+    // clap makes sure we have INPUT if we don't have --config
+    let filename = Path::new(matches.value_of("INPUT").unwrap_or_default());
 
     // If we are only handling a subset of the glyphs (usually for debugging
     // purposes), split that into a set here.
@@ -44,19 +96,55 @@ fn main() {
         .value_of("subset")
         .map(|x| x.split(',').map(|y| y.to_string()).collect());
 
-    let mut in_font = load_with_babelfont(filename);
+    let mut in_font = load_with_babelfont(filename)?;
+
+    // --otf means we produce CFF/CFF2 outlines instead of glyf/gvar
+    let otf = matches.is_present("otf");
+
+    // --autohint generates TrueType instructions, so does nothing for CFF
+    let autohint = matches.is_present("autohint");
+    if autohint && otf {
+        log::warn!("--autohint only applies to TrueType outlines; ignoring it");
+    }
+    let autohint = autohint && !otf;
+
+    // --vertical-metrics computes hhea/OS/2 metrics from the whole family
+    let vertical_metrics: Option<VerticalMetricsPolicy> = matches
+        .value_of("vertical-metrics")
+        .map(|policy| policy.parse().map_err(BuildError::Options))
+        .transpose()?;
+
+    let options = BuildOptions {
+        subset,
+        otf,
+        autohint,
+        vertical_metrics,
+        kern: true,
+        gdef: false,
+        cache_dir: matches.value_of("cache").map(PathBuf::from),
+    };
 
     // --masters means we produce a TTF for each master and don't do interpolation
+    let diagnostics = Diagnostics::new(filename);
     if matches.is_present("masters") {
-        create_ttf_per_master(&mut in_font, subset);
+        create_ttf_per_master(&mut in_font, &options, &diagnostics)
     } else {
-        create_variable_font(&mut in_font, subset, matches);
+        create_variable_font(&mut in_font, &options, matches, &diagnostics)
     }
+    .map_err(|e| BuildError::InSource(filename.to_path_buf(), Box::new(e)))?;
+    diagnostics.finish()
 }
 
 fn parse_command_line() -> ArgMatches<'static> {
     App::new("fonticulous")
         .about("A variable font builder")
+        .arg(
+            Arg::with_name("config")
+                .help("Build the fonts described by a TOML build config file")
+                .required(false)
+                .takes_value(true)
+                .long("config"),
+        )
         .arg(
             Arg::with_name("subset")
                 .help("Only convert the given glyphs (for testing only)")
@@ -71,10 +159,38 @@ fn parse_command_line() -> ArgMatches<'static> {
                 .takes_value(false)
                 .long("masters"),
         )
+        .arg(
+            Arg::with_name("otf")
+                .help("Produce PostScript (CFF/CFF2) outlines instead of TrueType")
+                .required(false)
+                .takes_value(false)
+                .long("otf"),
+        )
+        .arg(
+            Arg::with_name("autohint")
+                .help("Generate TrueType hinting instructions automatically")
+                .required(false)
+                .takes_value(false)
+                .long("autohint"),
+        )
+        .arg(
+            Arg::with_name("vertical-metrics")
+                .help("Make the vertical metrics consistent across the family, using the given policy (bounds or googlefonts)")
+                .required(false)
+                .takes_value(true)
+                .long("vertical-metrics"),
+        )
+        .arg(
+            Arg::with_name("cache")
+                .help("Keep converted glyphs in this directory, and reuse them when they haven't changed")
+                .required(false)
+                .takes_value(true)
+                .long("cache"),
+        )
         .arg(
             Arg::with_name("INPUT")
                 .help("Sets the input file to use")
-                .required(true),
+                .required_unless("config"),
         )
         .arg(
             Arg::with_name("OUTPUT")
@@ -84,25 +200,32 @@ fn parse_command_line() -> ArgMatches<'static> {
         .get_matches()
 }
 
-fn load_with_babelfont(filename: &str) -> babelfont::Font {
-    if filename.ends_with(".designspace") {
-        babelfont::convertors::designspace::load(PathBuf::from(filename))
-            .expect("Couldn't load source")
-    } else if filename.ends_with(".ufo") {
-        babelfont::convertors::ufo::load(PathBuf::from(filename)).expect("Couldn't load source")
-    } else if filename.ends_with(".glyphs") {
-        babelfont::convertors::glyphs3::load(PathBuf::from(filename)).expect("Couldn't load source")
-    } else {
-        panic!("Unknown file type {:?}", filename);
-    }
+fn load_with_babelfont(filename: &Path) -> Result<babelfont::Font, BuildError> {
+    let path = filename.to_path_buf();
+    let loaded = match filename.extension().and_then(|ext| ext.to_str()) {
+        Some("designspace") => babelfont::convertors::designspace::load(path),
+        Some("ufo") => babelfont::convertors::ufo::load(path),
+        Some("glyphs") => babelfont::convertors::glyphs3::load(path),
+        _ => return Err(BuildError::UnknownSourceType(path)),
+    };
+    // babelfont's errors don't always say which file they were loading
+    loaded.map_err(|e| BuildError::InSource(filename.to_path_buf(), Box::new(e.into())))
 }
 
-fn create_ttf_per_master(in_font: &mut babelfont::Font, subset: Option<HashSet<String>>) {
-    let family_name = in_font
+fn family_name(in_font: &babelfont::Font) -> String {
+    in_font
         .names
         .family_name
         .default()
-        .unwrap_or_else(|| "New Font".to_string());
+        .unwrap_or_else(|| "New Font".to_string())
+}
+
+// Builds a static font for each master, returning them with the master names
+fn build_master_fonts(
+    in_font: &mut babelfont::Font,
+    options: &BuildOptions,
+    diagnostics: &Diagnostics,
+) -> Result<Vec<(String, fonttools::font::Font)>, BuildError> {
     let master_names: Vec<String> = in_font
         .masters
         .iter()
@@ -114,36 +237,116 @@ fn create_ttf_per_master(in_font: &mut babelfont::Font, subset: Option<HashSet<S
                 .unwrap_or_else(|| format!("Master{}", ix))
         })
         .collect();
-    for (ix, master_name) in master_names.iter().enumerate() {
-        let mut out_font = build_font(in_font, &subset, Some(ix));
-        log::info!("Building {}", master_name);
-        out_font
-            .save(format!("{}-{}.ttf", family_name, master_name))
-            .expect("Could not write font");
+    let mut out_fonts: Vec<fonttools::font::Font> = master_names
+        .iter()
+        .enumerate()
+        .map(|(ix, master_name)| {
+            log::info!("Building {}", master_name);
+            build_font(
+                in_font,
+                &options.subset,
+                Some(ix),
+                options.otf,
+                options.kern,
+                options.gdef,
+                options.cache_dir.as_deref(),
+                diagnostics,
+            )
+        })
+        .collect::<Result<_, _>>()?;
+    if let Some(policy) = options.vertical_metrics {
+        vmetrics::apply_vertical_metrics(in_font, &mut out_fonts, policy)?;
+    }
+    if options.autohint {
+        for out_font in out_fonts.iter_mut() {
+            autohint_font(out_font, diagnostics);
+        }
     }
+    Ok(master_names.into_iter().zip(out_fonts).collect())
 }
 
-fn create_variable_font(
+fn build_variable_font(
     in_font: &mut babelfont::Font,
-    subset: Option<HashSet<String>>,
-    matches: ArgMatches<'static>,
-) {
+    options: &BuildOptions,
+    diagnostics: &Diagnostics,
+) -> Result<fonttools::font::Font, BuildError> {
     let mut out_font;
     if in_font.masters.len() > 1 {
-        out_font = build_font(in_font, &subset, None);
+        out_font = build_font(
+            in_font,
+            &options.subset,
+            None,
+            options.otf,
+            options.kern,
+            options.gdef,
+            options.cache_dir.as_deref(),
+            diagnostics,
+        )?;
         // Ask babelfont to make fvar/avar
-        in_font
-            .add_variation_tables(&mut out_font)
-            .expect("Couldn't add variation tables")
+        in_font.add_variation_tables(&mut out_font)?;
     } else {
-        out_font = build_font(in_font, &subset, Some(0));
+        out_font = build_font(
+            in_font,
+            &options.subset,
+            Some(0),
+            options.otf,
+            options.kern,
+            options.gdef,
+            options.cache_dir.as_deref(),
+            diagnostics,
+        )?;
+    }
+    if let Some(policy) = options.vertical_metrics {
+        vmetrics::apply_vertical_metrics(in_font, std::slice::from_mut(&mut out_font), policy)?;
+    }
+    if options.autohint {
+        autohint_font(&mut out_font, diagnostics);
     }
+    Ok(out_font)
+}
 
-    if matches.is_present("OUTPUT") {
-        out_font
-            .save(matches.value_of("OUTPUT").unwrap())
-            .expect("Could not write font");
-    } else {
-        out_font.write(io::stdout()).expect("Could not write font");
-    };
+fn save_font(font: &mut fonttools::font::Font, path: &Path) -> Result<(), BuildError> {
+    font.save(path)
+        .map_err(|e| BuildError::Write(path.to_path_buf(), e.to_string()))
+}
+
+fn create_ttf_per_master(
+    in_font: &mut babelfont::Font,
+    options: &BuildOptions,
+    diagnostics: &Diagnostics,
+) -> Result<(), BuildError> {
+    let family_name = family_name(in_font);
+    let extension = if options.otf { "otf" } else { "ttf" };
+    for (master_name, mut out_font) in build_master_fonts(in_font, options, diagnostics)? {
+        let path = format!("{}-{}.{}", family_name, master_name, extension);
+        save_font(&mut out_font, Path::new(&path))?;
+    }
+    Ok(())
+}
+
+fn create_variable_font(
+    in_font: &mut babelfont::Font,
+    options: &BuildOptions,
+    matches: &ArgMatches<'static>,
+    diagnostics: &Diagnostics,
+) -> Result<(), BuildError> {
+    let mut out_font = build_variable_font(in_font, options, diagnostics)?;
+    match matches.value_of("OUTPUT") {
+        Some(output) => save_font(&mut out_font, Path::new(output)),
+        None => out_font
+            .write(io::stdout())
+            .map_err(|e| BuildError::Write(PathBuf::from("<stdout>"), e.to_string())),
+    }
+}
+
+// A font which can't be autohinted is still usable, so this is a Problem
+// rather than a BuildError
+fn autohint_font(font: &mut fonttools::font::Font, diagnostics: &Diagnostics) {
+    let options = fonttools::autohint::AutohintOptions::default();
+    if let Err(e) = fonttools::autohint::autohint(font, &options) {
+        diagnostics.report(diagnostics::Problem::new(format!(
+            "could not autohint font: {}",
+            e
+        )));
+    }
 }
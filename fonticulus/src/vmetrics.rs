@@ -0,0 +1,59 @@
+use crate::cff::layer_to_kurbo;
+use crate::diagnostics::BuildError;
+use fonttools::font::Font;
+use fonttools::vmetrics::{FamilyMetrics, VerticalMetricsPolicy};
+use kurbo::{Rect, Shape};
+
+// The bounds of every glyph in every master, not just the ones which were
+// built, so that the static fonts of a family (and the instances of a
+// variable font) can all share the same metrics.
+fn all_masters_bounds(input: &babelfont::Font) -> Option<Rect> {
+    input
+        .glyphs
+        .iter()
+        .flat_map(|glyph| {
+            input.masters.iter().filter_map(move |master| {
+                input
+                    .master_layer_for(&glyph.name, master)
+                    .map(|layer| (glyph, master, layer))
+            })
+        })
+        // Any problems with the layers are reported when they are built
+        .flat_map(|(glyph, master, layer)| {
+            layer_to_kurbo(&glyph.name, master, layer, input, &mut vec![])
+        })
+        .map(|path| path.bounding_box())
+        .reduce(|a, b| a.union(b))
+}
+
+// The typo metrics come from the built fonts, which have already resolved
+// any custom values in the source.
+pub fn apply_vertical_metrics(
+    input: &babelfont::Font,
+    fonts: &mut [Font],
+    policy: VerticalMetricsPolicy,
+) -> Result<(), BuildError> {
+    let vmetrics_error = |e: fonttools::vmetrics::VerticalMetricsError| {
+        BuildError::Font(format!("Couldn't apply vertical metrics: {}", e))
+    };
+    let mut family = match fonts
+        .iter()
+        .map(FamilyMetrics::from_font)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(vmetrics_error)?
+        .into_iter()
+        .reduce(|a, b| a.union(&b))
+    {
+        Some(family) => family,
+        None => return Ok(()),
+    };
+    if let Some(bounds) = all_masters_bounds(input) {
+        family.y_max = family.y_max.max(bounds.y1.ceil() as i16);
+        family.y_min = family.y_min.min(bounds.y0.floor() as i16);
+    }
+    let metrics = policy.compute(&family);
+    for font in fonts.iter_mut() {
+        metrics.apply(font).map_err(vmetrics_error)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use clap::{App, Arg};
+use fonttools_cli::open_font;
+use std::io::Write;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("font-to-json")
+        .about("Converts a font to JSON")
+        .arg(Arg::from_usage("-c, --compact 'Write the JSON on a single line'"))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let infont = open_font(&matches);
+    let json = if matches.is_present("compact") {
+        serde_json::to_string(&infont)
+    } else {
+        serde_json::to_string_pretty(&infont)
+    };
+    let json = match json {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Could not convert font: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = matches.value_of("OUTPUT") {
+        std::fs::write(path, json)
+    } else {
+        std::io::stdout().write_all(json.as_bytes())
+    }
+    .expect("Could not write JSON");
+}
@@ -0,0 +1,37 @@
+use clap::{App, Arg};
+use fonttools::font::Font;
+use fonttools_cli::save_font;
+use std::io::Read;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("json-to-font")
+        .about("Converts JSON produced by font-to-json back to a font")
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let mut json = String::new();
+    if let Some(path) = matches.value_of("INPUT") {
+        json = std::fs::read_to_string(path).expect("Could not read JSON");
+    } else {
+        std::io::stdin()
+            .read_to_string(&mut json)
+            .expect("Could not read JSON");
+    }
+    match serde_json::from_str::<Font>(&json) {
+        Ok(font) => save_font(font, &matches),
+        Err(e) => {
+            log::error!("Could not parse JSON: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use fonttools::cff::subroutinizer::subroutinize_font;
+use fonttools_cli::{open_font, read_args, save_font};
+
+fn main() {
+    env_logger::init();
+    let matches = read_args(
+        "otf-subroutinize",
+        "Subroutinizes the charstrings of a CFF or CFF2 font",
+    );
+    let mut infont = open_font(&matches);
+    match subroutinize_font(&mut infont) {
+        Ok(true) => {}
+        Ok(false) => log::warn!("Font has no CFF or CFF2 table"),
+        Err(e) => {
+            log::error!("Could not subroutinize font: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+    save_font(infont, &matches);
+}
@@ -0,0 +1,46 @@
+use clap::{App, Arg};
+use fonttools::autohint::{autohint, AutohintOptions, Script};
+use fonttools_cli::{open_font, save_font};
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-autohint")
+        .about("Generates TrueType hinting instructions for a font")
+        .arg(Arg::from_usage(
+            "-l, --hinting-limit=[PPEM]  Switch off hinting above this size (default 200; 0 for no limit)",
+        ))
+        .arg(Arg::from_usage("--no-gasp  Do not add a gasp table"))
+        .arg(Arg::from_usage(
+            "-f, --fallback-script=[SCRIPT]  Script for glyphs without a character (latn, grek or cyrl)",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+    let mut options = AutohintOptions {
+        add_gasp: !matches.is_present("no-gasp"),
+        ..Default::default()
+    };
+    if let Some(limit) = matches.value_of("hinting-limit") {
+        options.hinting_limit = limit.parse().expect("Could not parse hinting limit");
+    }
+    if let Some(script) = matches.value_of("fallback-script") {
+        options.fallback_script = Some(script.parse::<Script>().unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }));
+    }
+    let mut infont = open_font(&matches);
+    if let Err(e) = autohint(&mut infont, &options) {
+        log::error!("Could not hint font: {}", e);
+        std::process::exit(1);
+    }
+    save_font(infont, &matches);
+}
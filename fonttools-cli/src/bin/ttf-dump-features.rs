@@ -0,0 +1,36 @@
+use clap::{App, Arg};
+use fonttools::fea;
+use fonttools_cli::open_font;
+use std::io::Write;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-dump-features")
+        .about("Decompiles the GSUB and GPOS tables of a font to a feature file")
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let infont = open_font(&matches);
+    let features = match fea::decompile(&infont) {
+        Ok(features) => features,
+        Err(e) => {
+            log::error!("Could not decompile layout tables: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = matches.value_of("OUTPUT") {
+        std::fs::write(path, features)
+    } else {
+        std::io::stdout().write_all(features.as_bytes())
+    }
+    .expect("Could not write feature file");
+}
@@ -0,0 +1,57 @@
+use clap::{App, Arg};
+use fonttools::instructions::{disassemble, to_text};
+use fonttools_cli::open_font;
+
+fn dump(title: &str, bytecode: &[u8]) {
+    if bytecode.is_empty() {
+        return;
+    }
+    println!("/* {} */", title);
+    match disassemble(bytecode) {
+        Ok(instructions) => print!("{}", to_text(&instructions)),
+        Err(e) => println!("/* Could not disassemble: {} */", e),
+    }
+    println!();
+}
+
+fn main() {
+    let matches = App::new("ttf-dump-instructions")
+        .about("Disassembles the TrueType instructions of a font")
+        .arg(Arg::from_usage(
+            "-g, --glyph=[GLYPH]  Only dump the instructions of the named glyph",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .get_matches();
+    let infont = open_font(&matches);
+    let only_glyph = matches.value_of("glyph");
+
+    if only_glyph.is_none() {
+        if let Some(fpgm) = infont.tables.fpgm().expect("Error reading fpgm table") {
+            dump("fpgm", &fpgm.0);
+        }
+        if let Some(prep) = infont.tables.prep().expect("Error reading prep table") {
+            dump("prep", &prep.0);
+        }
+    }
+
+    let glyph_names = infont
+        .tables
+        .post()
+        .expect("Error reading post table")
+        .and_then(|post| post.glyphnames.clone());
+    if let Some(glyf) = infont.tables.glyf().expect("Error reading glyf table") {
+        for (ix, glyph) in glyf.glyphs.iter().enumerate() {
+            let name = glyph_names
+                .as_ref()
+                .and_then(|names| names.get(ix).cloned())
+                .unwrap_or_else(|| format!("glyph{:05}", ix));
+            if only_glyph.is_none_or(|g| g == name) {
+                dump(&format!("glyph {}", name), &glyph.instructions);
+            }
+        }
+    }
+}
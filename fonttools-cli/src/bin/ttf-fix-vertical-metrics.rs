@@ -0,0 +1,61 @@
+use clap::{App, Arg};
+use fonttools::font::Font;
+use fonttools::vmetrics::{apply_to_family, VerticalMetricsPolicy};
+use std::path::Path;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-fix-vertical-metrics")
+        .about("Makes the vertical metrics of a family of fonts consistent")
+        .arg(Arg::from_usage(
+            "-p, --policy=[POLICY] 'How to compute the metrics: bounds (the default) or googlefonts'",
+        ))
+        .arg(Arg::from_usage(
+            "-o, --output-dir=[DIR] 'Writes the fonts to this directory instead of replacing them'",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("The fonts of the family")
+                .required(true)
+                .multiple(true),
+        )
+        .get_matches();
+
+    let policy: VerticalMetricsPolicy = matches
+        .value_of("policy")
+        .unwrap_or("bounds")
+        .parse()
+        .unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1);
+        });
+    let paths: Vec<&str> = matches.values_of("INPUT").unwrap().collect();
+    let mut fonts: Vec<Font> = paths
+        .iter()
+        .map(|path| {
+            Font::load(path).unwrap_or_else(|e| {
+                log::error!("Could not load {}: {}", path, e);
+                std::process::exit(1);
+            })
+        })
+        .collect();
+
+    match apply_to_family(&mut fonts, policy) {
+        Ok(metrics) => log::info!("Setting vertical metrics to {:?}", metrics),
+        Err(e) => {
+            log::error!("Could not set vertical metrics: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    for (font, path) in fonts.iter_mut().zip(paths.iter()) {
+        let output = match matches.value_of("output-dir") {
+            Some(dir) => Path::new(dir).join(Path::new(path).file_name().unwrap()),
+            None => Path::new(path).to_path_buf(),
+        };
+        font.save(&output).unwrap_or_else(|e| {
+            log::error!("Could not save {}: {}", output.display(), e);
+            std::process::exit(1);
+        });
+    }
+}
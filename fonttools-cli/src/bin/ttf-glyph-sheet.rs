@@ -0,0 +1,88 @@
+use clap::{App, Arg};
+use fonttools::proof::sheet::{glyph_sheet_pdf, glyph_sheet_svg, instance_locations, SheetOptions};
+use fonttools_cli::open_font;
+use std::io::Write;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-glyph-sheet")
+        .about("Draws all glyphs of a font into an SVG or PDF proof sheet")
+        .arg(Arg::from_usage(
+            "-s, --size=[SIZE] 'Size of each glyph cell in points (default 72)'",
+        ))
+        .arg(Arg::from_usage(
+            "--columns=[COLUMNS] 'Number of glyphs in each row (default 8)'",
+        ))
+        .arg(Arg::from_usage(
+            "--rows=[ROWS] 'Number of rows on each PDF page (default 10)'",
+        ))
+        .arg(Arg::from_usage(
+            "-i, --instances 'Draw the glyphs at each named instance of a variable font'",
+        ))
+        .arg(Arg::from_usage(
+            "-p, --points 'Show outline points and off curve handles'",
+        ))
+        .arg(Arg::from_usage(
+            "-f, --format=[FORMAT] 'Output format, svg or pdf (default from the output file name, or pdf)'",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let mut options = SheetOptions::default();
+    if let Some(size) = matches.value_of("size") {
+        options.cell_size = size.parse().expect("Could not parse cell size");
+    }
+    if let Some(columns) = matches.value_of("columns") {
+        options.columns = columns.parse().expect("Could not parse columns");
+    }
+    if let Some(rows) = matches.value_of("rows") {
+        options.rows_per_page = rows.parse().expect("Could not parse rows");
+    }
+    options.show_points = matches.is_present("points");
+
+    let output = matches.value_of("OUTPUT");
+    let svg = match matches.value_of("format") {
+        Some("svg") => true,
+        Some("pdf") => false,
+        Some(other) => {
+            log::error!("Unknown output format {}", other);
+            std::process::exit(1);
+        }
+        None => output.is_some_and(|o| o.to_lowercase().ends_with(".svg")),
+    };
+    let infont = open_font(&matches);
+    if matches.is_present("instances") {
+        options.locations = instance_locations(&infont).expect("Could not read instances");
+        if options.locations.is_empty() {
+            log::warn!("Font has no named instances; drawing the default location");
+        }
+    }
+
+    let sheet = if svg {
+        glyph_sheet_svg(&infont, &options).map(|svg| svg.into_bytes())
+    } else {
+        glyph_sheet_pdf(&infont, &options)
+    };
+    let sheet = match sheet {
+        Ok(sheet) => sheet,
+        Err(e) => {
+            log::error!("Could not draw glyph sheet: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = output {
+        std::fs::write(path, sheet)
+    } else {
+        std::io::stdout().write_all(&sheet)
+    }
+    .expect("Could not write glyph sheet");
+}
@@ -0,0 +1,71 @@
+use clap::{App, Arg};
+use fonttools::hinting::Hinter;
+use fonttools_cli::open_font;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-hint-glyph")
+        .about("Runs a glyph's TrueType instructions and prints the hinted outline")
+        .arg(Arg::from_usage(
+            "-p, --ppem=[PPEM]  Size in pixels per em (default 12)",
+        ))
+        .arg(Arg::from_usage(
+            "-c, --coords=[COORDS]  Comma-separated normalized variation coordinates",
+        ))
+        .arg(Arg::from_usage("<GLYPH>  Name of the glyph to hint"))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .get_matches();
+    let ppem: u16 = matches
+        .value_of("ppem")
+        .map_or(12, |p| p.parse().expect("Could not parse ppem"));
+    let coords: Vec<f32> = matches.value_of("coords").map_or(vec![], |c| {
+        c.split(',')
+            .map(|v| v.trim().parse().expect("Could not parse coordinate"))
+            .collect()
+    });
+    let glyph_name = matches.value_of("GLYPH").unwrap();
+    let infont = open_font(&matches);
+
+    let glyph_names = infont
+        .tables
+        .post()
+        .expect("Error reading post table")
+        .and_then(|post| post.glyphnames.clone())
+        .unwrap_or_default();
+    let gid = match glyph_names.iter().position(|n| n == glyph_name) {
+        Some(gid) => gid as u16,
+        None => {
+            log::error!("Glyph {} not found", glyph_name);
+            std::process::exit(1);
+        }
+    };
+
+    let glyph = Hinter::new(&infont, ppem, &coords).and_then(|mut h| h.hint_glyph(gid));
+    match glyph {
+        Ok(glyph) => {
+            println!("advance {}", glyph.advance_width as f32 / 64.0);
+            for contour in glyph.contours {
+                let points: Vec<String> = contour
+                    .iter()
+                    .map(|p| {
+                        format!(
+                            "{}{},{}",
+                            if p.on_curve { "" } else { "~" },
+                            p.x as f32 / 64.0,
+                            p.y as f32 / 64.0
+                        )
+                    })
+                    .collect();
+                println!("{}", points.join(" "));
+            }
+        }
+        Err(e) => {
+            log::error!("Could not hint {}: {}", glyph_name, e);
+            std::process::exit(1);
+        }
+    }
+}
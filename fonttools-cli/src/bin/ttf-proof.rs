@@ -0,0 +1,110 @@
+use clap::{App, Arg};
+use fonttools::proof::{render, render_svg, ProofOptions, DEFAULT_FEATURES};
+use fonttools::types::Tag;
+use fonttools_cli::open_font;
+use std::io::Write;
+
+fn parse_tag(tag: &str) -> Tag {
+    match Tag::from_raw(tag) {
+        Ok(tag) => tag,
+        Err(_) => {
+            log::error!("Invalid tag {}", tag);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-proof")
+        .about("Renders a line of text to a PNG or SVG image")
+        .arg(Arg::from_usage(
+            "-p, --ppem=[PPEM] 'Size in pixels per em (default 48)'",
+        ))
+        .arg(Arg::from_usage(
+            "-c, --coords=[COORDS] 'Normalized variation coordinates, separated by commas'",
+        ))
+        .arg(Arg::from_usage(
+            "-s, --script=[SCRIPT] 'OpenType script tag (default DFLT)'",
+        ))
+        .arg(Arg::from_usage(
+            "-l, --language=[LANGUAGE] 'OpenType language system tag'",
+        ))
+        .arg(Arg::from_usage(
+            "--features=[FEATURES] 'Feature tags to apply, separated by commas (default ccmp,locl,rlig,liga,clig,kern)'",
+        ))
+        .arg(Arg::from_usage(
+            "-f, --format=[FORMAT] 'Image format, png or svg (default from the output file name, or png)'",
+        ))
+        .arg(Arg::from_usage(
+            "<TEXT> 'Text to render'",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output image file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let mut options = ProofOptions::default();
+    if let Some(ppem) = matches.value_of("ppem") {
+        options.ppem = ppem.parse().expect("Could not parse ppem");
+    }
+    if let Some(coords) = matches.value_of("coords") {
+        options.coords = coords
+            .split(',')
+            .map(|v| v.trim().parse().expect("Could not parse coordinate"))
+            .collect();
+    }
+    if let Some(script) = matches.value_of("script") {
+        options.script = parse_tag(script);
+    }
+    options.language = matches.value_of("language").map(parse_tag);
+    options.features = matches.value_of("features").map_or_else(
+        || DEFAULT_FEATURES.to_vec(),
+        |features| {
+            features
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .map(|f| parse_tag(f.trim()))
+                .collect()
+        },
+    );
+
+    let output = matches.value_of("OUTPUT");
+    let svg = match matches.value_of("format") {
+        Some("svg") => true,
+        Some("png") => false,
+        Some(other) => {
+            log::error!("Unknown image format {}", other);
+            std::process::exit(1);
+        }
+        None => output.is_some_and(|o| o.to_lowercase().ends_with(".svg")),
+    };
+    let text = matches.value_of("TEXT").unwrap();
+    let infont = open_font(&matches);
+
+    let image = if svg {
+        render_svg(&infont, text, &options).map(|svg| svg.into_bytes())
+    } else {
+        render(&infont, text, &options).map(|bitmap| bitmap.inverted().to_png())
+    };
+    let image = match image {
+        Ok(image) => image,
+        Err(e) => {
+            log::error!("Could not render text: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = output {
+        std::fs::write(path, image)
+    } else {
+        std::io::stdout().write_all(&image)
+    }
+    .expect("Could not write image");
+}
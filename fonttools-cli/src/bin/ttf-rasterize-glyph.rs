@@ -0,0 +1,113 @@
+use clap::{App, Arg};
+use fonttools::raster::Rasterizer;
+use fonttools_cli::open_font;
+use std::io::Write;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-rasterize-glyph")
+        .about("Renders a glyph to a grayscale PNG or PGM image")
+        .arg(Arg::from_usage(
+            "-p, --ppem=[PPEM] 'Size in pixels per em (default 64)'",
+        ))
+        .arg(Arg::from_usage(
+            "-c, --coords=[COORDS] 'Normalized variation coordinates, separated by commas'",
+        ))
+        .arg(Arg::from_usage(
+            "-f, --format=[FORMAT] 'Image format, png or pgm (default from the output file name, or png)'",
+        ))
+        .arg(Arg::from_usage(
+            "-i, --invert 'Draw black ink on a white background'",
+        ))
+        .arg(Arg::from_usage(
+            "<GLYPH> 'Name of the glyph to render'",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output image file to use")
+                .required(false),
+        )
+        .get_matches();
+    let ppem: f32 = matches
+        .value_of("ppem")
+        .map_or(64.0, |p| p.parse().expect("Could not parse ppem"));
+    let coords: Vec<f32> = matches.value_of("coords").map_or(vec![], |c| {
+        c.split(',')
+            .map(|v| v.trim().parse().expect("Could not parse coordinate"))
+            .collect()
+    });
+    let output = matches.value_of("OUTPUT");
+    let pgm = match matches.value_of("format") {
+        Some("pgm") => true,
+        Some("png") => false,
+        Some(other) => {
+            log::error!("Unknown image format {}", other);
+            std::process::exit(1);
+        }
+        None => output.is_some_and(|o| o.to_lowercase().ends_with(".pgm")),
+    };
+    let glyph_name = matches.value_of("GLYPH").unwrap();
+    let infont = open_font(&matches);
+
+    let glyph_names = infont
+        .tables
+        .post()
+        .expect("Error reading post table")
+        .and_then(|post| post.glyphnames.clone())
+        .unwrap_or_default();
+    let gid = match glyph_names.iter().position(|n| n == glyph_name) {
+        Some(gid) => gid as u16,
+        None => {
+            log::error!("Glyph {} not found", glyph_name);
+            std::process::exit(1);
+        }
+    };
+
+    let glyf = infont
+        .tables
+        .glyf()
+        .expect("Error reading glyf table")
+        .expect("No glyf table found");
+    let upem = infont
+        .tables
+        .head()
+        .expect("Error reading head table")
+        .expect("No head table found")
+        .unitsPerEm;
+    let gvar = infont.tables.gvar().expect("Error reading gvar table");
+    let mut rasterizer = Rasterizer::new(&glyf, upem);
+    if let Some(gvar) = gvar.as_ref() {
+        rasterizer = rasterizer.with_variations(gvar, &coords);
+    }
+
+    let mut bitmap = match rasterizer.rasterize_gid(gid, ppem) {
+        Ok(bitmap) => bitmap,
+        Err(e) => {
+            log::error!("Could not rasterize {}: {}", glyph_name, e);
+            std::process::exit(1);
+        }
+    };
+    if bitmap.pixels.is_empty() {
+        log::error!("Glyph {} has no outline", glyph_name);
+        std::process::exit(1);
+    }
+    if matches.is_present("invert") {
+        bitmap = bitmap.inverted();
+    }
+    let image = if pgm {
+        bitmap.to_pgm()
+    } else {
+        bitmap.to_png()
+    };
+    if let Some(path) = output {
+        std::fs::write(path, image)
+    } else {
+        std::io::stdout().write_all(&image)
+    }
+    .expect("Could not write image");
+}
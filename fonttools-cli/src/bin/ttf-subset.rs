@@ -0,0 +1,129 @@
+use clap::{App, Arg};
+use fonttools::subset::{subset, SubsetOptions};
+use fonttools::types::Tag;
+use fonttools_cli::{open_font, save_font};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-subset")
+        .about("Reduces a font to the glyphs needed for a set of characters")
+        .arg(Arg::from_usage(
+            "-u, --unicodes=[CODEPOINTS] 'Comma separated hex codepoints or ranges to keep (e.g. U+0041-005A,61)'",
+        ))
+        .arg(Arg::from_usage("-t, --text=[TEXT] 'Characters to keep'"))
+        .arg(Arg::from_usage(
+            "-g, --glyphs=[NAMES] 'Comma separated glyph names to keep'",
+        ))
+        .arg(Arg::from_usage(
+            "--gids=[GIDS] 'Comma separated glyph IDs or ranges to keep (e.g. 0-10,20)'",
+        ))
+        .arg(Arg::from_usage(
+            "--layout-features=[TAGS] 'Comma separated layout features to keep (default all)'",
+        ))
+        .arg(Arg::from_usage(
+            "--no-layout-closure 'Do not keep glyphs produced by substitutions'",
+        ))
+        .arg(Arg::from_usage(
+            "--retain-gids 'Keep glyph IDs, leaving removed glyphs empty'",
+        ))
+        .arg(Arg::from_usage("--no-hinting 'Remove TrueType instructions'"))
+        .arg(Arg::from_usage(
+            "--name-ids=[IDS] 'Comma separated name IDs or ranges to keep (default all)'",
+        ))
+        .arg(Arg::from_usage(
+            "--name-languages=[IDS] 'Comma separated Windows language IDs to keep (e.g. 0x409)'",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let mut options = SubsetOptions {
+        layout_closure: !matches.is_present("no-layout-closure"),
+        retain_gids: matches.is_present("retain-gids"),
+        drop_hinting: matches.is_present("no-hinting"),
+        ..Default::default()
+    };
+    if let Some(unicodes) = matches.value_of("unicodes") {
+        options.unicodes = parse_ranges(unicodes, 16);
+    }
+    if let Some(text) = matches.value_of("text") {
+        options.unicodes.extend(text.chars().map(|c| c as u32));
+    }
+    if let Some(glyphs) = matches.value_of("glyphs") {
+        options.glyph_names = glyphs.split(',').map(|name| name.to_string()).collect();
+    }
+    if let Some(gids) = matches.value_of("gids") {
+        options.gids = parse_ranges(gids, 10);
+    }
+    if let Some(features) = matches.value_of("layout-features") {
+        options.layout_features = Some(
+            features
+                .split(',')
+                .map(|feature| {
+                    Tag::from_str(feature).unwrap_or_else(|e| {
+                        log::error!("Bad feature tag {}: {}", feature, e);
+                        std::process::exit(1);
+                    })
+                })
+                .collect(),
+        );
+    }
+    if let Some(ids) = matches.value_of("name-ids") {
+        options.name_ids = Some(parse_ranges(ids, 10));
+    }
+    if let Some(ids) = matches.value_of("name-languages") {
+        options.name_languages = Some(parse_ranges(ids, 10));
+    }
+
+    let mut infont = open_font(&matches);
+    if let Err(e) = subset(&mut infont, &options) {
+        log::error!("Could not subset font: {}", e);
+        std::process::exit(1);
+    }
+    save_font(infont, &matches);
+}
+
+/// Parses a comma separated list of numbers and ranges of numbers, which
+/// are hexadecimal if prefixed with `0x` or `U+`
+fn parse_ranges<T>(list: &str, radix: u32) -> BTreeSet<T>
+where
+    T: TryFrom<u32> + Ord,
+{
+    let parse = |number: &str| {
+        let (digits, radix) = match ["U+", "u+", "0x"]
+            .iter()
+            .find_map(|prefix| number.strip_prefix(prefix))
+        {
+            Some(digits) => (digits, 16),
+            None => (number, radix),
+        };
+        u32::from_str_radix(digits, radix).unwrap_or_else(|_| {
+            log::error!("Could not parse number {}", number);
+            std::process::exit(1);
+        })
+    };
+    let mut set = BTreeSet::new();
+    for item in list.split(',').filter(|item| !item.is_empty()) {
+        let (start, end) = match item.split_once('-') {
+            Some((start, end)) => (parse(start), parse(end)),
+            None => (parse(item), parse(item)),
+        };
+        for number in start..=end {
+            set.insert(T::try_from(number).unwrap_or_else(|_| {
+                log::error!("Number {} is out of range", number);
+                std::process::exit(1);
+            }));
+        }
+    }
+    set
+}
@@ -0,0 +1,82 @@
+use clap::{App, Arg};
+use fonttools::ttx;
+use fonttools::types::Tag;
+use fonttools_cli::{open_font, save_font};
+use std::io::{Read, Write};
+
+fn main() {
+    env_logger::init();
+    let matches = App::new("ttf-ttx")
+        .about("Converts a font to ttx XML, or ttx XML back to a font")
+        .arg(
+            Arg::from_usage("-t, --table=[TABLE]... 'Only dump the given tables'")
+                .number_of_values(1),
+        )
+        .arg(Arg::from_usage(
+            "-c, --compile 'Compile XML to a font (default if the input file ends in .ttx)'",
+        ))
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the input file to use")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("OUTPUT")
+                .help("Sets the output file to use")
+                .required(false),
+        )
+        .get_matches();
+
+    let compiling = matches.is_present("compile")
+        || matches
+            .value_of("INPUT")
+            .is_some_and(|input| input.to_lowercase().ends_with(".ttx"));
+
+    if compiling {
+        let mut xml = String::new();
+        if let Some(path) = matches.value_of("INPUT") {
+            xml = std::fs::read_to_string(path).expect("Could not read XML");
+        } else {
+            std::io::stdin()
+                .read_to_string(&mut xml)
+                .expect("Could not read XML");
+        }
+        match ttx::compile(&xml) {
+            Ok(font) => save_font(font, &matches),
+            Err(e) => {
+                log::error!("Could not compile font: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let infont = open_font(&matches);
+    let dumped = match matches.values_of("table") {
+        Some(tables) => {
+            let tags: Vec<Tag> = tables
+                .map(|table| {
+                    Tag::from_raw(format!("{:<4}", table)).unwrap_or_else(|_| {
+                        log::error!("Bad table tag {}", table);
+                        std::process::exit(1);
+                    })
+                })
+                .collect();
+            ttx::dump_tables(&infont, &tags)
+        }
+        None => ttx::dump(&infont),
+    };
+    let xml = match dumped {
+        Ok(xml) => xml,
+        Err(e) => {
+            log::error!("Could not dump font: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(path) = matches.value_of("OUTPUT") {
+        std::fs::write(path, xml)
+    } else {
+        std::io::stdout().write_all(xml.as_bytes())
+    }
+    .expect("Could not write XML");
+}
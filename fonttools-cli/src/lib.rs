@@ -7,14 +7,27 @@
 //! font is read from stdin. If you provide a second file name, it is understood
 //! as the output font; otherwise the font is written to stdout.
 //!
+//!  * `font-to-json` - Converts a font to JSON, for inspection or editing
 //!  * `fontcrunch` - A Rust port of https://github.com/googlefonts/fontcrunch
+//!  * `json-to-font` - Converts JSON produced by `font-to-json` back to a font
+//!  * `otf-subroutinize` - Subroutinizes the charstrings of a CFF or CFF2 font
 //!  * `ttf-add-minimal-dsig` - Adds a minimal DSIG table if one is not present
+//!  * `ttf-autohint` - Generates TrueType hinting instructions for a font
+//!  * `ttf-dump-features` - Decompiles the GSUB and GPOS tables of a font to a feature file
+//!  * `ttf-dump-instructions` - Disassembles the TrueType instructions of a font
 //!  * `ttf-fix-checksum` - Ensures TTF files have correct checksum
 //!  * `ttf-fix-non-hinted` - Adds a `gasp` and `prep` table which is set to smooth for all sizes
+//!  * `ttf-fix-vertical-metrics` - Makes the vertical metrics of a family of fonts consistent
 //!  * `ttf-flatten-components` - Flattens components
+//!  * `ttf-glyph-sheet` - Draws all glyphs of a font into an SVG or PDF proof sheet
+//!  * `ttf-hint-glyph` - Runs a glyph's TrueType instructions and prints the hinted outline
 //!  * `ttf-optimize-gvar` - Optimizes the gvar table by omitting points which can be inferred
+//!  * `ttf-proof` - Renders a line of text to a PNG or SVG image
+//!  * `ttf-rasterize-glyph` - Renders a glyph to a grayscale PNG or PGM image
 //!  * `ttf-remove-overlap` - Removes overlap from TTF files
 //!  * `ttf-rename-glyphs` - Renames glyphs to production names
+//!  * `ttf-subset` - Reduces a font to the glyphs needed for a set of characters
+//!  * `ttf-ttx` - Converts fonts to and from the XML format used by `fontTools`
 
 use clap::{App, Arg};
 use fonttools::font::Font;
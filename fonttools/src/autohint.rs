@@ -0,0 +1,756 @@
+//! Automatic TrueType hinting
+//!
+//! [`autohint`] analyses the outlines in a font's `glyf` table and
+//! generates instructions which align horizontal edges to the pixel grid,
+//! in the manner of ttfautohint: the heights of capitals, ascenders,
+//! x-height, baseline and descenders are measured for each script as
+//! "blue zones", and the widths of horizontal stems are measured from
+//! standard characters. These measurements are stored in the `cvt ` table
+//! and rounded by the `prep` program; each glyph's program then snaps its
+//! edges to the zones and links the edges of its stems using the standard
+//! widths. For variable fonts, the measurements are repeated at each
+//! master location to produce a `cvar` table.
+//!
+//! Only vertical (y-direction) hinting is performed, which is the approach
+//! taken by most modern hinting for grayscale and subpixel rendering.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::autohint::{autohint, AutohintOptions};
+//!
+//! let mut font = Font::load("Test.ttf").expect("Could not load font");
+//! autohint(&mut font, &AutohintOptions::default()).expect("Could not hint font");
+//! font.save("Test-hinted.ttf").expect("Could not save font");
+//! ```
+mod analysis;
+mod program;
+mod script;
+
+pub use script::Script;
+
+use crate::font::Font;
+use crate::hinting::tuple_scalar;
+use crate::instructions::InstructionError;
+use crate::tables;
+use crate::tables::cvar::{cvar, DeltaSet};
+use crate::tables::gasp::RangeGaspBehaviorFlags;
+use crate::tables::glyf::{ComponentFlags, Glyph};
+use crate::tables::gvar::gvar;
+use crate::tables::maxp::MaxpVariant;
+use analysis::{GlyphAnalysis, Outline};
+use otspec::types::Tag;
+use otspec::DeserializationError;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Options controlling the automatic hinter
+#[derive(Debug, Clone)]
+pub struct AutohintOptions {
+    /// Glyph programs are switched off above this size in pixels per em;
+    /// zero hints at all sizes.
+    pub hinting_limit: u16,
+    /// Whether to add a `gasp` table requesting grid-fitting and smoothing
+    /// at all sizes
+    pub add_gasp: bool,
+    /// The script used for glyphs which are not mapped to a character of a
+    /// supported script. By default, the most common script in the font is
+    /// used.
+    pub fallback_script: Option<Script>,
+}
+
+impl Default for AutohintOptions {
+    fn default() -> Self {
+        AutohintOptions {
+            hinting_limit: 200,
+            add_gasp: true,
+            fallback_script: None,
+        }
+    }
+}
+
+/// An error raised while autohinting a font
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutohintError {
+    /// A table needed for hinting was missing
+    MissingTable(Tag),
+    /// A table could not be read
+    Table(String),
+    /// A generated program could not be assembled
+    Instruction(InstructionError),
+}
+
+impl std::fmt::Display for AutohintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutohintError::MissingTable(tag) => write!(f, "missing {} table", tag),
+            AutohintError::Table(e) => write!(f, "could not read table: {}", e),
+            AutohintError::Instruction(e) => write!(f, "could not assemble program: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AutohintError {}
+
+impl From<DeserializationError> for AutohintError {
+    fn from(e: DeserializationError) -> Self {
+        AutohintError::Table(e.0)
+    }
+}
+
+impl From<InstructionError> for AutohintError {
+    fn from(e: InstructionError) -> Self {
+        AutohintError::Instruction(e)
+    }
+}
+
+/// An alignment zone, with the control values which hold it
+#[derive(Debug, Clone)]
+pub(crate) struct BlueZone {
+    pub top: bool,
+    pub reference: f64,
+    pub overshoot: f64,
+    pub reference_cvt: usize,
+    pub overshoot_cvt: usize,
+}
+
+/// The alignment zones and standard stem widths of a script
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScriptMetrics {
+    pub blue_zones: Vec<BlueZone>,
+    /// Widths and their control value indices
+    pub stem_widths: Vec<(f64, usize)>,
+}
+
+impl ScriptMetrics {
+    /// The control value of the standard width nearest to the given width,
+    /// if one is within 25% of it
+    pub fn closest_stem_width(&self, width: f64) -> Option<usize> {
+        self.stem_widths
+            .iter()
+            .filter(|(standard, _)| (standard - width).abs() <= standard / 4.0)
+            .min_by(|a, b| {
+                (a.0 - width)
+                    .abs()
+                    .partial_cmp(&(b.0 - width).abs())
+                    .unwrap()
+            })
+            .map(|&(_, cvt)| cvt)
+    }
+}
+
+/// A measured value, as the mean of point heights (or of distances between
+/// pairs of points) in particular glyphs. Keeping the points rather than
+/// the value lets the measurement be repeated on varied outlines.
+#[derive(Debug, Clone, Default)]
+struct Measurement(Vec<Term>);
+
+/// A glyph ID and a point, or a pair of points whose distance is measured
+type Term = (u16, usize, Option<usize>);
+
+/// The start, peak and end of a variation region
+type Region = (Vec<f32>, Vec<f32>, Vec<f32>);
+
+impl Measurement {
+    fn evaluate(&self, outlines: &HashMap<u16, Outline>) -> f64 {
+        let term = |&(gid, a, b): &Term| {
+            let points = &outlines[&gid].points;
+            match b {
+                Some(b) => points[b].1 - points[a].1,
+                None => points[a].1,
+            }
+        };
+        self.0.iter().map(term).sum::<f64>() / self.0.len() as f64
+    }
+}
+
+/// Reads the outlines of the glyphs which measurements refer to
+struct Measurer<'a> {
+    glyphs: &'a [Glyph],
+    mapping: &'a BTreeMap<u32, u16>,
+    upem: u16,
+    outlines: HashMap<u16, Outline>,
+}
+
+impl<'a> Measurer<'a> {
+    /// Returns the glyph mapped to a character, and its analysis, if it has
+    /// an outline
+    fn analyse(&mut self, character: char) -> Option<(u16, GlyphAnalysis)> {
+        let gid = *self.mapping.get(&(character as u32))?;
+        let glyph = self.glyphs.get(gid as usize)?;
+        if glyph.has_components() || glyph.contours.is_empty() {
+            return None;
+        }
+        let outline = self
+            .outlines
+            .entry(gid)
+            .or_insert_with(|| Outline::from_glyph(glyph));
+        Some((gid, GlyphAnalysis::new(outline, self.upem)))
+    }
+
+    /// Measures the alignment zones and stem widths of a script, as pairs
+    /// of (reference, overshoot) and a list of widths
+    fn measure(
+        &mut self,
+        script: Script,
+    ) -> (Vec<(bool, Measurement, Measurement)>, Vec<Measurement>) {
+        let mut zones = vec![];
+        let mut stems: Vec<(f64, Term)> = vec![];
+        let mut add_stems = |gid: u16, analysis: &GlyphAnalysis| {
+            for &(bottom, top) in &analysis.stems {
+                let (bottom, top) = (&analysis.edges[bottom], &analysis.edges[top]);
+                stems.push((top.y - bottom.y, (gid, bottom.anchor, Some(top.anchor))));
+            }
+        };
+        if let Some((gid, analysis)) = self.analyse(script.standard_character()) {
+            add_stems(gid, &analysis);
+        }
+        for spec in script.blue_zones() {
+            let mut flat = Measurement::default();
+            let mut round = Measurement::default();
+            for character in spec.characters.chars() {
+                let (gid, analysis) = match self.analyse(character) {
+                    Some(analysed) => analysed,
+                    None => continue,
+                };
+                if let Some(edge) = analysis.extreme_edge(spec.top) {
+                    let terms = if edge.round { &mut round } else { &mut flat };
+                    terms.0.push((gid, edge.anchor, None));
+                }
+                add_stems(gid, &analysis);
+            }
+            if flat.0.is_empty() && round.0.is_empty() {
+                continue;
+            }
+            let reference = if flat.0.is_empty() {
+                round.clone()
+            } else {
+                flat
+            };
+            let overshoot = if round.0.is_empty() {
+                reference.clone()
+            } else {
+                round
+            };
+            zones.push((spec.top, reference, overshoot));
+        }
+
+        // Cluster the widths within 10% of each other, keeping the cluster
+        // of the standard character first
+        let mut clusters: Vec<(f64, Measurement)> = vec![];
+        for (width, term) in stems {
+            let cluster = clusters
+                .iter_mut()
+                .find(|(mean, _)| (width - *mean).abs() <= *mean / 10.0);
+            match cluster {
+                Some((mean, measurement)) => {
+                    let count = measurement.0.len() as f64;
+                    *mean = (*mean * count + width) / (count + 1.0);
+                    measurement.0.push(term);
+                }
+                None => clusters.push((width, Measurement(vec![term]))),
+            }
+        }
+        if clusters.len() > 1 {
+            clusters[1..].sort_by_key(|(_, measurement)| std::cmp::Reverse(measurement.0.len()));
+        }
+        let widths = clusters.into_iter().take(3).map(|(_, m)| m).collect();
+        (zones, widths)
+    }
+}
+
+/// Returns the outlines of the given glyphs at a location
+fn varied_outlines(
+    outlines: &HashMap<u16, Outline>,
+    gvar: &gvar,
+    location: &[f32],
+) -> HashMap<u16, Outline> {
+    let mut varied = outlines.clone();
+    for (gid, outline) in varied.iter_mut() {
+        let variations = gvar.variations.get(*gid as usize).and_then(|v| v.as_ref());
+        for deltaset in variations.iter().flat_map(|v| v.deltasets.iter()) {
+            let scalar = tuple_scalar(location, &deltaset.start, &deltaset.peak, &deltaset.end);
+            if scalar != 0.0 {
+                outline.add_deltas(&deltaset.deltas, scalar as f64);
+            }
+        }
+    }
+    varied
+}
+
+/// Builds a `cvar` table by repeating the measurements at the peak
+/// of each region used by the measured glyphs.
+fn build_cvar(
+    measurements: &[Measurement],
+    outlines: &HashMap<u16, Outline>,
+    gvar: &gvar,
+) -> Option<cvar> {
+    let mut regions: Vec<Region> = vec![];
+    for gid in outlines.keys() {
+        let variations = gvar.variations.get(*gid as usize).and_then(|v| v.as_ref());
+        for deltaset in variations.iter().flat_map(|v| v.deltasets.iter()) {
+            let region = (
+                deltaset.start.clone(),
+                deltaset.peak.clone(),
+                deltaset.end.clone(),
+            );
+            if !regions.contains(&region) {
+                regions.push(region);
+            }
+        }
+    }
+    // Solve the simpler regions first, so that the deltas of intermediate
+    // and corner regions account for them
+    regions.sort_by_key(|(_, peak, _)| peak.iter().filter(|&&x| x != 0.0).count());
+
+    let default: Vec<f64> = measurements.iter().map(|m| m.evaluate(outlines)).collect();
+    let mut solved: Vec<(&Region, Vec<f64>)> = vec![];
+    for region in &regions {
+        let (_, peak, _) = region;
+        let varied = varied_outlines(outlines, gvar, peak);
+        let mut deltas: Vec<f64> = measurements
+            .iter()
+            .zip(default.iter())
+            .map(|(m, default)| m.evaluate(&varied) - default)
+            .collect();
+        for ((start, other_peak, end), other_deltas) in &solved {
+            let scalar = tuple_scalar(peak, start, other_peak, end) as f64;
+            for (delta, other) in deltas.iter_mut().zip(other_deltas.iter()) {
+                *delta -= scalar * other;
+            }
+        }
+        solved.push((region, deltas));
+    }
+
+    let deltasets: Vec<DeltaSet> = solved
+        .into_iter()
+        .filter(|(_, deltas)| deltas.iter().any(|d| d.round() != 0.0))
+        .map(|((start, peak, end), deltas)| DeltaSet {
+            peak: peak.clone(),
+            start: start.clone(),
+            end: end.clone(),
+            deltas: deltas.iter().map(|d| d.round() as i16).collect(),
+        })
+        .collect();
+    if deltasets.is_empty() {
+        None
+    } else {
+        Some(cvar { deltasets })
+    }
+}
+
+/// Generates TrueType instructions for the glyphs of a font.
+///
+/// This replaces the `fpgm`, `prep`, `cvt ` and `cvar` tables and the
+/// instructions of every glyph, and updates the hinting limits in `maxp`.
+pub fn autohint(font: &mut Font, options: &AutohintOptions) -> Result<(), AutohintError> {
+    let tables = &font.tables;
+    let upem = tables
+        .head()?
+        .ok_or(AutohintError::MissingTable(tables::head::TAG))?
+        .unitsPerEm;
+    let mut glyf = tables
+        .glyf()?
+        .ok_or(AutohintError::MissingTable(tables::glyf::TAG))?
+        .into_owned();
+    let mut maxp = tables
+        .maxp()?
+        .ok_or(AutohintError::MissingTable(tables::maxp::TAG))?
+        .into_owned();
+    let mapping = tables
+        .cmap()?
+        .and_then(|cmap| cmap.get_best_mapping().cloned())
+        .unwrap_or_default();
+    let gvar = if tables.fvar()?.is_some() {
+        tables.gvar()?
+    } else {
+        None
+    };
+
+    // Assign each glyph a script from its first character
+    let mut glyph_scripts: Vec<Option<Script>> = vec![None; glyf.glyphs.len()];
+    for (&codepoint, &gid) in &mapping {
+        if let Some(slot) = glyph_scripts.get_mut(gid as usize) {
+            if slot.is_none() {
+                *slot = Script::of(codepoint);
+            }
+        }
+    }
+    let mut counts: BTreeMap<Script, usize> = BTreeMap::new();
+    for script in glyph_scripts.iter().flatten() {
+        *counts.entry(*script).or_default() += 1;
+    }
+    let fallback = options.fallback_script.unwrap_or_else(|| {
+        counts
+            .iter()
+            .max_by_key(|(_, &count)| count)
+            .map_or(Script::Latin, |(&script, _)| script)
+    });
+    let scripts: BTreeSet<Script> = glyph_scripts
+        .iter()
+        .map(|s| s.unwrap_or(fallback))
+        .collect();
+
+    // Measure the scripts and lay out the control values
+    let mut measurer = Measurer {
+        glyphs: &glyf.glyphs,
+        mapping: &mapping,
+        upem,
+        outlines: HashMap::new(),
+    };
+    let mut measurements: Vec<Measurement> = vec![];
+    let mut blue_cvts = vec![];
+    let mut width_cvts = vec![];
+    let mut metrics: HashMap<Script, ScriptMetrics> = HashMap::new();
+    for &script in &scripts {
+        let (zones, widths) = measurer.measure(script);
+        let script_metrics = metrics.entry(script).or_default();
+        for (top, reference, overshoot) in zones {
+            let reference_cvt = measurements.len();
+            blue_cvts.push(reference_cvt);
+            script_metrics.blue_zones.push(BlueZone {
+                top,
+                reference: reference.evaluate(&measurer.outlines),
+                overshoot: overshoot.evaluate(&measurer.outlines),
+                reference_cvt,
+                overshoot_cvt: reference_cvt + 1,
+            });
+            measurements.push(reference);
+            measurements.push(overshoot);
+        }
+        for width in widths {
+            width_cvts.push(measurements.len());
+            script_metrics
+                .stem_widths
+                .push((width.evaluate(&measurer.outlines), measurements.len()));
+            measurements.push(width);
+        }
+    }
+    let outlines = measurer.outlines;
+    let cvt: Vec<i16> = measurements
+        .iter()
+        .map(|m| m.evaluate(&outlines).round() as i16)
+        .collect();
+
+    // Generate the glyph programs
+    let mut max_stack = blue_cvts.len().max(width_cvts.len()) + 8;
+    let mut max_size = 0;
+    for (gid, glyph) in glyf.glyphs.iter_mut().enumerate() {
+        glyph.instructions = vec![];
+        if glyph.has_components() {
+            for component in glyph.components.iter_mut() {
+                if component.match_points.is_none() {
+                    component.flags |= ComponentFlags::ROUND_XY_TO_GRID;
+                }
+            }
+            continue;
+        }
+        if glyph.contours.is_empty() {
+            continue;
+        }
+        let script = glyph_scripts[gid].unwrap_or(fallback);
+        let analysis = GlyphAnalysis::new(&Outline::from_glyph(glyph), upem);
+        if let Some((bytecode, depth)) = program::glyph_program(&analysis, &metrics[&script], upem)?
+        {
+            max_stack = max_stack.max(depth);
+            max_size = max_size.max(bytecode.len());
+            glyph.instructions = bytecode;
+        }
+    }
+
+    let fpgm = program::font_program();
+    let prep = program::control_value_program(&blue_cvts, &width_cvts, options.hinting_limit)?;
+    if let MaxpVariant::Maxp05(_) = maxp.table {
+        let mut upgraded = glyf.as_maxp10();
+        upgraded.set_num_glyphs(maxp.num_glyphs());
+        maxp = upgraded;
+    }
+    if let MaxpVariant::Maxp10(m) = &mut maxp.table {
+        m.maxZones = 1;
+        m.maxTwilightPoints = 0;
+        m.maxStorage = 0;
+        m.maxFunctionDefs = program::FUNCTION_COUNT;
+        m.maxInstructionDefs = 0;
+        m.maxStackElements = max_stack as u16;
+        m.maxSizeOfInstructions = max_size.max(fpgm.len()).max(prep.len()) as u16;
+    }
+
+    let cvar = gvar
+        .as_ref()
+        .and_then(|gvar| build_cvar(&measurements, &outlines, gvar));
+    let tables = &mut font.tables;
+    tables.insert(glyf);
+    tables.insert(maxp);
+    tables.insert(tables::cvt::cvt(cvt));
+    tables.insert(tables::fpgm::fpgm(fpgm));
+    tables.insert(tables::prep::prep(prep));
+    match cvar {
+        Some(cvar) => tables.insert(cvar),
+        None => {
+            tables.remove(tables::cvar::TAG);
+        }
+    }
+    if options.add_gasp {
+        tables.insert(tables::gasp::gasp {
+            version: 1,
+            gaspRanges: vec![tables::gasp::GaspRecord {
+                rangeMaxPPEM: 0xFFFF,
+                rangeGaspBehavior: RangeGaspBehaviorFlags::GASP_GRIDFIT
+                    | RangeGaspBehaviorFlags::GASP_DOGRAY
+                    | RangeGaspBehaviorFlags::GASP_SYMMETRIC_GRIDFIT
+                    | RangeGaspBehaviorFlags::GASP_SYMMETRIC_SMOOTHING,
+            }],
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hinting::Hinter;
+    use crate::tables::glyf::Point;
+    use crate::tag;
+
+    fn contour(points: &[(i16, i16)], on_curve: &[bool]) -> Vec<Point> {
+        points
+            .iter()
+            .zip(on_curve.iter())
+            .map(|(&(x, y), &on_curve)| Point { x, y, on_curve })
+            .collect()
+    }
+
+    fn glyph(contours: Vec<Vec<Point>>) -> Glyph {
+        Glyph {
+            xMin: 0,
+            xMax: 0,
+            yMin: 0,
+            yMax: 0,
+            contours,
+            instructions: vec![],
+            components: vec![],
+            overlap: false,
+        }
+    }
+
+    /// A font with .notdef, "H", "o" and "x" glyphs
+    fn test_font() -> Font {
+        let h = contour(
+            &[
+                (0, 0),
+                (0, 700),
+                (100, 700),
+                (100, 380),
+                (400, 380),
+                (400, 700),
+                (500, 700),
+                (500, 0),
+                (400, 0),
+                (400, 300),
+                (100, 300),
+                (100, 0),
+            ],
+            &[true; 12],
+        );
+        let alternating = [true, false, true, false, true, false, true, false];
+        let o_outer = contour(
+            &[
+                (250, -10),
+                (100, -10),
+                (0, 250),
+                (100, 510),
+                (250, 510),
+                (400, 510),
+                (500, 250),
+                (400, -10),
+            ],
+            &alternating,
+        );
+        let o_inner = contour(
+            &[
+                (250, 70),
+                (350, 70),
+                (420, 250),
+                (350, 430),
+                (250, 430),
+                (150, 430),
+                (80, 250),
+                (150, 70),
+            ],
+            &alternating,
+        );
+        let x = contour(&[(0, 0), (0, 500), (500, 500), (500, 0)], &[true; 4]);
+        let mut glyf = tables::glyf::glyf {
+            glyphs: vec![
+                glyph(vec![]),
+                glyph(vec![h]),
+                glyph(vec![o_outer, o_inner]),
+                glyph(vec![x]),
+            ],
+        };
+        glyf.recalc_bounds();
+
+        let mut font = Font::new(crate::font::SfntVersion::TrueType);
+        font.tables
+            .insert(tables::head::new(1.0, 1000, 0, -10, 500, 700));
+        font.tables.insert(tables::hhea::hhea {
+            majorVersion: 1,
+            minorVersion: 0,
+            ascender: 800,
+            descender: -200,
+            lineGap: 0,
+            advanceWidthMax: 600,
+            minLeftSideBearing: 0,
+            minRightSideBearing: 100,
+            xMaxExtent: 500,
+            caretSlopeRise: 1,
+            caretSlopeRun: 0,
+            caretOffset: 0,
+            reserved0: 0,
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+            metricDataFormat: 0,
+            numberOfHMetrics: 4,
+        });
+        font.tables.insert(tables::maxp::maxp::new05(4));
+        font.tables.insert(tables::hmtx::hmtx {
+            metrics: vec![
+                tables::hmtx::Metric {
+                    advanceWidth: 600,
+                    lsb: 0,
+                };
+                4
+            ],
+        });
+        font.tables.insert(glyf);
+        let mapping = [('H', 1), ('o', 2), ('x', 3)]
+            .iter()
+            .map(|&(c, gid)| (c as u32, gid))
+            .collect();
+        font.tables.insert(tables::cmap::cmap {
+            subtables: vec![tables::cmap::CmapSubtable {
+                format: 4,
+                platformID: 3,
+                encodingID: 1,
+                languageID: 0,
+                mapping,
+                uvs_mapping: None,
+            }],
+        });
+        font
+    }
+
+    fn heights(hinter: &mut Hinter, gid: u16) -> Vec<Vec<i32>> {
+        hinter
+            .hint_glyph(gid)
+            .unwrap()
+            .contours
+            .iter()
+            .map(|c| c.iter().map(|p| p.y).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_autohint() {
+        let mut font = test_font();
+        autohint(&mut font, &AutohintOptions::default()).unwrap();
+        // Cap height, baseline, x-height and its overshoot, baseline and its
+        // undershoot, then the stem width of "o"
+        let cvt = font.tables.cvt().unwrap().unwrap();
+        assert_eq!(cvt.0, vec![700, 700, 0, 0, 500, 510, 0, -10, 80]);
+        let maxp = font.tables.maxp().unwrap().unwrap();
+        match &maxp.table {
+            MaxpVariant::Maxp10(m) => {
+                assert_eq!(m.maxFunctionDefs, 2);
+                assert_eq!(m.maxZones, 1);
+                assert!(m.maxStackElements > 0);
+            }
+            _ => panic!("maxp was not upgraded"),
+        }
+        assert!(font.tables.contains(&tag!("gasp")));
+        assert!(!font.tables.contains(&tag!("cvar")));
+
+        // At 11ppem, the cap height of 7.7 pixels rounds up to 8; the
+        // x-height of 5.5 pixels rounds to 6, and the overshoot of "o" is
+        // suppressed
+        let mut hinter = Hinter::new(&font, 11, &[]).unwrap();
+        let h = heights(&mut hinter, 1);
+        assert_eq!(h[0].iter().max(), Some(&512));
+        assert_eq!(h[0].iter().min(), Some(&0));
+        // The crossbar is a whole pixel thick, on the grid
+        assert_eq!((h[0][10], h[0][3]), (192, 256));
+        let o = heights(&mut hinter, 2);
+        assert_eq!(o[0].iter().max(), Some(&384));
+        assert_eq!(o[0].iter().min(), Some(&0));
+        assert_eq!(o[1].iter().min(), Some(&64));
+        assert_eq!(o[1].iter().max(), Some(&320));
+        assert_eq!(heights(&mut hinter, 3), vec![vec![0, 384, 384, 0]]);
+    }
+
+    #[test]
+    fn test_hinting_limit() {
+        let mut font = test_font();
+        let options = AutohintOptions {
+            hinting_limit: 10,
+            add_gasp: false,
+            ..Default::default()
+        };
+        autohint(&mut font, &options).unwrap();
+        assert!(!font.tables.contains(&tag!("gasp")));
+        let mut hinter = Hinter::new(&font, 11, &[]).unwrap();
+        // 700 units at 11ppem is 492.8 in 26.6
+        assert_eq!(heights(&mut hinter, 1)[0].iter().max(), Some(&493));
+    }
+
+    #[test]
+    fn test_cvar() {
+        let mut font = test_font();
+        font.tables.insert(tables::fvar::fvar {
+            axes: vec![tables::fvar::VariationAxisRecord {
+                axisTag: tag!("wght"),
+                minValue: 400.0,
+                defaultValue: 400.0,
+                maxValue: 700.0,
+                flags: 0,
+                axisNameID: 256,
+            }],
+            instances: vec![],
+        });
+        // The x-height of "x" rises by 100 units at the heaviest weight
+        let deltaset = tables::gvar::DeltaSet {
+            peak: vec![1.0],
+            start: vec![0.0],
+            end: vec![1.0],
+            deltas: vec![
+                (0, 0),
+                (0, 100),
+                (0, 100),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+            ],
+        };
+        font.tables.insert(gvar {
+            variations: vec![
+                None,
+                None,
+                None,
+                Some(tables::gvar::GlyphVariationData {
+                    deltasets: vec![deltaset],
+                }),
+            ],
+        });
+        autohint(&mut font, &AutohintOptions::default()).unwrap();
+        let cvar = font.tables.cvar().unwrap().unwrap();
+        assert_eq!(cvar.deltasets.len(), 1);
+        assert_eq!(cvar.deltasets[0].deltas, vec![0, 0, 0, 0, 100, 0, 0, 0, 0]);
+
+        let mut hinter = Hinter::new(&font, 11, &[1.0]).unwrap();
+        // 600 units is 6.6 pixels, which rounds to 7
+        assert_eq!(hinter.cvt()[4], 448);
+        assert_eq!(heights(&mut hinter, 3), vec![vec![0, 448, 448, 0]]);
+        let hinter = Hinter::new(&font, 11, &[0.0]).unwrap();
+        assert_eq!(hinter.cvt()[4], 384);
+    }
+}
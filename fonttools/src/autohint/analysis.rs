@@ -0,0 +1,382 @@
+use crate::tables::glyf::Glyph;
+
+/// A glyph outline in font units, with variations applied
+#[derive(Debug, Clone)]
+pub(crate) struct Outline {
+    pub points: Vec<(f64, f64)>,
+    pub on_curve: Vec<bool>,
+    pub contour_ends: Vec<usize>,
+}
+
+impl Outline {
+    pub fn from_glyph(glyph: &Glyph) -> Self {
+        Outline {
+            points: glyph
+                .contours
+                .iter()
+                .flatten()
+                .map(|pt| (pt.x as f64, pt.y as f64))
+                .collect(),
+            on_curve: glyph
+                .contours
+                .iter()
+                .flatten()
+                .map(|pt| pt.on_curve)
+                .collect(),
+            contour_ends: glyph
+                .contours
+                .iter()
+                .scan(0, |count, contour| {
+                    *count += contour.len();
+                    Some(*count - 1)
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds a scaled set of `gvar` deltas to the points
+    pub fn add_deltas(&mut self, deltas: &[(i16, i16)], scalar: f64) {
+        for (point, delta) in self.points.iter_mut().zip(deltas.iter()) {
+            point.0 += scalar * delta.0 as f64;
+            point.1 += scalar * delta.1 as f64;
+        }
+    }
+
+    /// Twice the signed area of the outline; negative if the outer contours
+    /// are clockwise, as TrueType expects.
+    fn signed_area(&self) -> f64 {
+        let mut area = 0.0;
+        let mut start = 0;
+        for &end in &self.contour_ends {
+            for i in start..=end {
+                let j = if i == end { start } else { i + 1 };
+                let (a, b) = (self.points[i], self.points[j]);
+                area += a.0 * b.1 - b.0 * a.1;
+            }
+            start = end + 1;
+        }
+        area
+    }
+}
+
+/// A run of consecutive points at (nearly) the same height
+#[derive(Debug, Clone)]
+pub(crate) struct Segment {
+    pub points: Vec<usize>,
+    pub y: f64,
+    pub min_x: f64,
+    pub max_x: f64,
+    /// Whether the ink is below this segment
+    pub top: bool,
+    /// Whether this is the extremum of a curve rather than a straight line
+    pub round: bool,
+}
+
+/// A set of segments at the same height, which are hinted together
+#[derive(Debug, Clone)]
+pub(crate) struct Edge {
+    pub y: f64,
+    /// Whether the ink is below this edge
+    pub top: bool,
+    /// Whether all of the segments are round
+    pub round: bool,
+    /// The point which is positioned by the instructions; the other points
+    /// are aligned to it
+    pub anchor: usize,
+    pub points: Vec<usize>,
+    pub segments: Vec<Segment>,
+}
+
+impl Edge {
+    fn overlaps(&self, other: &Edge) -> bool {
+        self.segments.iter().any(|a| {
+            other
+                .segments
+                .iter()
+                .any(|b| a.min_x.max(b.min_x) < a.max_x.min(b.max_x))
+        })
+    }
+}
+
+/// The horizontal edges of a glyph, and the stems formed by pairs of them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GlyphAnalysis {
+    /// Edges, sorted from bottom to top
+    pub edges: Vec<Edge>,
+    /// Pairs of (bottom edge, top edge) indices
+    pub stems: Vec<(usize, usize)>,
+}
+
+impl GlyphAnalysis {
+    /// Finds the horizontal edges and stems of an outline
+    pub fn new(outline: &Outline, upem: u16) -> Self {
+        let upem = upem as f64;
+        let clockwise = outline.signed_area() <= 0.0;
+        let mut segments = vec![];
+        let mut start = 0;
+        for &end in &outline.contour_ends {
+            find_segments(outline, start, end, upem, clockwise, &mut segments);
+            start = end + 1;
+        }
+        let edges = group_segments(outline, segments, upem / 100.0);
+        let stems = find_stems(&edges, upem / 5.0);
+        GlyphAnalysis { edges, stems }
+    }
+
+    /// The highest top edge or lowest bottom edge
+    pub fn extreme_edge(&self, top: bool) -> Option<&Edge> {
+        if top {
+            self.edges.iter().rev().find(|e| e.top)
+        } else {
+            self.edges.iter().find(|e| !e.top)
+        }
+    }
+}
+
+fn find_segments(
+    outline: &Outline,
+    start: usize,
+    end: usize,
+    upem: f64,
+    clockwise: bool,
+    segments: &mut Vec<Segment>,
+) {
+    let count = end + 1 - start;
+    if count < 3 {
+        return;
+    }
+    let tolerance = (upem / 1000.0).max(1.0);
+    let min_length = upem / 40.0;
+    let next = |i: usize| if i == end { start } else { i + 1 };
+    let prev = |i: usize| if i == start { end } else { i - 1 };
+    let y = |i: usize| outline.points[i].1;
+    let x = |i: usize| outline.points[i].0;
+    let flat = |i: usize| (y(next(i)) - y(i)).abs() <= tolerance;
+
+    // Start at a point which does not continue a flat run
+    let first = match (start..=end).find(|&i| !flat(prev(i))) {
+        Some(first) => first,
+        None => return, // The contour is flat
+    };
+    let mut i = first;
+    loop {
+        let mut run = vec![i];
+        while flat(run[run.len() - 1]) {
+            run.push(next(run[run.len() - 1]));
+        }
+        let last = run[run.len() - 1];
+        let height = run.iter().map(|&p| y(p)).sum::<f64>() / run.len() as f64;
+        let before = y(prev(run[0])) - height;
+        let after = y(next(last)) - height;
+        let extremum = before.signum() == after.signum() && before != 0.0;
+        let min_x = run.iter().map(|&p| x(p)).fold(f64::INFINITY, f64::min);
+        let max_x = run.iter().map(|&p| x(p)).fold(f64::NEG_INFINITY, f64::max);
+        if max_x - min_x >= min_length || extremum {
+            let mut dx = x(last) - x(run[0]);
+            if dx == 0.0 {
+                dx = x(next(last)) - x(prev(run[0]));
+            }
+            let round = run.len() == 1 || run.iter().any(|&p| !outline.on_curve[p]);
+            if dx != 0.0 {
+                segments.push(Segment {
+                    points: run,
+                    y: height,
+                    min_x,
+                    max_x,
+                    top: (dx > 0.0) == clockwise,
+                    round,
+                });
+            }
+        }
+        // Runs never wrap past `first`, as the link into it is not flat
+        i = next(last);
+        if i == first {
+            break;
+        }
+    }
+}
+
+fn group_segments(outline: &Outline, mut segments: Vec<Segment>, tolerance: f64) -> Vec<Edge> {
+    segments.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap());
+    let mut edges: Vec<Edge> = vec![];
+    for segment in segments {
+        let existing = edges
+            .iter_mut()
+            .rev()
+            .take_while(|e| segment.y - e.y <= tolerance)
+            .find(|e| e.top == segment.top);
+        match existing {
+            Some(edge) => {
+                edge.round &= segment.round;
+                edge.points.extend(segment.points.iter());
+                edge.segments.push(segment);
+            }
+            None => edges.push(Edge {
+                y: segment.y,
+                top: segment.top,
+                round: segment.round,
+                anchor: 0,
+                points: segment.points.clone(),
+                segments: vec![segment],
+            }),
+        }
+    }
+    for edge in edges.iter_mut() {
+        // Anchor on the on-curve point of the longest segment closest to
+        // the edge's height
+        let longest = edge
+            .segments
+            .iter()
+            .max_by(|a, b| {
+                (a.max_x - a.min_x)
+                    .partial_cmp(&(b.max_x - b.min_x))
+                    .unwrap()
+            })
+            .unwrap();
+        edge.y = longest.y;
+        let candidates: Vec<usize> = if longest.points.iter().any(|&p| outline.on_curve[p]) {
+            longest
+                .points
+                .iter()
+                .copied()
+                .filter(|&p| outline.on_curve[p])
+                .collect()
+        } else {
+            longest.points.clone()
+        };
+        let y = edge.y;
+        edge.anchor = *candidates
+            .iter()
+            .min_by(|&&a, &&b| {
+                (outline.points[a].1 - y)
+                    .abs()
+                    .partial_cmp(&(outline.points[b].1 - y).abs())
+                    .unwrap()
+            })
+            .unwrap();
+        edge.points.sort_unstable();
+        edge.points.dedup();
+    }
+    edges.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap());
+    edges
+}
+
+/// Pairs each bottom edge with the nearest top edge above it, if they
+/// are each other's nearest partner
+fn find_stems(edges: &[Edge], max_width: f64) -> Vec<(usize, usize)> {
+    let partner_above = |bottom: usize| {
+        (bottom + 1..edges.len()).find(|&top| {
+            edges[top].top
+                && edges[top].y - edges[bottom].y <= max_width
+                && edges[bottom].overlaps(&edges[top])
+        })
+    };
+    let partner_below = |top: usize| {
+        (0..top).rev().find(|&bottom| {
+            !edges[bottom].top
+                && edges[top].y - edges[bottom].y <= max_width
+                && edges[bottom].overlaps(&edges[top])
+        })
+    };
+    (0..edges.len())
+        .filter(|&bottom| !edges[bottom].top)
+        .filter_map(|bottom| {
+            partner_above(bottom)
+                .filter(|&top| partner_below(top) == Some(bottom))
+                .map(|top| (bottom, top))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An "H"-like shape drawn clockwise: two vertical stems joined by a
+    /// crossbar from y=300 to y=380
+    fn h_outline() -> Outline {
+        let points = vec![
+            (0.0, 0.0),
+            (0.0, 700.0),
+            (100.0, 700.0),
+            (100.0, 380.0),
+            (400.0, 380.0),
+            (400.0, 700.0),
+            (500.0, 700.0),
+            (500.0, 0.0),
+            (400.0, 0.0),
+            (400.0, 300.0),
+            (100.0, 300.0),
+            (100.0, 0.0),
+        ];
+        Outline {
+            on_curve: vec![true; points.len()],
+            contour_ends: vec![points.len() - 1],
+            points,
+        }
+    }
+
+    #[test]
+    fn test_edges_and_stems() {
+        let analysis = GlyphAnalysis::new(&h_outline(), 1000);
+        let edges: Vec<(f64, bool)> = analysis.edges.iter().map(|e| (e.y, e.top)).collect();
+        assert_eq!(
+            edges,
+            vec![(0.0, false), (300.0, false), (380.0, true), (700.0, true)]
+        );
+        // The two baseline segments are combined into one edge
+        assert_eq!(analysis.edges[0].points, vec![0, 7, 8, 11]);
+        assert_eq!(analysis.edges[3].points, vec![1, 2, 5, 6]);
+        // Only the crossbar is a horizontal stem
+        assert_eq!(analysis.stems, vec![(1, 2)]);
+        assert_eq!(analysis.extreme_edge(true).unwrap().y, 700.0);
+        assert_eq!(analysis.extreme_edge(false).unwrap().y, 0.0);
+    }
+
+    #[test]
+    fn test_orientation() {
+        // The same shape drawn counter-clockwise gives the same edges
+        let mut outline = h_outline();
+        outline.points.reverse();
+        let analysis = GlyphAnalysis::new(&outline, 1000);
+        let edges: Vec<(f64, bool)> = analysis.edges.iter().map(|e| (e.y, e.top)).collect();
+        assert_eq!(
+            edges,
+            vec![(0.0, false), (300.0, false), (380.0, true), (700.0, true)]
+        );
+        assert_eq!(analysis.stems, vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_round_extrema() {
+        // A diamond-ish "o" with flat off-curve tangents at top and bottom
+        let points = vec![
+            (250.0, -10.0),
+            (100.0, -10.0),
+            (0.0, 250.0),
+            (100.0, 510.0),
+            (250.0, 510.0),
+            (400.0, 510.0),
+            (500.0, 250.0),
+            (400.0, -10.0),
+        ];
+        let on_curve = vec![true, false, true, false, true, false, true, false];
+        let mut outline = Outline {
+            on_curve,
+            contour_ends: vec![7],
+            points,
+        };
+        // Make it clockwise
+        outline.points.reverse();
+        outline.on_curve.reverse();
+        let analysis = GlyphAnalysis::new(&outline, 1000);
+        assert_eq!(analysis.edges.len(), 2);
+        assert!(analysis.edges.iter().all(|e| e.round));
+        assert_eq!(analysis.edges[0].y, -10.0);
+        assert!(!analysis.edges[0].top);
+        assert!(analysis.edges[1].top);
+        let anchor = analysis.edges[1].anchor;
+        assert!(outline.on_curve[anchor]);
+        assert_eq!(outline.points[anchor], (250.0, 510.0));
+    }
+}
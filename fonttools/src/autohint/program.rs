@@ -0,0 +1,301 @@
+use super::analysis::GlyphAnalysis;
+use super::ScriptMetrics;
+use crate::instructions::{assemble, from_text, push, Instruction, InstructionError};
+
+/// Function 0 rounds an alignment zone: it takes the index of the zone's
+/// reference control value, which is followed by its overshoot. The
+/// overshoot is kept a whole number of pixels from the rounded reference,
+/// and so is suppressed at small sizes.
+const ROUND_BLUE_ZONE: i32 = 0;
+/// Function 1 rounds a stem width control value to at least one pixel.
+const ROUND_STEM_WIDTH: i32 = 1;
+
+const FONT_PROGRAM: &str = "
+PUSH[ ] 0
+FDEF[ ]
+  DUP[ ]
+  PUSH[ ] 1
+  ADD[ ]
+  DUP[ ]
+  RCVT[ ]
+  PUSH[ ] 3
+  CINDEX[ ]
+  RCVT[ ]
+  SUB[ ]
+  RDTG[ ]
+  ROUND[00]
+  RTG[ ]
+  PUSH[ ] 3
+  CINDEX[ ]
+  RCVT[ ]
+  ROUND[00]
+  ADD[ ]
+  WCVTP[ ]
+  DUP[ ]
+  RCVT[ ]
+  ROUND[00]
+  WCVTP[ ]
+ENDF[ ]
+PUSH[ ] 1
+FDEF[ ]
+  DUP[ ]
+  RCVT[ ]
+  ROUND[00]
+  PUSH[ ] 64
+  MAX[ ]
+  WCVTP[ ]
+ENDF[ ]
+";
+
+/// The number of functions defined by the font program
+pub(crate) const FUNCTION_COUNT: u16 = 2;
+
+// Opcodes used in glyph programs
+const SVTCA_Y: u8 = 0x00;
+const SRP0: u8 = 0x10;
+const SRP1: u8 = 0x11;
+const SRP2: u8 = 0x12;
+const SLOOP: u8 = 0x17;
+const MDAP_ROUND: u8 = 0x2F;
+const IUP_Y: u8 = 0x30;
+const IP: u8 = 0x39;
+const ALIGNRP: u8 = 0x3C;
+const MIAP_ROUND: u8 = 0x3F;
+/// MDRP keeping the original distance
+const MDRP: u8 = 0xC0;
+/// MDRP rounding the distance
+const MDRP_ROUND: u8 = 0xC4;
+/// MDRP rounding the distance and keeping the minimum distance
+const MDRP_STEM: u8 = 0xCC;
+/// MIRP rounding the distance and keeping the minimum distance
+const MIRP_STEM: u8 = 0xEC;
+
+pub(crate) fn font_program() -> Vec<u8> {
+    assemble(&from_text(FONT_PROGRAM).unwrap()).unwrap()
+}
+
+/// Builds the control value program, which rounds the alignment zones and
+/// stem widths, turns on dropout control and disables hinting above the
+/// given size (unless it is zero).
+pub(crate) fn control_value_program(
+    blue_zones: &[usize],
+    stem_widths: &[usize],
+    hinting_limit: u16,
+) -> Result<Vec<u8>, InstructionError> {
+    let mut instructions = vec![];
+    if hinting_limit > 0 {
+        instructions.extend(from_text(&format!(
+            "MPPEM[ ] PUSH[ ] {} GT[ ] IF[ ] PUSH[ ] 1 1 INSTCTRL[ ] EIF[ ]",
+            hinting_limit
+        ))?);
+    }
+    instructions.extend(from_text("PUSH[ ] 511 SCANCTRL[ ] PUSH[ ] 4 SCANTYPE[ ]")?);
+    for (indices, function) in [
+        (blue_zones, ROUND_BLUE_ZONE),
+        (stem_widths, ROUND_STEM_WIDTH),
+    ] {
+        if indices.is_empty() {
+            continue;
+        }
+        let mut args: Vec<i32> = indices.iter().map(|&i| i as i32).collect();
+        args.push(indices.len() as i32);
+        args.push(function);
+        instructions.extend(push(&args));
+        instructions.push(Instruction::new(0x2A)); // LOOPCALL
+    }
+    assemble(&instructions)
+}
+
+/// Collects instructions whose arguments are all known in advance, so
+/// that they can be pushed at once at the start of the program.
+#[derive(Default)]
+struct ProgramBuilder {
+    /// Opcodes, and the arguments they pop in the order they pop them
+    operations: Vec<(u8, Vec<i32>)>,
+}
+
+impl ProgramBuilder {
+    fn op(&mut self, opcode: u8, args: &[usize]) {
+        self.operations
+            .push((opcode, args.iter().map(|&a| a as i32).collect()));
+    }
+
+    /// Returns the bytecode and the number of stack elements it needs
+    fn build(self) -> Result<(Vec<u8>, usize), InstructionError> {
+        let mut args: Vec<i32> = self
+            .operations
+            .iter()
+            .flat_map(|(_, args)| args.iter().copied())
+            .collect();
+        args.reverse();
+        let depth = args.len();
+        let mut instructions = push(&args);
+        instructions.extend(
+            self.operations
+                .iter()
+                .map(|(opcode, _)| Instruction::new(*opcode)),
+        );
+        Ok((assemble(&instructions)?, depth))
+    }
+}
+
+/// Builds the program of a glyph, which positions its horizontal edges
+/// vertically. Returns the bytecode and the number of stack elements it
+/// needs, or `None` if the glyph has nothing to hint.
+pub(crate) fn glyph_program(
+    analysis: &GlyphAnalysis,
+    metrics: &ScriptMetrics,
+    upem: u16,
+) -> Result<Option<(Vec<u8>, usize)>, InstructionError> {
+    let edges = &analysis.edges;
+    if edges.is_empty() {
+        return Ok(None);
+    }
+    let mut program = ProgramBuilder::default();
+    let mut placed = vec![false; edges.len()];
+    program.op(SVTCA_Y, &[]);
+
+    // Align edges to the alignment zones
+    let fuzz = upem as f64 / 50.0;
+    for (index, edge) in edges.iter().enumerate() {
+        let zone = metrics
+            .blue_zones
+            .iter()
+            .filter(|zone| zone.top == edge.top)
+            .filter(|zone| {
+                let low = zone.reference.min(zone.overshoot) - fuzz;
+                let high = zone.reference.max(zone.overshoot) + fuzz;
+                (low..=high).contains(&edge.y)
+            })
+            .min_by(|a, b| {
+                (a.reference - edge.y)
+                    .abs()
+                    .partial_cmp(&(b.reference - edge.y).abs())
+                    .unwrap()
+            });
+        if let Some(zone) = zone {
+            let cvt = if edge.round {
+                zone.overshoot_cvt
+            } else {
+                zone.reference_cvt
+            };
+            program.op(MIAP_ROUND, &[cvt, edge.anchor]);
+            placed[index] = true;
+        }
+    }
+
+    // Link stems whose other edge is aligned; then place the remaining
+    // stems, from the bottom up
+    let mut stems = analysis.stems.clone();
+    stems.sort_by_key(|&(bottom, top)| !(placed[bottom] || placed[top]));
+    for (bottom, top) in stems {
+        let (from, to) = match (placed[bottom], placed[top]) {
+            (true, true) => continue,
+            (false, true) => (top, bottom),
+            (true, false) => (bottom, top),
+            (false, false) => {
+                anchor_edge(&mut program, edges, &placed, bottom, true);
+                placed[bottom] = true;
+                (bottom, top)
+            }
+        };
+        let width = (edges[top].y - edges[bottom].y).abs();
+        program.op(SRP0, &[edges[from].anchor]);
+        match metrics.closest_stem_width(width) {
+            Some(cvt) => program.op(MIRP_STEM, &[cvt, edges[to].anchor]),
+            None => program.op(MDRP_STEM, &[edges[to].anchor]),
+        }
+        placed[to] = true;
+    }
+
+    // Interpolate any other edges between their neighbours
+    for index in 0..edges.len() {
+        if !placed[index] {
+            anchor_edge(&mut program, edges, &placed, index, false);
+        }
+    }
+
+    // Move the rest of each edge's points with its anchor
+    for edge in edges {
+        let others: Vec<usize> = edge
+            .points
+            .iter()
+            .copied()
+            .filter(|&p| p != edge.anchor)
+            .collect();
+        if others.is_empty() {
+            continue;
+        }
+        program.op(SRP0, &[edge.anchor]);
+        if others.len() > 1 {
+            program.op(SLOOP, &[others.len()]);
+        }
+        program.op(ALIGNRP, &others);
+    }
+    program.op(IUP_Y, &[]);
+    program.build().map(Some)
+}
+
+/// Positions an edge relative to the nearest positioned edges below and
+/// above it, optionally rounding it to the grid.
+fn anchor_edge(
+    program: &mut ProgramBuilder,
+    edges: &[super::analysis::Edge],
+    placed: &[bool],
+    index: usize,
+    round: bool,
+) {
+    let below = (0..index).rev().find(|&i| placed[i]);
+    let above = (index + 1..edges.len()).find(|&i| placed[i]);
+    let anchor = edges[index].anchor;
+    match (below, above) {
+        (Some(below), Some(above)) => {
+            program.op(SRP1, &[edges[below].anchor]);
+            program.op(SRP2, &[edges[above].anchor]);
+            program.op(IP, &[anchor]);
+            if round {
+                program.op(MDAP_ROUND, &[anchor]);
+            }
+        }
+        (Some(other), None) | (None, Some(other)) => {
+            program.op(SRP0, &[edges[other].anchor]);
+            program.op(if round { MDRP_ROUND } else { MDRP }, &[anchor]);
+        }
+        (None, None) => {
+            if round {
+                program.op(MDAP_ROUND, &[anchor]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::{disassemble, to_text};
+
+    #[test]
+    fn test_program_builder() {
+        let mut program = ProgramBuilder::default();
+        program.op(SVTCA_Y, &[]);
+        program.op(MIAP_ROUND, &[2, 7]);
+        program.op(SRP0, &[7]);
+        program.op(MIRP_STEM, &[4, 9]);
+        program.op(IUP_Y, &[]);
+        let (bytecode, depth) = program.build().unwrap();
+        assert_eq!(depth, 5);
+        assert_eq!(
+            to_text(&disassemble(&bytecode).unwrap()),
+            "PUSHB[ ] 9 4 7 7 2\nSVTCA[0]\nMIAP[1]\nSRP0[ ]\nMIRP[01100]\nIUP[0]\n"
+        );
+    }
+
+    #[test]
+    fn test_control_value_program() {
+        let bytecode = control_value_program(&[0, 2], &[4], 200).unwrap();
+        let text = to_text(&disassemble(&bytecode).unwrap());
+        assert!(text.contains("PUSHB[ ] 0 2 2 0\nLOOPCALL[ ]"));
+        assert!(text.contains("PUSHB[ ] 4 1 1\nLOOPCALL[ ]"));
+        assert!(!font_program().is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+/// A writing system with its own set of alignment zones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Script {
+    /// Latin
+    Latin,
+    /// Greek
+    Greek,
+    /// Cyrillic
+    Cyrillic,
+}
+
+/// Describes an alignment zone in terms of the characters which reach it
+pub(crate) struct BlueZoneSpec {
+    /// Characters whose extremes define the zone
+    pub characters: &'static str,
+    /// Whether the zone is at the top of the characters (otherwise at the
+    /// bottom)
+    pub top: bool,
+}
+
+const fn zone(characters: &'static str, top: bool) -> BlueZoneSpec {
+    BlueZoneSpec { characters, top }
+}
+
+const LATIN_BLUES: &[BlueZoneSpec] = &[
+    zone("THEZOCQS", true),
+    zone("HEZLOCUS", false),
+    zone("fijkdbh", true),
+    zone("xzroesc", true),
+    zone("xzroesc", false),
+    zone("pqgjy", false),
+];
+
+const GREEK_BLUES: &[BlueZoneSpec] = &[
+    zone("ΓΒΕΖΘΟΩ", true),
+    zone("ΒΔΖΞΘΟ", false),
+    zone("βθδζλξ", true),
+    zone("αειοπστω", true),
+    zone("αειοπστω", false),
+    zone("βγημρφχψ", false),
+];
+
+const CYRILLIC_BLUES: &[BlueZoneSpec] = &[
+    zone("БВЕПЗОСЭ", true),
+    zone("БВЕШЗОСЭ", false),
+    zone("хпншезоэс", true),
+    zone("хпншезоэс", false),
+    zone("руф", false),
+];
+
+impl Script {
+    /// All supported scripts
+    pub const ALL: [Script; 3] = [Script::Latin, Script::Greek, Script::Cyrillic];
+
+    /// Determines the script of a Unicode codepoint, if it is supported.
+    pub fn of(codepoint: u32) -> Option<Script> {
+        match codepoint {
+            0x0020..=0x024F | 0x1E00..=0x1EFF | 0x2C60..=0x2C7F | 0xA720..=0xA7FF => {
+                Some(Script::Latin)
+            }
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Some(Script::Greek),
+            0x0400..=0x052F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Some(Script::Cyrillic),
+            _ => None,
+        }
+    }
+
+    /// The alignment zones of the script
+    pub(crate) fn blue_zones(&self) -> &'static [BlueZoneSpec] {
+        match self {
+            Script::Latin => LATIN_BLUES,
+            Script::Greek => GREEK_BLUES,
+            Script::Cyrillic => CYRILLIC_BLUES,
+        }
+    }
+
+    /// The character whose horizontal stems give the standard stem width
+    pub(crate) fn standard_character(&self) -> char {
+        match self {
+            Script::Latin => 'o',
+            Script::Greek => 'ο',
+            Script::Cyrillic => 'о',
+        }
+    }
+}
+
+impl std::str::FromStr for Script {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "latn" | "latin" => Ok(Script::Latin),
+            "grek" | "greek" => Ok(Script::Greek),
+            "cyrl" | "cyrillic" => Ok(Script::Cyrillic),
+            _ => Err(format!("Unknown script {}", s)),
+        }
+    }
+}
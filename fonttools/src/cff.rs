@@ -0,0 +1,46 @@
+//! CFF and CFF2 common structures
+//!
+//! The `CFF ` and `CFF2` tables share a number of data structures: INDEXes,
+//! DICTs, Type 2 charstrings and subroutines. These are implemented here;
+//! the tables themselves live in [crate::tables::CFF] and [crate::tables::CFF2].
+
+/// Type 2 charstrings
+pub mod charstring;
+/// Top and Private DICT structures
+pub mod dict;
+/// INDEX structures
+pub mod index;
+/// Standard strings
+pub mod strings;
+/// Subroutinization of charstrings
+pub mod subroutinizer;
+
+use dict::Dict;
+
+/// Returns the bias applied to subroutine numbers for a subroutine INDEX
+/// with the given number of entries.
+pub fn subr_bias(count: usize) -> i32 {
+    if count < 1240 {
+        107
+    } else if count < 33900 {
+        1131
+    } else {
+        32768
+    }
+}
+
+/// A font dict, together with its Private DICT and local subroutines
+///
+/// Non-CID CFF fonts have a single font dict, which is the Top DICT; in that
+/// case `font_dict` is empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontDict {
+    /// The font DICT (an entry in the FDArray)
+    pub font_dict: Dict,
+    /// The Private DICT. The `Private` and `Subrs` operators are managed
+    /// automatically on serialization.
+    pub private_dict: Dict,
+    /// The local subroutines
+    pub subrs: Vec<Vec<u8>>,
+}
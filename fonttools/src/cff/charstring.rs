@@ -0,0 +1,661 @@
+use super::subr_bias;
+use kurbo::{BezPath, PathEl};
+use otspec::DeserializationError;
+
+/// Charstring operators are stored as a `u16`; two-byte operators (`12 x`)
+/// are stored as `0x0c00 | x`.
+pub type CharStringOperator = u16;
+
+/// `hstem` operator
+pub const HSTEM: CharStringOperator = 1;
+/// `vstem` operator
+pub const VSTEM: CharStringOperator = 3;
+/// `vmoveto` operator
+pub const VMOVETO: CharStringOperator = 4;
+/// `rlineto` operator
+pub const RLINETO: CharStringOperator = 5;
+/// `hlineto` operator
+pub const HLINETO: CharStringOperator = 6;
+/// `vlineto` operator
+pub const VLINETO: CharStringOperator = 7;
+/// `rrcurveto` operator
+pub const RRCURVETO: CharStringOperator = 8;
+/// `callsubr` operator
+pub const CALLSUBR: CharStringOperator = 10;
+/// `return` operator (CFF only)
+pub const RETURN: CharStringOperator = 11;
+/// `endchar` operator (CFF only)
+pub const ENDCHAR: CharStringOperator = 14;
+/// `vsindex` operator (CFF2 only)
+pub const VSINDEX: CharStringOperator = 15;
+/// `blend` operator (CFF2 only)
+pub const BLEND: CharStringOperator = 16;
+/// `hstemhm` operator
+pub const HSTEMHM: CharStringOperator = 18;
+/// `hintmask` operator
+pub const HINTMASK: CharStringOperator = 19;
+/// `cntrmask` operator
+pub const CNTRMASK: CharStringOperator = 20;
+/// `rmoveto` operator
+pub const RMOVETO: CharStringOperator = 21;
+/// `hmoveto` operator
+pub const HMOVETO: CharStringOperator = 22;
+/// `vstemhm` operator
+pub const VSTEMHM: CharStringOperator = 23;
+/// `callgsubr` operator
+pub const CALLGSUBR: CharStringOperator = 29;
+
+/// Maximum depth of the argument stack in a CFF charstring
+pub const CFF_MAX_STACK: usize = 48;
+/// Maximum depth of the argument stack in a CFF2 charstring
+pub const CFF2_MAX_STACK: usize = 513;
+/// Maximum subroutine nesting depth
+pub const MAX_SUBR_NESTING: usize = 10;
+
+/// A single element of a Type 2 charstring
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CharStringToken {
+    /// An integer operand
+    Integer(i32),
+    /// A 16.16 fixed-point operand, stored as its raw bits
+    Fixed(i32),
+    /// An operator
+    Operator(CharStringOperator),
+    /// A `hintmask` or `cntrmask` operator, together with its mask bytes
+    Mask(CharStringOperator, Vec<u8>),
+}
+
+impl CharStringToken {
+    /// The value of an operand token
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            CharStringToken::Integer(i) => Some(*i as f64),
+            CharStringToken::Fixed(f) => Some(*f as f64 / 65536.0),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if this token is an operand
+    pub fn is_operand(&self) -> bool {
+        matches!(
+            self,
+            CharStringToken::Integer(_) | CharStringToken::Fixed(_)
+        )
+    }
+
+    /// Appends the binary encoding of this token to `out`.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            CharStringToken::Integer(i) => match *i {
+                -107..=107 => out.push((i + 139) as u8),
+                108..=1131 => {
+                    let v = i - 108;
+                    out.extend([((v >> 8) + 247) as u8, (v & 0xff) as u8]);
+                }
+                -1131..=-108 => {
+                    let v = -i - 108;
+                    out.extend([((v >> 8) + 251) as u8, (v & 0xff) as u8]);
+                }
+                -32768..=32767 => {
+                    out.push(28);
+                    out.extend((*i as i16).to_be_bytes());
+                }
+                _ => {
+                    // Out of range for a charstring; the best we can do is clamp.
+                    log::warn!("Charstring operand {} out of range", i);
+                    out.push(28);
+                    out.extend(((*i).clamp(-32768, 32767) as i16).to_be_bytes());
+                }
+            },
+            CharStringToken::Fixed(f) => {
+                out.push(255);
+                out.extend(f.to_be_bytes());
+            }
+            CharStringToken::Operator(op) | CharStringToken::Mask(op, _) => {
+                if *op >= 0x0c00 {
+                    out.extend([12, (*op & 0xff) as u8]);
+                } else {
+                    out.push(*op as u8);
+                }
+                if let CharStringToken::Mask(_, mask) = self {
+                    out.extend(mask);
+                }
+            }
+        }
+    }
+
+    /// The number of bytes needed to encode this token.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            CharStringToken::Integer(i) => match *i {
+                -107..=107 => 1,
+                -1131..=1131 => 2,
+                _ => 3,
+            },
+            CharStringToken::Fixed(_) => 5,
+            CharStringToken::Operator(op) => {
+                if *op >= 0x0c00 {
+                    2
+                } else {
+                    1
+                }
+            }
+            CharStringToken::Mask(_, mask) => 1 + mask.len(),
+        }
+    }
+}
+
+impl From<i32> for CharStringToken {
+    fn from(i: i32) -> Self {
+        CharStringToken::Integer(i)
+    }
+}
+
+/// Encodes a list of tokens as a charstring.
+pub fn encode_charstring(tokens: &[CharStringToken]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(tokens.len() * 2);
+    for token in tokens {
+        token.encode(&mut out);
+    }
+    out
+}
+
+/// Turns a charstring into a list of tokens, optionally expanding subroutine calls.
+struct Tokenizer<'a> {
+    global_subrs: &'a [Vec<u8>],
+    local_subrs: &'a [Vec<u8>],
+    region_counts: &'a [usize],
+    expand_subrs: bool,
+    cff2: bool,
+    stems: usize,
+    stack: usize,
+    vsindex: usize,
+    out: Vec<CharStringToken>,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Tokenizes a (sub)program; returns `true` if `endchar` was reached.
+    fn run(&mut self, data: &[u8], depth: usize) -> Result<bool, DeserializationError> {
+        if depth > MAX_SUBR_NESTING {
+            return Err(DeserializationError(
+                "Subroutines nested too deeply".to_string(),
+            ));
+        }
+        let mut ptr = 0;
+        let byte_at = |ptr: usize| {
+            data.get(ptr)
+                .copied()
+                .ok_or_else(|| DeserializationError("Unexpected end of charstring".to_string()))
+        };
+        while ptr < data.len() {
+            let b0 = data[ptr];
+            ptr += 1;
+            let token = match b0 {
+                28 => {
+                    ptr += 2;
+                    CharStringToken::Integer(i16::from_be_bytes([
+                        byte_at(ptr - 2)?,
+                        byte_at(ptr - 1)?,
+                    ]) as i32)
+                }
+                32..=246 => CharStringToken::Integer(b0 as i32 - 139),
+                247..=250 => {
+                    ptr += 1;
+                    CharStringToken::Integer(
+                        (b0 as i32 - 247) * 256 + byte_at(ptr - 1)? as i32 + 108,
+                    )
+                }
+                251..=254 => {
+                    ptr += 1;
+                    CharStringToken::Integer(
+                        -(b0 as i32 - 251) * 256 - byte_at(ptr - 1)? as i32 - 108,
+                    )
+                }
+                255 => {
+                    ptr += 4;
+                    CharStringToken::Fixed(i32::from_be_bytes([
+                        byte_at(ptr - 4)?,
+                        byte_at(ptr - 3)?,
+                        byte_at(ptr - 2)?,
+                        byte_at(ptr - 1)?,
+                    ]))
+                }
+                12 => {
+                    ptr += 1;
+                    CharStringToken::Operator(0x0c00 | byte_at(ptr - 1)? as u16)
+                }
+                _ => CharStringToken::Operator(b0 as u16),
+            };
+            if token.is_operand() {
+                self.stack += 1;
+                self.out.push(token);
+                continue;
+            }
+            let op = match token {
+                CharStringToken::Operator(op) => op,
+                _ => unreachable!(),
+            };
+            match op {
+                HSTEM | VSTEM | HSTEMHM | VSTEMHM => {
+                    self.stems += self.stack / 2;
+                    self.stack = 0;
+                }
+                HINTMASK | CNTRMASK => {
+                    // Any pending arguments are an implicit vstem
+                    self.stems += self.stack / 2;
+                    self.stack = 0;
+                    let mask_len = self.stems.div_ceil(8);
+                    if ptr + mask_len > data.len() {
+                        return Err(DeserializationError("Truncated hint mask".to_string()));
+                    }
+                    self.out.push(CharStringToken::Mask(
+                        op,
+                        data[ptr..ptr + mask_len].to_vec(),
+                    ));
+                    ptr += mask_len;
+                    continue;
+                }
+                CALLSUBR | CALLGSUBR if self.expand_subrs => {
+                    let index = match self.out.pop().and_then(|t| t.value()) {
+                        Some(i) => i as i32,
+                        None => {
+                            return Err(DeserializationError(
+                                "Subroutine call without index".to_string(),
+                            ))
+                        }
+                    };
+                    self.stack = self.stack.saturating_sub(1);
+                    let subrs = if op == CALLSUBR {
+                        self.local_subrs
+                    } else {
+                        self.global_subrs
+                    };
+                    let subr = usize::try_from(index + subr_bias(subrs.len()))
+                        .ok()
+                        .and_then(|ix| subrs.get(ix))
+                        .ok_or_else(|| {
+                            DeserializationError(format!("Bad subroutine index {}", index))
+                        })?;
+                    if self.run(subr, depth + 1)? {
+                        return Ok(true);
+                    }
+                    continue;
+                }
+                CALLSUBR | CALLGSUBR => {
+                    // We can't know what the subroutine does to the stack
+                    self.stack = 0;
+                }
+                RETURN if self.expand_subrs && !self.cff2 => return Ok(false),
+                ENDCHAR if !self.cff2 => {
+                    self.out.push(token);
+                    return Ok(true);
+                }
+                VSINDEX if self.cff2 => {
+                    self.vsindex = self.out.last().and_then(|t| t.value()).unwrap_or(0.0) as usize;
+                    self.stack = 0;
+                }
+                BLEND if self.cff2 => {
+                    let n = self.out.last().and_then(|t| t.value()).unwrap_or(0.0) as usize;
+                    let k = self.region_counts.get(self.vsindex).copied().unwrap_or(0);
+                    self.stack = self.stack.saturating_sub(n * k + 1);
+                    self.out.push(token);
+                    continue;
+                }
+                // Arithmetic operators leave results on the stack; we don't
+                // attempt to track them, as they are deprecated and unused in practice.
+                op if op >= 0x0c00 && !(0x0c22..=0x0c25).contains(&op) => {}
+                _ => self.stack = 0,
+            }
+            self.out.push(token);
+        }
+        Ok(false)
+    }
+}
+
+/// Tokenizes a charstring without expanding subroutine calls.
+///
+/// Hint masks are sized by counting the stem hints declared within this
+/// charstring; if hints are declared in subroutines, use [`desubroutinize`]
+/// instead. For CFF2 charstrings, `region_counts` gives the number of
+/// regions referenced by each `vsindex`, and is needed to track `blend`s; for
+/// CFF charstrings, pass an empty slice.
+pub fn decode_charstring(
+    data: &[u8],
+    cff2: bool,
+    region_counts: &[usize],
+) -> Result<Vec<CharStringToken>, DeserializationError> {
+    let mut tokenizer = Tokenizer {
+        global_subrs: &[],
+        local_subrs: &[],
+        region_counts,
+        expand_subrs: false,
+        cff2,
+        stems: 0,
+        stack: 0,
+        vsindex: 0,
+        out: vec![],
+    };
+    tokenizer.run(data, 0)?;
+    Ok(tokenizer.out)
+}
+
+/// Tokenizes a charstring, inlining all calls to local and global subroutines.
+pub fn desubroutinize(
+    data: &[u8],
+    global_subrs: &[Vec<u8>],
+    local_subrs: &[Vec<u8>],
+    cff2: bool,
+    region_counts: &[usize],
+) -> Result<Vec<CharStringToken>, DeserializationError> {
+    let mut tokenizer = Tokenizer {
+        global_subrs,
+        local_subrs,
+        region_counts,
+        expand_subrs: true,
+        cff2,
+        stems: 0,
+        stack: 0,
+        vsindex: 0,
+        out: vec![],
+    };
+    tokenizer.run(data, 0)?;
+    Ok(tokenizer.out)
+}
+
+/// A path construction operator with its arguments
+pub type PathOperation = (CharStringOperator, Vec<i32>);
+
+/// Converts a set of contours into a list of relative path operations.
+///
+/// Coordinates are rounded to integers before being made relative, so that
+/// rounding errors do not accumulate. Quadratic curves are converted exactly
+/// to cubic curves. Only `rmoveto`, `rlineto` and `rrcurveto` are produced,
+/// with one segment per operation; this keeps the structure identical between
+/// compatible masters.
+pub fn path_operations(paths: &[BezPath]) -> Vec<PathOperation> {
+    let mut ops = vec![];
+    let mut current = (0, 0);
+    let mut start = (0.0, 0.0);
+    let mut last = (0.0, 0.0);
+    let rel = |pt: kurbo::Point, current: &mut (i32, i32)| -> [i32; 2] {
+        let (x, y) = (pt.x.round() as i32, pt.y.round() as i32);
+        let out = [x - current.0, y - current.1];
+        *current = (x, y);
+        out
+    };
+    for path in paths {
+        for el in path.elements() {
+            match *el {
+                PathEl::MoveTo(p) => {
+                    ops.push((RMOVETO, rel(p, &mut current).to_vec()));
+                    start = (p.x, p.y);
+                    last = start;
+                }
+                PathEl::LineTo(p) => {
+                    ops.push((RLINETO, rel(p, &mut current).to_vec()));
+                    last = (p.x, p.y);
+                }
+                PathEl::QuadTo(q, p) => {
+                    let p0 = kurbo::Point::new(last.0, last.1);
+                    let c1 = p0 + (q - p0) * (2.0 / 3.0);
+                    let c2 = p + (q - p) * (2.0 / 3.0);
+                    let mut args = rel(c1, &mut current).to_vec();
+                    args.extend(rel(c2, &mut current));
+                    args.extend(rel(p, &mut current));
+                    ops.push((RRCURVETO, args));
+                    last = (p.x, p.y);
+                }
+                PathEl::CurveTo(c1, c2, p) => {
+                    let mut args = rel(c1, &mut current).to_vec();
+                    args.extend(rel(c2, &mut current));
+                    args.extend(rel(p, &mut current));
+                    ops.push((RRCURVETO, args));
+                    last = (p.x, p.y);
+                }
+                // Charstring contours are implicitly closed; a closing line
+                // back to the start point is redundant.
+                PathEl::ClosePath => {
+                    if let Some((RLINETO, _)) = ops.last() {
+                        if last == start {
+                            ops.pop();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ops
+}
+
+/// Builds a CFF charstring from a list of path operations.
+///
+/// If `width` is given, it is written as the charstring's width argument (it
+/// should already be relative to the Private DICT's `nominalWidthX`).
+/// Consecutive operations of the same type are merged where the argument
+/// stack allows it.
+pub fn charstring_from_operations(
+    ops: &[PathOperation],
+    width: Option<i32>,
+) -> Vec<CharStringToken> {
+    // Leave space for a subroutine index on the stack
+    const MAX_ARGS: usize = CFF_MAX_STACK - 4;
+    let mut tokens: Vec<CharStringToken> = vec![];
+    let mut pending_op: Option<CharStringOperator> = None;
+    let mut pending_args = 0;
+    if let Some(width) = width {
+        // The width sits on the stack along with the first operator's arguments
+        tokens.push(width.into());
+        pending_args = 1;
+    }
+    for (op, args) in ops {
+        if let Some(pending) = pending_op {
+            if pending != *op || pending == RMOVETO || pending_args + args.len() > MAX_ARGS {
+                tokens.push(CharStringToken::Operator(pending));
+                pending_args = 0;
+            }
+        }
+        tokens.extend(args.iter().map(|&a| CharStringToken::Integer(a)));
+        pending_args += args.len();
+        pending_op = Some(*op);
+    }
+    if let Some(pending) = pending_op {
+        tokens.push(CharStringToken::Operator(pending));
+    }
+    tokens.push(CharStringToken::Operator(ENDCHAR));
+    tokens
+}
+
+/// Builds a CFF2 charstring from the path operations of the default master,
+/// together with per-region deltas for each argument.
+///
+/// `region_deltas` contains, for each region referenced by `vsindex`, one
+/// delta for each argument of `ops` (flattened, in order). Arguments whose
+/// deltas are all zero are written as plain numbers; the others are combined
+/// with the `blend` operator.
+pub fn blended_charstring_from_operations(
+    ops: &[PathOperation],
+    region_deltas: &[Vec<i32>],
+    vsindex: Option<u16>,
+) -> Vec<CharStringToken> {
+    let mut tokens: Vec<CharStringToken> = vec![];
+    if let Some(vsindex) = vsindex {
+        tokens.push((vsindex as i32).into());
+        tokens.push(CharStringToken::Operator(VSINDEX));
+    }
+    let k = region_deltas.len();
+    let mut arg_ix = 0;
+    for (op, args) in ops {
+        let n = args.len();
+        let varies = (arg_ix..arg_ix + n).any(|i| region_deltas.iter().any(|r| r[i] != 0));
+        if !varies {
+            tokens.extend(args.iter().map(|&a| CharStringToken::Integer(a)));
+        } else if n * (k + 1) + 1 < CFF2_MAX_STACK - 1 {
+            tokens.extend(args.iter().map(|&a| CharStringToken::Integer(a)));
+            for i in arg_ix..arg_ix + n {
+                tokens.extend(region_deltas.iter().map(|r| CharStringToken::Integer(r[i])));
+            }
+            tokens.push((n as i32).into());
+            tokens.push(CharStringToken::Operator(BLEND));
+        } else {
+            // Too many regions to blend all arguments at once; blend them one by one.
+            for (j, &a) in args.iter().enumerate() {
+                tokens.push(a.into());
+                tokens.extend(
+                    region_deltas
+                        .iter()
+                        .map(|r| CharStringToken::Integer(r[arg_ix + j])),
+                );
+                tokens.push(1.into());
+                tokens.push(CharStringToken::Operator(BLEND));
+            }
+        }
+        tokens.push(CharStringToken::Operator(*op));
+        arg_ix += n;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to((10.0, 10.0));
+        path.line_to((10.0, 110.0));
+        path.curve_to((40.0, 140.0), (80.0, 140.0), (110.0, 110.0));
+        path.line_to((110.0, 10.0));
+        path.line_to((10.0, 10.0));
+        path.close_path();
+        path
+    }
+
+    #[test]
+    fn test_token_roundtrip() {
+        let tokens = vec![
+            CharStringToken::Integer(0),
+            CharStringToken::Integer(-107),
+            CharStringToken::Integer(1131),
+            CharStringToken::Integer(-1131),
+            CharStringToken::Integer(-32000),
+            CharStringToken::Fixed(0x18000),
+            CharStringToken::Operator(HSTEMHM),
+            CharStringToken::Integer(5),
+            CharStringToken::Integer(6),
+            CharStringToken::Mask(HINTMASK, vec![0xe0]),
+            CharStringToken::Operator(0x0c23),
+            CharStringToken::Operator(ENDCHAR),
+        ];
+        let binary = encode_charstring(&tokens);
+        assert_eq!(
+            binary.len(),
+            tokens.iter().map(|t| t.encoded_len()).sum::<usize>()
+        );
+        assert_eq!(decode_charstring(&binary, false, &[]).unwrap(), tokens);
+    }
+
+    #[test]
+    fn test_desubroutinize() {
+        // A global subr which draws a line, and a local subr which calls it
+        let global_subrs = vec![encode_charstring(&[
+            CharStringToken::Integer(10),
+            CharStringToken::Integer(20),
+            CharStringToken::Operator(RLINETO),
+            CharStringToken::Operator(RETURN),
+        ])];
+        let local_subrs = vec![encode_charstring(&[
+            CharStringToken::Integer(-107),
+            CharStringToken::Operator(CALLGSUBR),
+            CharStringToken::Operator(ENDCHAR),
+        ])];
+        let charstring = encode_charstring(&[
+            CharStringToken::Integer(0),
+            CharStringToken::Integer(0),
+            CharStringToken::Operator(RMOVETO),
+            CharStringToken::Integer(-107),
+            CharStringToken::Operator(CALLSUBR),
+        ]);
+        let tokens = desubroutinize(&charstring, &global_subrs, &local_subrs, false, &[]).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                CharStringToken::Integer(0),
+                CharStringToken::Integer(0),
+                CharStringToken::Operator(RMOVETO),
+                CharStringToken::Integer(10),
+                CharStringToken::Integer(20),
+                CharStringToken::Operator(RLINETO),
+                CharStringToken::Operator(ENDCHAR),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_operations() {
+        let ops = path_operations(&[square()]);
+        assert_eq!(
+            ops,
+            vec![
+                (RMOVETO, vec![10, 10]),
+                (RLINETO, vec![0, 100]),
+                (RRCURVETO, vec![30, 30, 40, 0, 30, -30]),
+                (RLINETO, vec![0, -100]),
+            ]
+        );
+        let tokens = charstring_from_operations(&ops, Some(500));
+        assert_eq!(
+            tokens,
+            vec![
+                500.into(),
+                10.into(),
+                10.into(),
+                CharStringToken::Operator(RMOVETO),
+                0.into(),
+                100.into(),
+                CharStringToken::Operator(RLINETO),
+                30.into(),
+                30.into(),
+                40.into(),
+                0.into(),
+                30.into(),
+                (-30).into(),
+                CharStringToken::Operator(RRCURVETO),
+                0.into(),
+                (-100).into(),
+                CharStringToken::Operator(RLINETO),
+                CharStringToken::Operator(ENDCHAR),
+            ]
+        );
+        let empty = charstring_from_operations(&[], Some(250));
+        assert_eq!(empty, vec![250.into(), CharStringToken::Operator(ENDCHAR)]);
+    }
+
+    #[test]
+    fn test_blended_charstring() {
+        let ops = vec![(RMOVETO, vec![10, 10]), (RLINETO, vec![0, 100])];
+        let deltas = vec![vec![0, 0, 5, 0], vec![0, 0, 0, -3]];
+        let tokens = blended_charstring_from_operations(&ops, &deltas, Some(1));
+        assert_eq!(
+            tokens,
+            vec![
+                1.into(),
+                CharStringToken::Operator(VSINDEX),
+                10.into(),
+                10.into(),
+                CharStringToken::Operator(RMOVETO),
+                0.into(),
+                100.into(),
+                5.into(),
+                0.into(),
+                0.into(),
+                (-3).into(),
+                2.into(),
+                CharStringToken::Operator(BLEND),
+                CharStringToken::Operator(RLINETO),
+            ]
+        );
+        // The tokenizer tracks the stack through blends
+        let binary = encode_charstring(&tokens);
+        assert_eq!(decode_charstring(&binary, true, &[0, 2]).unwrap(), tokens);
+    }
+}
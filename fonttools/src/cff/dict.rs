@@ -0,0 +1,391 @@
+use otspec::DeserializationError;
+
+/// Operators are stored as a `u16`; two-byte operators (`12 x`) are stored as `0x0c00 | x`.
+pub type Operator = u16;
+
+const fn escaped(op: u8) -> Operator {
+    0x0c00 | op as Operator
+}
+
+/// Top DICT `version` operator (SID)
+pub const VERSION: Operator = 0;
+/// Top DICT `Notice` operator (SID)
+pub const NOTICE: Operator = 1;
+/// Top DICT `FullName` operator (SID)
+pub const FULL_NAME: Operator = 2;
+/// Top DICT `FamilyName` operator (SID)
+pub const FAMILY_NAME: Operator = 3;
+/// Top DICT `Weight` operator (SID)
+pub const WEIGHT: Operator = 4;
+/// Top DICT `FontBBox` operator
+pub const FONT_BBOX: Operator = 5;
+/// Private DICT `BlueValues` operator
+pub const BLUE_VALUES: Operator = 6;
+/// Private DICT `OtherBlues` operator
+pub const OTHER_BLUES: Operator = 7;
+/// Private DICT `StdHW` operator
+pub const STD_HW: Operator = 10;
+/// Private DICT `StdVW` operator
+pub const STD_VW: Operator = 11;
+/// Top DICT `charset` operator (offset)
+pub const CHARSET: Operator = 15;
+/// Top DICT `Encoding` operator (offset)
+pub const ENCODING: Operator = 16;
+/// Top DICT `CharStrings` operator (offset)
+pub const CHARSTRINGS: Operator = 17;
+/// Top DICT / Font DICT `Private` operator (size and offset)
+pub const PRIVATE: Operator = 18;
+/// Private DICT `Subrs` operator (offset, relative to the Private DICT)
+pub const SUBRS: Operator = 19;
+/// Private DICT `defaultWidthX` operator
+pub const DEFAULT_WIDTH_X: Operator = 20;
+/// Private DICT `nominalWidthX` operator
+pub const NOMINAL_WIDTH_X: Operator = 21;
+/// CFF2 Private DICT `vsindex` operator
+pub const VSINDEX: Operator = 22;
+/// CFF2 Private DICT `blend` operator
+pub const BLEND: Operator = 23;
+/// CFF2 Top DICT `vstore` operator (offset)
+pub const VSTORE: Operator = 24;
+/// Top DICT `Copyright` operator (SID)
+pub const COPYRIGHT: Operator = escaped(0);
+/// Top DICT `isFixedPitch` operator
+pub const IS_FIXED_PITCH: Operator = escaped(1);
+/// Top DICT `ItalicAngle` operator
+pub const ITALIC_ANGLE: Operator = escaped(2);
+/// Top DICT `UnderlinePosition` operator
+pub const UNDERLINE_POSITION: Operator = escaped(3);
+/// Top DICT `UnderlineThickness` operator
+pub const UNDERLINE_THICKNESS: Operator = escaped(4);
+/// Top DICT `CharstringType` operator
+pub const CHARSTRING_TYPE: Operator = escaped(6);
+/// Top DICT `FontMatrix` operator
+pub const FONT_MATRIX: Operator = escaped(7);
+/// Private DICT `BlueScale` operator
+pub const BLUE_SCALE: Operator = escaped(9);
+/// Private DICT `BlueShift` operator
+pub const BLUE_SHIFT: Operator = escaped(10);
+/// Private DICT `BlueFuzz` operator
+pub const BLUE_FUZZ: Operator = escaped(11);
+/// Top DICT `ROS` operator (SID, SID, number)
+pub const ROS: Operator = escaped(30);
+/// Top DICT `CIDCount` operator
+pub const CID_COUNT: Operator = escaped(34);
+/// Top DICT `FDArray` operator (offset)
+pub const FD_ARRAY: Operator = escaped(36);
+/// Top DICT `FDSelect` operator (offset)
+pub const FD_SELECT: Operator = escaped(37);
+/// Font DICT `FontName` operator (SID)
+pub const FONT_NAME: Operator = escaped(38);
+
+/// Operators whose operands are offsets. These are always written using the
+/// five-byte integer encoding, so that the size of a DICT does not depend on
+/// the values of its offsets.
+const OFFSET_OPERATORS: [Operator; 8] = [
+    CHARSET,
+    ENCODING,
+    CHARSTRINGS,
+    PRIVATE,
+    SUBRS,
+    VSTORE,
+    FD_ARRAY,
+    FD_SELECT,
+];
+
+/// An operand in a DICT
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DictOperand {
+    /// An integer operand
+    Integer(i32),
+    /// A real number operand
+    Real(f64),
+}
+
+impl DictOperand {
+    /// The operand's value as a floating point number
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            DictOperand::Integer(i) => *i as f64,
+            DictOperand::Real(r) => *r,
+        }
+    }
+
+    /// The operand's value, truncated to an integer
+    pub fn as_i32(&self) -> i32 {
+        match self {
+            DictOperand::Integer(i) => *i,
+            DictOperand::Real(r) => *r as i32,
+        }
+    }
+}
+
+impl From<i32> for DictOperand {
+    fn from(i: i32) -> Self {
+        DictOperand::Integer(i)
+    }
+}
+
+impl From<f64> for DictOperand {
+    fn from(r: f64) -> Self {
+        if r.fract() == 0.0 && r.abs() < i32::MAX as f64 {
+            DictOperand::Integer(r as i32)
+        } else {
+            DictOperand::Real(r)
+        }
+    }
+}
+
+/// A DICT: an ordered list of operators and their operands
+///
+/// Order is preserved, as some operators (e.g. `ROS`) must come first.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dict(pub Vec<(Operator, Vec<DictOperand>)>);
+
+impl Dict {
+    /// Returns the operands of the given operator, if present.
+    pub fn get(&self, op: Operator) -> Option<&[DictOperand]> {
+        self.0
+            .iter()
+            .find(|(o, _)| *o == op)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Returns the first operand of the given operator as an integer, if present.
+    pub fn get_i32(&self, op: Operator) -> Option<i32> {
+        self.get(op)
+            .and_then(|operands| operands.first())
+            .map(|x| x.as_i32())
+    }
+
+    /// Sets the operands of an operator, replacing any existing entry or
+    /// otherwise appending it to the end of the DICT.
+    pub fn set(&mut self, op: Operator, operands: Vec<DictOperand>) {
+        if let Some(entry) = self.0.iter_mut().find(|(o, _)| *o == op) {
+            entry.1 = operands;
+        } else {
+            self.0.push((op, operands));
+        }
+    }
+
+    /// Removes an operator from the DICT, returning its operands.
+    pub fn remove(&mut self, op: Operator) -> Option<Vec<DictOperand>> {
+        let position = self.0.iter().position(|(o, _)| *o == op)?;
+        Some(self.0.remove(position).1)
+    }
+
+    /// Returns `true` if the DICT has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn decode_real(data: &[u8], ptr: &mut usize) -> Result<f64, DeserializationError> {
+    let mut s = String::new();
+    'outer: loop {
+        let byte = *data
+            .get(*ptr)
+            .ok_or_else(|| DeserializationError("Unterminated real in DICT".to_string()))?;
+        *ptr += 1;
+        for nibble in [byte >> 4, byte & 0x0f] {
+            match nibble {
+                0..=9 => s.push((b'0' + nibble) as char),
+                0xa => s.push('.'),
+                0xb => s.push('E'),
+                0xc => s.push_str("E-"),
+                0xe => s.push('-'),
+                0xf => break 'outer,
+                _ => return Err(DeserializationError("Bad nibble in real".to_string())),
+            }
+        }
+    }
+    s.parse::<f64>()
+        .map_err(|_| DeserializationError(format!("Bad real number {:?}", s)))
+}
+
+fn encode_real(r: f64, out: &mut Vec<u8>) {
+    let mut nibbles: Vec<u8> = vec![];
+    let repr = format!("{}", r);
+    let repr = repr.strip_suffix(".0").unwrap_or(&repr);
+    for ch in repr.chars() {
+        match ch {
+            '0'..='9' => nibbles.push(ch as u8 - b'0'),
+            '.' => nibbles.push(0xa),
+            '-' => nibbles.push(0xe),
+            _ => {}
+        }
+    }
+    nibbles.push(0xf);
+    if nibbles.len() % 2 == 1 {
+        nibbles.push(0xf);
+    }
+    out.push(30);
+    for pair in nibbles.chunks(2) {
+        out.push(pair[0] << 4 | pair[1]);
+    }
+}
+
+/// Encodes an integer using the shortest DICT encoding.
+pub(crate) fn encode_integer(i: i32, out: &mut Vec<u8>) {
+    match i {
+        -107..=107 => out.push((i + 139) as u8),
+        108..=1131 => {
+            let v = i - 108;
+            out.extend([((v >> 8) + 247) as u8, (v & 0xff) as u8]);
+        }
+        -1131..=-108 => {
+            let v = -i - 108;
+            out.extend([((v >> 8) + 251) as u8, (v & 0xff) as u8]);
+        }
+        -32768..=32767 => {
+            out.push(28);
+            out.extend((i as i16).to_be_bytes());
+        }
+        _ => encode_long_integer(i, out),
+    }
+}
+
+fn encode_long_integer(i: i32, out: &mut Vec<u8>) {
+    out.push(29);
+    out.extend(i.to_be_bytes());
+}
+
+/// Decodes a DICT from its binary representation.
+pub fn decode_dict(data: &[u8]) -> Result<Dict, DeserializationError> {
+    let mut dict = Dict::default();
+    let mut operands = vec![];
+    let mut ptr = 0;
+    let byte_at = |ptr: usize| {
+        data.get(ptr)
+            .copied()
+            .ok_or_else(|| DeserializationError("Unexpected end of DICT".to_string()))
+    };
+    while ptr < data.len() {
+        let b0 = data[ptr];
+        ptr += 1;
+        match b0 {
+            0..=27 => {
+                let op = if b0 == 12 {
+                    ptr += 1;
+                    escaped(byte_at(ptr - 1)?)
+                } else {
+                    b0 as Operator
+                };
+                dict.0.push((op, std::mem::take(&mut operands)));
+            }
+            28 => {
+                let v = i16::from_be_bytes([byte_at(ptr)?, byte_at(ptr + 1)?]);
+                ptr += 2;
+                operands.push(DictOperand::Integer(v as i32));
+            }
+            29 => {
+                let v = i32::from_be_bytes([
+                    byte_at(ptr)?,
+                    byte_at(ptr + 1)?,
+                    byte_at(ptr + 2)?,
+                    byte_at(ptr + 3)?,
+                ]);
+                ptr += 4;
+                operands.push(DictOperand::Integer(v));
+            }
+            30 => operands.push(DictOperand::Real(decode_real(data, &mut ptr)?)),
+            32..=246 => operands.push(DictOperand::Integer(b0 as i32 - 139)),
+            247..=250 => {
+                let v = (b0 as i32 - 247) * 256 + byte_at(ptr)? as i32 + 108;
+                ptr += 1;
+                operands.push(DictOperand::Integer(v));
+            }
+            251..=254 => {
+                let v = -(b0 as i32 - 251) * 256 - byte_at(ptr)? as i32 - 108;
+                ptr += 1;
+                operands.push(DictOperand::Integer(v));
+            }
+            _ => {
+                return Err(DeserializationError(format!(
+                    "Reserved byte {} in DICT",
+                    b0
+                )))
+            }
+        }
+    }
+    Ok(dict)
+}
+
+/// Encodes a DICT into its binary representation.
+pub fn encode_dict(dict: &Dict) -> Vec<u8> {
+    let mut out = vec![];
+    for (op, operands) in &dict.0 {
+        for operand in operands {
+            match operand {
+                DictOperand::Integer(i) if OFFSET_OPERATORS.contains(op) => {
+                    encode_long_integer(*i, &mut out)
+                }
+                DictOperand::Integer(i) => encode_integer(*i, &mut out),
+                DictOperand::Real(r) => encode_real(*r, &mut out),
+            }
+        }
+        if *op >= 0x0c00 {
+            out.extend([12, (*op & 0xff) as u8]);
+        } else {
+            out.push(*op as u8);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dict_roundtrip() {
+        let dict = Dict(vec![
+            (VERSION, vec![DictOperand::Integer(391)]),
+            (
+                FONT_BBOX,
+                vec![
+                    DictOperand::Integer(-100),
+                    DictOperand::Integer(-1000),
+                    DictOperand::Integer(32000),
+                    DictOperand::Integer(100000),
+                ],
+            ),
+            (ITALIC_ANGLE, vec![DictOperand::Real(-12.5)]),
+            (BLUE_SCALE, vec![DictOperand::Real(0.039625)]),
+            (CHARSTRINGS, vec![DictOperand::Integer(10)]),
+        ]);
+        let binary = encode_dict(&dict);
+        assert_eq!(decode_dict(&binary).unwrap(), dict);
+    }
+
+    #[test]
+    fn test_dict_encodings() {
+        // Examples from the CFF specification, Table 3 and Table 5
+        let mut out = vec![];
+        for i in [0, 100, -100, 1000, -1000, 10000, -10000, 100000, -100000] {
+            encode_integer(i, &mut out);
+        }
+        assert_eq!(
+            out,
+            vec![
+                0x8b, 0xef, 0x27, 0xfa, 0x7c, 0xfe, 0x7c, 0x1c, 0x27, 0x10, 0x1c, 0xd8, 0xf0, 0x1d,
+                0x00, 0x01, 0x86, 0xa0, 0x1d, 0xff, 0xfe, 0x79, 0x60
+            ]
+        );
+        let mut out = vec![];
+        encode_real(-2.25, &mut out);
+        assert_eq!(out, vec![0x1e, 0xe2, 0xa2, 0x5f]);
+        let mut ptr = 1;
+        assert_eq!(
+            decode_real(&[0x1e, 0x0a, 0x14, 0x05, 0x41, 0xc3, 0xff], &mut ptr).unwrap(),
+            0.140541e-3
+        );
+    }
+
+    #[test]
+    fn test_offsets_are_long() {
+        let dict = Dict(vec![(CHARSTRINGS, vec![DictOperand::Integer(1)])]);
+        assert_eq!(encode_dict(&dict), vec![29, 0, 0, 0, 1, 17]);
+    }
+}
@@ -0,0 +1,120 @@
+use otspec::{DeserializationError, Deserializer, ReaderContext};
+
+fn read_offset(c: &mut ReaderContext, off_size: u8) -> Result<usize, DeserializationError> {
+    let mut value: usize = 0;
+    for _ in 0..off_size {
+        let byte: u8 = c.de()?;
+        value = (value << 8) | byte as usize;
+    }
+    Ok(value)
+}
+
+/// Reads an INDEX structure at the current position of the reader.
+///
+/// CFF2 INDEXes have a 32-bit count, CFF INDEXes a 16-bit count. On return,
+/// the reader is positioned after the end of the INDEX data.
+pub fn read_index(c: &mut ReaderContext, cff2: bool) -> Result<Vec<Vec<u8>>, DeserializationError> {
+    let count: usize = if cff2 {
+        let count: u32 = c.de()?;
+        count as usize
+    } else {
+        let count: u16 = c.de()?;
+        count as usize
+    };
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    let off_size: u8 = c.de()?;
+    if !(1..=4).contains(&off_size) {
+        return Err(DeserializationError(format!(
+            "Bad offSize {} in INDEX",
+            off_size
+        )));
+    }
+    let offsets = (0..=count)
+        .map(|_| read_offset(c, off_size))
+        .collect::<Result<Vec<usize>, DeserializationError>>()?;
+    // Offsets are relative to the byte preceding the object data
+    let base = c.ptr - 1;
+    let mut items = Vec::with_capacity(count);
+    for window in offsets.windows(2) {
+        let (start, end) = (base + window[0], base + window[1]);
+        if window[0] == 0 || end < start || end > c.input.len() {
+            return Err(DeserializationError("Bad offset in INDEX".to_string()));
+        }
+        items.push(c.input[start..end].to_vec());
+    }
+    c.ptr = base + offsets[count];
+    Ok(items)
+}
+
+/// Serializes a list of objects as an INDEX structure.
+pub fn write_index(items: &[Vec<u8>], cff2: bool) -> Vec<u8> {
+    let mut out = vec![];
+    if cff2 {
+        out.extend((items.len() as u32).to_be_bytes());
+    } else {
+        out.extend((items.len() as u16).to_be_bytes());
+    }
+    if items.is_empty() {
+        return out;
+    }
+    let last_offset = 1 + items.iter().map(|x| x.len()).sum::<usize>();
+    let off_size: usize = match last_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x10000..=0xFFFFFF => 3,
+        _ => 4,
+    };
+    out.push(off_size as u8);
+    let mut offset = 1;
+    for item in items.iter().map(|x| x.len()).chain(std::iter::once(0)) {
+        out.extend(&(offset as u32).to_be_bytes()[4 - off_size..]);
+        offset += item;
+    }
+    for item in items {
+        out.extend(item);
+    }
+    out
+}
+
+/// The size in bytes of an INDEX holding the given objects.
+pub fn index_size(items: &[Vec<u8>], cff2: bool) -> usize {
+    write_index(items, cff2).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_roundtrip() {
+        let items = vec![b"abc".to_vec(), vec![], vec![0; 300]];
+        for cff2 in [false, true] {
+            let binary = write_index(&items, cff2);
+            let count_size = if cff2 { 4 } else { 2 };
+            assert_eq!(binary[count_size], 2); // offSize
+            let mut c = ReaderContext::new(binary.clone());
+            assert_eq!(read_index(&mut c, cff2).unwrap(), items);
+            assert_eq!(c.ptr, binary.len());
+        }
+    }
+
+    #[test]
+    fn test_empty_index() {
+        assert_eq!(write_index(&[], false), vec![0, 0]);
+        let mut c = ReaderContext::new(vec![0, 0, 0, 0]);
+        assert!(read_index(&mut c, true).unwrap().is_empty());
+        assert_eq!(c.ptr, 4);
+    }
+
+    #[test]
+    fn test_index_de() {
+        let binary = vec![0x00, 0x02, 0x01, 0x01, 0x03, 0x04, b'h', b'i', b'!'];
+        let mut c = ReaderContext::new(binary);
+        assert_eq!(
+            read_index(&mut c, false).unwrap(),
+            vec![b"hi".to_vec(), b"!".to_vec()]
+        );
+    }
+}
@@ -0,0 +1,684 @@
+//! Subroutinization of CFF and CFF2 charstrings.
+//!
+//! This follows the approach of the `compreffor` library: all charstrings are
+//! concatenated and a suffix array is used to find every repeated sequence
+//! of tokens. Each repeated sequence is a candidate subroutine. We then
+//! repeatedly encode every charstring (and every candidate, so that
+//! subroutines may call other subroutines) optimally using the current set of
+//! candidates, and prune candidates which do not pay for themselves.
+use super::charstring::{
+    encode_charstring, CharStringToken, CALLGSUBR, CALLSUBR, ENDCHAR, MAX_SUBR_NESTING, RETURN,
+    VSINDEX,
+};
+use super::subr_bias;
+use crate::font::Font;
+use otspec::DeserializationError;
+use std::collections::HashMap;
+
+/// The maximum number of subroutines in a single INDEX.
+const MAX_SUBRS: usize = 65535;
+/// The number of rounds of encoding and pruning.
+const ROUNDS: usize = 4;
+/// The approximate overhead of storing a subroutine in an INDEX.
+const INDEX_OVERHEAD: f64 = 2.0;
+
+/// The result of subroutinizing a set of charstrings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subroutinized {
+    /// The encoded charstrings, in the original order.
+    pub charstrings: Vec<Vec<u8>>,
+    /// The global subroutines.
+    pub global_subrs: Vec<Vec<u8>>,
+    /// The local subroutines for each font dict.
+    pub local_subrs: Vec<Vec<Vec<u8>>>,
+}
+
+#[derive(Debug)]
+struct Candidate {
+    /// A position in the text where this candidate occurs
+    start: usize,
+    /// Length in tokens
+    len: usize,
+    active: bool,
+    /// Estimated cost of calling this subroutine
+    call_cost: f64,
+    /// Cost of the subroutine's body, as currently encoded
+    body_cost: f64,
+    /// Subroutines called from within the body, as (offset, candidate)
+    body_calls: Vec<(usize, usize)>,
+    usage: usize,
+    depth: usize,
+}
+
+/// Which subroutine INDEX a candidate was assigned to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assignment {
+    Global(usize),
+    Local(usize, usize),
+}
+
+struct Subroutinizer<'a> {
+    tokens: Vec<&'a CharStringToken>,
+    /// Byte cost of each token in `tokens`
+    token_cost: Vec<f64>,
+    /// Start and end positions of each glyph in `tokens`
+    glyph_ranges: Vec<(usize, usize)>,
+    /// Candidates starting at each position, in CSR layout
+    starts_offsets: Vec<usize>,
+    starts: Vec<usize>,
+    candidates: Vec<Candidate>,
+    glyph_calls: Vec<Vec<(usize, usize)>>,
+    fd_select: Vec<usize>,
+    num_fds: usize,
+    cff2: bool,
+}
+
+/// Computes the suffix array of `text` by prefix doubling.
+fn suffix_array(text: &[u32]) -> Vec<usize> {
+    let n = text.len();
+    let mut sa: Vec<usize> = (0..n).collect();
+    if n == 0 {
+        return sa;
+    }
+    let mut rank: Vec<usize> = text.iter().map(|&x| x as usize).collect();
+    let mut tmp = vec![0; n];
+    let mut k = 1;
+    loop {
+        let key = |i: usize| (rank[i], if i + k < n { rank[i + k] + 1 } else { 0 });
+        sa.sort_unstable_by_key(|&i| key(i));
+        tmp[sa[0]] = 0;
+        for w in 1..n {
+            tmp[sa[w]] = tmp[sa[w - 1]] + usize::from(key(sa[w - 1]) != key(sa[w]));
+        }
+        std::mem::swap(&mut rank, &mut tmp);
+        if rank[sa[n - 1]] == n - 1 {
+            break;
+        }
+        k *= 2;
+    }
+    sa
+}
+
+/// Computes the longest-common-prefix array (Kasai's algorithm): `lcp[i]` is
+/// the length of the common prefix of the suffixes at `sa[i-1]` and `sa[i]`.
+fn lcp_array(text: &[u32], sa: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut rank = vec![0; n];
+    for (i, &s) in sa.iter().enumerate() {
+        rank[s] = i;
+    }
+    let mut lcp = vec![0; n];
+    let mut h = 0;
+    for i in 0..n {
+        if rank[i] > 0 {
+            let j = sa[rank[i] - 1];
+            while i + h < n && j + h < n && text[i + h] == text[j + h] {
+                h += 1;
+            }
+            lcp[rank[i]] = h;
+            h = h.saturating_sub(1);
+        } else {
+            h = 0;
+        }
+    }
+    lcp
+}
+
+fn call_cost(index: usize, count: usize) -> f64 {
+    let biased = index as i32 - subr_bias(count);
+    (CharStringToken::Integer(biased).encoded_len() + 1) as f64
+}
+
+impl<'a> Subroutinizer<'a> {
+    fn new(
+        charstrings: &'a [Vec<CharStringToken>],
+        fd_select: &[usize],
+        num_fds: usize,
+        cff2: bool,
+    ) -> Self {
+        let mut alphabet: HashMap<&CharStringToken, u32> = HashMap::new();
+        let mut text: Vec<u32> = vec![];
+        let mut tokens = vec![];
+        let mut glyph_ranges = vec![];
+        // Tokens which may not appear in a subroutine, and glyph separators,
+        // are given unique values so that they never form part of a repeat.
+        // They are numbered downwards from the top of the range.
+        let mut unique = u32::MAX;
+        for charstring in charstrings {
+            let start = text.len();
+            for token in charstring {
+                let forbidden = matches!(
+                    token,
+                    CharStringToken::Operator(ENDCHAR | RETURN | CALLSUBR | CALLGSUBR | VSINDEX)
+                );
+                if forbidden {
+                    text.push(unique);
+                    unique -= 1;
+                } else {
+                    let next = alphabet.len() as u32;
+                    text.push(*alphabet.entry(token).or_insert(next));
+                }
+                tokens.push(token);
+            }
+            glyph_ranges.push((start, text.len()));
+            text.push(unique);
+            unique -= 1;
+            // Keep positions in `tokens` aligned with `text`
+            tokens.push(&CharStringToken::Operator(ENDCHAR));
+        }
+        // Compress the values to a dense range for the suffix array
+        let mut values: Vec<u32> = text.clone();
+        values.sort_unstable();
+        values.dedup();
+        let dense: Vec<u32> = text
+            .iter()
+            .map(|v| values.binary_search(v).unwrap() as u32)
+            .collect();
+
+        let token_cost: Vec<f64> = tokens.iter().map(|t| t.encoded_len() as f64).collect();
+        let mut prefix_cost = vec![0.0; token_cost.len() + 1];
+        for (i, c) in token_cost.iter().enumerate() {
+            prefix_cost[i + 1] = prefix_cost[i] + c;
+        }
+
+        let sa = suffix_array(&dense);
+        let lcp = lcp_array(&dense, &sa);
+        let return_cost = if cff2 { 0.0 } else { 1.0 };
+
+        // Enumerate the LCP intervals; each one is a repeated sequence.
+        let mut candidates = vec![];
+        let mut occurrences: Vec<Vec<usize>> = vec![];
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        for i in 1..=sa.len() {
+            let current = if i < sa.len() { lcp[i] } else { 0 };
+            let mut lb = i - 1;
+            while current < stack.last().unwrap().0 {
+                let (len, left) = stack.pop().unwrap();
+                lb = left;
+                let positions = &sa[left..i];
+                let start = positions[0];
+                let bytes = prefix_cost[start + len] - prefix_cost[start];
+                let freq = positions.len() as f64;
+                let savings = freq * (bytes - 2.5) - (bytes + return_cost + INDEX_OVERHEAD);
+                if len >= 2 && savings > 0.0 {
+                    candidates.push(Candidate {
+                        start,
+                        len,
+                        active: true,
+                        call_cost: 2.5,
+                        body_cost: bytes,
+                        body_calls: vec![],
+                        usage: 0,
+                        depth: 1,
+                    });
+                    occurrences.push(positions.to_vec());
+                }
+            }
+            if current > stack.last().unwrap().0 {
+                stack.push((current, lb));
+            }
+        }
+
+        let mut counts = vec![0; text.len() + 1];
+        for positions in &occurrences {
+            for &p in positions {
+                counts[p + 1] += 1;
+            }
+        }
+        for i in 1..counts.len() {
+            counts[i] += counts[i - 1];
+        }
+        let mut fill = counts.clone();
+        let mut starts = vec![0; *counts.last().unwrap()];
+        for (c, positions) in occurrences.iter().enumerate() {
+            for &p in positions {
+                starts[fill[p]] = c;
+                fill[p] += 1;
+            }
+        }
+
+        let fd_select = if fd_select.is_empty() {
+            vec![0; charstrings.len()]
+        } else {
+            fd_select.to_vec()
+        };
+
+        Subroutinizer {
+            tokens,
+            token_cost,
+            glyph_ranges,
+            starts_offsets: counts,
+            starts,
+            candidates,
+            glyph_calls: vec![],
+            fd_select,
+            num_fds: num_fds.max(1),
+            cff2,
+        }
+    }
+
+    /// Finds the cheapest encoding of the tokens in `start..end`, returning
+    /// its cost and the subroutine calls it makes. `exclude` is the candidate
+    /// being encoded, if any; `max_depth` limits the nesting of callees.
+    fn encode_range(
+        &self,
+        start: usize,
+        end: usize,
+        exclude: Option<usize>,
+        max_depth: usize,
+    ) -> (f64, Vec<(usize, usize)>) {
+        let len = end - start;
+        let mut cost = vec![0.0; len + 1];
+        let mut choice: Vec<Option<usize>> = vec![None; len + 1];
+        for i in (0..len).rev() {
+            let pos = start + i;
+            let mut best = self.token_cost[pos] + cost[i + 1];
+            let mut best_choice = None;
+            for &c in &self.starts[self.starts_offsets[pos]..self.starts_offsets[pos + 1]] {
+                let cand = &self.candidates[c];
+                if !cand.active
+                    || Some(c) == exclude
+                    || cand.len > len - i
+                    || cand.depth > max_depth
+                {
+                    continue;
+                }
+                let value = cand.call_cost + cost[i + cand.len];
+                if value < best {
+                    best = value;
+                    best_choice = Some(c);
+                }
+            }
+            cost[i] = best;
+            choice[i] = best_choice;
+        }
+        let mut calls = vec![];
+        let mut i = 0;
+        while i < len {
+            if let Some(c) = choice[i] {
+                calls.push((i, c));
+                i += self.candidates[c].len;
+            } else {
+                i += 1;
+            }
+        }
+        (cost[0], calls)
+    }
+
+    /// Encodes all candidate bodies (shortest first) and all glyphs, and
+    /// counts how often each candidate is used.
+    fn encode_all(&mut self, limit_depth: bool) {
+        let mut order: Vec<usize> = (0..self.candidates.len())
+            .filter(|&c| self.candidates[c].active)
+            .collect();
+        order.sort_by_key(|&c| self.candidates[c].len);
+        for &c in &order {
+            let (start, len) = (self.candidates[c].start, self.candidates[c].len);
+            let max_depth = if limit_depth {
+                MAX_SUBR_NESTING - 1
+            } else {
+                usize::MAX
+            };
+            let (cost, calls) = self.encode_range(start, start + len, Some(c), max_depth);
+            let depth = 1 + calls
+                .iter()
+                .map(|&(_, callee)| self.candidates[callee].depth)
+                .max()
+                .unwrap_or(0);
+            let cand = &mut self.candidates[c];
+            cand.body_cost = cost;
+            cand.body_calls = calls;
+            cand.depth = depth;
+            cand.usage = 0;
+        }
+        let max_depth = if limit_depth {
+            MAX_SUBR_NESTING
+        } else {
+            usize::MAX
+        };
+        self.glyph_calls = self
+            .glyph_ranges
+            .iter()
+            .map(|&(start, end)| self.encode_range(start, end, None, max_depth).1)
+            .collect();
+        for calls in &self.glyph_calls {
+            for &(_, c) in calls {
+                self.candidates[c].usage += 1;
+            }
+        }
+        // Longest first, so that a caller's usage is known before its callees'
+        for &c in order.iter().rev() {
+            if self.candidates[c].usage == 0 {
+                continue;
+            }
+            let callees: Vec<usize> = self.candidates[c]
+                .body_calls
+                .iter()
+                .map(|&(_, c)| c)
+                .collect();
+            for callee in callees {
+                self.candidates[callee].usage += 1;
+            }
+        }
+    }
+
+    /// Deactivates candidates which do not save space.
+    fn prune(&mut self) -> bool {
+        let return_cost = if self.cff2 { 0.0 } else { 1.0 };
+        let mut changed = false;
+        for cand in self.candidates.iter_mut().filter(|c| c.active) {
+            let savings = cand.usage as f64 * (cand.body_cost - cand.call_cost)
+                - (cand.body_cost + return_cost + INDEX_OVERHEAD);
+            if savings <= 0.0 {
+                cand.active = false;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Works out which font dicts use each candidate.
+    fn fd_usage(&self) -> Vec<Vec<bool>> {
+        let mut usage = vec![vec![false; self.num_fds]; self.candidates.len()];
+        for (glyph, calls) in self.glyph_calls.iter().enumerate() {
+            let fd = self.fd_select.get(glyph).copied().unwrap_or(0);
+            for &(_, c) in calls {
+                usage[c][fd.min(self.num_fds - 1)] = true;
+            }
+        }
+        let mut order: Vec<usize> = (0..self.candidates.len())
+            .filter(|&c| self.candidates[c].active && self.candidates[c].usage > 0)
+            .collect();
+        order.sort_by_key(|&c| std::cmp::Reverse(self.candidates[c].len));
+        for c in order {
+            let caller = usage[c].clone();
+            for &(_, callee) in &self.candidates[c].body_calls {
+                for (used, &caller_used) in usage[callee].iter_mut().zip(caller.iter()) {
+                    *used |= caller_used;
+                }
+            }
+        }
+        usage
+    }
+
+    /// Assigns the used candidates to the global and local subroutine
+    /// INDEXes, most-used first, and updates their call costs.
+    fn assign(&mut self) -> HashMap<usize, Assignment> {
+        let fd_usage = self.fd_usage();
+        let mut used: Vec<usize> = (0..self.candidates.len())
+            .filter(|&c| self.candidates[c].active && self.candidates[c].usage > 0)
+            .collect();
+        used.sort_by_key(|&c| (std::cmp::Reverse(self.candidates[c].usage), c));
+
+        let mut global: Vec<usize> = vec![];
+        let mut local: Vec<Vec<usize>> = vec![vec![]; self.num_fds];
+        for c in used {
+            let fds: Vec<usize> = (0..self.num_fds).filter(|&fd| fd_usage[c][fd]).collect();
+            if self.num_fds == 1 {
+                // Spread the subroutines across both INDEXes, so that more of
+                // them get the cheap one-byte indices.
+                if local[0].len() < global.len() {
+                    local[0].push(c);
+                } else {
+                    global.push(c);
+                }
+            } else if fds.len() == 1 {
+                local[fds[0]].push(c);
+            } else {
+                global.push(c);
+            }
+        }
+
+        let mut assignments = HashMap::new();
+        // Drop subroutines which don't fit into an INDEX
+        for c in global.iter().skip(MAX_SUBRS) {
+            self.candidates[*c].active = false;
+        }
+        global.truncate(MAX_SUBRS);
+        for (ix, &c) in global.iter().enumerate() {
+            self.candidates[c].call_cost = call_cost(ix, global.len());
+            assignments.insert(c, Assignment::Global(ix));
+        }
+        for (fd, subrs) in local.iter_mut().enumerate() {
+            for c in subrs.iter().skip(MAX_SUBRS) {
+                self.candidates[*c].active = false;
+            }
+            subrs.truncate(MAX_SUBRS);
+            for (ix, &c) in subrs.iter().enumerate() {
+                self.candidates[c].call_cost = call_cost(ix, subrs.len());
+                assignments.insert(c, Assignment::Local(fd, ix));
+            }
+        }
+        assignments
+    }
+
+    fn emit(
+        &self,
+        start: usize,
+        end: usize,
+        calls: &[(usize, usize)],
+        assignments: &HashMap<usize, Assignment>,
+        counts: &(usize, Vec<usize>),
+    ) -> Vec<CharStringToken> {
+        let mut out = vec![];
+        let mut pos = start;
+        for &(offset, c) in calls {
+            out.extend(self.tokens[pos..start + offset].iter().map(|&t| t.clone()));
+            let (index, count, op) = match assignments[&c] {
+                Assignment::Global(ix) => (ix, counts.0, CALLGSUBR),
+                Assignment::Local(fd, ix) => (ix, counts.1[fd], CALLSUBR),
+            };
+            out.push(CharStringToken::Integer(index as i32 - subr_bias(count)));
+            out.push(CharStringToken::Operator(op));
+            pos = start + offset + self.candidates[c].len;
+        }
+        out.extend(self.tokens[pos..end].iter().map(|&t| t.clone()));
+        out
+    }
+
+    fn run(mut self) -> Subroutinized {
+        for _ in 0..ROUNDS {
+            self.encode_all(false);
+            self.prune();
+            self.assign();
+        }
+        // Final encoding, respecting the nesting limit
+        self.encode_all(true);
+        self.prune();
+        let assignments = self.assign();
+        // Anything not given an index can no longer be called
+        for (c, cand) in self.candidates.iter_mut().enumerate() {
+            if !assignments.contains_key(&c) {
+                cand.active = false;
+            }
+        }
+        self.encode_all(true);
+
+        let mut global_count = 0;
+        let mut local_counts = vec![0; self.num_fds];
+        for a in assignments.values() {
+            match a {
+                Assignment::Global(_) => global_count += 1,
+                Assignment::Local(fd, _) => local_counts[*fd] += 1,
+            }
+        }
+        let counts = (global_count, local_counts.clone());
+
+        let mut result = Subroutinized {
+            charstrings: vec![],
+            global_subrs: vec![vec![]; global_count],
+            local_subrs: local_counts.iter().map(|&n| vec![vec![]; n]).collect(),
+        };
+        for (glyph, &(start, end)) in self.glyph_ranges.iter().enumerate() {
+            let tokens = self.emit(start, end, &self.glyph_calls[glyph], &assignments, &counts);
+            result.charstrings.push(encode_charstring(&tokens));
+        }
+        for (&c, assignment) in &assignments {
+            let cand = &self.candidates[c];
+            let mut tokens = self.emit(
+                cand.start,
+                cand.start + cand.len,
+                &cand.body_calls,
+                &assignments,
+                &counts,
+            );
+            if !self.cff2 {
+                tokens.push(CharStringToken::Operator(RETURN));
+            }
+            let encoded = encode_charstring(&tokens);
+            match assignment {
+                Assignment::Global(ix) => result.global_subrs[*ix] = encoded,
+                Assignment::Local(fd, ix) => result.local_subrs[*fd][*ix] = encoded,
+            }
+        }
+        result
+    }
+}
+
+/// Subroutinizes a set of (desubroutinized) charstrings.
+///
+/// `fd_select` gives the font dict index of each charstring; it may be empty
+/// if there is only one font dict. Subroutines used by glyphs of more than
+/// one font dict are placed in the global subroutine INDEX; the others are
+/// spread across the global and local INDEXes. Subroutines may call other
+/// subroutines, up to the nesting limit of the specification.
+pub fn subroutinize(
+    charstrings: &[Vec<CharStringToken>],
+    fd_select: &[usize],
+    num_fds: usize,
+    cff2: bool,
+) -> Subroutinized {
+    Subroutinizer::new(charstrings, fd_select, num_fds, cff2).run()
+}
+
+/// Subroutinizes the `CFF ` and/or `CFF2` table of a font.
+///
+/// Any existing subroutines are expanded first. Returns `false` if the font
+/// has no CFF outlines.
+pub fn subroutinize_font(font: &mut Font) -> Result<bool, DeserializationError> {
+    let mut found = false;
+    if let Some(mut cff) = font.tables.CFF()? {
+        cff.subroutinize()?;
+        font.tables.insert(cff);
+        found = true;
+    }
+    if let Some(mut cff2) = font.tables.CFF2()? {
+        cff2.subroutinize()?;
+        font.tables.insert(cff2);
+        found = true;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cff::charstring::{desubroutinize, RLINETO, RMOVETO, RRCURVETO};
+
+    fn glyph(offset: i32) -> Vec<CharStringToken> {
+        let mut tokens = vec![
+            CharStringToken::Integer(500),
+            CharStringToken::Integer(offset),
+            CharStringToken::Integer(0),
+            CharStringToken::Operator(RMOVETO),
+        ];
+        for i in 0..5 {
+            tokens.extend([
+                CharStringToken::Integer(100 + i),
+                CharStringToken::Integer(200),
+                CharStringToken::Integer(-300),
+                CharStringToken::Integer(400),
+                CharStringToken::Integer(500),
+                CharStringToken::Integer(-600),
+                CharStringToken::Operator(RRCURVETO),
+            ]);
+        }
+        tokens.extend([
+            CharStringToken::Integer(offset),
+            CharStringToken::Integer(1000),
+            CharStringToken::Operator(RLINETO),
+            CharStringToken::Operator(ENDCHAR),
+        ]);
+        tokens
+    }
+
+    #[test]
+    fn test_suffix_array() {
+        // "banana"
+        let text = vec![1, 0, 2, 0, 2, 0];
+        let sa = suffix_array(&text);
+        assert_eq!(sa, vec![5, 3, 1, 0, 4, 2]);
+        assert_eq!(lcp_array(&text, &sa), vec![0, 1, 3, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_subroutinize() {
+        let charstrings: Vec<Vec<CharStringToken>> = (0..20).map(glyph).collect();
+        let original_size: usize = charstrings.iter().map(|c| encode_charstring(c).len()).sum();
+        let result = subroutinize(&charstrings, &[], 1, false);
+        assert!(!result.global_subrs.is_empty() || !result.local_subrs[0].is_empty());
+        let new_size: usize = result.charstrings.iter().map(|c| c.len()).sum::<usize>()
+            + result.global_subrs.iter().map(|c| c.len()).sum::<usize>()
+            + result.local_subrs[0].iter().map(|c| c.len()).sum::<usize>();
+        assert!(new_size < original_size / 2);
+        for (original, encoded) in charstrings.iter().zip(result.charstrings.iter()) {
+            let expanded = desubroutinize(
+                encoded,
+                &result.global_subrs,
+                &result.local_subrs[0],
+                false,
+                &[],
+            )
+            .unwrap();
+            assert_eq!(&expanded, original);
+        }
+    }
+
+    #[test]
+    fn test_subroutinize_fds() {
+        let charstrings: Vec<Vec<CharStringToken>> = (0..20).map(|i| glyph(i % 3)).collect();
+        let fd_select: Vec<usize> = (0..20).map(|i| i % 2).collect();
+        let result = subroutinize(&charstrings, &fd_select, 2, true);
+        assert_eq!(result.local_subrs.len(), 2);
+        for (glyph, (original, encoded)) in charstrings
+            .iter()
+            .zip(result.charstrings.iter())
+            .enumerate()
+        {
+            let local = &result.local_subrs[fd_select[glyph]];
+            let expanded = desubroutinize(encoded, &result.global_subrs, local, true, &[]).unwrap();
+            assert_eq!(&expanded, original);
+        }
+    }
+
+    #[test]
+    fn test_subroutinize_font() {
+        use crate::cff::FontDict;
+        use crate::font::SfntVersion;
+        use crate::tables::CFF::CFF;
+
+        let charstrings: Vec<Vec<CharStringToken>> = (0..20).map(glyph).collect();
+        let cff = CFF {
+            name: "Test-Regular".to_string(),
+            glyph_names: (0..20).map(|i| format!("glyph{}", i)).collect(),
+            charstrings: charstrings.iter().map(|c| encode_charstring(c)).collect(),
+            font_dicts: vec![FontDict::default()],
+            ..Default::default()
+        };
+        let mut font = Font::new(SfntVersion::OpenType);
+        assert!(!subroutinize_font(&mut font).unwrap());
+        font.tables.insert(cff);
+        assert!(subroutinize_font(&mut font).unwrap());
+
+        let cff = font.tables.CFF().unwrap().unwrap();
+        assert!(!cff.global_subrs.is_empty() || !cff.font_dicts[0].subrs.is_empty());
+        let binary = otspec::ser::to_bytes(&*cff).unwrap();
+        let deserialized: CFF = otspec::de::from_bytes(&binary).unwrap();
+        for (glyph, original) in charstrings.iter().enumerate() {
+            assert_eq!(
+                &deserialized.desubroutinized_charstring(glyph).unwrap(),
+                original
+            );
+        }
+    }
+}
@@ -0,0 +1,1088 @@
+//! Decompiling layout tables to OpenType feature files
+//!
+//! [`decompile`] writes a font's `GSUB` and `GPOS` tables in the Adobe
+//! feature file syntax read by `feaLib` and `makeotf`, so that the layout
+//! rules of a compiled font can be read, edited and compiled again. Glyphs
+//! are named from the `post` table or, failing that, the `cmap` table. Each
+//! lookup becomes a named `lookup` block which the features refer to, sets
+//! of glyphs become named glyph classes, and the `GDEF` glyph classes, mark
+//! attachment classes and mark filtering sets are written too, so that the
+//! result compiles to equivalent tables.
+//!
+//! The subtables of a lookup are merged into one list of rules; where two
+//! subtables have a rule for the same glyph, the first one wins, as it does
+//! when the font is used. Pair positioning subtables are kept apart with
+//! `subtable` statements, and lookups are renumbered if a contextual lookup
+//! refers to one defined after it.
+//!
+//! The device tables and variation indices of value records are not
+//! written: a rule which loses them is marked with a
+//! `# device/variation data dropped` comment, and a warning is logged for
+//! its lookup. Anchors with device tables can't be read at all.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use fonttools::fea;
+//! use fonttools::font::Font;
+//!
+//! let font = Font::load("Test.ttf").unwrap();
+//! std::fs::write("Test.fea", fea::decompile(&font).unwrap()).unwrap();
+//! ```
+use crate::font::Font;
+use crate::layout::common::{Lookup, LookupFlags, GPOSGSUB};
+use crate::layout::contextual::{
+    ChainedSequenceContext, SequenceContext, SequenceContextRule, Slot,
+};
+use crate::tables::GDEF::{CaretValue, GlyphClass, GDEF};
+use crate::ttx::GlyphOrder;
+use otspec::types::{GlyphID, Tag};
+use otspec::DeserializationError;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+/// Writing positioning lookups
+mod gpos;
+/// Writing substitution lookups
+mod gsub;
+
+/// An error decompiling a font's layout tables
+#[derive(Debug)]
+pub enum FeaError {
+    /// A table in the font could not be read
+    Table(String),
+}
+
+impl std::fmt::Display for FeaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeaError::Table(e) => write!(f, "Table error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FeaError {}
+
+impl From<DeserializationError> for FeaError {
+    fn from(e: DeserializationError) -> Self {
+        FeaError::Table(e.0)
+    }
+}
+
+/// Words which have to be escaped with a backslash to be used as glyph names
+const KEYWORDS: &[&str] = &[
+    "anchor",
+    "anchorDef",
+    "anon",
+    "anonymous",
+    "base",
+    "by",
+    "contour",
+    "contourpoint",
+    "cursive",
+    "device",
+    "enum",
+    "enumerate",
+    "exclude_dflt",
+    "excludeDFLT",
+    "feature",
+    "from",
+    "ignore",
+    "IgnoreBaseGlyphs",
+    "IgnoreLigatures",
+    "IgnoreMarks",
+    "include",
+    "include_dflt",
+    "includeDFLT",
+    "language",
+    "languagesystem",
+    "ligature",
+    "ligComponent",
+    "lookup",
+    "lookupflag",
+    "mark",
+    "MarkAttachmentType",
+    "markClass",
+    "nameid",
+    "NULL",
+    "parameters",
+    "pos",
+    "position",
+    "required",
+    "reversesub",
+    "RightToLeft",
+    "rsub",
+    "script",
+    "sub",
+    "substitute",
+    "subtable",
+    "table",
+    "useExtension",
+    "UseMarkFilteringSet",
+    "valueRecordDef",
+];
+
+/// Features in which a single number in a value record is a vertical
+/// advance rather than a horizontal one
+const VERTICAL_FEATURES: &[&str] = &["vkrn", "vpal", "vhal", "valt"];
+
+/// Converts a font's `GSUB` and `GPOS` tables to feature file syntax.
+pub fn decompile(font: &Font) -> Result<String, FeaError> {
+    let num_glyphs = font
+        .tables
+        .maxp()?
+        .map(|maxp| maxp.num_glyphs() as usize)
+        .or(font.tables.glyf()?.map(|glyf| glyf.glyphs.len()))
+        .unwrap_or(0);
+    let order =
+        GlyphOrder::from_font(font, num_glyphs).map_err(|e| FeaError::Table(e.to_string()))?;
+    let gsub = font.tables.GSUB()?;
+    let gpos = font.tables.GPOS()?;
+    let gdef = font.tables.GDEF()?;
+
+    let mut language_systems = BTreeSet::new();
+    if let Some(gsub) = &gsub {
+        language_systems.extend(table_language_systems(gsub));
+    }
+    if let Some(gpos) = &gpos {
+        language_systems.extend(table_language_systems(gpos));
+    }
+
+    let mut decompiler = Decompiler::new(&order, gdef.as_deref(), language_systems);
+    let mut lookups = String::new();
+    let mut features = String::new();
+    if let Some(gsub) = &gsub {
+        decompiler.write_table("GSUB", gsub, &mut lookups, &mut features);
+    }
+    if let Some(gpos) = &gpos {
+        decompiler.write_table("GPOS", gpos, &mut lookups, &mut features);
+    }
+    let gdef_block = decompiler.gdef_block();
+
+    let mut fea = String::new();
+    writeln!(
+        fea,
+        "# Decompiled by fonttools-rs {}\n",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    for (script, language) in &decompiler.language_systems {
+        writeln!(
+            fea,
+            "languagesystem {} {};",
+            tag_name(script),
+            language.as_ref().map_or("dflt", tag_name)
+        )
+        .unwrap();
+    }
+    if !decompiler.language_systems.is_empty() {
+        fea.push('\n');
+    }
+    for (name, glyphs) in &decompiler.classes {
+        let names: Vec<String> = glyphs.iter().map(|&gid| decompiler.glyph(gid)).collect();
+        writeln!(fea, "{}", wrap(&format!("{} = [", name), &names, "];")).unwrap();
+    }
+    if !decompiler.classes.is_empty() {
+        fea.push('\n');
+    }
+    if !decompiler.mark_classes.is_empty() {
+        fea.push_str(&decompiler.mark_classes);
+        fea.push('\n');
+    }
+    fea.push_str(&lookups);
+    fea.push_str(&features);
+    if let Some(block) = gdef_block {
+        fea.push_str(&block);
+    }
+    fea.truncate(fea.trim_end().len());
+    fea.push('\n');
+    Ok(fea)
+}
+
+/// A script and a language (`None` for the script's default language)
+type LanguageSystem = (Tag, Option<Tag>);
+
+fn table_language_systems<T>(table: &GPOSGSUB<T>) -> Vec<LanguageSystem> {
+    let mut systems = vec![];
+    for (script_tag, script) in &table.scripts.scripts {
+        if script.default_language_system.is_some() {
+            systems.push((*script_tag, None));
+        }
+        for language_tag in script.language_systems.keys() {
+            systems.push((*script_tag, Some(*language_tag)));
+        }
+    }
+    systems
+}
+
+/// Tags are written without the spaces which pad them to four characters
+fn tag_name(tag: &Tag) -> &str {
+    tag.as_str().trim()
+}
+
+/// Writes a list of glyph names between `start` and `end`, breaking it into
+/// indented lines if it is long
+fn wrap(start: &str, names: &[String], end: &str) -> String {
+    let mut text = start.to_string();
+    let mut line_length = text.len();
+    for (index, name) in names.iter().enumerate() {
+        if index > 0 {
+            if line_length + 1 + name.len() > 78 {
+                text.push_str("\n   ");
+                line_length = 3;
+            }
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(name);
+        line_length += name.len();
+    }
+    text.push_str(end);
+    text
+}
+
+/// A name which can be used for a glyph in a feature file, or `None`
+fn fea_glyph_name(name: &str) -> Option<String> {
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if !(first.is_ascii_alphabetic() || first == '_' || first == '.')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || "._*+-:^|~".contains(c))
+    {
+        return None;
+    }
+    if KEYWORDS.contains(&name) {
+        Some(format!("\\{}", name))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Everything the rules of a lookup need to know about it
+pub(crate) struct LookupContext {
+    /// The lookup's name, which is also used to name its glyph classes
+    name: String,
+    /// `GSUB` or `GPOS`, used to name the lookups it refers to
+    table: &'static str,
+    /// Whether a single number in a value record is a vertical advance
+    vertical: bool,
+}
+
+impl LookupContext {
+    fn lookup_name(&self, index: u16) -> String {
+        format!("{}_{}", self.table, index)
+    }
+}
+
+/// The rules of the lookups of one layout table
+pub(crate) trait FeaRules {
+    /// `sub` or `pos`
+    const KEYWORD: &'static str;
+
+    /// The lookups which this lookup's contextual rules refer to
+    fn nested_lookups(&self) -> Vec<u16>;
+
+    /// Writes the lookup's rules, one statement to a line
+    fn write_rules(&self, decompiler: &mut Decompiler, lookup: &LookupContext, out: &mut String);
+
+    /// The lookup's rules as they are written inside an `aalt` feature, if
+    /// it can be used there
+    fn aalt_rules(&self, _decompiler: &Decompiler) -> Option<String> {
+        None
+    }
+}
+
+/// Merges the mappings of a lookup's subtables. A glyph only ever uses
+/// the first subtable which covers it, so the first mapping wins.
+pub(crate) fn merge_subtables<'a, K: Ord + Clone + 'a, V: 'a>(
+    mappings: impl Iterator<Item = &'a BTreeMap<K, V>>,
+) -> BTreeMap<K, &'a V> {
+    let mut merged = BTreeMap::new();
+    for mapping in mappings {
+        for (key, value) in mapping {
+            merged.entry(key.clone()).or_insert(value);
+        }
+    }
+    merged
+}
+
+pub(crate) fn contextual_lookups(subtables: &[SequenceContext]) -> Vec<u16> {
+    subtables
+        .iter()
+        .flat_map(|subtable| &subtable.rules)
+        .flat_map(|rule| rule.iter().flat_map(|(_, lookups)| lookups.iter().copied()))
+        .collect()
+}
+
+pub(crate) fn chained_contextual_lookups(subtables: &[ChainedSequenceContext]) -> Vec<u16> {
+    subtables
+        .iter()
+        .flat_map(|subtable| &subtable.rules)
+        .flat_map(|rule| {
+            rule.input
+                .iter()
+                .flat_map(|(_, lookups)| lookups.iter().copied())
+        })
+        .collect()
+}
+
+/// The features registered for one language system, and their lookups
+struct FeatureEntry {
+    language_system: LanguageSystem,
+    lookups: Vec<usize>,
+    required: bool,
+}
+
+pub(crate) struct Decompiler<'a> {
+    names: Vec<String>,
+    gdef: Option<&'a GDEF>,
+    language_systems: BTreeSet<LanguageSystem>,
+    /// Named glyph classes, in the order they were first used
+    classes: Vec<(String, Vec<GlyphID>)>,
+    class_ids: HashMap<Vec<GlyphID>, usize>,
+    class_counts: HashMap<String, usize>,
+    /// `markClass` statements, which come before the lookups using them
+    mark_classes: String,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(
+        order: &GlyphOrder,
+        gdef: Option<&'a GDEF>,
+        language_systems: BTreeSet<LanguageSystem>,
+    ) -> Self {
+        // Glyph names which can't be written in a feature file, or which
+        // clash with another glyph's, are replaced with glyphNNNNN names
+        let mut used = HashSet::new();
+        let mut names = Vec::with_capacity(order.len());
+        for gid in 0..order.len() {
+            let fallback = format!("glyph{:05}", gid);
+            let mut name = fea_glyph_name(&order.name(gid as u16))
+                .filter(|name| !used.contains(name))
+                .unwrap_or_else(|| fallback.clone());
+            let mut suffix = 1;
+            while used.contains(&name) {
+                name = format!("{}.{}", fallback, suffix);
+                suffix += 1;
+            }
+            used.insert(name.clone());
+            names.push(name);
+        }
+        Decompiler {
+            names,
+            gdef,
+            language_systems,
+            classes: vec![],
+            class_ids: HashMap::new(),
+            class_counts: HashMap::new(),
+            mark_classes: String::new(),
+        }
+    }
+
+    pub(crate) fn glyph(&self, gid: GlyphID) -> String {
+        self.names
+            .get(gid as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("glyph{:05}", gid))
+    }
+
+    /// Glyph names separated by spaces
+    pub(crate) fn glyph_list<'g>(&self, gids: impl IntoIterator<Item = &'g GlyphID>) -> String {
+        gids.into_iter()
+            .map(|&gid| self.glyph(gid))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// A glyph class written out in full, for lists whose order matters
+    pub(crate) fn inline_class<'g>(&self, gids: impl IntoIterator<Item = &'g GlyphID>) -> String {
+        format!("[{}]", self.glyph_list(gids))
+    }
+
+    /// A single glyph's name, or the name of a glyph class holding the
+    /// glyphs. Classes are shared between rules which use the same glyphs;
+    /// new ones are named after `hint`.
+    pub(crate) fn class(&mut self, glyphs: Vec<GlyphID>, hint: &str) -> String {
+        if glyphs.len() == 1 {
+            return self.glyph(glyphs[0]);
+        }
+        if let Some(&id) = self.class_ids.get(&glyphs) {
+            return self.classes[id].0.clone();
+        }
+        let count = self.class_counts.entry(hint.to_string()).or_insert(0);
+        *count += 1;
+        let name = format!("@{}_{}", hint, count);
+        self.add_class(glyphs, name)
+    }
+
+    /// Like [`Decompiler::class`], but always a class, and with the given
+    /// name if the glyphs aren't in a class already
+    fn named_class(&mut self, glyphs: Vec<GlyphID>, name: String) -> String {
+        match self.class_ids.get(&glyphs) {
+            Some(&id) => self.classes[id].0.clone(),
+            None => self.add_class(glyphs, name),
+        }
+    }
+
+    fn add_class(&mut self, glyphs: Vec<GlyphID>, name: String) -> String {
+        self.class_ids.insert(glyphs.clone(), self.classes.len());
+        self.classes.push((name.clone(), glyphs));
+        name
+    }
+
+    pub(crate) fn slot(&mut self, slot: &Slot, hint: &str) -> String {
+        self.class(slot.iter().copied().collect(), hint)
+    }
+
+    pub(crate) fn add_mark_class(&mut self, statement: String) {
+        self.mark_classes.push_str(&statement);
+        self.mark_classes.push('\n');
+    }
+
+    /// Writes a (chained) contextual rule. A rule which calls no lookups
+    /// is an exception, written with `ignore`.
+    fn contextual_rule(
+        &mut self,
+        keyword: &str,
+        lookup: &LookupContext,
+        backtrack: &[Slot],
+        input: &SequenceContextRule,
+        lookahead: &[Slot],
+        out: &mut String,
+    ) {
+        let ignore = input.iter().all(|(_, lookups)| lookups.is_empty());
+        let mut parts = vec![];
+        // The backtrack sequence is stored starting from the glyph nearest
+        // the input, but written in text order
+        for slot in backtrack.iter().rev() {
+            parts.push(self.slot(slot, &lookup.name));
+        }
+        for (slot, lookups) in input {
+            let mut part = format!("{}'", self.slot(slot, &lookup.name));
+            for &nested in lookups {
+                write!(part, " lookup {}", lookup.lookup_name(nested)).unwrap();
+            }
+            parts.push(part);
+        }
+        for slot in lookahead {
+            parts.push(self.slot(slot, &lookup.name));
+        }
+        writeln!(
+            out,
+            "    {}{} {};",
+            if ignore { "ignore " } else { "" },
+            keyword,
+            parts.join(" ")
+        )
+        .unwrap();
+    }
+
+    pub(crate) fn write_contextual(
+        &mut self,
+        keyword: &str,
+        lookup: &LookupContext,
+        subtables: &[SequenceContext],
+        out: &mut String,
+    ) {
+        for rule in subtables.iter().flat_map(|subtable| &subtable.rules) {
+            self.contextual_rule(keyword, lookup, &[], rule, &[], out);
+        }
+    }
+
+    pub(crate) fn write_chained_contextual(
+        &mut self,
+        keyword: &str,
+        lookup: &LookupContext,
+        subtables: &[ChainedSequenceContext],
+        out: &mut String,
+    ) {
+        for rule in subtables.iter().flat_map(|subtable| &subtable.rules) {
+            self.contextual_rule(
+                keyword,
+                lookup,
+                &rule.backtrack,
+                &rule.input,
+                &rule.lookahead,
+                out,
+            );
+        }
+    }
+
+    /// The `lookupflag` statement for a lookup, if it needs one
+    fn lookup_flags<T>(&mut self, lookup: &Lookup<T>) -> Option<String> {
+        let flags = lookup.flags;
+        if flags.is_empty() {
+            return None;
+        }
+        let mut parts = vec![];
+        for (flag, name) in [
+            (LookupFlags::RIGHT_TO_LEFT, "RightToLeft"),
+            (LookupFlags::IGNORE_BASE_GLYPHS, "IgnoreBaseGlyphs"),
+            (LookupFlags::IGNORE_LIGATURES, "IgnoreLigatures"),
+            (LookupFlags::IGNORE_MARKS, "IgnoreMarks"),
+        ] {
+            if flags.contains(flag) {
+                parts.push(name.to_string());
+            }
+        }
+        // Mark attachment classes and filtering sets are written as the
+        // glyph classes they stand for; if they aren't in GDEF, all we can
+        // do is write the flags as a number
+        let numeric = Some(format!("lookupflag {};", flags.bits()));
+        let attachment_type = (flags & LookupFlags::MARK_ATTACHMENT_TYPE_MASK).bits() >> 8;
+        if attachment_type != 0 {
+            let glyphs: Vec<GlyphID> = self
+                .gdef
+                .into_iter()
+                .flat_map(|gdef| &gdef.mark_attachment_class)
+                .filter(|(_, &class)| class == attachment_type)
+                .map(|(&gid, _)| gid)
+                .collect();
+            if glyphs.is_empty() {
+                return numeric;
+            }
+            let name = format!("@MarkAttachClass{}", attachment_type);
+            parts.push(format!(
+                "MarkAttachmentType {}",
+                self.named_class(glyphs, name)
+            ));
+        }
+        if flags.contains(LookupFlags::USE_MARK_FILTERING_SET) {
+            let set = lookup.mark_filtering_set.and_then(|index| {
+                self.gdef
+                    .and_then(|gdef| gdef.mark_glyph_sets.as_ref())
+                    .and_then(|sets| sets.get(index as usize))
+                    .map(|set| (index, set.iter().copied().collect::<Vec<_>>()))
+            });
+            match set {
+                Some((index, glyphs)) => {
+                    let name = format!("@MarkFilteringSet{}", index);
+                    parts.push(format!(
+                        "UseMarkFilteringSet {}",
+                        self.named_class(glyphs, name)
+                    ));
+                }
+                None => return numeric,
+            }
+        }
+        Some(format!("lookupflag {};", parts.join(" ")))
+    }
+
+    fn write_table<T: FeaRules>(
+        &mut self,
+        table_tag: &'static str,
+        table: &GPOSGSUB<T>,
+        lookups_out: &mut String,
+        features_out: &mut String,
+    ) {
+        let features = feature_entries(table);
+        let vertical: HashSet<usize> = features
+            .iter()
+            .filter(|(tag, _)| VERTICAL_FEATURES.contains(&tag.as_str()))
+            .flat_map(|(_, entries)| entries.iter().flat_map(|entry| entry.lookups.clone()))
+            .collect();
+
+        // Lookups which are only used by `aalt` have their rules written
+        // inside it instead
+        let mut used_elsewhere: HashSet<usize> = table
+            .lookups
+            .iter()
+            .flat_map(|lookup| lookup.rule.nested_lookups())
+            .map(|index| index as usize)
+            .collect();
+        let mut aalt_only = HashSet::new();
+        for (tag, entries) in &features {
+            let lookups = entries
+                .iter()
+                .flat_map(|entry| entry.lookups.iter().copied());
+            if tag == "aalt" {
+                aalt_only.extend(lookups);
+            } else {
+                used_elsewhere.extend(lookups);
+            }
+        }
+        aalt_only.retain(|index| !used_elsewhere.contains(index));
+
+        let mut written = vec![false; table.lookups.len()];
+        for index in 0..table.lookups.len() {
+            if !aalt_only.contains(&index) {
+                self.write_lookup(
+                    table_tag,
+                    table,
+                    index,
+                    &vertical,
+                    &mut written,
+                    lookups_out,
+                );
+            }
+        }
+
+        for (tag, entries) in &features {
+            if tag == "aalt" {
+                self.write_aalt(table, entries, features_out);
+            } else {
+                self.write_feature(table_tag, tag, entries, features_out);
+            }
+        }
+    }
+
+    /// Writes a lookup block, after any lookups it refers to, since a
+    /// lookup has to be defined before it can be used
+    fn write_lookup<T: FeaRules>(
+        &mut self,
+        table_tag: &'static str,
+        table: &GPOSGSUB<T>,
+        index: usize,
+        vertical: &HashSet<usize>,
+        written: &mut Vec<bool>,
+        out: &mut String,
+    ) {
+        if written[index] {
+            return;
+        }
+        written[index] = true;
+        let lookup = &table.lookups[index];
+        for nested in lookup.rule.nested_lookups() {
+            if (nested as usize) < table.lookups.len() {
+                self.write_lookup(table_tag, table, nested as usize, vertical, written, out);
+            }
+        }
+
+        let context = LookupContext {
+            name: format!("{}_{}", table_tag, index),
+            table: table_tag,
+            vertical: vertical.contains(&index),
+        };
+        writeln!(
+            out,
+            "lookup {}{} {{",
+            context.name,
+            if lookup.extension {
+                " useExtension"
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+        if let Some(flags) = self.lookup_flags(lookup) {
+            writeln!(out, "    {}", flags).unwrap();
+        }
+        lookup.rule.write_rules(self, &context, out);
+        writeln!(out, "}} {};\n", context.name).unwrap();
+    }
+
+    fn write_feature(
+        &mut self,
+        table_tag: &str,
+        tag: &Tag,
+        entries: &[FeatureEntry],
+        out: &mut String,
+    ) {
+        if entries.is_empty() {
+            // No language system uses the feature
+            return;
+        }
+        let tag = tag_name(tag);
+        writeln!(out, "feature {} {{", tag).unwrap();
+        // If the feature does the same thing for every language system,
+        // there is no need to list them
+        let everywhere = entries.len() == self.language_systems.len()
+            && entries
+                .iter()
+                .all(|entry| !entry.required && entry.lookups == entries[0].lookups)
+            && entries
+                .iter()
+                .all(|entry| self.language_systems.contains(&entry.language_system));
+        if everywhere {
+            for index in &entries[0].lookups {
+                writeln!(out, "    lookup {}_{};", table_tag, index).unwrap();
+            }
+        } else {
+            let mut script = None;
+            let mut default_lookups: &[usize] = &[];
+            for entry in entries {
+                let (script_tag, language_tag) = &entry.language_system;
+                if script != Some(script_tag) {
+                    writeln!(out, "    script {};", tag_name(script_tag)).unwrap();
+                    script = Some(script_tag);
+                    default_lookups = &[];
+                }
+                let required = if entry.required { " required" } else { "" };
+                let lookups: Vec<usize> = match language_tag {
+                    // A script statement starts with the default language
+                    None => {
+                        if entry.required {
+                            writeln!(out, "    language dflt required;").unwrap();
+                        }
+                        default_lookups = &entry.lookups;
+                        entry.lookups.clone()
+                    }
+                    // Other languages get the default language's lookups
+                    // as well as their own, unless told not to
+                    Some(language)
+                        if default_lookups
+                            .iter()
+                            .all(|index| entry.lookups.contains(index)) =>
+                    {
+                        writeln!(out, "    language {}{};", tag_name(language), required).unwrap();
+                        entry
+                            .lookups
+                            .iter()
+                            .filter(|index| !default_lookups.contains(index))
+                            .copied()
+                            .collect()
+                    }
+                    Some(language) => {
+                        writeln!(
+                            out,
+                            "    language {} exclude_dflt{};",
+                            tag_name(language),
+                            required
+                        )
+                        .unwrap();
+                        entry.lookups.clone()
+                    }
+                };
+                for index in lookups {
+                    writeln!(out, "    lookup {}_{};", table_tag, index).unwrap();
+                }
+            }
+        }
+        writeln!(out, "}} {};\n", tag).unwrap();
+    }
+
+    /// `aalt` can only contain single and alternate substitutions, which
+    /// are compiled into lookups of its own
+    fn write_aalt<T: FeaRules>(
+        &mut self,
+        table: &GPOSGSUB<T>,
+        entries: &[FeatureEntry],
+        out: &mut String,
+    ) {
+        let lookups: BTreeSet<usize> = entries
+            .iter()
+            .flat_map(|entry| entry.lookups.iter().copied())
+            .collect();
+        writeln!(out, "feature aalt {{").unwrap();
+        for index in lookups {
+            match table
+                .lookups
+                .get(index)
+                .and_then(|lookup| lookup.rule.aalt_rules(self))
+            {
+                Some(rules) => out.push_str(&rules),
+                None => writeln!(out, "    # Lookup {} can't be written in aalt", index).unwrap(),
+            }
+        }
+        writeln!(out, "}} aalt;\n").unwrap();
+    }
+
+    fn gdef_block(&mut self) -> Option<String> {
+        let gdef = self.gdef?;
+        let mut statements = String::new();
+        if !gdef.glyph_class.is_empty() {
+            let mut classes = vec![];
+            for (class, name) in [
+                (GlyphClass::BaseGlyph, "@GDEF_Base"),
+                (GlyphClass::LigatureGlyph, "@GDEF_Ligature"),
+                (GlyphClass::MarkGlyph, "@GDEF_Mark"),
+                (GlyphClass::ComponentGlyph, "@GDEF_Component"),
+            ] {
+                let glyphs: Vec<GlyphID> = gdef
+                    .glyph_class
+                    .iter()
+                    .filter(|(_, &glyph_class)| glyph_class == class)
+                    .map(|(&gid, _)| gid)
+                    .collect();
+                classes.push(if glyphs.is_empty() {
+                    String::new()
+                } else {
+                    self.named_class(glyphs, name.to_string())
+                });
+            }
+            writeln!(statements, "    GlyphClassDef {};", classes.join(", ")).unwrap();
+        }
+        for (&gid, points) in &gdef.attachment_point_list {
+            let points: Vec<String> = points.iter().map(|p| p.to_string()).collect();
+            writeln!(
+                statements,
+                "    Attach {} {};",
+                self.glyph(gid),
+                points.join(" ")
+            )
+            .unwrap();
+        }
+        for (&gid, carets) in &gdef.ligature_caret_list {
+            let mut positions = vec![];
+            let mut indices = vec![];
+            for caret in carets {
+                match caret {
+                    CaretValue::Format1 { coordinate } | CaretValue::Format3 { coordinate, .. } => {
+                        positions.push(coordinate.to_string())
+                    }
+                    CaretValue::Format2 { pointIndex } => indices.push(pointIndex.to_string()),
+                }
+            }
+            for (statement, values) in [
+                ("LigatureCaretByPos", positions),
+                ("LigatureCaretByIndex", indices),
+            ] {
+                if !values.is_empty() {
+                    writeln!(
+                        statements,
+                        "    {} {} {};",
+                        statement,
+                        self.glyph(gid),
+                        values.join(" ")
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        if statements.is_empty() {
+            return None;
+        }
+        Some(format!("table GDEF {{\n{}}} GDEF;\n", statements))
+    }
+}
+
+/// The lookups of each feature for each language system, by feature tag in
+/// the order the features first appear
+fn feature_entries<T>(table: &GPOSGSUB<T>) -> Vec<(Tag, Vec<FeatureEntry>)> {
+    let mut tags: Vec<Tag> = vec![];
+    for (tag, _, _) in table.features.iter() {
+        if !tags.contains(tag) {
+            tags.push(*tag);
+        }
+    }
+    let mut language_systems = vec![];
+    for (script_tag, script) in &table.scripts.scripts {
+        if let Some(default) = &script.default_language_system {
+            language_systems.push(((*script_tag, None), default));
+        }
+        for (language_tag, language) in &script.language_systems {
+            language_systems.push(((*script_tag, Some(*language_tag)), language));
+        }
+    }
+
+    tags.into_iter()
+        .map(|tag| {
+            let feature_lookups = |index: usize| {
+                table
+                    .features
+                    .get(index)
+                    .filter(|(feature_tag, _, _)| *feature_tag == tag)
+                    .map(|(_, lookups, _)| lookups.clone())
+            };
+            let entries = language_systems
+                .iter()
+                .filter_map(|(language_system, language)| {
+                    let required = language.required_feature.and_then(feature_lookups);
+                    let mut lookups: Vec<usize> = language
+                        .feature_indices
+                        .iter()
+                        .filter_map(|&index| feature_lookups(index))
+                        .flatten()
+                        .chain(required.clone().into_iter().flatten())
+                        .collect();
+                    lookups.sort_unstable();
+                    lookups.dedup();
+                    (!lookups.is_empty()).then(|| FeatureEntry {
+                        language_system: *language_system,
+                        lookups,
+                        required: required.is_some(),
+                    })
+                })
+                .collect();
+            (tag, entries)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::common::ValueRecord;
+    use crate::layout::contextual::ChainedSequenceContextRule;
+    use crate::layout::gpos1::SinglePos;
+    use crate::layout::gpos2::PairPos;
+    use crate::layout::gpos4::MarkBasePos;
+    use crate::layout::gsub1::SingleSubst;
+    use crate::tables::GPOS::Positioning;
+    use crate::tables::GSUB::Substitution;
+    use otspec::layout::anchor::Anchor;
+    use otspec::layout::device::Device;
+    use otspec::types::Offset16;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_glyph_names() {
+        assert_eq!(fea_glyph_name("f_i").unwrap(), "f_i");
+        assert_eq!(fea_glyph_name(".notdef").unwrap(), ".notdef");
+        assert_eq!(fea_glyph_name("by").unwrap(), "\\by");
+        assert!(fea_glyph_name("1st").is_none());
+        assert!(fea_glyph_name("a#1").is_none());
+        assert!(fea_glyph_name("").is_none());
+    }
+
+    #[test]
+    fn test_decompile() {
+        let font = crate::ttx::tests::test_font();
+        let fea = decompile(&font).unwrap();
+        assert!(fea.contains("languagesystem DFLT dflt;\n"));
+        assert!(fea.contains("lookup GSUB_0 {\n    sub f i by f_i;\n} GSUB_0;\n"));
+        assert!(fea.contains("feature liga {\n    lookup GSUB_0;\n} liga;\n"));
+        assert!(fea.contains("pos A V -100;\n"));
+        assert!(fea.contains("feature kern {\n    lookup GPOS_0;\n} kern;\n"));
+    }
+
+    fn lookup<T>(rule: T) -> Lookup<T> {
+        Lookup {
+            flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            extension: false,
+            rule,
+        }
+    }
+
+    fn kern(value: i16) -> (ValueRecord, ValueRecord) {
+        (
+            ValueRecord {
+                xAdvance: Some(value),
+                ..ValueRecord::new()
+            },
+            ValueRecord::new(),
+        )
+    }
+
+    #[test]
+    fn test_decompile_rules() {
+        // Glyphs are .notdef, f, i, f_i, A and V
+        let mut font = crate::ttx::tests::test_font();
+
+        // Lookup 0 calls lookup 1 in the context A f i [A V]
+        let mut gsub = font.tables.GSUB().unwrap().unwrap().into_owned();
+        let mut single = SingleSubst::default();
+        single.mapping.insert(1, 3);
+        let rule = ChainedSequenceContextRule {
+            backtrack: vec![[4].into()],
+            input: vec![([1].into(), vec![1]), ([2].into(), vec![])],
+            lookahead: vec![[4, 5].into()],
+        };
+        gsub.lookups = vec![
+            lookup(Substitution::ChainedContextual(vec![
+                ChainedSequenceContext { rules: vec![rule] },
+            ])),
+            lookup(Substitution::Single(vec![single])),
+        ];
+        font.tables.insert(gsub);
+
+        // f and A are kerned alike, but A is kerned again in the second
+        // subtable, so it can't be in a class in the first
+        let mut gpos = font.tables.GPOS().unwrap().unwrap().into_owned();
+        let mut first = PairPos::default();
+        for left in [1, 4] {
+            first.mapping.insert((left, 2), kern(-20));
+            first.mapping.insert((left, 5), kern(-80));
+        }
+        first.mapping.insert((3, 2), kern(-20));
+        first.mapping.insert((3, 5), kern(-80));
+        let mut second = PairPos::default();
+        second.mapping.insert((4, 4), kern(-10));
+        let mut marks = MarkBasePos::default();
+        marks.marks.insert(2, (0, Anchor::new(250, 0)));
+        for base in [4, 5] {
+            marks
+                .bases
+                .insert(base, [(0, Anchor::new(250, 700))].into());
+        }
+        gpos.lookups = vec![
+            lookup(Positioning::Pair(vec![first, second])),
+            lookup(Positioning::MarkToBase(vec![marks])),
+        ];
+        font.tables.insert(gpos);
+
+        let fea = decompile(&font).unwrap();
+        assert!(fea.contains("@GSUB_0_1 = [A V];\n@GPOS_0_1 = [f f_i];\n\n"));
+        assert!(fea.contains("markClass i <anchor 250 0> @GPOS_1_mark0;\n"));
+        // Lookup 1 has to be defined before lookup 0 can call it
+        assert!(fea.find("lookup GSUB_1 {").unwrap() < fea.find("lookup GSUB_0 {").unwrap());
+        assert!(fea.contains("    sub A f' lookup GSUB_1 i' @GSUB_0_1;\n"));
+        assert!(fea.contains(
+            "    pos A i -20;\n    pos A V -80;\n    pos @GPOS_0_1 i -20;\n    pos @GPOS_0_1 V -80;\n    subtable;\n    pos A A -10;\n"
+        ));
+        assert!(fea.contains("    pos base @GSUB_0_1 <anchor 250 700> mark @GPOS_1_mark0;\n"));
+    }
+
+    #[test]
+    fn test_decompile_device_tables() {
+        // Glyphs are .notdef, f, i, f_i, A and V
+        let mut font = crate::ttx::tests::test_font();
+        let device = || {
+            Some(Offset16::to(Device {
+                startSize: 11,
+                endSize: 12,
+                deltaFormat: None,
+                deltaValues: vec![-1, 1],
+            }))
+        };
+        let mut gpos = font.tables.GPOS().unwrap().unwrap().into_owned();
+        let mut single = SinglePos::default();
+        single.mapping.insert(
+            4,
+            ValueRecord {
+                xAdvance: Some(10),
+                xAdvDevice: device(),
+                ..ValueRecord::new()
+            },
+        );
+        single.mapping.insert(
+            5,
+            ValueRecord {
+                xAdvance: Some(10),
+                ..ValueRecord::new()
+            },
+        );
+        let mut pairs = PairPos::default();
+        pairs.mapping.insert((4, 5), kern(-100));
+        let (mut first, second) = kern(-30);
+        first.xPlaDevice = device();
+        pairs.mapping.insert((5, 4), (first, second));
+        gpos.lookups = vec![
+            lookup(Positioning::Pair(vec![pairs])),
+            lookup(Positioning::Single(vec![single])),
+        ];
+        font.tables.insert(gpos);
+
+        let fea = decompile(&font).unwrap();
+        let expected = format!(
+            "# Decompiled by fonttools-rs {}
+
+languagesystem DFLT dflt;
+
+lookup GSUB_0 {{
+    sub f i by f_i;
+}} GSUB_0;
+
+lookup GPOS_0 {{
+    pos A V -100;
+    pos V A -30; # device/variation data dropped
+}} GPOS_0;
+
+lookup GPOS_1 {{
+    pos A 10; # device/variation data dropped
+    pos V 10;
+}} GPOS_1;
+
+feature liga {{
+    lookup GSUB_0;
+}} liga;
+
+feature kern {{
+    lookup GPOS_0;
+}} kern;
+",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(fea, expected);
+    }
+}
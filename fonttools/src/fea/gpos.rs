@@ -0,0 +1,381 @@
+use super::{
+    chained_contextual_lookups, contextual_lookups, merge_subtables, Decompiler, FeaRules,
+    LookupContext,
+};
+use crate::layout::common::ValueRecord;
+use crate::layout::gpos1::SinglePos;
+use crate::layout::gpos2::PairPos;
+use crate::tables::GPOS::Positioning;
+use otspec::layout::anchor::Anchor;
+use otspec::types::GlyphID;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+impl FeaRules for Positioning {
+    const KEYWORD: &'static str = "pos";
+
+    fn nested_lookups(&self) -> Vec<u16> {
+        match self {
+            Positioning::Contextual(subtables) => contextual_lookups(subtables),
+            Positioning::ChainedContextual(subtables) => chained_contextual_lookups(subtables),
+            _ => vec![],
+        }
+    }
+
+    fn write_rules(&self, decompiler: &mut Decompiler, lookup: &LookupContext, out: &mut String) {
+        match self {
+            Positioning::Single(subtables) => write_single(decompiler, lookup, subtables, out),
+            Positioning::Pair(subtables) => write_pairs(decompiler, lookup, subtables, out),
+            Positioning::Cursive(subtables) => {
+                for (gid, (entry, exit)) in merge_subtables(subtables.iter().map(|s| &s.mapping)) {
+                    writeln!(
+                        out,
+                        "    pos cursive {} {} {};",
+                        decompiler.glyph(gid),
+                        anchor(entry.as_ref()),
+                        anchor(exit.as_ref())
+                    )
+                    .unwrap();
+                }
+            }
+            Positioning::MarkToBase(subtables) => {
+                let subtables: Vec<_> = subtables.iter().map(|s| (&s.bases, &s.marks)).collect();
+                write_mark_attachment(decompiler, lookup, "base", &subtables, attachments, out)
+            }
+            Positioning::MarkToLig(subtables) => {
+                let subtables: Vec<_> =
+                    subtables.iter().map(|s| (&s.ligatures, &s.marks)).collect();
+                write_mark_attachment(
+                    decompiler,
+                    lookup,
+                    "ligature",
+                    &subtables,
+                    |components, classes| {
+                        let components: Vec<String> = components
+                            .iter()
+                            .map(|anchors| {
+                                attachments(anchors, classes)
+                                    .unwrap_or_else(|| "<anchor NULL>".to_string())
+                            })
+                            .collect();
+                        Some(components.join(" ligComponent "))
+                    },
+                    out,
+                )
+            }
+            Positioning::MarkToMark(subtables) => {
+                let subtables: Vec<_> = subtables
+                    .iter()
+                    .map(|s| (&s.base_marks, &s.combining_marks))
+                    .collect();
+                write_mark_attachment(decompiler, lookup, "mark", &subtables, attachments, out)
+            }
+            Positioning::Contextual(subtables) => {
+                decompiler.write_contextual(Self::KEYWORD, lookup, subtables, out)
+            }
+            Positioning::ChainedContextual(subtables) => {
+                decompiler.write_chained_contextual(Self::KEYWORD, lookup, subtables, out)
+            }
+        }
+    }
+}
+
+fn anchor(anchor: Option<&Anchor>) -> String {
+    match anchor {
+        None => "<anchor NULL>".to_string(),
+        Some(Anchor {
+            xCoordinate,
+            yCoordinate,
+            anchorPoint: Some(point),
+        }) => format!(
+            "<anchor {} {} contourpoint {}>",
+            xCoordinate, yCoordinate, point
+        ),
+        Some(anchor) => format!("<anchor {} {}>", anchor.xCoordinate, anchor.yCoordinate),
+    }
+}
+
+/// Feature files can't hold the variation indices of a variable font, so
+/// rules whose value records have device or variation data are written
+/// without it, and marked with this comment
+const DROPPED: &str = " # device/variation data dropped";
+
+fn has_device(value: &ValueRecord) -> bool {
+    [
+        &value.xPlaDevice,
+        &value.yPlaDevice,
+        &value.xAdvDevice,
+        &value.yAdvDevice,
+    ]
+    .iter()
+    .any(|device| device.as_ref().is_some_and(|offset| offset.link.is_some()))
+}
+
+fn warn_dropped(lookup: &LookupContext, count: usize) {
+    if count > 0 {
+        log::warn!(
+            "Device and variation data dropped from {} rules of lookup {}",
+            count,
+            lookup.name
+        );
+    }
+}
+
+fn is_empty(value: &ValueRecord) -> bool {
+    value.xPlacement.is_none()
+        && value.yPlacement.is_none()
+        && value.xAdvance.is_none()
+        && value.yAdvance.is_none()
+}
+
+/// A value record which only changes the advance is written as a single
+/// number, which means a vertical advance in vertical features
+fn value_record(value: &ValueRecord, vertical: bool) -> String {
+    match (
+        value.xPlacement,
+        value.yPlacement,
+        value.xAdvance,
+        value.yAdvance,
+    ) {
+        (None, None, Some(advance), None) if !vertical => advance.to_string(),
+        (None, None, None, Some(advance)) if vertical => advance.to_string(),
+        (x_placement, y_placement, x_advance, y_advance) => format!(
+            "<{} {} {} {}>",
+            x_placement.unwrap_or(0),
+            y_placement.unwrap_or(0),
+            x_advance.unwrap_or(0),
+            y_advance.unwrap_or(0)
+        ),
+    }
+}
+
+/// Glyphs which are moved by the same amount are written as a class
+fn write_single(
+    decompiler: &mut Decompiler,
+    lookup: &LookupContext,
+    subtables: &[SinglePos],
+    out: &mut String,
+) {
+    let mut groups: Vec<((String, bool), Vec<GlyphID>)> = vec![];
+    let mut group_ids: HashMap<(String, bool), usize> = HashMap::new();
+    for (gid, value) in merge_subtables(subtables.iter().map(|s| &s.mapping)) {
+        let value = (value_record(value, lookup.vertical), has_device(value));
+        let id = *group_ids.entry(value.clone()).or_insert_with(|| {
+            groups.push((value, vec![]));
+            groups.len() - 1
+        });
+        groups[id].1.push(gid);
+    }
+    let mut dropped = 0;
+    for ((value, device), glyphs) in groups {
+        dropped += usize::from(device);
+        writeln!(
+            out,
+            "    pos {} {};{}",
+            decompiler.class(glyphs, &lookup.name),
+            value,
+            if device { DROPPED } else { "" }
+        )
+        .unwrap();
+    }
+    warn_dropped(lookup, dropped);
+}
+
+/// The value records of a pair, as written after the first glyph and after
+/// the second, and whether either had device or variation data
+type PairValue = (String, Option<String>, bool);
+
+/// The value of a pair for each class of glyphs on the left which has one
+type Column<'a> = Vec<(usize, &'a PairValue)>;
+
+fn pair_statement(left: &str, right: &str, (first, second, device): &PairValue) -> String {
+    let note = if *device { DROPPED } else { "" };
+    match second {
+        None => format!("    pos {} {} {};{}", left, right, first, note),
+        Some(second) => format!("    pos {} {} {} {};{}", left, first, right, second, note),
+    }
+}
+
+/// Writes each subtable of a pair positioning lookup, finding the classes
+/// of glyphs which are kerned alike
+fn write_pairs(
+    decompiler: &mut Decompiler,
+    lookup: &LookupContext,
+    subtables: &[PairPos],
+    out: &mut String,
+) {
+    let mut dropped = 0;
+    for (index, subtable) in subtables.iter().enumerate() {
+        if index > 0 {
+            writeln!(out, "    subtable;").unwrap();
+        }
+        let mut rows: BTreeMap<GlyphID, BTreeMap<GlyphID, PairValue>> = BTreeMap::new();
+        for (&(left, right), (first, second)) in &subtable.mapping {
+            let device = has_device(first) || has_device(second);
+            let value = if is_empty(second) {
+                (value_record(first, lookup.vertical), None, device)
+            } else if is_empty(first) {
+                (
+                    "<NULL>".to_string(),
+                    Some(value_record(second, lookup.vertical)),
+                    device,
+                )
+            } else {
+                (
+                    value_record(first, lookup.vertical),
+                    Some(value_record(second, lookup.vertical)),
+                    device,
+                )
+            };
+            rows.entry(left).or_default().insert(right, value);
+        }
+
+        // A class pair gives every other pair starting with one of its
+        // glyphs a zero adjustment, which would hide pairs in later
+        // subtables, so glyphs which are kerned again later only get
+        // glyph pairs
+        let later: HashSet<GlyphID> = subtables[index + 1..]
+            .iter()
+            .flat_map(|subtable| subtable.mapping.keys().map(|&(left, _)| left))
+            .collect();
+        let mut left_classes: Vec<(Vec<GlyphID>, &BTreeMap<GlyphID, PairValue>)> = vec![];
+        let mut left_class_ids: HashMap<&BTreeMap<GlyphID, PairValue>, usize> = HashMap::new();
+        for (&left, row) in &rows {
+            if later.contains(&left) {
+                for (&right, value) in row {
+                    dropped += usize::from(value.2);
+                    let statement =
+                        pair_statement(&decompiler.glyph(left), &decompiler.glyph(right), value);
+                    writeln!(out, "{}", statement).unwrap();
+                }
+                continue;
+            }
+            let id = *left_class_ids.entry(row).or_insert_with(|| {
+                left_classes.push((vec![], row));
+                left_classes.len() - 1
+            });
+            left_classes[id].0.push(left);
+        }
+
+        // Glyphs on the right are in the same class if they are kerned the
+        // same way against each class on the left
+        let rights: BTreeSet<GlyphID> = left_classes
+            .iter()
+            .flat_map(|(_, row)| row.keys().copied())
+            .collect();
+        let mut right_classes: Vec<(Vec<GlyphID>, Column)> = vec![];
+        let mut right_class_ids: HashMap<Column, usize> = HashMap::new();
+        for right in rights {
+            let column: Column = left_classes
+                .iter()
+                .enumerate()
+                .filter_map(|(id, (_, row))| row.get(&right).map(|value| (id, value)))
+                .collect();
+            let id = *right_class_ids.entry(column.clone()).or_insert_with(|| {
+                right_classes.push((vec![], column));
+                right_classes.len() - 1
+            });
+            right_classes[id].0.push(right);
+        }
+
+        let mut pairs: Vec<(usize, usize, &PairValue)> = right_classes
+            .iter()
+            .enumerate()
+            .flat_map(|(right_id, (_, column))| {
+                column
+                    .iter()
+                    .map(move |&(left_id, value)| (left_id, right_id, value))
+            })
+            .collect();
+        pairs.sort_by_key(|&(left_id, right_id, _)| (left_id, right_id));
+        for (left_id, right_id, value) in pairs {
+            dropped += usize::from(value.2);
+            let left = decompiler.class(left_classes[left_id].0.clone(), &lookup.name);
+            let right = decompiler.class(right_classes[right_id].0.clone(), &lookup.name);
+            writeln!(out, "{}", pair_statement(&left, &right, value)).unwrap();
+        }
+    }
+    warn_dropped(lookup, dropped);
+}
+
+/// The mark class and anchor of each mark glyph
+type Marks = BTreeMap<GlyphID, (u16, Anchor)>;
+
+/// The anchors of a base, ligature component or mark, with the mark
+/// classes which attach to them
+fn attachments(anchors: &BTreeMap<u16, Anchor>, classes: &BTreeMap<u16, String>) -> Option<String> {
+    let parts: Vec<String> = anchors
+        .iter()
+        .filter_map(|(class, base_anchor)| {
+            classes
+                .get(class)
+                .map(|name| format!("{} mark {}", anchor(Some(base_anchor)), name))
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// Writes the `markClass` statements and `pos base`, `pos ligature` or
+/// `pos mark` rules of a mark attachment lookup. Mark class numbers are
+/// particular to a subtable, so each subtable has its own mark classes.
+fn write_mark_attachment<B>(
+    decompiler: &mut Decompiler,
+    lookup: &LookupContext,
+    keyword: &str,
+    subtables: &[(&BTreeMap<GlyphID, B>, &Marks)],
+    attachment: impl Fn(&B, &BTreeMap<u16, String>) -> Option<String>,
+    out: &mut String,
+) {
+    // A glyph can only be in one of a lookup's mark classes, and a mark
+    // only ever uses the first subtable which covers it
+    let mut seen_marks = HashSet::new();
+    for (index, (bases, marks)) in subtables.iter().enumerate() {
+        let prefix = if subtables.len() > 1 {
+            format!("@{}_{}_mark", lookup.name, index)
+        } else {
+            format!("@{}_mark", lookup.name)
+        };
+        let mut groups: BTreeMap<(u16, String), Vec<GlyphID>> = BTreeMap::new();
+        for (&gid, (class, mark_anchor)) in marks.iter() {
+            if seen_marks.insert(gid) {
+                groups
+                    .entry((*class, anchor(Some(mark_anchor))))
+                    .or_default()
+                    .push(gid);
+            }
+        }
+        let mut classes = BTreeMap::new();
+        for ((class, mark_anchor), glyphs) in groups {
+            let name = format!("{}{}", prefix, class);
+            let glyphs = if glyphs.len() == 1 {
+                decompiler.glyph(glyphs[0])
+            } else {
+                decompiler.inline_class(&glyphs)
+            };
+            decompiler.add_mark_class(format!("markClass {} {} {};", glyphs, mark_anchor, name));
+            classes.insert(class, name);
+        }
+
+        let mut groups: Vec<(String, Vec<GlyphID>)> = vec![];
+        let mut group_ids: HashMap<String, usize> = HashMap::new();
+        for (&gid, base) in bases.iter() {
+            if let Some(attachment) = attachment(base, &classes) {
+                let id = *group_ids.entry(attachment.clone()).or_insert_with(|| {
+                    groups.push((attachment, vec![]));
+                    groups.len() - 1
+                });
+                groups[id].1.push(gid);
+            }
+        }
+        for (attachment, glyphs) in groups {
+            writeln!(
+                out,
+                "    pos {} {} {};",
+                keyword,
+                decompiler.class(glyphs, &lookup.name),
+                attachment
+            )
+            .unwrap();
+        }
+    }
+}
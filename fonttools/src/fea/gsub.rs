@@ -0,0 +1,111 @@
+use super::{
+    chained_contextual_lookups, contextual_lookups, merge_subtables, Decompiler, FeaRules,
+    LookupContext,
+};
+use crate::layout::gsub1::SingleSubst;
+use crate::layout::gsub3::AlternateSubst;
+use crate::tables::GSUB::Substitution;
+use std::fmt::Write;
+
+impl FeaRules for Substitution {
+    const KEYWORD: &'static str = "sub";
+
+    fn nested_lookups(&self) -> Vec<u16> {
+        match self {
+            Substitution::Contextual(subtables) => contextual_lookups(subtables),
+            Substitution::ChainedContextual(subtables) => chained_contextual_lookups(subtables),
+            _ => vec![],
+        }
+    }
+
+    fn write_rules(&self, decompiler: &mut Decompiler, lookup: &LookupContext, out: &mut String) {
+        match self {
+            Substitution::Single(subtables) => write_single(decompiler, subtables, out),
+            Substitution::Multiple(subtables) => {
+                for (from, to) in merge_subtables(subtables.iter().map(|s| &s.mapping)) {
+                    // An empty sequence deletes the glyph
+                    let to = if to.is_empty() {
+                        "NULL".to_string()
+                    } else {
+                        decompiler.glyph_list(to)
+                    };
+                    writeln!(out, "    sub {} by {};", decompiler.glyph(from), to).unwrap();
+                }
+            }
+            Substitution::Alternate(subtables) => write_alternate(decompiler, subtables, out),
+            Substitution::Ligature(subtables) => {
+                for (components, &ligature) in merge_subtables(subtables.iter().map(|s| &s.mapping))
+                {
+                    writeln!(
+                        out,
+                        "    sub {} by {};",
+                        decompiler.glyph_list(&components),
+                        decompiler.glyph(ligature)
+                    )
+                    .unwrap();
+                }
+            }
+            Substitution::Contextual(subtables) => {
+                decompiler.write_contextual(Self::KEYWORD, lookup, subtables, out)
+            }
+            Substitution::ChainedContextual(subtables) => {
+                decompiler.write_chained_contextual(Self::KEYWORD, lookup, subtables, out)
+            }
+            Substitution::ReverseChainContextual(subtables) => {
+                for subtable in subtables {
+                    let mut parts = vec![];
+                    for slot in subtable.backtrack.iter().rev() {
+                        parts.push(decompiler.slot(slot, &lookup.name));
+                    }
+                    // The input is a set, but the replacements have to be
+                    // in the same order as it, so they are written in full
+                    let input: Vec<_> = subtable.mapping.keys().copied().collect();
+                    parts.push(format!("{}'", decompiler.class(input, &lookup.name)));
+                    for slot in &subtable.lookahead {
+                        parts.push(decompiler.slot(slot, &lookup.name));
+                    }
+                    let replacement = if subtable.mapping.len() == 1 {
+                        decompiler.glyph_list(subtable.mapping.values())
+                    } else {
+                        decompiler.inline_class(subtable.mapping.values())
+                    };
+                    writeln!(out, "    rsub {} by {};", parts.join(" "), replacement).unwrap();
+                }
+            }
+        }
+    }
+
+    fn aalt_rules(&self, decompiler: &Decompiler) -> Option<String> {
+        let mut out = String::new();
+        match self {
+            Substitution::Single(subtables) => write_single(decompiler, subtables, &mut out),
+            Substitution::Alternate(subtables) => write_alternate(decompiler, subtables, &mut out),
+            _ => return None,
+        }
+        Some(out)
+    }
+}
+
+fn write_single(decompiler: &Decompiler, subtables: &[SingleSubst], out: &mut String) {
+    for (from, &to) in merge_subtables(subtables.iter().map(|s| &s.mapping)) {
+        writeln!(
+            out,
+            "    sub {} by {};",
+            decompiler.glyph(from),
+            decompiler.glyph(to)
+        )
+        .unwrap();
+    }
+}
+
+fn write_alternate(decompiler: &Decompiler, subtables: &[AlternateSubst], out: &mut String) {
+    for (from, alternates) in merge_subtables(subtables.iter().map(|s| &s.mapping)) {
+        writeln!(
+            out,
+            "    sub {} from {};",
+            decompiler.glyph(from),
+            decompiler.inline_class(alternates)
+        )
+        .unwrap();
+    }
+}
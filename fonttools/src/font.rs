@@ -15,6 +15,7 @@ use std::path::Path;
 
 /// Magic number used to identify the font type
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SfntVersion {
     /// TrueType (generally containing glyf outlines)
     TrueType = 0x00010000,
@@ -55,12 +56,14 @@ struct TableHeader {
 
 /// An OpenType font object
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_snake_case)]
 pub struct Font {
     /// Font version (TrueType/OpenType)
     sfntVersion: SfntVersion,
     /// Dictionary of tables in the font
     pub tables: super::table_store::TableSet,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _numGlyphs: Option<u16>,
 }
 
@@ -92,6 +95,11 @@ impl Font {
         }
     }
 
+    /// The font's version (TrueType/OpenType)
+    pub fn sfnt_version(&self) -> SfntVersion {
+        self.sfntVersion
+    }
+
     //FIXME: do we want to keep this? do we want top-level methods generally?
     /// Returns `true` if the font contains a table with this `Tag`.
     pub fn contains_table(&self, tag: Tag) -> bool {
@@ -116,12 +124,27 @@ impl Font {
     /// Attempt to write the font into the provided [`Writer`][std::io::Write];
     pub fn write(&mut self, mut writer: impl std::io::Write) -> Result<(), Box<dyn Error>> {
         self.tables.compile_glyf_loca_maxp();
-        self.tables.compile_gsub_gpos();
+        self.tables.compile_hmtx_gvar();
+        self.tables.compile_gsub_gpos()?;
         let mut bytes = Vec::new();
         self.to_bytes(&mut bytes)?;
         writer.write_all(&bytes).map_err(Into::into)
     }
 
+    /// Attempt to save the font to the provided path as WOFF2.
+    pub fn save_woff2(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let file = std::fs::File::create(path)?;
+        self.write_woff2(file)
+    }
+
+    /// Attempt to write the font as WOFF2 into the provided [`Writer`][std::io::Write];
+    pub fn write_woff2(&mut self, mut writer: impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        let mut sfnt = Vec::new();
+        self.write(&mut sfnt)?;
+        let woff2 = crate::woff2::compress(&sfnt)?;
+        writer.write_all(&woff2).map_err(Into::into)
+    }
+
     /// Total number of glyphs in the font, from the maxp table.
     ///
     /// Deserializes the maxp table if this is not already done.
@@ -399,6 +422,29 @@ mod tests {
         )
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_roundtrip() {
+        let mut font = crate::ttx::tests::test_font();
+        let mut bytes = vec![];
+        font.write(&mut bytes).unwrap();
+        let font = Font::from_bytes(&bytes).unwrap();
+
+        let json = serde_json::to_string(&font).unwrap();
+        assert!(json.contains("\"sfntVersion\":\"TrueType\""));
+        assert!(json.contains("\"DSIG\":\"0000000100000000\""));
+        assert!(!json.contains("\"loca\""));
+
+        let mut roundtripped: Font = serde_json::from_str(&json).unwrap();
+        let mut bytes = vec![];
+        roundtripped.write(&mut bytes).unwrap();
+        let reloaded = Font::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            crate::ttx::dump(&reloaded).unwrap(),
+            crate::ttx::dump(&font).unwrap()
+        );
+    }
+
     // #[test]
     // fn test_load() {
     //     let f = font::load("data/test1.ttf").unwrap();
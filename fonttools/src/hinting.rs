@@ -0,0 +1,811 @@
+//! A TrueType bytecode interpreter
+//!
+//! The [`Hinter`] runs a font's `fpgm` and `prep` programs at a given size
+//! and location in the designspace, and then executes glyph programs to
+//! produce grid-fitted outlines. Variable fonts are supported: `gvar` deltas
+//! are applied to the outlines and `cvar` deltas to the control values
+//! before hinting.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::hinting::Hinter;
+//!
+//! let font = Font::load("Test.ttf").expect("Could not load font");
+//! let mut hinter = Hinter::new(&font, 12, &[]).expect("Could not set up hinting");
+//! let glyph = hinter.hint_glyph(3).expect("Could not hint glyph");
+//! println!("Advance width: {}", glyph.advance_width as f32 / 64.0);
+//! ```
+mod graphics_state;
+mod interpreter;
+
+pub use graphics_state::{GraphicsState, RoundState, UnitVector};
+
+use crate::font::Font;
+use crate::table_store::CowPtr;
+use crate::tables;
+use crate::tables::gasp::RangeGaspBehaviorFlags;
+use crate::tables::glyf::{glyf, ComponentFlags, Glyph};
+use crate::tables::gvar::gvar;
+use crate::tables::hmtx::hmtx;
+use crate::tables::maxp::MaxpVariant;
+use interpreter::{Interpreter, Zone};
+use otspec::types::Tag;
+use otspec::DeserializationError;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A 26.6 fixed-point number, as used for distances in pixels
+pub type F26Dot6 = i32;
+
+/// The maximum depth of nested components
+const MAX_COMPONENT_DEPTH: u16 = 16;
+/// The default number of instructions a program may execute
+const DEFAULT_INSTRUCTION_LIMIT: usize = 1_000_000;
+
+/// Identifies a program run by the interpreter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Program {
+    /// The font program (`fpgm`)
+    Font,
+    /// The control value program (`prep`)
+    ControlValue,
+    /// The program of the glyph with the given ID
+    Glyph(u16),
+}
+
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Program::Font => write!(f, "fpgm"),
+            Program::ControlValue => write!(f, "prep"),
+            Program::Glyph(gid) => write!(f, "glyph {}", gid),
+        }
+    }
+}
+
+/// An error raised while executing an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    /// An instruction needed more values than there were on the stack
+    StackUnderflow,
+    /// The stack grew beyond the limit given in the `maxp` table
+    StackOverflow,
+    /// A point index was out of range for its zone
+    InvalidPoint(i32),
+    /// A contour index was out of range
+    InvalidContour(i32),
+    /// A zone pointer was neither 0 nor 1
+    InvalidZone(i32),
+    /// A control value index was out of range
+    InvalidCvt(i32),
+    /// A storage area index was out of range
+    InvalidStorage(i32),
+    /// A function was called before being defined
+    InvalidFunction(i32),
+    /// An opcode was neither a standard instruction nor defined with `IDEF`
+    InvalidOpcode(u8),
+    /// An instruction received an argument outside its valid range
+    InvalidArgument(i32),
+    /// A jump went outside the program
+    InvalidJump(i32),
+    /// `DIV` was asked to divide by zero
+    DivideByZero,
+    /// Functions were nested too deeply (probably through recursion)
+    CallDepthExceeded,
+    /// An `IF`, `FDEF` or `IDEF` was not terminated, or a function did
+    /// not return
+    UnterminatedBlock,
+    /// An `FDEF` or `IDEF` appeared within another definition
+    NestedDefinition,
+    /// `ENDF` was found outside a function
+    UnexpectedEndf,
+    /// A push instruction ran past the end of the program
+    TruncatedInstruction,
+    /// The program executed more instructions than the limit allows
+    TooManyInstructions,
+}
+
+impl std::fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::StackUnderflow => write!(f, "stack underflow"),
+            ExecutionError::StackOverflow => write!(f, "stack overflow"),
+            ExecutionError::InvalidPoint(p) => write!(f, "invalid point {}", p),
+            ExecutionError::InvalidContour(c) => write!(f, "invalid contour {}", c),
+            ExecutionError::InvalidZone(z) => write!(f, "invalid zone {}", z),
+            ExecutionError::InvalidCvt(i) => write!(f, "invalid control value index {}", i),
+            ExecutionError::InvalidStorage(i) => write!(f, "invalid storage index {}", i),
+            ExecutionError::InvalidFunction(n) => write!(f, "undefined function {}", n),
+            ExecutionError::InvalidOpcode(op) => write!(f, "invalid opcode 0x{:02x}", op),
+            ExecutionError::InvalidArgument(v) => write!(f, "invalid argument {}", v),
+            ExecutionError::InvalidJump(o) => write!(f, "jump by {} leaves the program", o),
+            ExecutionError::DivideByZero => write!(f, "division by zero"),
+            ExecutionError::CallDepthExceeded => write!(f, "function calls nested too deeply"),
+            ExecutionError::UnterminatedBlock => write!(f, "unterminated block"),
+            ExecutionError::NestedDefinition => write!(f, "nested function definition"),
+            ExecutionError::UnexpectedEndf => write!(f, "ENDF outside a function"),
+            ExecutionError::TruncatedInstruction => write!(f, "truncated instruction"),
+            ExecutionError::TooManyInstructions => write!(f, "instruction limit exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// An error raised while hinting a font
+#[derive(Debug, Clone, PartialEq)]
+pub enum HintingError {
+    /// A table needed for hinting was missing
+    MissingTable(Tag),
+    /// A table could not be read
+    Table(String),
+    /// A glyph ID was out of range
+    InvalidGlyph(u16),
+    /// Components were nested too deeply within the given glyph
+    ComponentDepthExceeded(u16),
+    /// A program failed
+    Execution {
+        /// The program which failed
+        program: Program,
+        /// The offset of the failing instruction within its program
+        offset: usize,
+        /// What went wrong
+        error: ExecutionError,
+    },
+}
+
+impl std::fmt::Display for HintingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HintingError::MissingTable(tag) => write!(f, "missing {} table", tag),
+            HintingError::Table(e) => write!(f, "could not read table: {}", e),
+            HintingError::InvalidGlyph(gid) => write!(f, "invalid glyph ID {}", gid),
+            HintingError::ComponentDepthExceeded(gid) => {
+                write!(f, "components of glyph {} nested too deeply", gid)
+            }
+            HintingError::Execution {
+                program,
+                offset,
+                error,
+            } => write!(f, "error in {} at offset {}: {}", program, offset, error),
+        }
+    }
+}
+
+impl std::error::Error for HintingError {}
+
+impl From<DeserializationError> for HintingError {
+    fn from(e: DeserializationError) -> Self {
+        HintingError::Table(e.0)
+    }
+}
+
+/// A point of a hinted glyph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HintedPoint {
+    /// The x coordinate in 26.6 pixels
+    pub x: F26Dot6,
+    /// The y coordinate in 26.6 pixels
+    pub y: F26Dot6,
+    /// Whether the point is on the curve
+    pub on_curve: bool,
+}
+
+/// A hinted glyph outline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintedGlyph {
+    /// The contours of the glyph, positioned relative to its hinted origin
+    pub contours: Vec<Vec<HintedPoint>>,
+    /// The hinted advance width in 26.6 pixels
+    pub advance_width: F26Dot6,
+}
+
+/// A glyph's points after loading and hinting, including phantom points
+struct LoadedGlyph {
+    points: Vec<(F26Dot6, F26Dot6)>,
+    on_curve: Vec<bool>,
+    contour_ends: Vec<usize>,
+    phantoms: [(F26Dot6, F26Dot6); 4],
+}
+
+/// Returns the contribution of a region to the given location. Tuples are
+/// in the same (fvar) axis order as the location.
+pub(crate) fn tuple_scalar(coords: &[f32], start: &[f32], peak: &[f32], end: &[f32]) -> f32 {
+    let mut scalar = 1.0;
+    for (axis, &peak) in peak.iter().enumerate() {
+        let (start, end) = (start[axis], end[axis]);
+        if peak == 0.0 || start > peak || peak > end || (start < 0.0 && end > 0.0) {
+            continue;
+        }
+        let coord = coords.get(axis).copied().unwrap_or(0.0);
+        if coord == peak {
+            continue;
+        }
+        if coord <= start || coord >= end {
+            return 0.0;
+        }
+        scalar *= if coord < peak {
+            (coord - start) / (peak - start)
+        } else {
+            (end - coord) / (end - peak)
+        };
+    }
+    scalar
+}
+
+fn round_to_grid(value: F26Dot6) -> F26Dot6 {
+    (value + 32) & !63
+}
+
+/// Executes TrueType instructions to produce hinted glyph outlines
+pub struct Hinter {
+    glyf: CowPtr<glyf>,
+    hmtx: CowPtr<hmtx>,
+    gvar: Option<CowPtr<gvar>>,
+    interpreter: Interpreter,
+    /// The graphics state left by the control value program
+    default_state: GraphicsState,
+    /// The control values and storage area left by the control value program
+    prep_cvt: Vec<F26Dot6>,
+    prep_storage: Vec<i32>,
+    twilight_points: usize,
+    coords: Vec<f32>,
+    ascender: i16,
+    descender: i16,
+    /// Whether glyph programs are run. This is initialized from the `gasp`
+    /// table; when it is false, outlines are only scaled.
+    pub gridfit: bool,
+}
+
+impl Hinter {
+    /// Prepares to hint a font at the given size in pixels per em.
+    ///
+    /// `coords` is a location in normalized coordinates, one per axis in
+    /// `fvar` order; missing coordinates are taken as zero. The font program
+    /// and control value program are executed immediately.
+    pub fn new(font: &Font, ppem: u16, coords: &[f32]) -> Result<Self, HintingError> {
+        let tables = &font.tables;
+        let head = tables
+            .head()?
+            .ok_or(HintingError::MissingTable(tables::head::TAG))?;
+        let hhea = tables
+            .hhea()?
+            .ok_or(HintingError::MissingTable(tables::hhea::TAG))?;
+        let maxp = tables
+            .maxp()?
+            .ok_or(HintingError::MissingTable(tables::maxp::TAG))?;
+        let glyf = tables
+            .glyf()?
+            .ok_or(HintingError::MissingTable(tables::glyf::TAG))?;
+        let hmtx = tables
+            .hmtx()?
+            .ok_or(HintingError::MissingTable(tables::hmtx::TAG))?;
+        let gvar = tables.gvar()?;
+        let axis_count = tables.fvar()?.map_or(0, |fvar| fvar.axes.len());
+        let mut coords = coords.to_vec();
+        coords.resize(axis_count, 0.0);
+
+        let (twilight_points, storage_size, max_stack) = match &maxp.table {
+            MaxpVariant::Maxp10(m) => (
+                m.maxTwilightPoints as usize,
+                m.maxStorage as usize,
+                m.maxStackElements as usize,
+            ),
+            MaxpVariant::Maxp05(_) => (0, 0, 0),
+        };
+
+        let scale = ppem as f64 * 64.0 / head.unitsPerEm.max(1) as f64;
+        let mut cvt: Vec<f64> = tables
+            .cvt()?
+            .map(|cvt| cvt.0.iter().map(|&v| v as f64).collect())
+            .unwrap_or_default();
+        if axis_count > 0 {
+            for deltaset in tables.cvar()?.iter().flat_map(|cvar| cvar.deltasets.iter()) {
+                let scalar = tuple_scalar(&coords, &deltaset.start, &deltaset.peak, &deltaset.end);
+                if scalar == 0.0 {
+                    continue;
+                }
+                for (value, delta) in cvt.iter_mut().zip(deltaset.deltas.iter()) {
+                    *value += scalar as f64 * *delta as f64;
+                }
+            }
+        }
+        let cvt = cvt.iter().map(|v| (v * scale).round() as i32).collect();
+
+        let gridfit = tables.gasp()?.is_none_or(|gasp| {
+            gasp.gaspRanges
+                .iter()
+                .find(|range| ppem <= range.rangeMaxPPEM)
+                .is_none_or(|range| {
+                    range.rangeGaspBehavior.intersects(
+                        RangeGaspBehaviorFlags::GASP_GRIDFIT
+                            | RangeGaspBehaviorFlags::GASP_SYMMETRIC_GRIDFIT,
+                    )
+                })
+        });
+
+        let interpreter = Interpreter {
+            stack: vec![],
+            max_stack,
+            storage: vec![0; storage_size],
+            cvt,
+            functions: HashMap::new(),
+            instruction_defs: HashMap::new(),
+            graphics_state: GraphicsState::default(),
+            zones: [Zone::twilight(twilight_points), Zone::default()],
+            ppem,
+            point_size: ppem as i32 * 64,
+            scale,
+            coords: coords.clone(),
+            axis_count,
+            max_instructions: DEFAULT_INSTRUCTION_LIMIT,
+            in_prep: false,
+        };
+        let mut hinter = Hinter {
+            glyf,
+            hmtx,
+            gvar,
+            interpreter,
+            default_state: GraphicsState::default(),
+            prep_cvt: vec![],
+            prep_storage: vec![],
+            twilight_points,
+            coords,
+            ascender: hhea.ascender,
+            descender: hhea.descender,
+            gridfit,
+        };
+
+        if let Some(fpgm) = tables.fpgm()? {
+            hinter
+                .interpreter
+                .run(Program::Font, fpgm.0.clone().into())?;
+        }
+        if let Some(prep) = tables.prep()? {
+            hinter.interpreter.stack.clear();
+            hinter.interpreter.in_prep = true;
+            let result = hinter
+                .interpreter
+                .run(Program::ControlValue, prep.0.clone().into());
+            hinter.interpreter.in_prep = false;
+            result?;
+        }
+        hinter.default_state = hinter.interpreter.graphics_state.clone();
+        hinter.prep_cvt = hinter.interpreter.cvt.clone();
+        hinter.prep_storage = hinter.interpreter.storage.clone();
+        Ok(hinter)
+    }
+
+    /// The graphics state left by the control value program
+    pub fn graphics_state(&self) -> &GraphicsState {
+        &self.default_state
+    }
+
+    /// The scaled control values left by the control value program
+    pub fn cvt(&self) -> &[F26Dot6] {
+        &self.prep_cvt
+    }
+
+    /// The storage area left by the control value program
+    pub fn storage(&self) -> &[i32] {
+        &self.prep_storage
+    }
+
+    /// Sets the maximum number of instructions a glyph program may execute
+    /// before it fails with [`ExecutionError::TooManyInstructions`].
+    pub fn set_instruction_limit(&mut self, limit: usize) {
+        self.interpreter.max_instructions = limit;
+    }
+
+    /// Scales, varies and hints a glyph.
+    pub fn hint_glyph(&mut self, gid: u16) -> Result<HintedGlyph, HintingError> {
+        let loaded = self.load_glyph(gid, 0)?;
+        let origin = loaded.phantoms[0].0;
+        let mut contours = vec![];
+        let mut start = 0;
+        for &end in &loaded.contour_ends {
+            contours.push(
+                (start..=end)
+                    .map(|i| HintedPoint {
+                        x: loaded.points[i].0 - origin,
+                        y: loaded.points[i].1,
+                        on_curve: loaded.on_curve[i],
+                    })
+                    .collect(),
+            );
+            start = end + 1;
+        }
+        Ok(HintedGlyph {
+            contours,
+            advance_width: loaded.phantoms[1].0 - origin,
+        })
+    }
+
+    /// Returns the unscaled coordinates of a glyph's points, component
+    /// offsets and phantom points, with variations applied.
+    fn varied_coordinates(&self, gid: u16, glyph: &Glyph) -> Vec<(f64, f64)> {
+        let metric = self
+            .hmtx
+            .metrics
+            .get(gid as usize)
+            .or_else(|| self.hmtx.metrics.last());
+        let (advance, lsb) = metric.map_or((0, 0), |m| (m.advanceWidth, m.lsb));
+        let mut coords: Vec<(f64, f64)> = glyph
+            .contours
+            .iter()
+            .flatten()
+            .map(|pt| (pt.x as f64, pt.y as f64))
+            .collect();
+        for component in &glyph.components {
+            let [_, _, _, _, x, y] = component.transformation.as_coeffs();
+            coords.push((x, y));
+        }
+        let left = glyph.xMin as f64 - lsb as f64;
+        coords.push((left, 0.0));
+        coords.push((left + advance as f64, 0.0));
+        coords.push((0.0, self.ascender as f64));
+        coords.push((0.0, self.descender as f64));
+
+        let variations = self
+            .gvar
+            .as_ref()
+            .and_then(|gvar| gvar.variations.get(gid as usize))
+            .and_then(|v| v.as_ref());
+        if let Some(variations) = variations {
+            for deltaset in &variations.deltasets {
+                let scalar =
+                    tuple_scalar(&self.coords, &deltaset.start, &deltaset.peak, &deltaset.end)
+                        as f64;
+                if scalar == 0.0 {
+                    continue;
+                }
+                for (coord, delta) in coords.iter_mut().zip(deltaset.deltas.iter()) {
+                    coord.0 += scalar * delta.0 as f64;
+                    coord.1 += scalar * delta.1 as f64;
+                }
+            }
+        }
+        coords
+    }
+
+    fn load_glyph(&mut self, gid: u16, depth: u16) -> Result<LoadedGlyph, HintingError> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return Err(HintingError::ComponentDepthExceeded(gid));
+        }
+        let glyf = self.glyf.clone();
+        let glyph = glyf
+            .glyphs
+            .get(gid as usize)
+            .ok_or(HintingError::InvalidGlyph(gid))?;
+        let scale = self.interpreter.scale;
+        let scaled: Vec<(F26Dot6, F26Dot6)> = self
+            .varied_coordinates(gid, glyph)
+            .iter()
+            .map(|(x, y)| ((x * scale).round() as i32, (y * scale).round() as i32))
+            .collect();
+        let phantom_start = scaled.len() - 4;
+        let mut phantoms = [
+            scaled[phantom_start],
+            scaled[phantom_start + 1],
+            scaled[phantom_start + 2],
+            scaled[phantom_start + 3],
+        ];
+        if self.gridfit {
+            phantoms[0].0 = round_to_grid(phantoms[0].0);
+            phantoms[1].0 = round_to_grid(phantoms[1].0);
+            phantoms[2].1 = round_to_grid(phantoms[2].1);
+            phantoms[3].1 = round_to_grid(phantoms[3].1);
+        }
+
+        let mut loaded = if glyph.has_components() {
+            let mut loaded = LoadedGlyph {
+                points: vec![],
+                on_curve: vec![],
+                contour_ends: vec![],
+                phantoms,
+            };
+            let offsets = &scaled[..phantom_start];
+            for (component, &offset) in glyph.components.iter().zip(offsets.iter()) {
+                let child = self.load_glyph(component.glyph_index, depth + 1)?;
+                self.add_component(&mut loaded, component, child, offset, gid)?;
+            }
+            loaded
+        } else {
+            LoadedGlyph {
+                points: scaled[..phantom_start].to_vec(),
+                on_curve: glyph
+                    .contours
+                    .iter()
+                    .flatten()
+                    .map(|p| p.on_curve)
+                    .collect(),
+                contour_ends: glyph
+                    .contours
+                    .iter()
+                    .scan(0, |count, contour| {
+                        *count += contour.len();
+                        Some(*count - 1)
+                    })
+                    .collect(),
+                phantoms,
+            }
+        };
+
+        if self.gridfit
+            && !glyph.instructions.is_empty()
+            && self.default_state.instruct_control & 1 == 0
+        {
+            let original: Vec<(F26Dot6, F26Dot6)> = if glyph.has_components() {
+                loaded.points.clone()
+            } else {
+                scaled[..phantom_start].to_vec()
+            };
+            self.run_glyph_program(gid, &glyph.instructions, &mut loaded, original)?;
+        }
+        Ok(loaded)
+    }
+
+    /// Transforms and positions a component, adding it to its parent
+    fn add_component(
+        &self,
+        parent: &mut LoadedGlyph,
+        component: &crate::tables::glyf::Component,
+        mut child: LoadedGlyph,
+        offset: (F26Dot6, F26Dot6),
+        gid: u16,
+    ) -> Result<(), HintingError> {
+        let [a, b, c, d, _, _] = component.transformation.as_coeffs();
+        if (a, b, c, d) != (1.0, 0.0, 0.0, 1.0) {
+            for point in child.points.iter_mut() {
+                let (x, y) = (point.0 as f64, point.1 as f64);
+                *point = (
+                    (a * x + c * y).round() as i32,
+                    (b * x + d * y).round() as i32,
+                );
+            }
+        }
+        let (dx, dy) = if let Some((parent_point, child_point)) = component.match_points {
+            let from = parent
+                .points
+                .get(parent_point as usize)
+                .ok_or(HintingError::InvalidGlyph(gid))?;
+            let to = child
+                .points
+                .get(child_point as usize)
+                .ok_or(HintingError::InvalidGlyph(component.glyph_index))?;
+            (from.0 - to.0, from.1 - to.1)
+        } else if self.gridfit && component.flags.contains(ComponentFlags::ROUND_XY_TO_GRID) {
+            (round_to_grid(offset.0), round_to_grid(offset.1))
+        } else {
+            offset
+        };
+        let base = parent.points.len();
+        parent
+            .points
+            .extend(child.points.iter().map(|(x, y)| (x + dx, y + dy)));
+        parent.on_curve.extend(child.on_curve);
+        parent
+            .contour_ends
+            .extend(child.contour_ends.iter().map(|end| end + base));
+        if component.flags.contains(ComponentFlags::USE_MY_METRICS) {
+            parent.phantoms = child.phantoms;
+        }
+        Ok(())
+    }
+
+    fn run_glyph_program(
+        &mut self,
+        gid: u16,
+        instructions: &[u8],
+        loaded: &mut LoadedGlyph,
+        mut original: Vec<(F26Dot6, F26Dot6)>,
+    ) -> Result<(), HintingError> {
+        let interpreter = &mut self.interpreter;
+        let mut current = loaded.points.clone();
+        original.extend(loaded.phantoms.iter());
+        current.extend(loaded.phantoms.iter());
+        let mut on_curve = loaded.on_curve.clone();
+        on_curve.extend([true; 4]);
+        interpreter.zones = [
+            Zone::twilight(self.twilight_points),
+            Zone::glyph(original, current, on_curve, loaded.contour_ends.clone()),
+        ];
+        interpreter.graphics_state = self.default_state.for_glyph();
+        interpreter.cvt = self.prep_cvt.clone();
+        interpreter.storage = self.prep_storage.clone();
+        interpreter.stack.clear();
+        let code: Rc<[u8]> = instructions.into();
+        interpreter.run(Program::Glyph(gid), code)?;
+
+        let zone = &interpreter.zones[1];
+        let count = loaded.points.len();
+        loaded.points = zone.current[..count].to_vec();
+        loaded.on_curve = zone.on_curve[..count].to_vec();
+        for (i, phantom) in loaded.phantoms.iter_mut().enumerate() {
+            *phantom = zone.current[count + i];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::{assemble, from_text};
+    use crate::tables::glyf::Point;
+
+    fn bytecode(text: &str) -> Vec<u8> {
+        assemble(&from_text(text).unwrap()).unwrap()
+    }
+
+    fn square(size: i16, instructions: Vec<u8>) -> Glyph {
+        let point = |x, y| Point {
+            x,
+            y,
+            on_curve: true,
+        };
+        Glyph {
+            xMin: 10,
+            xMax: 10 + size,
+            yMin: 0,
+            yMax: size,
+            contours: vec![vec![
+                point(10, 0),
+                point(10, size),
+                point(10 + size, size),
+                point(10 + size, 0),
+            ]],
+            instructions,
+            components: vec![],
+            overlap: false,
+        }
+    }
+
+    fn test_font(instructions: Vec<u8>) -> Font {
+        let mut font = Font::new(crate::font::SfntVersion::TrueType);
+        font.tables
+            .insert(tables::head::new(1.0, 1000, 0, 0, 1000, 1000));
+        font.tables.insert(tables::hhea::hhea {
+            majorVersion: 1,
+            minorVersion: 0,
+            ascender: 800,
+            descender: -200,
+            lineGap: 0,
+            advanceWidthMax: 600,
+            minLeftSideBearing: 10,
+            minRightSideBearing: 10,
+            xMaxExtent: 590,
+            caretSlopeRise: 1,
+            caretSlopeRun: 0,
+            caretOffset: 0,
+            reserved0: 0,
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+            metricDataFormat: 0,
+            numberOfHMetrics: 1,
+        });
+        let mut maxp = tables::maxp::maxp::new10(1, 4, 1, 0, 0, 0, 0);
+        if let MaxpVariant::Maxp10(m) = &mut maxp.table {
+            m.maxStackElements = 16;
+            m.maxStorage = 2;
+            m.maxFunctionDefs = 1;
+            m.maxTwilightPoints = 2;
+        }
+        font.tables.insert(maxp);
+        font.tables.insert(tables::hmtx::hmtx {
+            metrics: vec![tables::hmtx::Metric {
+                advanceWidth: 600,
+                lsb: 10,
+            }],
+        });
+        font.tables.insert(tables::glyf::glyf {
+            glyphs: vec![square(570, instructions)],
+        });
+        font.tables.insert(tables::cvt::cvt(vec![570]));
+        // Function 0 rounds a point to the grid
+        font.tables.insert(tables::fpgm::fpgm(bytecode(
+            "PUSH[ ] 0 FDEF[ ] MDAP[1] ENDF[ ]",
+        )));
+        font.tables
+            .insert(tables::prep::prep(bytecode("PUSH[ ] 1 100 WS[ ]")));
+        font
+    }
+
+    #[test]
+    fn test_unhinted() {
+        let font = test_font(vec![]);
+        let mut hinter = Hinter::new(&font, 10, &[]).unwrap();
+        assert!(hinter.gridfit);
+        assert_eq!(hinter.cvt(), &[365]);
+        assert_eq!(hinter.storage(), &[0, 100]);
+        let glyph = hinter.hint_glyph(0).unwrap();
+        // 10 font units at 10ppem/1000upem is 6.4 in 26.6; the origin is at 0
+        assert_eq!(glyph.advance_width, 384);
+        let xs: Vec<_> = glyph.contours[0].iter().map(|p| p.x).collect();
+        let ys: Vec<_> = glyph.contours[0].iter().map(|p| p.y).collect();
+        assert_eq!(xs, vec![6, 6, 371, 371]);
+        assert_eq!(ys, vec![0, 365, 365, 0]);
+    }
+
+    #[test]
+    fn test_hinted() {
+        let font = test_font(bytecode(
+            "SVTCA[1] PUSH[ ] 0 0 CALL[ ] PUSH[ ] 3 0 MIRP[10101]
+             SVTCA[0] PUSH[ ] 0 MDAP[1] PUSH[ ] 1 0 MIAP[1] IUP[0] IUP[1]",
+        ));
+        let mut hinter = Hinter::new(&font, 10, &[]).unwrap();
+        let glyph = hinter.hint_glyph(0).unwrap();
+        let points: Vec<_> = glyph.contours[0].iter().map(|p| (p.x, p.y)).collect();
+        assert_eq!(points, vec![(0, 0), (0, 384), (384, 384), (384, 0)]);
+
+        // Hinting is disabled by gasp
+        let mut font = test_font(bytecode("SVTCA[1] PUSH[ ] 0 0 CALL[ ]"));
+        font.tables.insert(tables::gasp::gasp {
+            version: 1,
+            gaspRanges: vec![tables::gasp::GaspRecord {
+                rangeMaxPPEM: 0xFFFF,
+                rangeGaspBehavior: RangeGaspBehaviorFlags::GASP_DOGRAY,
+            }],
+        });
+        let mut hinter = Hinter::new(&font, 10, &[]).unwrap();
+        assert!(!hinter.gridfit);
+        assert_eq!(hinter.hint_glyph(0).unwrap().contours[0][0].x, 6);
+    }
+
+    #[test]
+    fn test_errors() {
+        let font = test_font(bytecode("PUSH[ ] -3 JMPR[ ]"));
+        let mut hinter = Hinter::new(&font, 10, &[]).unwrap();
+        hinter.set_instruction_limit(100);
+        assert_eq!(
+            hinter.hint_glyph(0),
+            Err(HintingError::Execution {
+                program: Program::Glyph(0),
+                offset: 0,
+                error: ExecutionError::TooManyInstructions
+            })
+        );
+        assert_eq!(hinter.hint_glyph(5), Err(HintingError::InvalidGlyph(5)));
+    }
+
+    #[test]
+    fn test_tuple_scalar() {
+        assert_eq!(tuple_scalar(&[0.5], &[0.0], &[1.0], &[1.0]), 0.5);
+        assert_eq!(tuple_scalar(&[1.0], &[0.0], &[1.0], &[1.0]), 1.0);
+        assert_eq!(tuple_scalar(&[-0.5], &[0.0], &[1.0], &[1.0]), 0.0);
+        assert_eq!(tuple_scalar(&[0.75], &[0.0], &[0.5], &[1.0]), 0.5);
+        assert_eq!(
+            tuple_scalar(&[0.5, 0.5], &[0.0, 0.0], &[1.0, 0.0], &[1.0, 0.0]),
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_cvar() {
+        let mut font = test_font(vec![]);
+        font.tables.insert(tables::fvar::fvar {
+            axes: vec![tables::fvar::VariationAxisRecord {
+                axisTag: crate::tag!("wght"),
+                minValue: 400.0,
+                defaultValue: 400.0,
+                maxValue: 700.0,
+                flags: 0,
+                axisNameID: 256,
+            }],
+            instances: vec![],
+        });
+        font.tables.insert(tables::cvar::cvar {
+            deltasets: vec![tables::cvar::DeltaSet {
+                peak: vec![1.0],
+                start: vec![0.0],
+                end: vec![1.0],
+                deltas: vec![100],
+            }],
+        });
+        // Halfway along the axis, the control value of 570 units becomes
+        // 620, which is 396.8 in 26.6 at 10ppem
+        let hinter = Hinter::new(&font, 10, &[0.5]).unwrap();
+        assert_eq!(hinter.cvt(), &[397]);
+    }
+}
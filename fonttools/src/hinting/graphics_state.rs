@@ -0,0 +1,273 @@
+use super::F26Dot6;
+
+/// A unit vector, with components in 2.14 fixed-point format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitVector {
+    /// The x component
+    pub x: i32,
+    /// The y component
+    pub y: i32,
+}
+
+impl UnitVector {
+    /// The unit vector along the x axis
+    pub const X_AXIS: UnitVector = UnitVector { x: 0x4000, y: 0 };
+    /// The unit vector along the y axis
+    pub const Y_AXIS: UnitVector = UnitVector { x: 0, y: 0x4000 };
+
+    /// Normalizes a vector into a unit vector. A zero vector becomes the
+    /// x axis.
+    pub(crate) fn normalize(dx: i32, dy: i32) -> Self {
+        if dx == 0 && dy == 0 {
+            return UnitVector::X_AXIS;
+        }
+        let length = (dx as f64).hypot(dy as f64);
+        UnitVector {
+            x: (dx as f64 * 16384.0 / length).round() as i32,
+            y: (dy as f64 * 16384.0 / length).round() as i32,
+        }
+    }
+
+    /// The dot product of two unit vectors, in 2.14 format
+    pub(crate) fn dot(&self, other: &UnitVector) -> i32 {
+        ((self.x as i64 * other.x as i64 + self.y as i64 * other.y as i64) >> 14) as i32
+    }
+
+    /// Projects a vector onto this one
+    pub(crate) fn project(&self, dx: F26Dot6, dy: F26Dot6) -> F26Dot6 {
+        ((dx as i64 * self.x as i64 + dy as i64 * self.y as i64 + 0x2000) >> 14) as i32
+    }
+}
+
+/// How distances are rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundState {
+    /// Round to the nearest pixel (`RTG`)
+    ToGrid,
+    /// Round to the nearest half pixel (`RTHG`)
+    ToHalfGrid,
+    /// Round to the nearest half or whole pixel (`RTDG`)
+    ToDoubleGrid,
+    /// Round down (`RDTG`)
+    DownToGrid,
+    /// Round up (`RUTG`)
+    UpToGrid,
+    /// No rounding (`ROFF`)
+    Off,
+    /// Rounding set by `SROUND` or `S45ROUND`
+    Super {
+        /// The length of the grid
+        period: F26Dot6,
+        /// The offset of the grid from the origin
+        phase: F26Dot6,
+        /// Distances at least this far past a grid point round up
+        threshold: F26Dot6,
+    },
+}
+
+impl RoundState {
+    /// Decodes the argument to `SROUND` (with a grid period of one pixel)
+    /// or `S45ROUND` (with a grid period of √2/2 pixels).
+    pub(crate) fn super_round(selector: i32, grid: F26Dot6) -> Self {
+        let period = match (selector >> 6) & 3 {
+            0 => grid / 2,
+            2 => grid * 2,
+            _ => grid,
+        };
+        let phase = match (selector >> 4) & 3 {
+            0 => 0,
+            1 => period / 4,
+            2 => period / 2,
+            _ => period * 3 / 4,
+        };
+        let threshold = match selector & 15 {
+            0 => period - 1,
+            n => (n - 4) * period / 8,
+        };
+        RoundState::Super {
+            period,
+            phase,
+            threshold,
+        }
+    }
+
+    /// Rounds a distance. The sign of the distance is preserved, and
+    /// distances are never rounded past zero.
+    pub fn round(&self, distance: F26Dot6) -> F26Dot6 {
+        let magnitude = distance.wrapping_abs();
+        let rounded = match *self {
+            RoundState::ToGrid => (magnitude + 32) & !63,
+            RoundState::ToHalfGrid => (magnitude & !63) + 32,
+            RoundState::ToDoubleGrid => (magnitude + 16) & !31,
+            RoundState::DownToGrid => magnitude & !63,
+            RoundState::UpToGrid => (magnitude + 63) & !63,
+            RoundState::Off => magnitude,
+            RoundState::Super {
+                period,
+                phase,
+                threshold,
+            } => {
+                if period <= 0 {
+                    magnitude
+                } else {
+                    let value = (magnitude - phase + threshold).div_euclid(period) * period + phase;
+                    value.max(phase)
+                }
+            }
+        };
+        if distance >= 0 {
+            rounded.max(0)
+        } else {
+            -rounded.max(0)
+        }
+    }
+}
+
+/// The TrueType graphics state
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphicsState {
+    /// Whether `MIRP` may flip the sign of control values
+    pub auto_flip: bool,
+    /// The control value cut-in
+    pub control_value_cutin: F26Dot6,
+    /// The base ppem for delta instructions
+    pub delta_base: i32,
+    /// The shift applied to delta instruction magnitudes
+    pub delta_shift: i32,
+    /// The dual projection vector
+    pub dual_vector: UnitVector,
+    /// The freedom vector
+    pub freedom_vector: UnitVector,
+    /// The projection vector
+    pub projection_vector: UnitVector,
+    /// Flags set by `INSTCTRL`
+    pub instruct_control: i32,
+    /// The loop counter for instructions which take several points
+    pub loop_count: i32,
+    /// The minimum distance
+    pub minimum_distance: F26Dot6,
+    /// The round state
+    pub round_state: RoundState,
+    /// Reference point 0
+    pub rp0: i32,
+    /// Reference point 1
+    pub rp1: i32,
+    /// Reference point 2
+    pub rp2: i32,
+    /// The value set by `SCANCTRL`
+    pub scan_control: i32,
+    /// The value set by `SCANTYPE`
+    pub scan_type: i32,
+    /// The single width cut-in
+    pub single_width_cutin: F26Dot6,
+    /// The single width value
+    pub single_width_value: F26Dot6,
+    /// Zone pointer 0 (0 is the twilight zone, 1 the glyph zone)
+    pub zp0: usize,
+    /// Zone pointer 1
+    pub zp1: usize,
+    /// Zone pointer 2
+    pub zp2: usize,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            auto_flip: true,
+            control_value_cutin: 68,
+            delta_base: 9,
+            delta_shift: 3,
+            dual_vector: UnitVector::X_AXIS,
+            freedom_vector: UnitVector::X_AXIS,
+            projection_vector: UnitVector::X_AXIS,
+            instruct_control: 0,
+            loop_count: 1,
+            minimum_distance: 64,
+            round_state: RoundState::ToGrid,
+            rp0: 0,
+            rp1: 0,
+            rp2: 0,
+            scan_control: 0,
+            scan_type: 0,
+            single_width_cutin: 0,
+            single_width_value: 0,
+            zp0: 1,
+            zp1: 1,
+            zp2: 1,
+        }
+    }
+}
+
+impl GraphicsState {
+    /// Returns the state in which a glyph program starts, given the state
+    /// left by the control value program.
+    pub(crate) fn for_glyph(&self) -> Self {
+        let mut state = if self.instruct_control & 2 != 0 {
+            GraphicsState {
+                instruct_control: self.instruct_control,
+                ..Default::default()
+            }
+        } else {
+            self.clone()
+        };
+        state.dual_vector = UnitVector::X_AXIS;
+        state.freedom_vector = UnitVector::X_AXIS;
+        state.projection_vector = UnitVector::X_AXIS;
+        state.loop_count = 1;
+        state.round_state = RoundState::ToGrid;
+        state.rp0 = 0;
+        state.rp1 = 0;
+        state.rp2 = 0;
+        state.zp0 = 1;
+        state.zp1 = 1;
+        state.zp2 = 1;
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(RoundState::ToGrid.round(95), 64);
+        assert_eq!(RoundState::ToGrid.round(96), 128);
+        assert_eq!(RoundState::ToGrid.round(-96), -128);
+        assert_eq!(RoundState::ToGrid.round(10), 0);
+        assert_eq!(RoundState::ToHalfGrid.round(10), 32);
+        assert_eq!(RoundState::ToHalfGrid.round(-70), -96);
+        assert_eq!(RoundState::ToDoubleGrid.round(50), 64);
+        assert_eq!(RoundState::ToDoubleGrid.round(40), 32);
+        assert_eq!(RoundState::DownToGrid.round(127), 64);
+        assert_eq!(RoundState::UpToGrid.round(65), 128);
+        assert_eq!(RoundState::UpToGrid.round(-65), -128);
+        assert_eq!(RoundState::Off.round(-65), -65);
+        // SROUND 0x48: period 1 pixel, phase 0, threshold 1/2 pixel
+        let super_round = RoundState::super_round(0x48, 64);
+        assert_eq!(
+            super_round,
+            RoundState::Super {
+                period: 64,
+                phase: 0,
+                threshold: 32
+            }
+        );
+        assert_eq!(super_round.round(95), 64);
+        assert_eq!(super_round.round(96), 128);
+        // Threshold of period - 1 rounds up
+        assert_eq!(RoundState::super_round(0x40, 64).round(65), 128);
+        // Phase of 1/2 pixel rounds to half-pixels
+        assert_eq!(RoundState::super_round(0x68, 64).round(70), 96);
+    }
+
+    #[test]
+    fn test_vectors() {
+        let v = UnitVector::normalize(3, 4);
+        assert_eq!(v, UnitVector { x: 9830, y: 13107 });
+        assert_eq!(UnitVector::X_AXIS.project(100, 50), 100);
+        assert_eq!(UnitVector::Y_AXIS.project(100, 50), 50);
+        assert_eq!(UnitVector::X_AXIS.dot(&UnitVector::Y_AXIS), 0);
+        assert_eq!(UnitVector::normalize(0, 0), UnitVector::X_AXIS);
+    }
+}
@@ -0,0 +1,1498 @@
+use super::graphics_state::{GraphicsState, RoundState, UnitVector};
+use super::{ExecutionError, F26Dot6, HintingError, Program};
+use crate::instructions::{is_defined, length_at};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The maximum depth of nested function calls
+const MAX_CALL_DEPTH: usize = 64;
+/// The number of extra stack elements allowed beyond `maxStackElements`,
+/// as many fonts underestimate their requirements.
+const STACK_SLACK: usize = 32;
+
+const TOUCHED_X: u8 = 1;
+const TOUCHED_Y: u8 = 2;
+
+/// A set of points which instructions can manipulate
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Zone {
+    /// Scaled but unhinted positions
+    pub original: Vec<(F26Dot6, F26Dot6)>,
+    /// Current positions
+    pub current: Vec<(F26Dot6, F26Dot6)>,
+    /// Touched flags
+    pub touched: Vec<u8>,
+    pub on_curve: Vec<bool>,
+    /// Indices of the last point of each contour
+    pub contour_ends: Vec<usize>,
+}
+
+impl Zone {
+    pub fn twilight(size: usize) -> Self {
+        Zone {
+            original: vec![(0, 0); size],
+            current: vec![(0, 0); size],
+            touched: vec![0; size],
+            on_curve: vec![true; size],
+            contour_ends: vec![],
+        }
+    }
+
+    pub fn glyph(
+        original: Vec<(F26Dot6, F26Dot6)>,
+        current: Vec<(F26Dot6, F26Dot6)>,
+        on_curve: Vec<bool>,
+        contour_ends: Vec<usize>,
+    ) -> Self {
+        Zone {
+            touched: vec![0; original.len()],
+            original,
+            current,
+            on_curve,
+            contour_ends,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.current.len()
+    }
+}
+
+/// A function or instruction definition
+#[derive(Debug, Clone)]
+pub(crate) struct Definition {
+    program: Program,
+    code: Rc<[u8]>,
+    start: usize,
+}
+
+/// What to do after executing an instruction
+enum Flow {
+    Next(usize),
+    Call(Definition, i32),
+    Return,
+}
+
+/// A suspended caller
+struct Frame {
+    program: Program,
+    code: Rc<[u8]>,
+    return_pc: usize,
+    start: usize,
+    remaining: i32,
+}
+
+/// The TrueType bytecode interpreter
+pub(crate) struct Interpreter {
+    pub stack: Vec<i32>,
+    pub max_stack: usize,
+    pub storage: Vec<i32>,
+    pub cvt: Vec<F26Dot6>,
+    pub functions: HashMap<i32, Definition>,
+    pub instruction_defs: HashMap<u8, Definition>,
+    pub graphics_state: GraphicsState,
+    /// Zone 0 is the twilight zone, zone 1 the glyph zone
+    pub zones: [Zone; 2],
+    pub ppem: u16,
+    pub point_size: F26Dot6,
+    /// Converts font units to 26.6 pixels
+    pub scale: f64,
+    /// Normalized variation coordinates, one per axis of a variable font
+    pub coords: Vec<f32>,
+    pub axis_count: usize,
+    pub max_instructions: usize,
+    pub in_prep: bool,
+}
+
+fn mul_div(a: i32, b: i32, c: i32) -> i32 {
+    if c == 0 {
+        return if (a ^ b) < 0 {
+            -0x7FFF_FFFF
+        } else {
+            0x7FFF_FFFF
+        };
+    }
+    let numerator = a as i64 * b as i64;
+    let c = c as i64;
+    let half = c.abs() / 2;
+    let result = if (numerator < 0) != (c < 0) {
+        (numerator - half * numerator.signum().abs()) / c
+    } else {
+        (numerator + half) / c
+    };
+    result.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// Finds the end of an `IF` or `ELSE` block starting at `pc`, returning the
+/// offset after the matching `EIF` (or, if `stop_at_else` is set, after a
+/// matching `ELSE`).
+fn skip_block(code: &[u8], mut pc: usize, stop_at_else: bool) -> Result<usize, ExecutionError> {
+    let mut depth = 0;
+    while pc < code.len() {
+        let length = length_at(code, pc).ok_or(ExecutionError::TruncatedInstruction)?;
+        match code[pc] {
+            0x58 => depth += 1,
+            0x1B if depth == 0 && stop_at_else => return Ok(pc + length),
+            0x59 => {
+                if depth == 0 {
+                    return Ok(pc + length);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        pc += length;
+    }
+    Err(ExecutionError::UnterminatedBlock)
+}
+
+/// Finds the `ENDF` ending a definition starting at `pc`, returning the
+/// offset after it.
+fn skip_definition(code: &[u8], mut pc: usize) -> Result<usize, ExecutionError> {
+    while pc < code.len() {
+        let length = length_at(code, pc).ok_or(ExecutionError::TruncatedInstruction)?;
+        match code[pc] {
+            0x2D => return Ok(pc + length),
+            0x2C | 0x89 => return Err(ExecutionError::NestedDefinition),
+            _ => {}
+        }
+        pc += length;
+    }
+    Err(ExecutionError::UnterminatedBlock)
+}
+
+fn jump(code: &[u8], pc: usize, offset: i32) -> Result<usize, ExecutionError> {
+    let target = pc as i64 + offset as i64;
+    if target < 0 || target > code.len() as i64 {
+        return Err(ExecutionError::InvalidJump(offset));
+    }
+    Ok(target as usize)
+}
+
+impl Interpreter {
+    /// Runs a program to completion.
+    pub fn run(&mut self, program: Program, code: Rc<[u8]>) -> Result<(), HintingError> {
+        let mut program = program;
+        let mut code = code;
+        let mut pc = 0;
+        let mut frames: Vec<Frame> = vec![];
+        let mut count = 0;
+        loop {
+            if pc >= code.len() {
+                if frames.is_empty() {
+                    return Ok(());
+                }
+                return Err(HintingError::Execution {
+                    program,
+                    offset: pc,
+                    error: ExecutionError::UnterminatedBlock,
+                });
+            }
+            count += 1;
+            let flow = if count > self.max_instructions {
+                Err(ExecutionError::TooManyInstructions)
+            } else {
+                self.step(program, &code, pc, frames.len())
+            };
+            match flow {
+                Ok(Flow::Next(next)) => pc = next,
+                Ok(Flow::Call(definition, times)) => {
+                    let next = pc + length_at(&code, pc).unwrap_or(1);
+                    if times > 0 {
+                        frames.push(Frame {
+                            program,
+                            code: code.clone(),
+                            return_pc: next,
+                            start: definition.start,
+                            remaining: times,
+                        });
+                        program = definition.program;
+                        code = definition.code;
+                        pc = definition.start;
+                    } else {
+                        pc = next;
+                    }
+                }
+                Ok(Flow::Return) => {
+                    let frame = frames.last_mut().unwrap();
+                    if frame.remaining > 1 {
+                        frame.remaining -= 1;
+                        pc = frame.start;
+                    } else {
+                        let frame = frames.pop().unwrap();
+                        program = frame.program;
+                        code = frame.code;
+                        pc = frame.return_pc;
+                    }
+                }
+                Err(error) => {
+                    return Err(HintingError::Execution {
+                        program,
+                        offset: pc,
+                        error,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Executes the instruction at `pc`, handling control flow.
+    fn step(
+        &mut self,
+        program: Program,
+        code: &Rc<[u8]>,
+        pc: usize,
+        depth: usize,
+    ) -> Result<Flow, ExecutionError> {
+        let opcode = code[pc];
+        let next = pc + length_at(code, pc).ok_or(ExecutionError::TruncatedInstruction)?;
+        match opcode {
+            // NPUSHB, NPUSHW, PUSHB, PUSHW
+            0x40 | 0x41 | 0xB0..=0xBF => {
+                let (start, words) = match opcode {
+                    0x40 => (pc + 2, false),
+                    0x41 => (pc + 2, true),
+                    0xB0..=0xB7 => (pc + 1, false),
+                    _ => (pc + 1, true),
+                };
+                let data = &code[start..next];
+                if words {
+                    for word in data.chunks(2) {
+                        self.push(i16::from_be_bytes([word[0], word[1]]) as i32)?;
+                    }
+                } else {
+                    for &byte in data {
+                        self.push(byte as i32)?;
+                    }
+                }
+            }
+            // IF
+            0x58 => {
+                if self.pop()? == 0 {
+                    return Ok(Flow::Next(skip_block(code, next, true)?));
+                }
+            }
+            // ELSE: we only reach this at the end of a true IF block
+            0x1B => return Ok(Flow::Next(skip_block(code, next, false)?)),
+            // EIF
+            0x59 => {}
+            // JMPR
+            0x1C => {
+                let offset = self.pop()?;
+                return Ok(Flow::Next(jump(code, pc, offset)?));
+            }
+            // JROT, JROF
+            0x78 | 0x79 => {
+                let condition = self.pop()?;
+                let offset = self.pop()?;
+                if (condition != 0) == (opcode == 0x78) {
+                    return Ok(Flow::Next(jump(code, pc, offset)?));
+                }
+            }
+            // FDEF, IDEF
+            0x2C | 0x89 => {
+                let number = self.pop()?;
+                let definition = Definition {
+                    program,
+                    code: code.clone(),
+                    start: next,
+                };
+                let end = skip_definition(code, next)?;
+                if opcode == 0x2C {
+                    self.functions.insert(number, definition);
+                } else {
+                    if !(0..=255).contains(&number) {
+                        return Err(ExecutionError::InvalidArgument(number));
+                    }
+                    self.instruction_defs.insert(number as u8, definition);
+                }
+                return Ok(Flow::Next(end));
+            }
+            // ENDF
+            0x2D => {
+                if depth == 0 {
+                    return Err(ExecutionError::UnexpectedEndf);
+                }
+                return Ok(Flow::Return);
+            }
+            // CALL, LOOPCALL
+            0x2B | 0x2A => {
+                let number = self.pop()?;
+                let times = if opcode == 0x2A { self.pop()? } else { 1 };
+                if depth >= MAX_CALL_DEPTH {
+                    return Err(ExecutionError::CallDepthExceeded);
+                }
+                let definition = self
+                    .functions
+                    .get(&number)
+                    .cloned()
+                    .ok_or(ExecutionError::InvalidFunction(number))?;
+                return Ok(Flow::Call(definition, times));
+            }
+            opcode if !is_defined(opcode) => {
+                if depth >= MAX_CALL_DEPTH {
+                    return Err(ExecutionError::CallDepthExceeded);
+                }
+                let definition = self
+                    .instruction_defs
+                    .get(&opcode)
+                    .cloned()
+                    .ok_or(ExecutionError::InvalidOpcode(opcode))?;
+                return Ok(Flow::Call(definition, 1));
+            }
+            _ => self.execute(opcode)?,
+        }
+        Ok(Flow::Next(next))
+    }
+
+    fn pop(&mut self) -> Result<i32, ExecutionError> {
+        self.stack.pop().ok_or(ExecutionError::StackUnderflow)
+    }
+
+    fn push(&mut self, value: i32) -> Result<(), ExecutionError> {
+        if self.stack.len() >= self.max_stack + STACK_SLACK {
+            return Err(ExecutionError::StackOverflow);
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn round(&self, distance: F26Dot6) -> F26Dot6 {
+        self.graphics_state.round_state.round(distance)
+    }
+
+    fn zone_pointer(value: i32) -> Result<usize, ExecutionError> {
+        match value {
+            0 | 1 => Ok(value as usize),
+            _ => Err(ExecutionError::InvalidZone(value)),
+        }
+    }
+
+    /// Checks that a point exists in the given zone
+    fn point(&self, zone: usize, point: i32) -> Result<usize, ExecutionError> {
+        if point < 0 || point as usize >= self.zones[zone].len() {
+            return Err(ExecutionError::InvalidPoint(point));
+        }
+        Ok(point as usize)
+    }
+
+    fn current(&self, zone: usize, point: usize) -> (F26Dot6, F26Dot6) {
+        self.zones[zone].current[point]
+    }
+
+    fn original(&self, zone: usize, point: usize) -> (F26Dot6, F26Dot6) {
+        self.zones[zone].original[point]
+    }
+
+    /// Projects the vector between two current positions onto the
+    /// projection vector
+    fn project(&self, a: (F26Dot6, F26Dot6), b: (F26Dot6, F26Dot6)) -> F26Dot6 {
+        self.graphics_state
+            .projection_vector
+            .project(a.0.wrapping_sub(b.0), a.1.wrapping_sub(b.1))
+    }
+
+    /// Projects the vector between two original positions onto the dual
+    /// projection vector
+    fn dual_project(&self, a: (F26Dot6, F26Dot6), b: (F26Dot6, F26Dot6)) -> F26Dot6 {
+        self.graphics_state
+            .dual_vector
+            .project(a.0.wrapping_sub(b.0), a.1.wrapping_sub(b.1))
+    }
+
+    /// The dot product of the freedom and projection vectors, guarding
+    /// against near-orthogonal vectors
+    fn f_dot_p(&self) -> i32 {
+        let gs = &self.graphics_state;
+        let f_dot_p = gs.freedom_vector.dot(&gs.projection_vector);
+        if f_dot_p.abs() < 0x400 {
+            0x4000
+        } else {
+            f_dot_p
+        }
+    }
+
+    /// Converts a distance along the projection vector into a displacement
+    /// along the freedom vector
+    fn displacement(&self, distance: F26Dot6) -> (F26Dot6, F26Dot6) {
+        let f_dot_p = self.f_dot_p();
+        let fv = self.graphics_state.freedom_vector;
+        (
+            if fv.x != 0 {
+                mul_div(distance, fv.x, f_dot_p)
+            } else {
+                0
+            },
+            if fv.y != 0 {
+                mul_div(distance, fv.y, f_dot_p)
+            } else {
+                0
+            },
+        )
+    }
+
+    /// Shifts a point by a displacement, touching it in the directions of
+    /// the freedom vector if requested
+    fn shift_point(&mut self, zone: usize, point: usize, delta: (F26Dot6, F26Dot6), touch: bool) {
+        let fv = self.graphics_state.freedom_vector;
+        let zone = &mut self.zones[zone];
+        if fv.x != 0 {
+            zone.current[point].0 = zone.current[point].0.wrapping_add(delta.0);
+            if touch {
+                zone.touched[point] |= TOUCHED_X;
+            }
+        }
+        if fv.y != 0 {
+            zone.current[point].1 = zone.current[point].1.wrapping_add(delta.1);
+            if touch {
+                zone.touched[point] |= TOUCHED_Y;
+            }
+        }
+    }
+
+    /// Moves a point so that its projection changes by `distance`
+    fn move_point(&mut self, zone: usize, point: usize, distance: F26Dot6) {
+        let delta = self.displacement(distance);
+        self.shift_point(zone, point, delta, true);
+    }
+
+    /// Moves the original position of a point
+    fn move_original(&mut self, zone: usize, point: usize, distance: F26Dot6) {
+        let (dx, dy) = self.displacement(distance);
+        let original = &mut self.zones[zone].original[point];
+        original.0 = original.0.wrapping_add(dx);
+        original.1 = original.1.wrapping_add(dy);
+    }
+
+    fn cvt_index(&self, index: i32) -> Result<usize, ExecutionError> {
+        if index < 0 || index as usize >= self.cvt.len() {
+            return Err(ExecutionError::InvalidCvt(index));
+        }
+        Ok(index as usize)
+    }
+
+    fn storage_index(&self, index: i32) -> Result<usize, ExecutionError> {
+        if index < 0 || index as usize >= self.storage.len() {
+            return Err(ExecutionError::InvalidStorage(index));
+        }
+        Ok(index as usize)
+    }
+
+    /// Pops `loop_count` points and resets the loop counter
+    fn pop_loop_points(&mut self, zone: usize) -> Result<Vec<usize>, ExecutionError> {
+        let count = self.graphics_state.loop_count;
+        self.graphics_state.loop_count = 1;
+        (0..count)
+            .map(|_| {
+                let point = self.pop()?;
+                self.point(zone, point)
+            })
+            .collect()
+    }
+
+    fn set_vector_to_line(&mut self, opcode: u8) -> Result<(), ExecutionError> {
+        let gs = &self.graphics_state;
+        let (zp1, zp2) = (gs.zp1, gs.zp2);
+        let p1 = self.pop()?;
+        let p2 = self.pop()?;
+        let p1 = self.point(zp2, p1)?;
+        let p2 = self.point(zp1, p2)?;
+        let vector = |a: (i32, i32), b: (i32, i32)| {
+            let (dx, dy) = (b.0.wrapping_sub(a.0), b.1.wrapping_sub(a.1));
+            if opcode & 1 == 1 {
+                UnitVector::normalize(dy.wrapping_neg(), dx)
+            } else {
+                UnitVector::normalize(dx, dy)
+            }
+        };
+        let current = vector(self.current(zp2, p1), self.current(zp1, p2));
+        let gs = &mut self.graphics_state;
+        match opcode {
+            // SPVTL
+            0x06 | 0x07 => {
+                gs.projection_vector = current;
+                gs.dual_vector = current;
+            }
+            // SFVTL
+            0x08 | 0x09 => gs.freedom_vector = current,
+            // SDPVTL
+            _ => {
+                let original = vector(self.original(zp2, p1), self.original(zp1, p2));
+                let gs = &mut self.graphics_state;
+                gs.projection_vector = current;
+                gs.dual_vector = original;
+            }
+        }
+        Ok(())
+    }
+
+    /// The displacement of a reference point, for SHP, SHC and SHZ
+    fn reference_displacement(
+        &self,
+        opcode: u8,
+    ) -> Result<(usize, usize, (i32, i32)), ExecutionError> {
+        let gs = &self.graphics_state;
+        let (zone, point) = if opcode & 1 == 1 {
+            (gs.zp0, gs.rp1)
+        } else {
+            (gs.zp1, gs.rp2)
+        };
+        let point = self.point(zone, point)?;
+        let distance = self.project(self.current(zone, point), self.original(zone, point));
+        Ok((zone, point, self.displacement(distance)))
+    }
+
+    fn isect(&mut self) -> Result<(), ExecutionError> {
+        let gs = &self.graphics_state;
+        let (zp0, zp1, zp2) = (gs.zp0, gs.zp1, gs.zp2);
+        let b1 = self.pop()?;
+        let b0 = self.pop()?;
+        let a1 = self.pop()?;
+        let a0 = self.pop()?;
+        let point = self.pop()?;
+        let b1 = self.current(zp0, self.point(zp0, b1)?);
+        let b0 = self.current(zp0, self.point(zp0, b0)?);
+        let a1 = self.current(zp1, self.point(zp1, a1)?);
+        let a0 = self.current(zp1, self.point(zp1, a0)?);
+        let point = self.point(zp2, point)?;
+
+        let (dbx, dby) = (b1.0 as i64 - b0.0 as i64, b1.1 as i64 - b0.1 as i64);
+        let (dax, day) = (a1.0 as i64 - a0.0 as i64, a1.1 as i64 - a0.1 as i64);
+        let (dx, dy) = (b0.0 as i64 - a0.0 as i64, b0.1 as i64 - a0.1 as i64);
+        let discriminant = (dax * -dby + day * dbx) / 64;
+        let dot_product = (dax * dbx + day * dby) / 64;
+        let result = if discriminant != 0 && 19 * discriminant.abs() > dot_product.abs() {
+            let v = (dx * -dby + dy * dbx) / 64;
+            (
+                (a0.0 as i64 + dax * v / discriminant) as i32,
+                (a0.1 as i64 + day * v / discriminant) as i32,
+            )
+        } else {
+            // The lines are (nearly) parallel: use the middle of the points
+            (
+                ((a0.0 as i64 + a1.0 as i64 + b0.0 as i64 + b1.0 as i64) / 4) as i32,
+                ((a0.1 as i64 + a1.1 as i64 + b0.1 as i64 + b1.1 as i64) / 4) as i32,
+            )
+        };
+        let zone = &mut self.zones[zp2];
+        zone.current[point] = result;
+        zone.touched[point] |= TOUCHED_X | TOUCHED_Y;
+        Ok(())
+    }
+
+    /// Interpolates untouched points in the glyph zone along one axis
+    fn interpolate_untouched(&mut self, x_axis: bool) {
+        let flag = if x_axis { TOUCHED_X } else { TOUCHED_Y };
+        let coord = |p: (i32, i32)| if x_axis { p.0 } else { p.1 };
+        let zone = &mut self.zones[1];
+        let mut start = 0;
+        for &end in &zone.contour_ends {
+            if end >= zone.current.len() || end < start {
+                break;
+            }
+            let touched: Vec<usize> = (start..=end)
+                .filter(|&p| zone.touched[p] & flag != 0)
+                .collect();
+            if touched.len() == 1 {
+                let p = touched[0];
+                let delta = coord(zone.current[p]) - coord(zone.original[p]);
+                for q in (start..=end).filter(|&q| q != p) {
+                    let value = coord(zone.current[q]) + delta;
+                    set_coord(&mut zone.current[q], x_axis, value);
+                }
+            } else if touched.len() > 1 {
+                for (i, &p1) in touched.iter().enumerate() {
+                    let p2 = touched[(i + 1) % touched.len()];
+                    // The untouched points between p1 and p2, wrapping around
+                    let mut q = if p1 == end { start } else { p1 + 1 };
+                    while q != p2 {
+                        let value = interpolate(
+                            coord(zone.original[q]),
+                            (coord(zone.original[p1]), coord(zone.current[p1])),
+                            (coord(zone.original[p2]), coord(zone.current[p2])),
+                        );
+                        set_coord(&mut zone.current[q], x_axis, value);
+                        q = if q == end { start } else { q + 1 };
+                    }
+                }
+            }
+            start = end + 1;
+        }
+    }
+
+    /// Executes an instruction which does not affect control flow.
+    fn execute(&mut self, opcode: u8) -> Result<(), ExecutionError> {
+        match opcode {
+            // SVTCA, SPVTCA, SFVTCA
+            0x00..=0x05 => {
+                let vector = if opcode & 1 == 1 {
+                    UnitVector::X_AXIS
+                } else {
+                    UnitVector::Y_AXIS
+                };
+                let gs = &mut self.graphics_state;
+                if opcode < 0x04 {
+                    gs.projection_vector = vector;
+                    gs.dual_vector = vector;
+                }
+                if !(0x02..0x04).contains(&opcode) {
+                    gs.freedom_vector = vector;
+                }
+            }
+            // SPVTL, SFVTL, SDPVTL
+            0x06..=0x09 | 0x86 | 0x87 => self.set_vector_to_line(opcode)?,
+            // SPVFS, SFVFS
+            0x0A | 0x0B => {
+                let y = self.pop()?;
+                let x = self.pop()?;
+                let vector = UnitVector::normalize(x, y);
+                let gs = &mut self.graphics_state;
+                if opcode == 0x0A {
+                    gs.projection_vector = vector;
+                    gs.dual_vector = vector;
+                } else {
+                    gs.freedom_vector = vector;
+                }
+            }
+            // GPV, GFV
+            0x0C | 0x0D => {
+                let gs = &self.graphics_state;
+                let vector = if opcode == 0x0C {
+                    gs.projection_vector
+                } else {
+                    gs.freedom_vector
+                };
+                self.push(vector.x)?;
+                self.push(vector.y)?;
+            }
+            // SFVTPV
+            0x0E => self.graphics_state.freedom_vector = self.graphics_state.projection_vector,
+            // ISECT
+            0x0F => self.isect()?,
+            // SRP0, SRP1, SRP2
+            0x10 => self.graphics_state.rp0 = self.pop()?,
+            0x11 => self.graphics_state.rp1 = self.pop()?,
+            0x12 => self.graphics_state.rp2 = self.pop()?,
+            // SZP0, SZP1, SZP2, SZPS
+            0x13..=0x16 => {
+                let zone = Self::zone_pointer(self.pop()?)?;
+                let gs = &mut self.graphics_state;
+                match opcode {
+                    0x13 => gs.zp0 = zone,
+                    0x14 => gs.zp1 = zone,
+                    0x15 => gs.zp2 = zone,
+                    _ => {
+                        gs.zp0 = zone;
+                        gs.zp1 = zone;
+                        gs.zp2 = zone;
+                    }
+                }
+            }
+            // SLOOP
+            0x17 => {
+                let count = self.pop()?;
+                if count < 0 {
+                    return Err(ExecutionError::InvalidArgument(count));
+                }
+                self.graphics_state.loop_count = count.min(0xFFFF);
+            }
+            // RTG, RTHG, RTDG, ROFF, RUTG, RDTG
+            0x18 => self.graphics_state.round_state = RoundState::ToGrid,
+            0x19 => self.graphics_state.round_state = RoundState::ToHalfGrid,
+            0x3D => self.graphics_state.round_state = RoundState::ToDoubleGrid,
+            0x7A => self.graphics_state.round_state = RoundState::Off,
+            0x7C => self.graphics_state.round_state = RoundState::UpToGrid,
+            0x7D => self.graphics_state.round_state = RoundState::DownToGrid,
+            // SROUND, S45ROUND
+            0x76 => self.graphics_state.round_state = RoundState::super_round(self.pop()?, 64),
+            0x77 => self.graphics_state.round_state = RoundState::super_round(self.pop()?, 45),
+            // SMD, SCVTCI, SSWCI, SSW
+            0x1A => self.graphics_state.minimum_distance = self.pop()?,
+            0x1D => self.graphics_state.control_value_cutin = self.pop()?,
+            0x1E => self.graphics_state.single_width_cutin = self.pop()?,
+            0x1F => {
+                let value = self.pop()?;
+                self.graphics_state.single_width_value = (value as f64 * self.scale).round() as i32;
+            }
+            // DUP, POP, CLEAR, SWAP, DEPTH
+            0x20 => {
+                let value = self.pop()?;
+                self.push(value)?;
+                self.push(value)?;
+            }
+            0x21 => {
+                self.pop()?;
+            }
+            0x22 => self.stack.clear(),
+            0x23 => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(b)?;
+                self.push(a)?;
+            }
+            0x24 => self.push(self.stack.len() as i32)?,
+            // CINDEX, MINDEX
+            0x25 | 0x26 => {
+                let k = self.pop()?;
+                if k <= 0 || k as usize > self.stack.len() {
+                    return Err(ExecutionError::InvalidArgument(k));
+                }
+                let index = self.stack.len() - k as usize;
+                let value = if opcode == 0x25 {
+                    self.stack[index]
+                } else {
+                    self.stack.remove(index)
+                };
+                self.push(value)?;
+            }
+            // ROLL
+            0x8A => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                let c = self.pop()?;
+                self.push(b)?;
+                self.push(a)?;
+                self.push(c)?;
+            }
+            // ALIGNPTS
+            0x27 => {
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                let p2 = self.pop()?;
+                let p1 = self.pop()?;
+                let p2 = self.point(zp0, p2)?;
+                let p1 = self.point(zp1, p1)?;
+                let distance = self.project(self.current(zp0, p2), self.current(zp1, p1)) / 2;
+                self.move_point(zp1, p1, distance);
+                self.move_point(zp0, p2, -distance);
+            }
+            // UTP
+            0x29 => {
+                let zp0 = self.graphics_state.zp0;
+                let point = self.pop()?;
+                let point = self.point(zp0, point)?;
+                let fv = self.graphics_state.freedom_vector;
+                let touched = &mut self.zones[zp0].touched[point];
+                if fv.x != 0 {
+                    *touched &= !TOUCHED_X;
+                }
+                if fv.y != 0 {
+                    *touched &= !TOUCHED_Y;
+                }
+            }
+            // MDAP
+            0x2E | 0x2F => {
+                let zp0 = self.graphics_state.zp0;
+                let point = self.pop()?;
+                let point = self.point(zp0, point)?;
+                let distance = if opcode & 1 == 1 {
+                    let position = self.project(self.current(zp0, point), (0, 0));
+                    self.round(position) - position
+                } else {
+                    0
+                };
+                self.move_point(zp0, point, distance);
+                self.graphics_state.rp0 = point as i32;
+                self.graphics_state.rp1 = point as i32;
+            }
+            // IUP
+            0x30 | 0x31 => self.interpolate_untouched(opcode & 1 == 1),
+            // SHP
+            0x32 | 0x33 => {
+                let (_, _, delta) = self.reference_displacement(opcode)?;
+                let zp2 = self.graphics_state.zp2;
+                for point in self.pop_loop_points(zp2)? {
+                    self.shift_point(zp2, point, delta, true);
+                }
+            }
+            // SHC
+            0x34 | 0x35 => {
+                let (zone, reference, delta) = self.reference_displacement(opcode)?;
+                let zp2 = self.graphics_state.zp2;
+                let contour = self.pop()?;
+                let ends = &self.zones[zp2].contour_ends;
+                if contour < 0 || contour as usize >= ends.len() {
+                    return Err(ExecutionError::InvalidContour(contour));
+                }
+                let start = if contour == 0 {
+                    0
+                } else {
+                    ends[contour as usize - 1] + 1
+                };
+                let end = ends[contour as usize];
+                for point in start..=end.min(self.zones[zp2].len().saturating_sub(1)) {
+                    if zone != zp2 || point != reference {
+                        self.shift_point(zp2, point, delta, true);
+                    }
+                }
+            }
+            // SHZ
+            0x36 | 0x37 => {
+                let (zone, reference, delta) = self.reference_displacement(opcode)?;
+                let target = Self::zone_pointer(self.pop()?)?;
+                // Phantom points are not shifted
+                let count = if target == 1 {
+                    self.zones[1].len().saturating_sub(4)
+                } else {
+                    self.zones[0].len()
+                };
+                for point in 0..count {
+                    if zone != target || point != reference {
+                        self.shift_point(target, point, delta, false);
+                    }
+                }
+            }
+            // SHPIX
+            0x38 => {
+                let distance = self.pop()?;
+                let fv = self.graphics_state.freedom_vector;
+                let delta = (
+                    ((distance as i64 * fv.x as i64) >> 14) as i32,
+                    ((distance as i64 * fv.y as i64) >> 14) as i32,
+                );
+                let zp2 = self.graphics_state.zp2;
+                for point in self.pop_loop_points(zp2)? {
+                    self.shift_point(zp2, point, delta, true);
+                }
+            }
+            // IP
+            0x39 => {
+                let gs = &self.graphics_state;
+                let (zp0, zp1, zp2) = (gs.zp0, gs.zp1, gs.zp2);
+                let (rp1, rp2) = (gs.rp1, gs.rp2);
+                let rp1 = self.point(zp0, rp1)?;
+                let rp2 = self.point(zp1, rp2)?;
+                let original_base = self.original(zp0, rp1);
+                let current_base = self.current(zp0, rp1);
+                let original_range = self.dual_project(self.original(zp1, rp2), original_base);
+                let current_range = self.project(self.current(zp1, rp2), current_base);
+                for point in self.pop_loop_points(zp2)? {
+                    let original_distance =
+                        self.dual_project(self.original(zp2, point), original_base);
+                    let current_distance = self.project(self.current(zp2, point), current_base);
+                    let new_distance = if original_distance == 0 {
+                        0
+                    } else if original_range != 0 {
+                        mul_div(original_distance, current_range, original_range)
+                    } else {
+                        current_distance
+                    };
+                    self.move_point(zp2, point, new_distance - current_distance);
+                }
+            }
+            // MSIRP
+            0x3A | 0x3B => {
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                let distance = self.pop()?;
+                let point = self.pop()?;
+                let point = self.point(zp1, point)?;
+                let rp0 = self.point(zp0, self.graphics_state.rp0)?;
+                if zp1 == 0 {
+                    // Twilight points are placed relative to the reference
+                    self.zones[0].original[point] = self.original(zp0, rp0);
+                    self.move_original(0, point, distance);
+                    self.zones[0].current[point] = self.zones[0].original[point];
+                }
+                let current = self.project(self.current(zp1, point), self.current(zp0, rp0));
+                self.move_point(zp1, point, distance.wrapping_sub(current));
+                let gs = &mut self.graphics_state;
+                gs.rp1 = gs.rp0;
+                gs.rp2 = point as i32;
+                if opcode & 1 == 1 {
+                    gs.rp0 = point as i32;
+                }
+            }
+            // ALIGNRP
+            0x3C => {
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                let rp0 = self.point(zp0, self.graphics_state.rp0)?;
+                for point in self.pop_loop_points(zp1)? {
+                    let distance = self.project(self.current(zp1, point), self.current(zp0, rp0));
+                    self.move_point(zp1, point, -distance);
+                }
+            }
+            // MIAP
+            0x3E | 0x3F => {
+                let zp0 = self.graphics_state.zp0;
+                let index = self.pop()?;
+                let point = self.pop()?;
+                let mut distance = self.cvt[self.cvt_index(index)?];
+                let point = self.point(zp0, point)?;
+                if zp0 == 0 {
+                    let fv = self.graphics_state.freedom_vector;
+                    let position = (
+                        ((distance as i64 * fv.x as i64) >> 14) as i32,
+                        ((distance as i64 * fv.y as i64) >> 14) as i32,
+                    );
+                    self.zones[0].original[point] = position;
+                    self.zones[0].current[point] = position;
+                }
+                let original = self.project(self.current(zp0, point), (0, 0));
+                if opcode & 1 == 1 {
+                    if (distance - original).abs() > self.graphics_state.control_value_cutin {
+                        distance = original;
+                    }
+                    distance = self.round(distance);
+                }
+                self.move_point(zp0, point, distance.wrapping_sub(original));
+                self.graphics_state.rp0 = point as i32;
+                self.graphics_state.rp1 = point as i32;
+            }
+            // WS, RS
+            0x42 => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                let index = self.storage_index(index)?;
+                self.storage[index] = value;
+            }
+            0x43 => {
+                let index = self.pop()?;
+                let index = self.storage_index(index)?;
+                self.push(self.storage[index])?;
+            }
+            // WCVTP, WCVTF, RCVT
+            0x44 | 0x70 => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                let index = self.cvt_index(index)?;
+                self.cvt[index] = if opcode == 0x70 {
+                    (value as f64 * self.scale).round() as i32
+                } else {
+                    value
+                };
+            }
+            0x45 => {
+                let index = self.pop()?;
+                let index = self.cvt_index(index)?;
+                self.push(self.cvt[index])?;
+            }
+            // GC
+            0x46 | 0x47 => {
+                let zp2 = self.graphics_state.zp2;
+                let point = self.pop()?;
+                let point = self.point(zp2, point)?;
+                let value = if opcode & 1 == 1 {
+                    self.dual_project(self.original(zp2, point), (0, 0))
+                } else {
+                    self.project(self.current(zp2, point), (0, 0))
+                };
+                self.push(value)?;
+            }
+            // SCFS
+            0x48 => {
+                let zp2 = self.graphics_state.zp2;
+                let value = self.pop()?;
+                let point = self.pop()?;
+                let point = self.point(zp2, point)?;
+                let current = self.project(self.current(zp2, point), (0, 0));
+                self.move_point(zp2, point, value.wrapping_sub(current));
+                if zp2 == 0 {
+                    self.zones[0].original[point] = self.zones[0].current[point];
+                }
+            }
+            // MD
+            0x49 | 0x4A => {
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                let k = self.pop()?;
+                let l = self.pop()?;
+                let k = self.point(zp1, k)?;
+                let l = self.point(zp0, l)?;
+                let distance = if opcode & 1 == 1 {
+                    self.project(self.current(zp0, l), self.current(zp1, k))
+                } else {
+                    self.dual_project(self.original(zp0, l), self.original(zp1, k))
+                };
+                self.push(distance)?;
+            }
+            // MPPEM, MPS
+            0x4B => self.push(self.ppem as i32)?,
+            0x4C => self.push(self.point_size)?,
+            // FLIPON, FLIPOFF
+            0x4D => self.graphics_state.auto_flip = true,
+            0x4E => self.graphics_state.auto_flip = false,
+            // DEBUG, SANGW, AA
+            0x4F | 0x7E | 0x7F => {
+                self.pop()?;
+            }
+            // LT, LTEQ, GT, GTEQ, EQ, NEQ, AND, OR
+            0x50..=0x55 | 0x5A | 0x5B => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let result = match opcode {
+                    0x50 => a < b,
+                    0x51 => a <= b,
+                    0x52 => a > b,
+                    0x53 => a >= b,
+                    0x54 => a == b,
+                    0x55 => a != b,
+                    0x5A => a != 0 && b != 0,
+                    _ => a != 0 || b != 0,
+                };
+                self.push(result as i32)?;
+            }
+            // ODD, EVEN
+            0x56 | 0x57 => {
+                let value = self.pop()?;
+                let rounded = self.round(value) & 127;
+                let result = if opcode == 0x56 {
+                    rounded == 64
+                } else {
+                    rounded == 0
+                };
+                self.push(result as i32)?;
+            }
+            // NOT
+            0x5C => {
+                let value = self.pop()?;
+                self.push((value == 0) as i32)?;
+            }
+            // DELTAP1, DELTAP2, DELTAP3, DELTAC1, DELTAC2, DELTAC3
+            0x5D | 0x71..=0x75 => {
+                let base = self.graphics_state.delta_base
+                    + match opcode {
+                        0x5D | 0x73 => 0,
+                        0x71 | 0x74 => 16,
+                        _ => 32,
+                    };
+                let count = self.pop()?;
+                for _ in 0..count.max(0) {
+                    let target = self.pop()?;
+                    let argument = self.pop()?;
+                    if (argument >> 4) & 15 != self.ppem as i32 - base {
+                        continue;
+                    }
+                    let mut steps = (argument & 15) - 8;
+                    if steps >= 0 {
+                        steps += 1;
+                    }
+                    let distance = steps * 64 / (1 << self.graphics_state.delta_shift);
+                    if matches!(opcode, 0x5D | 0x71 | 0x72) {
+                        let zp0 = self.graphics_state.zp0;
+                        let point = self.point(zp0, target)?;
+                        self.move_point(zp0, point, distance);
+                    } else {
+                        let index = self.cvt_index(target)?;
+                        self.cvt[index] = self.cvt[index].wrapping_add(distance);
+                    }
+                }
+            }
+            // SDB, SDS
+            0x5E => self.graphics_state.delta_base = self.pop()?,
+            0x5F => self.graphics_state.delta_shift = self.pop()?.clamp(0, 6),
+            // ADD, SUB, DIV, MUL, MAX, MIN
+            0x60..=0x63 | 0x8B | 0x8C => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let result = match opcode {
+                    0x60 => a.wrapping_add(b),
+                    0x61 => a.wrapping_sub(b),
+                    0x62 => {
+                        if b == 0 {
+                            return Err(ExecutionError::DivideByZero);
+                        }
+                        ((a as i64 * 64) / b as i64) as i32
+                    }
+                    0x63 => mul_div(a, b, 64),
+                    0x8B => a.max(b),
+                    _ => a.min(b),
+                };
+                self.push(result)?;
+            }
+            // ABS, NEG, FLOOR, CEILING
+            0x64..=0x67 => {
+                let value = self.pop()?;
+                let result = match opcode {
+                    0x64 => value.wrapping_abs(),
+                    0x65 => value.wrapping_neg(),
+                    0x66 => value & !63,
+                    _ => value.wrapping_add(63) & !63,
+                };
+                self.push(result)?;
+            }
+            // ROUND
+            0x68..=0x6B => {
+                let value = self.pop()?;
+                self.push(self.round(value))?;
+            }
+            // NROUND: we apply no engine compensation
+            0x6C..=0x6F => {}
+            // FLIPPT
+            0x80 => {
+                let zp0 = self.graphics_state.zp0;
+                for point in self.pop_loop_points(zp0)? {
+                    let on_curve = &mut self.zones[zp0].on_curve[point];
+                    *on_curve = !*on_curve;
+                }
+            }
+            // FLIPRGON, FLIPRGOFF
+            0x81 | 0x82 => {
+                let zp0 = self.graphics_state.zp0;
+                let high = self.pop()?;
+                let low = self.pop()?;
+                let high = self.point(zp0, high)?;
+                let low = self.point(zp0, low)?;
+                for point in low..=high {
+                    self.zones[zp0].on_curve[point] = opcode == 0x81;
+                }
+            }
+            // SCANCTRL, SCANTYPE
+            0x85 => self.graphics_state.scan_control = self.pop()?,
+            0x8D => self.graphics_state.scan_type = self.pop()?,
+            // GETINFO
+            0x88 => {
+                let selector = self.pop()?;
+                let mut result = 0;
+                if selector & 1 != 0 {
+                    // Behave as a v40 interpreter
+                    result |= 40;
+                }
+                if selector & 8 != 0 && self.axis_count > 0 {
+                    result |= 1 << 10;
+                }
+                if selector & 32 != 0 {
+                    // Grayscale rendering
+                    result |= 1 << 12;
+                }
+                self.push(result)?;
+            }
+            // INSTCTRL
+            0x8E => {
+                let selector = self.pop()?;
+                let value = self.pop()?;
+                if !(1..=3).contains(&selector) {
+                    return Err(ExecutionError::InvalidArgument(selector));
+                }
+                if self.in_prep {
+                    let mask = 1 << (selector - 1);
+                    let value = if value != 0 { mask } else { 0 };
+                    let gs = &mut self.graphics_state;
+                    gs.instruct_control = (gs.instruct_control & !mask) | value;
+                }
+            }
+            // GETVARIATION
+            0x91 => {
+                if self.axis_count == 0 {
+                    return Err(ExecutionError::InvalidOpcode(opcode));
+                }
+                for axis in 0..self.axis_count {
+                    let coord = self.coords.get(axis).copied().unwrap_or(0.0);
+                    self.push((coord * 16384.0).round() as i32)?;
+                }
+            }
+            // GETDATA
+            0x92 => self.push(17)?,
+            // MDRP
+            0xC0..=0xDF => {
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                let point = self.pop()?;
+                let point = self.point(zp1, point)?;
+                let rp0 = self.point(zp0, self.graphics_state.rp0)?;
+                let gs = &self.graphics_state;
+                let mut original =
+                    self.dual_project(self.original(zp1, point), self.original(zp0, rp0));
+                if (original - gs.single_width_value).abs() < gs.single_width_cutin {
+                    original = if original >= 0 {
+                        gs.single_width_value
+                    } else {
+                        -gs.single_width_value
+                    };
+                }
+                let mut distance = if opcode & 4 != 0 {
+                    self.round(original)
+                } else {
+                    original
+                };
+                if opcode & 8 != 0 {
+                    distance = self.apply_minimum_distance(original, distance);
+                }
+                let current = self.project(self.current(zp1, point), self.current(zp0, rp0));
+                self.move_point(zp1, point, distance.wrapping_sub(current));
+                let gs = &mut self.graphics_state;
+                gs.rp1 = gs.rp0;
+                gs.rp2 = point as i32;
+                if opcode & 16 != 0 {
+                    gs.rp0 = point as i32;
+                }
+            }
+            // MIRP
+            0xE0..=0xFF => {
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                let index = self.pop()?;
+                let point = self.pop()?;
+                let point = self.point(zp1, point)?;
+                let rp0 = self.point(zp0, self.graphics_state.rp0)?;
+                // An index of -1 refers to a control value of zero
+                let mut cvt_distance = if index == -1 {
+                    0
+                } else {
+                    self.cvt[self.cvt_index(index)?]
+                };
+                let gs = &self.graphics_state;
+                if (cvt_distance - gs.single_width_value).abs() < gs.single_width_cutin {
+                    cvt_distance = if cvt_distance >= 0 {
+                        gs.single_width_value
+                    } else {
+                        -gs.single_width_value
+                    };
+                }
+                if zp1 == 0 {
+                    // Twilight points are placed relative to the reference
+                    let fv = gs.freedom_vector;
+                    let reference = self.original(zp0, rp0);
+                    let position = (
+                        reference.0 + ((cvt_distance as i64 * fv.x as i64) >> 14) as i32,
+                        reference.1 + ((cvt_distance as i64 * fv.y as i64) >> 14) as i32,
+                    );
+                    self.zones[0].original[point] = position;
+                    self.zones[0].current[point] = position;
+                }
+                let gs = &self.graphics_state;
+                let original =
+                    self.dual_project(self.original(zp1, point), self.original(zp0, rp0));
+                let current = self.project(self.current(zp1, point), self.current(zp0, rp0));
+                if gs.auto_flip && (original ^ cvt_distance) < 0 {
+                    cvt_distance = -cvt_distance;
+                }
+                let mut distance = if opcode & 4 != 0 {
+                    if zp0 == zp1 && (cvt_distance - original).abs() > gs.control_value_cutin {
+                        cvt_distance = original;
+                    }
+                    self.round(cvt_distance)
+                } else {
+                    cvt_distance
+                };
+                if opcode & 8 != 0 {
+                    distance = self.apply_minimum_distance(original, distance);
+                }
+                self.move_point(zp1, point, distance.wrapping_sub(current));
+                let gs = &mut self.graphics_state;
+                gs.rp1 = gs.rp0;
+                gs.rp2 = point as i32;
+                if opcode & 16 != 0 {
+                    gs.rp0 = point as i32;
+                }
+            }
+            _ => return Err(ExecutionError::InvalidOpcode(opcode)),
+        }
+        Ok(())
+    }
+
+    fn apply_minimum_distance(&self, original: F26Dot6, distance: F26Dot6) -> F26Dot6 {
+        let minimum = self.graphics_state.minimum_distance;
+        if original >= 0 {
+            distance.max(minimum)
+        } else {
+            distance.min(-minimum)
+        }
+    }
+}
+
+fn set_coord(point: &mut (F26Dot6, F26Dot6), x_axis: bool, value: F26Dot6) {
+    if x_axis {
+        point.0 = value;
+    } else {
+        point.1 = value;
+    }
+}
+
+/// Interpolates an untouched coordinate between two touched points, given
+/// as (original, current) pairs.
+fn interpolate(
+    original: F26Dot6,
+    mut p1: (F26Dot6, F26Dot6),
+    mut p2: (F26Dot6, F26Dot6),
+) -> F26Dot6 {
+    if p1.0 > p2.0 {
+        std::mem::swap(&mut p1, &mut p2);
+    }
+    if original <= p1.0 {
+        original + p1.1 - p1.0
+    } else if original >= p2.0 {
+        original + p2.1 - p2.0
+    } else {
+        p1.1 + mul_div(original - p1.0, p2.1 - p1.1, p2.0 - p1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::{assemble, from_text};
+
+    fn interpreter() -> Interpreter {
+        Interpreter {
+            stack: vec![],
+            max_stack: 32,
+            storage: vec![0; 4],
+            cvt: vec![0, 64, 100],
+            functions: HashMap::new(),
+            instruction_defs: HashMap::new(),
+            graphics_state: GraphicsState::default(),
+            zones: [
+                Zone::twilight(2),
+                Zone::glyph(
+                    vec![(0, 0), (100, 0), (100, 100), (0, 100), (0, 0), (200, 0)],
+                    vec![(0, 0), (100, 0), (100, 100), (0, 100), (0, 0), (200, 0)],
+                    vec![true; 6],
+                    vec![3],
+                ),
+            ],
+            ppem: 12,
+            point_size: 12 * 64,
+            scale: 12.0 * 64.0 / 1000.0,
+            coords: vec![],
+            axis_count: 0,
+            max_instructions: 10000,
+            in_prep: false,
+        }
+    }
+
+    fn run(interpreter: &mut Interpreter, text: &str) -> Result<(), HintingError> {
+        let code = assemble(&from_text(text).unwrap()).unwrap();
+        interpreter.run(Program::Glyph(0), code.into())
+    }
+
+    fn error(result: Result<(), HintingError>) -> ExecutionError {
+        match result {
+            Err(HintingError::Execution { error, .. }) => error,
+            other => panic!("Expected an execution error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_and_control_flow() {
+        let mut interpreter = interpreter();
+        run(
+            &mut interpreter,
+            "PUSH[ ] 1 FDEF[ ] PUSH[ ] 64 MUL[ ] ENDF[ ]
+             PUSH[ ] 3 1 CALL[ ]
+             PUSH[ ] 2 LT[ ] IF[ ] PUSH[ ] 10 ELSE[ ] PUSH[ ] 20 EIF[ ]
+             PUSH[ ] 5 6 7 3 MINDEX[ ]",
+        )
+        .unwrap();
+        assert_eq!(interpreter.stack, vec![20, 6, 7, 5]);
+    }
+
+    #[test]
+    fn test_loopcall_and_storage() {
+        let mut interpreter = interpreter();
+        run(
+            &mut interpreter,
+            "PUSH[ ] 0 FDEF[ ] PUSH[ ] 0 DUP[ ] RS[ ] PUSH[ ] 1 ADD[ ] WS[ ] ENDF[ ]
+             PUSH[ ] 5 0 LOOPCALL[ ] PUSH[ ] 0 RS[ ]",
+        )
+        .unwrap();
+        assert_eq!(interpreter.stack, vec![5]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error(run(&mut interpreter(), "POP[ ]")),
+            ExecutionError::StackUnderflow
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "PUSH[ ] -3 JMPR[ ]")),
+            ExecutionError::TooManyInstructions
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "PUSH[ ] 3 CALL[ ]")),
+            ExecutionError::InvalidFunction(3)
+        );
+        assert_eq!(
+            error(run(
+                &mut interpreter(),
+                "PUSH[ ] 0 FDEF[ ] PUSH[ ] 0 CALL[ ] ENDF[ ] PUSH[ ] 0 CALL[ ]"
+            )),
+            ExecutionError::CallDepthExceeded
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "PUSH[ ] 1 0 DIV[ ]")),
+            ExecutionError::DivideByZero
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "PUSH[ ] 9 RCVT[ ]")),
+            ExecutionError::InvalidCvt(9)
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "PUSH[ ] 0 IF[ ] PUSH[ ] 1")),
+            ExecutionError::UnterminatedBlock
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "PUSH[ ] 1 ENDF[ ]")),
+            ExecutionError::UnexpectedEndf
+        );
+        assert_eq!(
+            error(run(&mut interpreter(), "INS_28[ ]")),
+            ExecutionError::InvalidOpcode(0x28)
+        );
+        match run(&mut interpreter(), "PUSH[ ] 1 2 9 MDAP[1]") {
+            Err(HintingError::Execution {
+                program, offset, ..
+            }) => {
+                assert_eq!(program, Program::Glyph(0));
+                assert_eq!(offset, 4);
+            }
+            other => panic!("Expected an execution error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut interpreter = interpreter();
+        interpreter.max_stack = 0;
+        let values: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        assert_eq!(
+            error(run(
+                &mut interpreter,
+                &format!("PUSH[ ] {}", values.join(" "))
+            )),
+            ExecutionError::StackOverflow
+        );
+    }
+
+    #[test]
+    fn test_idef() {
+        let mut interpreter = interpreter();
+        run(
+            &mut interpreter,
+            "PUSH[ ] 40 IDEF[ ] PUSH[ ] 7 ENDF[ ] INS_28[ ] INS_28[ ]",
+        )
+        .unwrap();
+        assert_eq!(interpreter.stack, vec![7, 7]);
+    }
+
+    #[test]
+    fn test_point_movement() {
+        let mut interpreter = interpreter();
+        // Round the right edge to the grid and keep the left edge a rounded
+        // distance from it, then fit the bottom and top edges
+        run(
+            &mut interpreter,
+            "SVTCA[1] PUSH[ ] 1 MDAP[1] PUSH[ ] 0 MDRP[01100]
+             SVTCA[0] PUSH[ ] 0 MDAP[1] PUSH[ ] 2 2 MIAP[1] IUP[0] IUP[1]",
+        )
+        .unwrap();
+        let zone = &interpreter.zones[1];
+        assert_eq!(zone.current[1], (128, 0));
+        assert_eq!(zone.current[0], (0, 0));
+        assert_eq!(zone.current[2], (128, 128));
+        // Untouched point 3 is interpolated in both directions
+        assert_eq!(zone.current[3], (0, 128));
+        // Phantom points are not affected by IUP
+        assert_eq!(zone.current[4], (0, 0));
+    }
+
+    #[test]
+    fn test_shift_and_interpolate() {
+        let mut interpreter = interpreter();
+        run(
+            &mut interpreter,
+            "SVTCA[1] PUSH[ ] 0 MDAP[0] PUSH[ ] 1 64 SHPIX[ ]
+             PUSH[ ] 0 SRP1[ ] PUSH[ ] 1 SRP2[ ] PUSH[ ] 2 IP[ ]",
+        )
+        .unwrap();
+        let zone = &interpreter.zones[1];
+        assert_eq!(zone.current[1], (164, 0));
+        assert_eq!(zone.current[2], (164, 100));
+    }
+}
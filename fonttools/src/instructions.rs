@@ -0,0 +1,645 @@
+//! TrueType instructions
+//!
+//! The `fpgm` and `prep` tables, and each glyph in the `glyf` table, contain
+//! TrueType bytecode. This module disassembles that bytecode into a list of
+//! [`Instruction`]s, assembles it back, and converts instructions to and from
+//! a readable text form in the style of `fontTools`' `ttx`:
+//!
+//! ```text
+//! PUSHB[ ] 0
+//! FDEF[ ]
+//!   MDAP[1]
+//!   MIRP[01101]
+//! ENDF[ ]
+//! ```
+//!
+//! In the text form, flag bits are written in brackets, most significant bit
+//! first; instructions without flags are written with an empty `[ ]`. The
+//! values of push instructions follow the mnemonic. When assembling text,
+//! the pseudo-instruction `PUSH[ ]` may be used to push values using the
+//! most compact encoding.
+use std::fmt;
+
+/// The mnemonic, first opcode and number of flag bits of each instruction.
+const OPCODES: &[(&str, u8, u8)] = &[
+    ("SVTCA", 0x00, 1),
+    ("SPVTCA", 0x02, 1),
+    ("SFVTCA", 0x04, 1),
+    ("SPVTL", 0x06, 1),
+    ("SFVTL", 0x08, 1),
+    ("SPVFS", 0x0A, 0),
+    ("SFVFS", 0x0B, 0),
+    ("GPV", 0x0C, 0),
+    ("GFV", 0x0D, 0),
+    ("SFVTPV", 0x0E, 0),
+    ("ISECT", 0x0F, 0),
+    ("SRP0", 0x10, 0),
+    ("SRP1", 0x11, 0),
+    ("SRP2", 0x12, 0),
+    ("SZP0", 0x13, 0),
+    ("SZP1", 0x14, 0),
+    ("SZP2", 0x15, 0),
+    ("SZPS", 0x16, 0),
+    ("SLOOP", 0x17, 0),
+    ("RTG", 0x18, 0),
+    ("RTHG", 0x19, 0),
+    ("SMD", 0x1A, 0),
+    ("ELSE", 0x1B, 0),
+    ("JMPR", 0x1C, 0),
+    ("SCVTCI", 0x1D, 0),
+    ("SSWCI", 0x1E, 0),
+    ("SSW", 0x1F, 0),
+    ("DUP", 0x20, 0),
+    ("POP", 0x21, 0),
+    ("CLEAR", 0x22, 0),
+    ("SWAP", 0x23, 0),
+    ("DEPTH", 0x24, 0),
+    ("CINDEX", 0x25, 0),
+    ("MINDEX", 0x26, 0),
+    ("ALIGNPTS", 0x27, 0),
+    ("UTP", 0x29, 0),
+    ("LOOPCALL", 0x2A, 0),
+    ("CALL", 0x2B, 0),
+    ("FDEF", 0x2C, 0),
+    ("ENDF", 0x2D, 0),
+    ("MDAP", 0x2E, 1),
+    ("IUP", 0x30, 1),
+    ("SHP", 0x32, 1),
+    ("SHC", 0x34, 1),
+    ("SHZ", 0x36, 1),
+    ("SHPIX", 0x38, 0),
+    ("IP", 0x39, 0),
+    ("MSIRP", 0x3A, 1),
+    ("ALIGNRP", 0x3C, 0),
+    ("RTDG", 0x3D, 0),
+    ("MIAP", 0x3E, 1),
+    ("NPUSHB", 0x40, 0),
+    ("NPUSHW", 0x41, 0),
+    ("WS", 0x42, 0),
+    ("RS", 0x43, 0),
+    ("WCVTP", 0x44, 0),
+    ("RCVT", 0x45, 0),
+    ("GC", 0x46, 1),
+    ("SCFS", 0x48, 0),
+    ("MD", 0x49, 1),
+    ("MPPEM", 0x4B, 0),
+    ("MPS", 0x4C, 0),
+    ("FLIPON", 0x4D, 0),
+    ("FLIPOFF", 0x4E, 0),
+    ("DEBUG", 0x4F, 0),
+    ("LT", 0x50, 0),
+    ("LTEQ", 0x51, 0),
+    ("GT", 0x52, 0),
+    ("GTEQ", 0x53, 0),
+    ("EQ", 0x54, 0),
+    ("NEQ", 0x55, 0),
+    ("ODD", 0x56, 0),
+    ("EVEN", 0x57, 0),
+    ("IF", 0x58, 0),
+    ("EIF", 0x59, 0),
+    ("AND", 0x5A, 0),
+    ("OR", 0x5B, 0),
+    ("NOT", 0x5C, 0),
+    ("DELTAP1", 0x5D, 0),
+    ("SDB", 0x5E, 0),
+    ("SDS", 0x5F, 0),
+    ("ADD", 0x60, 0),
+    ("SUB", 0x61, 0),
+    ("DIV", 0x62, 0),
+    ("MUL", 0x63, 0),
+    ("ABS", 0x64, 0),
+    ("NEG", 0x65, 0),
+    ("FLOOR", 0x66, 0),
+    ("CEILING", 0x67, 0),
+    ("ROUND", 0x68, 2),
+    ("NROUND", 0x6C, 2),
+    ("WCVTF", 0x70, 0),
+    ("DELTAP2", 0x71, 0),
+    ("DELTAP3", 0x72, 0),
+    ("DELTAC1", 0x73, 0),
+    ("DELTAC2", 0x74, 0),
+    ("DELTAC3", 0x75, 0),
+    ("SROUND", 0x76, 0),
+    ("S45ROUND", 0x77, 0),
+    ("JROT", 0x78, 0),
+    ("JROF", 0x79, 0),
+    ("ROFF", 0x7A, 0),
+    ("RUTG", 0x7C, 0),
+    ("RDTG", 0x7D, 0),
+    ("SANGW", 0x7E, 0),
+    ("AA", 0x7F, 0),
+    ("FLIPPT", 0x80, 0),
+    ("FLIPRGON", 0x81, 0),
+    ("FLIPRGOFF", 0x82, 0),
+    ("SCANCTRL", 0x85, 0),
+    ("SDPVTL", 0x86, 1),
+    ("GETINFO", 0x88, 0),
+    ("IDEF", 0x89, 0),
+    ("ROLL", 0x8A, 0),
+    ("MAX", 0x8B, 0),
+    ("MIN", 0x8C, 0),
+    ("SCANTYPE", 0x8D, 0),
+    ("INSTCTRL", 0x8E, 0),
+    ("GETVARIATION", 0x91, 0),
+    ("GETDATA", 0x92, 0),
+    ("PUSHB", 0xB0, 3),
+    ("PUSHW", 0xB8, 3),
+    ("MDRP", 0xC0, 5),
+    ("MIRP", 0xE0, 5),
+];
+
+/// Prefix of the mnemonic used for opcodes which have no standard meaning
+/// (these may be given one by an `IDEF`).
+const UNDEFINED_PREFIX: &str = "INS_";
+
+fn opcode_info(opcode: u8) -> Option<(&'static str, u8, u8)> {
+    OPCODES
+        .iter()
+        .find(|(_, base, bits)| opcode >= *base && (opcode - *base) as u16 >> *bits == 0)
+        .copied()
+}
+
+/// An error encountered when disassembling or assembling instructions
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionError {
+    /// A push instruction at the given byte offset runs past the end of the
+    /// bytecode
+    TruncatedPush(usize),
+    /// The mnemonic is not known
+    UnknownMnemonic(String),
+    /// The flags given for an instruction have the wrong length or are not
+    /// binary digits
+    BadFlags(String),
+    /// A push instruction has the wrong number of values for its encoding
+    BadPushCount(String),
+    /// A value is out of range for the push instruction which contains it
+    ValueOutOfRange(String, i32),
+    /// A value was found following an instruction which is not a push
+    UnexpectedValue(i32),
+    /// The text could not be tokenized
+    BadToken(String),
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::TruncatedPush(offset) => {
+                write!(f, "truncated push instruction at offset {}", offset)
+            }
+            InstructionError::UnknownMnemonic(m) => write!(f, "unknown mnemonic {}", m),
+            InstructionError::BadFlags(m) => write!(f, "bad flags in {}", m),
+            InstructionError::BadPushCount(m) => write!(f, "wrong number of values for {}", m),
+            InstructionError::ValueOutOfRange(m, value) => {
+                write!(f, "value {} out of range for {}", value, m)
+            }
+            InstructionError::UnexpectedValue(value) => {
+                write!(f, "value {} does not follow a push instruction", value)
+            }
+            InstructionError::BadToken(t) => write!(f, "could not parse {:?}", t),
+        }
+    }
+}
+
+impl std::error::Error for InstructionError {}
+
+/// A single TrueType instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// The opcode, including any flag bits
+    pub opcode: u8,
+    /// The values pushed, for push instructions; otherwise empty
+    pub args: Vec<i32>,
+}
+
+impl Instruction {
+    /// Creates an instruction with no arguments.
+    pub fn new(opcode: u8) -> Self {
+        Instruction {
+            opcode,
+            args: vec![],
+        }
+    }
+
+    /// Returns `true` if this is one of the push instructions
+    /// (`NPUSHB`, `NPUSHW`, `PUSHB` or `PUSHW`).
+    pub fn is_push(&self) -> bool {
+        matches!(self.opcode, 0x40 | 0x41 | 0xB0..=0xBF)
+    }
+
+    /// Returns `true` if this push instruction pushes 16-bit words.
+    fn pushes_words(&self) -> bool {
+        matches!(self.opcode, 0x41 | 0xB8..=0xBF)
+    }
+
+    /// The name of the instruction, without flags (e.g. `MIRP`).
+    pub fn name(&self) -> String {
+        match opcode_info(self.opcode) {
+            Some((name, _, _)) => name.to_string(),
+            None => format!("{}{:02X}", UNDEFINED_PREFIX, self.opcode),
+        }
+    }
+
+    /// The flag bits of the instruction (e.g. 0b01101 for `MIRP[01101]`).
+    pub fn flags(&self) -> u8 {
+        opcode_info(self.opcode)
+            .map(|(_, base, _)| self.opcode - base)
+            .unwrap_or(0)
+    }
+
+    /// The mnemonic of the instruction, including flags (e.g. `MIRP[01101]`
+    /// or `SRP0[ ]`).
+    pub fn mnemonic(&self) -> String {
+        match opcode_info(self.opcode) {
+            Some((_, _, bits)) if bits > 0 && !self.is_push() => format!(
+                "{}[{:0width$b}]",
+                self.name(),
+                self.flags(),
+                width = bits as usize
+            ),
+            _ => format!("{}[ ]", self.name()),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns push instructions which push the given values, using the most
+/// compact encoding.
+pub fn push(values: &[i32]) -> Vec<Instruction> {
+    let words = values.iter().any(|v| !(0..=255).contains(v));
+    values
+        .chunks(255)
+        .map(|chunk| {
+            let opcode = match (chunk.len() <= 8, words) {
+                (true, false) => 0xB0 + chunk.len() as u8 - 1,
+                (true, true) => 0xB8 + chunk.len() as u8 - 1,
+                (false, false) => 0x40,
+                (false, true) => 0x41,
+            };
+            Instruction {
+                opcode,
+                args: chunk.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Returns `true` if the opcode has a standard meaning.
+pub(crate) fn is_defined(opcode: u8) -> bool {
+    opcode_info(opcode).is_some()
+}
+
+/// Returns the length in bytes of the instruction at the given offset,
+/// including any pushed values, or `None` if it runs past the end of the
+/// bytecode.
+pub(crate) fn length_at(bytecode: &[u8], offset: usize) -> Option<usize> {
+    let length = match *bytecode.get(offset)? {
+        0x40 => 2 + *bytecode.get(offset + 1)? as usize,
+        0x41 => 2 + 2 * *bytecode.get(offset + 1)? as usize,
+        opcode @ 0xB0..=0xB7 => 2 + (opcode - 0xB0) as usize,
+        opcode @ 0xB8..=0xBF => 3 + 2 * (opcode - 0xB8) as usize,
+        _ => 1,
+    };
+    if offset + length > bytecode.len() {
+        None
+    } else {
+        Some(length)
+    }
+}
+
+/// Disassembles bytecode into a list of instructions.
+pub fn disassemble(bytecode: &[u8]) -> Result<Vec<Instruction>, InstructionError> {
+    let mut instructions = vec![];
+    let mut pos = 0;
+    while pos < bytecode.len() {
+        let start = pos;
+        let mut instruction = Instruction::new(bytecode[pos]);
+        pos += 1;
+        if instruction.is_push() {
+            let count = match instruction.opcode {
+                0x40 | 0x41 => {
+                    let count = *bytecode
+                        .get(pos)
+                        .ok_or(InstructionError::TruncatedPush(start))?;
+                    pos += 1;
+                    count as usize
+                }
+                opcode => (opcode & 0x07) as usize + 1,
+            };
+            let size = if instruction.pushes_words() { 2 } else { 1 };
+            let data = bytecode
+                .get(pos..pos + count * size)
+                .ok_or(InstructionError::TruncatedPush(start))?;
+            instruction.args = if size == 2 {
+                data.chunks(2)
+                    .map(|w| i16::from_be_bytes([w[0], w[1]]) as i32)
+                    .collect()
+            } else {
+                data.iter().map(|&b| b as i32).collect()
+            };
+            pos += count * size;
+        }
+        instructions.push(instruction);
+    }
+    Ok(instructions)
+}
+
+/// Assembles a list of instructions into bytecode.
+pub fn assemble(instructions: &[Instruction]) -> Result<Vec<u8>, InstructionError> {
+    let mut bytecode = vec![];
+    for instruction in instructions {
+        bytecode.push(instruction.opcode);
+        if !instruction.is_push() {
+            if let Some(&value) = instruction.args.first() {
+                return Err(InstructionError::UnexpectedValue(value));
+            }
+            continue;
+        }
+        let count = instruction.args.len();
+        match instruction.opcode {
+            0x40 | 0x41 if count <= 255 => bytecode.push(count as u8),
+            0xB0..=0xBF if count == (instruction.opcode & 0x07) as usize + 1 => {}
+            _ => return Err(InstructionError::BadPushCount(instruction.mnemonic())),
+        }
+        for &value in &instruction.args {
+            if instruction.pushes_words() {
+                let word = i16::try_from(value).map_err(|_| {
+                    InstructionError::ValueOutOfRange(instruction.mnemonic(), value)
+                })?;
+                bytecode.extend(word.to_be_bytes());
+            } else {
+                let byte = u8::try_from(value).map_err(|_| {
+                    InstructionError::ValueOutOfRange(instruction.mnemonic(), value)
+                })?;
+                bytecode.push(byte);
+            }
+        }
+    }
+    Ok(bytecode)
+}
+
+/// Converts a list of instructions to text, one instruction per line.
+///
+/// The bodies of `IF`, `FDEF` and `IDEF` blocks are indented.
+pub fn to_text(instructions: &[Instruction]) -> String {
+    let mut text = String::new();
+    let mut depth: usize = 0;
+    for instruction in instructions {
+        if matches!(instruction.opcode, 0x59 | 0x2D) {
+            // EIF, ENDF
+            depth = depth.saturating_sub(1);
+        }
+        let indent = if instruction.opcode == 0x1B {
+            // ELSE
+            depth.saturating_sub(1)
+        } else {
+            depth
+        };
+        text.push_str(&format!(
+            "{:indent$}{}\n",
+            "",
+            instruction,
+            indent = indent * 2
+        ));
+        if matches!(instruction.opcode, 0x58 | 0x2C | 0x89) {
+            // IF, FDEF, IDEF
+            depth += 1;
+        }
+    }
+    text
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Mnemonic(String, String),
+    Value(i32),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, InstructionError> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    let rest = |pos: usize| chars[pos..].iter().collect::<String>();
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c == '/' && chars.get(pos + 1) == Some(&'*') {
+            let end = (pos + 2..chars.len().saturating_sub(1))
+                .find(|&i| chars[i] == '*' && chars[i + 1] == '/')
+                .ok_or_else(|| InstructionError::BadToken(rest(pos)))?;
+            pos = end + 2;
+        } else if c == '-' || c.is_ascii_digit() {
+            let start = pos;
+            pos += 1;
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            let token: String = chars[start..pos].iter().collect();
+            tokens.push(Token::Value(
+                token
+                    .parse()
+                    .map_err(|_| InstructionError::BadToken(token))?,
+            ));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            let name: String = chars[start..pos].iter().collect();
+            let mut flags = String::new();
+            if chars.get(pos) == Some(&'[') {
+                let end = (pos..chars.len())
+                    .find(|&i| chars[i] == ']')
+                    .ok_or_else(|| InstructionError::BadToken(rest(start)))?;
+                flags = chars[pos + 1..end]
+                    .iter()
+                    .collect::<String>()
+                    .trim()
+                    .to_string();
+                pos = end + 1;
+            }
+            tokens.push(Token::Mnemonic(name, flags));
+        } else {
+            return Err(InstructionError::BadToken(rest(pos)));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_mnemonic(name: &str, flags: &str) -> Result<u8, InstructionError> {
+    let full = format!("{}[{}]", name, flags);
+    if let Some(hex) = name.strip_prefix(UNDEFINED_PREFIX) {
+        let opcode =
+            u8::from_str_radix(hex, 16).map_err(|_| InstructionError::UnknownMnemonic(full))?;
+        if opcode_info(opcode).is_some() {
+            return Err(InstructionError::UnknownMnemonic(name.to_string()));
+        }
+        return Ok(opcode);
+    }
+    let (_, base, bits) = OPCODES
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .ok_or_else(|| InstructionError::UnknownMnemonic(name.to_string()))?;
+    // The number of values pushed determines the flags of PUSHB and PUSHW
+    let bits = if matches!(*base, 0xB0 | 0xB8) {
+        0
+    } else {
+        *bits
+    };
+    if flags.is_empty() && bits == 0 {
+        return Ok(*base);
+    }
+    if flags.len() != bits as usize {
+        return Err(InstructionError::BadFlags(full));
+    }
+    let value = u8::from_str_radix(flags, 2).map_err(|_| InstructionError::BadFlags(full))?;
+    Ok(base + value)
+}
+
+/// Parses the text form of a list of instructions.
+///
+/// Comments of the form `/* ... */` are ignored.
+pub fn from_text(text: &str) -> Result<Vec<Instruction>, InstructionError> {
+    let mut instructions: Vec<Instruction> = vec![];
+    // Values for a PUSH pseudo-instruction
+    let mut pending: Option<Vec<i32>> = None;
+    for token in tokenize(text)? {
+        match token {
+            Token::Value(value) => {
+                if let Some(values) = pending.as_mut() {
+                    values.push(value);
+                } else {
+                    match instructions.last_mut() {
+                        Some(last) if last.is_push() => last.args.push(value),
+                        _ => return Err(InstructionError::UnexpectedValue(value)),
+                    }
+                }
+            }
+            Token::Mnemonic(name, flags) => {
+                if let Some(values) = pending.take() {
+                    instructions.extend(push(&values));
+                }
+                if name == "PUSH" {
+                    if !flags.is_empty() {
+                        return Err(InstructionError::BadFlags(format!("PUSH[{}]", flags)));
+                    }
+                    pending = Some(vec![]);
+                } else {
+                    instructions.push(Instruction::new(parse_mnemonic(&name, &flags)?));
+                }
+            }
+        }
+    }
+    if let Some(values) = pending {
+        instructions.extend(push(&values));
+    }
+    for instruction in instructions.iter_mut() {
+        if let 0xB0..=0xBF = instruction.opcode {
+            let count = instruction.args.len();
+            if count == 0 || count > 8 {
+                return Err(InstructionError::BadPushCount(instruction.mnemonic()));
+            }
+            instruction.opcode = (instruction.opcode & 0xF8) + count as u8 - 1;
+        }
+    }
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        let bytecode = vec![
+            0xb0, 0x00, 0x2c, 0x20, 0xb9, 0xff, 0x38, 0x01, 0x00, 0x55, 0x58, 0xe9, 0x1b, 0x2e,
+            0x59, 0x2d,
+        ];
+        let instructions = disassemble(&bytecode).unwrap();
+        assert_eq!(
+            to_text(&instructions),
+            "PUSHB[ ] 0\nFDEF[ ]\n  DUP[ ]\n  PUSHW[ ] -200 256\n  NEQ[ ]\n  IF[ ]\n    MIRP[01001]\n  ELSE[ ]\n    MDAP[0]\n  EIF[ ]\nENDF[ ]\n"
+        );
+        assert_eq!(assemble(&instructions).unwrap(), bytecode);
+        assert_eq!(from_text(&to_text(&instructions)).unwrap(), instructions);
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(
+            disassemble(&[0x20, 0x40, 0x03, 0x01]),
+            Err(InstructionError::TruncatedPush(1))
+        );
+        assert_eq!(
+            disassemble(&[0xb8, 0x01]),
+            Err(InstructionError::TruncatedPush(0))
+        );
+    }
+
+    #[test]
+    fn test_undefined_opcode() {
+        let instructions = disassemble(&[0x28, 0x83]).unwrap();
+        assert_eq!(to_text(&instructions), "INS_28[ ]\nINS_83[ ]\n");
+        assert_eq!(from_text("INS_28[ ] INS_83").unwrap(), instructions);
+        assert!(from_text("INS_20[ ]").is_err());
+    }
+
+    #[test]
+    fn test_from_text() {
+        let instructions = from_text(
+            "/* Set vectors */ SVTCA[1]
+             PUSH[ ] 1 2 300
+             PUSHB[ ] 4 5
+             NPUSHB[ ] 6
+             ROUND[01] MDRP[10110]",
+        )
+        .unwrap();
+        assert_eq!(
+            assemble(&instructions).unwrap(),
+            vec![
+                0x01, 0xBA, 0x00, 0x01, 0x00, 0x02, 0x01, 0x2C, 0xB1, 0x04, 0x05, 0x40, 0x01, 0x06,
+                0x69, 0xD6
+            ]
+        );
+        assert_eq!(
+            from_text("SVTCA[ ]"),
+            Err(InstructionError::BadFlags("SVTCA[]".to_string()))
+        );
+        assert_eq!(
+            from_text("SRP0[ ] 5"),
+            Err(InstructionError::UnexpectedValue(5))
+        );
+        assert!(from_text("FOO[ ]").is_err());
+        assert!(from_text("PUSHB[ ] 1 2 3 4 5 6 7 8 9").is_err());
+    }
+
+    #[test]
+    fn test_push() {
+        let values: Vec<i32> = (0..300).collect();
+        let instructions = push(&values);
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].opcode, 0x41);
+        assert_eq!(instructions[1].opcode, 0x41);
+        assert_eq!(push(&[1, 2])[0].opcode, 0xB1);
+        let bad = Instruction {
+            opcode: 0xB0,
+            args: vec![256],
+        };
+        assert_eq!(
+            assemble(&[bad]),
+            Err(InstructionError::ValueOutOfRange(
+                "PUSHB[ ]".to_string(),
+                256
+            ))
+        );
+    }
+}
@@ -25,3 +25,5 @@ pub mod gsub4;
 /// GSUB8 reverse chaining contextual single substitution
 pub mod gsub8;
 pub(crate) mod macros;
+/// Applying GSUB and GPOS lookups to glyph runs
+pub mod shaping;
@@ -7,7 +7,8 @@ use otspec::types::*;
 
 pub use otspec::layout::common::LookupFlags;
 pub use otspec::layout::valuerecord::{ValueRecord, ValueRecordFlags};
-use std::collections::BTreeMap; // For predictable ordering
+use otspec::Serialize;
+use std::collections::{BTreeMap, BTreeSet}; // For predictable ordering
 use std::fmt::Debug;
 
 // A trait for moving things from the otspec representation to our representation.
@@ -22,6 +23,59 @@ pub(crate) trait ToLowlevel<T> {
     fn to_lowlevel(&self, max_glyph_id: GlyphID) -> T;
 }
 
+/// Replaces each subtable which is too big to compile on its own with two
+/// halves, returning `true` if any were split. Used when a layout table's
+/// offsets overflow even with every lookup promoted to an extension.
+pub(crate) fn split_oversized<S, L: Serialize>(
+    subtables: &mut Vec<S>,
+    lower: impl Fn(&S) -> Vec<L>,
+    split: impl Fn(&S) -> Option<(S, S)>,
+) -> bool {
+    let mut changed = false;
+    let mut out = vec![];
+    for subtable in subtables.drain(..) {
+        let oversized = lower(&subtable).iter().any(|lowlevel| {
+            let mut data = vec![];
+            lowlevel.to_bytes(&mut data).is_err() || data.len() > 0xFFFF
+        });
+        if oversized {
+            if let Some((first, second)) = split(&subtable) {
+                out.push(first);
+                out.push(second);
+                changed = true;
+                continue;
+            }
+        }
+        out.push(subtable);
+    }
+    *subtables = out;
+    changed
+}
+
+/// Splits a map in half without separating keys which fall into the same
+/// group (for example, ligatures starting with the same glyph).
+pub(crate) fn split_map<K, V, G>(
+    map: &BTreeMap<K, V>,
+    group: impl Fn(&K) -> G,
+) -> Option<(BTreeMap<K, V>, BTreeMap<K, V>)>
+where
+    K: Ord + Clone,
+    V: Clone,
+    G: Ord,
+{
+    let groups: BTreeSet<G> = map.keys().map(&group).collect();
+    if groups.len() < 2 {
+        return None;
+    }
+    let count = groups.len();
+    let middle = groups.into_iter().nth(count / 2)?;
+    Some(
+        map.iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .partition(|(k, _)| group(k) < middle),
+    )
+}
+
 pub(crate) fn coverage_or_nah(off: Offset16<Coverage>) -> Vec<GlyphID> {
     off.link
         .map(|x| x.glyphs)
@@ -33,6 +87,7 @@ pub(crate) fn coverage_or_nah(off: Offset16<Coverage>) -> Vec<GlyphID> {
 
 /// A script list
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ScriptList {
     /// A mapping between script tags and `Script` tables.
     pub scripts: BTreeMap<Tag, Script>,
@@ -40,6 +95,7 @@ pub struct ScriptList {
 
 /// A Script table, containing information about language systems for a certain script.
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Script {
     /// Optionally, a default language system to be used when no specific
     /// language is selected.
@@ -51,6 +107,7 @@ pub struct Script {
 /// A LanguageSystem table, selecting which features should be applied in the
 /// current script/language combination.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LanguageSystem {
     /// Each language system can define a required feature which must be processed
     /// for this script/language combination.
@@ -157,17 +214,24 @@ impl From<ScriptListLowLevel> for ScriptList {
 
 /// A general lookup rule, of whatever type
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lookup<T> {
     /// Lookup flags
     pub flags: LookupFlags,
     /// The mark filtering set index in the `GDEF` table.
     pub mark_filtering_set: Option<uint16>,
+    /// Whether the lookup is written as an extension lookup (`GSUB` type 7
+    /// or `GPOS` type 9). Set when an extension lookup is read, so that it
+    /// is wrapped again when the table is written.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extension: bool,
     /// The concrete rule (set of subtables)
     pub rule: T,
 }
 
 // GPOS and GSUB tables
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A list of features within a GPOS or GSUB table
 ///
 /// Associates a feature tag with a set of lookup IDs, and optional feature
@@ -246,6 +310,7 @@ impl From<&FeatureList> for FeatureListLowLevel {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::upper_case_acronyms)]
 /// The Glyph Positioning table
 pub struct GPOSGSUB<T> {
@@ -1,13 +1,16 @@
 use crate::layout::common::{coverage_or_nah, FromLowlevel};
+use otspec::layout::classdef::ClassDef;
 use otspec::layout::contextual::{
     ChainedSequenceContextFormat1, ChainedSequenceContextFormat2, ChainedSequenceContextFormat3,
-    SequenceContextFormat1, SequenceContextFormat2, SequenceContextFormat3, SequenceLookupRecord,
+    ChainedSequenceRule, ChainedSequenceRuleSet, SequenceContextFormat1, SequenceContextFormat2,
+    SequenceContextFormat3, SequenceLookupRecord, SequenceRule, SequenceRuleSet,
 };
 use otspec::layout::coverage::Coverage;
 use otspec::tables::GPOS::GPOSSubtable;
 use otspec::tables::GSUB::GSUBSubtable;
 use otspec::types::*;
-use std::collections::BTreeSet;
+use otspec::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// A helpful alias which makes the type a bit more self-documenting
 pub type LookupID = uint16;
@@ -18,7 +21,14 @@ pub type SequenceContextRule = Vec<(Slot, Vec<LookupID>)>;
 
 /* This struct is the user-facing representation of sequence context. */
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A contextual substitution/positioning table (GSUB5/GPOS7).
+///
+/// When written, the rules become a single format 1 subtable if every slot
+/// holds one glyph, a single format 2 subtable if the slots can be expressed
+/// as classes, or one format 3 subtable per rule, whichever is smallest.
+/// Rules starting with different glyphs never compete, so a format 1 or 2
+/// subtable may group them by their first glyph or class.
 pub struct SequenceContext {
     /// A set of sequence context rules
     pub rules: Vec<SequenceContextRule>,
@@ -52,6 +62,134 @@ fn collate_lookup_records(
     rule
 }
 
+/// The inverse of `collate_lookup_records`
+fn lookup_records(rule: &SequenceContextRule) -> Vec<SequenceLookupRecord> {
+    rule.iter()
+        .enumerate()
+        .flat_map(|(ix, (_, lookup_ids))| {
+            lookup_ids
+                .iter()
+                .map(move |&lookup_id| SequenceLookupRecord {
+                    sequenceIndex: ix as uint16,
+                    lookupIndex: lookup_id,
+                })
+        })
+        .collect()
+}
+
+fn slot_to_coverage(slot: &Slot) -> Offset16<Coverage> {
+    Offset16::to(Coverage {
+        glyphs: slot.iter().copied().collect(),
+    })
+}
+
+/// The glyph in each slot, if every slot holds exactly one glyph
+fn single_glyphs<'a>(slots: impl IntoIterator<Item = &'a Slot>) -> Option<Vec<GlyphID>> {
+    slots
+        .into_iter()
+        .map(|slot| match slot.len() {
+            1 => slot.iter().next().copied(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Numbers each distinct slot as a glyph class, from 1 in order of first
+/// appearance, if no two distinct slots share a glyph
+fn classify<'a>(slots: impl IntoIterator<Item = &'a Slot>) -> Option<BTreeMap<&'a Slot, uint16>> {
+    let mut classes: BTreeMap<&Slot, uint16> = BTreeMap::new();
+    let mut seen: BTreeSet<GlyphID> = BTreeSet::new();
+    for slot in slots {
+        if classes.contains_key(slot) {
+            continue;
+        }
+        if slot.is_empty() || !slot.is_disjoint(&seen) {
+            return None;
+        }
+        seen.extend(slot);
+        classes.insert(slot, classes.len() as uint16 + 1);
+    }
+    Some(classes)
+}
+
+fn to_classdef(classes: &BTreeMap<&Slot, uint16>) -> ClassDef {
+    ClassDef {
+        classes: classes
+            .iter()
+            .flat_map(|(slot, &class)| slot.iter().map(move |&gid| (gid, class)))
+            .collect(),
+    }
+}
+
+/// Lays out rule sets indexed by the class of their first glyph, leaving
+/// a null offset for classes which start no rule
+fn class_rule_sets<R, S>(
+    mut rulesets: BTreeMap<uint16, Vec<R>>,
+    to_rule_set: impl Fn(Vec<R>) -> Offset16<S>,
+) -> VecOffset16<S> {
+    let count = rulesets.keys().next_back().map_or(0, |&class| class + 1);
+    (0..count)
+        .map(|class| {
+            rulesets
+                .remove(&class)
+                .map_or_else(Offset16::to_nothing, &to_rule_set)
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn sequence_rule_set(rules: Vec<SequenceRule>) -> Offset16<SequenceRuleSet> {
+    Offset16::to(SequenceRuleSet {
+        sequenceRules: rules
+            .into_iter()
+            .map(Offset16::to)
+            .collect::<Vec<_>>()
+            .into(),
+    })
+}
+
+fn chained_sequence_rule_set(rules: Vec<ChainedSequenceRule>) -> Offset16<ChainedSequenceRuleSet> {
+    Offset16::to(ChainedSequenceRuleSet {
+        chainedSequenceRules: rules
+            .into_iter()
+            .map(Offset16::to)
+            .collect::<Vec<_>>()
+            .into(),
+    })
+}
+
+fn serialized_size<T: Serialize>(subtables: &[T]) -> usize {
+    subtables
+        .iter()
+        .map(|st| otspec::ser::to_bytes(st).map_or(usize::MAX, |data| data.len()))
+        .fold(0, usize::saturating_add)
+}
+
+/// Picks the smallest way of lowering a (chained) sequence context: a
+/// single format 1 or format 2 subtable if the rules can be expressed that
+/// way, or one format 3 subtable per rule
+fn smallest_format<T, F1: Serialize, F2: Serialize, F3: Serialize>(
+    format1: Option<F1>,
+    format2: Option<F2>,
+    format3: Vec<F3>,
+    wrap1: impl FnOnce(F1) -> T,
+    wrap2: impl FnOnce(F2) -> T,
+    wrap3: impl Fn(F3) -> T,
+) -> Vec<T> {
+    let size3 = serialized_size(&format3);
+    let size2 = format2
+        .as_ref()
+        .map_or(usize::MAX, |st| serialized_size(std::slice::from_ref(st)));
+    let size1 = format1
+        .as_ref()
+        .map_or(usize::MAX, |st| serialized_size(std::slice::from_ref(st)));
+    match (format1, format2) {
+        (Some(st), _) if size1 <= size2.min(size3) => vec![wrap1(st)],
+        (_, Some(st)) if size2 <= size3 => vec![wrap2(st)],
+        _ => format3.into_iter().map(wrap3).collect(),
+    }
+}
+
 impl SequenceContext {
     fn from_lowlevel_format1(st: SequenceContextFormat1, _max_glyph_id: GlyphID) -> Self {
         let mut sequence_context = SequenceContext::default();
@@ -83,11 +221,14 @@ impl SequenceContext {
     fn from_lowlevel_format2(st: SequenceContextFormat2, max_glyph_id: GlyphID) -> Self {
         let mut sequence_context = SequenceContext::default();
         let classdef = st.classDef.link.unwrap_or_default();
+        // Only glyphs in the coverage table can start a match
+        let coverage = coverage_to_slot(st.coverage);
         for (first_class, ruleset) in st.classSeqRuleSets.v.iter().enumerate() {
             if let Some(ruleset) = &ruleset.link {
                 for rule in ruleset.sequenceRules.v.iter() {
                     if let Some(rule) = &rule.link {
-                        let mut slots = vec![classdef.get_glyphs(first_class as u16, max_glyph_id)];
+                        let first = classdef.get_glyphs(first_class as u16, max_glyph_id);
+                        let mut slots = vec![&first & &coverage];
                         slots.extend(
                             rule.inputSequence
                                 .iter()
@@ -104,7 +245,7 @@ impl SequenceContext {
     }
     fn from_lowlevel_format3(st: SequenceContextFormat3, _max_glyph_id: GlyphID) -> Self {
         let mut sequence_context = SequenceContext::default();
-        let slots: Vec<Slot> = st.coverages.into_iter().map(coverage_to_slot).collect();
+        let slots: Vec<Slot> = st.coverages.v.into_iter().map(coverage_to_slot).collect();
         sequence_context
             .rules
             .push(collate_lookup_records(slots, &st.seqLookupRecords));
@@ -146,51 +287,109 @@ impl FromLowlevel<GSUBSubtable> for SequenceContext {
 }
 
 impl SequenceContext {
+    /// The length of the longest input sequence matched by these rules
+    pub fn max_context(&self) -> usize {
+        self.rules.iter().map(|rule| rule.len()).max().unwrap_or(0)
+    }
+
+    fn to_format1(&self) -> Option<SequenceContextFormat1> {
+        let mut rulesets: BTreeMap<GlyphID, Vec<SequenceRule>> = BTreeMap::new();
+        for rule in &self.rules {
+            let glyphs = single_glyphs(rule.iter().map(|(slot, _)| slot))?;
+            let (first, rest) = glyphs.split_first()?;
+            let records = lookup_records(rule);
+            rulesets.entry(*first).or_default().push(SequenceRule {
+                glyphCount: glyphs.len() as uint16,
+                inputSequence: rest.to_vec(),
+                seqLookupCount: records.len() as uint16,
+                seqLookupRecords: records,
+            });
+        }
+        Some(SequenceContextFormat1 {
+            format: 1,
+            coverage: Offset16::to(Coverage {
+                glyphs: rulesets.keys().copied().collect(),
+            }),
+            seqRuleSets: rulesets
+                .into_values()
+                .map(sequence_rule_set)
+                .collect::<Vec<_>>()
+                .into(),
+        })
+    }
+
+    fn to_format2(&self) -> Option<SequenceContextFormat2> {
+        let classes = classify(self.rules.iter().flatten().map(|(slot, _)| slot))?;
+        let mut rulesets: BTreeMap<uint16, Vec<SequenceRule>> = BTreeMap::new();
+        let mut coverage: BTreeSet<GlyphID> = BTreeSet::new();
+        for rule in &self.rules {
+            let ((first, _), rest) = rule.split_first()?;
+            coverage.extend(first);
+            let records = lookup_records(rule);
+            rulesets
+                .entry(classes[first])
+                .or_default()
+                .push(SequenceRule {
+                    glyphCount: rule.len() as uint16,
+                    inputSequence: rest.iter().map(|(slot, _)| classes[slot]).collect(),
+                    seqLookupCount: records.len() as uint16,
+                    seqLookupRecords: records,
+                });
+        }
+        Some(SequenceContextFormat2 {
+            format: 2,
+            coverage: Offset16::to(Coverage {
+                glyphs: coverage.into_iter().collect(),
+            }),
+            classDef: Offset16::to(to_classdef(&classes)),
+            classSeqRuleSets: class_rule_sets(rulesets, sequence_rule_set),
+        })
+    }
+
     fn to_format3(&self) -> Vec<SequenceContextFormat3> {
         self.rules
             .iter()
             .map(|rule| {
-                let mut coverages: Vec<Offset16<Coverage>> = vec![];
-
-                let mut sequence_lookup_records: Vec<SequenceLookupRecord> = vec![];
-
-                for (ix, (slot, lookup_ids)) in rule.iter().enumerate() {
-                    coverages.push(Offset16::to(Coverage {
-                        glyphs: slot.iter().copied().collect(),
-                    }));
-                    for lookup_id in lookup_ids {
-                        sequence_lookup_records.push(SequenceLookupRecord {
-                            sequenceIndex: ix as uint16,
-                            lookupIndex: *lookup_id,
-                        });
-                    }
-                }
+                let records = lookup_records(rule);
                 SequenceContextFormat3 {
                     format: 3,
                     glyphCount: rule.len() as uint16,
-                    seqLookupCount: sequence_lookup_records.len() as uint16,
-                    seqLookupRecords: sequence_lookup_records,
-                    coverages,
+                    seqLookupCount: records.len() as uint16,
+                    seqLookupRecords: records,
+                    coverages: rule
+                        .iter()
+                        .map(|(slot, _)| slot_to_coverage(slot))
+                        .collect::<Vec<_>>()
+                        .into(),
                 }
             })
             .collect()
     }
 
     pub(crate) fn to_lowlevel_subtables_gpos(&self, _max_glyph_id: GlyphID) -> Vec<GPOSSubtable> {
-        self.to_format3()
-            .into_iter()
-            .map(GPOSSubtable::GPOS7_3)
-            .collect()
+        smallest_format(
+            self.to_format1(),
+            self.to_format2(),
+            self.to_format3(),
+            GPOSSubtable::GPOS7_1,
+            GPOSSubtable::GPOS7_2,
+            GPOSSubtable::GPOS7_3,
+        )
     }
     pub(crate) fn to_lowlevel_subtables_gsub(&self, _max_glyph_id: GlyphID) -> Vec<GSUBSubtable> {
-        self.to_format3()
-            .into_iter()
-            .map(GSUBSubtable::GSUB5_3)
-            .collect()
+        smallest_format(
+            self.to_format1(),
+            self.to_format2(),
+            self.to_format3(),
+            GSUBSubtable::GSUB5_1,
+            GSUBSubtable::GSUB5_2,
+            GSUBSubtable::GSUB5_3,
+        )
     }
 }
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A chained contextual rule, with backtrack and lookahead
 pub struct ChainedSequenceContextRule {
     /// Glyphs which must appear before the input sequence
@@ -206,7 +405,12 @@ pub struct ChainedSequenceContextRule {
 
 /* This struct is the user-facing representation of chained sequence context. */
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A chained contextual substitution/positioning table (GSUB6/GPOS8).
+///
+/// This is lowered to format 1, 2 or 3 subtables in the same way as a
+/// [`SequenceContext`], with the backtrack and lookahead slots classed
+/// separately from the input slots in format 2.
 pub struct ChainedSequenceContext {
     /// A set of sequence context rules
     pub rules: Vec<ChainedSequenceContextRule>,
@@ -255,11 +459,13 @@ impl ChainedSequenceContext {
         let classdef = st.inputClassDef.link.unwrap_or_default();
         let backtrack_classdef = st.backtrackClassDef.link.unwrap_or_default();
         let lookahead_classdef = st.lookaheadClassDef.link.unwrap_or_default();
+        let coverage = coverage_to_slot(st.coverage);
         for (first_class, ruleset) in st.chainedClassSeqRuleSets.v.iter().enumerate() {
             if let Some(ruleset) = &ruleset.link {
                 for rule in ruleset.chainedSequenceRules.v.iter() {
                     if let Some(rule) = &rule.link {
-                        let mut slots = vec![classdef.get_glyphs(first_class as u16, max_glyph_id)];
+                        let first = classdef.get_glyphs(first_class as u16, max_glyph_id);
+                        let mut slots = vec![&first & &coverage];
                         slots.extend(
                             rule.inputSequence
                                 .iter()
@@ -354,65 +560,136 @@ impl FromLowlevel<GSUBSubtable> for ChainedSequenceContext {
 }
 
 impl ChainedSequenceContext {
+    /// The length of the longest input and lookahead sequence matched by
+    /// these rules. (Backtrack glyphs have already been processed.)
+    pub fn max_context(&self) -> usize {
+        self.rules
+            .iter()
+            .map(|rule| rule.input.len() + rule.lookahead.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn to_format1(&self) -> Option<ChainedSequenceContextFormat1> {
+        let mut rulesets: BTreeMap<GlyphID, Vec<ChainedSequenceRule>> = BTreeMap::new();
+        for rule in &self.rules {
+            let glyphs = single_glyphs(rule.input.iter().map(|(slot, _)| slot))?;
+            let (first, rest) = glyphs.split_first()?;
+            rulesets
+                .entry(*first)
+                .or_default()
+                .push(ChainedSequenceRule {
+                    backtrackSequence: single_glyphs(&rule.backtrack)?,
+                    inputGlyphCount: glyphs.len() as uint16,
+                    inputSequence: rest.to_vec(),
+                    lookaheadSequence: single_glyphs(&rule.lookahead)?,
+                    seqLookupRecords: lookup_records(&rule.input),
+                });
+        }
+        Some(ChainedSequenceContextFormat1 {
+            format: 1,
+            coverage: Offset16::to(Coverage {
+                glyphs: rulesets.keys().copied().collect(),
+            }),
+            chainedSeqRuleSets: rulesets
+                .into_values()
+                .map(chained_sequence_rule_set)
+                .collect::<Vec<_>>()
+                .into(),
+        })
+    }
+
+    fn to_format2(&self) -> Option<ChainedSequenceContextFormat2> {
+        let classes = classify(
+            self.rules
+                .iter()
+                .flat_map(|r| r.input.iter().map(|(s, _)| s)),
+        )?;
+        let backtrack_classes = classify(self.rules.iter().flat_map(|r| r.backtrack.iter()))?;
+        let lookahead_classes = classify(self.rules.iter().flat_map(|r| r.lookahead.iter()))?;
+        let mut rulesets: BTreeMap<uint16, Vec<ChainedSequenceRule>> = BTreeMap::new();
+        let mut coverage: BTreeSet<GlyphID> = BTreeSet::new();
+        for rule in &self.rules {
+            let ((first, _), rest) = rule.input.split_first()?;
+            coverage.extend(first);
+            rulesets
+                .entry(classes[first])
+                .or_default()
+                .push(ChainedSequenceRule {
+                    backtrackSequence: rule
+                        .backtrack
+                        .iter()
+                        .map(|slot| backtrack_classes[slot])
+                        .collect(),
+                    inputGlyphCount: rule.input.len() as uint16,
+                    inputSequence: rest.iter().map(|(slot, _)| classes[slot]).collect(),
+                    lookaheadSequence: rule
+                        .lookahead
+                        .iter()
+                        .map(|slot| lookahead_classes[slot])
+                        .collect(),
+                    seqLookupRecords: lookup_records(&rule.input),
+                });
+        }
+        Some(ChainedSequenceContextFormat2 {
+            format: 2,
+            coverage: Offset16::to(Coverage {
+                glyphs: coverage.into_iter().collect(),
+            }),
+            backtrackClassDef: Offset16::to(to_classdef(&backtrack_classes)),
+            inputClassDef: Offset16::to(to_classdef(&classes)),
+            lookaheadClassDef: Offset16::to(to_classdef(&lookahead_classes)),
+            chainedClassSeqRuleSets: class_rule_sets(rulesets, chained_sequence_rule_set),
+        })
+    }
+
     fn to_format3(&self) -> Vec<ChainedSequenceContextFormat3> {
         self.rules
             .iter()
-            .map(|rule| {
-                let mut coverages: Vec<Offset16<Coverage>> = vec![];
-                let lookahead_coverages: Vec<Offset16<Coverage>> = rule
-                    .lookahead
-                    .iter()
-                    .map(|slot| {
-                        Offset16::to(Coverage {
-                            glyphs: slot.iter().copied().collect(),
-                        })
-                    })
-                    .collect();
-                let backtrack_coverages: Vec<Offset16<Coverage>> = rule
+            .map(|rule| ChainedSequenceContextFormat3 {
+                format: 3,
+                backtrackCoverages: rule
                     .backtrack
                     .iter()
-                    .map(|slot| {
-                        Offset16::to(Coverage {
-                            glyphs: slot.iter().copied().collect(),
-                        })
-                    })
-                    .collect();
-
-                let mut sequence_lookup_records: Vec<SequenceLookupRecord> = vec![];
-
-                for (ix, (slot, lookup_ids)) in rule.input.iter().enumerate() {
-                    coverages.push(Offset16::to(Coverage {
-                        glyphs: slot.iter().copied().collect(),
-                    }));
-                    for lookup_id in lookup_ids {
-                        sequence_lookup_records.push(SequenceLookupRecord {
-                            sequenceIndex: ix as uint16,
-                            lookupIndex: *lookup_id,
-                        });
-                    }
-                }
-                ChainedSequenceContextFormat3 {
-                    format: 3,
-                    inputCoverages: coverages.into(),
-                    seqLookupRecords: sequence_lookup_records,
-                    backtrackCoverages: backtrack_coverages.into(),
-                    lookaheadCoverages: lookahead_coverages.into(),
-                }
+                    .map(slot_to_coverage)
+                    .collect::<Vec<_>>()
+                    .into(),
+                inputCoverages: rule
+                    .input
+                    .iter()
+                    .map(|(slot, _)| slot_to_coverage(slot))
+                    .collect::<Vec<_>>()
+                    .into(),
+                lookaheadCoverages: rule
+                    .lookahead
+                    .iter()
+                    .map(slot_to_coverage)
+                    .collect::<Vec<_>>()
+                    .into(),
+                seqLookupRecords: lookup_records(&rule.input),
             })
             .collect()
     }
 
     pub(crate) fn to_lowlevel_subtables_gpos(&self, _max_glyph_id: GlyphID) -> Vec<GPOSSubtable> {
-        self.to_format3()
-            .into_iter()
-            .map(GPOSSubtable::GPOS8_3)
-            .collect()
+        smallest_format(
+            self.to_format1(),
+            self.to_format2(),
+            self.to_format3(),
+            GPOSSubtable::GPOS8_1,
+            GPOSSubtable::GPOS8_2,
+            GPOSSubtable::GPOS8_3,
+        )
     }
     pub(crate) fn to_lowlevel_subtables_gsub(&self, _max_glyph_id: GlyphID) -> Vec<GSUBSubtable> {
-        self.to_format3()
-            .into_iter()
-            .map(GSUBSubtable::GSUB6_3)
-            .collect()
+        smallest_format(
+            self.to_format1(),
+            self.to_format2(),
+            self.to_format3(),
+            GSUBSubtable::GSUB6_1,
+            GSUBSubtable::GSUB6_2,
+            GSUBSubtable::GSUB6_3,
+        )
     }
 }
 
@@ -421,10 +698,37 @@ mod tests {
     use super::*;
     use crate::layout::common::{Lookup, LookupFlags};
     use crate::tables::GPOS::tests::{assert_can_deserialize, assert_can_roundtrip, expected_gpos};
-    use crate::tables::GPOS::Positioning;
+    use crate::tables::GPOS::{from_bytes, to_bytes, Positioning, GPOS};
     use otspec::btreeset;
+    use otspec::tables::GPOS::GPOS10;
+    use otspec::{Deserializer, ReaderContext};
     use std::iter::FromIterator;
 
+    /// Writes a table, returning the formats of the subtables its lookup
+    /// was lowered to and the table read back from the binary
+    fn lower(gpos: &GPOS) -> (Vec<uint16>, GPOS) {
+        let mut data = vec![];
+        to_bytes(gpos, &mut data, 200).unwrap();
+        let lowlevel: GPOS10 = ReaderContext::new(data.clone()).de().unwrap();
+        let formats = lowlevel.lookupList.link.as_ref().unwrap().lookups.v[0]
+            .link
+            .as_ref()
+            .unwrap()
+            .subtables
+            .v
+            .iter()
+            .map(|st| match st.link.as_ref().unwrap() {
+                GPOSSubtable::GPOS7_1(_) | GPOSSubtable::GPOS8_1(_) => 1,
+                GPOSSubtable::GPOS7_2(_) | GPOSSubtable::GPOS8_2(_) => 2,
+                _ => 3,
+            })
+            .collect();
+        (
+            formats,
+            from_bytes(&mut ReaderContext::new(data), 200).unwrap(),
+        )
+    }
+
     #[test]
     fn test_gpos_format_1() {
         /*
@@ -455,11 +759,12 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Contextual(vec![SequenceContext {
                 rules: vec![rule_one, rule_two, rule_three],
             }]),
         }]);
-        assert_can_deserialize(binary_gpos, &expected);
+        assert_can_roundtrip(binary_gpos, &expected);
     }
 
     #[test]
@@ -499,11 +804,14 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Contextual(vec![SequenceContext {
                 rules: vec![rule_one, rule_two, rule_three],
             }]),
         }]);
         assert_can_deserialize(binary_gpos, &expected);
+        // We leave out the empty rule set for class 0
+        assert_eq!(lower(&expected), (vec![2], expected.clone()));
     }
     #[test]
     fn test_gsub_format_3() {
@@ -531,11 +839,12 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Contextual(vec![SequenceContext {
                 rules: vec![rule_one],
             }]),
         }]);
-        assert_can_deserialize(binary_gpos, &expected);
+        assert_can_roundtrip(binary_gpos, &expected);
     }
 
     #[test]
@@ -563,6 +872,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::ChainedContextual(vec![ChainedSequenceContext {
                 rules: vec![
                     ChainedSequenceContextRule {
@@ -583,7 +893,7 @@ mod tests {
                 ],
             }]),
         }]);
-        assert_can_deserialize(binary_gpos, &expected);
+        assert_can_roundtrip(binary_gpos, &expected);
     }
 
     #[test]
@@ -624,6 +934,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::ChainedContextual(vec![ChainedSequenceContext {
                 rules: vec![
                     ChainedSequenceContextRule {
@@ -645,6 +956,8 @@ mod tests {
             }]),
         }]);
         assert_can_deserialize(binary_gpos, &expected);
+        // We leave out the empty rule set for class 0
+        assert_eq!(lower(&expected), (vec![2], expected.clone()));
     }
     #[test]
     fn test_gpos_chained_format3() {
@@ -667,6 +980,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::ChainedContextual(vec![ChainedSequenceContext {
                 rules: vec![ChainedSequenceContextRule {
                     backtrack: vec![btreeset!(89)],
@@ -677,4 +991,55 @@ mod tests {
         }]);
         assert_can_roundtrip(binary_gpos, &expected);
     }
+
+    fn contextual(rules: Vec<SequenceContextRule>) -> GPOS {
+        expected_gpos(vec![Lookup {
+            flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            extension: false,
+            rule: Positioning::Contextual(vec![SequenceContext { rules }]),
+        }])
+    }
+
+    #[test]
+    fn test_lowering_picks_format() {
+        // A single rule is smallest as format 3
+        let gpos = contextual(vec![vec![
+            (btreeset!(66), vec![0]),
+            (btreeset!(67), vec![]),
+        ]]);
+        assert_eq!(lower(&gpos), (vec![3], gpos.clone()));
+
+        // Several rules of single glyphs share a format 1 subtable
+        let gpos = contextual(vec![
+            vec![(btreeset!(66), vec![0]), (btreeset!(67), vec![])],
+            vec![(btreeset!(66), vec![]), (btreeset!(68), vec![0])],
+            vec![(btreeset!(67), vec![0]), (btreeset!(68), vec![0])],
+        ]);
+        assert_eq!(lower(&gpos), (vec![1], gpos.clone()));
+
+        // Rules of disjoint classes share a format 2 subtable
+        let gpos = contextual(vec![
+            vec![(btreeset!(66, 67), vec![0]), (btreeset!(68, 69), vec![])],
+            vec![(btreeset!(66, 67), vec![0]), (btreeset!(66, 67), vec![0])],
+            vec![(btreeset!(68, 69), vec![]), (btreeset!(66, 67), vec![0])],
+            vec![(btreeset!(68, 69), vec![0]), (btreeset!(68, 69), vec![0])],
+        ]);
+        assert_eq!(lower(&gpos), (vec![2], gpos.clone()));
+
+        // Overlapping classes need a format 3 subtable for each rule
+        let rules = vec![
+            vec![(btreeset!(66, 67), vec![0]), (btreeset!(68), vec![])],
+            vec![(btreeset!(67, 68), vec![0]), (btreeset!(66), vec![])],
+        ];
+        let (formats, gpos) = lower(&contextual(rules.clone()));
+        assert_eq!(formats, vec![3, 3]);
+        if let Positioning::Contextual(subtables) = &gpos.lookups[0].rule {
+            let raised: Vec<SequenceContextRule> =
+                subtables.iter().flat_map(|st| st.rules.clone()).collect();
+            assert_eq!(raised, rules);
+        } else {
+            panic!("Wrong lookup type");
+        }
+    }
 }
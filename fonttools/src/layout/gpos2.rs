@@ -12,9 +12,11 @@ pub type PairPositioningMap = BTreeMap<(GlyphID, GlyphID), (ValueRecord, ValueRe
 pub type SplitPairPositioningMap = BTreeMap<GlyphID, BTreeMap<GlyphID, (ValueRecord, ValueRecord)>>;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A pair positioning subtable.
 pub struct PairPos {
     /// The mapping of pair glyph IDs to pairs of value records.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_map"))]
     pub mapping: PairPositioningMap,
 }
 
@@ -173,6 +175,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Pair(vec![PairPos {
                 mapping: btreemap!(
                     (34,35) => (valuerecord!(xAdvance = -20),valuerecord!()),
@@ -8,6 +8,7 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A cursive positioning subtable.
 pub struct CursivePos {
     /// The mapping of glyph IDs to entry and exit anchor records.
@@ -87,6 +88,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Cursive(vec![CursivePos {
                 mapping: btreemap!(
                     34 => (Some(Anchor { xCoordinate: 100, yCoordinate: 200, anchorPoint: None }), None),
@@ -9,6 +9,7 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A mark-to-base subtable.
 pub struct MarkBasePos {
     /// Base glyphs to be attached in this subtable
@@ -186,6 +187,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::MarkToBase(vec![MarkBasePos {
                 marks: btreemap!(819 => (0, Anchor::new(346,-98)), 831 => (1, Anchor::new(261, 88))),
                 bases: btreemap!(400 => expected_tah_marks),
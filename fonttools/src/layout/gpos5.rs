@@ -9,6 +9,7 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A mark-to-ligature subtable.
 pub struct MarkLigPos {
     /// Ligature glyphs to be attached in this subtable
@@ -220,6 +221,7 @@ mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::MarkToLig(vec![MarkLigPos {
                 ligatures: btreemap!(564 => vec![
                    btreemap!(0 => Anchor { xCoordinate: 625, yCoordinate: 1800, anchorPoint: None },
@@ -9,6 +9,7 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A mark-to-base subtable.
 pub struct MarkMarkPos {
     /// Base marks to be attached in this subtable
@@ -9,6 +9,7 @@ use std::collections::BTreeMap;
 GID -> GID is a friendly way to represent what's going on. */
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A single substitution subtable.
 pub struct SingleSubst {
     /// The mapping of input glyph IDs to replacement glyph IDs.
@@ -104,6 +105,7 @@ mod tests {
         let expected = expected_gsub(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Substitution::Single(vec![SingleSubst {
                 mapping: btreemap!(
                     66 => 67,
@@ -127,6 +129,7 @@ mod tests {
         let expected = expected_gsub(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Substitution::Single(vec![SingleSubst {
                 mapping: btreemap!(
                     66 => 67,
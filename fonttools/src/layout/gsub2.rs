@@ -6,6 +6,7 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A multiple substitution (one-to-many) subtable.
 pub struct MultipleSubst {
     /// The mapping of input glyph IDs to sequence of replacement glyph IDs.
@@ -74,6 +75,7 @@ mod tests {
         let expected = expected_gsub(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Substitution::Multiple(vec![MultipleSubst {
                 mapping: btreemap!(
                     66 => vec![67, 68],
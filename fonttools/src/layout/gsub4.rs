@@ -6,9 +6,11 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A ligature substitution (many-to-one) subtable.
 pub struct LigatureSubst {
     /// The mapping of sequences of input glyphs IDs to replacement glyph IDs.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_map"))]
     pub mapping: BTreeMap<Vec<GlyphID>, GlyphID>,
 }
 
@@ -106,6 +108,7 @@ mod tests {
         let expected = expected_gsub(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Substitution::Ligature(vec![LigatureSubst {
                 mapping: btreemap!(
                     vec![71, 71, 77] => 240,
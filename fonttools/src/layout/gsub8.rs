@@ -7,6 +7,7 @@ use otspec::types::*;
 use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A reverse chaining substitution subtable.
 pub struct ReverseChainSubst {
     /// The mapping of input glyph IDs to substitute.
@@ -106,6 +107,7 @@ mod tests {
         let expected = expected_gsub(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Substitution::ReverseChainContextual(vec![ReverseChainSubst {
                 backtrack: vec![btreeset!(69), btreeset!(66, 67, 68)],
                 lookahead: vec![btreeset!(70, 71), btreeset!(72)],
@@ -41,6 +41,7 @@ macro_rules! format_switching_lookup {
         paste::paste! {
 
             #[derive(Debug, Clone, PartialEq)]
+            #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
             /// Internal representation of $lookup
             pub enum [<$lookup Internal>] {
                 $(
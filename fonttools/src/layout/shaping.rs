@@ -0,0 +1,1073 @@
+//! Applying OpenType Layout lookups to a run of glyphs
+//!
+//! The [`Shaper`] takes a sequence of glyph IDs, applies the GSUB and then
+//! the GPOS lookups of the selected features to it, and returns the
+//! resulting glyphs with their positions. Lookup flags, mark filtering sets
+//! and `GDEF` glyph classes are honoured, and all lookup types are
+//! supported, including contextual lookups.
+//!
+//! This is not a complete shaping engine: there is no script-specific
+//! processing, every selected feature applies to every glyph, alternate
+//! substitutions choose the first alternate, and text is assumed to run
+//! from left to right.
+//!
+//! ```
+//! use fonttools::layout::shaping::{FeatureSelection, Shaper};
+//! use fonttools::tag;
+//! # let gsub = fonttools::tables::GSUB::GSUB::default();
+//!
+//! let shaper = Shaper::new(Some(&gsub), None, None);
+//! let selection = FeatureSelection::new(&[tag!("liga"), tag!("kern")]);
+//! let glyphs = shaper.shape(&[1, 2, 3], &selection, |_gid| 500);
+//! assert_eq!(glyphs[2].cluster, 2);
+//! ```
+use crate::layout::common::{Lookup, LookupFlags, ValueRecord, GPOSGSUB};
+use crate::layout::contextual::{
+    ChainedSequenceContext, SequenceContext, SequenceContextRule, Slot,
+};
+use crate::layout::gsub4::LigatureSubst;
+use crate::tables::GDEF::{GlyphClass, GDEF};
+use crate::tables::GPOS::{Positioning, GPOS};
+use crate::tables::GSUB::{Substitution, GSUB};
+use otspec::layout::anchor::Anchor;
+use otspec::types::{GlyphID, Tag};
+use std::collections::BTreeSet;
+
+/// How deeply contextual lookups may invoke other contextual lookups
+const MAX_NESTING_LEVEL: u8 = 6;
+
+/// Which script, language system and features to apply
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureSelection {
+    /// The script whose features are applied. If a table has no such
+    /// script, its `DFLT` script is used instead.
+    pub script: Tag,
+    /// The language system whose features are applied, or the script's
+    /// default language system if `None` or not present in the table
+    pub language: Option<Tag>,
+    /// The features to apply, in addition to any required feature
+    pub features: Vec<Tag>,
+}
+
+impl FeatureSelection {
+    /// Selects the given features in the default script and language
+    pub fn new(features: &[Tag]) -> Self {
+        FeatureSelection {
+            script: crate::tag!("DFLT"),
+            language: None,
+            features: features.to_vec(),
+        }
+    }
+}
+
+/// Returns the indices of the lookups of a GSUB or GPOS table which a
+/// selection of features refers to, in the order they are applied.
+pub fn selected_lookups<T>(table: &GPOSGSUB<T>, selection: &FeatureSelection) -> Vec<usize> {
+    let scripts = &table.scripts.scripts;
+    let script = match scripts
+        .get(&selection.script)
+        .or_else(|| scripts.get(&crate::tag!("DFLT")))
+    {
+        Some(script) => script,
+        None => return vec![],
+    };
+    let language_system = match selection
+        .language
+        .and_then(|language| script.language_systems.get(&language))
+        .or(script.default_language_system.as_ref())
+    {
+        Some(language_system) => language_system,
+        None => return vec![],
+    };
+    let mut lookups = BTreeSet::new();
+    if let Some((_, indices, _)) = language_system
+        .required_feature
+        .and_then(|index| table.features.get(index))
+    {
+        lookups.extend(indices.iter().copied());
+    }
+    for &index in &language_system.feature_indices {
+        if let Some((tag, indices, _)) = table.features.get(index) {
+            if selection.features.contains(tag) {
+                lookups.extend(indices.iter().copied());
+            }
+        }
+    }
+    lookups.into_iter().collect()
+}
+
+/// A glyph after shaping, with its position in font units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShapedGlyph {
+    /// The glyph ID
+    pub gid: GlyphID,
+    /// The index of the input glyph this glyph came from. Glyphs produced
+    /// by a ligature take the index of the first glyph of the ligature.
+    pub cluster: usize,
+    /// How far to move the pen horizontally after drawing this glyph
+    pub x_advance: i32,
+    /// How far to move the pen vertically after drawing this glyph
+    pub y_advance: i32,
+    /// How far to move the glyph horizontally from the pen position
+    pub x_offset: i32,
+    /// How far to move the glyph vertically from the pen position
+    pub y_offset: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AttachmentKind {
+    Mark,
+    Cursive,
+}
+
+/// A glyph in the buffer being shaped
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    gid: GlyphID,
+    cluster: usize,
+    /// Identifies the ligature which this glyph is, or a mark belongs to;
+    /// zero if none
+    ligature_id: u32,
+    /// For a mark within a ligature, the (1-based) component it follows
+    ligature_component: u16,
+    x_advance: i32,
+    y_advance: i32,
+    x_offset: i32,
+    y_offset: i32,
+    /// The glyph this glyph's offsets are relative to
+    attachment: Option<(usize, AttachmentKind)>,
+}
+
+struct Buffer {
+    items: Vec<Item>,
+    next_ligature_id: u32,
+}
+
+/// The parts of a lookup which decide which glyphs it skips
+#[derive(Debug, Clone, Copy)]
+struct Filter {
+    flags: LookupFlags,
+    mark_filtering_set: Option<u16>,
+}
+
+impl<T> From<&Lookup<T>> for Filter {
+    fn from(lookup: &Lookup<T>) -> Self {
+        Filter {
+            flags: lookup.flags,
+            mark_filtering_set: lookup.mark_filtering_set,
+        }
+    }
+}
+
+/// A contextual rule: its backtrack, input (with lookups) and lookahead
+type ContextRule<'r> = (&'r [Slot], &'r SequenceContextRule, &'r [Slot]);
+
+/// Applies GSUB and GPOS lookups to runs of glyphs
+pub struct Shaper<'a> {
+    gsub: Option<&'a GSUB>,
+    gpos: Option<&'a GPOS>,
+    gdef: Option<&'a GDEF>,
+}
+
+impl<'a> Shaper<'a> {
+    /// Creates a shaper for a font's layout tables, any of which may be
+    /// absent.
+    pub fn new(gsub: Option<&'a GSUB>, gpos: Option<&'a GPOS>, gdef: Option<&'a GDEF>) -> Self {
+        Shaper { gsub, gpos, gdef }
+    }
+
+    /// Applies the selected features to a run of glyphs. `advance` gives
+    /// the default advance width of a glyph, which GPOS lookups adjust.
+    pub fn shape(
+        &self,
+        glyphs: &[GlyphID],
+        selection: &FeatureSelection,
+        advance: impl Fn(GlyphID) -> i32,
+    ) -> Vec<ShapedGlyph> {
+        let mut buffer = Buffer {
+            items: glyphs
+                .iter()
+                .enumerate()
+                .map(|(cluster, &gid)| Item {
+                    gid,
+                    cluster,
+                    ligature_id: 0,
+                    ligature_component: 0,
+                    x_advance: 0,
+                    y_advance: 0,
+                    x_offset: 0,
+                    y_offset: 0,
+                    attachment: None,
+                })
+                .collect(),
+            next_ligature_id: 1,
+        };
+
+        if let Some(gsub) = self.gsub {
+            for index in selected_lookups(gsub, selection) {
+                self.apply_substitution(&mut buffer, index);
+            }
+        }
+        for item in buffer.items.iter_mut() {
+            item.x_advance = advance(item.gid);
+        }
+        if let Some(gpos) = self.gpos {
+            for index in selected_lookups(gpos, selection) {
+                self.apply_positioning(&mut buffer, index);
+            }
+        }
+        resolve_attachments(&mut buffer.items);
+
+        buffer
+            .items
+            .iter()
+            .map(|item| ShapedGlyph {
+                gid: item.gid,
+                cluster: item.cluster,
+                x_advance: item.x_advance,
+                y_advance: item.y_advance,
+                x_offset: item.x_offset,
+                y_offset: item.y_offset,
+            })
+            .collect()
+    }
+
+    fn glyph_class(&self, gid: GlyphID) -> Option<GlyphClass> {
+        self.gdef?.glyph_class.get(&gid).copied()
+    }
+
+    fn is_mark(&self, gid: GlyphID) -> bool {
+        self.glyph_class(gid) == Some(GlyphClass::MarkGlyph)
+    }
+
+    /// Whether a lookup passes over the given glyph
+    fn skips(&self, gid: GlyphID, filter: Filter) -> bool {
+        let flags = filter.flags;
+        match self.glyph_class(gid) {
+            Some(GlyphClass::BaseGlyph) => flags.contains(LookupFlags::IGNORE_BASE_GLYPHS),
+            Some(GlyphClass::LigatureGlyph) => flags.contains(LookupFlags::IGNORE_LIGATURES),
+            Some(GlyphClass::MarkGlyph) => {
+                if flags.contains(LookupFlags::IGNORE_MARKS) {
+                    return true;
+                }
+                if flags.contains(LookupFlags::USE_MARK_FILTERING_SET) {
+                    let set = filter
+                        .mark_filtering_set
+                        .and_then(|index| self.gdef?.mark_glyph_sets.as_ref()?.get(index as usize));
+                    return !set.is_some_and(|set| set.contains(&gid));
+                }
+                let mark_class = (flags & LookupFlags::MARK_ATTACHMENT_TYPE_MASK).bits() >> 8;
+                mark_class != 0
+                    && self
+                        .gdef
+                        .and_then(|gdef| gdef.mark_attachment_class.get(&gid))
+                        != Some(&mark_class)
+            }
+            _ => false,
+        }
+    }
+
+    /// The position of the next glyph after `from` which is not skipped
+    fn next(&self, items: &[Item], from: usize, filter: Filter) -> Option<usize> {
+        (from + 1..items.len()).find(|&j| !self.skips(items[j].gid, filter))
+    }
+
+    /// The position of the last glyph before `from` which is not skipped
+    fn previous(&self, items: &[Item], from: usize, filter: Filter) -> Option<usize> {
+        (0..from).rev().find(|&j| !self.skips(items[j].gid, filter))
+    }
+
+    /// Matches a sequence of slots starting at a position, returning the
+    /// positions of the matched glyphs
+    fn match_input<'s>(
+        &self,
+        items: &[Item],
+        start: usize,
+        mut slots: impl Iterator<Item = &'s Slot>,
+        filter: Filter,
+    ) -> Option<Vec<usize>> {
+        if !slots.next()?.contains(&items[start].gid) {
+            return None;
+        }
+        let mut positions = vec![start];
+        for slot in slots {
+            let next = self.next(items, *positions.last().unwrap(), filter)?;
+            if !slot.contains(&items[next].gid) {
+                return None;
+            }
+            positions.push(next);
+        }
+        Some(positions)
+    }
+
+    /// Matches glyphs before a position, the first slot being closest
+    fn match_backtrack(
+        &self,
+        items: &[Item],
+        start: usize,
+        slots: &[Slot],
+        filter: Filter,
+    ) -> bool {
+        let mut position = start;
+        slots
+            .iter()
+            .all(|slot| match self.previous(items, position, filter) {
+                Some(previous) if slot.contains(&items[previous].gid) => {
+                    position = previous;
+                    true
+                }
+                _ => false,
+            })
+    }
+
+    /// Matches glyphs after a position
+    fn match_lookahead(&self, items: &[Item], end: usize, slots: &[Slot], filter: Filter) -> bool {
+        let mut position = end;
+        slots
+            .iter()
+            .all(|slot| match self.next(items, position, filter) {
+                Some(next) if slot.contains(&items[next].gid) => {
+                    position = next;
+                    true
+                }
+                _ => false,
+            })
+    }
+
+    /// Applies the first matching contextual rule at a position, calling
+    /// `apply` to run each of its nested lookups. Returns the position after
+    /// the matched input.
+    fn apply_context<'r>(
+        &self,
+        buffer: &mut Buffer,
+        position: usize,
+        filter: Filter,
+        rules: impl Iterator<Item = ContextRule<'r>>,
+        nesting_level: u8,
+        apply: fn(&Self, &mut Buffer, usize, usize, u8) -> Option<usize>,
+    ) -> Option<usize> {
+        let (mut positions, input) =
+            rules
+                .into_iter()
+                .find_map(|(backtrack, input, lookahead)| {
+                    let positions = self.match_input(
+                        &buffer.items,
+                        position,
+                        input.iter().map(|(slot, _)| slot),
+                        filter,
+                    )?;
+                    let matched = self.match_backtrack(&buffer.items, position, backtrack, filter)
+                        && self.match_lookahead(
+                            &buffer.items,
+                            *positions.last()?,
+                            lookahead,
+                            filter,
+                        );
+                    matched.then_some((positions, input))
+                })?;
+        for (sequence_index, (_, lookups)) in input.iter().enumerate() {
+            for &lookup in lookups {
+                if nesting_level >= MAX_NESTING_LEVEL {
+                    break;
+                }
+                let target = positions[sequence_index];
+                if target >= buffer.items.len() {
+                    continue;
+                }
+                let length = buffer.items.len();
+                apply(self, buffer, lookup as usize, target, nesting_level + 1);
+                // Keep track of the later input glyphs if the lookup
+                // changed the length of the buffer
+                let change = buffer.items.len() as isize - length as isize;
+                for later in positions.iter_mut().skip(sequence_index + 1) {
+                    *later = (*later as isize + change).max(target as isize) as usize;
+                }
+            }
+        }
+        Some((positions.last()? + 1).max(position + 1))
+    }
+
+    fn apply_substitution(&self, buffer: &mut Buffer, lookup_index: usize) {
+        let lookup = match self.gsub.and_then(|gsub| gsub.lookups.get(lookup_index)) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        let filter = Filter::from(lookup);
+        if let Substitution::ReverseChainContextual(subtables) = &lookup.rule {
+            // These are applied from the end of the buffer to the start
+            let items = &mut buffer.items;
+            for position in (0..items.len()).rev() {
+                if self.skips(items[position].gid, filter) {
+                    continue;
+                }
+                let replacement = subtables.iter().find_map(|st| {
+                    let replacement = st.mapping.get(&items[position].gid)?;
+                    (self.match_backtrack(items, position, &st.backtrack, filter)
+                        && self.match_lookahead(items, position, &st.lookahead, filter))
+                    .then_some(*replacement)
+                });
+                if let Some(replacement) = replacement {
+                    items[position].gid = replacement;
+                }
+            }
+            return;
+        }
+        let mut position = 0;
+        while position < buffer.items.len() {
+            if self.skips(buffer.items[position].gid, filter) {
+                position += 1;
+                continue;
+            }
+            position = self
+                .substitute_at(buffer, lookup_index, position, 0)
+                .unwrap_or(position + 1);
+        }
+    }
+
+    /// Applies a GSUB lookup at a position. If it applies, returns the
+    /// position after the glyphs it consumed or produced.
+    fn substitute_at(
+        &self,
+        buffer: &mut Buffer,
+        lookup_index: usize,
+        position: usize,
+        nesting_level: u8,
+    ) -> Option<usize> {
+        let lookup = self.gsub?.lookups.get(lookup_index)?;
+        let filter = Filter::from(lookup);
+        let gid = buffer.items[position].gid;
+        match &lookup.rule {
+            Substitution::Single(subtables) => {
+                let replacement = subtables.iter().find_map(|st| st.mapping.get(&gid))?;
+                buffer.items[position].gid = *replacement;
+                Some(position + 1)
+            }
+            Substitution::Multiple(subtables) => {
+                let sequence = subtables.iter().find_map(|st| st.mapping.get(&gid))?;
+                let template = buffer.items[position];
+                buffer.items.splice(
+                    position..=position,
+                    sequence.iter().map(|&gid| Item { gid, ..template }),
+                );
+                Some(position + sequence.len())
+            }
+            Substitution::Alternate(subtables) => {
+                let alternates = subtables.iter().find_map(|st| st.mapping.get(&gid))?;
+                buffer.items[position].gid = *alternates.first()?;
+                Some(position + 1)
+            }
+            Substitution::Ligature(subtables) => self.ligate(buffer, subtables, position, filter),
+            Substitution::Contextual(subtables) => self.apply_context(
+                buffer,
+                position,
+                filter,
+                sequence_rules(subtables),
+                nesting_level,
+                Self::substitute_at,
+            ),
+            Substitution::ChainedContextual(subtables) => self.apply_context(
+                buffer,
+                position,
+                filter,
+                chained_rules(subtables),
+                nesting_level,
+                Self::substitute_at,
+            ),
+            // Only applied directly, never from a contextual lookup
+            Substitution::ReverseChainContextual(_) => None,
+        }
+    }
+
+    fn ligate(
+        &self,
+        buffer: &mut Buffer,
+        subtables: &[LigatureSubst],
+        position: usize,
+        filter: Filter,
+    ) -> Option<usize> {
+        let gid = buffer.items[position].gid;
+        for subtable in subtables {
+            let mut candidates: Vec<(&Vec<GlyphID>, &GlyphID)> = subtable
+                .mapping
+                .range(vec![gid]..)
+                .take_while(|(components, _)| components.first() == Some(&gid))
+                .collect();
+            // Prefer the longest ligatures
+            candidates.sort_by_key(|(components, _)| std::cmp::Reverse(components.len()));
+            for (components, &ligature) in candidates {
+                let slots: Vec<Slot> = components.iter().map(|&c| [c].into()).collect();
+                let positions =
+                    match self.match_input(&buffer.items, position, slots.iter(), filter) {
+                        Some(positions) => positions,
+                        None => continue,
+                    };
+                let ligature_id = buffer.next_ligature_id;
+                buffer.next_ligature_id += 1;
+                // Skipped marks between the components remember which
+                // component they follow, for mark-to-ligature positioning
+                for (component, pair) in positions.windows(2).enumerate() {
+                    for mark in buffer.items[pair[0] + 1..pair[1]].iter_mut() {
+                        mark.ligature_id = ligature_id;
+                        mark.ligature_component = component as u16 + 1;
+                    }
+                }
+                for &matched in positions[1..].iter().rev() {
+                    buffer.items.remove(matched);
+                }
+                let item = &mut buffer.items[position];
+                item.gid = ligature;
+                item.ligature_id = ligature_id;
+                item.ligature_component = 0;
+                return Some(position + 1);
+            }
+        }
+        None
+    }
+
+    fn apply_positioning(&self, buffer: &mut Buffer, lookup_index: usize) {
+        let lookup = match self.gpos.and_then(|gpos| gpos.lookups.get(lookup_index)) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        let filter = Filter::from(lookup);
+        let mut position = 0;
+        while position < buffer.items.len() {
+            if self.skips(buffer.items[position].gid, filter) {
+                position += 1;
+                continue;
+            }
+            position = self
+                .position_at(buffer, lookup_index, position, 0)
+                .unwrap_or(position + 1);
+        }
+    }
+
+    /// Applies a GPOS lookup at a position. If it applies, returns the
+    /// position at which to continue.
+    fn position_at(
+        &self,
+        buffer: &mut Buffer,
+        lookup_index: usize,
+        position: usize,
+        nesting_level: u8,
+    ) -> Option<usize> {
+        let lookup = self.gpos?.lookups.get(lookup_index)?;
+        let filter = Filter::from(lookup);
+        let items = &mut buffer.items;
+        let gid = items[position].gid;
+        match &lookup.rule {
+            Positioning::Single(subtables) => {
+                let value = subtables.iter().find_map(|st| st.mapping.get(&gid))?;
+                adjust(&mut items[position], value);
+                Some(position + 1)
+            }
+            Positioning::Pair(subtables) => {
+                let next = self.next(items, position, filter)?;
+                let pair = (gid, items[next].gid);
+                let (first, second) = subtables.iter().find_map(|st| st.mapping.get(&pair))?;
+                adjust(&mut items[position], first);
+                adjust(&mut items[next], second);
+                // If the second glyph was adjusted, it cannot start a pair
+                Some(if second.has_any() { next + 1 } else { next })
+            }
+            Positioning::Cursive(subtables) => {
+                let next = self.next(items, position, filter)?;
+                let (exit, entry) = subtables.iter().find_map(|st| {
+                    let exit = st.mapping.get(&gid)?.1.as_ref()?;
+                    let entry = st.mapping.get(&items[next].gid)?.0.as_ref()?;
+                    Some((exit, entry))
+                })?;
+                let exit_x = exit.xCoordinate as i32;
+                items[position].x_advance = exit_x + items[position].x_offset;
+                let entry_x = entry.xCoordinate as i32 + items[next].x_offset;
+                items[next].x_advance -= entry_x;
+                items[next].x_offset -= entry_x;
+                let rise = exit.yCoordinate as i32 - entry.yCoordinate as i32;
+                if lookup.flags.contains(LookupFlags::RIGHT_TO_LEFT) {
+                    items[position].y_offset = -rise;
+                    items[position].attachment = Some((next, AttachmentKind::Cursive));
+                } else {
+                    items[next].y_offset = rise;
+                    items[next].attachment = Some((position, AttachmentKind::Cursive));
+                }
+                Some(next)
+            }
+            Positioning::MarkToBase(subtables) => {
+                let (base, base_anchor, mark_anchor) = subtables.iter().find_map(|st| {
+                    let (class, mark_anchor) = st.marks.get(&gid)?;
+                    let base = (0..position).rev().find(|&j| {
+                        !(self.is_mark(items[j].gid)
+                            || self.gdef.is_none() && st.marks.contains_key(&items[j].gid))
+                    })?;
+                    let base_anchor = st.bases.get(&items[base].gid)?.get(class)?;
+                    Some((base, base_anchor, mark_anchor))
+                })?;
+                attach_mark(items, position, base, base_anchor, mark_anchor);
+                Some(position + 1)
+            }
+            Positioning::MarkToLig(subtables) => {
+                let (base, base_anchor, mark_anchor) = subtables.iter().find_map(|st| {
+                    let (class, mark_anchor) = st.marks.get(&gid)?;
+                    let base = (0..position).rev().find(|&j| {
+                        !(self.is_mark(items[j].gid)
+                            || self.gdef.is_none() && st.marks.contains_key(&items[j].gid))
+                    })?;
+                    let components = st.ligatures.get(&items[base].gid)?;
+                    let (mark, ligature) = (&items[position], &items[base]);
+                    // Marks within a ligature attach to the component
+                    // they follow; others to the last component
+                    let component = if mark.ligature_id != 0
+                        && mark.ligature_id == ligature.ligature_id
+                        && mark.ligature_component > 0
+                    {
+                        (mark.ligature_component as usize - 1).min(components.len().max(1) - 1)
+                    } else {
+                        components.len().checked_sub(1)?
+                    };
+                    let base_anchor = components.get(component)?.get(class)?;
+                    Some((base, base_anchor, mark_anchor))
+                })?;
+                attach_mark(items, position, base, base_anchor, mark_anchor);
+                Some(position + 1)
+            }
+            Positioning::MarkToMark(subtables) => {
+                let previous = self.previous(items, position, filter)?;
+                let (mark, base) = (&items[position], &items[previous]);
+                // Marks on different components of a ligature don't interact
+                if mark.ligature_id == base.ligature_id
+                    && mark.ligature_component != base.ligature_component
+                {
+                    return None;
+                }
+                let (base_anchor, mark_anchor) = subtables.iter().find_map(|st| {
+                    let (class, mark_anchor) = st.combining_marks.get(&gid)?;
+                    let base_anchor = st.base_marks.get(&base.gid)?.get(class)?;
+                    Some((base_anchor, mark_anchor))
+                })?;
+                attach_mark(items, position, previous, base_anchor, mark_anchor);
+                Some(position + 1)
+            }
+            Positioning::Contextual(subtables) => self.apply_context(
+                buffer,
+                position,
+                filter,
+                sequence_rules(subtables),
+                nesting_level,
+                Self::position_at,
+            ),
+            Positioning::ChainedContextual(subtables) => self.apply_context(
+                buffer,
+                position,
+                filter,
+                chained_rules(subtables),
+                nesting_level,
+                Self::position_at,
+            ),
+        }
+    }
+}
+
+fn sequence_rules(subtables: &[SequenceContext]) -> impl Iterator<Item = ContextRule<'_>> {
+    subtables
+        .iter()
+        .flat_map(|st| st.rules.iter())
+        .map(|rule| (&[][..], rule, &[][..]))
+}
+
+fn chained_rules(subtables: &[ChainedSequenceContext]) -> impl Iterator<Item = ContextRule<'_>> {
+    subtables
+        .iter()
+        .flat_map(|st| st.rules.iter())
+        .map(|rule| (&rule.backtrack[..], &rule.input, &rule.lookahead[..]))
+}
+
+fn adjust(item: &mut Item, value: &ValueRecord) {
+    item.x_offset += value.xPlacement.unwrap_or(0) as i32;
+    item.y_offset += value.yPlacement.unwrap_or(0) as i32;
+    item.x_advance += value.xAdvance.unwrap_or(0) as i32;
+    item.y_advance += value.yAdvance.unwrap_or(0) as i32;
+}
+
+fn attach_mark(
+    items: &mut [Item],
+    mark: usize,
+    base: usize,
+    base_anchor: &Anchor,
+    mark_anchor: &Anchor,
+) {
+    let item = &mut items[mark];
+    item.x_offset = base_anchor.xCoordinate as i32 - mark_anchor.xCoordinate as i32;
+    item.y_offset = base_anchor.yCoordinate as i32 - mark_anchor.yCoordinate as i32;
+    item.attachment = Some((base, AttachmentKind::Mark));
+}
+
+/// Makes the offsets of attached glyphs relative to their own pen
+/// positions rather than to the glyphs they are attached to.
+fn resolve_attachments(items: &mut [Item]) {
+    let mut resolved = vec![false; items.len()];
+    for index in 0..items.len() {
+        resolve_attachment(items, index, &mut resolved, 0);
+    }
+}
+
+fn resolve_attachment(items: &mut [Item], index: usize, resolved: &mut [bool], depth: usize) {
+    if resolved[index] {
+        return;
+    }
+    resolved[index] = true;
+    let (parent, kind) = match items[index].attachment {
+        Some(attachment) if attachment.0 < items.len() && depth < items.len() => attachment,
+        _ => return,
+    };
+    resolve_attachment(items, parent, resolved, depth + 1);
+    items[index].y_offset += items[parent].y_offset;
+    if kind == AttachmentKind::Mark {
+        items[index].x_offset += items[parent].x_offset;
+        if parent < index {
+            items[index].x_offset -= items[parent..index]
+                .iter()
+                .map(|item| item.x_advance)
+                .sum::<i32>();
+        } else {
+            items[index].x_offset += items[index..parent]
+                .iter()
+                .map(|item| item.x_advance)
+                .sum::<i32>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::common::{FeatureList, LanguageSystem, Script, ScriptList};
+    use crate::layout::contextual::ChainedSequenceContextRule;
+    use crate::layout::gpos2::PairPos;
+    use crate::layout::gpos3::CursivePos;
+    use crate::layout::gpos4::MarkBasePos;
+    use crate::layout::gpos5::MarkLigPos;
+    use crate::layout::gpos6::MarkMarkPos;
+    use crate::layout::gsub1::SingleSubst;
+    use crate::layout::gsub8::ReverseChainSubst;
+    use crate::tag;
+    use std::collections::BTreeMap;
+
+    // Glyph IDs used in the tests
+    const F: GlyphID = 1;
+    const I: GlyphID = 2;
+    const FI: GlyphID = 3;
+    const A: GlyphID = 4;
+    const ACUTE: GlyphID = 5;
+    const GRAVE: GlyphID = 6;
+    const B: GlyphID = 7;
+    const A_ALT: GlyphID = 8;
+
+    fn gdef() -> GDEF {
+        let mut glyph_class = BTreeMap::new();
+        for gid in [F, I, A, B, A_ALT] {
+            glyph_class.insert(gid, GlyphClass::BaseGlyph);
+        }
+        glyph_class.insert(FI, GlyphClass::LigatureGlyph);
+        glyph_class.insert(ACUTE, GlyphClass::MarkGlyph);
+        glyph_class.insert(GRAVE, GlyphClass::MarkGlyph);
+        GDEF {
+            glyph_class,
+            attachment_point_list: BTreeMap::new(),
+            ligature_caret_list: BTreeMap::new(),
+            mark_attachment_class: [(ACUTE, 1), (GRAVE, 2)].into_iter().collect(),
+            mark_glyph_sets: Some(vec![[GRAVE].into()]),
+            item_variation_store: None,
+        }
+    }
+
+    /// A table whose `test` feature contains the given lookups
+    fn table<T>(lookups: Vec<Lookup<T>>, feature_lookups: Vec<usize>) -> GPOSGSUB<T> {
+        let mut scripts = BTreeMap::new();
+        scripts.insert(
+            tag!("DFLT"),
+            Script {
+                default_language_system: Some(LanguageSystem {
+                    required_feature: None,
+                    feature_indices: vec![0],
+                }),
+                language_systems: BTreeMap::new(),
+            },
+        );
+        GPOSGSUB {
+            lookups,
+            scripts: ScriptList { scripts },
+            features: FeatureList::new(vec![(tag!("test"), feature_lookups, None)]),
+        }
+    }
+
+    fn lookup<T>(flags: LookupFlags, rule: T) -> Lookup<T> {
+        Lookup {
+            flags,
+            mark_filtering_set: None,
+            extension: false,
+            rule,
+        }
+    }
+
+    fn selection() -> FeatureSelection {
+        FeatureSelection::new(&[tag!("test")])
+    }
+
+    fn gids(glyphs: &[ShapedGlyph]) -> Vec<GlyphID> {
+        glyphs.iter().map(|g| g.gid).collect()
+    }
+
+    fn ligatures() -> LigatureSubst {
+        let mut ligatures = LigatureSubst::default();
+        ligatures.mapping.insert(vec![F, I], FI);
+        ligatures
+    }
+
+    #[test]
+    fn test_ligature_flags() {
+        let gdef = gdef();
+        let gsub = table(
+            vec![
+                lookup(
+                    LookupFlags::empty(),
+                    Substitution::Ligature(vec![ligatures()]),
+                ),
+                lookup(
+                    LookupFlags::IGNORE_MARKS,
+                    Substitution::Ligature(vec![ligatures()]),
+                ),
+            ],
+            vec![0],
+        );
+        let shaper = Shaper::new(Some(&gsub), None, Some(&gdef));
+        let shaped = shaper.shape(&[F, I, A], &selection(), |_| 500);
+        assert_eq!(gids(&shaped), vec![FI, A]);
+        assert_eq!(shaped[1].cluster, 2);
+        // A mark between the components blocks the ligature...
+        let shaped = shaper.shape(&[F, ACUTE, I], &selection(), |_| 500);
+        assert_eq!(gids(&shaped), vec![F, ACUTE, I]);
+
+        // ...unless the lookup ignores marks, when it follows the ligature
+        let gsub = table(gsub.lookups, vec![1]);
+        let shaper = Shaper::new(Some(&gsub), None, Some(&gdef));
+        let shaped = shaper.shape(&[F, ACUTE, I], &selection(), |_| 500);
+        assert_eq!(gids(&shaped), vec![FI, ACUTE]);
+        assert_eq!(shaped[1].cluster, 1);
+
+        // Without GDEF, nothing is known to be a mark
+        let shaper = Shaper::new(Some(&gsub), None, None);
+        let shaped = shaper.shape(&[F, ACUTE, I], &selection(), |_| 500);
+        assert_eq!(gids(&shaped), vec![F, ACUTE, I]);
+    }
+
+    #[test]
+    fn test_mark_filtering() {
+        let gdef = gdef();
+        let mut single = SingleSubst::default();
+        single.mapping.insert(A, A_ALT);
+        let rule = ChainedSequenceContextRule {
+            backtrack: vec![],
+            lookahead: vec![[B].into()],
+            input: vec![([A].into(), vec![1])],
+        };
+        let context = ChainedSequenceContext { rules: vec![rule] };
+        let mut filtered = lookup(
+            LookupFlags::USE_MARK_FILTERING_SET,
+            Substitution::ChainedContextual(vec![context.clone()]),
+        );
+        filtered.mark_filtering_set = Some(0);
+        let gsub = table(
+            vec![
+                filtered,
+                lookup(LookupFlags::empty(), Substitution::Single(vec![single])),
+                // Skips marks other than those of attachment class 2
+                lookup(
+                    LookupFlags::from_bits_truncate(0x0200),
+                    Substitution::ChainedContextual(vec![context]),
+                ),
+            ],
+            vec![0],
+        );
+        let shaper = Shaper::new(Some(&gsub), None, Some(&gdef));
+        // Grave is in the filtering set, so is not skipped; acute is
+        assert_eq!(
+            gids(&shaper.shape(&[A, ACUTE, B], &selection(), |_| 0)),
+            vec![A_ALT, ACUTE, B]
+        );
+        assert_eq!(
+            gids(&shaper.shape(&[A, GRAVE, B], &selection(), |_| 0)),
+            vec![A, GRAVE, B]
+        );
+
+        let gsub = table(gsub.lookups, vec![2]);
+        let shaper = Shaper::new(Some(&gsub), None, Some(&gdef));
+        assert_eq!(
+            gids(&shaper.shape(&[A, ACUTE, B], &selection(), |_| 0)),
+            vec![A_ALT, ACUTE, B]
+        );
+        assert_eq!(
+            gids(&shaper.shape(&[A, GRAVE, B], &selection(), |_| 0)),
+            vec![A, GRAVE, B]
+        );
+    }
+
+    #[test]
+    fn test_contextual_multiple() {
+        // Expands a to "a b" when followed by i, then ligates "b i" to fi
+        let mut multiple = crate::layout::gsub2::MultipleSubst::default();
+        multiple.mapping.insert(A, vec![A, B]);
+        let mut ligature = LigatureSubst::default();
+        ligature.mapping.insert(vec![B, I], FI);
+        let context = SequenceContext {
+            rules: vec![vec![([A].into(), vec![1]), ([I].into(), vec![])]],
+        };
+        let gsub = table(
+            vec![
+                lookup(
+                    LookupFlags::empty(),
+                    Substitution::Contextual(vec![context]),
+                ),
+                lookup(LookupFlags::empty(), Substitution::Multiple(vec![multiple])),
+                lookup(LookupFlags::empty(), Substitution::Ligature(vec![ligature])),
+            ],
+            vec![0, 2],
+        );
+        let shaper = Shaper::new(Some(&gsub), None, None);
+        let shaped = shaper.shape(&[A, I, A], &selection(), |_| 0);
+        assert_eq!(gids(&shaped), vec![A, FI, A]);
+        let clusters: Vec<usize> = shaped.iter().map(|g| g.cluster).collect();
+        assert_eq!(clusters, vec![0, 0, 2]);
+    }
+
+    #[test]
+    fn test_reverse_chain() {
+        let gsub = table(
+            vec![lookup(
+                LookupFlags::empty(),
+                Substitution::ReverseChainContextual(vec![ReverseChainSubst {
+                    mapping: [(A, A_ALT)].into_iter().collect(),
+                    backtrack: vec![],
+                    lookahead: vec![[A_ALT, B].into()],
+                }]),
+            )],
+            vec![0],
+        );
+        // Each substitution feeds the one before it
+        let shaper = Shaper::new(Some(&gsub), None, None);
+        assert_eq!(
+            gids(&shaper.shape(&[A, A, A, B], &selection(), |_| 0)),
+            vec![A_ALT, A_ALT, A_ALT, B]
+        );
+    }
+
+    #[test]
+    fn test_pair_and_marks() {
+        let gdef = gdef();
+        let mut kerning = PairPos::default();
+        kerning.mapping.insert(
+            (A, B),
+            (
+                ValueRecord {
+                    xAdvance: Some(-50),
+                    ..ValueRecord::new()
+                },
+                ValueRecord::new(),
+            ),
+        );
+        let mut mark_base = MarkBasePos::default();
+        mark_base
+            .bases
+            .insert(A, [(0, Anchor::new(250, 600))].into_iter().collect());
+        mark_base.marks.insert(ACUTE, (0, Anchor::new(50, 0)));
+        let mut mark_mark = MarkMarkPos::default();
+        mark_mark
+            .base_marks
+            .insert(ACUTE, [(0, Anchor::new(50, 200))].into_iter().collect());
+        mark_mark
+            .combining_marks
+            .insert(GRAVE, (0, Anchor::new(50, 0)));
+        let gpos = table(
+            vec![
+                lookup(LookupFlags::IGNORE_MARKS, Positioning::Pair(vec![kerning])),
+                lookup(
+                    LookupFlags::empty(),
+                    Positioning::MarkToBase(vec![mark_base]),
+                ),
+                lookup(
+                    LookupFlags::empty(),
+                    Positioning::MarkToMark(vec![mark_mark]),
+                ),
+            ],
+            vec![0, 1, 2],
+        );
+        let shaper = Shaper::new(None, Some(&gpos), Some(&gdef));
+        let advance = |gid| {
+            if gid == ACUTE || gid == GRAVE {
+                100
+            } else {
+                500
+            }
+        };
+        let shaped = shaper.shape(&[A, ACUTE, GRAVE, B], &selection(), advance);
+        let positions: Vec<(i32, i32, i32)> = shaped
+            .iter()
+            .map(|g| (g.x_advance, g.x_offset, g.y_offset))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                // Kerned against b, skipping the marks
+                (450, 0, 0),
+                // The acute is 450 units along from a's origin
+                (100, 200 - 450, 600),
+                // The grave sits on the acute, 100 units further along
+                (100, 200 - 550, 800),
+                (500, 0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ligature_marks_and_cursive() {
+        let gdef = gdef();
+        let gsub = table(
+            vec![lookup(
+                LookupFlags::IGNORE_MARKS,
+                Substitution::Ligature(vec![ligatures()]),
+            )],
+            vec![0],
+        );
+        let mut mark_lig = MarkLigPos::default();
+        mark_lig.ligatures.insert(
+            FI,
+            vec![
+                [(0, Anchor::new(100, 700))].into_iter().collect(),
+                [(0, Anchor::new(400, 700))].into_iter().collect(),
+            ],
+        );
+        mark_lig.marks.insert(ACUTE, (0, Anchor::new(0, 0)));
+        let mut cursive = CursivePos::default();
+        cursive
+            .mapping
+            .insert(A, (Some(Anchor::new(0, 0)), Some(Anchor::new(400, 100))));
+        let gpos = table(
+            vec![
+                lookup(LookupFlags::empty(), Positioning::MarkToLig(vec![mark_lig])),
+                lookup(LookupFlags::empty(), Positioning::Cursive(vec![cursive])),
+            ],
+            vec![0, 1],
+        );
+        let shaper = Shaper::new(Some(&gsub), Some(&gpos), Some(&gdef));
+        let advance = |gid| if gid == ACUTE { 0 } else { 500 };
+        // The first acute follows the f, the second the i
+        let shaped = shaper.shape(&[F, ACUTE, I, ACUTE], &selection(), advance);
+        assert_eq!(gids(&shaped), vec![FI, ACUTE, ACUTE]);
+        let offsets: Vec<(i32, i32)> = shaped.iter().map(|g| (g.x_offset, g.y_offset)).collect();
+        assert_eq!(offsets, vec![(0, 0), (100 - 500, 700), (400 - 500, 700)]);
+
+        // Each a joins the next, rising as it goes
+        let shaped = shaper.shape(&[A, A, A], &selection(), advance);
+        let positions: Vec<(i32, i32)> = shaped.iter().map(|g| (g.x_advance, g.y_offset)).collect();
+        assert_eq!(positions, vec![(400, 0), (400, 100), (500, 200)]);
+    }
+}
@@ -36,15 +36,39 @@
 //! the [font] module as the entry point to creating, parsing and
 //! saving an OpenType font.
 
+/// Automatic TrueType hinting
+pub mod autohint;
+/// CFF and CFF2 common structures
+pub mod cff;
+/// Decompiling layout tables to feature files
+pub mod fea;
 /// The main font object. Start here.
 pub mod font;
+/// TrueType hinting
+pub mod hinting;
+/// TrueType instructions
+pub mod instructions;
 /// OpenType Layout common tables
 pub mod layout;
 /// OpenType Variations common tables
 pub mod otvar;
+/// Text proofing
+pub mod proof;
+/// Glyph rasterization
+pub mod raster;
+#[cfg(feature = "serde")]
+mod serde_map;
+/// Font subsetting
+pub mod subset;
 pub mod table_store;
 /// OpenType table definitions.
 pub mod tables;
+/// TTX XML import and export
+pub mod ttx;
+/// Family-wide vertical metrics
+pub mod vmetrics;
+/// WOFF2 compression
+pub mod woff2;
 
 pub use otspec::types;
 pub use otspec_macros::tag;
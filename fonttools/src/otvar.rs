@@ -18,7 +18,7 @@ mod tuplevariationstore;
 pub mod instancer;
 
 pub use itemvariationstore::{ItemVariationData, ItemVariationStore, RegionAxisCoordinates};
-pub use locations::{support_scalar, Location, NormalizedLocation, VariationModel};
+pub use locations::{support_scalar, Location, NormalizedLocation, Support, VariationModel};
 use otspec::types::int16;
 pub use packeddeltas::PackedDeltas;
 pub use packedpoints::PackedPoints;
@@ -27,6 +27,7 @@ pub use tuplevariationstore::{TupleVariation, TupleVariationStore};
 
 /// Represents either a two-dimensional (`gvar`) or one-dimensional (`cvt`) delta value
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Delta {
     /// A one-dimensional delta (used in the `cvt` table)
     Delta1D(int16),
@@ -5,7 +5,7 @@ use super::support_scalar;
 use crate::font::Font;
 use crate::tables::avar::{self, SegmentMap};
 use crate::tables::gvar::{self, Coords, DeltaSet, GlyphVariationData};
-use crate::tables::{fvar, glyf};
+use crate::tables::{cvar, fvar, glyf};
 use crate::tag;
 use crate::types::*;
 
@@ -119,7 +119,7 @@ fn instantiate_gvar_data(
         new_variations = pin_tuple_variation_axes(&mut new_variations, &pinned, axis_tags)
     }
     if !axis_ranges.is_empty() {
-        new_variations = limit_tuple_variation_axis_ranges(new_variations, axis_ranges)
+        new_variations = limit_tuple_variation_axis_ranges(new_variations, axis_ranges, axis_tags)
     }
     let mut merged_variations: BTreeMap<Vec<(Tag, F2DOT14, F2DOT14, F2DOT14)>, DeltaSet> =
         BTreeMap::new();
@@ -128,7 +128,9 @@ fn instantiate_gvar_data(
         let mut tent = vec![];
         for (ix, ax) in axis_tags.iter().enumerate() {
             let peak = deltaset.peak.get(ix).expect("Where'd my axis go?");
-            if pinned.contains_key(ax) {
+            // Regions which no longer depend on any axis apply everywhere,
+            // and so are merged into the default
+            if pinned.contains_key(ax) || *peak == 0.0 {
                 continue;
             }
             let start = deltaset.start.get(ix).expect("Where'd my axis go?");
@@ -143,8 +145,12 @@ fn instantiate_gvar_data(
         merged_variations.insert(tent, new_var);
     }
 
-    println!("Merged variations: {:?}", merged_variations);
-    // XXX - wait - axis_tags has the old set of axes...
+    log::debug!("Merged variations: {:?}", merged_variations);
+    for deltaset in merged_variations.values_mut() {
+        deltaset.start = unpinned(&deltaset.start, axis_tags, &pinned);
+        deltaset.peak = unpinned(&deltaset.peak, axis_tags, &pinned);
+        deltaset.end = unpinned(&deltaset.end, axis_tags, &pinned);
+    }
     let default_tent: Vec<(Tag, F2DOT14, F2DOT14, F2DOT14)> = vec![];
     let default_var = merged_variations.remove(&default_tent);
 
@@ -169,7 +175,7 @@ fn pin_tuple_variation_axes(
 ) -> GlyphVariationData {
     let mut new_deltas: Vec<gvar::DeltaSet> = vec![];
     for var in variations.deltasets.iter_mut() {
-        println!("Deltaset : {:?}", var);
+        log::debug!("Deltaset : {:?}", var);
 
         // Deltaset is a set of tuples using the font's existing axes
         let mut support = BTreeMap::new();
@@ -179,28 +185,125 @@ fn pin_tuple_variation_axes(
                 .position(|t| t == tag)
                 .expect("Axis in location wasn't in font");
             let support_for_this_axis = (var.start[index], var.peak[index], var.end[index]);
-            println!("Support for {}: {:?}", tag, support_for_this_axis);
+            log::debug!("Support for {}: {:?}", tag, support_for_this_axis);
             support.insert(*tag, support_for_this_axis);
         }
         let scalar = support_scalar(location, &support);
-        println!("Support scalar for {:?}: {:?}", location, scalar);
+        log::debug!("Support scalar for {:?}: {:?}", location, scalar);
         if scalar == 0.0 {
             continue;
         }
         var.scale_deltas(scalar);
         new_deltas.push(var.clone());
     }
-    println!("Pinned deltas: {:?}", new_deltas);
+    log::debug!("Pinned deltas: {:?}", new_deltas);
     GlyphVariationData {
         deltasets: new_deltas,
     }
 }
 
 fn limit_tuple_variation_axis_ranges(
-    _tvs: GlyphVariationData,
-    _axis_ranges: PartialNormalizedAxisLimits,
+    tvs: GlyphVariationData,
+    axis_ranges: PartialNormalizedAxisLimits,
+    axis_tags: &[Tag],
 ) -> GlyphVariationData {
-    unimplemented!()
+    let mut new_deltas: Vec<gvar::DeltaSet> = vec![];
+    for var in tvs.deltasets {
+        let region = (var.start.clone(), var.peak.clone(), var.end.clone());
+        for (scalar, (start, peak, end)) in limit_region(&region, &axis_ranges, axis_tags) {
+            let mut new_var = var.clone();
+            new_var.scale_deltas(scalar);
+            new_var.start = start;
+            new_var.peak = peak;
+            new_var.end = end;
+            new_deltas.push(new_var);
+        }
+    }
+    GlyphVariationData {
+        deltasets: new_deltas,
+    }
+}
+
+/// The start, peak and end of a region of the design space
+type Region = (Tuple, Tuple, Tuple);
+
+/// Re-expresses a tent on one axis after the axis is limited to a range
+/// containing the default, and renormalized so that the range spans from
+/// -1 to 1. Returns the tents which replace it, with the scalars to apply
+/// to their deltas.
+fn limit_tent(tent: (f32, f32, f32), minimum: f32, maximum: f32) -> Vec<(f32, (f32, f32, f32))> {
+    let (start, peak, end) = tent;
+    if peak == 0.0 {
+        return vec![(1.0, tent)];
+    }
+    if peak < 0.0 {
+        // Work on the positive side and mirror the result
+        return limit_tent((-end, -peak, -start), -maximum, -minimum)
+            .into_iter()
+            .map(|(scalar, (s, p, e))| (scalar, (-e, -p, -s)))
+            .collect();
+    }
+    let limit = maximum;
+    if start >= limit {
+        // The tent lies entirely outside the new range
+        return vec![];
+    }
+    if peak > limit {
+        // Keep the rising edge up to the limit, which becomes the new peak
+        let scalar = (limit - start) / (peak - start);
+        return vec![(scalar, (start / limit, 1.0, 1.0))];
+    }
+    let mut tents = vec![(1.0, (start / limit, peak / limit, (end / limit).min(1.0)))];
+    if end > limit && peak < limit {
+        // The falling edge is cut off above zero, so add a tent which
+        // carries the remaining value up to the new maximum
+        let scalar = (end - limit) / (end - peak);
+        tents.push((scalar, (peak / limit, 1.0, 1.0)));
+    }
+    tents
+}
+
+/// Limits a region to the given axis ranges, returning the regions which
+/// replace it, with the scalars to apply to their deltas
+fn limit_region(
+    region: &Region,
+    axis_ranges: &PartialNormalizedAxisLimits,
+    axis_tags: &[Tag],
+) -> Vec<(f32, Region)> {
+    let mut regions = vec![(1.0, region.clone())];
+    for (&tag, &(minimum, maximum)) in axis_ranges {
+        let index = axis_tags
+            .iter()
+            .position(|t| *t == tag)
+            .expect("Axis in limits wasn't in font");
+        regions = regions
+            .into_iter()
+            .flat_map(|(scalar, (start, peak, end))| {
+                let tent = (start[index], peak[index], end[index]);
+                limit_tent(tent, minimum, maximum).into_iter().map(
+                    move |(tent_scalar, (s, p, e))| {
+                        let (mut start, mut peak, mut end) =
+                            (start.clone(), peak.clone(), end.clone());
+                        start[index] = s;
+                        peak[index] = p;
+                        end[index] = e;
+                        (scalar * tent_scalar, (start, peak, end))
+                    },
+                )
+            })
+            .collect();
+    }
+    regions
+}
+
+/// Removes the pinned axes from a tuple
+fn unpinned(tuple: &[f32], axis_tags: &[Tag], pinned: &FullNormalizedAxisLimits) -> Tuple {
+    tuple
+        .iter()
+        .zip(axis_tags.iter())
+        .filter(|(_, tag)| !pinned.contains_key(*tag))
+        .map(|(&value, _)| value)
+        .collect()
 }
 
 fn sanity_check(font: &Font) {
@@ -220,11 +323,11 @@ fn instantiate_gvar_glyph(
     axis_limits: &NormalizedAxisLimits,
 ) {
     let glyph = glyf.glyphs.get_mut(ix).unwrap();
-    println!("Handling glyph {:?}", ix);
+    log::debug!("Handling glyph {:?}", ix);
 
     if let Some(var) = gvar.variations.get_mut(ix).unwrap() {
         let mut deltas = instantiate_gvar_data(var, axis_tags, axis_limits).into_iter();
-        println!("New deltas: {:?}", deltas);
+        log::debug!("New deltas: {:?}", deltas);
         for contour in glyph.contours.iter_mut() {
             for point in contour.iter_mut() {
                 let delta = deltas.next().expect("Not enough deltas for glyph");
@@ -267,6 +370,104 @@ fn instantiate_gvar(font: &mut Font, axis_limits: &NormalizedAxisLimits) {
     font.tables.insert(glyf);
 }
 
+fn instantiate_cvar(font: &mut Font, axis_limits: &NormalizedAxisLimits) {
+    log::info!("Instantiating cvar/cvt table");
+    let axis_tags: Vec<Tag> = font
+        .tables
+        .fvar()
+        .unwrap()
+        .unwrap()
+        .axes
+        .iter()
+        .map(|x| x.axisTag)
+        .collect();
+    let (pinned, axis_ranges): (FullNormalizedAxisLimits, PartialNormalizedAxisLimits) =
+        axis_limits.split_up();
+    let mut cvar = font.tables.cvar().unwrap().unwrap();
+    let mut cvt = font.tables.cvt().unwrap().unwrap();
+
+    // Pin and limit each region, keeping unrounded deltas until the regions
+    // have been merged
+    let mut scaled: Vec<(Region, Vec<f32>)> = vec![];
+    for deltaset in &cvar.deltasets {
+        let support = pinned
+            .keys()
+            .map(|tag| {
+                let index = axis_tags
+                    .iter()
+                    .position(|t| t == tag)
+                    .expect("Axis in location wasn't in font");
+                (
+                    *tag,
+                    (
+                        deltaset.start[index],
+                        deltaset.peak[index],
+                        deltaset.end[index],
+                    ),
+                )
+            })
+            .collect();
+        let scalar = support_scalar(&pinned, &support);
+        if scalar == 0.0 {
+            continue;
+        }
+        let region = (
+            deltaset.start.clone(),
+            deltaset.peak.clone(),
+            deltaset.end.clone(),
+        );
+        for (limit_scalar, region) in limit_region(&region, &axis_ranges, &axis_tags) {
+            let factor = scalar * limit_scalar;
+            let deltas = deltaset.deltas.iter().map(|&d| d as f32 * factor).collect();
+            scaled.push((region, deltas));
+        }
+    }
+
+    // Merge the regions which coincide once the pinned axes are removed;
+    // those which no longer depend on any axis are applied to the cvt
+    let mut default = vec![0.0; cvt.0.len()];
+    let mut merged: Vec<(Region, Vec<f32>)> = vec![];
+    for ((start, peak, end), deltas) in scaled {
+        let region = (
+            unpinned(&start, &axis_tags, &pinned),
+            unpinned(&peak, &axis_tags, &pinned),
+            unpinned(&end, &axis_tags, &pinned),
+        );
+        let target = if region.1.iter().all(|&p| p == 0.0) {
+            &mut default
+        } else if let Some(index) = merged.iter().position(|(r, _)| *r == region) {
+            &mut merged[index].1
+        } else {
+            merged.push((region, vec![0.0; deltas.len()]));
+            &mut merged.last_mut().unwrap().1
+        };
+        for (total, delta) in target.iter_mut().zip(deltas) {
+            *total += delta;
+        }
+    }
+    for (value, delta) in cvt.0.iter_mut().zip(default) {
+        *value += delta.round() as i16;
+    }
+    font.tables.insert(cvt);
+
+    cvar.deltasets = merged
+        .into_iter()
+        .map(|((start, peak, end), deltas)| cvar::DeltaSet {
+            peak,
+            start,
+            end,
+            deltas: deltas.iter().map(|d| d.round() as i16).collect(),
+        })
+        .filter(|deltaset| deltaset.deltas.iter().any(|&d| d != 0))
+        .collect();
+    if cvar.deltasets.is_empty() {
+        log::info!("Dropping cvar table");
+        font.tables.remove(cvar::TAG);
+    } else {
+        font.tables.insert(cvar);
+    }
+}
+
 fn instantiate_avar(font: &mut Font, axis_limits: &UserAxisLimits) {
     let (location, _axis_ranges): (FullUserAxisLimits, PartialUserAxisLimits) =
         axis_limits.split_up();
@@ -615,7 +816,7 @@ pub fn instantiate_variable_font(font: &mut Font, limits: UserAxisLimits) -> boo
         instantiate_gvar(font, &normalized_limits);
     }
     if font.tables.contains(b"cvar") {
-        // instantiate_cvar(font, normalized_limits);
+        instantiate_cvar(font, &normalized_limits);
     }
     if font.tables.contains(b"MVAR") {
         // instantiate_MVAR(font, normalized_limits);
@@ -645,3 +846,95 @@ pub fn instantiate_variable_font(font: &mut Font, limits: UserAxisLimits) -> boo
     // set_default_weight_width_slant(font, full);
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::cvt::cvt;
+
+    #[test]
+    fn test_limit_tent() {
+        // A tent which peaks beyond the new maximum keeps its rising edge
+        assert_eq!(
+            limit_tent((0.0, 1.0, 1.0), -1.0, 0.5),
+            vec![(0.5, (0.0, 1.0, 1.0))]
+        );
+        // One which falls beyond it gains a tent up to the new maximum
+        assert_eq!(
+            limit_tent((0.0, 0.25, 1.0), -1.0, 0.5),
+            vec![(1.0, (0.0, 0.5, 1.0)), (2.0 / 3.0, (0.5, 1.0, 1.0))]
+        );
+        assert_eq!(limit_tent((0.6, 0.8, 1.0), -1.0, 0.5), vec![]);
+        assert_eq!(
+            limit_tent((-1.0, -1.0, 0.0), -0.5, 1.0),
+            vec![(0.5, (-1.0, -1.0, 0.0))]
+        );
+        assert_eq!(
+            limit_tent((-1.0, 0.0, 0.0), -0.5, 0.5),
+            vec![(1.0, (-1.0, 0.0, 0.0))]
+        );
+    }
+
+    fn cvar_font() -> Font {
+        let mut font = Font::new(crate::font::SfntVersion::TrueType);
+        font.tables.insert(fvar::fvar {
+            axes: vec![fvar::VariationAxisRecord {
+                axisTag: tag!("wght"),
+                minValue: 400.0,
+                defaultValue: 400.0,
+                maxValue: 700.0,
+                flags: 0,
+                axisNameID: 256,
+            }],
+            instances: vec![],
+        });
+        font.tables.insert(cvt(vec![100, 200]));
+        font.tables.insert(cvar::cvar {
+            deltasets: vec![cvar::DeltaSet {
+                peak: vec![1.0],
+                start: vec![0.0],
+                end: vec![1.0],
+                deltas: vec![50, -20],
+            }],
+        });
+        font
+    }
+
+    #[test]
+    fn test_instantiate_cvar() {
+        let mut font = cvar_font();
+        let limits = NormalizedAxisLimits(
+            vec![(tag!("wght"), NormalizedAxisLimit::Full(0.5))]
+                .into_iter()
+                .collect(),
+        );
+        instantiate_cvar(&mut font, &limits);
+        assert_eq!(font.tables.cvt().unwrap().unwrap().0, vec![125, 190]);
+        assert!(!font.tables.contains(&cvar::TAG));
+
+        let mut font = cvar_font();
+        let limits = NormalizedAxisLimits(
+            vec![(
+                tag!("wght"),
+                NormalizedAxisLimit::Partial(NormalizedAxisRange {
+                    minimum: 0.0,
+                    maximum: 0.5,
+                }),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        instantiate_cvar(&mut font, &limits);
+        assert_eq!(font.tables.cvt().unwrap().unwrap().0, vec![100, 200]);
+        let cvar = font.tables.cvar().unwrap().unwrap();
+        assert_eq!(
+            cvar.deltasets,
+            vec![cvar::DeltaSet {
+                peak: vec![1.0],
+                start: vec![0.0],
+                end: vec![1.0],
+                deltas: vec![25, -10],
+            }]
+        );
+    }
+}
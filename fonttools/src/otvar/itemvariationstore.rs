@@ -22,6 +22,7 @@ tables!(
 );
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents variation data inside an item variation store
 pub struct ItemVariationData {
     /// Indices into the IVS's region array.
@@ -80,6 +81,7 @@ impl Serialize for ItemVariationData {
 }
 #[allow(non_snake_case, non_camel_case_types)]
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A set of regions used in a variation
 pub struct VariationRegionList {
     /// The number of variation axes for this font. This must be the same number as axisCount in the 'fvar' table.
@@ -117,6 +119,7 @@ impl Serialize for VariationRegionList {
 
 #[allow(non_snake_case, non_camel_case_types)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// An item variation store, collecting a set of variation data for scalar values.
 pub struct ItemVariationStore {
     /// Format - set to 1
@@ -13,6 +13,7 @@ use otspec_macros::{Deserialize, Serialize};
 bitflags! {
     /// Flags used internally to a tuple variation header
     #[derive(Serialize, Deserialize)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct TupleIndexFlags: u16 {
         /// This header contains its own peak tuple (rather than a shared tuple)
         const EMBEDDED_PEAK_TUPLE = 0x8000;
@@ -30,6 +31,7 @@ bitflags! {
 /// Used to locate a set of deltas within the design space.
 #[allow(non_snake_case, non_camel_case_types)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TupleVariationHeader {
     /// Size in bytes of the serialized data (the data *after* the header/tuples
     // including the private points but *not* including the shared points)
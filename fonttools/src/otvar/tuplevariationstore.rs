@@ -16,6 +16,7 @@ use std::collections::VecDeque;
 /// TupleVariationHeader (which serves to locate the deltas in the design space)
 /// and an optimized set of deltas, some of which may be omitted due to IUP.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TupleVariation(pub TupleVariationHeader, pub Vec<Option<Delta>>);
 
 impl TupleVariation {
@@ -65,6 +66,7 @@ impl TupleVariation {
 /// A tuple variation store is the way that OpenType internally represents
 /// variation records in the `gvar` and `cvt` tables.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TupleVariationStore(pub Vec<TupleVariation>);
 
 impl TupleVariationStore {
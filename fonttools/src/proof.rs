@@ -0,0 +1,537 @@
+//! Text proofing
+//!
+//! Renders a line of text with a font's TrueType outlines, so that builds
+//! can be proofed without external tools. Text is mapped to glyphs through
+//! the `cmap` table, shaped with the selected features by the
+//! [`Shaper`](crate::layout::shaping::Shaper), and laid out along the
+//! baseline with `hmtx` advances (varied by `gvar`, if a location is given)
+//! before being rendered to a [`Bitmap`] or to SVG.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::proof::{render, ProofOptions};
+//!
+//! let font = Font::load("Test.ttf").expect("Could not load font");
+//! let options = ProofOptions {
+//!     ppem: 72.0,
+//!     ..Default::default()
+//! };
+//! let bitmap = render(&font, "Hamburgefonstiv", &options).expect("Could not render");
+//! std::fs::write("proof.png", bitmap.inverted().to_png()).unwrap();
+//! ```
+use crate::font::Font;
+use crate::layout::shaping::{FeatureSelection, Shaper};
+use crate::raster::{contour_segments, Bitmap, OutlinePoint, RasterError, Rasterizer, Segment};
+use crate::table_store::CowPtr;
+use crate::tables;
+use crate::tables::glyf::glyf;
+use crate::tables::gvar::gvar;
+use crate::tables::hmtx::hmtx;
+use crate::tag;
+use otspec::types::Tag;
+use otspec::DeserializationError;
+use std::fmt::Write;
+
+/// Glyph proof sheets
+pub mod sheet;
+
+/// The features applied by default
+pub const DEFAULT_FEATURES: [Tag; 6] = [
+    tag!("ccmp"),
+    tag!("locl"),
+    tag!("rlig"),
+    tag!("liga"),
+    tag!("clig"),
+    tag!("kern"),
+];
+
+/// An error produced while proofing text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// A required table was not present in the font
+    MissingTable(Tag),
+    /// A table could not be read
+    Table(String),
+    /// A glyph could not be rasterized
+    Raster(RasterError),
+}
+
+impl std::fmt::Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofError::MissingTable(tag) => write!(f, "missing {} table", tag),
+            ProofError::Table(e) => write!(f, "could not read table: {}", e),
+            ProofError::Raster(e) => write!(f, "could not rasterize glyph: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+impl From<DeserializationError> for ProofError {
+    fn from(e: DeserializationError) -> Self {
+        ProofError::Table(e.0)
+    }
+}
+
+impl From<RasterError> for ProofError {
+    fn from(e: RasterError) -> Self {
+        ProofError::Raster(e)
+    }
+}
+
+/// Options for shaping and rendering a line of text
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofOptions {
+    /// The size in pixels per em
+    pub ppem: f32,
+    /// A location in normalized coordinates, one per axis in `fvar` order
+    pub coords: Vec<f32>,
+    /// The script whose features are applied. If the font has no such
+    /// script, the `DFLT` script is used instead.
+    pub script: Tag,
+    /// The language system whose features are applied, or the script's
+    /// default language system if `None` or not present in the font
+    pub language: Option<Tag>,
+    /// The features to apply
+    pub features: Vec<Tag>,
+}
+
+impl Default for ProofOptions {
+    fn default() -> Self {
+        ProofOptions {
+            ppem: 48.0,
+            coords: vec![],
+            script: tag!("DFLT"),
+            language: None,
+            features: DEFAULT_FEATURES.to_vec(),
+        }
+    }
+}
+
+/// A glyph laid out on a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    /// The glyph ID
+    pub gid: u16,
+    /// The horizontal position of the glyph's origin in font units, relative
+    /// to the start of the line
+    pub x: f32,
+    /// The vertical position of the glyph's origin in font units, relative
+    /// to the baseline
+    pub y: f32,
+    /// The advance width in font units
+    pub advance: f32,
+}
+
+/// The contours of one glyph, in font units
+type GlyphContours = Vec<Vec<OutlinePoint>>;
+
+/// The tables needed to lay out and render text
+struct ProofTables {
+    glyf: CowPtr<glyf>,
+    gvar: Option<CowPtr<gvar>>,
+    hmtx: CowPtr<hmtx>,
+    units_per_em: u16,
+    ascender: i16,
+    descender: i16,
+}
+
+impl ProofTables {
+    fn load(font: &Font) -> Result<Self, ProofError> {
+        let tables = &font.tables;
+        let head = tables
+            .head()?
+            .ok_or(ProofError::MissingTable(tables::head::TAG))?;
+        let hhea = tables
+            .hhea()?
+            .ok_or(ProofError::MissingTable(tables::hhea::TAG))?;
+        Ok(ProofTables {
+            glyf: tables
+                .glyf()?
+                .ok_or(ProofError::MissingTable(tables::glyf::TAG))?,
+            gvar: tables.gvar()?,
+            hmtx: tables
+                .hmtx()?
+                .ok_or(ProofError::MissingTable(tables::hmtx::TAG))?,
+            units_per_em: head.unitsPerEm,
+            ascender: hhea.ascender,
+            descender: hhea.descender,
+        })
+    }
+
+    fn rasterizer(&self, coords: &[f32]) -> Rasterizer<'_> {
+        let rasterizer = Rasterizer::new(&self.glyf, self.units_per_em);
+        match &self.gvar {
+            Some(gvar) if !coords.is_empty() => rasterizer.with_variations(gvar, coords),
+            _ => rasterizer,
+        }
+    }
+}
+
+fn shape_with(
+    font: &Font,
+    tables: &ProofTables,
+    text: &str,
+    options: &ProofOptions,
+) -> Result<Vec<PositionedGlyph>, ProofError> {
+    let cmap = font
+        .tables
+        .cmap()?
+        .ok_or(ProofError::MissingTable(tables::cmap::TAG))?;
+    let mapping = cmap.get_best_mapping();
+    let glyphs: Vec<u16> = text
+        .chars()
+        .map(|c| {
+            mapping
+                .and_then(|m| m.get(&(c as u32)).copied())
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let gsub = font.tables.GSUB()?;
+    let gpos = font.tables.GPOS()?;
+    let gdef = font.tables.GDEF()?;
+    let shaper = Shaper::new(gsub.as_deref(), gpos.as_deref(), gdef.as_deref());
+    let selection = FeatureSelection {
+        script: options.script,
+        language: options.language,
+        features: options.features.clone(),
+    };
+    let rasterizer = tables.rasterizer(&options.coords);
+    let advance = |gid: u16| {
+        let metric = tables
+            .hmtx
+            .metrics
+            .get(gid as usize)
+            .or_else(|| tables.hmtx.metrics.last());
+        let advance = metric.map_or(0, |m| m.advanceWidth) as f64;
+        (advance + rasterizer.advance_delta(gid)).round() as i32
+    };
+
+    // Turn offsets into positions along the line
+    let mut pen = 0.0;
+    Ok(shaper
+        .shape(&glyphs, &selection, advance)
+        .iter()
+        .map(|glyph| {
+            let positioned = PositionedGlyph {
+                gid: glyph.gid,
+                x: pen + glyph.x_offset as f32,
+                y: glyph.y_offset as f32,
+                advance: glyph.x_advance as f32,
+            };
+            pen += positioned.advance;
+            positioned
+        })
+        .collect())
+}
+
+/// Maps text to glyphs and lays them out along a line.
+pub fn shape(
+    font: &Font,
+    text: &str,
+    options: &ProofOptions,
+) -> Result<Vec<PositionedGlyph>, ProofError> {
+    let tables = ProofTables::load(font)?;
+    shape_with(font, &tables, text, options)
+}
+
+/// The outlines of a shaped line, one list of contours per glyph, in font
+/// units; and the line's frame, which spans its advance horizontally and
+/// the font's ascender and descender vertically.
+fn line_outlines(
+    font: &Font,
+    tables: &ProofTables,
+    text: &str,
+    options: &ProofOptions,
+) -> Result<(Vec<GlyphContours>, kurbo::Rect), ProofError> {
+    let glyphs = shape_with(font, tables, text, options)?;
+    let rasterizer = tables.rasterizer(&options.coords);
+    let mut outlines = vec![];
+    for glyph in &glyphs {
+        let mut contours = rasterizer.glyph_outline(glyph.gid)?;
+        for (x, y, _) in contours.iter_mut().flatten() {
+            *x += glyph.x as f64;
+            *y += glyph.y as f64;
+        }
+        outlines.push(contours);
+    }
+    let advance: f32 = glyphs.iter().map(|g| g.advance).sum();
+    let frame = kurbo::Rect::new(
+        0.0,
+        tables.descender as f64,
+        advance as f64,
+        tables.ascender as f64,
+    );
+    Ok((outlines, frame))
+}
+
+/// Renders a line of text into a coverage bitmap.
+///
+/// The bitmap covers the line's advance and the font's ascender and
+/// descender, as well as any ink outside them.
+pub fn render(font: &Font, text: &str, options: &ProofOptions) -> Result<Bitmap, ProofError> {
+    let tables = ProofTables::load(font)?;
+    let (outlines, frame) = line_outlines(font, &tables, text, options)?;
+    let contours: GlyphContours = outlines.into_iter().flatten().collect();
+    Ok(tables
+        .rasterizer(&options.coords)
+        .fill(&contours, options.ppem, Some(frame)))
+}
+
+/// Formats a coordinate with at most two decimal places
+fn format_number(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Renders a line of text as an SVG document, with black glyphs on a white
+/// background, one path per glyph.
+///
+/// The image covers the same area as that of [`render`], and is measured
+/// in pixels.
+pub fn render_svg(font: &Font, text: &str, options: &ProofOptions) -> Result<String, ProofError> {
+    let tables = ProofTables::load(font)?;
+    let (outlines, frame) = line_outlines(font, &tables, text, options)?;
+    let scale = options.ppem as f64 / tables.units_per_em.max(1) as f64;
+    let bounds = outlines
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(frame, |bounds, &(x, y, _)| {
+            bounds.union_pt(kurbo::Point::new(x, y))
+        });
+    let left = (bounds.min_x() * scale).floor();
+    let top = (-bounds.max_y() * scale).floor();
+    let width = (bounds.max_x() * scale).ceil() - left;
+    let height = (-bounds.min_y() * scale).ceil() - top;
+    let point = |(x, y): (f64, f64)| {
+        format!(
+            "{} {}",
+            format_number(x * scale - left),
+            format_number(-y * scale - top)
+        )
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+    for contours in outlines {
+        let mut path = String::new();
+        for (start, segments) in contours.iter().filter_map(|c| contour_segments(c)) {
+            write!(path, "M{}", point(start)).unwrap();
+            for segment in segments {
+                match segment {
+                    // Closing the path draws the final line
+                    Segment::Line(p) if p == start => {}
+                    Segment::Line(p) => write!(path, "L{}", point(p)).unwrap(),
+                    Segment::Quad(c, p) => write!(path, "Q{} {}", point(c), point(p)).unwrap(),
+                }
+            }
+            path.push('Z');
+        }
+        if !path.is_empty() {
+            writeln!(svg, "<path d=\"{}\"/>", path).unwrap();
+        }
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::layout::common::{
+        FeatureList, LanguageSystem, Lookup, LookupFlags, Script, ScriptList, ValueRecord,
+    };
+    use crate::layout::gpos2::PairPos;
+    use crate::layout::gsub4::LigatureSubst;
+    use crate::tables::glyf::{Glyph, Point};
+    use crate::tables::GPOS::Positioning;
+    use crate::tables::GSUB::Substitution;
+    use std::collections::BTreeMap;
+
+    fn glyph(contours: Vec<Vec<Point>>) -> Glyph {
+        Glyph {
+            xMin: 0,
+            xMax: 0,
+            yMin: 0,
+            yMax: 0,
+            contours,
+            instructions: vec![],
+            components: vec![],
+            overlap: false,
+        }
+    }
+
+    fn square(size: i16) -> Vec<Point> {
+        [(0, 0), (0, size), (size, size), (size, 0)]
+            .iter()
+            .map(|&(x, y)| Point {
+                x,
+                y,
+                on_curve: true,
+            })
+            .collect()
+    }
+
+    fn script_list() -> ScriptList {
+        let mut scripts = BTreeMap::new();
+        scripts.insert(
+            tag!("DFLT"),
+            Script {
+                default_language_system: Some(LanguageSystem {
+                    required_feature: None,
+                    feature_indices: vec![0],
+                }),
+                language_systems: BTreeMap::new(),
+            },
+        );
+        ScriptList { scripts }
+    }
+
+    /// A font with glyphs for f, i, an fi ligature, A and V, where A and V
+    /// are kerned
+    pub(crate) fn test_font() -> Font {
+        let mut font = Font::new(crate::font::SfntVersion::TrueType);
+        font.tables
+            .insert(tables::head::new(1.0, 1000, 0, 0, 500, 500));
+        font.tables.insert(tables::hhea::hhea {
+            majorVersion: 1,
+            minorVersion: 0,
+            ascender: 800,
+            descender: -200,
+            lineGap: 0,
+            advanceWidthMax: 500,
+            minLeftSideBearing: 0,
+            minRightSideBearing: 0,
+            xMaxExtent: 500,
+            caretSlopeRise: 1,
+            caretSlopeRun: 0,
+            caretOffset: 0,
+            reserved0: 0,
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+            metricDataFormat: 0,
+            numberOfHMetrics: 6,
+        });
+        font.tables.insert(tables::hmtx::hmtx {
+            metrics: vec![
+                tables::hmtx::Metric {
+                    advanceWidth: 500,
+                    lsb: 0,
+                };
+                6
+            ],
+        });
+        let mut glyphs = vec![glyph(vec![])];
+        glyphs.extend((1..6).map(|_| glyph(vec![square(500)])));
+        font.tables.insert(tables::glyf::glyf { glyphs });
+        let mapping = [('f', 1), ('i', 2), ('A', 4), ('V', 5)]
+            .iter()
+            .map(|&(c, gid)| (c as u32, gid))
+            .collect();
+        font.tables.insert(tables::cmap::cmap {
+            subtables: vec![tables::cmap::CmapSubtable {
+                format: 4,
+                platformID: 3,
+                encodingID: 1,
+                languageID: 0,
+                mapping,
+                uvs_mapping: None,
+            }],
+        });
+
+        let mut ligatures = LigatureSubst::default();
+        ligatures.mapping.insert(vec![1, 2], 3);
+        font.tables.insert(tables::GSUB::GSUB {
+            lookups: vec![Lookup {
+                flags: LookupFlags::empty(),
+                mark_filtering_set: None,
+                extension: false,
+                rule: Substitution::Ligature(vec![ligatures]),
+            }],
+            scripts: script_list(),
+            features: FeatureList::new(vec![(tag!("liga"), vec![0], None)]),
+        });
+        let mut kerning = PairPos::default();
+        kerning.mapping.insert(
+            (4, 5),
+            (
+                ValueRecord {
+                    xAdvance: Some(-100),
+                    ..ValueRecord::new()
+                },
+                ValueRecord::new(),
+            ),
+        );
+        font.tables.insert(tables::GPOS::GPOS {
+            lookups: vec![Lookup {
+                flags: LookupFlags::empty(),
+                mark_filtering_set: None,
+                extension: false,
+                rule: Positioning::Pair(vec![kerning]),
+            }],
+            scripts: script_list(),
+            features: FeatureList::new(vec![(tag!("kern"), vec![0], None)]),
+        });
+        font
+    }
+
+    #[test]
+    fn test_shape() {
+        let font = test_font();
+        let shaped = shape(&font, "fiAVx", &ProofOptions::default()).unwrap();
+        let glyphs: Vec<(u16, f32, f32)> = shaped.iter().map(|g| (g.gid, g.x, g.advance)).collect();
+        assert_eq!(
+            glyphs,
+            vec![
+                (3, 0.0, 500.0),
+                (4, 500.0, 400.0),
+                (5, 900.0, 500.0),
+                (0, 1400.0, 500.0)
+            ]
+        );
+
+        // Without features, nothing is substituted or kerned
+        let options = ProofOptions {
+            features: vec![],
+            ..Default::default()
+        };
+        let shaped = shape(&font, "fiAV", &options).unwrap();
+        let gids: Vec<u16> = shaped.iter().map(|g| g.gid).collect();
+        assert_eq!(gids, vec![1, 2, 4, 5]);
+        assert_eq!(shaped[3].x, 1500.0);
+    }
+
+    #[test]
+    fn test_render() {
+        let font = test_font();
+        let options = ProofOptions {
+            ppem: 10.0,
+            ..Default::default()
+        };
+        let bitmap = render(&font, "AV", &options).unwrap();
+        // The frame spans the advance and the ascender and descender
+        assert_eq!((bitmap.width, bitmap.height), (9, 10));
+        assert_eq!((bitmap.left, bitmap.top), (0, 8));
+        // The kerned glyphs overlap in the fifth column
+        let row: Vec<u8> = (0..9).map(|x| bitmap.get(x, 5)).collect();
+        assert_eq!(row, vec![255, 255, 255, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(bitmap.get(0, 2), 0);
+
+        let svg = render_svg(&font, "AV", &options).unwrap();
+        assert!(svg.contains("width=\"9\" height=\"10\""));
+        assert!(svg.contains("<path d=\"M0 8L0 3L5 3L5 8Z\"/>"));
+        assert!(svg.contains("<path d=\"M4 8L4 3L9 3L9 8Z\"/>"));
+    }
+}
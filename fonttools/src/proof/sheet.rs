@@ -0,0 +1,868 @@
+//! Glyph proof sheets
+//!
+//! Draws every glyph of a font into a grid, one cell per glyph, labelled
+//! with its name, glyph ID, the codepoints which map to it and its advance
+//! width. Each cell shows the glyph's origin, advance and baseline, and can
+//! also show its outline points and off-curve handles. A sheet can cover
+//! several locations of a variable font, one section per location, and can
+//! be written as a single SVG image or as a PDF document with a page per
+//! `rows_per_page` rows.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::proof::sheet::{glyph_sheet_pdf, instance_locations, SheetOptions};
+//!
+//! let font = Font::load("Test-VF.ttf").expect("Could not load font");
+//! let options = SheetOptions {
+//!     locations: instance_locations(&font).expect("Could not read instances"),
+//!     ..Default::default()
+//! };
+//! let pdf = glyph_sheet_pdf(&font, &options).expect("Could not draw sheet");
+//! std::fs::write("glyphs.pdf", pdf).unwrap();
+//! ```
+use super::{format_number, GlyphContours, ProofError, ProofTables};
+use crate::font::Font;
+use crate::raster::{contour_segments, Segment};
+use crate::tables::avar::avar;
+use crate::tables::fvar::fvar;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// The margin around each page, in points
+const MARGIN: f64 = 24.0;
+/// The height of the heading above each section, in points
+const HEADING: f64 = 28.0;
+/// The size of section headings, in points
+const HEADING_SIZE: f64 = 12.0;
+/// The space between a cell's edge and its contents, in points
+const PADDING: f64 = 3.0;
+/// The number of label lines beneath each glyph
+const LABEL_LINES: usize = 3;
+/// The radius of the dots marking outline points, in points
+const POINT_RADIUS: f64 = 1.2;
+
+/// A location at which to draw a font's glyphs
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetLocation {
+    /// The name shown in the heading of the location's section
+    pub name: String,
+    /// The location in normalized coordinates, one per axis in `fvar` order
+    pub coords: Vec<f32>,
+}
+
+/// Options for drawing a glyph proof sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetOptions {
+    /// The width and height of each glyph's cell, in points
+    pub cell_size: f64,
+    /// The number of cells in each row
+    pub columns: usize,
+    /// The number of rows on each page of a PDF
+    pub rows_per_page: usize,
+    /// The locations to draw the glyphs at. If empty, the glyphs are drawn
+    /// once, at the default location.
+    pub locations: Vec<SheetLocation>,
+    /// Whether to mark outline points and off-curve handles
+    pub show_points: bool,
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        SheetOptions {
+            cell_size: 72.0,
+            columns: 8,
+            rows_per_page: 10,
+            locations: vec![],
+            show_points: false,
+        }
+    }
+}
+
+/// Returns the named instances of a variable font as sheet locations,
+/// named after their subfamily names. Fonts without an `fvar` table have no
+/// instances.
+pub fn instance_locations(font: &Font) -> Result<Vec<SheetLocation>, ProofError> {
+    let fvar = match font.tables.fvar()? {
+        Some(fvar) => fvar,
+        None => return Ok(vec![]),
+    };
+    let avar = font.tables.avar()?;
+    let name = font.tables.name()?;
+    Ok(fvar
+        .instances
+        .iter()
+        .enumerate()
+        .map(|(index, instance)| {
+            let records = name.as_ref().map_or(&[][..], |name| &name.records[..]);
+            let subfamily = records
+                .iter()
+                .filter(|record| record.nameID == instance.subfamilyNameID)
+                .max_by_key(|record| record.platformID == 3);
+            SheetLocation {
+                name: subfamily.map_or_else(
+                    || format!("Instance {}", index + 1),
+                    |record| record.string.clone(),
+                ),
+                coords: normalize_location(&fvar, avar.as_deref(), &instance.coordinates),
+            }
+        })
+        .collect())
+}
+
+/// Converts a location in user coordinates to normalized coordinates,
+/// applying the `avar` mappings if there are any.
+fn normalize_location(fvar: &fvar, avar: Option<&avar>, location: &[f32]) -> Vec<f32> {
+    fvar.axes
+        .iter()
+        .zip(location)
+        .enumerate()
+        .map(|(index, (axis, &value))| {
+            let (min, default, max) = (axis.minValue, axis.defaultValue, axis.maxValue);
+            let value = value.clamp(min.min(default), max.max(default));
+            let normalized = if value < default {
+                (value - default) / (default - min)
+            } else if value > default {
+                (value - default) / (max - default)
+            } else {
+                0.0
+            };
+            match avar.and_then(|avar| avar.maps.get(index)) {
+                Some(map) if map.is_valid() => map.piecewise_linear_map(normalized),
+                _ => normalized,
+            }
+        })
+        .collect()
+}
+
+/// A glyph ready to be drawn into a cell
+struct GlyphCell {
+    gid: u16,
+    name: String,
+    codepoints: Vec<u32>,
+    advance: f64,
+    contours: GlyphContours,
+}
+
+/// The glyphs of a font at one location
+struct Section {
+    title: String,
+    cells: Vec<GlyphCell>,
+}
+
+/// A drawing operation, in points with the origin at the bottom left of
+/// the page
+enum Mark {
+    /// A glyph outline, filled with the nonzero rule
+    Outline { contours: GlyphContours, gray: f64 },
+    /// A straight line
+    Line {
+        from: (f64, f64),
+        to: (f64, f64),
+        width: f64,
+        gray: f64,
+    },
+    /// A rectangle outline, from its bottom left corner
+    Rect {
+        origin: (f64, f64),
+        size: (f64, f64),
+        gray: f64,
+    },
+    /// A dot, filled or hollow
+    Dot { center: (f64, f64), filled: bool },
+    /// A line of text, from the left end of its baseline
+    Text {
+        origin: (f64, f64),
+        size: f64,
+        text: String,
+    },
+}
+
+struct Page {
+    width: f64,
+    height: f64,
+    marks: Vec<Mark>,
+}
+
+fn sections(
+    font: &Font,
+    tables: &ProofTables,
+    options: &SheetOptions,
+) -> Result<Vec<Section>, ProofError> {
+    let glyph_names = font
+        .tables
+        .post()?
+        .and_then(|post| post.glyphnames.clone())
+        .unwrap_or_default();
+    let codepoints = font
+        .tables
+        .cmap()?
+        .map(|cmap| cmap.reversed())
+        .unwrap_or_default();
+    let default_location = [SheetLocation {
+        name: "Default".to_string(),
+        coords: vec![],
+    }];
+    let locations = if options.locations.is_empty() {
+        &default_location[..]
+    } else {
+        &options.locations[..]
+    };
+
+    let mut sections = vec![];
+    for location in locations {
+        let rasterizer = tables.rasterizer(&location.coords);
+        let mut cells = vec![];
+        for gid in 0..tables.glyf.glyphs.len() as u16 {
+            let contours = rasterizer.glyph_outline(gid).unwrap_or_else(|e| {
+                log::warn!("Could not draw glyph {}: {}", gid, e);
+                vec![]
+            });
+            let metric = tables
+                .hmtx
+                .metrics
+                .get(gid as usize)
+                .or_else(|| tables.hmtx.metrics.last());
+            let advance = metric.map_or(0, |m| m.advanceWidth) as f64;
+            let mut codepoints: Vec<u32> = codepoints
+                .get(&gid)
+                .map(|set| set.iter().copied().collect())
+                .unwrap_or_default();
+            codepoints.sort_unstable();
+            cells.push(GlyphCell {
+                gid,
+                name: glyph_names.get(gid as usize).cloned().unwrap_or_default(),
+                codepoints,
+                advance: advance + rasterizer.advance_delta(gid),
+                contours,
+            });
+        }
+        sections.push(Section {
+            title: location.name.clone(),
+            cells,
+        });
+    }
+    Ok(sections)
+}
+
+/// Shortens text to roughly fit a width, assuming an average character
+/// width of half the text size
+fn fit_text(text: &str, size: f64, width: f64) -> String {
+    let limit = (width / (size * 0.5)).max(1.0) as usize;
+    if text.chars().count() <= limit {
+        text.to_string()
+    } else {
+        let mut short: String = text.chars().take(limit.saturating_sub(3)).collect();
+        short.push_str("...");
+        short
+    }
+}
+
+/// Draws a glyph and its labels into the cell with the given bottom left
+/// corner.
+fn draw_cell(
+    marks: &mut Vec<Mark>,
+    cell: &GlyphCell,
+    origin: (f64, f64),
+    tables: &ProofTables,
+    options: &SheetOptions,
+) {
+    let size = options.cell_size;
+    let (left, bottom) = origin;
+    marks.push(Mark::Rect {
+        origin,
+        size: (size, size),
+        gray: 0.8,
+    });
+
+    // Labels, from the top line down
+    let label_size = size / 12.0;
+    let label_height = LABEL_LINES as f64 * label_size * 1.2;
+    let codepoints: Vec<String> = cell
+        .codepoints
+        .iter()
+        .map(|c| format!("U+{:04X}", c))
+        .collect();
+    let labels = [
+        cell.name.clone(),
+        codepoints.join(" "),
+        format!("GID {}, advance {}", cell.gid, cell.advance.round()),
+    ];
+    for (line, label) in labels.iter().enumerate().filter(|(_, l)| !l.is_empty()) {
+        marks.push(Mark::Text {
+            origin: (
+                left + PADDING,
+                bottom + PADDING + label_height - (line + 1) as f64 * label_size * 1.2
+                    + label_size * 0.25,
+            ),
+            size: label_size,
+            text: fit_text(label, label_size, size - 2.0 * PADDING),
+        });
+    }
+
+    // Fit the font's ascender and descender into the rest of the cell
+    let extent = match (tables.ascender - tables.descender) as f64 {
+        extent if extent > 0.0 => extent,
+        _ => tables.units_per_em as f64,
+    };
+    let scale = (size - label_height - 3.0 * PADDING) / extent;
+    let x0 = left + (size - cell.advance * scale) / 2.0;
+    let baseline = bottom + label_height + 2.0 * PADDING - tables.descender as f64 * scale;
+    let point = |x: f64, y: f64| (x0 + x * scale, baseline + y * scale);
+    let (descender, ascender) = (tables.descender as f64, tables.ascender as f64);
+    for x in [0.0, cell.advance] {
+        marks.push(Mark::Line {
+            from: point(x, descender),
+            to: point(x, ascender),
+            width: 0.25,
+            gray: 0.6,
+        });
+    }
+    marks.push(Mark::Line {
+        from: point(0.0, 0.0),
+        to: point(cell.advance, 0.0),
+        width: 0.25,
+        gray: 0.6,
+    });
+
+    let contours: GlyphContours = cell
+        .contours
+        .iter()
+        .map(|contour| {
+            contour
+                .iter()
+                .map(|&(x, y, on_curve)| {
+                    let (x, y) = point(x, y);
+                    (x, y, on_curve)
+                })
+                .collect()
+        })
+        .collect();
+    if !options.show_points {
+        marks.push(Mark::Outline {
+            contours,
+            gray: 0.0,
+        });
+        return;
+    }
+    let mut points = vec![];
+    for contour in &contours {
+        // Handles join each off-curve point to its neighbours
+        for (index, &(x, y, on_curve)) in contour.iter().enumerate() {
+            let (next_x, next_y, next_on_curve) = contour[(index + 1) % contour.len()];
+            if !(on_curve && next_on_curve) {
+                points.push(Mark::Line {
+                    from: (x, y),
+                    to: (next_x, next_y),
+                    width: 0.25,
+                    gray: 0.3,
+                });
+            }
+        }
+        points.extend(contour.iter().map(|&(x, y, on_curve)| Mark::Dot {
+            center: (x, y),
+            filled: on_curve,
+        }));
+    }
+    marks.push(Mark::Outline {
+        contours,
+        gray: 0.75,
+    });
+    marks.extend(points);
+}
+
+/// Lays out the sections into pages. If `rows_per_page` is given, each
+/// section starts a new page; otherwise everything is drawn onto one page.
+fn layout(
+    sections: &[Section],
+    tables: &ProofTables,
+    options: &SheetOptions,
+    rows_per_page: Option<usize>,
+) -> Vec<Page> {
+    let columns = options.columns.max(1);
+    let size = options.cell_size;
+    let width = 2.0 * MARGIN + columns as f64 * size;
+    let rows = |cells: usize| cells.div_ceil(columns);
+
+    // Each chunk of cells is drawn under a heading
+    let chunks: Vec<(String, &[GlyphCell])> = sections
+        .iter()
+        .flat_map(|section| {
+            let per_chunk = rows_per_page.map_or(section.cells.len(), |r| r.max(1) * columns);
+            let chunks: Vec<&[GlyphCell]> = section.cells.chunks(per_chunk.max(1)).collect();
+            let count = chunks.len();
+            chunks.into_iter().enumerate().map(move |(index, chunk)| {
+                let title = if count > 1 {
+                    format!("{} ({}/{})", section.title, index + 1, count)
+                } else {
+                    section.title.clone()
+                };
+                (title, chunk)
+            })
+        })
+        .collect();
+    let chunk_height = |cells: &[GlyphCell]| {
+        HEADING + rows_per_page.map_or(rows(cells.len()), |r| r.max(1)) as f64 * size
+    };
+
+    let mut pages: Vec<Page> = vec![];
+    let mut top = 0.0;
+    for (index, (title, cells)) in chunks.iter().enumerate() {
+        if index == 0 || rows_per_page.is_some() {
+            let height = if rows_per_page.is_some() {
+                2.0 * MARGIN + chunk_height(cells)
+            } else {
+                2.0 * MARGIN + chunks.iter().map(|(_, c)| chunk_height(c)).sum::<f64>()
+            };
+            pages.push(Page {
+                width,
+                height,
+                marks: vec![],
+            });
+            top = height - MARGIN;
+        }
+        let page = pages.last_mut().unwrap();
+        page.marks.push(Mark::Text {
+            origin: (MARGIN, top - HEADING_SIZE - PADDING),
+            size: HEADING_SIZE,
+            text: title.clone(),
+        });
+        top -= HEADING;
+        for (position, cell) in cells.iter().enumerate() {
+            let (row, column) = (position / columns, position % columns);
+            let origin = (MARGIN + column as f64 * size, top - (row + 1) as f64 * size);
+            draw_cell(&mut page.marks, cell, origin, tables, options);
+        }
+        top -= rows(cells.len()) as f64 * size;
+    }
+    pages
+}
+
+fn pages(font: &Font, options: &SheetOptions, paginate: bool) -> Result<Vec<Page>, ProofError> {
+    let tables = ProofTables::load(font)?;
+    let sections = sections(font, &tables, options)?;
+    let rows_per_page = paginate.then_some(options.rows_per_page);
+    Ok(layout(&sections, &tables, options, rows_per_page))
+}
+
+fn svg_gray(gray: f64) -> String {
+    let level = (gray.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{0:02x}{0:02x}{0:02x}", level)
+}
+
+fn svg_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draws a proof sheet of all glyphs as an SVG image.
+pub fn glyph_sheet_svg(font: &Font, options: &SheetOptions) -> Result<String, ProofError> {
+    let page = match pages(font, options, false)?.pop() {
+        Some(page) => page,
+        None => {
+            return Ok(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"0\" height=\"0\"/>\n"
+                    .to_string(),
+            )
+        }
+    };
+    let point =
+        |(x, y): (f64, f64)| format!("{} {}", format_number(x), format_number(page.height - y));
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        format_number(page.width),
+        format_number(page.height)
+    );
+    svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+    for mark in &page.marks {
+        match mark {
+            Mark::Outline { contours, gray } => {
+                let mut path = String::new();
+                for (start, segments) in contours.iter().filter_map(|c| contour_segments(c)) {
+                    write!(path, "M{}", point(start)).unwrap();
+                    for segment in segments {
+                        match segment {
+                            Segment::Line(p) if p == start => {}
+                            Segment::Line(p) => write!(path, "L{}", point(p)).unwrap(),
+                            Segment::Quad(c, p) => {
+                                write!(path, "Q{} {}", point(c), point(p)).unwrap()
+                            }
+                        }
+                    }
+                    path.push('Z');
+                }
+                if !path.is_empty() {
+                    writeln!(svg, "<path d=\"{}\" fill=\"{}\"/>", path, svg_gray(*gray)).unwrap();
+                }
+            }
+            Mark::Line {
+                from,
+                to,
+                width,
+                gray,
+            } => writeln!(
+                svg,
+                "<path d=\"M{}L{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+                point(*from),
+                point(*to),
+                svg_gray(*gray),
+                format_number(*width)
+            )
+            .unwrap(),
+            Mark::Rect { origin, size, gray } => writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"0.5\"/>",
+                format_number(origin.0),
+                format_number(page.height - origin.1 - size.1),
+                format_number(size.0),
+                format_number(size.1),
+                svg_gray(*gray)
+            )
+            .unwrap(),
+            Mark::Dot { center, filled } => writeln!(
+                svg,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"black\" stroke-width=\"0.25\"/>",
+                format_number(center.0),
+                format_number(page.height - center.1),
+                format_number(POINT_RADIUS),
+                if *filled { "black" } else { "white" }
+            )
+            .unwrap(),
+            Mark::Text { origin, size, text } => writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\">{}</text>",
+                format_number(origin.0),
+                format_number(page.height - origin.1),
+                format_number(*size),
+                svg_escape(text)
+            )
+            .unwrap(),
+        }
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Escapes text for a PDF string in the standard encoding, replacing
+/// characters it cannot represent
+fn pdf_string(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            ' '..='~' => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// The content stream drawing a page
+fn pdf_content(page: &Page) -> String {
+    let n = format_number;
+    let mut content = String::new();
+    for mark in &page.marks {
+        match mark {
+            Mark::Outline { contours, gray } => {
+                writeln!(content, "{} g", n(*gray)).unwrap();
+                for (start, segments) in contours.iter().filter_map(|c| contour_segments(c)) {
+                    writeln!(content, "{} {} m", n(start.0), n(start.1)).unwrap();
+                    let mut current = start;
+                    for segment in segments {
+                        match segment {
+                            Segment::Line(p) => {
+                                writeln!(content, "{} {} l", n(p.0), n(p.1)).unwrap()
+                            }
+                            // PDF has no quadratic curves, so raise them to
+                            // cubic ones
+                            Segment::Quad(c, p) => {
+                                let c1 = (
+                                    current.0 + 2.0 / 3.0 * (c.0 - current.0),
+                                    current.1 + 2.0 / 3.0 * (c.1 - current.1),
+                                );
+                                let c2 =
+                                    (p.0 + 2.0 / 3.0 * (c.0 - p.0), p.1 + 2.0 / 3.0 * (c.1 - p.1));
+                                writeln!(
+                                    content,
+                                    "{} {} {} {} {} {} c",
+                                    n(c1.0),
+                                    n(c1.1),
+                                    n(c2.0),
+                                    n(c2.1),
+                                    n(p.0),
+                                    n(p.1)
+                                )
+                                .unwrap();
+                            }
+                        }
+                        current = match segment {
+                            Segment::Line(p) | Segment::Quad(_, p) => p,
+                        };
+                    }
+                    content.push_str("h\n");
+                }
+                content.push_str("f\n");
+            }
+            Mark::Line {
+                from,
+                to,
+                width,
+                gray,
+            } => writeln!(
+                content,
+                "{} w {} G {} {} m {} {} l S",
+                n(*width),
+                n(*gray),
+                n(from.0),
+                n(from.1),
+                n(to.0),
+                n(to.1)
+            )
+            .unwrap(),
+            Mark::Rect { origin, size, gray } => writeln!(
+                content,
+                "0.5 w {} G {} {} {} {} re S",
+                n(*gray),
+                n(origin.0),
+                n(origin.1),
+                n(size.0),
+                n(size.1)
+            )
+            .unwrap(),
+            Mark::Dot { center, filled } => {
+                // A circle from four cubic arcs
+                let (x, y) = *center;
+                let r = POINT_RADIUS;
+                let k = r * 0.5523;
+                write!(
+                    content,
+                    "0.25 w 0 G {} g {} {} m {} {} {} {} {} {} c {} {} {} {} {} {} c ",
+                    if *filled { "0" } else { "1" },
+                    n(x + r),
+                    n(y),
+                    n(x + r),
+                    n(y + k),
+                    n(x + k),
+                    n(y + r),
+                    n(x),
+                    n(y + r),
+                    n(x - k),
+                    n(y + r),
+                    n(x - r),
+                    n(y + k),
+                    n(x - r),
+                    n(y)
+                )
+                .unwrap();
+                writeln!(
+                    content,
+                    "{} {} {} {} {} {} c {} {} {} {} {} {} c b",
+                    n(x - r),
+                    n(y - k),
+                    n(x - k),
+                    n(y - r),
+                    n(x),
+                    n(y - r),
+                    n(x + k),
+                    n(y - r),
+                    n(x + r),
+                    n(y - k),
+                    n(x + r),
+                    n(y)
+                )
+                .unwrap();
+            }
+            Mark::Text { origin, size, text } => writeln!(
+                content,
+                "0 g BT /F1 {} Tf {} {} Td ({}) Tj ET",
+                n(*size),
+                n(origin.0),
+                n(origin.1),
+                pdf_string(text)
+            )
+            .unwrap(),
+        }
+    }
+    content
+}
+
+/// Draws a proof sheet of all glyphs as a PDF document.
+pub fn glyph_sheet_pdf(font: &Font, options: &SheetOptions) -> Result<Vec<u8>, ProofError> {
+    let pages = pages(font, options, true)?;
+
+    // The catalog, page tree and font come first, followed by each page
+    // and its content stream
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|index| format!("{} 0 R", 4 + 2 * index))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                format_number(page.width),
+                format_number(page.height),
+                5 + 2 * index
+            )
+            .into_bytes(),
+        );
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(pdf_content(page).as_bytes())
+            .expect("Writing to a vector cannot fail");
+        let stream = encoder.finish().expect("Writing to a vector cannot fail");
+        let mut object = format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            stream.len()
+        )
+        .into_bytes();
+        object.extend(stream);
+        object.extend(b"\nendstream");
+        objects.push(object);
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        writeln!(trailer, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    )
+    .unwrap();
+    pdf.extend(trailer.into_bytes());
+    Ok(pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof::tests::test_font;
+    use crate::tables;
+    use crate::tag;
+
+    #[test]
+    fn test_normalize_location() {
+        let fvar = tables::fvar::fvar {
+            axes: vec![tables::fvar::VariationAxisRecord {
+                axisTag: tag!("wght"),
+                minValue: 100.0,
+                defaultValue: 400.0,
+                maxValue: 900.0,
+                flags: 0,
+                axisNameID: 256,
+            }],
+            instances: vec![],
+        };
+        let location = |value| normalize_location(&fvar, None, &[value]);
+        assert_eq!(location(100.0), vec![-1.0]);
+        assert_eq!(location(250.0), vec![-0.5]);
+        assert_eq!(location(400.0), vec![0.0]);
+        assert_eq!(location(650.0), vec![0.5]);
+        assert_eq!(location(1000.0), vec![1.0]);
+
+        let avar = tables::avar::avar {
+            maps: vec![tables::avar::SegmentMap::new(vec![
+                (-1.0, -1.0),
+                (0.0, 0.0),
+                (0.5, 0.25),
+                (1.0, 1.0),
+            ])],
+        };
+        assert_eq!(normalize_location(&fvar, Some(&avar), &[650.0]), vec![0.25]);
+    }
+
+    #[test]
+    fn test_svg_sheet() {
+        let mut font = test_font();
+        let names = [".notdef", "f", "i", "f_i", "A", "V"];
+        let names = names.iter().map(|n| n.to_string()).collect();
+        font.tables
+            .insert(tables::post::post::new(2.0, 0.0, 0, 0, false, Some(names)));
+        let options = SheetOptions {
+            columns: 4,
+            ..Default::default()
+        };
+        let svg = glyph_sheet_svg(&font, &options).unwrap();
+        // Six glyphs in two rows of four, under one heading
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"336\" height=\"220\""));
+        assert!(svg.contains(">Default</text>"));
+        assert_eq!(svg.matches("fill=\"#000000\"/>").count(), 5);
+        assert!(svg.contains(">f_i</text>"));
+        assert!(svg.contains(">U+0041</text>"));
+        assert!(svg.contains(">GID 5, advance 500</text>"));
+        assert!(!svg.contains("<circle"));
+
+        let options = SheetOptions {
+            show_points: true,
+            ..options
+        };
+        let svg = glyph_sheet_svg(&font, &options).unwrap();
+        // Four corners of each square
+        assert_eq!(svg.matches("<circle").count(), 20);
+    }
+
+    #[test]
+    fn test_pdf_sheet() {
+        let font = test_font();
+        let options = SheetOptions {
+            columns: 2,
+            rows_per_page: 2,
+            locations: vec![
+                SheetLocation {
+                    name: "Regular".to_string(),
+                    coords: vec![],
+                },
+                SheetLocation {
+                    name: "Bold".to_string(),
+                    coords: vec![1.0],
+                },
+            ],
+            ..Default::default()
+        };
+        let pdf = glyph_sheet_pdf(&font, &options).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        // Six glyphs take two pages of four cells, for each location
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Count 4 >>"));
+
+        // Every object is where the cross-reference table says it is
+        let xref = text.rfind("\nxref\n").unwrap() + 1;
+        let offsets: Vec<usize> = text[xref..]
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        assert_eq!(offsets.len(), 11);
+        for (index, &offset) in offsets.iter().enumerate() {
+            let header = format!("{} 0 obj\n", index + 1);
+            assert_eq!(&pdf[offset..offset + header.len()], header.as_bytes());
+        }
+    }
+}
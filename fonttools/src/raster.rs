@@ -0,0 +1,712 @@
+//! A scanline rasterizer for TrueType outlines
+//!
+//! The [`Rasterizer`] renders `glyf` outlines into 8-bit anti-aliased
+//! coverage [`Bitmap`]s using the nonzero winding rule, resolving components
+//! and optionally applying `gvar` variations first. It needs neither a GPU
+//! nor a graphics library, which makes it suitable for proofs and for
+//! comparing renderings in tests.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::raster::Rasterizer;
+//!
+//! let font = Font::load("Test.ttf").expect("Could not load font");
+//! let glyf = font.tables.glyf().unwrap().expect("No glyf table");
+//! let head = font.tables.head().unwrap().expect("No head table");
+//! let bitmap = Rasterizer::new(&glyf, head.unitsPerEm)
+//!     .rasterize_gid(3, 64.0)
+//!     .expect("Could not rasterize glyph");
+//! std::fs::write("glyph.png", bitmap.to_png()).unwrap();
+//! ```
+use crate::hinting::tuple_scalar;
+use crate::tables::glyf::{glyf, Glyph};
+use crate::tables::gvar::gvar;
+use std::io::Write;
+
+/// The number of sample lines per pixel row
+const SUBSAMPLES: usize = 16;
+/// How far, in pixels, a flattened curve may stray from the true curve
+const FLATNESS: f64 = 1.0 / 16.0;
+/// How deeply components may be nested before we give up
+const MAX_COMPONENT_DEPTH: u16 = 64;
+
+/// An error produced while rasterizing a glyph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RasterError {
+    /// A glyph ID was out of range
+    InvalidGlyph(u16),
+    /// Components were nested too deeply within the given glyph
+    ComponentDepthExceeded(u16),
+}
+
+impl std::fmt::Display for RasterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RasterError::InvalidGlyph(gid) => write!(f, "invalid glyph ID {}", gid),
+            RasterError::ComponentDepthExceeded(gid) => {
+                write!(f, "components of glyph {} nested too deeply", gid)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RasterError {}
+
+/// An 8-bit coverage bitmap
+///
+/// Each pixel holds how much of it is covered by ink, from 0 (none) to 255
+/// (all). Rows run from top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Bitmap {
+    /// The width in pixels
+    pub width: usize,
+    /// The height in pixels
+    pub height: usize,
+    /// The distance in pixels from the glyph origin to the left edge
+    pub left: i32,
+    /// The distance in pixels from the baseline up to the top edge
+    pub top: i32,
+    /// The coverage values, `width` per row
+    pub pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Returns the coverage at the given column and row
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Returns a copy of the bitmap with its values inverted, so that ink is
+    /// black on a white background when it is viewed as an image.
+    pub fn inverted(&self) -> Bitmap {
+        Bitmap {
+            pixels: self.pixels.iter().map(|&p| 255 - p).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Encodes the bitmap as a binary (P5) PGM image
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut out = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        out.extend(&self.pixels);
+        out
+    }
+
+    /// Encodes the bitmap as an 8-bit grayscale PNG image
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // Bit depth 8, grayscale, deflate, no filtering, no interlacing
+        header.extend([8, 0, 0, 0, 0]);
+
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+        for row in self.pixels.chunks(self.width.max(1)) {
+            // Each scanline starts with its filter type
+            encoder
+                .write_all(&[0])
+                .and_then(|_| encoder.write_all(row))
+                .expect("Could not compress to memory");
+        }
+        let data = encoder.finish().expect("Could not compress to memory");
+
+        let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+        write_png_chunk(&mut out, b"IHDR", &header);
+        write_png_chunk(&mut out, b"IDAT", &data);
+        write_png_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(chunk_type);
+    out.extend(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(chunk_type);
+    crc.update(data);
+    out.extend(crc.finalize().to_be_bytes());
+}
+
+/// A contour point in font units
+pub(crate) type OutlinePoint = (f64, f64, bool);
+
+/// A line segment in pixels, with y increasing downwards
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+}
+
+/// Renders glyphs from a `glyf` table into coverage bitmaps
+pub struct Rasterizer<'a> {
+    glyf: &'a glyf,
+    units_per_em: u16,
+    variations: Option<(&'a gvar, Vec<f32>)>,
+}
+
+impl<'a> Rasterizer<'a> {
+    /// Creates a rasterizer for the glyphs of a font with the given
+    /// units per em.
+    pub fn new(glyf: &'a glyf, units_per_em: u16) -> Self {
+        Rasterizer {
+            glyf,
+            units_per_em,
+            variations: None,
+        }
+    }
+
+    /// Applies the deltas of a `gvar` table at the given location, in
+    /// normalized coordinates in `fvar` order, to the outlines rendered.
+    pub fn with_variations(mut self, gvar: &'a gvar, coords: &[f32]) -> Self {
+        self.variations = Some((gvar, coords.to_vec()));
+        self
+    }
+
+    /// Renders a glyph at the given size in pixels per em.
+    ///
+    /// Its components are resolved from the rasterizer's `glyf` table and
+    /// varied, but as the glyph itself need not come from that table, its
+    /// own outline is not; use [`Rasterizer::rasterize_gid`] for that.
+    pub fn rasterize(&self, glyph: &Glyph, ppem: f32) -> Result<Bitmap, RasterError> {
+        let contours = self.outline(glyph, None, 0)?;
+        Ok(self.fill(&contours, ppem, None))
+    }
+
+    /// Renders the glyph with the given ID at the given size in pixels
+    /// per em.
+    pub fn rasterize_gid(&self, gid: u16, ppem: f32) -> Result<Bitmap, RasterError> {
+        let contours = self.glyph_outline(gid)?;
+        Ok(self.fill(&contours, ppem, None))
+    }
+
+    /// Returns the contours of the glyph with the given ID in font units,
+    /// with its components resolved and variations applied.
+    pub(crate) fn glyph_outline(&self, gid: u16) -> Result<Vec<Vec<OutlinePoint>>, RasterError> {
+        let glyph = self
+            .glyf
+            .glyphs
+            .get(gid as usize)
+            .ok_or(RasterError::InvalidGlyph(gid))?;
+        self.outline(glyph, Some(gid), 0)
+    }
+
+    /// Returns how much the advance width of a glyph changes at the
+    /// rasterizer's location, from the deltas of its phantom points.
+    pub(crate) fn advance_delta(&self, gid: u16) -> f64 {
+        let glyph = match self.glyf.glyphs.get(gid as usize) {
+            Some(glyph) => glyph,
+            None => return 0.0,
+        };
+        let phantom_start = if glyph.has_components() {
+            glyph.components.len()
+        } else {
+            glyph.contours.iter().map(|c| c.len()).sum()
+        };
+        let deltas = self.deltas(Some(gid)).unwrap_or_default();
+        let delta = |i: usize| deltas.get(i).map_or(0.0, |d| d.0);
+        delta(phantom_start + 1) - delta(phantom_start)
+    }
+
+    /// Returns the deltas of a glyph at the rasterizer's location, one
+    /// per point (or per component), if it varies.
+    fn deltas(&self, gid: Option<u16>) -> Option<Vec<(f64, f64)>> {
+        let (gvar, coords) = self.variations.as_ref()?;
+        let variations = gvar.variations.get(gid? as usize)?.as_ref()?;
+        let mut total: Vec<(f64, f64)> = vec![];
+        for deltaset in &variations.deltasets {
+            let scalar = tuple_scalar(coords, &deltaset.start, &deltaset.peak, &deltaset.end);
+            if scalar == 0.0 {
+                continue;
+            }
+            total.resize(total.len().max(deltaset.deltas.len()), (0.0, 0.0));
+            for (sum, delta) in total.iter_mut().zip(deltaset.deltas.iter()) {
+                sum.0 += scalar as f64 * delta.0 as f64;
+                sum.1 += scalar as f64 * delta.1 as f64;
+            }
+        }
+        Some(total)
+    }
+
+    /// Returns the contours of a glyph in font units, with its components
+    /// resolved and variations applied.
+    fn outline(
+        &self,
+        glyph: &Glyph,
+        gid: Option<u16>,
+        depth: u16,
+    ) -> Result<Vec<Vec<OutlinePoint>>, RasterError> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return Err(RasterError::ComponentDepthExceeded(gid.unwrap_or(0)));
+        }
+        let deltas = self.deltas(gid).unwrap_or_default();
+        let delta = |i: usize| deltas.get(i).copied().unwrap_or((0.0, 0.0));
+
+        if !glyph.has_components() {
+            let mut index = 0;
+            return Ok(glyph
+                .contours
+                .iter()
+                .map(|contour| {
+                    contour
+                        .iter()
+                        .map(|pt| {
+                            let (dx, dy) = delta(index);
+                            index += 1;
+                            (pt.x as f64 + dx, pt.y as f64 + dy, pt.on_curve)
+                        })
+                        .collect()
+                })
+                .collect());
+        }
+
+        let mut contours: Vec<Vec<OutlinePoint>> = vec![];
+        for (index, component) in glyph.components.iter().enumerate() {
+            let child_gid = component.glyph_index;
+            let child = self
+                .glyf
+                .glyphs
+                .get(child_gid as usize)
+                .ok_or(RasterError::InvalidGlyph(child_gid))?;
+            let mut child_contours = self.outline(child, Some(child_gid), depth + 1)?;
+            let [a, b, c, d, e, f] = component.transformation.as_coeffs();
+            for (x, y, _) in child_contours.iter_mut().flatten() {
+                let (tx, ty) = (*x, *y);
+                *x = a * tx + c * ty;
+                *y = b * tx + d * ty;
+            }
+            let (dx, dy) = if let Some((parent_point, child_point)) = component.match_points {
+                let point_at = |contours: &[Vec<OutlinePoint>], i: usize| {
+                    contours.iter().flatten().nth(i).map(|&(x, y, _)| (x, y))
+                };
+                let from = point_at(&contours, parent_point as usize);
+                let to = point_at(&child_contours, child_point as usize);
+                match (from, to) {
+                    (Some(from), Some(to)) => (from.0 - to.0, from.1 - to.1),
+                    _ => return Err(RasterError::InvalidGlyph(gid.unwrap_or(child_gid))),
+                }
+            } else {
+                let (vx, vy) = delta(index);
+                (e + vx, f + vy)
+            };
+            for (x, y, _) in child_contours.iter_mut().flatten() {
+                *x += dx;
+                *y += dy;
+            }
+            contours.extend(child_contours);
+        }
+        Ok(contours)
+    }
+
+    /// Scales and flattens contours and fills them into a bitmap. If a
+    /// frame is given, in font units, the bitmap covers it as well as the
+    /// ink.
+    pub(crate) fn fill(
+        &self,
+        contours: &[Vec<OutlinePoint>],
+        ppem: f32,
+        frame: Option<kurbo::Rect>,
+    ) -> Bitmap {
+        let scale = ppem as f64 / self.units_per_em.max(1) as f64;
+        let mut lines = vec![];
+        for contour in contours {
+            let scaled: Vec<OutlinePoint> = contour
+                .iter()
+                .map(|&(x, y, on_curve)| (x * scale, -y * scale, on_curve))
+                .collect();
+            flatten_contour(&scaled, &mut lines);
+        }
+        if lines.is_empty() && frame.is_none() {
+            return Bitmap::default();
+        }
+
+        let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+        let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+        if let Some(frame) = frame {
+            min_x = frame.min_x() * scale;
+            max_x = frame.max_x() * scale;
+            min_y = -frame.max_y() * scale;
+            max_y = -frame.min_y() * scale;
+        }
+        for line in &lines {
+            min_x = min_x.min(line.x0).min(line.x1);
+            max_x = max_x.max(line.x0).max(line.x1);
+            min_y = min_y.min(line.y0).min(line.y1);
+            max_y = max_y.max(line.y0).max(line.y1);
+        }
+        let (left, top) = (min_x.floor(), min_y.floor());
+        let width = (max_x.ceil() - left).max(1.0) as usize;
+        let height = (max_y.ceil() - top).max(1.0) as usize;
+        for line in lines.iter_mut() {
+            line.x0 -= left;
+            line.x1 -= left;
+            line.y0 -= top;
+            line.y1 -= top;
+        }
+
+        Bitmap {
+            width,
+            height,
+            left: left as i32,
+            top: -top as i32,
+            pixels: scan(&lines, width, height),
+        }
+    }
+}
+
+/// A piece of a contour, ending at the given point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Segment {
+    /// A straight line
+    Line((f64, f64)),
+    /// A quadratic curve with the given control point
+    Quad((f64, f64), (f64, f64)),
+}
+
+/// Splits a TrueType contour into lines and quadratic curves, making its
+/// implied on-curve points explicit. Returns the start point and the
+/// segments, the last of which closes the contour.
+pub(crate) fn contour_segments(contour: &[OutlinePoint]) -> Option<((f64, f64), Vec<Segment>)> {
+    let n = contour.len();
+    if n < 2 {
+        return None;
+    }
+    let point = |i: usize| (contour[i % n].0, contour[i % n].1);
+    let midpoint = |a: (f64, f64), b: (f64, f64)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    // Start from an on-curve point, or the implied one between the first
+    // two points if there are none
+    let first = contour.iter().position(|p| p.2);
+    let (start, start_index) = match first {
+        Some(i) => (point(i), i),
+        None => (midpoint(point(0), point(1)), 0),
+    };
+
+    let mut segments = vec![];
+    // Visit every other point, and then the first point again if it is not
+    // the start
+    let steps = if first.is_some() { n - 1 } else { n };
+    let mut control: Option<(f64, f64)> = None;
+    for step in 1..=steps {
+        let index = start_index + step;
+        let p = point(index);
+        match (contour[index % n].2, control) {
+            (true, None) => segments.push(Segment::Line(p)),
+            (true, Some(c)) => {
+                segments.push(Segment::Quad(c, p));
+                control = None;
+            }
+            (false, None) => control = Some(p),
+            (false, Some(c)) => {
+                segments.push(Segment::Quad(c, midpoint(c, p)));
+                control = Some(p);
+            }
+        }
+    }
+    match control {
+        Some(c) => segments.push(Segment::Quad(c, start)),
+        None => segments.push(Segment::Line(start)),
+    }
+    Some((start, segments))
+}
+
+/// Converts a TrueType contour to line segments, appending them to `lines`
+fn flatten_contour(contour: &[OutlinePoint], lines: &mut Vec<Line>) {
+    let (mut current, segments) = match contour_segments(contour) {
+        Some(segments) => segments,
+        None => return,
+    };
+    for segment in segments {
+        match segment {
+            Segment::Line(p) => {
+                push_line(lines, current, p);
+                current = p;
+            }
+            Segment::Quad(c, p) => {
+                flatten_quad(lines, current, c, p);
+                current = p;
+            }
+        }
+    }
+}
+
+fn flatten_quad(lines: &mut Vec<Line>, p0: (f64, f64), p1: (f64, f64), p2: (f64, f64)) {
+    // The deviation of the curve from a chord is bounded by an eighth of
+    // this second difference divided by the square of the segment count
+    let dd = ((p0.0 - 2.0 * p1.0 + p2.0).powi(2) + (p0.1 - 2.0 * p1.1 + p2.1).powi(2)).sqrt();
+    let segments = ((dd / (8.0 * FLATNESS)).sqrt().ceil() as usize).clamp(1, 100);
+    let mut previous = p0;
+    for i in 1..=segments {
+        let t = i as f64 / segments as f64;
+        let mt = 1.0 - t;
+        let p = (
+            mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+            mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+        );
+        push_line(lines, previous, p);
+        previous = p;
+    }
+}
+
+fn push_line(lines: &mut Vec<Line>, from: (f64, f64), to: (f64, f64)) {
+    // Horizontal lines never cross a sample line, so they can be dropped
+    if from.1 != to.1 {
+        lines.push(Line {
+            x0: from.0,
+            y0: from.1,
+            x1: to.0,
+            y1: to.1,
+        });
+    }
+}
+
+/// Computes the coverage of each pixel, by finding where each sample line
+/// crosses the outline and accumulating the spans of nonzero winding.
+fn scan(lines: &[Line], width: usize, height: usize) -> Vec<u8> {
+    // Bucket the lines by the rows they touch
+    let mut rows: Vec<Vec<usize>> = vec![vec![]; height];
+    for (index, line) in lines.iter().enumerate() {
+        let top = line.y0.min(line.y1).floor().max(0.0) as usize;
+        let bottom = (line.y0.max(line.y1).ceil() as usize).min(height);
+        for row in rows.iter_mut().take(bottom).skip(top) {
+            row.push(index);
+        }
+    }
+
+    let weight = 1.0 / SUBSAMPLES as f64;
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut accumulator = vec![0.0; width];
+    let mut crossings: Vec<(f64, i32)> = vec![];
+    for (y, row) in rows.iter().enumerate() {
+        accumulator.iter_mut().for_each(|a| *a = 0.0);
+        for sample in 0..SUBSAMPLES {
+            let sample_y = y as f64 + (sample as f64 + 0.5) * weight;
+            crossings.clear();
+            for line in row.iter().map(|&i| &lines[i]) {
+                let (y0, y1, direction) = if line.y0 < line.y1 {
+                    (line.y0, line.y1, 1)
+                } else {
+                    (line.y1, line.y0, -1)
+                };
+                if sample_y < y0 || sample_y >= y1 {
+                    continue;
+                }
+                let t = (sample_y - line.y0) / (line.y1 - line.y0);
+                crossings.push((line.x0 + t * (line.x1 - line.x0), direction));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            let mut span_start = 0.0;
+            for &(x, direction) in &crossings {
+                if winding == 0 {
+                    span_start = x;
+                }
+                winding += direction;
+                if winding == 0 {
+                    add_span(&mut accumulator, span_start, x, weight);
+                }
+            }
+        }
+        pixels.extend(
+            accumulator
+                .iter()
+                .map(|&a| (a.min(1.0) * 255.0).round() as u8),
+        );
+    }
+    pixels
+}
+
+/// Adds the horizontal coverage of a span to a row of pixels
+fn add_span(row: &mut [f64], x0: f64, x1: f64, weight: f64) {
+    let width = row.len() as f64;
+    let (x0, x1) = (x0.clamp(0.0, width), x1.clamp(0.0, width));
+    if x1 <= x0 {
+        return;
+    }
+    let (first, last) = (x0.floor() as usize, x1.floor() as usize);
+    if first == last {
+        row[first] += (x1 - x0) * weight;
+        return;
+    }
+    row[first] += (first as f64 + 1.0 - x0) * weight;
+    for pixel in row.iter_mut().take(last).skip(first + 1) {
+        *pixel += weight;
+    }
+    if last < row.len() {
+        row[last] += (x1 - last as f64) * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::glyf::{Component, ComponentFlags, Point};
+    use crate::tables::gvar::{DeltaSet, GlyphVariationData};
+
+    fn rectangle(x0: i16, y0: i16, x1: i16, y1: i16) -> Vec<Point> {
+        // Clockwise, as TrueType outer contours are
+        [(x0, y0), (x0, y1), (x1, y1), (x1, y0)]
+            .iter()
+            .map(|&(x, y)| Point {
+                x,
+                y,
+                on_curve: true,
+            })
+            .collect()
+    }
+
+    fn glyph(contours: Vec<Vec<Point>>, components: Vec<Component>) -> Glyph {
+        Glyph {
+            xMin: 0,
+            xMax: 0,
+            yMin: 0,
+            yMax: 0,
+            contours,
+            instructions: vec![],
+            components,
+            overlap: false,
+        }
+    }
+
+    fn rows(bitmap: &Bitmap) -> Vec<Vec<u8>> {
+        bitmap
+            .pixels
+            .chunks(bitmap.width)
+            .map(|r| r.to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_rectangle() {
+        let glyf = glyf {
+            glyphs: vec![glyph(vec![rectangle(100, -100, 300, 200)], vec![])],
+        };
+        // 100 units per pixel
+        let bitmap = Rasterizer::new(&glyf, 1000).rasterize_gid(0, 10.0).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (2, 3));
+        assert_eq!((bitmap.left, bitmap.top), (1, 2));
+        assert!(bitmap.pixels.iter().all(|&p| p == 255));
+
+        // Half a pixel off the grid
+        let glyf = glyf {
+            glyphs: vec![glyph(vec![rectangle(50, 0, 150, 100)], vec![])],
+        };
+        let bitmap = Rasterizer::new(&glyf, 1000).rasterize_gid(0, 10.0).unwrap();
+        assert_eq!(rows(&bitmap), vec![vec![128, 128]]);
+    }
+
+    #[test]
+    fn test_winding() {
+        let mut hole = rectangle(100, 100, 200, 200);
+        hole.reverse();
+        let glyf = glyf {
+            glyphs: vec![
+                // Overlapping contours in the same direction stay filled
+                glyph(
+                    vec![rectangle(0, 0, 200, 100), rectangle(100, 0, 300, 100)],
+                    vec![],
+                ),
+                // A contour in the opposite direction cuts a hole
+                glyph(vec![rectangle(0, 0, 300, 300), hole], vec![]),
+            ],
+        };
+        let rasterizer = Rasterizer::new(&glyf, 1000);
+        let bitmap = rasterizer.rasterize_gid(0, 10.0).unwrap();
+        assert_eq!(rows(&bitmap), vec![vec![255, 255, 255]]);
+        let bitmap = rasterizer.rasterize_gid(1, 10.0).unwrap();
+        assert_eq!(
+            rows(&bitmap),
+            vec![vec![255, 255, 255], vec![255, 0, 255], vec![255, 255, 255]]
+        );
+    }
+
+    #[test]
+    fn test_curve() {
+        // A circle of radius 4 pixels drawn with off-curve points only
+        let circle: Vec<Point> = [(400, 400), (400, -400), (-400, -400), (-400, 400)]
+            .iter()
+            .map(|&(x, y)| Point {
+                x,
+                y,
+                on_curve: false,
+            })
+            .collect();
+        let glyf = glyf {
+            glyphs: vec![glyph(vec![circle], vec![])],
+        };
+        let bitmap = Rasterizer::new(&glyf, 1000).rasterize_gid(0, 10.0).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (8, 8));
+        // Solid in the middle, partially covered at the corners
+        assert_eq!(bitmap.get(4, 4), 255);
+        assert!(bitmap.get(0, 0) < 128);
+        let ink: f64 = bitmap.pixels.iter().map(|&p| p as f64 / 255.0).sum();
+        // The curve is a squircle somewhere between the circle and the square
+        assert!(ink > std::f64::consts::PI * 16.0 && ink < 64.0);
+    }
+
+    #[test]
+    fn test_components_and_variations() {
+        let component = Component {
+            glyph_index: 0,
+            transformation: kurbo::Affine::new([1.0, 0.0, 0.0, 1.0, 200.0, 0.0]),
+            match_points: None,
+            flags: ComponentFlags::empty(),
+        };
+        let glyf = glyf {
+            glyphs: vec![
+                glyph(vec![rectangle(0, 0, 100, 100)], vec![]),
+                glyph(vec![], vec![component]),
+            ],
+        };
+        let rasterizer = Rasterizer::new(&glyf, 1000);
+        let bitmap = rasterizer.rasterize_gid(1, 10.0).unwrap();
+        assert_eq!((bitmap.left, bitmap.top, bitmap.width), (2, 1, 1));
+
+        // Move the component's offset at the peak of a single axis
+        let gvar = gvar {
+            variations: vec![
+                None,
+                Some(GlyphVariationData {
+                    deltasets: vec![DeltaSet {
+                        peak: vec![1.0],
+                        start: vec![0.0],
+                        end: vec![1.0],
+                        deltas: vec![(100, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+                    }],
+                }),
+            ],
+        };
+        let varied = Rasterizer::new(&glyf, 1000).with_variations(&gvar, &[1.0]);
+        let bitmap = varied.rasterize_gid(1, 10.0).unwrap();
+        assert_eq!((bitmap.left, bitmap.width), (3, 1));
+        let bitmap = Rasterizer::new(&glyf, 1000)
+            .with_variations(&gvar, &[0.5])
+            .rasterize_gid(1, 10.0)
+            .unwrap();
+        assert_eq!((bitmap.left, bitmap.width), (2, 2));
+        assert_eq!(rows(&bitmap), vec![vec![128, 128]]);
+    }
+
+    #[test]
+    fn test_image_formats() {
+        let bitmap = Bitmap {
+            width: 2,
+            height: 1,
+            left: 0,
+            top: 0,
+            pixels: vec![0, 255],
+        };
+        assert_eq!(bitmap.to_pgm(), b"P5\n2 1\n255\n\x00\xff".to_vec());
+        assert_eq!(bitmap.inverted().pixels, vec![255, 0]);
+        let png = bitmap.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR: length 13, then the dimensions
+        assert_eq!(&png[8..16], b"\x00\x00\x00\x0dIHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(
+            &png[png.len() - 12..],
+            b"\x00\x00\x00\x00IEND\xae\x42\x60\x82"
+        );
+    }
+}
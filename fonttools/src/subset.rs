@@ -0,0 +1,704 @@
+//! Font subsetting
+//!
+//! [`subset`] reduces a font to the glyphs needed to render a set of
+//! characters, in the manner of `pyftsubset`. Glyphs may be requested by
+//! codepoint, by glyph name or by glyph ID; the request is then extended
+//! to its closure, which adds the glyphs which `GSUB` substitutions can
+//! produce from the requested glyphs, the variants and assembly parts of
+//! `MATH` constructions, the glyphs selected by Unicode variation
+//! sequences, and the components of composite glyphs.
+//!
+//! The remaining glyphs are renumbered (unless glyph IDs are retained),
+//! and the `glyf`, `hmtx`, `gvar`, `cmap`, `post`, `GDEF`, `GSUB`, `GPOS`,
+//! `MATH` and `maxp` tables are rewritten to refer to the new glyph IDs.
+//! Layout lookups and features which no longer apply to any glyph are
+//! removed. Other tables which refer to glyph IDs cannot yet be subset,
+//! and are dropped.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::subset::{subset, SubsetOptions};
+//!
+//! let mut font = Font::load("Test.ttf").expect("Could not load font");
+//! let options = SubsetOptions {
+//!     unicodes: "Hello world".chars().map(|c| c as u32).collect(),
+//!     drop_hinting: true,
+//!     ..Default::default()
+//! };
+//! subset(&mut font, &options).expect("Could not subset font");
+//! font.save("Test-subset.ttf").expect("Could not save font");
+//! ```
+mod closure;
+mod layout;
+
+use crate::font::Font;
+use crate::tables;
+use crate::tables::cmap::cmap;
+use crate::tables::glyf::{glyf, Glyph};
+use crate::tables::gvar::gvar;
+use crate::tables::hmtx::{hmtx, Metric};
+use crate::tables::maxp::MaxpVariant;
+use crate::tables::name::name;
+use crate::tables::MATH::{MathGlyphConstruction, MATH};
+use crate::tag;
+use otspec::types::*;
+use otspec::DeserializationError;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Tables which only contain hinting information
+const HINTING_TABLES: [Tag; 6] = [
+    tag!("cvt "),
+    tag!("cvar"),
+    tag!("fpgm"),
+    tag!("prep"),
+    tag!("hdmx"),
+    tag!("VDMX"),
+];
+
+/// Tables which refer to glyph IDs but which cannot yet be subset. They are
+/// dropped, rather than left referring to the wrong glyphs.
+const UNSUBSETTABLE_TABLES: [Tag; 19] = [
+    tag!("hdmx"),
+    tag!("LTSH"),
+    tag!("kern"),
+    tag!("vhea"),
+    tag!("vmtx"),
+    tag!("VORG"),
+    tag!("HVAR"),
+    tag!("VVAR"),
+    tag!("COLR"),
+    tag!("SVG "),
+    tag!("sbix"),
+    tag!("CBDT"),
+    tag!("CBLC"),
+    tag!("EBDT"),
+    tag!("EBLC"),
+    tag!("EBSC"),
+    tag!("JSTF"),
+    tag!("morx"),
+    tag!("kerx"),
+];
+
+/// Options controlling which glyphs are retained, and what else is kept
+#[derive(Debug, Clone)]
+pub struct SubsetOptions {
+    /// Codepoints whose glyphs are retained
+    pub unicodes: BTreeSet<u32>,
+    /// Names of glyphs to retain, as found in the `post` table
+    pub glyph_names: Vec<String>,
+    /// IDs of glyphs to retain
+    pub gids: BTreeSet<GlyphID>,
+    /// Whether to retain the glyphs which `GSUB` substitutions can produce
+    /// from the retained glyphs
+    pub layout_closure: bool,
+    /// The layout features to keep, or `None` to keep all features
+    pub layout_features: Option<BTreeSet<Tag>>,
+    /// Whether to keep glyphs at their original IDs, leaving empty glyphs
+    /// in place of those which are removed
+    pub retain_gids: bool,
+    /// Whether to remove TrueType instructions and hinting tables
+    pub drop_hinting: bool,
+    /// The name IDs to keep, or `None` to keep all name records
+    pub name_ids: Option<BTreeSet<u16>>,
+    /// The language IDs of Windows name records to keep, or `None` to keep
+    /// all languages
+    pub name_languages: Option<BTreeSet<u16>>,
+}
+
+impl Default for SubsetOptions {
+    fn default() -> Self {
+        SubsetOptions {
+            unicodes: BTreeSet::new(),
+            glyph_names: vec![],
+            gids: BTreeSet::new(),
+            layout_closure: true,
+            layout_features: None,
+            retain_gids: false,
+            drop_hinting: false,
+            name_ids: None,
+            name_languages: None,
+        }
+    }
+}
+
+/// An error raised while subsetting a font
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubsetError {
+    /// A table needed for subsetting was missing
+    MissingTable(Tag),
+    /// A table could not be read
+    Table(String),
+    /// A requested glyph name was not found in the font
+    UnknownGlyphName(String),
+    /// The font's outlines are in a format which cannot be subset
+    UnsupportedTable(Tag),
+}
+
+impl std::fmt::Display for SubsetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubsetError::MissingTable(tag) => write!(f, "missing {} table", tag),
+            SubsetError::Table(e) => write!(f, "could not read table: {}", e),
+            SubsetError::UnknownGlyphName(name) => write!(f, "no glyph named {}", name),
+            SubsetError::UnsupportedTable(tag) => write!(f, "cannot subset {} table", tag),
+        }
+    }
+}
+
+impl std::error::Error for SubsetError {}
+
+impl From<DeserializationError> for SubsetError {
+    fn from(e: DeserializationError) -> Self {
+        SubsetError::Table(e.0)
+    }
+}
+
+/// The glyphs retained by a subsetting operation, and their new IDs
+pub(crate) struct Plan {
+    /// A mapping from retained glyph IDs to new glyph IDs
+    mapping: BTreeMap<GlyphID, GlyphID>,
+    /// The original ID of each glyph in the subset font, or `None` for an
+    /// empty glyph left in place of a removed one
+    old_gids: Vec<Option<GlyphID>>,
+    /// The requested codepoints which are mapped in the font
+    unicodes: BTreeSet<u32>,
+    /// The glyphs requested by name or ID
+    requested: BTreeSet<GlyphID>,
+}
+
+impl Plan {
+    fn new(
+        glyphs: &BTreeSet<GlyphID>,
+        unicodes: BTreeSet<u32>,
+        requested: BTreeSet<GlyphID>,
+        retain_gids: bool,
+    ) -> Self {
+        let (mapping, old_gids) = if retain_gids {
+            let count = glyphs.iter().max().map_or(0, |&max| max as usize + 1);
+            let old_gids = (0..count as GlyphID)
+                .map(|gid| glyphs.get(&gid).copied())
+                .collect();
+            (glyphs.iter().map(|&gid| (gid, gid)).collect(), old_gids)
+        } else {
+            let mapping = glyphs
+                .iter()
+                .enumerate()
+                .map(|(new, &old)| (old, new as GlyphID))
+                .collect();
+            (mapping, glyphs.iter().map(|&gid| Some(gid)).collect())
+        };
+        Plan {
+            mapping,
+            old_gids,
+            unicodes,
+            requested,
+        }
+    }
+
+    /// The new ID of a glyph, or `None` if it has been removed
+    pub(crate) fn map(&self, gid: GlyphID) -> Option<GlyphID> {
+        self.mapping.get(&gid).copied()
+    }
+
+    /// The new IDs of the retained glyphs in a set
+    pub(crate) fn map_set(&self, glyphs: &BTreeSet<GlyphID>) -> BTreeSet<GlyphID> {
+        glyphs.iter().filter_map(|&gid| self.map(gid)).collect()
+    }
+
+    /// Renumbers the keys of a glyph-indexed map, dropping removed glyphs
+    pub(crate) fn map_keys<V: Clone>(&self, map: &BTreeMap<GlyphID, V>) -> BTreeMap<GlyphID, V> {
+        map.iter()
+            .filter_map(|(&gid, value)| Some((self.map(gid)?, value.clone())))
+            .collect()
+    }
+}
+
+/// Reduces a font to the glyphs selected by the options, and their closure
+///
+/// Returns the mapping from the original IDs of the retained glyphs to
+/// their IDs in the subset font. Fonts with CFF outlines are not supported.
+pub fn subset(
+    font: &mut Font,
+    options: &SubsetOptions,
+) -> Result<BTreeMap<GlyphID, GlyphID>, SubsetError> {
+    for tag in [tables::CFF::TAG, tables::CFF2::TAG] {
+        if font.tables.contains(&tag) {
+            return Err(SubsetError::UnsupportedTable(tag));
+        }
+    }
+    // Read everything first: glyf, hmtx, gvar and the layout tables are all
+    // parsed with the help of other tables, which are about to be replaced.
+    let mut maxp = font
+        .tables
+        .maxp()?
+        .ok_or(SubsetError::MissingTable(tables::maxp::TAG))?;
+    let glyf = font.tables.glyf()?;
+    let hmtx = font.tables.hmtx()?;
+    let gvar = font.tables.gvar()?;
+    let cmap = font.tables.cmap()?;
+    let post = font.tables.post()?;
+    let gdef = font.tables.GDEF()?;
+    let gsub = font.tables.GSUB()?;
+    let gpos = font.tables.GPOS()?;
+    let math = font.tables.MATH()?;
+    let num_glyphs = maxp.num_glyphs();
+
+    let mut requested: BTreeSet<GlyphID> = BTreeSet::new();
+    for &gid in &options.gids {
+        if gid < num_glyphs {
+            requested.insert(gid);
+        } else {
+            log::warn!("Ignoring glyph ID {}, which is not in the font", gid);
+        }
+    }
+    if !options.glyph_names.is_empty() {
+        let names = post
+            .as_ref()
+            .and_then(|post| post.glyphnames.as_ref())
+            .ok_or(SubsetError::MissingTable(tables::post::TAG))?;
+        for glyph_name in &options.glyph_names {
+            let gid = names
+                .iter()
+                .position(|n| n == glyph_name)
+                .ok_or_else(|| SubsetError::UnknownGlyphName(glyph_name.clone()))?;
+            requested.insert(gid as GlyphID);
+        }
+    }
+
+    // .notdef is always kept
+    let mut glyphs: BTreeSet<GlyphID> = std::iter::once(0).chain(requested.clone()).collect();
+    let mut unicodes = BTreeSet::new();
+    if let Some(cmap) = &cmap {
+        for subtable in cmap.subtables.iter().filter(|st| st.is_unicode()) {
+            for codepoint in &options.unicodes {
+                if let Some(&gid) = subtable.mapping.get(codepoint) {
+                    unicodes.insert(*codepoint);
+                    glyphs.insert(gid);
+                }
+            }
+            if let Some(uvs_mapping) = &subtable.uvs_mapping {
+                glyphs.extend(
+                    uvs_mapping
+                        .iter()
+                        .filter(|((codepoint, _), _)| options.unicodes.contains(codepoint))
+                        .map(|(_, &gid)| gid),
+                );
+            }
+        }
+    }
+    if options.layout_closure {
+        if let Some(gsub) = &gsub {
+            closure::gsub_closure(gsub, options.layout_features.as_ref(), &mut glyphs);
+        }
+    }
+    if let Some(math) = &math {
+        closure::math_closure(math, &mut glyphs);
+    }
+    if let Some(glyf) = &glyf {
+        closure::component_closure(glyf, &mut glyphs);
+    }
+    glyphs.retain(|&gid| gid < num_glyphs);
+
+    let plan = Plan::new(&glyphs, unicodes, requested, options.retain_gids);
+    log::info!("Retaining {} of {} glyphs", plan.mapping.len(), num_glyphs);
+
+    if let Some(glyf) = glyf {
+        font.tables
+            .insert(subset_glyf(&glyf, &plan, options.drop_hinting));
+    }
+    if let Some(hmtx) = hmtx {
+        font.tables.insert(subset_hmtx(&hmtx, &plan));
+    }
+    if let Some(gvar) = gvar {
+        // A gvar with no variations at all cannot be compiled, and says
+        // nothing anyway
+        let gvar = subset_gvar(&gvar, &plan);
+        if gvar
+            .variations
+            .iter()
+            .flatten()
+            .any(|var| !var.deltasets.is_empty())
+        {
+            font.tables.insert(gvar);
+        } else {
+            font.tables.remove(tables::gvar::TAG);
+        }
+    }
+    if let Some(cmap) = cmap {
+        let cmap = subset_cmap(&cmap, &plan);
+        if let Some(mut os2) = font.tables.os2()? {
+            let codepoints: BTreeSet<u32> = cmap
+                .subtables
+                .iter()
+                .flat_map(|st| st.mapping.keys().copied())
+                .collect();
+            if let (Some(&first), Some(&last)) =
+                (codepoints.iter().next(), codepoints.iter().last())
+            {
+                os2.usFirstCharIndex = first.min(0xFFFF) as u16;
+                os2.usLastCharIndex = last.min(0xFFFF) as u16;
+                font.tables.insert(os2);
+            }
+        }
+        font.tables.insert(cmap);
+    }
+    if let Some(mut post) = post {
+        if let Some(names) = &post.glyphnames {
+            // Empty glyphs are only left behind when glyph IDs are retained,
+            // so they keep the names of the glyphs they replace.
+            let names = plan
+                .old_gids
+                .iter()
+                .enumerate()
+                .map(|(new, old)| names[old.unwrap_or(new as GlyphID) as usize].clone())
+                .collect();
+            post.glyphnames = Some(names);
+            font.tables.insert(post);
+        }
+    }
+    if let Some(gdef) = gdef {
+        font.tables.insert(layout::subset_gdef(&gdef, &plan));
+    }
+    if let Some(gsub) = gsub {
+        font.tables.insert(layout::subset_layout(
+            &gsub,
+            &plan,
+            options.layout_features.as_ref(),
+        ));
+    }
+    if let Some(gpos) = gpos {
+        font.tables.insert(layout::subset_layout(
+            &gpos,
+            &plan,
+            options.layout_features.as_ref(),
+        ));
+    }
+    if let Some(math) = math {
+        font.tables.insert(subset_math(&math, &plan));
+    }
+    if let Some(name) = font.tables.name()? {
+        font.tables.insert(subset_name(&name, options));
+    }
+
+    maxp.set_num_glyphs(plan.old_gids.len() as u16);
+    if options.drop_hinting {
+        if let MaxpVariant::Maxp10(table) = &mut maxp.table {
+            table.maxZones = 1;
+            table.maxTwilightPoints = 0;
+            table.maxStorage = 0;
+            table.maxFunctionDefs = 0;
+            table.maxInstructionDefs = 0;
+            table.maxStackElements = 0;
+            table.maxSizeOfInstructions = 0;
+        }
+        for tag in HINTING_TABLES {
+            font.tables.remove(tag);
+        }
+    }
+    font.tables.insert(maxp);
+
+    for tag in UNSUBSETTABLE_TABLES {
+        if font.tables.remove(tag).is_some() {
+            log::warn!("Dropping {} table, which cannot be subset", tag);
+        }
+    }
+    // Any signature no longer matches the font
+    font.tables.remove(tag!("DSIG"));
+
+    Ok(plan.mapping)
+}
+
+fn subset_glyf(glyf: &glyf, plan: &Plan, drop_hinting: bool) -> glyf {
+    let glyphs = plan
+        .old_gids
+        .iter()
+        .map(|old| {
+            let mut glyph = match old {
+                Some(old) => glyf.glyphs[*old as usize].clone(),
+                None => Glyph {
+                    xMin: 0,
+                    xMax: 0,
+                    yMin: 0,
+                    yMax: 0,
+                    contours: vec![],
+                    instructions: vec![],
+                    components: vec![],
+                    overlap: false,
+                },
+            };
+            for component in glyph.components.iter_mut() {
+                // Components are part of the closure, so are always retained
+                component.glyph_index = plan.map(component.glyph_index).unwrap_or(0);
+            }
+            if drop_hinting {
+                glyph.instructions.clear();
+            }
+            glyph
+        })
+        .collect();
+    glyf { glyphs }
+}
+
+fn subset_hmtx(hmtx: &hmtx, plan: &Plan) -> hmtx {
+    let metrics = plan
+        .old_gids
+        .iter()
+        .map(|old| {
+            old.and_then(|old| hmtx.metrics.get(old as usize).copied())
+                .unwrap_or(Metric {
+                    advanceWidth: 0,
+                    lsb: 0,
+                })
+        })
+        .collect();
+    hmtx { metrics }
+}
+
+fn subset_gvar(gvar: &gvar, plan: &Plan) -> gvar {
+    let variations = plan
+        .old_gids
+        .iter()
+        .map(|old| old.and_then(|old| gvar.variations.get(old as usize).cloned().flatten()))
+        .collect();
+    gvar { variations }
+}
+
+/// Keeps the mappings of the requested codepoints, and of the glyphs
+/// requested by name or ID
+fn subset_cmap(cmap: &cmap, plan: &Plan) -> cmap {
+    let mut subtables = vec![];
+    for subtable in &cmap.subtables {
+        let mut subtable = subtable.clone();
+        subtable.mapping = subtable
+            .mapping
+            .iter()
+            .filter(|(codepoint, gid)| {
+                plan.unicodes.contains(codepoint) || plan.requested.contains(gid)
+            })
+            .filter_map(|(&codepoint, &gid)| Some((codepoint, plan.map(gid)?)))
+            .collect();
+        if let Some(uvs_mapping) = &subtable.uvs_mapping {
+            subtable.uvs_mapping = Some(
+                uvs_mapping
+                    .iter()
+                    .filter(|((codepoint, _), _)| plan.unicodes.contains(codepoint))
+                    .filter_map(|(&sequence, &gid)| Some((sequence, plan.map(gid)?)))
+                    .collect(),
+            );
+        }
+        if !subtable.mapping.is_empty()
+            || subtable.uvs_mapping.as_ref().is_some_and(|m| !m.is_empty())
+        {
+            subtables.push(subtable);
+        }
+    }
+    cmap { subtables }
+}
+
+fn subset_math(math: &MATH, plan: &Plan) -> MATH {
+    let subset_construction = |construction: &MathGlyphConstruction| {
+        let mut construction = construction.clone();
+        construction.mathGlyphVariantRecord = construction
+            .mathGlyphVariantRecord
+            .into_iter()
+            .filter_map(|mut record| {
+                record.variantGlyph = plan.map(record.variantGlyph)?;
+                Some(record)
+            })
+            .collect();
+        if let Some(assembly) = construction.glyphAssembly.link.as_mut() {
+            for part in assembly.partRecords.iter_mut() {
+                // Parts are part of the closure, so are always retained
+                part.glyphID = plan.map(part.glyphID).unwrap_or(0);
+            }
+        }
+        construction
+    };
+    let subset_extensions = |extensions: &BTreeMap<GlyphID, MathGlyphConstruction>| {
+        extensions
+            .iter()
+            .filter_map(|(&gid, construction)| {
+                Some((plan.map(gid)?, subset_construction(construction)))
+            })
+            .collect()
+    };
+    MATH {
+        constants: math.constants.clone(),
+        italic_correction: plan.map_keys(&math.italic_correction),
+        top_accent_attachment: plan.map_keys(&math.top_accent_attachment),
+        extended_shapes: plan.map_set(&math.extended_shapes),
+        kerning: plan.map_keys(&math.kerning),
+        min_overlap: math.min_overlap,
+        vertical_extensions: subset_extensions(&math.vertical_extensions),
+        horizontal_extensions: subset_extensions(&math.horizontal_extensions),
+    }
+}
+
+fn subset_name(name: &name, options: &SubsetOptions) -> name {
+    let records = name
+        .records
+        .iter()
+        .filter(|record| {
+            options
+                .name_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&record.nameID))
+        })
+        .filter(|record| {
+            record.platformID != 3
+                || options
+                    .name_languages
+                    .as_ref()
+                    .is_none_or(|languages| languages.contains(&record.languageID))
+        })
+        .cloned()
+        .collect();
+    name { records }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::gpos2::PairPos;
+    use crate::tables::GPOS::Positioning;
+    use otspec::btreemap;
+
+    fn subset_font(options: &SubsetOptions) -> (Font, BTreeMap<GlyphID, GlyphID>) {
+        let mut font = crate::ttx::tests::test_font();
+        let mapping = subset(&mut font, options).unwrap();
+        let mut bytes = vec![];
+        font.write(&mut bytes).unwrap();
+        (Font::from_bytes(&bytes).unwrap(), mapping)
+    }
+
+    fn unicodes(text: &str) -> SubsetOptions {
+        SubsetOptions {
+            unicodes: text.chars().map(|c| c as u32).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ligature_closure() {
+        let (mut font, mapping) = subset_font(&unicodes("fi"));
+        assert_eq!(mapping, btreemap!(0 => 0, 1 => 1, 2 => 2, 3 => 3));
+        assert_eq!(font.num_glyphs(), 4);
+        let post = font.tables.post().unwrap().unwrap();
+        assert_eq!(
+            post.glyphnames.as_ref().unwrap(),
+            &vec![".notdef", "f", "i", "f_i"]
+        );
+        assert!(font.tables.GSUB().unwrap().unwrap().lookups.len() == 1);
+        // The kerning of A and V no longer applies to anything
+        let gpos = font.tables.GPOS().unwrap().unwrap();
+        assert!(gpos.lookups.is_empty());
+        assert!(gpos.features.is_empty());
+        assert!(!font.tables.contains(&tag!("DSIG")));
+    }
+
+    #[test]
+    fn test_renumbering() {
+        let (font, mapping) = subset_font(&unicodes("AV"));
+        assert_eq!(mapping, btreemap!(0 => 0, 4 => 1, 5 => 2));
+        let cmap = font.tables.cmap().unwrap().unwrap();
+        assert_eq!(
+            cmap.get_best_mapping().unwrap(),
+            &btreemap!('A' as u32 => 1, 'V' as u32 => 2)
+        );
+        assert_eq!(font.tables.hmtx().unwrap().unwrap().metrics.len(), 3);
+        // The ligature no longer applies to anything
+        assert!(font.tables.GSUB().unwrap().unwrap().lookups.is_empty());
+        let gpos = font.tables.GPOS().unwrap().unwrap();
+        match &gpos.lookups[0].rule {
+            Positioning::Pair(subtables) => {
+                let pairs: Vec<(GlyphID, GlyphID)> = subtables
+                    .iter()
+                    .flat_map(|st: &PairPos| st.mapping.keys().copied())
+                    .collect();
+                assert_eq!(pairs, vec![(1, 2)]);
+            }
+            _ => panic!("Expected a pair positioning lookup"),
+        }
+        assert_eq!(gpos.features.get(0).unwrap().1, vec![0]);
+    }
+
+    #[test]
+    fn test_glyph_names_and_retain_gids() {
+        let options = SubsetOptions {
+            glyph_names: vec!["V".to_string()],
+            layout_closure: false,
+            retain_gids: true,
+            ..Default::default()
+        };
+        let (font, mapping) = subset_font(&options);
+        assert_eq!(mapping, btreemap!(0 => 0, 5 => 5));
+        let glyf = font.tables.glyf().unwrap().unwrap();
+        assert_eq!(glyf.glyphs.len(), 6);
+        assert!(glyf.glyphs[4].is_empty());
+        assert!(!glyf.glyphs[5].is_empty());
+        // Requested glyphs keep their codepoints
+        let cmap = font.tables.cmap().unwrap().unwrap();
+        assert_eq!(
+            cmap.get_best_mapping().unwrap(),
+            &btreemap!('V' as u32 => 5)
+        );
+
+        let mut font = crate::ttx::tests::test_font();
+        let options = SubsetOptions {
+            glyph_names: vec!["W".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            subset(&mut font, &options),
+            Err(SubsetError::UnknownGlyphName("W".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_layout_features() {
+        let options = SubsetOptions {
+            layout_features: Some(std::iter::once(tag!("kern")).collect()),
+            ..unicodes("fi")
+        };
+        let (font, mapping) = subset_font(&options);
+        assert_eq!(mapping.len(), 3);
+        let gsub = font.tables.GSUB().unwrap().unwrap();
+        assert!(gsub.lookups.is_empty());
+        assert!(gsub.features.is_empty());
+        assert_eq!(font.tables.GPOS().unwrap().unwrap().features.len(), 0);
+    }
+
+    #[test]
+    fn test_components_and_hinting() {
+        let mut font = crate::ttx::tests::test_font();
+        let mut glyf = font.tables.glyf().unwrap().unwrap();
+        glyf.glyphs[3] = Glyph {
+            xMin: 0,
+            xMax: 500,
+            yMin: 0,
+            yMax: 500,
+            contours: vec![],
+            instructions: vec![0xb0, 0x01],
+            components: vec![tables::glyf::Component {
+                glyph_index: 4,
+                transformation: kurbo::Affine::IDENTITY,
+                match_points: None,
+                flags: tables::glyf::ComponentFlags::empty(),
+            }],
+            overlap: false,
+        };
+        font.tables.insert(glyf);
+        font.tables.insert_raw(tag!("prep"), vec![0xb0, 0x01]);
+        let options = SubsetOptions {
+            gids: std::iter::once(3).collect(),
+            drop_hinting: true,
+            ..Default::default()
+        };
+        let mapping = subset(&mut font, &options).unwrap();
+        assert_eq!(mapping, btreemap!(0 => 0, 3 => 1, 4 => 2));
+        let glyf = font.tables.glyf().unwrap().unwrap();
+        assert_eq!(glyf.glyphs[1].components[0].glyph_index, 2);
+        assert!(glyf.glyphs[1].instructions.is_empty());
+        assert!(!font.tables.contains(&tag!("prep")));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::layout::common::GPOSGSUB;
+use crate::layout::contextual::Slot;
+use crate::tables::glyf::glyf;
+use crate::tables::GSUB::{Substitution, GSUB};
+use crate::tables::MATH::MATH;
+use otspec::types::*;
+use std::collections::BTreeSet;
+
+/// The indices of the lookups which a set of features refers to, or which
+/// all features refer to if no set is given
+pub(crate) fn feature_lookups<T>(
+    table: &GPOSGSUB<T>,
+    features: Option<&BTreeSet<Tag>>,
+) -> BTreeSet<usize> {
+    table
+        .features
+        .iter()
+        .filter(|(tag, _, _)| features.is_none_or(|features| features.contains(tag)))
+        .flat_map(|(_, indices, _)| indices.iter().copied())
+        .collect()
+}
+
+/// Adds the glyphs which the substitutions of a `GSUB` table can produce
+/// from a set of glyphs
+///
+/// Lookups invoked by contextual rules are only followed once every slot
+/// of the rule can be matched by a glyph in the set.
+pub(crate) fn gsub_closure(
+    gsub: &GSUB,
+    features: Option<&BTreeSet<Tag>>,
+    glyphs: &mut BTreeSet<GlyphID>,
+) {
+    let mut active = feature_lookups(gsub, features);
+    loop {
+        let before = (glyphs.len(), active.len());
+        for index in active.clone() {
+            if let Some(lookup) = gsub.lookups.get(index) {
+                close_substitution(&lookup.rule, glyphs, &mut active);
+            }
+        }
+        if (glyphs.len(), active.len()) == before {
+            break;
+        }
+    }
+}
+
+fn matches(slots: &[Slot], glyphs: &BTreeSet<GlyphID>) -> bool {
+    slots.iter().all(|slot| !slot.is_disjoint(glyphs))
+}
+
+fn close_substitution(
+    rule: &Substitution,
+    glyphs: &mut BTreeSet<GlyphID>,
+    active: &mut BTreeSet<usize>,
+) {
+    let mut reached: Vec<GlyphID> = vec![];
+    match rule {
+        Substitution::Single(subtables) => {
+            for st in subtables {
+                reached.extend(
+                    st.mapping
+                        .iter()
+                        .filter(|(input, _)| glyphs.contains(input))
+                        .map(|(_, &output)| output),
+                );
+            }
+        }
+        Substitution::Multiple(subtables) => {
+            for st in subtables {
+                reached.extend(
+                    st.mapping
+                        .iter()
+                        .filter(|(input, _)| glyphs.contains(input))
+                        .flat_map(|(_, outputs)| outputs.iter().copied()),
+                );
+            }
+        }
+        Substitution::Alternate(subtables) => {
+            for st in subtables {
+                reached.extend(
+                    st.mapping
+                        .iter()
+                        .filter(|(input, _)| glyphs.contains(input))
+                        .flat_map(|(_, alternates)| alternates.iter().copied()),
+                );
+            }
+        }
+        Substitution::Ligature(subtables) => {
+            for st in subtables {
+                reached.extend(
+                    st.mapping
+                        .iter()
+                        .filter(|(components, _)| components.iter().all(|g| glyphs.contains(g)))
+                        .map(|(_, &ligature)| ligature),
+                );
+            }
+        }
+        Substitution::Contextual(subtables) => {
+            for rule in subtables.iter().flat_map(|st| st.rules.iter()) {
+                if rule.iter().all(|(slot, _)| !slot.is_disjoint(glyphs)) {
+                    active.extend(
+                        rule.iter()
+                            .flat_map(|(_, lookups)| lookups.iter())
+                            .map(|&l| l as usize),
+                    );
+                }
+            }
+        }
+        Substitution::ChainedContextual(subtables) => {
+            for rule in subtables.iter().flat_map(|st| st.rules.iter()) {
+                if matches(&rule.backtrack, glyphs)
+                    && matches(&rule.lookahead, glyphs)
+                    && rule.input.iter().all(|(slot, _)| !slot.is_disjoint(glyphs))
+                {
+                    active.extend(
+                        rule.input
+                            .iter()
+                            .flat_map(|(_, lookups)| lookups.iter())
+                            .map(|&l| l as usize),
+                    );
+                }
+            }
+        }
+        Substitution::ReverseChainContextual(subtables) => {
+            for st in subtables {
+                if matches(&st.backtrack, glyphs) && matches(&st.lookahead, glyphs) {
+                    reached.extend(
+                        st.mapping
+                            .iter()
+                            .filter(|(input, _)| glyphs.contains(input))
+                            .map(|(_, &output)| output),
+                    );
+                }
+            }
+        }
+    }
+    glyphs.extend(reached);
+}
+
+/// Adds the variants and assembly parts of the `MATH` constructions of a set
+/// of glyphs
+pub(crate) fn math_closure(math: &MATH, glyphs: &mut BTreeSet<GlyphID>) {
+    loop {
+        let mut reached: Vec<GlyphID> = vec![];
+        for extensions in [&math.vertical_extensions, &math.horizontal_extensions] {
+            for (_, construction) in extensions.iter().filter(|(gid, _)| glyphs.contains(gid)) {
+                reached.extend(
+                    construction
+                        .mathGlyphVariantRecord
+                        .iter()
+                        .map(|record| record.variantGlyph),
+                );
+                if let Some(assembly) = &construction.glyphAssembly.link {
+                    reached.extend(assembly.partRecords.iter().map(|part| part.glyphID));
+                }
+            }
+        }
+        let before = glyphs.len();
+        glyphs.extend(reached);
+        if glyphs.len() == before {
+            break;
+        }
+    }
+}
+
+/// Adds the components of composite glyphs in a set, recursively
+pub(crate) fn component_closure(glyf: &glyf, glyphs: &mut BTreeSet<GlyphID>) {
+    let mut stack: Vec<GlyphID> = glyphs.iter().copied().collect();
+    while let Some(gid) = stack.pop() {
+        if let Some(glyph) = glyf.glyphs.get(gid as usize) {
+            for component in &glyph.components {
+                if glyphs.insert(component.glyph_index) {
+                    stack.push(component.glyph_index);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use super::closure::feature_lookups;
+use super::Plan;
+use crate::layout::common::{FeatureList, Lookup, GPOSGSUB};
+use crate::layout::contextual::{
+    ChainedSequenceContext, ChainedSequenceContextRule, LookupID, SequenceContext,
+    SequenceContextRule, Slot,
+};
+use crate::layout::gpos1::SinglePos;
+use crate::layout::gpos2::PairPos;
+use crate::layout::gpos3::CursivePos;
+use crate::layout::gpos4::MarkBasePos;
+use crate::layout::gpos5::MarkLigPos;
+use crate::layout::gpos6::MarkMarkPos;
+use crate::layout::gsub1::SingleSubst;
+use crate::layout::gsub2::MultipleSubst;
+use crate::layout::gsub3::AlternateSubst;
+use crate::layout::gsub4::LigatureSubst;
+use crate::layout::gsub8::ReverseChainSubst;
+use crate::tables::GDEF::GDEF;
+use crate::tables::GPOS::Positioning;
+use crate::tables::GSUB::Substitution;
+use otspec::types::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A `GSUB` or `GPOS` rule, which can be reduced to a set of glyphs
+pub(crate) trait SubsetRule: Sized {
+    /// The rule renumbered for the retained glyphs, or `None` if none of it
+    /// applies to them
+    fn subset(&self, plan: &Plan) -> Option<Self>;
+    /// The lookups which contextual rules invoke
+    fn nested_lookups(&self) -> BTreeSet<usize>;
+    /// Renumbers the lookups which contextual rules invoke, dropping those
+    /// which have been removed
+    fn remap_lookups(&mut self, lookup_map: &BTreeMap<usize, usize>);
+}
+
+/// Reduces a `GSUB` or `GPOS` table to the retained glyphs and features
+///
+/// Lookups which no retained feature refers to, directly or through a
+/// contextual rule, or which no longer apply to any glyph are removed, as
+/// are features which are left without lookups.
+pub(crate) fn subset_layout<T: SubsetRule>(
+    table: &GPOSGSUB<T>,
+    plan: &Plan,
+    features: Option<&BTreeSet<Tag>>,
+) -> GPOSGSUB<T> {
+    let mut reachable = feature_lookups(table, features);
+    let mut stack: Vec<usize> = reachable.iter().copied().collect();
+    while let Some(index) = stack.pop() {
+        if let Some(lookup) = table.lookups.get(index) {
+            for nested in lookup.rule.nested_lookups() {
+                if reachable.insert(nested) {
+                    stack.push(nested);
+                }
+            }
+        }
+    }
+
+    let mut lookups = vec![];
+    let mut lookup_map = BTreeMap::new();
+    for (index, lookup) in table.lookups.iter().enumerate() {
+        if !reachable.contains(&index) {
+            continue;
+        }
+        if let Some(rule) = lookup.rule.subset(plan) {
+            lookup_map.insert(index, lookups.len());
+            lookups.push(Lookup {
+                flags: lookup.flags,
+                mark_filtering_set: lookup.mark_filtering_set,
+                extension: lookup.extension,
+                rule,
+            });
+        }
+    }
+    for lookup in lookups.iter_mut() {
+        lookup.rule.remap_lookups(&lookup_map);
+    }
+
+    let mut feature_list = FeatureList::default();
+    let mut feature_map = BTreeMap::new();
+    for (index, (tag, indices, params)) in table.features.iter().enumerate() {
+        if features.is_some_and(|features| !features.contains(tag)) {
+            continue;
+        }
+        let indices: Vec<usize> = indices
+            .iter()
+            .filter_map(|index| lookup_map.get(index).copied())
+            .collect();
+        if indices.is_empty() && params.is_none() {
+            continue;
+        }
+        feature_map.insert(index, feature_list.len());
+        feature_list.push((*tag, indices, params.clone()));
+    }
+
+    let mut scripts = table.scripts.clone();
+    for script in scripts.scripts.values_mut() {
+        for language_system in script
+            .default_language_system
+            .iter_mut()
+            .chain(script.language_systems.values_mut())
+        {
+            language_system.required_feature = language_system
+                .required_feature
+                .and_then(|index| feature_map.get(&index).copied());
+            language_system.feature_indices = language_system
+                .feature_indices
+                .iter()
+                .filter_map(|index| feature_map.get(index).copied())
+                .collect();
+        }
+    }
+
+    GPOSGSUB {
+        lookups,
+        scripts,
+        features: feature_list,
+    }
+}
+
+/// Renumbers the glyphs of a `GDEF` table
+pub(crate) fn subset_gdef(gdef: &GDEF, plan: &Plan) -> GDEF {
+    GDEF {
+        glyph_class: plan.map_keys(&gdef.glyph_class),
+        attachment_point_list: plan.map_keys(&gdef.attachment_point_list),
+        ligature_caret_list: plan.map_keys(&gdef.ligature_caret_list),
+        mark_attachment_class: plan.map_keys(&gdef.mark_attachment_class),
+        // Mark filtering sets are referred to by index, so are kept even
+        // when they are empty
+        mark_glyph_sets: gdef
+            .mark_glyph_sets
+            .as_ref()
+            .map(|sets| sets.iter().map(|set| plan.map_set(set)).collect()),
+        item_variation_store: gdef.item_variation_store.clone(),
+    }
+}
+
+/// Subsets each item of a list, returning `None` if none are left
+fn subset_each<T>(items: &[T], subset: impl Fn(&T) -> Option<T>) -> Option<Vec<T>> {
+    let items: Vec<T> = items.iter().filter_map(subset).collect();
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
+
+fn nonempty<K, V>(map: BTreeMap<K, V>) -> Option<BTreeMap<K, V>> {
+    if map.is_empty() {
+        None
+    } else {
+        Some(map)
+    }
+}
+
+/// Renumbers the glyphs of a slot, returning `None` if none are left
+fn subset_slot(slot: &Slot, plan: &Plan) -> Option<Slot> {
+    let slot = plan.map_set(slot);
+    if slot.is_empty() {
+        None
+    } else {
+        Some(slot)
+    }
+}
+
+fn subset_slots(slots: &[Slot], plan: &Plan) -> Option<Vec<Slot>> {
+    slots.iter().map(|slot| subset_slot(slot, plan)).collect()
+}
+
+fn subset_sequence_rule(rule: &SequenceContextRule, plan: &Plan) -> Option<SequenceContextRule> {
+    rule.iter()
+        .map(|(slot, lookups)| Some((subset_slot(slot, plan)?, lookups.clone())))
+        .collect()
+}
+
+fn subset_sequence_context(st: &SequenceContext, plan: &Plan) -> Option<SequenceContext> {
+    subset_each(&st.rules, |rule| subset_sequence_rule(rule, plan))
+        .map(|rules| SequenceContext { rules })
+}
+
+fn subset_chained_sequence_context(
+    st: &ChainedSequenceContext,
+    plan: &Plan,
+) -> Option<ChainedSequenceContext> {
+    subset_each(&st.rules, |rule| {
+        Some(ChainedSequenceContextRule {
+            backtrack: subset_slots(&rule.backtrack, plan)?,
+            lookahead: subset_slots(&rule.lookahead, plan)?,
+            input: subset_sequence_rule(&rule.input, plan)?,
+        })
+    })
+    .map(|rules| ChainedSequenceContext { rules })
+}
+
+fn sequence_rule_lookups(rule: &SequenceContextRule) -> impl Iterator<Item = usize> + '_ {
+    rule.iter()
+        .flat_map(|(_, lookups)| lookups.iter())
+        .map(|&lookup| lookup as usize)
+}
+
+fn remap_sequence_rule(rule: &mut SequenceContextRule, lookup_map: &BTreeMap<usize, usize>) {
+    for (_, lookups) in rule.iter_mut() {
+        *lookups = lookups
+            .iter()
+            .filter_map(|&lookup| lookup_map.get(&(lookup as usize)))
+            .map(|&lookup| lookup as LookupID)
+            .collect();
+    }
+}
+
+fn contextual_lookups(subtables: &[SequenceContext]) -> BTreeSet<usize> {
+    subtables
+        .iter()
+        .flat_map(|st| st.rules.iter())
+        .flat_map(sequence_rule_lookups)
+        .collect()
+}
+
+fn chained_contextual_lookups(subtables: &[ChainedSequenceContext]) -> BTreeSet<usize> {
+    subtables
+        .iter()
+        .flat_map(|st| st.rules.iter())
+        .flat_map(|rule| sequence_rule_lookups(&rule.input))
+        .collect()
+}
+
+fn remap_contextual(subtables: &mut [SequenceContext], lookup_map: &BTreeMap<usize, usize>) {
+    for rule in subtables.iter_mut().flat_map(|st| st.rules.iter_mut()) {
+        remap_sequence_rule(rule, lookup_map);
+    }
+}
+
+fn remap_chained_contextual(
+    subtables: &mut [ChainedSequenceContext],
+    lookup_map: &BTreeMap<usize, usize>,
+) {
+    for rule in subtables.iter_mut().flat_map(|st| st.rules.iter_mut()) {
+        remap_sequence_rule(&mut rule.input, lookup_map);
+    }
+}
+
+impl SubsetRule for Substitution {
+    fn subset(&self, plan: &Plan) -> Option<Self> {
+        match self {
+            Substitution::Single(subtables) => subset_each(subtables, |st| {
+                nonempty(
+                    st.mapping
+                        .iter()
+                        .filter_map(|(&input, &output)| Some((plan.map(input)?, plan.map(output)?)))
+                        .collect(),
+                )
+                .map(|mapping| SingleSubst { mapping })
+            })
+            .map(Substitution::Single),
+            Substitution::Multiple(subtables) => subset_each(subtables, |st| {
+                nonempty(
+                    st.mapping
+                        .iter()
+                        .filter_map(|(&input, outputs)| {
+                            let outputs: Option<Vec<GlyphID>> =
+                                outputs.iter().map(|&output| plan.map(output)).collect();
+                            Some((plan.map(input)?, outputs?))
+                        })
+                        .collect(),
+                )
+                .map(|mapping| MultipleSubst { mapping })
+            })
+            .map(Substitution::Multiple),
+            Substitution::Alternate(subtables) => subset_each(subtables, |st| {
+                nonempty(
+                    st.mapping
+                        .iter()
+                        .filter_map(|(&input, alternates)| {
+                            let alternates: Vec<GlyphID> = alternates
+                                .iter()
+                                .filter_map(|&alternate| plan.map(alternate))
+                                .collect();
+                            if alternates.is_empty() {
+                                return None;
+                            }
+                            Some((plan.map(input)?, alternates))
+                        })
+                        .collect(),
+                )
+                .map(|mapping| AlternateSubst { mapping })
+            })
+            .map(Substitution::Alternate),
+            Substitution::Ligature(subtables) => subset_each(subtables, |st| {
+                nonempty(
+                    st.mapping
+                        .iter()
+                        .filter_map(|(components, &ligature)| {
+                            let components: Option<Vec<GlyphID>> =
+                                components.iter().map(|&g| plan.map(g)).collect();
+                            Some((components?, plan.map(ligature)?))
+                        })
+                        .collect(),
+                )
+                .map(|mapping| LigatureSubst { mapping })
+            })
+            .map(Substitution::Ligature),
+            Substitution::Contextual(subtables) => {
+                subset_each(subtables, |st| subset_sequence_context(st, plan))
+                    .map(Substitution::Contextual)
+            }
+            Substitution::ChainedContextual(subtables) => {
+                subset_each(subtables, |st| subset_chained_sequence_context(st, plan))
+                    .map(Substitution::ChainedContextual)
+            }
+            Substitution::ReverseChainContextual(subtables) => subset_each(subtables, |st| {
+                Some(ReverseChainSubst {
+                    mapping: nonempty(
+                        st.mapping
+                            .iter()
+                            .filter_map(|(&input, &output)| {
+                                Some((plan.map(input)?, plan.map(output)?))
+                            })
+                            .collect(),
+                    )?,
+                    backtrack: subset_slots(&st.backtrack, plan)?,
+                    lookahead: subset_slots(&st.lookahead, plan)?,
+                })
+            })
+            .map(Substitution::ReverseChainContextual),
+        }
+    }
+
+    fn nested_lookups(&self) -> BTreeSet<usize> {
+        match self {
+            Substitution::Contextual(subtables) => contextual_lookups(subtables),
+            Substitution::ChainedContextual(subtables) => chained_contextual_lookups(subtables),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    fn remap_lookups(&mut self, lookup_map: &BTreeMap<usize, usize>) {
+        match self {
+            Substitution::Contextual(subtables) => remap_contextual(subtables, lookup_map),
+            Substitution::ChainedContextual(subtables) => {
+                remap_chained_contextual(subtables, lookup_map)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl SubsetRule for Positioning {
+    fn subset(&self, plan: &Plan) -> Option<Self> {
+        match self {
+            Positioning::Single(subtables) => subset_each(subtables, |st| {
+                nonempty(plan.map_keys(&st.mapping)).map(|mapping| SinglePos { mapping })
+            })
+            .map(Positioning::Single),
+            Positioning::Pair(subtables) => subset_each(subtables, |st| {
+                nonempty(
+                    st.mapping
+                        .iter()
+                        .filter_map(|(&(left, right), values)| {
+                            Some(((plan.map(left)?, plan.map(right)?), values.clone()))
+                        })
+                        .collect(),
+                )
+                .map(|mapping| PairPos { mapping })
+            })
+            .map(Positioning::Pair),
+            Positioning::Cursive(subtables) => subset_each(subtables, |st| {
+                nonempty(plan.map_keys(&st.mapping)).map(|mapping| CursivePos { mapping })
+            })
+            .map(Positioning::Cursive),
+            Positioning::MarkToBase(subtables) => subset_each(subtables, |st| {
+                Some(MarkBasePos {
+                    bases: nonempty(plan.map_keys(&st.bases))?,
+                    marks: nonempty(plan.map_keys(&st.marks))?,
+                })
+            })
+            .map(Positioning::MarkToBase),
+            Positioning::MarkToLig(subtables) => subset_each(subtables, |st| {
+                Some(MarkLigPos {
+                    ligatures: nonempty(plan.map_keys(&st.ligatures))?,
+                    marks: nonempty(plan.map_keys(&st.marks))?,
+                })
+            })
+            .map(Positioning::MarkToLig),
+            Positioning::MarkToMark(subtables) => subset_each(subtables, |st| {
+                Some(MarkMarkPos {
+                    base_marks: nonempty(plan.map_keys(&st.base_marks))?,
+                    combining_marks: nonempty(plan.map_keys(&st.combining_marks))?,
+                })
+            })
+            .map(Positioning::MarkToMark),
+            Positioning::Contextual(subtables) => {
+                subset_each(subtables, |st| subset_sequence_context(st, plan))
+                    .map(Positioning::Contextual)
+            }
+            Positioning::ChainedContextual(subtables) => {
+                subset_each(subtables, |st| subset_chained_sequence_context(st, plan))
+                    .map(Positioning::ChainedContextual)
+            }
+        }
+    }
+
+    fn nested_lookups(&self) -> BTreeSet<usize> {
+        match self {
+            Positioning::Contextual(subtables) => contextual_lookups(subtables),
+            Positioning::ChainedContextual(subtables) => chained_contextual_lookups(subtables),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    fn remap_lookups(&mut self, lookup_map: &BTreeMap<usize, usize>) {
+        match self {
+            Positioning::Contextual(subtables) => remap_contextual(subtables, lookup_map),
+            Positioning::ChainedContextual(subtables) => {
+                remap_chained_contextual(subtables, lookup_map)
+            }
+            _ => {}
+        }
+    }
+}
@@ -73,8 +73,14 @@ pub struct Table {
 pub enum LoadedTable {
     /// Contains an axis variations table.
     avar(Rc<tables::avar::avar>),
+    /// Contains a compact font format table.
+    CFF(Rc<tables::CFF::CFF>),
+    /// Contains a compact font format (version 2) table.
+    CFF2(Rc<tables::CFF2::CFF2>),
     /// Contains a character to glyph index mapping table.
     cmap(Rc<tables::cmap::cmap>),
+    /// Contains a CVT variations table.
+    cvar(Rc<tables::cvar::cvar>),
     /// Contains a control value table.
     cvt(Rc<tables::cvt::cvt>),
     /// Contains a font program table.
@@ -326,6 +332,8 @@ impl TableSet {
     fn deserialize_table(&self, tag: Tag, data: Rc<[u8]>) -> Result<Table, DeserializationError> {
         let typed_data: LoadedTable = match tag.as_bytes() {
             b"avar" => otspec::de::from_bytes::<tables::avar::avar>(&data)?.into(),
+            b"CFF " => otspec::de::from_bytes::<tables::CFF::CFF>(&data)?.into(),
+            b"CFF2" => otspec::de::from_bytes::<tables::CFF2::CFF2>(&data)?.into(),
             b"cmap" => otspec::de::from_bytes::<tables::cmap::cmap>(&data)?.into(),
             b"cvt " => otspec::de::from_bytes::<tables::cvt::cvt>(&data)?.into(),
             b"fpgm" => otspec::de::from_bytes::<tables::fpgm::fpgm>(&data)?.into(),
@@ -383,6 +391,17 @@ impl TableSet {
                     .ok_or_else(|| DeserializationError("deserialize loca before glyf".into()))?;
                 tables::glyf::from_bytes(&data, &loca.indices)?.into()
             }
+            b"cvar" => {
+                let axis_count = self
+                    .fvar()?
+                    .map(|fvar| fvar.axes.len() as u16)
+                    .ok_or_else(|| DeserializationError("deserialize fvar before cvar".into()))?;
+                let cvt_count = self
+                    .cvt()?
+                    .map(|cvt| cvt.0.len() as u16)
+                    .ok_or_else(|| DeserializationError("deserialize cvt before cvar".into()))?;
+                tables::cvar::from_bytes(&data, axis_count, cvt_count)?.into()
+            }
             b"gvar" => {
                 let glyf = self
                     .glyf()?
@@ -425,7 +444,10 @@ impl TableSet {
         let glyf = match self.glyf().unwrap() {
             Some(table) => table,
             None => {
-                println!("Warning: no glyf table");
+                // CFF-flavoured fonts have no glyf table, and that's fine
+                if !self.contains(&tables::CFF::TAG) && !self.contains(&tables::CFF2::TAG) {
+                    println!("Warning: no glyf table");
+                }
                 return;
             }
         };
@@ -477,22 +499,45 @@ impl TableSet {
         }
     }
 
-    pub(crate) fn compile_gsub_gpos(&mut self) {
+    /// Compiles typed `hmtx` and `gvar` tables, which need information from
+    /// other tables to be serialized.
+    pub(crate) fn compile_hmtx_gvar(&mut self) {
+        if !self.is_serialized(tables::hmtx::TAG).unwrap_or(true) {
+            if let Some(hmtx) = self.hmtx().unwrap() {
+                let (hmtx_data, number_of_hmetrics) = hmtx.to_bytes();
+                self.insert_raw(tables::hmtx::TAG, hmtx_data);
+                if let Some(mut hhea) = self.hhea().unwrap() {
+                    hhea.numberOfHMetrics = number_of_hmetrics;
+                    self.insert(hhea);
+                }
+            }
+        }
+        if !self.is_serialized(tables::gvar::TAG).unwrap_or(true) {
+            if let Some(gvar) = self.gvar().unwrap() {
+                let glyf = self.glyf().unwrap();
+                let gvar_data = gvar.to_bytes(glyf.as_deref());
+                self.insert_raw(tables::gvar::TAG, gvar_data)
+            }
+        }
+    }
+
+    pub(crate) fn compile_gsub_gpos(&mut self) -> Result<(), SerializationError> {
         let num_glyphs = self.maxp().unwrap().unwrap().num_glyphs();
         if !self.is_serialized(tables::GPOS::TAG).unwrap_or(true) {
             if let Some(gpos) = self.GPOS().unwrap() {
                 let mut gpos_data = vec![];
-                tables::GPOS::to_bytes(&gpos, &mut gpos_data, num_glyphs).unwrap();
+                tables::GPOS::to_bytes(&gpos, &mut gpos_data, num_glyphs)?;
                 self.insert_raw(tables::GPOS::TAG, gpos_data)
             }
         }
         if !self.is_serialized(tables::GSUB::TAG).unwrap_or(true) {
             if let Some(gsub) = self.GSUB().unwrap() {
                 let mut gsub_data = vec![];
-                tables::GSUB::to_bytes(&gsub, &mut gsub_data, num_glyphs).unwrap();
+                tables::GSUB::to_bytes(&gsub, &mut gsub_data, num_glyphs)?;
                 self.insert_raw(tables::GSUB::TAG, gsub_data)
             }
         }
+        Ok(())
     }
 
     pub(crate) fn write_table(
@@ -513,6 +558,48 @@ impl TableSet {
             LazyItem::Loaded(table) => table.loaded.to_bytes(buffer),
         }
     }
+
+    /// Returns the binary data of a table, compiling it if it has been
+    /// modified.
+    ///
+    /// Unlike [`write_table`](TableSet::write_table), this also compiles
+    /// the tables which need information from other tables in the font.
+    pub(crate) fn table_data(&self, tag: Tag) -> Result<Option<Vec<u8>>, SerializationError> {
+        let table = match self.tables.get(&tag) {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let table = match &*table.borrow() {
+            LazyItem::Unloaded(raw) | LazyItem::Loaded(Table { raw: Some(raw), .. }) => {
+                return Ok(Some(raw.to_vec()))
+            }
+            LazyItem::Loaded(table) => table.loaded.clone(),
+        };
+        let num_glyphs = || -> Result<u16, SerializationError> {
+            self.maxp()
+                .map_err(|e| SerializationError(e.to_string()))?
+                .map(|maxp| maxp.num_glyphs())
+                .ok_or_else(|| SerializationError("no maxp table".into()))
+        };
+        let mut data = vec![];
+        match table {
+            LoadedTable::GSUB(gsub) => tables::GSUB::to_bytes(&gsub, &mut data, num_glyphs()?)?,
+            LoadedTable::GPOS(gpos) => tables::GPOS::to_bytes(&gpos, &mut data, num_glyphs()?)?,
+            LoadedTable::hmtx(hmtx) => data = hmtx.to_bytes().0,
+            LoadedTable::gvar(gvar) => {
+                let glyf = self.glyf().map_err(|e| SerializationError(e.to_string()))?;
+                data = gvar.to_bytes(glyf.as_deref());
+            }
+            LoadedTable::glyf(_) | LoadedTable::loca(_) => {
+                return Err(SerializationError(format!(
+                    "cannot compile the {} table on its own",
+                    tag
+                )))
+            }
+            other => other.to_bytes(&mut data)?,
+        }
+        Ok(Some(data))
+    }
 }
 
 impl LazyItem {
@@ -604,12 +691,15 @@ macro_rules! table_boilerplate {
     };
 }
 
+table_boilerplate!(tables::CFF::CFF, CFF);
+table_boilerplate!(tables::CFF2::CFF2, CFF2);
 table_boilerplate!(tables::GDEF::GDEF, GDEF);
 table_boilerplate!(tables::GPOS::GPOS, GPOS);
 table_boilerplate!(tables::GSUB::GSUB, GSUB);
 table_boilerplate!(tables::STAT::STAT, STAT);
 table_boilerplate!(tables::avar::avar, avar);
 table_boilerplate!(tables::cmap::cmap, cmap);
+table_boilerplate!(tables::cvar::cvar, cvar);
 table_boilerplate!(tables::cvt::cvt, cvt);
 table_boilerplate!(tables::fpgm::fpgm, fpgm);
 table_boilerplate!(tables::fvar::fvar, fvar);
@@ -632,7 +722,10 @@ impl Serialize for LoadedTable {
         match self {
             LoadedTable::Unknown(expr) => expr.to_bytes(data),
             LoadedTable::avar(expr) => expr.to_bytes(data),
+            LoadedTable::CFF(expr) => expr.to_bytes(data),
+            LoadedTable::CFF2(expr) => expr.to_bytes(data),
             LoadedTable::cmap(expr) => expr.to_bytes(data),
+            LoadedTable::cvar(expr) => expr.to_bytes(data),
             LoadedTable::cvt(expr) => expr.to_bytes(data),
             LoadedTable::fpgm(expr) => expr.to_bytes(data),
             LoadedTable::fvar(expr) => expr.to_bytes(data),
@@ -647,7 +740,7 @@ impl Serialize for LoadedTable {
             LoadedTable::glyf(_) => unimplemented!(),
             LoadedTable::loca(_) => unimplemented!(),
             LoadedTable::maxp(expr) => expr.to_bytes(data),
-            LoadedTable::MATH(_) => unimplemented!(),
+            LoadedTable::MATH(expr) => expr.to_bytes(data),
             LoadedTable::name(expr) => expr.to_bytes(data),
             LoadedTable::os2(expr) => expr.to_bytes(data),
             LoadedTable::post(expr) => expr.to_bytes(data),
@@ -656,3 +749,253 @@ impl Serialize for LoadedTable {
         }
     }
 }
+
+/// Tables are serialized as a map from tag to the typed table, or to the
+/// binary data for tables without a typed representation (or which could
+/// not be parsed). The `loca` table is left out, as it is recomputed from
+/// the `glyf` table.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TableSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        for tag in self.keys() {
+            if tag == tables::loca::TAG {
+                continue;
+            }
+            let table = match self.get(tag) {
+                Ok(Some(table)) => table,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!("Couldn't deserialize {}: '{}'", tag, e);
+                    if let LazyItem::Unloaded(data) = &*self.tables[&tag].borrow() {
+                        map.serialize_entry(&tag, &HexData(data.to_vec()))?;
+                    }
+                    continue;
+                }
+            };
+            match &table.loaded {
+                LoadedTable::Unknown(expr) => map.serialize_entry(&tag, &HexData(expr.to_vec())),
+                LoadedTable::avar(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::CFF(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::CFF2(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::cmap(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::cvar(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::cvt(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::fpgm(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::fvar(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::gasp(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::GDEF(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::GPOS(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::GSUB(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::glyf(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::gvar(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::head(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::hhea(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::hmtx(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::loca(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::MATH(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::maxp(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::name(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::os2(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::post(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::prep(expr) => map.serialize_entry(&tag, expr.as_ref()),
+                LoadedTable::STAT(expr) => map.serialize_entry(&tag, expr.as_ref()),
+            }?;
+        }
+        map.end()
+    }
+}
+
+/// Binary table data, serialized as a hexadecimal string
+#[cfg(feature = "serde")]
+struct HexData(Vec<u8>);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for HexData {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = self.0.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for HexData {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hexadecimal digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map(HexData)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A table in its typed form, or as binary data
+#[cfg(feature = "serde")]
+enum TypedOrRaw<T> {
+    Typed(T),
+    Raw(HexData),
+}
+
+/// Binary data is a string, while typed tables are maps or sequences. (An
+/// untagged enum would buffer the table, which loses the ability to read
+/// integer map keys from strings.)
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for TypedOrRaw<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+        use serde::de::IntoDeserializer;
+        struct TypedOrRawVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: serde::Deserialize<'de>> serde::de::Visitor<'de> for TypedOrRawVisitor<T> {
+            type Value = TypedOrRaw<T>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a table, or a string of hexadecimal data")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, hex: &str) -> Result<Self::Value, E> {
+                <HexData as serde::Deserialize>::deserialize(hex.into_deserializer())
+                    .map(TypedOrRaw::Raw)
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                T::deserialize(MapAccessDeserializer::new(map)).map(TypedOrRaw::Typed)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                T::deserialize(SeqAccessDeserializer::new(seq)).map(TypedOrRaw::Typed)
+            }
+        }
+
+        deserializer.deserialize_any(TypedOrRawVisitor(std::marker::PhantomData))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Into<Table>> TypedOrRaw<T> {
+    fn insert_into(self, tableset: &mut TableSet, tag: Tag) {
+        match self {
+            TypedOrRaw::Typed(table) => tableset.insert(table),
+            TypedOrRaw::Raw(data) => tableset.insert_raw(tag, data.0),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TableSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TableSetVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for TableSetVisitor {
+            type Value = TableSet;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of table tags to tables")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<TableSet, A::Error> {
+                let mut tableset = TableSet::default();
+                while let Some(tag) = map.next_key::<Tag>()? {
+                    match tag.as_bytes() {
+                        b"avar" => map
+                            .next_value::<TypedOrRaw<tables::avar::avar>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"CFF " => map
+                            .next_value::<TypedOrRaw<tables::CFF::CFF>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"CFF2" => map
+                            .next_value::<TypedOrRaw<tables::CFF2::CFF2>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"cmap" => map
+                            .next_value::<TypedOrRaw<tables::cmap::cmap>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"cvar" => map
+                            .next_value::<TypedOrRaw<tables::cvar::cvar>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"cvt " => map
+                            .next_value::<TypedOrRaw<tables::cvt::cvt>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"fpgm" => map
+                            .next_value::<TypedOrRaw<tables::fpgm::fpgm>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"fvar" => map
+                            .next_value::<TypedOrRaw<tables::fvar::fvar>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"gasp" => map
+                            .next_value::<TypedOrRaw<tables::gasp::gasp>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"GDEF" => map
+                            .next_value::<TypedOrRaw<tables::GDEF::GDEF>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"GPOS" => map
+                            .next_value::<TypedOrRaw<tables::GPOS::GPOS>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"GSUB" => map
+                            .next_value::<TypedOrRaw<tables::GSUB::GSUB>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"glyf" => map
+                            .next_value::<TypedOrRaw<tables::glyf::glyf>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"gvar" => map
+                            .next_value::<TypedOrRaw<tables::gvar::gvar>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"head" => map
+                            .next_value::<TypedOrRaw<tables::head::head>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"hhea" => map
+                            .next_value::<TypedOrRaw<tables::hhea::hhea>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"hmtx" => map
+                            .next_value::<TypedOrRaw<tables::hmtx::hmtx>>()?
+                            .insert_into(&mut tableset, tag),
+                        // Recomputed from the glyf table
+                        b"loca" => {
+                            map.next_value::<serde::de::IgnoredAny>()?;
+                        }
+                        b"MATH" => map
+                            .next_value::<TypedOrRaw<tables::MATH::MATH>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"maxp" => map
+                            .next_value::<TypedOrRaw<tables::maxp::maxp>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"name" => map
+                            .next_value::<TypedOrRaw<tables::name::name>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"OS/2" => map
+                            .next_value::<TypedOrRaw<tables::os2::os2>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"post" => map
+                            .next_value::<TypedOrRaw<tables::post::post>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"prep" => map
+                            .next_value::<TypedOrRaw<tables::prep::prep>>()?
+                            .insert_into(&mut tableset, tag),
+                        b"STAT" => map
+                            .next_value::<TypedOrRaw<tables::STAT::STAT>>()?
+                            .insert_into(&mut tableset, tag),
+                        _ => tableset.insert_raw(tag, map.next_value::<HexData>()?.0),
+                    }
+                }
+                Ok(tableset)
+            }
+        }
+
+        deserializer.deserialize_map(TableSetVisitor)
+    }
+}
@@ -1,3 +1,9 @@
+/// The `CFF ` (Compact Font Format) table
+#[allow(non_snake_case)]
+pub mod CFF;
+/// The `CFF2` (Compact Font Format version 2) table
+#[allow(non_snake_case)]
+pub mod CFF2;
 /// The `GDEF` (Glyph definition) table
 #[allow(non_snake_case)]
 pub mod GDEF;
@@ -17,6 +23,8 @@ pub mod STAT;
 pub mod avar;
 /// The `cmap` (Character To Glyph Index Mapping) table
 pub mod cmap;
+/// The `cvar` (CVT variations) table
+pub mod cvar;
 /// The `cvt ` (Control Value) table
 pub mod cvt;
 /// The `fpgm` (Font program) table
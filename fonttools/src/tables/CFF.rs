@@ -0,0 +1,662 @@
+use crate::cff::charstring::{desubroutinize, CharStringToken};
+use crate::cff::dict::{self, decode_dict, encode_dict, Dict, DictOperand};
+use crate::cff::index::{read_index, write_index};
+use crate::cff::strings::{standard_sid, STANDARD_STRINGS, STANDARD_STRING_COUNT};
+use crate::cff::subroutinizer::subroutinize;
+use crate::cff::FontDict;
+use otspec::types::*;
+use otspec::{DeserializationError, Deserialize, Deserializer, ReaderContext, Serialize};
+use std::collections::HashMap;
+
+/// The 'CFF ' OpenType tag.
+pub const TAG: Tag = crate::tag!("CFF ");
+
+/// The Compact Font Format table
+///
+/// Only the first font of the FontSet is handled, as OpenType requires.
+/// Offsets (to the charset, CharStrings, Private DICTs and so on) are not
+/// stored in the DICTs; they are computed afresh on serialization. Other DICT
+/// operands which refer to strings (`FullName`, `Notice` etc.) hold string
+/// IDs; use [`CFF::string`] and [`CFF::add_string`] to work with them.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CFF {
+    /// The PostScript name of the font
+    pub name: String,
+    /// The Top DICT
+    pub top_dict: Dict,
+    /// Custom strings, starting from string ID 391
+    pub strings: Vec<String>,
+    /// Glyph names, in glyph order. For CID-keyed fonts these are of the
+    /// form `cid00001`.
+    pub glyph_names: Vec<String>,
+    /// Charstrings, in glyph order
+    pub charstrings: Vec<Vec<u8>>,
+    /// Global subroutines
+    pub global_subrs: Vec<Vec<u8>>,
+    /// The font dicts. For non-CID fonts there is exactly one, whose
+    /// `font_dict` is empty.
+    pub font_dicts: Vec<FontDict>,
+    /// The index into `font_dicts` for each glyph. This is empty for
+    /// non-CID fonts.
+    pub fd_select: Vec<u8>,
+}
+
+impl CFF {
+    /// Returns `true` if this is a CID-keyed font.
+    pub fn is_cid(&self) -> bool {
+        self.top_dict.get(dict::ROS).is_some()
+    }
+
+    /// The number of glyphs in the font
+    pub fn num_glyphs(&self) -> usize {
+        self.charstrings.len()
+    }
+
+    /// Looks up a string by its string ID.
+    pub fn string(&self, sid: u16) -> Option<&str> {
+        let sid = sid as usize;
+        if sid < STANDARD_STRING_COUNT {
+            Some(STANDARD_STRINGS[sid])
+        } else {
+            self.strings
+                .get(sid - STANDARD_STRING_COUNT)
+                .map(|x| x.as_str())
+        }
+    }
+
+    /// Returns the string ID for a string, adding it to the custom strings if
+    /// it is neither a standard string nor already present.
+    pub fn add_string(&mut self, s: &str) -> u16 {
+        if let Some(sid) = standard_sid(s) {
+            return sid;
+        }
+        let position = match self.strings.iter().position(|x| x == s) {
+            Some(position) => position,
+            None => {
+                self.strings.push(s.to_string());
+                self.strings.len() - 1
+            }
+        };
+        (position + STANDARD_STRING_COUNT) as u16
+    }
+
+    /// Returns the string value of a Top DICT operator such as `FullName`.
+    pub fn top_dict_string(&self, op: dict::Operator) -> Option<&str> {
+        self.top_dict
+            .get_i32(op)
+            .and_then(|sid| self.string(sid as u16))
+    }
+
+    /// Sets a Top DICT operator which takes a string operand.
+    pub fn set_top_dict_string(&mut self, op: dict::Operator, value: &str) {
+        let sid = self.add_string(value);
+        self.top_dict.set(op, vec![(sid as i32).into()]);
+    }
+
+    /// The font dict index used by the given glyph
+    pub fn fd_index(&self, glyph: usize) -> usize {
+        self.fd_select.get(glyph).copied().unwrap_or(0) as usize
+    }
+
+    /// Returns the charstring for a glyph as a list of tokens, with all
+    /// subroutine calls expanded.
+    pub fn desubroutinized_charstring(
+        &self,
+        glyph: usize,
+    ) -> Result<Vec<CharStringToken>, DeserializationError> {
+        let charstring = self
+            .charstrings
+            .get(glyph)
+            .ok_or_else(|| DeserializationError(format!("No glyph {}", glyph)))?;
+        let local_subrs = self
+            .font_dicts
+            .get(self.fd_index(glyph))
+            .map(|fd| fd.subrs.as_slice())
+            .unwrap_or(&[]);
+        desubroutinize(charstring, &self.global_subrs, local_subrs, false, &[])
+    }
+
+    /// Subroutinizes the charstrings, replacing any existing global and
+    /// local subroutines.
+    pub fn subroutinize(&mut self) -> Result<(), DeserializationError> {
+        let charstrings = (0..self.num_glyphs())
+            .map(|glyph| self.desubroutinized_charstring(glyph))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fd_select: Vec<usize> = (0..self.num_glyphs())
+            .map(|glyph| self.fd_index(glyph))
+            .collect();
+        if self.font_dicts.is_empty() {
+            self.font_dicts.push(FontDict::default());
+        }
+        let result = subroutinize(&charstrings, &fd_select, self.font_dicts.len(), false);
+        self.charstrings = result.charstrings;
+        self.global_subrs = result.global_subrs;
+        for (font_dict, subrs) in self.font_dicts.iter_mut().zip(result.local_subrs) {
+            font_dict.subrs = subrs;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a charset, returning the SID (or CID) of each glyph.
+fn read_charset(
+    data: &[u8],
+    offset: usize,
+    num_glyphs: usize,
+) -> Result<Vec<u16>, DeserializationError> {
+    let mut charset = vec![0];
+    if num_glyphs == 0 {
+        return Ok(vec![]);
+    }
+    if offset == 0 {
+        // ISOAdobe: SIDs 1-228 in order
+        charset.extend(1..num_glyphs.min(229) as u16);
+        return Ok(charset);
+    }
+    if offset < 3 {
+        return Err(DeserializationError(
+            "Expert charsets are not supported".to_string(),
+        ));
+    }
+    let mut c = ReaderContext::new(data.to_vec());
+    c.ptr = offset;
+    let format: u8 = c.de()?;
+    while charset.len() < num_glyphs {
+        match format {
+            0 => charset.push(c.de()?),
+            1 | 2 => {
+                let first: u16 = c.de()?;
+                let n_left: u16 = if format == 1 {
+                    let n: u8 = c.de()?;
+                    n as u16
+                } else {
+                    c.de()?
+                };
+                charset.extend((0..=n_left).map(|i| first.wrapping_add(i)));
+            }
+            _ => {
+                return Err(DeserializationError(format!(
+                    "Unknown charset format {}",
+                    format
+                )))
+            }
+        }
+    }
+    charset.truncate(num_glyphs);
+    Ok(charset)
+}
+
+/// Reads an FDSelect structure (formats 0, 3 and 4).
+pub(crate) fn read_fd_select(
+    data: &[u8],
+    offset: usize,
+    num_glyphs: usize,
+) -> Result<Vec<u8>, DeserializationError> {
+    let mut c = ReaderContext::new(data.to_vec());
+    c.ptr = offset;
+    let format: u8 = c.de()?;
+    match format {
+        0 => c.de_counted(num_glyphs),
+        3 | 4 => {
+            let n_ranges: usize = if format == 3 {
+                let n: u16 = c.de()?;
+                n as usize
+            } else {
+                let n: u32 = c.de()?;
+                n as usize
+            };
+            let mut ranges: Vec<(usize, u8)> = vec![];
+            for _ in 0..n_ranges {
+                let first: usize = if format == 3 {
+                    let first: u16 = c.de()?;
+                    first as usize
+                } else {
+                    let first: u32 = c.de()?;
+                    first as usize
+                };
+                let fd: u8 = if format == 3 {
+                    c.de()?
+                } else {
+                    let fd: u16 = c.de()?;
+                    fd as u8
+                };
+                ranges.push((first, fd));
+            }
+            let sentinel: usize = if format == 3 {
+                let s: u16 = c.de()?;
+                s as usize
+            } else {
+                let s: u32 = c.de()?;
+                s as usize
+            };
+            let mut fd_select = vec![0; num_glyphs];
+            for (ix, &(first, fd)) in ranges.iter().enumerate() {
+                let end = ranges
+                    .get(ix + 1)
+                    .map(|r| r.0)
+                    .unwrap_or(sentinel)
+                    .min(num_glyphs);
+                for entry in fd_select.iter_mut().take(end).skip(first) {
+                    *entry = fd;
+                }
+            }
+            Ok(fd_select)
+        }
+        _ => Err(DeserializationError(format!(
+            "Unknown FDSelect format {}",
+            format
+        ))),
+    }
+}
+
+/// Writes an FDSelect structure in format 3.
+pub(crate) fn write_fd_select(fd_select: &[u8]) -> Vec<u8> {
+    let mut ranges: Vec<(u16, u8)> = vec![];
+    for (glyph, &fd) in fd_select.iter().enumerate() {
+        if ranges.last().map(|r| r.1) != Some(fd) {
+            ranges.push((glyph as u16, fd));
+        }
+    }
+    let mut out = vec![3];
+    out.extend((ranges.len() as u16).to_be_bytes());
+    for (first, fd) in ranges {
+        out.extend(first.to_be_bytes());
+        out.push(fd);
+    }
+    out.extend((fd_select.len() as u16).to_be_bytes());
+    out
+}
+
+/// Reads a Private DICT and its local subroutines, given the operands of the
+/// `Private` operator.
+pub(crate) fn read_private(
+    data: &[u8],
+    operands: Option<&[DictOperand]>,
+    cff2: bool,
+) -> Result<(Dict, Vec<Vec<u8>>), DeserializationError> {
+    let (size, offset) = match operands {
+        Some([size, offset]) => (size.as_i32() as usize, offset.as_i32() as usize),
+        _ => return Ok((Dict::default(), vec![])),
+    };
+    let private_data = data
+        .get(offset..offset + size)
+        .ok_or_else(|| DeserializationError("Bad Private DICT offset".to_string()))?;
+    let mut private_dict = decode_dict(private_data)?;
+    let subrs = match private_dict.remove(dict::SUBRS) {
+        Some(subrs_offset) => {
+            let mut c = ReaderContext::new(data.to_vec());
+            c.ptr = offset + subrs_offset.first().map(|x| x.as_i32()).unwrap_or(0) as usize;
+            read_index(&mut c, cff2)?
+        }
+        None => vec![],
+    };
+    Ok((private_dict, subrs))
+}
+
+/// Serializes a Private DICT followed by its local subroutines, and returns
+/// the data together with the size of the DICT.
+pub(crate) fn write_private(font_dict: &FontDict, cff2: bool) -> (Vec<u8>, usize) {
+    let mut private_dict = font_dict.private_dict.clone();
+    private_dict.remove(dict::SUBRS);
+    if !font_dict.subrs.is_empty() {
+        // The Subrs offset is relative to the Private DICT, and the
+        // operand is always five bytes long, so we know the size in advance.
+        private_dict.set(dict::SUBRS, vec![0.into()]);
+        let size = encode_dict(&private_dict).len();
+        private_dict.set(dict::SUBRS, vec![(size as i32).into()]);
+    }
+    let mut out = encode_dict(&private_dict);
+    let size = out.len();
+    if !font_dict.subrs.is_empty() {
+        out.extend(write_index(&font_dict.subrs, cff2));
+    }
+    (out, size)
+}
+
+impl Deserialize for CFF {
+    fn from_bytes(c: &mut ReaderContext) -> Result<Self, DeserializationError> {
+        let data = c.input[c.ptr..].to_vec();
+        let mut c = ReaderContext::new(data.clone());
+        let major: u8 = c.de()?;
+        if major != 1 {
+            return Err(DeserializationError(format!(
+                "Unsupported CFF version {}",
+                major
+            )));
+        }
+        let _minor: u8 = c.de()?;
+        let header_size: u8 = c.de()?;
+        c.ptr = header_size as usize;
+        let name = read_index(&mut c, false)?
+            .first()
+            .map(|x| String::from_utf8_lossy(x).to_string())
+            .unwrap_or_default();
+        let mut top_dict = read_index(&mut c, false)?
+            .first()
+            .map(|x| decode_dict(x))
+            .transpose()?
+            .unwrap_or_default();
+        let strings: Vec<String> = read_index(&mut c, false)?
+            .iter()
+            .map(|x| String::from_utf8_lossy(x).to_string())
+            .collect();
+        let global_subrs = read_index(&mut c, false)?;
+
+        let charstrings_offset = top_dict
+            .remove(dict::CHARSTRINGS)
+            .and_then(|x| x.first().map(|x| x.as_i32()))
+            .ok_or_else(|| DeserializationError("No CharStrings in CFF".to_string()))?;
+        c.ptr = charstrings_offset as usize;
+        let charstrings = read_index(&mut c, false)?;
+        let num_glyphs = charstrings.len();
+
+        let mut cff = CFF {
+            name,
+            strings,
+            charstrings,
+            global_subrs,
+            ..Default::default()
+        };
+
+        let charset_offset = top_dict
+            .remove(dict::CHARSET)
+            .and_then(|x| x.first().map(|x| x.as_i32()))
+            .unwrap_or(0);
+        let charset = read_charset(&data, charset_offset as usize, num_glyphs)?;
+        // A custom encoding would need an offset we can't preserve; OpenType
+        // fonts use the cmap table anyway.
+        if top_dict.get_i32(dict::ENCODING).unwrap_or(0) > 1 {
+            top_dict.remove(dict::ENCODING);
+        }
+
+        if top_dict.get(dict::ROS).is_some() {
+            cff.glyph_names = charset.iter().map(|cid| format!("cid{:05}", cid)).collect();
+            let fd_array_offset = top_dict
+                .remove(dict::FD_ARRAY)
+                .and_then(|x| x.first().map(|x| x.as_i32()))
+                .ok_or_else(|| DeserializationError("No FDArray in CID font".to_string()))?;
+            c.ptr = fd_array_offset as usize;
+            for font_dict_data in read_index(&mut c, false)? {
+                let mut font_dict = decode_dict(&font_dict_data)?;
+                let (private_dict, subrs) =
+                    read_private(&data, font_dict.get(dict::PRIVATE), false)?;
+                font_dict.remove(dict::PRIVATE);
+                cff.font_dicts.push(FontDict {
+                    font_dict,
+                    private_dict,
+                    subrs,
+                });
+            }
+            cff.fd_select = match top_dict.remove(dict::FD_SELECT) {
+                Some(offset) => read_fd_select(
+                    &data,
+                    offset.first().map(|x| x.as_i32()).unwrap_or(0) as usize,
+                    num_glyphs,
+                )?,
+                None => vec![0; num_glyphs],
+            };
+        } else {
+            cff.glyph_names = charset
+                .iter()
+                .map(|&sid| {
+                    cff.string(sid)
+                        .map(|x| x.to_string())
+                        .ok_or_else(|| DeserializationError(format!("Bad glyph SID {}", sid)))
+                })
+                .collect::<Result<Vec<String>, DeserializationError>>()?;
+            let (private_dict, subrs) = read_private(&data, top_dict.get(dict::PRIVATE), false)?;
+            top_dict.remove(dict::PRIVATE);
+            cff.font_dicts.push(FontDict {
+                font_dict: Dict::default(),
+                private_dict,
+                subrs,
+            });
+        }
+        cff.top_dict = top_dict;
+        Ok(cff)
+    }
+}
+
+impl Serialize for CFF {
+    fn to_bytes(&self, data: &mut Vec<u8>) -> Result<(), otspec::SerializationError> {
+        let is_cid = self.is_cid();
+        let mut strings = self.strings.clone();
+        let mut string_ids: HashMap<&str, u16> = self
+            .strings
+            .iter()
+            .enumerate()
+            .map(|(ix, s)| (s.as_str(), (ix + STANDARD_STRING_COUNT) as u16))
+            .collect();
+
+        // The charset, in format 0
+        let mut charset = vec![0];
+        for (glyph, name) in self.glyph_names.iter().enumerate().skip(1) {
+            let id = if is_cid {
+                name.strip_prefix("cid")
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or(glyph as u16)
+            } else if let Some(sid) =
+                standard_sid(name).or_else(|| string_ids.get(name.as_str()).copied())
+            {
+                sid
+            } else {
+                let sid = (strings.len() + STANDARD_STRING_COUNT) as u16;
+                strings.push(name.clone());
+                string_ids.insert(name, sid);
+                sid
+            };
+            charset.extend(id.to_be_bytes());
+        }
+
+        let privates: Vec<(Vec<u8>, usize)> = self
+            .font_dicts
+            .iter()
+            .map(|fd| write_private(fd, false))
+            .collect();
+        let fd_select = if is_cid {
+            write_fd_select(&self.fd_select)
+        } else {
+            vec![]
+        };
+
+        // Lay out the table twice; offsets are always five bytes long in
+        // DICTs, so the second pass does not change any sizes.
+        let mut top_dict = self.top_dict.clone();
+        let mut fd_array = vec![];
+        let mut offsets = (0, 0, 0, 0, 0);
+        for _ in 0..2 {
+            let (
+                charset_offset,
+                fd_select_offset,
+                charstrings_offset,
+                fd_array_offset,
+                private_offset,
+            ) = offsets;
+            top_dict.set(dict::CHARSET, vec![charset_offset.into()]);
+            top_dict.set(dict::CHARSTRINGS, vec![charstrings_offset.into()]);
+            let mut private_offset = private_offset;
+            if is_cid {
+                top_dict.set(dict::FD_SELECT, vec![fd_select_offset.into()]);
+                top_dict.set(dict::FD_ARRAY, vec![fd_array_offset.into()]);
+                top_dict.remove(dict::PRIVATE);
+                fd_array = vec![];
+                for (fd, (private, size)) in self.font_dicts.iter().zip(privates.iter()) {
+                    let mut font_dict = fd.font_dict.clone();
+                    font_dict.set(
+                        dict::PRIVATE,
+                        vec![(*size as i32).into(), private_offset.into()],
+                    );
+                    fd_array.push(encode_dict(&font_dict));
+                    private_offset += private.len() as i32;
+                }
+            } else if let Some((_, size)) = privates.first() {
+                top_dict.set(
+                    dict::PRIVATE,
+                    vec![(*size as i32).into(), private_offset.into()],
+                );
+            }
+
+            let mut position = 4 + write_index(&[self.name.as_bytes().to_vec()], false).len();
+            position += write_index(&[encode_dict(&top_dict)], false).len();
+            position += write_index(
+                &strings
+                    .iter()
+                    .map(|x| x.as_bytes().to_vec())
+                    .collect::<Vec<_>>(),
+                false,
+            )
+            .len();
+            position += write_index(&self.global_subrs, false).len();
+            let charset_offset = position;
+            position += charset.len();
+            let fd_select_offset = position;
+            position += fd_select.len();
+            let charstrings_offset = position;
+            position += write_index(&self.charstrings, false).len();
+            let fd_array_offset = position;
+            if is_cid {
+                position += write_index(&fd_array, false).len();
+            }
+            offsets = (
+                charset_offset as i32,
+                fd_select_offset as i32,
+                charstrings_offset as i32,
+                fd_array_offset as i32,
+                position as i32,
+            );
+        }
+
+        data.extend([1, 0, 4, 4]);
+        data.extend(write_index(&[self.name.as_bytes().to_vec()], false));
+        data.extend(write_index(&[encode_dict(&top_dict)], false));
+        data.extend(write_index(
+            &strings
+                .iter()
+                .map(|x| x.as_bytes().to_vec())
+                .collect::<Vec<_>>(),
+            false,
+        ));
+        data.extend(write_index(&self.global_subrs, false));
+        data.extend(charset);
+        data.extend(fd_select);
+        data.extend(write_index(&self.charstrings, false));
+        if is_cid {
+            data.extend(write_index(&fd_array, false));
+        }
+        for (private, _) in privates
+            .iter()
+            .take(if is_cid { privates.len() } else { 1 })
+        {
+            data.extend(private);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cff::charstring::{encode_charstring, ENDCHAR, RLINETO, RMOVETO};
+
+    fn simple_cff() -> CFF {
+        let mut cff = CFF {
+            name: "Test-Regular".to_string(),
+            glyph_names: vec![
+                ".notdef".to_string(),
+                "A".to_string(),
+                "Aogonek.ss01".to_string(),
+            ],
+            ..Default::default()
+        };
+        cff.set_top_dict_string(dict::FULL_NAME, "Test Regular");
+        cff.top_dict.set(
+            dict::FONT_BBOX,
+            vec![0.into(), (-10).into(), 500.into(), 700.into()],
+        );
+        let glyph = encode_charstring(&[
+            CharStringToken::Integer(500),
+            CharStringToken::Integer(10),
+            CharStringToken::Integer(10),
+            CharStringToken::Operator(RMOVETO),
+            CharStringToken::Integer(100),
+            CharStringToken::Integer(-107),
+            CharStringToken::Operator(10), // callsubr 0
+            CharStringToken::Integer(0),
+            CharStringToken::Integer(-100),
+            CharStringToken::Operator(RLINETO),
+            CharStringToken::Operator(ENDCHAR),
+        ]);
+        cff.charstrings = vec![
+            encode_charstring(&[CharStringToken::Operator(ENDCHAR)]),
+            glyph.clone(),
+            glyph,
+        ];
+        cff.font_dicts.push(FontDict {
+            font_dict: Dict::default(),
+            private_dict: Dict(vec![(dict::NOMINAL_WIDTH_X, vec![0.into()])]),
+            subrs: vec![encode_charstring(&[
+                CharStringToken::Integer(0),
+                CharStringToken::Operator(RLINETO),
+                CharStringToken::Operator(11),
+            ])],
+        });
+        cff
+    }
+
+    #[test]
+    fn test_cff_roundtrip() {
+        let cff = simple_cff();
+        let binary = otspec::ser::to_bytes(&cff).unwrap();
+        let deserialized: CFF = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(deserialized.name, "Test-Regular");
+        assert_eq!(
+            deserialized.top_dict_string(dict::FULL_NAME),
+            Some("Test Regular")
+        );
+        assert_eq!(deserialized.glyph_names, cff.glyph_names);
+        assert_eq!(deserialized.strings, vec!["Test Regular", "Aogonek.ss01"]);
+        assert_eq!(deserialized.charstrings, cff.charstrings);
+        assert_eq!(deserialized.font_dicts, cff.font_dicts);
+        assert_eq!(
+            deserialized.top_dict.get(dict::FONT_BBOX),
+            cff.top_dict.get(dict::FONT_BBOX)
+        );
+        // And again, now that all the strings are in place
+        let binary2 = otspec::ser::to_bytes(&deserialized).unwrap();
+        assert_eq!(binary, binary2);
+
+        let expanded = deserialized.desubroutinized_charstring(1).unwrap();
+        assert_eq!(expanded.len(), 11);
+        assert_eq!(expanded[5], CharStringToken::Integer(0));
+        assert_eq!(expanded[6], CharStringToken::Operator(RLINETO));
+    }
+
+    #[test]
+    fn test_cid_roundtrip() {
+        let mut cff = simple_cff();
+        cff.top_dict
+            .0
+            .insert(0, (dict::ROS, vec![391.into(), 392.into(), 0.into()]));
+        cff.strings = vec!["Adobe".to_string(), "Identity".to_string()];
+        cff.glyph_names = vec!["cid00000".into(), "cid00001".into(), "cid00007".into()];
+        cff.font_dicts[0].font_dict = Dict(vec![(dict::FONT_NAME, vec![391.into()])]);
+        cff.font_dicts.push(FontDict::default());
+        cff.fd_select = vec![0, 0, 1];
+        let binary = otspec::ser::to_bytes(&cff).unwrap();
+        let deserialized: CFF = otspec::de::from_bytes(&binary).unwrap();
+        assert!(deserialized.is_cid());
+        assert_eq!(deserialized.glyph_names, cff.glyph_names);
+        assert_eq!(deserialized.fd_select, cff.fd_select);
+        assert_eq!(deserialized.font_dicts, cff.font_dicts);
+        assert_eq!(deserialized.charstrings, cff.charstrings);
+    }
+
+    #[test]
+    fn test_fd_select() {
+        let fd_select = vec![0, 0, 1, 1, 1, 0, 2];
+        let binary = write_fd_select(&fd_select);
+        assert_eq!(binary.len(), 1 + 2 + 4 * 3 + 2);
+        assert_eq!(read_fd_select(&binary, 0, 7).unwrap(), fd_select);
+    }
+}
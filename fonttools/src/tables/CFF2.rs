@@ -0,0 +1,347 @@
+use crate::cff::charstring::{desubroutinize, CharStringToken};
+use crate::cff::dict::{self, decode_dict, encode_dict, Dict};
+use crate::cff::index::{read_index, write_index};
+use crate::cff::subroutinizer::subroutinize;
+use crate::cff::FontDict;
+use crate::otvar::ItemVariationStore;
+use crate::tables::CFF::{read_fd_select, read_private, write_private};
+use otspec::types::*;
+use otspec::{DeserializationError, Deserialize, Deserializer, ReaderContext, Serialize};
+
+/// The 'CFF2' OpenType tag.
+pub const TAG: Tag = crate::tag!("CFF2");
+
+/// The Compact Font Format (version 2) table
+///
+/// As with the [`CFF`](crate::tables::CFF::CFF) table, offsets are not
+/// stored in the DICTs but computed on serialization.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CFF2 {
+    /// The Top DICT
+    pub top_dict: Dict,
+    /// Charstrings, in glyph order
+    pub charstrings: Vec<Vec<u8>>,
+    /// Global subroutines
+    pub global_subrs: Vec<Vec<u8>>,
+    /// The font dicts (at least one)
+    pub font_dicts: Vec<FontDict>,
+    /// The index into `font_dicts` for each glyph. This may be empty if
+    /// there is only one font dict.
+    pub fd_select: Vec<u8>,
+    /// The variation store used by `blend` operators
+    pub variation_store: Option<ItemVariationStore>,
+}
+
+impl CFF2 {
+    /// The number of glyphs in the font
+    pub fn num_glyphs(&self) -> usize {
+        self.charstrings.len()
+    }
+
+    /// The font dict index used by the given glyph
+    pub fn fd_index(&self, glyph: usize) -> usize {
+        self.fd_select.get(glyph).copied().unwrap_or(0) as usize
+    }
+
+    /// The number of regions referenced by each `vsindex`, as needed to
+    /// decode charstrings
+    pub fn region_counts(&self) -> Vec<usize> {
+        self.variation_store
+            .as_ref()
+            .map(|store| {
+                store
+                    .variationData
+                    .iter()
+                    .map(|d| d.region_indexes.len())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the charstring for a glyph as a list of tokens, with all
+    /// subroutine calls expanded.
+    pub fn desubroutinized_charstring(
+        &self,
+        glyph: usize,
+    ) -> Result<Vec<CharStringToken>, DeserializationError> {
+        let charstring = self
+            .charstrings
+            .get(glyph)
+            .ok_or_else(|| DeserializationError(format!("No glyph {}", glyph)))?;
+        let local_subrs = self
+            .font_dicts
+            .get(self.fd_index(glyph))
+            .map(|fd| fd.subrs.as_slice())
+            .unwrap_or(&[]);
+        desubroutinize(
+            charstring,
+            &self.global_subrs,
+            local_subrs,
+            true,
+            &self.region_counts(),
+        )
+    }
+
+    /// Subroutinizes the charstrings, replacing any existing global and
+    /// local subroutines.
+    pub fn subroutinize(&mut self) -> Result<(), DeserializationError> {
+        let charstrings = (0..self.num_glyphs())
+            .map(|glyph| self.desubroutinized_charstring(glyph))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fd_select: Vec<usize> = (0..self.num_glyphs())
+            .map(|glyph| self.fd_index(glyph))
+            .collect();
+        if self.font_dicts.is_empty() {
+            self.font_dicts.push(FontDict::default());
+        }
+        let result = subroutinize(&charstrings, &fd_select, self.font_dicts.len(), true);
+        self.charstrings = result.charstrings;
+        self.global_subrs = result.global_subrs;
+        for (font_dict, subrs) in self.font_dicts.iter_mut().zip(result.local_subrs) {
+            font_dict.subrs = subrs;
+        }
+        Ok(())
+    }
+}
+
+fn offset_operand(dict: &mut Dict, op: dict::Operator) -> Option<usize> {
+    dict.remove(op)
+        .and_then(|x| x.first().map(|x| x.as_i32() as usize))
+}
+
+impl Deserialize for CFF2 {
+    fn from_bytes(c: &mut ReaderContext) -> Result<Self, DeserializationError> {
+        let data = c.input[c.ptr..].to_vec();
+        let mut c = ReaderContext::new(data.clone());
+        let major: u8 = c.de()?;
+        if major != 2 {
+            return Err(DeserializationError(format!(
+                "Unsupported CFF2 version {}",
+                major
+            )));
+        }
+        let _minor: u8 = c.de()?;
+        let header_size: u8 = c.de()?;
+        let top_dict_length: u16 = c.de()?;
+        let top_dict_start = header_size as usize;
+        let mut top_dict = decode_dict(
+            data.get(top_dict_start..top_dict_start + top_dict_length as usize)
+                .ok_or_else(|| DeserializationError("Bad CFF2 Top DICT length".to_string()))?,
+        )?;
+        c.ptr = top_dict_start + top_dict_length as usize;
+        let global_subrs = read_index(&mut c, true)?;
+
+        c.ptr = offset_operand(&mut top_dict, dict::CHARSTRINGS)
+            .ok_or_else(|| DeserializationError("No CharStrings in CFF2".to_string()))?;
+        let charstrings = read_index(&mut c, true)?;
+
+        let variation_store = match offset_operand(&mut top_dict, dict::VSTORE) {
+            Some(offset) => {
+                // The store is preceded by its length
+                let mut vc = ReaderContext::new(data.get(offset + 2..).unwrap_or(&[]).to_vec());
+                Some(vc.de()?)
+            }
+            None => None,
+        };
+
+        c.ptr = offset_operand(&mut top_dict, dict::FD_ARRAY)
+            .ok_or_else(|| DeserializationError("No FDArray in CFF2".to_string()))?;
+        let mut font_dicts = vec![];
+        for font_dict_data in read_index(&mut c, true)? {
+            let mut font_dict = decode_dict(&font_dict_data)?;
+            let (private_dict, subrs) = read_private(&data, font_dict.get(dict::PRIVATE), true)?;
+            font_dict.remove(dict::PRIVATE);
+            font_dicts.push(FontDict {
+                font_dict,
+                private_dict,
+                subrs,
+            });
+        }
+        let fd_select = match offset_operand(&mut top_dict, dict::FD_SELECT) {
+            Some(offset) => read_fd_select(&data, offset, charstrings.len())?,
+            None => vec![],
+        };
+
+        Ok(CFF2 {
+            top_dict,
+            charstrings,
+            global_subrs,
+            font_dicts,
+            fd_select,
+            variation_store,
+        })
+    }
+}
+
+/// Writes an FDSelect structure in format 3 or, for large fonts, format 4.
+fn write_cff2_fd_select(fd_select: &[u8]) -> Vec<u8> {
+    if fd_select.len() <= 0xFFFF {
+        return crate::tables::CFF::write_fd_select(fd_select);
+    }
+    let mut ranges: Vec<(u32, u16)> = vec![];
+    for (glyph, &fd) in fd_select.iter().enumerate() {
+        if ranges.last().map(|r| r.1) != Some(fd as u16) {
+            ranges.push((glyph as u32, fd as u16));
+        }
+    }
+    let mut out = vec![4];
+    out.extend((ranges.len() as u32).to_be_bytes());
+    for (first, fd) in ranges {
+        out.extend(first.to_be_bytes());
+        out.extend(fd.to_be_bytes());
+    }
+    out.extend((fd_select.len() as u32).to_be_bytes());
+    out
+}
+
+impl Serialize for CFF2 {
+    fn to_bytes(&self, data: &mut Vec<u8>) -> Result<(), otspec::SerializationError> {
+        let privates: Vec<(Vec<u8>, usize)> = self
+            .font_dicts
+            .iter()
+            .map(|fd| write_private(fd, true))
+            .collect();
+        let fd_select = if self.font_dicts.len() > 1 {
+            write_cff2_fd_select(&self.fd_select)
+        } else {
+            vec![]
+        };
+        let vstore = match &self.variation_store {
+            Some(store) => {
+                let store_data = otspec::ser::to_bytes(store)?;
+                let mut out = (store_data.len() as u16).to_be_bytes().to_vec();
+                out.extend(store_data);
+                out
+            }
+            None => vec![],
+        };
+
+        // As with CFF, offsets have a fixed size, so two passes suffice.
+        let mut top_dict = self.top_dict.clone();
+        let mut fd_array = vec![];
+        let mut offsets = (0, 0, 0, 0, 0);
+        for _ in 0..2 {
+            let (
+                charstrings_offset,
+                fd_array_offset,
+                fd_select_offset,
+                vstore_offset,
+                private_offset,
+            ) = offsets;
+            top_dict.set(dict::CHARSTRINGS, vec![charstrings_offset.into()]);
+            top_dict.set(dict::FD_ARRAY, vec![fd_array_offset.into()]);
+            if !fd_select.is_empty() {
+                top_dict.set(dict::FD_SELECT, vec![fd_select_offset.into()]);
+            }
+            if !vstore.is_empty() {
+                top_dict.set(dict::VSTORE, vec![vstore_offset.into()]);
+            }
+            let mut private_offset = private_offset;
+            fd_array = vec![];
+            for (fd, (private, size)) in self.font_dicts.iter().zip(privates.iter()) {
+                let mut font_dict = fd.font_dict.clone();
+                font_dict.set(
+                    dict::PRIVATE,
+                    vec![(*size as i32).into(), private_offset.into()],
+                );
+                fd_array.push(encode_dict(&font_dict));
+                private_offset += private.len() as i32;
+            }
+
+            let mut position = 5 + encode_dict(&top_dict).len();
+            position += write_index(&self.global_subrs, true).len();
+            let charstrings_offset = position;
+            position += write_index(&self.charstrings, true).len();
+            let fd_array_offset = position;
+            position += write_index(&fd_array, true).len();
+            let fd_select_offset = position;
+            position += fd_select.len();
+            let vstore_offset = position;
+            position += vstore.len();
+            offsets = (
+                charstrings_offset as i32,
+                fd_array_offset as i32,
+                fd_select_offset as i32,
+                vstore_offset as i32,
+                position as i32,
+            );
+        }
+
+        let top_dict_data = encode_dict(&top_dict);
+        data.extend([2, 0, 5]);
+        data.extend((top_dict_data.len() as u16).to_be_bytes());
+        data.extend(top_dict_data);
+        data.extend(write_index(&self.global_subrs, true));
+        data.extend(write_index(&self.charstrings, true));
+        data.extend(write_index(&fd_array, true));
+        data.extend(fd_select);
+        data.extend(vstore);
+        for (private, _) in privates {
+            data.extend(private);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cff::charstring::{encode_charstring, BLEND, RLINETO, RMOVETO};
+    use crate::otvar::{ItemVariationData, RegionAxisCoordinates};
+
+    #[test]
+    fn test_cff2_roundtrip() {
+        let glyph = encode_charstring(&[
+            CharStringToken::Integer(10),
+            CharStringToken::Integer(10),
+            CharStringToken::Operator(RMOVETO),
+            CharStringToken::Integer(100),
+            CharStringToken::Integer(0),
+            CharStringToken::Integer(20),
+            CharStringToken::Integer(1),
+            CharStringToken::Operator(BLEND),
+            CharStringToken::Operator(RLINETO),
+        ]);
+        let cff2 = CFF2 {
+            top_dict: Dict(vec![(
+                dict::FONT_MATRIX,
+                vec![
+                    0.001.into(),
+                    0.into(),
+                    0.into(),
+                    0.001.into(),
+                    0.into(),
+                    0.into(),
+                ],
+            )]),
+            charstrings: vec![vec![], glyph.clone(), glyph],
+            global_subrs: vec![],
+            font_dicts: vec![FontDict {
+                font_dict: Dict::default(),
+                private_dict: Dict(vec![(dict::BLUE_VALUES, vec![(-10).into(), 10.into()])]),
+                subrs: vec![encode_charstring(&[CharStringToken::Integer(5)])],
+            }],
+            fd_select: vec![],
+            variation_store: Some(ItemVariationStore {
+                format: 1,
+                axisCount: 1,
+                variationRegions: vec![vec![RegionAxisCoordinates {
+                    startCoord: 0.0,
+                    peakCoord: 1.0,
+                    endCoord: 1.0,
+                }]],
+                variationData: vec![ItemVariationData {
+                    region_indexes: vec![0],
+                    delta_values: vec![],
+                }],
+            }),
+        };
+        let binary = otspec::ser::to_bytes(&cff2).unwrap();
+        let deserialized: CFF2 = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(deserialized, cff2);
+        assert_eq!(deserialized.region_counts(), vec![1]);
+        let tokens = deserialized.desubroutinized_charstring(1).unwrap();
+        assert_eq!(tokens.len(), 9);
+    }
+}
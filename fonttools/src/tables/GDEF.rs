@@ -77,6 +77,7 @@ tables!(
 
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A low-level caret value in a GDEF table
 pub enum CaretValue {
     /// A format 1 caret value
@@ -156,6 +157,7 @@ impl Deserialize for CaretValue {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A glyph class definition in the GDEF table
 pub enum GlyphClass {
     /// Base glyph (single character, spacing glyph)
@@ -179,6 +181,7 @@ impl From<u16> for GlyphClass {
 }
 /// A GDEF (Glyph Definition) table
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GDEF {
     /// Glyph class definitions
     pub glyph_class: BTreeMap<GlyphID, GlyphClass>,
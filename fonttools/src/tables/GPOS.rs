@@ -1,4 +1,6 @@
-use crate::layout::common::{FromLowlevel, Lookup, ToLowlevel, GPOSGSUB};
+use crate::layout::common::{
+    split_map, split_oversized, FromLowlevel, Lookup, ToLowlevel, GPOSGSUB,
+};
 use crate::layout::contextual::{ChainedSequenceContext, SequenceContext};
 use crate::layout::gpos1::SinglePos;
 use crate::layout::gpos2::PairPos;
@@ -20,6 +22,7 @@ pub const TAG: Tag = crate::tag!("GPOS");
 ///
 /// Each rule is expressed as a vector of subtables.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Positioning {
     /// Contains a single positioning rule.
     Single(Vec<SinglePos>),
@@ -53,6 +56,110 @@ impl Positioning {
             Positioning::ChainedContextual(v) => v.push(ChainedSequenceContext::default()),
         }
     }
+
+    /// Splits in two any subtables which are too big to compile, returning
+    /// `true` if any were split. Contextual subtables cannot be split, and
+    /// mark attachment subtables are split between their bases.
+    pub(crate) fn split_oversized_subtables(&mut self, max_glyph_id: GlyphID) -> bool {
+        match self {
+            Positioning::Single(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.mapping, |&g| g)
+                        .map(|(a, b)| (SinglePos { mapping: a }, SinglePos { mapping: b }))
+                },
+            ),
+            Positioning::Pair(v) => split_oversized(
+                v,
+                |st| st.to_lowlevel_subtables(max_glyph_id),
+                |st| {
+                    split_map(&st.mapping, |&(left, _)| left)
+                        .map(|(a, b)| (PairPos { mapping: a }, PairPos { mapping: b }))
+                },
+            ),
+            Positioning::Cursive(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.mapping, |&g| g)
+                        .map(|(a, b)| (CursivePos { mapping: a }, CursivePos { mapping: b }))
+                },
+            ),
+            Positioning::MarkToBase(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.bases, |&g| g).map(|(a, b)| {
+                        let marks = st.marks.clone();
+                        (
+                            MarkBasePos {
+                                bases: a,
+                                marks: marks.clone(),
+                            },
+                            MarkBasePos { bases: b, marks },
+                        )
+                    })
+                },
+            ),
+            Positioning::MarkToLig(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.ligatures, |&g| g).map(|(a, b)| {
+                        let marks = st.marks.clone();
+                        (
+                            MarkLigPos {
+                                ligatures: a,
+                                marks: marks.clone(),
+                            },
+                            MarkLigPos {
+                                ligatures: b,
+                                marks,
+                            },
+                        )
+                    })
+                },
+            ),
+            Positioning::MarkToMark(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.base_marks, |&g| g).map(|(a, b)| {
+                        let combining_marks = st.combining_marks.clone();
+                        (
+                            MarkMarkPos {
+                                base_marks: a,
+                                combining_marks: combining_marks.clone(),
+                            },
+                            MarkMarkPos {
+                                base_marks: b,
+                                combining_marks,
+                            },
+                        )
+                    })
+                },
+            ),
+            _ => false,
+        }
+    }
+
+    /// The number of glyphs this rule needs to see at once, as calculated by
+    /// fontTools' `maxContextCalc`
+    pub fn max_context(&self) -> usize {
+        match self {
+            Positioning::Single(_) => 1,
+            Positioning::Pair(_)
+            | Positioning::Cursive(_)
+            | Positioning::MarkToBase(_)
+            | Positioning::MarkToLig(_)
+            | Positioning::MarkToMark(_) => 2,
+            Positioning::Contextual(v) => v.iter().map(|st| st.max_context()).max().unwrap_or(0),
+            Positioning::ChainedContextual(v) => {
+                v.iter().map(|st| st.max_context()).max().unwrap_or(0)
+            }
+        }
+    }
 }
 
 impl Lookup<Positioning> {
@@ -75,6 +182,18 @@ impl Lookup<Positioning> {
 /// The Glyph Positioning table
 pub type GPOS = GPOSGSUB<Positioning>;
 
+impl GPOS {
+    /// Computes the OS/2 usMaxContext value for the lookups in this table
+    pub fn max_context(&self) -> u16 {
+        self.lookups
+            .iter()
+            .map(|lookup| lookup.rule.max_context())
+            .max()
+            .unwrap_or(0)
+            .min(u16::MAX as usize) as u16
+    }
+}
+
 pub(crate) fn from_bytes(
     c: &mut ReaderContext,
     max_glyph_id: GlyphID,
@@ -194,6 +313,7 @@ impl FromLowlevel<GPOS10> for GPOS {
                 let lookup_highlevel: Lookup<Positioning> = Lookup {
                     flags: lookup_lowlevel.lookupFlag,
                     mark_filtering_set: lookup_lowlevel.markFilteringSet,
+                    extension: lookup_lowlevel.lookupType == 9,
                     rule: theirs,
                 };
                 lookups.push(lookup_highlevel)
@@ -261,12 +381,16 @@ impl ToLowlevel<GPOSLookupLowlevel> for Lookup<Positioning> {
                 .flatten()
                 .collect(),
         };
-        GPOSLookupLowlevel {
+        let mut lookup = GPOSLookupLowlevel {
             lookupType: self.lookup_type(),
             lookupFlag: self.flags,
             subtables: subtables.into(),
             markFilteringSet: self.mark_filtering_set,
+        };
+        if self.extension {
+            promote_to_extension(&mut lookup);
         }
+        lookup
     }
 }
 impl ToLowlevel<GPOS10> for GPOS {
@@ -292,8 +416,67 @@ pub(crate) fn to_bytes(
     data: &mut Vec<u8>,
     max_glyph_id: GlyphID,
 ) -> Result<(), SerializationError> {
-    let gpos10 = gpos.to_lowlevel(max_glyph_id);
-    gpos10.to_bytes(data)
+    // If the offsets overflow, try again with every lookup promoted to an
+    // extension lookup, and then with oversized subtables split in half
+    // for as long as there is something left to split.
+    let mut result = compile(gpos, max_glyph_id, false);
+    if result.is_err() {
+        result = compile(gpos, max_glyph_id, true);
+    }
+    if result.is_err() {
+        let mut gpos = gpos.clone();
+        while result.is_err() {
+            let mut split = false;
+            for lookup in gpos.lookups.iter_mut() {
+                split |= lookup.rule.split_oversized_subtables(max_glyph_id);
+            }
+            if !split {
+                break;
+            }
+            result = compile(&gpos, max_glyph_id, true);
+        }
+    }
+    data.extend(result?);
+    Ok(())
+}
+
+fn compile(
+    gpos: &GPOS,
+    max_glyph_id: GlyphID,
+    use_extension: bool,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut gpos10 = gpos.to_lowlevel(max_glyph_id);
+    if use_extension {
+        if let Some(lookup_list) = gpos10.lookupList.link.as_mut() {
+            for lookup in lookup_list.lookups.v.iter_mut() {
+                if let Some(lookup) = lookup.link.as_mut().filter(|l| l.lookupType != 9) {
+                    promote_to_extension(lookup);
+                }
+            }
+        }
+    }
+    let mut data = vec![];
+    gpos10.to_bytes(&mut data)?;
+    Ok(data)
+}
+
+/// Wraps each subtable of a lookup in an extension subtable, so that it can
+/// be placed anywhere in the table
+fn promote_to_extension(lookup: &mut GPOSLookupLowlevel) {
+    let subtables: Vec<Offset16<GPOSSubtable>> = std::mem::take(&mut lookup.subtables.v)
+        .into_iter()
+        .map(|subtable| {
+            Offset16::to(GPOSSubtable::GPOS9_1(Box::new(ExtensionPosFormat1 {
+                substFormat: 1,
+                extensionLookupType: lookup.lookupType,
+                extension: subtable
+                    .link
+                    .map_or_else(Offset32::to_nothing, Offset32::to),
+            })))
+        })
+        .collect();
+    lookup.subtables = subtables.into();
+    lookup.lookupType = 9;
 }
 
 #[cfg(test)]
@@ -375,6 +558,7 @@ pub(crate) mod tests {
         let expected = expected_gpos(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: false,
             rule: Positioning::Single(vec![SinglePos {
                 mapping: btreemap!(
                     37 => valuerecord!(xAdvance = 35),
@@ -385,4 +569,76 @@ pub(crate) mod tests {
         }]);
         assert_can_roundtrip(binary_gpos, &expected);
     }
+
+    #[test]
+    fn test_extension_roundtrip() {
+        let gpos = expected_gpos(vec![Lookup {
+            flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            extension: true,
+            rule: Positioning::Single(vec![SinglePos {
+                mapping: btreemap!(
+                    37 => valuerecord!(xAdvance = 35)
+                ),
+            }]),
+        }]);
+        let mut gpos_data = vec![];
+        to_bytes(&gpos, &mut gpos_data, 200).unwrap();
+
+        let mut rc = ReaderContext::new(gpos_data.clone());
+        let lowlevel: GPOS10 = rc.de().unwrap();
+        let lookup = lowlevel.lookupList.link.as_ref().unwrap().lookups.v[0]
+            .link
+            .as_ref()
+            .unwrap();
+        assert_eq!(lookup.lookupType, 9);
+        assert_can_roundtrip(gpos_data, &gpos);
+    }
+
+    #[test]
+    fn test_overflow_extension_and_split() {
+        // Too many (distinct) pair sets to address with 16-bit offsets from
+        // one subtable
+        let mut mapping = BTreeMap::new();
+        for left in 0..2000 {
+            for right in 2000..2010 {
+                mapping.insert(
+                    (left, right),
+                    (
+                        valuerecord!(xAdvance = -1 - left as i16),
+                        ValueRecord::new(),
+                    ),
+                );
+            }
+        }
+        let gpos = expected_gpos(vec![Lookup {
+            flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            extension: false,
+            rule: Positioning::Pair(vec![PairPos {
+                mapping: mapping.clone(),
+            }]),
+        }]);
+        let mut gpos_data = vec![];
+        to_bytes(&gpos, &mut gpos_data, 2010).unwrap();
+
+        let mut rc = ReaderContext::new(gpos_data);
+        let lowlevel: GPOS10 = rc.de().unwrap();
+        let lookup = lowlevel.lookupList.link.as_ref().unwrap().lookups.v[0]
+            .link
+            .as_ref()
+            .unwrap();
+        assert_eq!(lookup.lookupType, 9);
+
+        let rede = GPOS::from_lowlevel(lowlevel, 2010);
+        assert!(rede.lookups[0].extension);
+        if let Positioning::Pair(subtables) = &rede.lookups[0].rule {
+            assert_eq!(subtables.len(), 2);
+            let rede_mapping: crate::layout::gpos2::PairPositioningMap =
+                subtables.iter().flat_map(|st| st.mapping.clone()).collect();
+            assert_eq!(rede_mapping, mapping);
+        } else {
+            panic!("Wrong lookup type");
+        }
+    }
 }
@@ -1,4 +1,6 @@
-use crate::layout::common::{FromLowlevel, Lookup, ToLowlevel, GPOSGSUB};
+use crate::layout::common::{
+    split_map, split_oversized, FromLowlevel, Lookup, ToLowlevel, GPOSGSUB,
+};
 use crate::layout::contextual::{ChainedSequenceContext, SequenceContext};
 use crate::layout::gsub1::SingleSubst;
 use crate::layout::gsub2::MultipleSubst;
@@ -19,6 +21,7 @@ pub const TAG: Tag = crate::tag!("GSUB");
 ///
 /// Each rule is expressed as a vector of subtables.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Substitution {
     /// Contains a single substitution rule.
     Single(Vec<SingleSubst>),
@@ -50,6 +53,68 @@ impl Substitution {
             Substitution::ReverseChainContextual(v) => v.push(ReverseChainSubst::default()),
         }
     }
+
+    /// Splits in two any subtables which are too big to compile, returning
+    /// `true` if any were split. Contextual subtables cannot be split.
+    pub(crate) fn split_oversized_subtables(&mut self, max_glyph_id: GlyphID) -> bool {
+        match self {
+            Substitution::Single(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.mapping, |&g| g)
+                        .map(|(a, b)| (SingleSubst { mapping: a }, SingleSubst { mapping: b }))
+                },
+            ),
+            Substitution::Multiple(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.mapping, |&g| g)
+                        .map(|(a, b)| (MultipleSubst { mapping: a }, MultipleSubst { mapping: b }))
+                },
+            ),
+            Substitution::Alternate(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.mapping, |&g| g).map(|(a, b)| {
+                        (AlternateSubst { mapping: a }, AlternateSubst { mapping: b })
+                    })
+                },
+            ),
+            Substitution::Ligature(v) => split_oversized(
+                v,
+                |st| vec![st.to_lowlevel(max_glyph_id)],
+                |st| {
+                    split_map(&st.mapping, |sequence| sequence.first().copied())
+                        .map(|(a, b)| (LigatureSubst { mapping: a }, LigatureSubst { mapping: b }))
+                },
+            ),
+            _ => false,
+        }
+    }
+
+    /// The number of glyphs this rule needs to see at once, as calculated by
+    /// fontTools' `maxContextCalc`
+    pub fn max_context(&self) -> usize {
+        match self {
+            Substitution::Single(_) | Substitution::Multiple(_) | Substitution::Alternate(_) => 1,
+            Substitution::Ligature(v) => v
+                .iter()
+                .flat_map(|st| st.mapping.keys())
+                .map(|sequence| sequence.len())
+                .max()
+                .unwrap_or(0),
+            Substitution::Contextual(v) => v.iter().map(|st| st.max_context()).max().unwrap_or(0),
+            Substitution::ChainedContextual(v) => {
+                v.iter().map(|st| st.max_context()).max().unwrap_or(0)
+            }
+            Substitution::ReverseChainContextual(v) => {
+                v.iter().map(|st| 1 + st.lookahead.len()).max().unwrap_or(0)
+            }
+        }
+    }
 }
 
 impl Lookup<Substitution> {
@@ -71,6 +136,18 @@ impl Lookup<Substitution> {
 /// The Glyph Substitution table
 pub type GSUB = GPOSGSUB<Substitution>;
 
+impl GSUB {
+    /// Computes the OS/2 usMaxContext value for the lookups in this table
+    pub fn max_context(&self) -> u16 {
+        self.lookups
+            .iter()
+            .map(|lookup| lookup.rule.max_context())
+            .max()
+            .unwrap_or(0)
+            .min(u16::MAX as usize) as u16
+    }
+}
+
 pub(crate) fn from_bytes(
     c: &mut ReaderContext,
     max_glyph_id: GlyphID,
@@ -188,6 +265,7 @@ impl FromLowlevel<GSUB10> for GSUB {
                 let lookup_highlevel: Lookup<Substitution> = Lookup {
                     flags: lookup_lowlevel.lookupFlag,
                     mark_filtering_set: lookup_lowlevel.markFilteringSet,
+                    extension: lookup_lowlevel.lookupType == 7,
                     rule: theirs,
                 };
                 lookups.push(lookup_highlevel)
@@ -245,12 +323,16 @@ impl ToLowlevel<GSUBLookupLowlevel> for Lookup<Substitution> {
                 .map(|subtable| Offset16::to(subtable.to_lowlevel(max_glyph_id)))
                 .collect(),
         };
-        GSUBLookupLowlevel {
+        let mut lookup = GSUBLookupLowlevel {
             lookupType: self.lookup_type(),
             lookupFlag: self.flags,
             subtables: subtables.into(),
             markFilteringSet: self.mark_filtering_set,
+        };
+        if self.extension {
+            promote_to_extension(&mut lookup);
         }
+        lookup
     }
 }
 impl ToLowlevel<GSUB10> for GSUB {
@@ -276,8 +358,67 @@ pub(crate) fn to_bytes(
     data: &mut Vec<u8>,
     max_glyph_id: GlyphID,
 ) -> Result<(), SerializationError> {
-    let gsub10 = gsub.to_lowlevel(max_glyph_id);
-    gsub10.to_bytes(data)
+    // If the offsets overflow, try again with every lookup promoted to an
+    // extension lookup, and then with oversized subtables split in half
+    // for as long as there is something left to split.
+    let mut result = compile(gsub, max_glyph_id, false);
+    if result.is_err() {
+        result = compile(gsub, max_glyph_id, true);
+    }
+    if result.is_err() {
+        let mut gsub = gsub.clone();
+        while result.is_err() {
+            let mut split = false;
+            for lookup in gsub.lookups.iter_mut() {
+                split |= lookup.rule.split_oversized_subtables(max_glyph_id);
+            }
+            if !split {
+                break;
+            }
+            result = compile(&gsub, max_glyph_id, true);
+        }
+    }
+    data.extend(result?);
+    Ok(())
+}
+
+fn compile(
+    gsub: &GSUB,
+    max_glyph_id: GlyphID,
+    use_extension: bool,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut gsub10 = gsub.to_lowlevel(max_glyph_id);
+    if use_extension {
+        if let Some(lookup_list) = gsub10.lookupList.link.as_mut() {
+            for lookup in lookup_list.lookups.v.iter_mut() {
+                if let Some(lookup) = lookup.link.as_mut().filter(|l| l.lookupType != 7) {
+                    promote_to_extension(lookup);
+                }
+            }
+        }
+    }
+    let mut data = vec![];
+    gsub10.to_bytes(&mut data)?;
+    Ok(data)
+}
+
+/// Wraps each subtable of a lookup in an extension subtable, so that it can
+/// be placed anywhere in the table
+fn promote_to_extension(lookup: &mut GSUBLookupLowlevel) {
+    let subtables: Vec<Offset16<GSUBSubtable>> = std::mem::take(&mut lookup.subtables.v)
+        .into_iter()
+        .map(|subtable| {
+            Offset16::to(GSUBSubtable::GSUB7_1(Box::new(ExtensionSubstFormat1 {
+                substFormat: 1,
+                extensionLookupType: lookup.lookupType,
+                extension: subtable
+                    .link
+                    .map_or_else(Offset32::to_nothing, Offset32::to),
+            })))
+        })
+        .collect();
+    lookup.subtables = subtables.into();
+    lookup.lookupType = 7;
 }
 
 #[cfg(test)]
@@ -285,7 +426,7 @@ pub(crate) mod tests {
     use super::*;
     use crate::layout::common::{FeatureList, LanguageSystem, LookupFlags, Script, ScriptList};
     use crate::tag;
-    use otspec::btreemap;
+    use otspec::{btreemap, btreeset};
     use std::collections::BTreeMap;
     use std::iter::FromIterator;
 
@@ -336,12 +477,75 @@ pub(crate) mod tests {
         let expected = expected_gsub(vec![Lookup {
             flags: LookupFlags::empty(),
             mark_filtering_set: None,
+            extension: true,
             rule: Substitution::Single(vec![SingleSubst {
                 mapping: btreemap!(
                     66 => 67
                 ),
             }]),
         }]);
-        assert_can_deserialize(binary_gsub, &expected);
+        assert_can_deserialize(binary_gsub.clone(), &expected);
+        // The lookup is written back as an extension lookup
+        assert_can_roundtrip(binary_gsub, &expected);
+    }
+
+    #[test]
+    fn test_max_context() {
+        use crate::layout::contextual::ChainedSequenceContextRule;
+        let lookup = |rule| Lookup {
+            flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            extension: false,
+            rule,
+        };
+        let ligature = lookup(Substitution::Ligature(vec![LigatureSubst {
+            mapping: btreemap!(vec![1, 2, 3] => 4, vec![1, 2] => 5),
+        }]));
+        let chained = lookup(Substitution::ChainedContextual(vec![ChainedSequenceContext {
+            rules: vec![ChainedSequenceContextRule {
+                backtrack: vec![btreeset!(1), btreeset!(2)],
+                input: vec![(btreeset!(3), vec![0]), (btreeset!(4), vec![])],
+                lookahead: vec![btreeset!(5), btreeset!(6), btreeset!(7)],
+            }],
+        }]));
+        assert_eq!(expected_gsub(vec![]).max_context(), 0);
+        assert_eq!(expected_gsub(vec![ligature.clone()]).max_context(), 3);
+        // Backtrack glyphs don't count, but lookahead glyphs do
+        assert_eq!(expected_gsub(vec![ligature, chained]).max_context(), 5);
+    }
+
+    #[test]
+    fn test_overflow_extension_and_split() {
+        // A single substitution of 40000 glyphs is too big to address its
+        // coverage table with a 16-bit offset.
+        let mapping: BTreeMap<GlyphID, GlyphID> = (0..40000).map(|g| (g, 40000 - g)).collect();
+        let gsub = expected_gsub(vec![Lookup {
+            flags: LookupFlags::empty(),
+            mark_filtering_set: None,
+            extension: false,
+            rule: Substitution::Single(vec![SingleSubst {
+                mapping: mapping.clone(),
+            }]),
+        }]);
+        let mut gsub_data = vec![];
+        to_bytes(&gsub, &mut gsub_data, 40001).unwrap();
+
+        let mut rc = ReaderContext::new(gsub_data);
+        let lowlevel: GSUB10 = rc.de().unwrap();
+        let lookup = lowlevel.lookupList.link.as_ref().unwrap().lookups.v[0]
+            .link
+            .as_ref()
+            .unwrap();
+        assert_eq!(lookup.lookupType, 7);
+
+        let rede = GSUB::from_lowlevel(lowlevel, 40001);
+        if let Substitution::Single(subtables) = &rede.lookups[0].rule {
+            assert_eq!(subtables.len(), 2);
+            let rede_mapping: BTreeMap<GlyphID, GlyphID> =
+                subtables.iter().flat_map(|st| st.mapping.clone()).collect();
+            assert_eq!(rede_mapping, mapping);
+        } else {
+            panic!("Wrong lookup type");
+        }
     }
 }
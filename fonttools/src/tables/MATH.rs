@@ -2,7 +2,9 @@ use otspec::layout::coverage::Coverage;
 use otspec::layout::device::Device;
 
 use otspec::types::*;
-use otspec::{DeserializationError, Deserialize, Deserializer, Serialize, Serializer};
+use otspec::{
+    DeserializationError, Deserialize, Deserializer, SerializationError, Serialize, Serializer,
+};
 use otspec_macros::{tables, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -140,6 +142,7 @@ impl MathValueRecord {
 // Needs to be handled manually because of awkward layout
 #[allow(missing_docs, non_snake_case)]
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MathVariants {
     pub minConnectorOverlap: UFWORD,
     pub vertGlyphCoverage: Offset16<Coverage>,
@@ -198,10 +201,14 @@ impl Serialize for MathVariants {
         data.put(&self.horizGlyphConstruction)?;
         Ok(())
     }
+    fn ot_binary_size(&self) -> usize {
+        10 + 2 * (self.vertGlyphConstruction.len() + self.horizGlyphConstruction.len())
+    }
 }
 // Needs to be handled manually because of n+1 count in kernValues...
 #[allow(missing_docs, non_snake_case)]
 #[derive(Debug, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MathKern {
     pub heightCount: uint16,
     pub correctionHeight: Vec<MathValueRecord>,
@@ -223,6 +230,7 @@ impl Deserialize for MathKern {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The Mathematical Typesetting table
 pub struct MATH {
     /// Global constants for the mathematical typesetter
@@ -341,6 +349,97 @@ impl Deserialize for MATH {
     }
 }
 
+/// Builds a coverage table and its records from a glyph-keyed map
+fn coverage_and_records<T: Clone>(map: &BTreeMap<GlyphID, T>) -> (Offset16<Coverage>, Vec<T>) {
+    (
+        Offset16::to(Coverage {
+            glyphs: map.keys().copied().collect(),
+        }),
+        map.values().cloned().collect(),
+    )
+}
+
+impl MATH {
+    #[allow(non_snake_case)]
+    fn glyph_info(&self) -> MathGlyphInfo {
+        let mathItalicsCorrectionInfo = if self.italic_correction.is_empty() {
+            Offset16::to_nothing()
+        } else {
+            let (coverage, records) = coverage_and_records(&self.italic_correction);
+            Offset16::to(MathItalicsCorrectionInfo {
+                italicsCorrectionCoverage: coverage,
+                italicsCorrection: records,
+            })
+        };
+        let mathTopAccentAttachment = if self.top_accent_attachment.is_empty() {
+            Offset16::to_nothing()
+        } else {
+            let (coverage, records) = coverage_and_records(&self.top_accent_attachment);
+            Offset16::to(MathTopAccentAttachment {
+                topAccentCoverage: coverage,
+                topAccentAttachment: records,
+            })
+        };
+        let extendedShapeCoverage = if self.extended_shapes.is_empty() {
+            Offset16::to_nothing()
+        } else {
+            Offset16::to(Coverage {
+                glyphs: self.extended_shapes.iter().copied().collect(),
+            })
+        };
+        let mathKernInfo = if self.kerning.is_empty() {
+            Offset16::to_nothing()
+        } else {
+            let (coverage, records) = coverage_and_records(&self.kerning);
+            Offset16::to(MathKernInfo {
+                mathKernCoverage: coverage,
+                mathKernInfoRecords: records,
+            })
+        };
+        MathGlyphInfo {
+            mathItalicsCorrectionInfo,
+            mathTopAccentAttachment,
+            extendedShapeCoverage,
+            mathKernInfo,
+        }
+    }
+
+    #[allow(non_snake_case)]
+    fn variants(&self) -> MathVariants {
+        let (vertGlyphCoverage, vertical) = coverage_and_records(&self.vertical_extensions);
+        let (horizGlyphCoverage, horizontal) = coverage_and_records(&self.horizontal_extensions);
+        MathVariants {
+            minConnectorOverlap: self.min_overlap.unwrap_or(0),
+            vertGlyphCoverage,
+            horizGlyphCoverage,
+            vertGlyphCount: vertical.len() as uint16,
+            horizGlyphCount: horizontal.len() as uint16,
+            vertGlyphConstruction: vertical.into_iter().map(Offset16::to).collect(),
+            horizGlyphConstruction: horizontal.into_iter().map(Offset16::to).collect(),
+        }
+    }
+}
+
+impl Serialize for MATH {
+    fn to_bytes(&self, data: &mut Vec<u8>) -> Result<(), SerializationError> {
+        let has_variants = self.min_overlap.is_some()
+            || !self.vertical_extensions.is_empty()
+            || !self.horizontal_extensions.is_empty();
+        let core = MATHinternal {
+            majorVersion: 1,
+            minorVersion: 0,
+            mathConstants: Offset16::to(self.constants.clone()),
+            mathGlyphInfo: Offset16::to(self.glyph_info()),
+            mathVariants: if has_variants {
+                Offset16::to(self.variants())
+            } else {
+                Offset16::to_nothing()
+            },
+        };
+        core.to_bytes(data)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use otspec::btreemap;
@@ -559,6 +658,10 @@ mod tests {
                 ),
                 horizontal_extensions: BTreeMap::new(),
             },
-        )
+        );
+
+        let serialized = otspec::ser::to_bytes(&math).unwrap();
+        let roundtripped: MATH = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(roundtripped, math);
     }
 }
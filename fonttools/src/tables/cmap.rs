@@ -29,6 +29,7 @@ CmapHeader {
 );
 
 #[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types, non_snake_case)]
 struct cmap0 {
     format: uint16,
@@ -71,6 +72,7 @@ impl Deserialize for cmap0 {
 
 #[allow(non_camel_case_types, non_snake_case)]
 #[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A format 4 cmap subtable, used for mapping Unicode characters in the
 /// basic mutilingual plane.
 pub struct cmap4 {
@@ -501,6 +503,7 @@ impl cmap14 {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_snake_case)]
 /// A cmap subtable.
 ///
@@ -524,6 +527,7 @@ pub struct CmapSubtable {
     /// A mapping between Unicode codepoints and glyph IDs.
     pub mapping: BTreeMap<uint32, uint16>,
     /// A mapping of Unicode codepoints + glyph selectors to glyph IDs.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_map::option"))]
     pub uvs_mapping: Option<BTreeMap<(uint32, uint32), uint16>>,
 }
 
@@ -555,6 +559,7 @@ impl Serialize for CmapSubtable {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 /// cmap table. The cmap table is a collection of subtables, as described above.
 pub struct cmap {
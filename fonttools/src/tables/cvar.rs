@@ -0,0 +1,187 @@
+use crate::otvar::TupleVariationStore;
+use crate::otvar::{Delta, TupleIndexFlags, TupleVariation, TupleVariationHeader};
+use otspec::types::*;
+use otspec::{
+    DeserializationError, Deserializer, ReaderContext, SerializationError, Serialize, Serializer,
+};
+
+/// The 'cvar' OpenType tag.
+pub const TAG: Tag = crate::tag!("cvar");
+
+/// How the control values vary at one region of the design space.
+///
+/// (This is the user-friendly version of what is serialized as a TupleVariation)
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeltaSet {
+    /// The peak location at which this region is active.
+    pub peak: Tuple,
+    /// The location at which this region begins to be active.
+    pub start: Tuple,
+    /// The location at which this region is no longer active.
+    pub end: Tuple,
+    /// A list of deltas to be applied to the control values at the peak of
+    /// this region, one for each entry in the `cvt ` table.
+    pub deltas: Vec<i16>,
+}
+
+impl DeltaSet {
+    /// Whether the region's start and end are those implied by its peak,
+    /// and so need not be serialized
+    fn has_default_region(&self) -> bool {
+        self.peak
+            .iter()
+            .zip(self.start.iter().zip(self.end.iter()))
+            .all(|(&peak, (&start, &end))| {
+                let (default_start, default_end) =
+                    if peak > 0.0 { (0.0, 1.0) } else { (-1.0, 0.0) };
+                start == default_start && end == default_end
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(non_camel_case_types)]
+/// A CVT Variations table, describing how control values vary across the
+/// designspace.
+pub struct cvar {
+    /// A list of deltasets, containing deltas at particular designspace regions.
+    pub deltasets: Vec<DeltaSet>,
+}
+
+/// Constructs a `cvar` object from a binary table, given the number of axes
+/// in the `fvar` table and the number of values in the `cvt ` table.
+pub fn from_bytes(
+    s: &[u8],
+    axis_count: uint16,
+    cvt_count: uint16,
+) -> Result<cvar, DeserializationError> {
+    let mut c = ReaderContext::new(s.to_vec());
+    let _major_version: uint16 = c.de()?;
+    let _minor_version: uint16 = c.de()?;
+    let tvs = TupleVariationStore::from_bytes(&mut c, axis_count, false, cvt_count)?;
+    let mut deltasets = vec![];
+    for tv in tvs.0 {
+        // cvar has no shared tuples, so each peak must be embedded
+        let peak = tv
+            .0
+            .peakTuple
+            .ok_or_else(|| DeserializationError("cvar tuple variation has no peak".to_string()))?;
+        let start = tv.0.startTuple.unwrap_or_else(|| {
+            peak.iter()
+                .map(|&x| if x > 0.0 { 0.0 } else { -1.0 })
+                .collect()
+        });
+        let end = tv.0.endTuple.unwrap_or_else(|| {
+            peak.iter()
+                .map(|&x| if x > 0.0 { 1.0 } else { 0.0 })
+                .collect()
+        });
+        let deltas =
+            tv.1.iter()
+                .map(|d| match d {
+                    Some(Delta::Delta1D(d)) => *d,
+                    _ => 0,
+                })
+                .collect();
+        deltasets.push(DeltaSet {
+            peak,
+            start,
+            end,
+            deltas,
+        });
+    }
+    Ok(cvar { deltasets })
+}
+
+impl Serialize for cvar {
+    fn to_bytes(&self, data: &mut Vec<u8>) -> Result<(), SerializationError> {
+        let variations: Vec<TupleVariation> = self
+            .deltasets
+            .iter()
+            .map(|ds| {
+                let mut flags = TupleIndexFlags::EMBEDDED_PEAK_TUPLE;
+                let intermediate = !ds.has_default_region();
+                if intermediate {
+                    flags |= TupleIndexFlags::INTERMEDIATE_REGION;
+                }
+                let header = TupleVariationHeader {
+                    size: 0, // This will be filled in when serializing the TVS
+                    flags,
+                    sharedTupleIndex: 0,
+                    peakTuple: Some(ds.peak.clone()),
+                    startTuple: intermediate.then(|| ds.start.clone()),
+                    endTuple: intermediate.then(|| ds.end.clone()),
+                };
+                let deltas = ds.deltas.iter().map(|&d| Some(Delta::Delta1D(d))).collect();
+                TupleVariation(header, deltas)
+            })
+            .collect();
+        let mut tvs = otspec::ser::to_bytes(&TupleVariationStore(variations))?;
+        // The store's data offset is relative to the store, but cvar's is
+        // relative to the start of the table, before the version
+        let offset = u16::from_be_bytes([tvs[2], tvs[3]]) + 4;
+        tvs[2..4].copy_from_slice(&offset.to_be_bytes());
+        data.put(1_u16)?;
+        data.put(0_u16)?;
+        data.put(tvs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cvar_de() {
+        // One tuple with embedded peak at wght=1.0 and private deltas for
+        // all points: +100
+        let binary_cvar = vec![
+            0x00, 0x01, 0x00, 0x00, // version
+            0x00, 0x01, // tupleVariationCount
+            0x00, 0x0E, // dataOffset
+            0x00, 0x03, // variationDataSize
+            0xA0, 0x00, // EMBEDDED_PEAK_TUPLE | PRIVATE_POINT_NUMBERS
+            0x40, 0x00, // peak 1.0
+            0x00, // all points
+            0x00, 0x64, // one byte delta of 100
+        ];
+        let deserialized = from_bytes(&binary_cvar, 1, 1).unwrap();
+        assert_eq!(
+            deserialized,
+            cvar {
+                deltasets: vec![DeltaSet {
+                    peak: vec![1.0],
+                    start: vec![0.0],
+                    end: vec![1.0],
+                    deltas: vec![100],
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn cvar_roundtrip() {
+        let table = cvar {
+            deltasets: vec![
+                DeltaSet {
+                    peak: vec![1.0, 0.0],
+                    start: vec![0.0, -1.0],
+                    end: vec![1.0, 0.0],
+                    deltas: vec![10, 0, -300],
+                },
+                DeltaSet {
+                    peak: vec![0.5, 0.0],
+                    start: vec![0.0, 0.0],
+                    end: vec![1.0, 0.0],
+                    deltas: vec![-1, 2, 3],
+                },
+            ],
+        };
+        let serialized = otspec::ser::to_bytes(&table).unwrap();
+        // The data follows the two headers, of 8 and 16 bytes
+        assert_eq!(&serialized[..8], &[0, 1, 0, 0, 0x80, 2, 0, 32]);
+        assert_eq!(from_bytes(&serialized, 2, 3).unwrap(), table);
+    }
+}
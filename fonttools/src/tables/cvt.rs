@@ -9,8 +9,13 @@ pub const TAG: Tag = crate::tag!("cvt ");
 
 /// Represents a font's cvt (Control Value) table
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 #[allow(non_camel_case_types)]
-pub struct cvt(Vec<FWORD>);
+pub struct cvt(pub Vec<FWORD>);
 
 impl Deserialize for cvt {
     fn from_bytes(c: &mut ReaderContext) -> Result<Self, DeserializationError> {
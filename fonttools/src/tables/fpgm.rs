@@ -8,8 +8,13 @@ pub const TAG: Tag = crate::tag!("fpgm");
 
 /// Represents a font's fpgm (Font Program) table
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 #[allow(non_camel_case_types)]
-pub struct fpgm(Vec<uint8>);
+pub struct fpgm(pub Vec<uint8>);
 
 impl Deserialize for fpgm {
     fn from_bytes(c: &mut ReaderContext) -> Result<Self, DeserializationError> {
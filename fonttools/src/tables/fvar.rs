@@ -31,6 +31,7 @@ tables!(
 
 /// Struct representing a named instance within the variable font's design space
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_snake_case)]
 pub struct InstanceRecord {
     /// The name ID for entries in the 'name' table that provide subfamily names for this instance.
@@ -73,6 +74,7 @@ impl InstanceRecord {
 
 /// Represents a font's fvar (Font Variations) table
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 pub struct fvar {
     /// The font's axes of variation
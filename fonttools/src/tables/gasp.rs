@@ -20,6 +20,7 @@ gasp {
 
 bitflags! {
     #[derive(Serialize, Deserialize)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     /// Flags which determine how grid-fitting should be carried out
     pub struct RangeGaspBehaviorFlags: u16 {
         /// Use gridfitting
@@ -10,6 +10,7 @@ bitflags! {
     ///
     /// These are computed automatically, so you don't need to worry about them.
     #[derive(Serialize, Deserialize)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct ComponentFlags: u16 {
         ///  If this is set, the arguments are 16-bit (uint16 or int16); otherwise, they are bytes (uint8 or int8).
         const ARG_1_AND_2_ARE_WORDS = 0x0001;
@@ -53,6 +54,7 @@ enum ComponentScalingMode {
 
 /// A high-level representation of a component within a glyph
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Component {
     /// The glyph ID that this component references.
     pub glyph_index: uint16,
@@ -1,5 +1,9 @@
 use super::Point;
-use kurbo::{PathEl, PathSeg};
+use kurbo::{BezPath, CubicBez, ParamCurve, PathEl, PathSeg, QuadBez, Vec2};
+use std::fmt;
+
+/// The maximum number of quadratic segments used to approximate one cubic.
+pub const MAX_N: usize = 100;
 
 /// Adds explicit oncurve points to a contour
 pub fn insert_explicit_oncurves(contour: &mut Vec<Point>) {
@@ -130,3 +134,462 @@ pub fn glyf_contour_to_kurbo_contour(contour: &[Point]) -> kurbo::BezPath {
     path.close_path();
     path
 }
+
+/// An error encountered when converting cubic contours to quadratics
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cu2QuError {
+    /// The masters have differing numbers of contours
+    IncompatibleContourCount(Vec<usize>),
+    /// The given contour has different segment structure in different masters
+    IncompatibleContour(usize),
+    /// The given cubic segment could not be approximated within the tolerance
+    /// using at most `MAX_N` quadratics
+    ApproximationNotFound {
+        /// Index of the contour
+        contour: usize,
+        /// Index of the path element within the contour
+        segment: usize,
+    },
+}
+
+impl fmt::Display for Cu2QuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cu2QuError::IncompatibleContourCount(counts) => {
+                write!(f, "incompatible contour counts: {:?}", counts)
+            }
+            Cu2QuError::IncompatibleContour(ix) => write!(f, "incompatible contour {}", ix),
+            Cu2QuError::ApproximationNotFound { contour, segment } => write!(
+                f,
+                "could not approximate segment {} of contour {}",
+                segment, contour
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Cu2QuError {}
+
+/// The result of converting a set of compatible cubic contours to quadratics
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuadraticContours {
+    /// The converted contours, one list of contours per master.
+    pub contours: Vec<Vec<Vec<Point>>>,
+    /// The largest distance measured between a cubic curve and its quadratic
+    /// approximation, across all curves and masters.
+    pub max_error: f64,
+}
+
+/// Converts the contours of a glyph in several masters to quadratic contours
+/// which remain point-compatible.
+///
+/// `masters` contains, for each master, the glyph's contours; these must have
+/// the same structure in every master. Each cubic segment is converted together
+/// with the corresponding segments in the other masters, so that all masters
+/// use the same number of quadratics for it, and no approximation deviates by
+/// more than `max_err` units from its cubic. If `reverse_direction` is true,
+/// the contours are reversed, as is usually needed when turning PostScript
+/// outlines into TrueType outlines.
+pub fn kurbo_contours_to_compatible_glyf_contours(
+    masters: &[Vec<BezPath>],
+    max_err: f64,
+    reverse_direction: bool,
+) -> Result<QuadraticContours, Cu2QuError> {
+    let counts: Vec<usize> = masters.iter().map(|m| m.len()).collect();
+    if counts.windows(2).any(|w| w[0] != w[1]) {
+        return Err(Cu2QuError::IncompatibleContourCount(counts));
+    }
+    let mut contours: Vec<Vec<Vec<Point>>> = masters.iter().map(|_| vec![]).collect();
+    let mut max_error = 0.0_f64;
+    for contour_ix in 0..counts.first().copied().unwrap_or(0) {
+        let paths: Vec<&BezPath> = masters.iter().map(|m| &m[contour_ix]).collect();
+        let (converted, error) = compatible_contour(&paths, contour_ix, max_err)?;
+        max_error = max_error.max(error);
+        for (master_contours, mut contour) in contours.iter_mut().zip(converted) {
+            if reverse_direction {
+                contour.reverse();
+            }
+            master_contours.push(contour);
+        }
+    }
+    Ok(QuadraticContours {
+        contours,
+        max_error,
+    })
+}
+
+fn to_glyf_point(pt: kurbo::Point, on_curve: bool) -> Point {
+    Point {
+        x: pt.x.round() as i16,
+        y: pt.y.round() as i16,
+        on_curve,
+    }
+}
+
+fn compatible_contour(
+    paths: &[&BezPath],
+    contour_ix: usize,
+    max_err: f64,
+) -> Result<(Vec<Vec<Point>>, f64), Cu2QuError> {
+    let incompatible = Cu2QuError::IncompatibleContour(contour_ix);
+    let elements: Vec<&[PathEl]> = paths.iter().map(|p| p.elements()).collect();
+    let len = elements.first().map_or(0, |e| e.len());
+    if elements.iter().any(|e| e.len() != len) {
+        return Err(incompatible);
+    }
+    let mut out: Vec<Vec<Point>> = paths.iter().map(|_| vec![]).collect();
+    let mut max_error = 0.0_f64;
+    let mut current: Vec<kurbo::Point> = vec![kurbo::Point::ZERO; paths.len()];
+    let mut start: Vec<kurbo::Point> = current.clone();
+
+    for el_ix in 0..len {
+        let els: Vec<PathEl> = elements.iter().map(|e| e[el_ix]).collect();
+        match els[0] {
+            PathEl::MoveTo(_) | PathEl::LineTo(_) => {
+                for (m, el) in els.iter().enumerate() {
+                    match el {
+                        PathEl::MoveTo(p) => {
+                            start[m] = *p;
+                            current[m] = *p;
+                            out[m].push(to_glyf_point(*p, true));
+                        }
+                        PathEl::LineTo(p) => {
+                            current[m] = *p;
+                            out[m].push(to_glyf_point(*p, true));
+                        }
+                        _ => return Err(incompatible),
+                    }
+                }
+                if !els
+                    .iter()
+                    .all(|el| std::mem::discriminant(el) == std::mem::discriminant(&els[0]))
+                {
+                    return Err(incompatible);
+                }
+            }
+            PathEl::QuadTo(_, _) => {
+                for (m, el) in els.iter().enumerate() {
+                    if let PathEl::QuadTo(p1, p2) = el {
+                        current[m] = *p2;
+                        out[m].push(to_glyf_point(*p1, false));
+                        out[m].push(to_glyf_point(*p2, true));
+                    } else {
+                        return Err(incompatible);
+                    }
+                }
+            }
+            PathEl::CurveTo(_, _, _) => {
+                let mut curves = vec![];
+                for (m, el) in els.iter().enumerate() {
+                    if let PathEl::CurveTo(p1, p2, p3) = el {
+                        curves.push(CubicBez::new(current[m], *p1, *p2, *p3));
+                        current[m] = *p3;
+                    } else {
+                        return Err(incompatible);
+                    }
+                }
+                let max_errors = vec![max_err; curves.len()];
+                let splines = curves_to_quadratic(&curves, &max_errors).ok_or(
+                    Cu2QuError::ApproximationNotFound {
+                        contour: contour_ix,
+                        segment: el_ix,
+                    },
+                )?;
+                for ((contour, spline), curve) in out.iter_mut().zip(splines).zip(curves) {
+                    max_error = max_error.max(spline_error(&curve, &spline));
+                    // Skip the spline start, because we already have a point for that
+                    let last = spline.len() - 1;
+                    for (ix, pt) in spline.iter().enumerate().skip(1) {
+                        contour.push(to_glyf_point(*pt, ix == last));
+                    }
+                }
+            }
+            PathEl::ClosePath => {
+                if !els.iter().all(|el| *el == PathEl::ClosePath) {
+                    return Err(incompatible);
+                }
+                // Drop the closing on-curve point if it duplicates the start
+                // point in every master; the decision must be the same for all
+                // masters to keep them point-compatible.
+                if current.iter().zip(start.iter()).all(|(c, s)| c == s)
+                    && out.iter().all(|c| c.len() > 1)
+                {
+                    for contour in out.iter_mut() {
+                        contour.pop();
+                    }
+                }
+            }
+        }
+    }
+    Ok((out, max_error))
+}
+
+/// Approximates a cubic Bézier curve with a quadratic spline.
+///
+/// The spline is returned as a list of points: the start point, one or more
+/// off-curve points (with implied on-curve points between them) and the end
+/// point. Returns `None` if no spline of at most `MAX_N` segments is within
+/// `max_err` of the curve.
+pub fn curve_to_quadratic(curve: &CubicBez, max_err: f64) -> Option<Vec<kurbo::Point>> {
+    (1..=MAX_N).find_map(|n| cubic_approx_spline(curve, n, max_err))
+}
+
+/// Approximates a set of cubic Bézier curves with compatible quadratic splines.
+///
+/// All the returned splines have the same number of segments, and each spline
+/// is within the corresponding entry of `max_errors` of its curve. Returns
+/// `None` if this is not possible using at most `MAX_N` segments.
+pub fn curves_to_quadratic(
+    curves: &[CubicBez],
+    max_errors: &[f64],
+) -> Option<Vec<Vec<kurbo::Point>>> {
+    assert_eq!(curves.len(), max_errors.len());
+    if curves.is_empty() {
+        return Some(vec![]);
+    }
+    let mut splines: Vec<Vec<kurbo::Point>> = vec![vec![]; curves.len()];
+    let mut n = 1;
+    let mut i = 0;
+    let mut last_i = 0;
+    loop {
+        match cubic_approx_spline(&curves[i], n, max_errors[i]) {
+            Some(spline) => {
+                splines[i] = spline;
+                i = (i + 1) % curves.len();
+                if i == last_i {
+                    return Some(splines);
+                }
+            }
+            None => {
+                if n == MAX_N {
+                    return None;
+                }
+                n += 1;
+                last_i = i;
+            }
+        }
+    }
+}
+
+/// Measures the largest distance between a cubic curve and a quadratic
+/// spline approximating it, by sampling both at the same parameter values.
+fn spline_error(curve: &CubicBez, spline: &[kurbo::Point]) -> f64 {
+    const SAMPLES: usize = 8;
+    let n = spline.len() - 2;
+    let mut max_error = 0.0_f64;
+    for seg in 0..n {
+        let p0 = if seg == 0 {
+            spline[0]
+        } else {
+            spline[seg].midpoint(spline[seg + 1])
+        };
+        let p2 = if seg == n - 1 {
+            spline[n + 1]
+        } else {
+            spline[seg + 1].midpoint(spline[seg + 2])
+        };
+        let quad = QuadBez::new(p0, spline[seg + 1], p2);
+        for s in 0..=SAMPLES {
+            let t = s as f64 / SAMPLES as f64;
+            let cubic_t = (seg as f64 + t) / n as f64;
+            max_error = max_error.max((quad.eval(t) - curve.eval(cubic_t)).hypot());
+        }
+    }
+    max_error
+}
+
+// The remainder of this file is a port of the cu2qu algorithm from the Python
+// fontTools library. Points are handled as vectors to keep the arithmetic
+// close to the original.
+
+fn cubic_approx_control(t: f64, p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2) -> Vec2 {
+    let p1 = p0 + (p1 - p0) * 1.5;
+    let p2 = p3 + (p2 - p3) * 1.5;
+    p1 + (p2 - p1) * t
+}
+
+/// Finds the intersection of the lines a-b and c-d, if there is one.
+fn calc_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> Option<Vec2> {
+    let ab = b - a;
+    let cd = d - c;
+    let p = Vec2::new(-ab.y, ab.x);
+    let denominator = p.dot(cd);
+    if denominator == 0.0 {
+        return None;
+    }
+    let h = p.dot(a - c) / denominator;
+    Some(c + cd * h)
+}
+
+/// Checks whether a cubic (given as the difference between two curves)
+/// stays within a circle of radius `tolerance` around the origin.
+fn cubic_farthest_fit_inside(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, tolerance: f64) -> bool {
+    if p2.hypot() <= tolerance && p1.hypot() <= tolerance {
+        return true;
+    }
+    let mid = (p0 + (p1 + p2) * 3.0 + p3) * 0.125;
+    if mid.hypot() > tolerance {
+        return false;
+    }
+    let deriv3 = (p3 + p2 - p1 - p0) * 0.125;
+    cubic_farthest_fit_inside(p0, (p0 + p1) * 0.5, mid - deriv3, mid, tolerance)
+        && cubic_farthest_fit_inside(mid, mid + deriv3, (p2 + p3) * 0.5, p3, tolerance)
+}
+
+fn cubic_approx_quadratic(cubic: [Vec2; 4], tolerance: f64) -> Option<Vec<kurbo::Point>> {
+    let q1 = calc_intersect(cubic[0], cubic[1], cubic[2], cubic[3])?;
+    let c0 = cubic[0];
+    let c3 = cubic[3];
+    let c1 = c0 + (q1 - c0) * (2.0 / 3.0);
+    let c2 = c3 + (q1 - c3) * (2.0 / 3.0);
+    if !cubic_farthest_fit_inside(
+        Vec2::ZERO,
+        c1 - cubic[1],
+        c2 - cubic[2],
+        Vec2::ZERO,
+        tolerance,
+    ) {
+        return None;
+    }
+    Some(vec![c0.to_point(), q1.to_point(), c3.to_point()])
+}
+
+fn cubic_to_vecs(c: &CubicBez) -> [Vec2; 4] {
+    [
+        c.p0.to_vec2(),
+        c.p1.to_vec2(),
+        c.p2.to_vec2(),
+        c.p3.to_vec2(),
+    ]
+}
+
+fn cubic_approx_spline(curve: &CubicBez, n: usize, tolerance: f64) -> Option<Vec<kurbo::Point>> {
+    if n == 1 {
+        return cubic_approx_quadratic(cubic_to_vecs(curve), tolerance);
+    }
+    let cubics: Vec<[Vec2; 4]> = (0..n)
+        .map(|i| cubic_to_vecs(&curve.subsegment(i as f64 / n as f64..(i + 1) as f64 / n as f64)))
+        .collect();
+
+    let [c0, _, _, c3] = cubic_to_vecs(curve);
+    let mut next_q1 =
+        cubic_approx_control(0.0, cubics[0][0], cubics[0][1], cubics[0][2], cubics[0][3]);
+    let mut q2 = c0;
+    let mut d1 = Vec2::ZERO;
+    let mut spline = vec![c0, next_q1];
+    for i in 1..=n {
+        let [_, c1, c2, c3_i] = cubics[i - 1];
+        let q0 = q2;
+        let q1 = next_q1;
+        if i < n {
+            let [n0, n1, n2, n3] = cubics[i];
+            next_q1 = cubic_approx_control(i as f64 / (n - 1) as f64, n0, n1, n2, n3);
+            spline.push(next_q1);
+            q2 = (q1 + next_q1) * 0.5;
+        } else {
+            q2 = c3_i;
+        }
+        let d0 = d1;
+        d1 = q2 - c3_i;
+        if d1.hypot() > tolerance
+            || !cubic_farthest_fit_inside(
+                d0,
+                q0 + (q1 - q0) * (2.0 / 3.0) - c1,
+                q2 + (q1 - q2) * (2.0 / 3.0) - c2,
+                d1,
+                tolerance,
+            )
+        {
+            return None;
+        }
+    }
+    spline.push(c3);
+    Some(spline.into_iter().map(|v| v.to_point()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_with_curve(bulge: f64) -> BezPath {
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((0.0, 100.0));
+        path.curve_to((bulge, 100.0 + bulge), (100.0, bulge), (100.0, 0.0));
+        path.line_to((0.0, 0.0));
+        path.close_path();
+        path
+    }
+
+    #[test]
+    fn test_curve_to_quadratic() {
+        let curve = CubicBez::new((0.0, 0.0), (0.0, 55.0), (45.0, 100.0), (100.0, 100.0));
+        let spline = curve_to_quadratic(&curve, 1.0).unwrap();
+        assert_eq!(spline[0], curve.p0);
+        assert_eq!(*spline.last().unwrap(), curve.p3);
+        assert!(spline.len() > 3);
+        assert!(spline_error(&curve, &spline) <= 1.0);
+
+        // A degree-elevated quadratic needs only one segment
+        let quadratic = CubicBez::new((0.0, 0.0), (0.0, 100.0), (100.0, 200.0), (300.0, 300.0));
+        assert_eq!(curve_to_quadratic(&quadratic, 0.5).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_compatible_conversion() {
+        let masters = vec![
+            vec![square_with_curve(10.0)],
+            vec![square_with_curve(400.0)],
+        ];
+        let result = kurbo_contours_to_compatible_glyf_contours(&masters, 1.0, false).unwrap();
+        assert_eq!(result.contours.len(), 2);
+        let light = &result.contours[0][0];
+        let bold = &result.contours[1][0];
+        assert_eq!(light.len(), bold.len());
+        for (a, b) in light.iter().zip(bold.iter()) {
+            assert_eq!(a.on_curve, b.on_curve);
+        }
+        // The closing point duplicates the start and is dropped
+        assert_eq!(
+            light[0],
+            Point {
+                x: 0,
+                y: 0,
+                on_curve: true
+            }
+        );
+        assert_ne!(*light.last().unwrap(), light[0]);
+        assert!(result.max_error <= 1.0);
+        assert!(result.max_error > 0.0);
+
+        // Converting the light master on its own needs fewer points
+        let alone = kurbo_contours_to_compatible_glyf_contours(&masters[0..1], 1.0, false).unwrap();
+        assert!(alone.contours[0][0].len() < light.len());
+
+        let reversed = kurbo_contours_to_compatible_glyf_contours(&masters, 1.0, true).unwrap();
+        let mut expected = light.clone();
+        expected.reverse();
+        assert_eq!(reversed.contours[0][0], expected);
+    }
+
+    #[test]
+    fn test_incompatible_conversion() {
+        let mut line = BezPath::new();
+        line.move_to((0.0, 0.0));
+        line.line_to((0.0, 100.0));
+        line.line_to((100.0, 100.0));
+        line.line_to((100.0, 0.0));
+        line.line_to((0.0, 0.0));
+        line.close_path();
+        let masters = vec![vec![square_with_curve(10.0)], vec![line.clone()]];
+        assert_eq!(
+            kurbo_contours_to_compatible_glyf_contours(&masters, 1.0, false),
+            Err(Cu2QuError::IncompatibleContour(0))
+        );
+        let masters = vec![vec![square_with_curve(10.0)], vec![line.clone(), line]];
+        assert_eq!(
+            kurbo_contours_to_compatible_glyf_contours(&masters, 1.0, false),
+            Err(Cu2QuError::IncompatibleContourCount(vec![1, 2]))
+        );
+    }
+}
@@ -33,6 +33,7 @@ bitflags! {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_snake_case)]
 /// A higher-level representation of a TrueType outline glyph.
 pub struct Glyph {
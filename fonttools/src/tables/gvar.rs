@@ -34,6 +34,7 @@ tables!( gvarcore {
 ///
 /// (This is the user-friendly version of what is serialized as a TupleVariation)
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeltaSet {
     /// The peak location at which this region is active.
     pub peak: Tuple,
@@ -145,6 +146,7 @@ impl DeltaSet {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A description of how an individual glyph's outline varies across the designspace.
 pub struct GlyphVariationData {
     /// A list of designsets, containing deltas at particular designspace regions.
@@ -152,6 +154,7 @@ pub struct GlyphVariationData {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 /// A Glyph Variations table, describing how glyph outlines vary across the
 /// designspace.
@@ -9,6 +9,7 @@ pub const TAG: Tag = crate::tag!("hmtx");
 
 /// A single horizontal metric
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_snake_case)]
 pub struct Metric {
     /// The full horizontal advance width of the glyph
@@ -19,6 +20,7 @@ pub struct Metric {
 
 /// The horizontal metrics table
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 pub struct hmtx {
     /// The list of metrics, corresponding to the glyph order
@@ -33,6 +33,7 @@ maxp10 {
 /// different fields. The enum allows a single maxp object to represent
 /// both versions.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MaxpVariant {
     /// This table is a maxp version 0.5
     Maxp05(maxp05),
@@ -52,6 +53,7 @@ impl Serialize for MaxpVariant {
 /// A maxp table, regardless of version.
 #[allow(non_snake_case, non_camel_case_types)]
 #[derive(Clone, Debug, Serialize, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct maxp {
     /// The version number as a fixed U16F16 value (for ease of serialization)
     #[otspec(with = "Version16Dot16")]
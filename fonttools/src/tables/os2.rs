@@ -9,6 +9,182 @@ use std::collections::{BTreeMap, HashSet};
 /// The 'OS/2' OpenType tag.
 pub const TAG: Tag = crate::tag!("OS/2");
 
+/// The Unicode blocks assigned to each bit of the ulUnicodeRange fields, as
+/// (bit, first codepoint, last codepoint). See
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/os2#ur
+#[rustfmt::skip]
+const UNICODE_RANGES: &[(u8, u32, u32)] = &[
+    (0, 0x0000, 0x007F), // Basic Latin
+    (1, 0x0080, 0x00FF), // Latin-1 Supplement
+    (2, 0x0100, 0x017F), // Latin Extended-A
+    (3, 0x0180, 0x024F), // Latin Extended-B
+    (4, 0x0250, 0x02AF), // IPA Extensions
+    (4, 0x1D00, 0x1D7F), // Phonetic Extensions
+    (4, 0x1D80, 0x1DBF), // Phonetic Extensions Supplement
+    (5, 0x02B0, 0x02FF), // Spacing Modifier Letters
+    (5, 0xA700, 0xA71F), // Modifier Tone Letters
+    (6, 0x0300, 0x036F), // Combining Diacritical Marks
+    (6, 0x1DC0, 0x1DFF), // Combining Diacritical Marks Supplement
+    (7, 0x0370, 0x03FF), // Greek and Coptic
+    (8, 0x2C80, 0x2CFF), // Coptic
+    (9, 0x0400, 0x04FF), // Cyrillic
+    (9, 0x0500, 0x052F), // Cyrillic Supplement
+    (9, 0x2DE0, 0x2DFF), // Cyrillic Extended-A
+    (9, 0xA640, 0xA69F), // Cyrillic Extended-B
+    (10, 0x0530, 0x058F), // Armenian
+    (11, 0x0590, 0x05FF), // Hebrew
+    (12, 0xA500, 0xA63F), // Vai
+    (13, 0x0600, 0x06FF), // Arabic
+    (13, 0x0750, 0x077F), // Arabic Supplement
+    (14, 0x07C0, 0x07FF), // NKo
+    (15, 0x0900, 0x097F), // Devanagari
+    (16, 0x0980, 0x09FF), // Bengali
+    (17, 0x0A00, 0x0A7F), // Gurmukhi
+    (18, 0x0A80, 0x0AFF), // Gujarati
+    (19, 0x0B00, 0x0B7F), // Oriya
+    (20, 0x0B80, 0x0BFF), // Tamil
+    (21, 0x0C00, 0x0C7F), // Telugu
+    (22, 0x0C80, 0x0CFF), // Kannada
+    (23, 0x0D00, 0x0D7F), // Malayalam
+    (24, 0x0E00, 0x0E7F), // Thai
+    (25, 0x0E80, 0x0EFF), // Lao
+    (26, 0x10A0, 0x10FF), // Georgian
+    (26, 0x2D00, 0x2D2F), // Georgian Supplement
+    (27, 0x1B00, 0x1B7F), // Balinese
+    (28, 0x1100, 0x11FF), // Hangul Jamo
+    (29, 0x1E00, 0x1EFF), // Latin Extended Additional
+    (29, 0x2C60, 0x2C7F), // Latin Extended-C
+    (29, 0xA720, 0xA7FF), // Latin Extended-D
+    (30, 0x1F00, 0x1FFF), // Greek Extended
+    (31, 0x2000, 0x206F), // General Punctuation
+    (31, 0x2E00, 0x2E7F), // Supplemental Punctuation
+    (32, 0x2070, 0x209F), // Superscripts And Subscripts
+    (33, 0x20A0, 0x20CF), // Currency Symbols
+    (34, 0x20D0, 0x20FF), // Combining Diacritical Marks For Symbols
+    (35, 0x2100, 0x214F), // Letterlike Symbols
+    (36, 0x2150, 0x218F), // Number Forms
+    (37, 0x2190, 0x21FF), // Arrows
+    (37, 0x27F0, 0x27FF), // Supplemental Arrows-A
+    (37, 0x2900, 0x297F), // Supplemental Arrows-B
+    (37, 0x2B00, 0x2BFF), // Miscellaneous Symbols and Arrows
+    (38, 0x2200, 0x22FF), // Mathematical Operators
+    (38, 0x2A00, 0x2AFF), // Supplemental Mathematical Operators
+    (38, 0x27C0, 0x27EF), // Miscellaneous Mathematical Symbols-A
+    (38, 0x2980, 0x29FF), // Miscellaneous Mathematical Symbols-B
+    (39, 0x2300, 0x23FF), // Miscellaneous Technical
+    (40, 0x2400, 0x243F), // Control Pictures
+    (41, 0x2440, 0x245F), // Optical Character Recognition
+    (42, 0x2460, 0x24FF), // Enclosed Alphanumerics
+    (43, 0x2500, 0x257F), // Box Drawing
+    (44, 0x2580, 0x259F), // Block Elements
+    (45, 0x25A0, 0x25FF), // Geometric Shapes
+    (46, 0x2600, 0x26FF), // Miscellaneous Symbols
+    (47, 0x2700, 0x27BF), // Dingbats
+    (48, 0x3000, 0x303F), // CJK Symbols And Punctuation
+    (49, 0x3040, 0x309F), // Hiragana
+    (50, 0x30A0, 0x30FF), // Katakana
+    (50, 0x31F0, 0x31FF), // Katakana Phonetic Extensions
+    (51, 0x3100, 0x312F), // Bopomofo
+    (51, 0x31A0, 0x31BF), // Bopomofo Extended
+    (52, 0x3130, 0x318F), // Hangul Compatibility Jamo
+    (53, 0xA840, 0xA87F), // Phags-pa
+    (54, 0x3200, 0x32FF), // Enclosed CJK Letters And Months
+    (55, 0x3300, 0x33FF), // CJK Compatibility
+    (56, 0xAC00, 0xD7AF), // Hangul Syllables
+    (57, 0xD800, 0xDFFF), // Non-Plane 0
+    (58, 0x10900, 0x1091F), // Phoenician
+    (59, 0x4E00, 0x9FFF), // CJK Unified Ideographs
+    (59, 0x2E80, 0x2EFF), // CJK Radicals Supplement
+    (59, 0x2F00, 0x2FDF), // Kangxi Radicals
+    (59, 0x2FF0, 0x2FFF), // Ideographic Description Characters
+    (59, 0x3400, 0x4DBF), // CJK Unified Ideographs Extension A
+    (59, 0x20000, 0x2A6DF), // CJK Unified Ideographs Extension B
+    (59, 0x3190, 0x319F), // Kanbun
+    (60, 0xE000, 0xF8FF), // Private Use Area (plane 0)
+    (61, 0x31C0, 0x31EF), // CJK Strokes
+    (61, 0xF900, 0xFAFF), // CJK Compatibility Ideographs
+    (61, 0x2F800, 0x2FA1F), // CJK Compatibility Ideographs Supplement
+    (62, 0xFB00, 0xFB4F), // Alphabetic Presentation Forms
+    (63, 0xFB50, 0xFDFF), // Arabic Presentation Forms-A
+    (64, 0xFE20, 0xFE2F), // Combining Half Marks
+    (65, 0xFE10, 0xFE1F), // Vertical Forms
+    (65, 0xFE30, 0xFE4F), // CJK Compatibility Forms
+    (66, 0xFE50, 0xFE6F), // Small Form Variants
+    (67, 0xFE70, 0xFEFF), // Arabic Presentation Forms-B
+    (68, 0xFF00, 0xFFEF), // Halfwidth And Fullwidth Forms
+    (69, 0xFFF0, 0xFFFF), // Specials
+    (70, 0x0F00, 0x0FFF), // Tibetan
+    (71, 0x0700, 0x074F), // Syriac
+    (72, 0x0780, 0x07BF), // Thaana
+    (73, 0x0D80, 0x0DFF), // Sinhala
+    (74, 0x1000, 0x109F), // Myanmar
+    (75, 0x1200, 0x137F), // Ethiopic
+    (75, 0x1380, 0x139F), // Ethiopic Supplement
+    (75, 0x2D80, 0x2DDF), // Ethiopic Extended
+    (76, 0x13A0, 0x13FF), // Cherokee
+    (77, 0x1400, 0x167F), // Unified Canadian Aboriginal Syllabics
+    (78, 0x1680, 0x169F), // Ogham
+    (79, 0x16A0, 0x16FF), // Runic
+    (80, 0x1780, 0x17FF), // Khmer
+    (80, 0x19E0, 0x19FF), // Khmer Symbols
+    (81, 0x1800, 0x18AF), // Mongolian
+    (82, 0x2800, 0x28FF), // Braille Patterns
+    (83, 0xA000, 0xA48F), // Yi Syllables
+    (83, 0xA490, 0xA4CF), // Yi Radicals
+    (84, 0x1700, 0x171F), // Tagalog
+    (84, 0x1720, 0x173F), // Hanunoo
+    (84, 0x1740, 0x175F), // Buhid
+    (84, 0x1760, 0x177F), // Tagbanwa
+    (85, 0x10300, 0x1032F), // Old Italic
+    (86, 0x10330, 0x1034F), // Gothic
+    (87, 0x10400, 0x1044F), // Deseret
+    (88, 0x1D000, 0x1D0FF), // Byzantine Musical Symbols
+    (88, 0x1D100, 0x1D1FF), // Musical Symbols
+    (88, 0x1D200, 0x1D24F), // Ancient Greek Musical Notation
+    (89, 0x1D400, 0x1D7FF), // Mathematical Alphanumeric Symbols
+    (90, 0xF0000, 0xFFFFD), // Private Use (plane 15)
+    (90, 0x100000, 0x10FFFD), // Private Use (plane 16)
+    (91, 0xFE00, 0xFE0F), // Variation Selectors
+    (91, 0xE0100, 0xE01EF), // Variation Selectors Supplement
+    (92, 0xE0000, 0xE007F), // Tags
+    (93, 0x1900, 0x194F), // Limbu
+    (94, 0x1950, 0x197F), // Tai Le
+    (95, 0x1980, 0x19DF), // New Tai Lue
+    (96, 0x1A00, 0x1A1F), // Buginese
+    (97, 0x2C00, 0x2C5F), // Glagolitic
+    (98, 0x2D30, 0x2D7F), // Tifinagh
+    (99, 0x4DC0, 0x4DFF), // Yijing Hexagram Symbols
+    (100, 0xA800, 0xA82F), // Syloti Nagri
+    (101, 0x10000, 0x1007F), // Linear B Syllabary
+    (101, 0x10080, 0x100FF), // Linear B Ideograms
+    (101, 0x10100, 0x1013F), // Aegean Numbers
+    (102, 0x10140, 0x1018F), // Ancient Greek Numbers
+    (103, 0x10380, 0x1039F), // Ugaritic
+    (104, 0x103A0, 0x103DF), // Old Persian
+    (105, 0x10450, 0x1047F), // Shavian
+    (106, 0x10480, 0x104AF), // Osmanya
+    (107, 0x10800, 0x1083F), // Cypriot Syllabary
+    (108, 0x10A00, 0x10A5F), // Kharoshthi
+    (109, 0x1D300, 0x1D35F), // Tai Xuan Jing Symbols
+    (110, 0x12000, 0x123FF), // Cuneiform
+    (110, 0x12400, 0x1247F), // Cuneiform Numbers and Punctuation
+    (111, 0x1D360, 0x1D37F), // Counting Rod Numerals
+    (112, 0x1B80, 0x1BBF), // Sundanese
+    (113, 0x1C00, 0x1C4F), // Lepcha
+    (114, 0x1C50, 0x1C7F), // Ol Chiki
+    (115, 0xA880, 0xA8DF), // Saurashtra
+    (116, 0xA900, 0xA92F), // Kayah Li
+    (117, 0xA930, 0xA95F), // Rejang
+    (118, 0xAA00, 0xAA5F), // Cham
+    (119, 0x10190, 0x101CF), // Ancient Symbols
+    (120, 0x101D0, 0x101FF), // Phaistos Disc
+    (121, 0x102A0, 0x102DF), // Carian
+    (121, 0x10280, 0x1029F), // Lycian
+    (121, 0x10920, 0x1093F), // Lydian
+    (122, 0x1F030, 0x1F09F), // Domino Tiles
+    (122, 0x1F000, 0x1F02F), // Mahjong Tiles
+];
+
 tables!(
     Panose {
         u8 panose0
@@ -75,6 +251,7 @@ tables!(
 
 /// Represents a font's OS/2 (OS/2 and Windows Metrics) table
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types, non_snake_case)]
 pub struct os2 {
     /// Table version (between 0 and 5)
@@ -298,6 +475,37 @@ impl Deserialize for os2 {
 }
 
 impl os2 {
+    /// Populate ulUnicodeRange fields from a list of bit numbers
+    pub fn int_list_to_unicode_ranges(&mut self, bitlist: &[u8]) {
+        let mut ranges = [0_u32; 4];
+        for &bit in bitlist.iter().filter(|&&bit| bit < 128) {
+            ranges[(bit / 32) as usize] |= 1 << (bit % 32);
+        }
+        self.ulUnicodeRange1 = ranges[0];
+        self.ulUnicodeRange2 = ranges[1];
+        self.ulUnicodeRange3 = ranges[2];
+        self.ulUnicodeRange4 = ranges[3];
+    }
+
+    /// Populate ulUnicodeRange fields by checking which Unicode blocks are
+    /// covered by the codepoints in the mapping. As in fontTools, a block
+    /// counts as supported if any of its characters are mapped, and bit 57
+    /// is set for any supplementary-plane codepoint.
+    pub fn calc_unicode_ranges(&mut self, mapping: &BTreeMap<u32, u16>) {
+        let mut bits: Vec<u8> = vec![];
+        for &codepoint in mapping.keys() {
+            if let Some(&(bit, _, _)) = UNICODE_RANGES
+                .iter()
+                .find(|(_, start, end)| (*start..=*end).contains(&codepoint))
+            {
+                bits.push(bit);
+            }
+            if codepoint >= 0x10000 {
+                bits.push(57);
+            }
+        }
+        self.int_list_to_unicode_ranges(&bits);
+    }
     /// Populate ulCodePageRange fields using a
     pub fn int_list_to_code_page_ranges(&mut self, bitlist: &[u8]) {
         let mut code_pages1 = bitlist.to_owned();
@@ -433,3 +641,93 @@ impl os2 {
         self.int_list_to_code_page_ranges(&code_page_ranges);
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn blank_os2() -> os2 {
+        let panose = Panose {
+            panose0: 0,
+            panose1: 0,
+            panose2: 0,
+            panose3: 0,
+            panose4: 0,
+            panose5: 0,
+            panose6: 0,
+            panose7: 0,
+            panose8: 0,
+            panose9: 0,
+        };
+        os2 {
+            version: 4,
+            xAvgCharWidth: 0,
+            usWeightClass: 400,
+            usWidthClass: 5,
+            fsType: 0,
+            ySubscriptXSize: 0,
+            ySubscriptYSize: 0,
+            ySubscriptXOffset: 0,
+            ySubscriptYOffset: 0,
+            ySuperscriptXSize: 0,
+            ySuperscriptYSize: 0,
+            ySuperscriptXOffset: 0,
+            ySuperscriptYOffset: 0,
+            yStrikeoutSize: 0,
+            yStrikeoutPosition: 0,
+            sFamilyClass: 0,
+            panose,
+            ulUnicodeRange1: 0,
+            ulUnicodeRange2: 0,
+            ulUnicodeRange3: 0,
+            ulUnicodeRange4: 0,
+            achVendID: crate::tag!("NONE"),
+            fsSelection: 0,
+            usFirstCharIndex: 0,
+            usLastCharIndex: 0,
+            sTypoAscender: 0,
+            sTypoDescender: 0,
+            sTypoLineGap: 0,
+            usWinAscent: 0,
+            usWinDescent: 0,
+            ulCodePageRange1: Some(0),
+            ulCodePageRange2: Some(0),
+            sxHeight: Some(0),
+            sCapHeight: Some(0),
+            usDefaultChar: Some(0),
+            usBreakChar: Some(32),
+            usMaxContext: Some(0),
+            usLowerOpticalPointSize: None,
+            usUpperOpticalPointSize: None,
+        }
+    }
+
+    #[test]
+    fn test_calc_unicode_ranges() {
+        let mut table = blank_os2();
+        let mapping: BTreeMap<u32, u16> = vec![
+            ('A' as u32, 1),
+            ('é' as u32, 2),
+            ('Б' as u32, 3),
+            ('→' as u32, 4),
+            (0x1D400, 5), // MATHEMATICAL BOLD CAPITAL A
+        ]
+        .into_iter()
+        .collect();
+        table.calc_unicode_ranges(&mapping);
+        assert_eq!(table.ulUnicodeRange1, 0b1000000011);
+        assert_eq!(table.ulUnicodeRange2, (1 << (37 - 32)) | (1 << (57 - 32)));
+        assert_eq!(table.ulUnicodeRange3, 1 << (89 - 64));
+        assert_eq!(table.ulUnicodeRange4, 0);
+    }
+
+    #[test]
+    fn test_int_list_to_unicode_ranges() {
+        let mut table = blank_os2();
+        table.int_list_to_unicode_ranges(&[0, 1, 31, 32, 122]);
+        assert_eq!(table.ulUnicodeRange1, 0x80000003);
+        assert_eq!(table.ulUnicodeRange2, 1);
+        assert_eq!(table.ulUnicodeRange3, 0);
+        assert_eq!(table.ulUnicodeRange4, 1 << (122 - 96));
+    }
+}
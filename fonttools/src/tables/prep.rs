@@ -8,8 +8,13 @@ pub const TAG: Tag = crate::tag!("prep");
 
 /// Represents a font's prep (Font Program) table
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 #[allow(non_camel_case_types)]
-pub struct prep(Vec<uint8>);
+pub struct prep(pub Vec<uint8>);
 
 impl Deserialize for prep {
     fn from_bytes(c: &mut ReaderContext) -> Result<Self, DeserializationError> {
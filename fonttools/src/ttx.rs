@@ -0,0 +1,562 @@
+//! Converting fonts to and from the `ttx` XML format
+//!
+//! The XML produced here follows the layout written by the Python
+//! `fontTools` library's `ttx` tool, so that fonts can be passed between
+//! the two. Tables with a typed representation in this crate (`head`,
+//! `name`, `cmap`, `glyf` and so on) are written field by field; all others,
+//! including tables this crate does not understand, are written as
+//! hexadecimal data inside an element marked `raw="True"`, which `ttx` also
+//! reads back unchanged.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use fonttools::font::Font;
+//! use fonttools::ttx;
+//!
+//! let font = Font::load("Test.ttf").unwrap();
+//! let xml = ttx::dump(&font).unwrap();
+//! let mut roundtripped = ttx::compile(&xml).unwrap();
+//! roundtripped.save("Test-roundtripped.ttf").unwrap();
+//! ```
+use self::xml::Element;
+use crate::font::{Font, SfntVersion};
+use crate::tables;
+use otspec::types::Tag;
+use otspec::{DeserializationError, SerializationError};
+use std::collections::{BTreeMap, HashMap};
+
+/// Tables made of fixed fields
+mod binary;
+/// Tables which name things
+mod naming;
+/// TrueType outlines, metrics and programs
+mod outlines;
+/// Variation tables
+mod variations;
+/// Reading and writing XML
+mod xml;
+
+/// An error converting a font to or from XML
+#[derive(Debug)]
+pub enum TtxError {
+    /// The XML could not be parsed
+    Xml(String),
+    /// The XML was well-formed but did not describe a font
+    Invalid(String),
+    /// A table in the font could not be read or written
+    Table(String),
+}
+
+impl std::fmt::Display for TtxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TtxError::Xml(e) => write!(f, "XML error: {}", e),
+            TtxError::Invalid(e) => write!(f, "Invalid ttx file: {}", e),
+            TtxError::Table(e) => write!(f, "Table error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TtxError {}
+
+impl From<DeserializationError> for TtxError {
+    fn from(e: DeserializationError) -> Self {
+        TtxError::Table(e.0)
+    }
+}
+
+impl From<SerializationError> for TtxError {
+    fn from(e: SerializationError) -> Self {
+        TtxError::Table(e.0)
+    }
+}
+
+/// Parses an integer written in decimal or, with a `0x` prefix, in
+/// hexadecimal
+pub(crate) fn parse_int(text: &str) -> Result<i64, TtxError> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }
+    .map_err(|_| TtxError::Invalid(format!("Bad number {}", text)))?;
+    Ok(if negative { -value } else { value })
+}
+
+/// Parses a decimal number
+pub(crate) fn parse_float(text: &str) -> Result<f64, TtxError> {
+    text.trim()
+        .parse()
+        .map_err(|_| TtxError::Invalid(format!("Bad number {}", text)))
+}
+
+/// The element name used for a table tag.
+///
+/// Tags which are valid XML names are used as they are, without trailing
+/// spaces; others are escaped as `fontTools` does.
+fn tag_to_xml(tag: &Tag) -> String {
+    if tag == "OS/2" {
+        return "OS_2".to_string();
+    }
+    let trimmed = tag.as_str().trim_end_matches(' ');
+    let mut chars = trimmed.chars();
+    let first_ok = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if first_ok && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return trimmed.to_string();
+    }
+    let mut identifier = String::new();
+    for c in trimmed.chars() {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            identifier.push('_');
+            identifier.push(c);
+        } else if c.is_ascii_uppercase() {
+            identifier.push(c);
+        } else {
+            identifier.push_str(&format!("{:x}", c as u32));
+        }
+    }
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// The table tag for an element name; the reverse of [`tag_to_xml`]
+fn xml_to_tag(name: &str) -> Result<Tag, TtxError> {
+    let invalid = || TtxError::Invalid(format!("Bad table name {}", name));
+    if name == "OS_2" {
+        return Ok(crate::tag!("OS/2"));
+    }
+    if name.len() != 8 {
+        return Tag::from_raw(format!("{:<4}", name)).map_err(|_| invalid());
+    }
+    let identifier = if name.len() % 2 == 1 && name.starts_with('_') {
+        &name[1..]
+    } else {
+        name
+    };
+    let bytes = identifier.as_bytes();
+    let mut tag = String::new();
+    for pair in bytes.chunks(2) {
+        match pair {
+            [b'_', c] | [c, b'_'] => tag.push(*c as char),
+            _ => {
+                let hex = std::str::from_utf8(pair).map_err(|_| invalid())?;
+                tag.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())? as char);
+            }
+        }
+    }
+    Tag::from_raw(format!("{:<4}", tag)).map_err(|_| invalid())
+}
+
+/// The names of the glyphs in a font, as used throughout the XML
+pub(crate) struct GlyphOrder {
+    names: Vec<String>,
+    ids: HashMap<String, u16>,
+}
+
+impl GlyphOrder {
+    /// Creates a glyph order, making the names unique by adding `#1`,
+    /// `#2` and so on to repeated names
+    pub fn new(names: Vec<String>) -> Self {
+        let mut unique = Vec::with_capacity(names.len());
+        let mut ids = HashMap::new();
+        for (gid, name) in names.into_iter().enumerate() {
+            let mut candidate = name.clone();
+            let mut suffix = 1;
+            while ids.contains_key(&candidate) {
+                candidate = format!("{}#{}", name, suffix);
+                suffix += 1;
+            }
+            ids.insert(candidate.clone(), gid as u16);
+            unique.push(candidate);
+        }
+        GlyphOrder { names: unique, ids }
+    }
+
+    /// Works out glyph names from the font's `post` table or, failing that,
+    /// from its `cmap` table
+    pub(crate) fn from_font(font: &Font, num_glyphs: usize) -> Result<Self, TtxError> {
+        if let Some(names) = font
+            .tables
+            .post()?
+            .and_then(|post| post.glyphnames.clone())
+            .filter(|names| names.len() == num_glyphs)
+        {
+            return Ok(GlyphOrder::new(names));
+        }
+        let reversed = font
+            .tables
+            .cmap()?
+            .map(|cmap| cmap.reversed())
+            .unwrap_or_default();
+        let names = (0..num_glyphs)
+            .map(|gid| {
+                if gid == 0 {
+                    return ".notdef".to_string();
+                }
+                match reversed
+                    .get(&(gid as u16))
+                    .and_then(|codepoints| codepoints.iter().min())
+                {
+                    Some(&codepoint) if codepoint > 0xFFFF => format!("u{:X}", codepoint),
+                    Some(&codepoint) => format!("uni{:04X}", codepoint),
+                    None => format!("glyph{:05}", gid),
+                }
+            })
+            .collect();
+        Ok(GlyphOrder::new(names))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn name(&self, gid: u16) -> String {
+        self.names
+            .get(gid as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("glyph{:05}", gid))
+    }
+
+    /// Finds a glyph by name. Names of the form `glyph00012` which are not
+    /// in the glyph order refer to the glyph with that ID.
+    pub fn id(&self, name: &str) -> Result<u16, TtxError> {
+        if let Some(id) = self.ids.get(name) {
+            return Ok(*id);
+        }
+        name.strip_prefix("glyph")
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| TtxError::Invalid(format!("Unknown glyph name {}", name)))
+    }
+
+    fn dump(&self) -> Element {
+        let mut element = Element::new("GlyphOrder");
+        element.comment("The 'id' attribute is only for humans; it is ignored when parsed.");
+        for (gid, name) in self.names.iter().enumerate() {
+            element.push(Element::new("GlyphID").attr("id", gid).attr("name", name));
+        }
+        element
+    }
+
+    fn compile(element: &Element) -> Result<Self, TtxError> {
+        let names = element
+            .elements()
+            .filter(|e| e.name == "GlyphID")
+            .map(|e| e.required("name").map(|name| name.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(GlyphOrder::new(names))
+    }
+}
+
+/// Writes binary data as `ttx` does: sixteen bytes to a line, in groups
+/// of four
+fn dump_hexdata(element: &mut Element, data: &[u8]) {
+    let mut hexdata = Element::new("hexdata");
+    let lines: Vec<String> = data
+        .chunks(16)
+        .map(|line| {
+            line.chunks(4)
+                .map(|group| group.iter().map(|b| format!("{:02x}", b)).collect())
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect();
+    hexdata.text(&lines.join("\n"));
+    element.push(hexdata);
+}
+
+fn compile_hexdata(element: &Element) -> Result<Vec<u8>, TtxError> {
+    let digits: Vec<u8> = element
+        .content()
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| TtxError::Invalid("Bad hexadecimal data".to_string()))
+        })
+        .collect()
+}
+
+/// Converts one table, returning `false` if it has no typed conversion
+fn dump_typed(
+    element: &mut Element,
+    font: &Font,
+    tag: &Tag,
+    data: &[u8],
+    order: &GlyphOrder,
+) -> Result<bool, TtxError> {
+    let tables = &font.tables;
+    match tag.as_str() {
+        "head" => {
+            element.comment("Most of this table will be recalculated by the compiler");
+            binary::dump(element, data);
+        }
+        "hhea" | "maxp" | "OS/2" => {
+            binary::dump(element, data);
+        }
+        "post" => naming::dump_post(element, data, order),
+        "name" => naming::dump_name(element, &tables.name()?.unwrap()),
+        "cmap" => naming::dump_cmap(element, &tables.cmap()?.unwrap(), order),
+        "hmtx" => outlines::dump_hmtx(element, &tables.hmtx()?.unwrap(), order),
+        "loca" => outlines::dump_loca(element),
+        "glyf" => outlines::dump_glyf(element, &tables.glyf()?.unwrap(), order),
+        "cvt " => outlines::dump_cvt(element, &tables.cvt()?.unwrap()),
+        "fpgm" => outlines::dump_program(element, &tables.fpgm()?.unwrap().0),
+        "prep" => outlines::dump_program(element, &tables.prep()?.unwrap().0),
+        "gasp" => outlines::dump_gasp(element, &tables.gasp()?.unwrap()),
+        "fvar" => variations::dump_fvar(element, &tables.fvar()?.unwrap()),
+        "avar" => {
+            let fvar = tables.fvar()?;
+            variations::dump_avar(element, &tables.avar()?.unwrap(), fvar.as_deref())
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn dump_table(font: &Font, tag: &Tag, order: &GlyphOrder) -> Result<Element, TtxError> {
+    // Typed glyf and loca tables have no binary form on their own, but
+    // don't need one
+    let data = font.tables.table_data(*tag);
+    let bytes = match &data {
+        Ok(Some(bytes)) => bytes.as_slice(),
+        _ => &[],
+    };
+    let mut element = Element::new(&tag_to_xml(tag));
+    match dump_typed(&mut element, font, tag, bytes, order) {
+        Ok(true) => return Ok(element),
+        Ok(false) => {}
+        Err(e) => {
+            log::warn!("Could not read {} table, writing it as data: {}", tag, e);
+            element = Element::new(&tag_to_xml(tag));
+        }
+    }
+    element = element.attr("raw", "True");
+    dump_hexdata(&mut element, &data?.unwrap_or_default());
+    Ok(element)
+}
+
+/// Converts all tables of a font to XML.
+pub fn dump(font: &Font) -> Result<String, TtxError> {
+    let tags: Vec<Tag> = font.tables.keys().collect();
+    dump_tables(font, &tags)
+}
+
+/// Converts the given tables of a font to XML. Tags of tables which are
+/// not in the font are ignored.
+pub fn dump_tables(font: &Font, tags: &[Tag]) -> Result<String, TtxError> {
+    let num_glyphs = font
+        .tables
+        .maxp()?
+        .map(|maxp| maxp.num_glyphs() as usize)
+        .or(font.tables.glyf()?.map(|glyf| glyf.glyphs.len()))
+        .unwrap_or(0);
+    let order = GlyphOrder::from_font(font, num_glyphs)?;
+    let sfnt_version = match font.sfnt_version() {
+        SfntVersion::TrueType => "\\x00\\x01\\x00\\x00",
+        SfntVersion::OpenType => "OTTO",
+    };
+    let mut root = Element::new("ttFont").attr("sfntVersion", sfnt_version);
+    root.push(order.dump());
+    for tag in font.tables.keys().filter(|tag| tags.contains(tag)) {
+        root.push(dump_table(font, &tag, &order)?);
+    }
+    Ok(root.to_document())
+}
+
+fn compile_table(
+    font: &mut Font,
+    root: &Element,
+    element: &Element,
+    order: &GlyphOrder,
+) -> Result<(), TtxError> {
+    let tag = xml_to_tag(&element.name)?;
+    if let Some(hexdata) = element.child("hexdata") {
+        font.tables.insert_raw(tag, compile_hexdata(hexdata)?);
+        return Ok(());
+    }
+    let tables = &mut font.tables;
+    match tag.as_str() {
+        "head" | "hhea" | "maxp" | "OS/2" => tables.insert_raw(tag, binary::compile(element)?),
+        "post" => tables.insert(naming::compile_post(element, order)?),
+        "name" => tables.insert(naming::compile_name(element)?),
+        "cmap" => tables.insert(naming::compile_cmap(element, order)?),
+        "hmtx" => tables.insert(outlines::compile_hmtx(element, order)?),
+        // Compiled along with glyf when the font is written
+        "loca" => {}
+        "glyf" => tables.insert(outlines::compile_glyf(element, order)?),
+        "cvt " => tables.insert(outlines::compile_cvt(element)?),
+        "fpgm" => tables.insert(tables::fpgm::fpgm(outlines::compile_program(element)?)),
+        "prep" => tables.insert(tables::prep::prep(outlines::compile_program(element)?)),
+        "gasp" => tables.insert(outlines::compile_gasp(element)?),
+        "fvar" => tables.insert(variations::compile_fvar(element)?),
+        "avar" => {
+            let fvar = root
+                .child("fvar")
+                .map(variations::compile_fvar)
+                .transpose()?;
+            tables.insert(variations::compile_avar(element, fvar.as_ref())?)
+        }
+        _ => {
+            return Err(TtxError::Invalid(format!(
+                "Don't know how to compile the {} table without hexdata",
+                tag
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Builds a font from XML.
+///
+/// Values which are derived from other tables, such as the number of
+/// glyphs, the glyph bounds and the `loca` table, are recalculated.
+pub fn compile(xml: &str) -> Result<Font, TtxError> {
+    let root = Element::parse(xml)?;
+    if root.name != "ttFont" {
+        return Err(TtxError::Invalid(format!(
+            "Expected a <ttFont> element, found <{}>",
+            root.name
+        )));
+    }
+    let sfnt_version = match root.get_attr("sfntVersion") {
+        Some("OTTO") => SfntVersion::OpenType,
+        _ => SfntVersion::TrueType,
+    };
+    let mut font = Font::new(sfnt_version);
+    let order = match root.child("GlyphOrder") {
+        Some(element) => GlyphOrder::compile(element)?,
+        None => GlyphOrder::new(vec![]),
+    };
+    let mut seen = BTreeMap::new();
+    for element in root.elements().filter(|e| e.name != "GlyphOrder") {
+        if seen.insert(element.name.clone(), ()).is_some() {
+            return Err(TtxError::Invalid(format!(
+                "The {} table appears twice",
+                element.name
+            )));
+        }
+        compile_table(&mut font, &root, element, &order)?;
+    }
+
+    let num_glyphs = order.len() as u16;
+    if let Some(mut maxp) = font.tables.maxp()? {
+        maxp.set_num_glyphs(num_glyphs);
+        font.tables.insert(maxp);
+    }
+    Ok(font)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tag;
+
+    #[test]
+    fn test_tag_names() {
+        for (tag, name) in [
+            (tag!("OS/2"), "OS_2"),
+            (tag!("cvt "), "cvt"),
+            (tag!("GSUB"), "GSUB"),
+            (tag!("CFF "), "CFF"),
+            (tag!("1abc"), "_1_a_b_c"),
+            (tag!("a/b2"), "_a2f_b_2"),
+        ] {
+            assert_eq!(tag_to_xml(&tag), name);
+            assert_eq!(xml_to_tag(name).unwrap(), tag);
+        }
+        // Like fontTools, only escaped names of eight characters are
+        // converted back
+        assert_eq!(tag_to_xml(&tag!("O/S2")), "O2fS_2");
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int("0x409").unwrap(), 0x409);
+        assert_eq!(parse_int(" -12 ").unwrap(), -12);
+        assert!(parse_int("twelve").is_err());
+    }
+
+    #[test]
+    fn test_glyph_order() {
+        let order = GlyphOrder::new(vec![".notdef".into(), "a".into(), "a".into()]);
+        assert_eq!(order.name(2), "a#1");
+        assert_eq!(order.id("a#1").unwrap(), 2);
+        assert_eq!(order.id("glyph00001").unwrap(), 1);
+        assert!(order.id("b").is_err());
+    }
+
+    pub(crate) fn test_font() -> Font {
+        let mut font = crate::proof::tests::test_font();
+        font.tables
+            .insert(tables::maxp::maxp::new10(6, 4, 1, 0, 0, 0, 0));
+        font.tables.insert(tables::post::post::new(
+            2.0,
+            0.0,
+            -100,
+            50,
+            false,
+            Some(
+                [".notdef", "f", "i", "f_i", "A", "V"]
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+            ),
+        ));
+        font.tables.insert(tables::name::name {
+            records: vec![tables::name::NameRecord::windows_unicode(1u16, "Test")],
+        });
+        font.tables
+            .insert_raw(tag!("DSIG"), vec![0, 0, 0, 1, 0, 0, 0, 0]);
+        let mut glyf = font.tables.glyf().unwrap().unwrap();
+        glyf.recalc_bounds();
+        font.tables.insert(glyf);
+        font
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut font = test_font();
+        let mut bytes = vec![];
+        font.write(&mut bytes).unwrap();
+        let font = Font::from_bytes(&bytes).unwrap();
+        let xml = dump(&font).unwrap();
+        assert!(xml.contains("<GlyphID id=\"3\" name=\"f_i\"/>"));
+        assert!(xml.contains("<DSIG raw=\"True\">\n    <hexdata>\n      00000001 00000000\n"));
+        assert!(xml.contains("<mtx name=\"f_i\" width=\"500\" lsb=\"0\"/>"));
+
+        let mut compiled = compile(&xml).unwrap();
+        let mut bytes = vec![];
+        compiled.write(&mut bytes).unwrap();
+        let reloaded = Font::from_bytes(&bytes).unwrap();
+        assert_eq!(dump(&reloaded).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_dump_tables() {
+        let xml = dump_tables(&test_font(), &[tag!("name"), tag!("glyf")]).unwrap();
+        assert!(
+            xml.contains("<TTGlyph name=\"f_i\" xMin=\"0\" yMin=\"0\" xMax=\"500\" yMax=\"500\">")
+        );
+        assert!(xml.contains(
+            "<namerecord nameID=\"1\" platformID=\"3\" platEncID=\"1\" langID=\"0x409\">\n      Test\n"
+        ));
+        assert!(!xml.contains("<cmap>"));
+    }
+}
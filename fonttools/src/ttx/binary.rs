@@ -0,0 +1,420 @@
+//! Tables made of fixed fields: `head`, `hhea`, `maxp`, `OS/2` and the
+//! header of `post`
+//!
+//! Like `fontTools`, these are converted field by field from their binary
+//! form, following a description of each field.
+use super::xml::Element;
+use super::{parse_int, TtxError};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+/// How a field is stored in the binary table and written in XML
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    I16,
+    U16,
+    U32,
+    /// A 16.16 number written as a decimal
+    Fixed,
+    /// A 16.16 version number written in hexadecimal
+    Version,
+    /// A 32-bit number written in hexadecimal
+    Hex,
+    /// A 16-bit bit field written in binary
+    Bits16,
+    /// A 32-bit bit field written in binary
+    Bits32,
+    /// Seconds since 1904, written as a date
+    Date,
+    /// Four characters
+    Tag,
+    /// The ten bytes of a PANOSE classification
+    Panose,
+}
+
+impl Kind {
+    fn size(self) -> usize {
+        match self {
+            Kind::I16 | Kind::U16 | Kind::Bits16 => 2,
+            Kind::U32 | Kind::Fixed | Kind::Version | Kind::Hex | Kind::Bits32 | Kind::Tag => 4,
+            Kind::Date => 8,
+            Kind::Panose => 10,
+        }
+    }
+}
+
+/// A field, and the table version which introduced it
+struct Field(&'static str, Kind, u32);
+
+const HEAD: &[Field] = &[
+    Field("tableVersion", Kind::Fixed, 0),
+    Field("fontRevision", Kind::Fixed, 0),
+    Field("checkSumAdjustment", Kind::Hex, 0),
+    Field("magicNumber", Kind::Hex, 0),
+    Field("flags", Kind::Bits16, 0),
+    Field("unitsPerEm", Kind::U16, 0),
+    Field("created", Kind::Date, 0),
+    Field("modified", Kind::Date, 0),
+    Field("xMin", Kind::I16, 0),
+    Field("yMin", Kind::I16, 0),
+    Field("xMax", Kind::I16, 0),
+    Field("yMax", Kind::I16, 0),
+    Field("macStyle", Kind::Bits16, 0),
+    Field("lowestRecPPEM", Kind::U16, 0),
+    Field("fontDirectionHint", Kind::I16, 0),
+    Field("indexToLocFormat", Kind::I16, 0),
+    Field("glyphDataFormat", Kind::I16, 0),
+];
+
+const HHEA: &[Field] = &[
+    Field("tableVersion", Kind::Version, 0),
+    Field("ascent", Kind::I16, 0),
+    Field("descent", Kind::I16, 0),
+    Field("lineGap", Kind::I16, 0),
+    Field("advanceWidthMax", Kind::U16, 0),
+    Field("minLeftSideBearing", Kind::I16, 0),
+    Field("minRightSideBearing", Kind::I16, 0),
+    Field("xMaxExtent", Kind::I16, 0),
+    Field("caretSlopeRise", Kind::I16, 0),
+    Field("caretSlopeRun", Kind::I16, 0),
+    Field("caretOffset", Kind::I16, 0),
+    Field("reserved0", Kind::I16, 0),
+    Field("reserved1", Kind::I16, 0),
+    Field("reserved2", Kind::I16, 0),
+    Field("reserved3", Kind::I16, 0),
+    Field("metricDataFormat", Kind::I16, 0),
+    Field("numberOfHMetrics", Kind::U16, 0),
+];
+
+const MAXP: &[Field] = &[
+    Field("tableVersion", Kind::Version, 0),
+    Field("numGlyphs", Kind::U16, 0),
+    Field("maxPoints", Kind::U16, 0x10000),
+    Field("maxContours", Kind::U16, 0x10000),
+    Field("maxCompositePoints", Kind::U16, 0x10000),
+    Field("maxCompositeContours", Kind::U16, 0x10000),
+    Field("maxZones", Kind::U16, 0x10000),
+    Field("maxTwilightPoints", Kind::U16, 0x10000),
+    Field("maxStorage", Kind::U16, 0x10000),
+    Field("maxFunctionDefs", Kind::U16, 0x10000),
+    Field("maxInstructionDefs", Kind::U16, 0x10000),
+    Field("maxStackElements", Kind::U16, 0x10000),
+    Field("maxSizeOfInstructions", Kind::U16, 0x10000),
+    Field("maxComponentElements", Kind::U16, 0x10000),
+    Field("maxComponentDepth", Kind::U16, 0x10000),
+];
+
+const OS2: &[Field] = &[
+    Field("version", Kind::U16, 0),
+    Field("xAvgCharWidth", Kind::I16, 0),
+    Field("usWeightClass", Kind::U16, 0),
+    Field("usWidthClass", Kind::U16, 0),
+    Field("fsType", Kind::Bits16, 0),
+    Field("ySubscriptXSize", Kind::I16, 0),
+    Field("ySubscriptYSize", Kind::I16, 0),
+    Field("ySubscriptXOffset", Kind::I16, 0),
+    Field("ySubscriptYOffset", Kind::I16, 0),
+    Field("ySuperscriptXSize", Kind::I16, 0),
+    Field("ySuperscriptYSize", Kind::I16, 0),
+    Field("ySuperscriptXOffset", Kind::I16, 0),
+    Field("ySuperscriptYOffset", Kind::I16, 0),
+    Field("yStrikeoutSize", Kind::I16, 0),
+    Field("yStrikeoutPosition", Kind::I16, 0),
+    Field("sFamilyClass", Kind::I16, 0),
+    Field("panose", Kind::Panose, 0),
+    Field("ulUnicodeRange1", Kind::Bits32, 0),
+    Field("ulUnicodeRange2", Kind::Bits32, 0),
+    Field("ulUnicodeRange3", Kind::Bits32, 0),
+    Field("ulUnicodeRange4", Kind::Bits32, 0),
+    Field("achVendID", Kind::Tag, 0),
+    Field("fsSelection", Kind::Bits16, 0),
+    Field("usFirstCharIndex", Kind::U16, 0),
+    Field("usLastCharIndex", Kind::U16, 0),
+    Field("sTypoAscender", Kind::I16, 0),
+    Field("sTypoDescender", Kind::I16, 0),
+    Field("sTypoLineGap", Kind::I16, 0),
+    Field("usWinAscent", Kind::U16, 0),
+    Field("usWinDescent", Kind::U16, 0),
+    Field("ulCodePageRange1", Kind::Bits32, 1),
+    Field("ulCodePageRange2", Kind::Bits32, 1),
+    Field("sxHeight", Kind::I16, 2),
+    Field("sCapHeight", Kind::I16, 2),
+    Field("usDefaultChar", Kind::U16, 2),
+    Field("usBreakChar", Kind::U16, 2),
+    Field("usMaxContext", Kind::U16, 2),
+    Field("usLowerOpticalPointSize", Kind::U16, 5),
+    Field("usUpperOpticalPointSize", Kind::U16, 5),
+];
+
+const POST: &[Field] = &[
+    Field("formatType", Kind::Fixed, 0),
+    Field("italicAngle", Kind::Fixed, 0),
+    Field("underlinePosition", Kind::I16, 0),
+    Field("underlineThickness", Kind::I16, 0),
+    Field("isFixedPitch", Kind::U32, 0),
+    Field("minMemType42", Kind::U32, 0),
+    Field("maxMemType42", Kind::U32, 0),
+    Field("minMemType1", Kind::U32, 0),
+    Field("maxMemType1", Kind::U32, 0),
+];
+
+const PANOSE: [&str; 10] = [
+    "bFamilyType",
+    "bSerifStyle",
+    "bWeight",
+    "bProportion",
+    "bContrast",
+    "bStrokeVariation",
+    "bArmStyle",
+    "bLetterForm",
+    "bMidline",
+    "bXHeight",
+];
+
+/// Field names used by other versions of `ttx`
+const ALIASES: &[(&str, &str)] = &[("ascent", "ascender"), ("descent", "descender")];
+
+fn fields(table: &str) -> &'static [Field] {
+    match table {
+        "head" => HEAD,
+        "hhea" => HHEA,
+        "maxp" => MAXP,
+        "OS_2" => OS2,
+        "post" => POST,
+        _ => &[],
+    }
+}
+
+/// The length of the header of a `post` table
+pub(crate) const POST_HEADER_LENGTH: usize = 32;
+
+fn epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd(1904, 1, 1).and_hms(0, 0, 0)
+}
+
+/// Formats a 16.16 number as the shortest decimal which converts back to
+/// it, always with a fractional part
+fn format_fixed(raw: i32) -> String {
+    format_fixed_point(raw as i64, 16)
+}
+
+/// Formats a fixed-point number with the given number of fractional bits
+/// as the shortest decimal which converts back to it
+pub(crate) fn format_fixed_point(raw: i64, fraction_bits: u32) -> String {
+    let scale = (1i64 << fraction_bits) as f64;
+    let value = raw as f64 / scale;
+    for precision in 1..=6 {
+        let text = format!("{:.*}", precision, value);
+        let parsed: f64 = text.parse().unwrap();
+        if (parsed * scale).round() as i64 == raw {
+            return text;
+        }
+    }
+    value.to_string()
+}
+
+/// Writes a number in binary, in groups of eight bits
+fn format_bits(value: u32, bits: usize) -> String {
+    let digits = format!("{:0width$b}", value, width = bits);
+    digits
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn read(data: &[u8], kind: Kind) -> i64 {
+    let be = |n: usize| data[..n].iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    match kind {
+        Kind::I16 => be(2) as u16 as i16 as i64,
+        Kind::U16 | Kind::Bits16 => be(2) as i64,
+        Kind::Fixed => be(4) as u32 as i32 as i64,
+        Kind::U32 | Kind::Version | Kind::Hex | Kind::Bits32 | Kind::Tag => be(4) as i64,
+        Kind::Date => be(8) as i64,
+        Kind::Panose => 0,
+    }
+}
+
+/// Converts a table of fixed fields to XML, adding its elements to
+/// `element`. Returns the number of bytes read.
+pub(crate) fn dump(element: &mut Element, data: &[u8]) -> usize {
+    let fields = fields(&element.name);
+    let version = fields
+        .first()
+        .filter(|field| data.len() >= field.1.size())
+        .map_or(0, |field| read(data, field.1) as u32);
+    let mut position = 0;
+    for Field(name, kind, since) in fields {
+        if *since > version || position + kind.size() > data.len() {
+            break;
+        }
+        let bytes = &data[position..];
+        position += kind.size();
+        let value = read(bytes, *kind);
+        let text = match kind {
+            Kind::I16 | Kind::U16 | Kind::U32 => value.to_string(),
+            Kind::Fixed => format_fixed(value as i32),
+            Kind::Version => format!("0x{:08x}", value),
+            Kind::Hex => format!("0x{:x}", value),
+            Kind::Bits16 => format_bits(value as u32, 16),
+            Kind::Bits32 => format_bits(value as u32, 32),
+            Kind::Date => (epoch() + Duration::seconds(value))
+                .format("%a %b %e %H:%M:%S %Y")
+                .to_string(),
+            Kind::Tag => String::from_utf8_lossy(&bytes[..4]).to_string(),
+            Kind::Panose => {
+                let mut panose = Element::new(name);
+                for (index, name) in PANOSE.iter().enumerate() {
+                    panose.push(Element::value(name, bytes[index]));
+                }
+                element.push(panose);
+                continue;
+            }
+        };
+        element.push(Element::value(name, text));
+    }
+    position
+}
+
+fn parse_value(element: &Element, kind: Kind) -> Result<Vec<u8>, TtxError> {
+    if kind == Kind::Panose {
+        return PANOSE
+            .iter()
+            .map(|name| match element.child(name) {
+                Some(field) => Ok(parse_int(field.required("value")?)? as u8),
+                None => Ok(0),
+            })
+            .collect();
+    }
+    let text = element.required("value")?;
+    let invalid = || TtxError::Invalid(format!("Bad value {} for {}", text, element.name));
+    Ok(match kind {
+        Kind::I16 | Kind::U16 => (parse_int(text)? as u16).to_be_bytes().to_vec(),
+        Kind::U32 | Kind::Version | Kind::Hex => (parse_int(text)? as u32).to_be_bytes().to_vec(),
+        Kind::Fixed => {
+            let value: f64 = text.trim().parse().map_err(|_| invalid())?;
+            ((value * 65536.0).round() as i32).to_be_bytes().to_vec()
+        }
+        Kind::Bits16 | Kind::Bits32 => {
+            let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+            let value = u32::from_str_radix(&digits, 2).map_err(|_| invalid())?;
+            if kind == Kind::Bits16 {
+                (value as u16).to_be_bytes().to_vec()
+            } else {
+                value.to_be_bytes().to_vec()
+            }
+        }
+        Kind::Date => {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let date = NaiveDateTime::parse_from_str(&text, "%a %b %d %H:%M:%S %Y")
+                .map_err(|_| invalid())?;
+            (date - epoch()).num_seconds().to_be_bytes().to_vec()
+        }
+        Kind::Tag => {
+            let mut tag: Vec<u8> = text.bytes().take(4).collect();
+            tag.resize(4, b' ');
+            tag
+        }
+        Kind::Panose => unreachable!(),
+    })
+}
+
+/// Compiles a table of fixed fields from XML. Fields which are missing
+/// are set to zero.
+pub(crate) fn compile(element: &Element) -> Result<Vec<u8>, TtxError> {
+    let fields = fields(&element.name);
+    let find = |name: &str| {
+        element.child(name).or_else(|| {
+            ALIASES
+                .iter()
+                .find(|(field, _)| *field == name)
+                .and_then(|(_, alias)| element.child(alias))
+        })
+    };
+    let mut data = vec![];
+    let mut version = 0;
+    for (index, Field(name, kind, since)) in fields.iter().enumerate() {
+        if *since > version {
+            break;
+        }
+        let bytes = match find(name) {
+            Some(field) => parse_value(field, *kind)?,
+            None => vec![0; kind.size()],
+        };
+        if index == 0 {
+            version = bytes.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        }
+        data.extend(bytes);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(format_fixed(0x10000), "1.0");
+        assert_eq!(format_fixed(-0xC8000), "-12.5");
+        assert_eq!(format_fixed(0x10042), "1.001");
+        assert_eq!(format_bits(3, 16), "00000000 00000011");
+    }
+
+    #[test]
+    fn test_head_roundtrip() {
+        let head = crate::tables::head::head {
+            majorVersion: 1,
+            minorVersion: 0,
+            fontRevision: 1.5,
+            checksumAdjustment: 0xaf8fe61,
+            magicNumber: 0x5F0F3CF5,
+            flags: 3,
+            unitsPerEm: 1000,
+            created: NaiveDate::from_ymd(2020, 1, 8).and_hms(21, 31, 22),
+            modified: NaiveDate::from_ymd(2021, 4, 14).and_hms(12, 1, 45),
+            xMin: -9,
+            yMin: 0,
+            xMax: 592,
+            yMax: 1000,
+            macStyle: 0,
+            lowestRecPPEM: 6,
+            fontDirectionHint: 2,
+            indexToLocFormat: 1,
+            glyphDataFormat: 0,
+        };
+        let data = otspec::ser::to_bytes(&head).unwrap();
+        let mut element = Element::new("head");
+        assert_eq!(dump(&mut element, &data), data.len());
+        let value = |name: &str| element.child(name).unwrap().get_attr("value").unwrap();
+        assert_eq!(value("tableVersion"), "1.0");
+        assert_eq!(value("fontRevision"), "1.5");
+        assert_eq!(value("checkSumAdjustment"), "0xaf8fe61");
+        assert_eq!(value("flags"), "00000000 00000011");
+        assert_eq!(value("created"), "Wed Jan  8 21:31:22 2020");
+        assert_eq!(value("xMin"), "-9");
+        assert_eq!(compile(&element).unwrap(), data);
+    }
+
+    #[test]
+    fn test_versions() {
+        // A version 0.5 maxp has only a glyph count
+        let mut element = Element::new("maxp");
+        element.push(Element::value("tableVersion", "0x5000"));
+        element.push(Element::value("numGlyphs", "7"));
+        element.push(Element::value("maxPoints", "100"));
+        let data = compile(&element).unwrap();
+        assert_eq!(data, vec![0, 0, 0x50, 0, 0, 7]);
+        let mut dumped = Element::new("maxp");
+        dump(&mut dumped, &data);
+        assert_eq!(dumped.elements().count(), 2);
+
+        // An OS/2 table's fields depend on its version
+        let mut element = Element::new("OS_2");
+        element.push(Element::value("version", "1"));
+        element.push(Element::value("achVendID", "AB"));
+        let data = compile(&element).unwrap();
+        assert_eq!(data.len(), 86);
+        assert_eq!(&data[58..62], b"AB  ");
+    }
+}
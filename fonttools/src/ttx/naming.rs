@@ -0,0 +1,344 @@
+//! Tables which name things: `name`, `cmap` and the glyph names in `post`
+use super::xml::Element;
+use super::{binary, parse_int, GlyphOrder, TtxError};
+use crate::tables;
+use crate::tables::cmap::CmapSubtable;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub(crate) fn dump_name(element: &mut Element, name: &tables::name::name) {
+    for record in &name.records {
+        let mut namerecord = Element::new("namerecord")
+            .attr("nameID", record.nameID)
+            .attr("platformID", record.platformID)
+            .attr("platEncID", record.encodingID)
+            .attr("langID", format!("0x{:x}", record.languageID));
+        namerecord.text(&record.string);
+        element.push(namerecord);
+    }
+}
+
+pub(crate) fn compile_name(element: &Element) -> Result<tables::name::name, TtxError> {
+    let mut records = vec![];
+    for namerecord in element.elements().filter(|e| e.name == "namerecord") {
+        records.push(tables::name::NameRecord {
+            platformID: parse_int(namerecord.required("platformID")?)? as u16,
+            encodingID: parse_int(namerecord.required("platEncID")?)? as u16,
+            languageID: parse_int(namerecord.required("langID")?)? as u16,
+            nameID: parse_int(namerecord.required("nameID")?)? as u16,
+            string: namerecord.content(),
+        });
+    }
+    Ok(tables::name::name { records })
+}
+
+/// The number of groups of consecutive codepoints mapped to consecutive
+/// glyphs, as stored in a format 12 subtable
+fn count_groups(mapping: &BTreeMap<u32, u16>) -> usize {
+    let mut groups = 0;
+    let mut last: Option<(u32, u16)> = None;
+    for (&codepoint, &gid) in mapping {
+        match last {
+            Some((c, g)) if c + 1 == codepoint && g as u32 + 1 == gid as u32 => {}
+            _ => groups += 1,
+        }
+        last = Some((codepoint, gid));
+    }
+    groups
+}
+
+fn dump_subtable(subtable: &CmapSubtable, order: &GlyphOrder) -> Element {
+    let mut element = Element::new(&format!("cmap_format_{}", subtable.format))
+        .attr("platformID", subtable.platformID)
+        .attr("platEncID", subtable.encodingID);
+    match subtable.format {
+        12 => {
+            let groups = count_groups(&subtable.mapping);
+            element = element
+                .attr("format", 12)
+                .attr("reserved", 0)
+                .attr("length", 16 + 12 * groups)
+                .attr("language", subtable.languageID)
+                .attr("nGroups", groups);
+        }
+        14 => {
+            let sequences = subtable.uvs_mapping.as_ref().map_or(0, |m| m.len());
+            let selectors: BTreeSet<u32> = subtable
+                .uvs_mapping
+                .iter()
+                .flat_map(|m| m.keys().map(|(_, selector)| *selector))
+                .collect();
+            // Each selector record takes 11 bytes and points to a list of
+            // 5-byte mappings with a 4-byte count
+            element = element
+                .attr("format", 14)
+                .attr("length", 10 + 15 * selectors.len() + 5 * sequences)
+                .attr("numVarSelectorRecords", selectors.len());
+        }
+        _ => element = element.attr("language", subtable.languageID),
+    }
+    for (codepoint, gid) in &subtable.mapping {
+        element.push(
+            Element::new("map")
+                .attr("code", format!("0x{:x}", codepoint))
+                .attr("name", order.name(*gid)),
+        );
+    }
+    if let Some(uvs_mapping) = &subtable.uvs_mapping {
+        // ttx lists sequences by selector
+        let mut sequences: Vec<_> = uvs_mapping.iter().collect();
+        sequences.sort_by_key(|((codepoint, selector), _)| (*selector, *codepoint));
+        for ((codepoint, selector), gid) in sequences {
+            element.push(
+                Element::new("map")
+                    .attr("uv", format!("0x{:x}", codepoint))
+                    .attr("uvs", format!("0x{:x}", selector))
+                    .attr("name", order.name(*gid)),
+            );
+        }
+    }
+    element
+}
+
+pub(crate) fn dump_cmap(element: &mut Element, cmap: &tables::cmap::cmap, order: &GlyphOrder) {
+    element.push(Element::new("tableVersion").attr("version", 0));
+    for subtable in &cmap.subtables {
+        element.push(dump_subtable(subtable, order));
+    }
+}
+
+pub(crate) fn compile_cmap(
+    element: &Element,
+    order: &GlyphOrder,
+) -> Result<tables::cmap::cmap, TtxError> {
+    let mut subtables = vec![];
+    for child in element.elements() {
+        let format: u16 = match child.name.strip_prefix("cmap_format_") {
+            Some(format) => format
+                .parse()
+                .map_err(|_| TtxError::Invalid(format!("Unknown cmap subtable {}", child.name)))?,
+            None => continue,
+        };
+        let mut mapping = BTreeMap::new();
+        let mut uvs_mapping = BTreeMap::new();
+        for map in child.elements().filter(|e| e.name == "map") {
+            match map.get_attr("uvs") {
+                Some(selector) => {
+                    let name = map.required("name")?;
+                    let codepoint = parse_int(map.required("uv")?)? as u32;
+                    // Default sequences use the glyph of the base character
+                    let gid = if name == "None" {
+                        match mapping_for(&subtables, codepoint) {
+                            Some(gid) => gid,
+                            None => continue,
+                        }
+                    } else {
+                        order.id(name)?
+                    };
+                    uvs_mapping.insert((codepoint, parse_int(selector)? as u32), gid);
+                }
+                None => {
+                    mapping.insert(
+                        parse_int(map.required("code")?)? as u32,
+                        order.id(map.required("name")?)?,
+                    );
+                }
+            }
+        }
+        // Only some formats can be written; the others are converted to
+        // the closest which can
+        let format = match format {
+            0 | 4 | 12 | 14 => format,
+            2 | 6 => 4,
+            _ => 12,
+        };
+        subtables.push(CmapSubtable {
+            format,
+            platformID: parse_int(child.required("platformID")?)? as u16,
+            encodingID: parse_int(child.required("platEncID")?)? as u16,
+            languageID: child.get_attr("language").map_or(Ok(0), parse_int)? as u16,
+            mapping,
+            uvs_mapping: if format == 14 {
+                Some(uvs_mapping)
+            } else {
+                None
+            },
+        });
+    }
+    Ok(tables::cmap::cmap { subtables })
+}
+
+/// The glyph of a character in the Unicode subtables compiled so far
+fn mapping_for(subtables: &[CmapSubtable], codepoint: u32) -> Option<u16> {
+    subtables
+        .iter()
+        .filter(|subtable| subtable.is_unicode())
+        .find_map(|subtable| subtable.mapping.get(&codepoint).copied())
+}
+
+pub(crate) fn dump_post(element: &mut Element, data: &[u8], order: &GlyphOrder) {
+    binary::dump(element, data);
+    let post: tables::post::post = match otspec::de::from_bytes(data) {
+        Ok(post) => post,
+        Err(_) => return,
+    };
+    let glyphnames = match post.glyphnames {
+        Some(glyphnames) => glyphnames,
+        None => return,
+    };
+    let mut ps_names = Element::new("psNames");
+    ps_names.comment(
+        "This file uses unique glyph names based on the information found in the 'post' \
+         table. Since these names might not be unique, we have to invent artificial names \
+         in case of clashes. In order to be able to retain the original information, we \
+         need a name to ps name mapping for those cases where they differ. That's what you \
+         see below.",
+    );
+    for (gid, ps_name) in glyphnames.iter().enumerate() {
+        let name = order.name(gid as u16);
+        if &name != ps_name {
+            ps_names.push(
+                Element::new("psName")
+                    .attr("name", name)
+                    .attr("psName", ps_name),
+            );
+        }
+    }
+    element.push(ps_names);
+    let mut extra_names = Element::new("extraNames");
+    extra_names
+        .comment("following are the name that are not taken from the standard Mac glyph order");
+    let mut seen = vec![];
+    for name in &glyphnames {
+        if !tables::post::APPLE_NAMES.contains(&name.as_str()) && !seen.contains(&name) {
+            extra_names.push(Element::new("psName").attr("name", name));
+            seen.push(name);
+        }
+    }
+    element.push(extra_names);
+}
+
+pub(crate) fn compile_post(
+    element: &Element,
+    order: &GlyphOrder,
+) -> Result<tables::post::post, TtxError> {
+    let mut data = binary::compile(element)?;
+    data.truncate(binary::POST_HEADER_LENGTH);
+    let is_format_2 = data.starts_with(&[0, 2, 0, 0]);
+    if is_format_2 {
+        // An empty list of names, which is filled in below
+        data.extend([0, 0]);
+    }
+    let mut post: tables::post::post =
+        otspec::de::from_bytes(&data).map_err(|e| TtxError::Invalid(e.to_string()))?;
+    if is_format_2 {
+        let mut ps_names = BTreeMap::new();
+        if let Some(element) = element.child("psNames") {
+            for ps_name in element.elements().filter(|e| e.name == "psName") {
+                ps_names.insert(ps_name.required("name")?, ps_name.required("psName")?);
+            }
+        }
+        post.glyphnames = Some(
+            (0..order.len())
+                .map(|gid| {
+                    let name = order.name(gid as u16);
+                    ps_names
+                        .get(name.as_str())
+                        .map_or(name, |ps_name| ps_name.to_string())
+                })
+                .collect(),
+        );
+    }
+    Ok(post)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order() -> GlyphOrder {
+        GlyphOrder::new(vec![".notdef".into(), "A".into(), "B".into()])
+    }
+
+    #[test]
+    fn test_name_roundtrip() {
+        let name = tables::name::name {
+            records: vec![tables::name::NameRecord {
+                platformID: 3,
+                encodingID: 1,
+                languageID: 0x409,
+                nameID: 1,
+                string: "Fish & <Chips>".to_string(),
+            }],
+        };
+        let mut element = Element::new("name");
+        dump_name(&mut element, &name);
+        let record = element.child("namerecord").unwrap();
+        assert_eq!(record.get_attr("langID"), Some("0x409"));
+        assert_eq!(compile_name(&element).unwrap(), name);
+    }
+
+    #[test]
+    fn test_cmap_roundtrip() {
+        let mapping: BTreeMap<u32, u16> = vec![(0x41, 1), (0x42, 2), (0x1F600, 1)]
+            .into_iter()
+            .collect();
+        let cmap = tables::cmap::cmap {
+            subtables: vec![
+                CmapSubtable {
+                    format: 12,
+                    platformID: 3,
+                    encodingID: 10,
+                    languageID: 0,
+                    mapping,
+                    uvs_mapping: None,
+                },
+                CmapSubtable {
+                    format: 14,
+                    platformID: 0,
+                    encodingID: 5,
+                    languageID: 0,
+                    mapping: BTreeMap::new(),
+                    uvs_mapping: Some(vec![((0x41, 0xFE00), 2)].into_iter().collect()),
+                },
+            ],
+        };
+        let mut element = Element::new("cmap");
+        dump_cmap(&mut element, &cmap, &order());
+        let format_12 = element.child("cmap_format_12").unwrap();
+        assert_eq!(format_12.get_attr("nGroups"), Some("2"));
+        assert_eq!(format_12.get_attr("length"), Some("40"));
+        let format_14 = element.child("cmap_format_14").unwrap();
+        let map = format_14.child("map").unwrap();
+        assert_eq!(map.get_attr("uvs"), Some("0xfe00"));
+        assert_eq!(map.get_attr("name"), Some("B"));
+        assert_eq!(compile_cmap(&element, &order()).unwrap(), cmap);
+    }
+
+    #[test]
+    fn test_post_roundtrip() {
+        let post = tables::post::post::new(
+            2.0,
+            -12.5,
+            -100,
+            50,
+            false,
+            Some(vec![".notdef".into(), "A".into(), "B.alt".into()]),
+        );
+        let data = otspec::ser::to_bytes(&post).unwrap();
+        let order = GlyphOrder::new(vec![".notdef".into(), "A".into(), "B".into()]);
+        let mut element = Element::new("post");
+        dump_post(&mut element, &data, &order);
+        assert_eq!(
+            element.child("italicAngle").unwrap().get_attr("value"),
+            Some("-12.5")
+        );
+        let extra: Vec<&str> = element
+            .child("extraNames")
+            .unwrap()
+            .elements()
+            .map(|e| e.get_attr("name").unwrap())
+            .collect();
+        assert_eq!(extra, vec!["B.alt"]);
+        assert_eq!(compile_post(&element, &order).unwrap(), post);
+    }
+}
@@ -0,0 +1,464 @@
+//! TrueType outlines, metrics and programs: `glyf`, `loca`, `hmtx`, `cvt`,
+//! `fpgm`, `prep` and `gasp`
+use super::xml::Element;
+use super::{parse_float, parse_int, GlyphOrder, TtxError};
+use crate::instructions::{assemble, disassemble, from_text, to_text};
+use crate::tables;
+use crate::tables::glyf::{Component, ComponentFlags, Glyph, Point};
+use kurbo::{Affine, Rect};
+
+/// The component flags which are kept in XML; the others are worked out
+/// when the glyph is compiled
+const KEPT_FLAGS: ComponentFlags = ComponentFlags::from_bits_truncate(
+    ComponentFlags::ROUND_XY_TO_GRID.bits()
+        | ComponentFlags::USE_MY_METRICS.bits()
+        | ComponentFlags::SCALED_COMPONENT_OFFSET.bits()
+        | ComponentFlags::UNSCALED_COMPONENT_OFFSET.bits()
+        | ComponentFlags::OVERLAP_COMPOUND.bits(),
+);
+
+/// Formats a number with at most the given number of decimal places,
+/// keeping a fractional part
+fn format_float(value: f64, precision: usize) -> String {
+    let text = format!("{:.*}", precision, value);
+    let text = text.trim_end_matches('0');
+    if text.ends_with('.') {
+        format!("{}0", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes TrueType bytecode as an `<assembly>` element
+fn dump_assembly(bytecode: &[u8]) -> Element {
+    let mut assembly = Element::new("assembly");
+    match disassemble(bytecode) {
+        Ok(instructions) => assembly.text(&to_text(&instructions)),
+        Err(e) => {
+            log::warn!("Could not disassemble instructions: {}", e);
+        }
+    }
+    assembly
+}
+
+fn compile_assembly(element: &Element) -> Result<Vec<u8>, TtxError> {
+    let assembly = match element.child("assembly") {
+        Some(assembly) => assembly,
+        None => return Ok(vec![]),
+    };
+    let instructions = from_text(&assembly.content())
+        .map_err(|e| TtxError::Invalid(format!("Bad instructions: {}", e)))?;
+    assemble(&instructions).map_err(|e| TtxError::Invalid(format!("Bad instructions: {}", e)))
+}
+
+pub(crate) fn dump_program(element: &mut Element, bytecode: &[u8]) {
+    element.push(dump_assembly(bytecode));
+}
+
+pub(crate) fn compile_program(element: &Element) -> Result<Vec<u8>, TtxError> {
+    compile_assembly(element)
+}
+
+pub(crate) fn dump_cvt(element: &mut Element, cvt: &tables::cvt::cvt) {
+    for (index, value) in cvt.0.iter().enumerate() {
+        element.push(Element::new("cv").attr("index", index).attr("value", value));
+    }
+}
+
+pub(crate) fn compile_cvt(element: &Element) -> Result<tables::cvt::cvt, TtxError> {
+    let mut values = vec![];
+    for cv in element.elements().filter(|e| e.name == "cv") {
+        let index = parse_int(cv.required("index")?)? as usize;
+        if values.len() <= index {
+            values.resize(index + 1, 0);
+        }
+        values[index] = parse_int(cv.required("value")?)? as i16;
+    }
+    Ok(tables::cvt::cvt(values))
+}
+
+pub(crate) fn dump_gasp(element: &mut Element, gasp: &tables::gasp::gasp) {
+    for range in &gasp.gaspRanges {
+        element.push(
+            Element::new("gaspRange")
+                .attr("rangeMaxPPEM", range.rangeMaxPPEM)
+                .attr("rangeGaspBehavior", range.rangeGaspBehavior.bits()),
+        );
+    }
+}
+
+pub(crate) fn compile_gasp(element: &Element) -> Result<tables::gasp::gasp, TtxError> {
+    let mut ranges = vec![];
+    for range in element.elements().filter(|e| e.name == "gaspRange") {
+        let behavior = parse_int(range.required("rangeGaspBehavior")?)? as u16;
+        ranges.push(tables::gasp::GaspRecord {
+            rangeMaxPPEM: parse_int(range.required("rangeMaxPPEM")?)? as u16,
+            rangeGaspBehavior: tables::gasp::RangeGaspBehaviorFlags::from_bits_truncate(behavior),
+        });
+    }
+    // Version 1 introduced the symmetric flags
+    let version = if ranges.iter().any(|r| r.rangeGaspBehavior.bits() > 3) {
+        1
+    } else {
+        0
+    };
+    Ok(tables::gasp::gasp {
+        version,
+        gaspRanges: ranges,
+    })
+}
+
+pub(crate) fn dump_hmtx(element: &mut Element, hmtx: &tables::hmtx::hmtx, order: &GlyphOrder) {
+    // ttx lists metrics by glyph name
+    let mut metrics: Vec<(String, &tables::hmtx::Metric)> = hmtx
+        .metrics
+        .iter()
+        .enumerate()
+        .map(|(gid, metric)| (order.name(gid as u16), metric))
+        .collect();
+    metrics.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, metric) in metrics {
+        element.push(
+            Element::new("mtx")
+                .attr("name", name)
+                .attr("width", metric.advanceWidth)
+                .attr("lsb", metric.lsb),
+        );
+    }
+}
+
+pub(crate) fn compile_hmtx(
+    element: &Element,
+    order: &GlyphOrder,
+) -> Result<tables::hmtx::hmtx, TtxError> {
+    let mut metrics = vec![
+        tables::hmtx::Metric {
+            advanceWidth: 0,
+            lsb: 0
+        };
+        order.len()
+    ];
+    for mtx in element.elements().filter(|e| e.name == "mtx") {
+        let gid = order.id(mtx.required("name")?)? as usize;
+        if let Some(metric) = metrics.get_mut(gid) {
+            metric.advanceWidth = parse_int(mtx.required("width")?)? as u16;
+            metric.lsb = parse_int(mtx.required("lsb")?)? as i16;
+        }
+    }
+    Ok(tables::hmtx::hmtx { metrics })
+}
+
+pub(crate) fn dump_loca(element: &mut Element) {
+    element.comment("The 'loca' table will be calculated by the compiler");
+}
+
+fn dump_component(component: &Component, order: &GlyphOrder) -> Element {
+    let mut element =
+        Element::new("component").attr("glyphName", order.name(component.glyph_index));
+    let [x_scale, scale01, scale10, y_scale, x, y] = component.transformation.as_coeffs();
+    match component.match_points {
+        Some((first, second)) => {
+            element = element.attr("firstPt", first).attr("secondPt", second);
+        }
+        None => {
+            element = element.attr("x", x).attr("y", y);
+        }
+    }
+    let scale = |value: f64| format_float(value, 6);
+    if scale01 != 0.0 || scale10 != 0.0 {
+        element = element
+            .attr("scalex", scale(x_scale))
+            .attr("scale01", scale(scale01))
+            .attr("scale10", scale(scale10))
+            .attr("scaley", scale(y_scale));
+    } else if x_scale != y_scale {
+        element = element
+            .attr("scalex", scale(x_scale))
+            .attr("scaley", scale(y_scale));
+    } else if x_scale != 1.0 {
+        element = element.attr("scale", scale(x_scale));
+    }
+    element.attr(
+        "flags",
+        format!("0x{:x}", (component.flags & KEPT_FLAGS).bits()),
+    )
+}
+
+fn dump_glyph(glyph: &Glyph, name: String, order: &GlyphOrder) -> Element {
+    let mut element = Element::new("TTGlyph").attr("name", name);
+    if glyph.is_empty() {
+        return element;
+    }
+    element = element
+        .attr("xMin", glyph.xMin)
+        .attr("yMin", glyph.yMin)
+        .attr("xMax", glyph.xMax)
+        .attr("yMax", glyph.yMax);
+    for (index, contour) in glyph.contours.iter().enumerate() {
+        let mut contour_element = Element::new("contour");
+        for (point_index, point) in contour.iter().enumerate() {
+            let mut pt = Element::new("pt")
+                .attr("x", point.x)
+                .attr("y", point.y)
+                .attr("on", point.on_curve as u8);
+            if glyph.overlap && index == 0 && point_index == 0 {
+                pt = pt.attr("overlap", 1);
+            }
+            contour_element.push(pt);
+        }
+        element.push(contour_element);
+    }
+    for component in &glyph.components {
+        element.push(dump_component(component, order));
+    }
+    let mut instructions = Element::new("instructions");
+    if !glyph.instructions.is_empty() {
+        instructions.push(dump_assembly(&glyph.instructions));
+    }
+    element.push(instructions);
+    element
+}
+
+pub(crate) fn dump_glyf(element: &mut Element, glyf: &tables::glyf::glyf, order: &GlyphOrder) {
+    element.comment("The xMin, yMin, xMax and yMax values will be recalculated by the compiler.");
+    for (gid, glyph) in glyf.glyphs.iter().enumerate() {
+        element.push(dump_glyph(glyph, order.name(gid as u16), order));
+    }
+}
+
+fn compile_component(element: &Element, order: &GlyphOrder) -> Result<Component, TtxError> {
+    let attr = |name: &str| element.get_attr(name);
+    let number = |name: &str, default: f64| attr(name).map_or(Ok(default), parse_float);
+    let match_points = match (attr("firstPt"), attr("secondPt")) {
+        (Some(first), Some(second)) => Some((parse_int(first)? as u16, parse_int(second)? as u16)),
+        _ => None,
+    };
+    let (x_scale, y_scale) = match attr("scale") {
+        Some(scale) => (parse_float(scale)?, parse_float(scale)?),
+        None => (number("scalex", 1.0)?, number("scaley", 1.0)?),
+    };
+    let flags = attr("flags").map_or(Ok(0), parse_int)? as u16;
+    Ok(Component {
+        glyph_index: order.id(element.required("glyphName")?)?,
+        transformation: Affine::new([
+            x_scale,
+            number("scale01", 0.0)?,
+            number("scale10", 0.0)?,
+            y_scale,
+            number("x", 0.0)?,
+            number("y", 0.0)?,
+        ]),
+        match_points,
+        flags: ComponentFlags::from_bits_truncate(flags) & KEPT_FLAGS,
+    })
+}
+
+fn compile_glyph(element: &Element, order: &GlyphOrder) -> Result<Glyph, TtxError> {
+    let mut glyph = Glyph {
+        xMin: 0,
+        xMax: 0,
+        yMin: 0,
+        yMax: 0,
+        contours: vec![],
+        instructions: vec![],
+        components: vec![],
+        overlap: false,
+    };
+    for child in element.elements() {
+        match child.name.as_str() {
+            "contour" => {
+                let mut contour = vec![];
+                for pt in child.elements().filter(|e| e.name == "pt") {
+                    contour.push(Point {
+                        x: parse_int(pt.required("x")?)? as i16,
+                        y: parse_int(pt.required("y")?)? as i16,
+                        on_curve: parse_int(pt.get_attr("on").unwrap_or("1"))? & 1 == 1,
+                    });
+                    if pt.get_attr("overlap").map_or(Ok(0), parse_int)? != 0 {
+                        glyph.overlap = true;
+                    }
+                }
+                glyph.contours.push(contour);
+            }
+            "component" => glyph.components.push(compile_component(child, order)?),
+            "instructions" => glyph.instructions = compile_assembly(child)?,
+            _ => {}
+        }
+    }
+    Ok(glyph)
+}
+
+/// The bounds of a glyph, with components resolved
+fn glyph_bounds(glyphs: &[Glyph], gid: usize, depth: u8) -> Option<Rect> {
+    let glyph = glyphs.get(gid)?;
+    let points = glyph
+        .contours
+        .iter()
+        .flatten()
+        .map(|p| Rect::from_points((p.x as f64, p.y as f64), (p.x as f64, p.y as f64)));
+    let components = glyph.components.iter().filter_map(|component| {
+        if depth > 64 {
+            return None;
+        }
+        let bounds = glyph_bounds(glyphs, component.glyph_index as usize, depth + 1)?;
+        Some(component.transformation.transform_rect_bbox(bounds))
+    });
+    points.chain(components).reduce(|a, b| a.union(b))
+}
+
+pub(crate) fn compile_glyf(
+    element: &Element,
+    order: &GlyphOrder,
+) -> Result<tables::glyf::glyf, TtxError> {
+    let mut glyphs: Vec<Option<Glyph>> = vec![None; order.len()];
+    for child in element.elements().filter(|e| e.name == "TTGlyph") {
+        let gid = order.id(child.required("name")?)? as usize;
+        if gid < glyphs.len() {
+            glyphs[gid] = Some(compile_glyph(child, order)?);
+        }
+    }
+    let mut glyphs: Vec<Glyph> = glyphs
+        .into_iter()
+        .map(|glyph| {
+            glyph.unwrap_or(Glyph {
+                xMin: 0,
+                xMax: 0,
+                yMin: 0,
+                yMax: 0,
+                contours: vec![],
+                instructions: vec![],
+                components: vec![],
+                overlap: false,
+            })
+        })
+        .collect();
+    let bounds: Vec<Option<Rect>> = (0..glyphs.len())
+        .map(|gid| glyph_bounds(&glyphs, gid, 0))
+        .collect();
+    for (glyph, bounds) in glyphs.iter_mut().zip(bounds) {
+        if let Some(bounds) = bounds {
+            glyph.set_bounds_rect(bounds);
+        }
+    }
+    Ok(tables::glyf::glyf { glyphs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order() -> GlyphOrder {
+        GlyphOrder::new(vec![".notdef".into(), "A".into(), "Aacute".into()])
+    }
+
+    #[test]
+    fn test_glyf_roundtrip() {
+        let square = |size: i16| {
+            [(0, 0), (0, size), (size, size), (size, 0)]
+                .iter()
+                .map(|&(x, y)| Point {
+                    x,
+                    y,
+                    on_curve: x == 0,
+                })
+                .collect::<Vec<_>>()
+        };
+        let glyf = tables::glyf::glyf {
+            glyphs: vec![
+                Glyph {
+                    xMin: 0,
+                    xMax: 0,
+                    yMin: 0,
+                    yMax: 0,
+                    contours: vec![],
+                    instructions: vec![],
+                    components: vec![],
+                    overlap: false,
+                },
+                Glyph {
+                    xMin: 0,
+                    xMax: 500,
+                    yMin: 0,
+                    yMax: 500,
+                    contours: vec![square(500)],
+                    instructions: vec![0xB0, 0x01, 0x2B],
+                    components: vec![],
+                    overlap: true,
+                },
+                Glyph {
+                    xMin: 10,
+                    xMax: 260,
+                    yMin: 20,
+                    yMax: 270,
+                    contours: vec![],
+                    instructions: vec![],
+                    components: vec![Component {
+                        glyph_index: 1,
+                        transformation: Affine::new([0.5, 0.0, 0.0, 0.5, 10.0, 20.0]),
+                        match_points: None,
+                        flags: ComponentFlags::USE_MY_METRICS | ComponentFlags::MORE_COMPONENTS,
+                    }],
+                    overlap: false,
+                },
+            ],
+        };
+        let mut element = Element::new("glyf");
+        dump_glyf(&mut element, &glyf, &order());
+        let glyphs: Vec<&Element> = element.elements().collect();
+        assert_eq!(glyphs[0], &Element::new("TTGlyph").attr("name", ".notdef"));
+        let component = glyphs[2].child("component").unwrap();
+        assert_eq!(component.get_attr("scale"), Some("0.5"));
+        assert_eq!(component.get_attr("flags"), Some("0x200"));
+        let assembly = glyphs[1].child("instructions").unwrap().child("assembly");
+        assert_eq!(assembly.unwrap().content(), "PUSHB[ ] 1\nCALL[ ]");
+
+        let mut compiled = compile_glyf(&element, &order()).unwrap();
+        compiled.glyphs[2].components[0].flags |= ComponentFlags::MORE_COMPONENTS;
+        assert_eq!(compiled, glyf);
+    }
+
+    #[test]
+    fn test_hmtx_cvt_gasp() {
+        let hmtx = tables::hmtx::hmtx {
+            metrics: vec![
+                tables::hmtx::Metric {
+                    advanceWidth: 500,
+                    lsb: 0,
+                },
+                tables::hmtx::Metric {
+                    advanceWidth: 600,
+                    lsb: 10,
+                },
+                tables::hmtx::Metric {
+                    advanceWidth: 600,
+                    lsb: 20,
+                },
+            ],
+        };
+        let mut element = Element::new("hmtx");
+        dump_hmtx(&mut element, &hmtx, &order());
+        // Sorted by name
+        let names: Vec<&str> = element
+            .elements()
+            .map(|e| e.get_attr("name").unwrap())
+            .collect();
+        assert_eq!(names, vec![".notdef", "A", "Aacute"]);
+        assert_eq!(compile_hmtx(&element, &order()).unwrap(), hmtx);
+
+        let cvt = tables::cvt::cvt(vec![10, -20, 30]);
+        let mut element = Element::new("cvt");
+        dump_cvt(&mut element, &cvt);
+        assert_eq!(compile_cvt(&element).unwrap(), cvt);
+
+        let gasp = tables::gasp::gasp {
+            version: 1,
+            gaspRanges: vec![tables::gasp::GaspRecord {
+                rangeMaxPPEM: 65535,
+                rangeGaspBehavior: tables::gasp::RangeGaspBehaviorFlags::from_bits_truncate(15),
+            }],
+        };
+        let mut element = Element::new("gasp");
+        dump_gasp(&mut element, &gasp);
+        assert_eq!(compile_gasp(&element).unwrap(), gasp);
+    }
+}
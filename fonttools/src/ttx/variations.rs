@@ -0,0 +1,187 @@
+//! The variation tables with a typed representation: `fvar` and `avar`
+//!
+//! Other variation tables (`gvar`, `cvar`, `HVAR` and so on) are written
+//! as hexadecimal data.
+use super::binary::format_fixed_point;
+use super::xml::Element;
+use super::{parse_float, parse_int, TtxError};
+use crate::tables;
+use crate::tables::fvar::{InstanceRecord, VariationAxisRecord};
+use otspec::types::Tag;
+
+/// Formats a 16.16 value
+fn fixed(value: f32) -> String {
+    format_fixed_point((value as f64 * 65536.0).round() as i64, 16)
+}
+
+/// Formats a 2.14 value
+fn f2dot14(value: f32) -> String {
+    format_fixed_point((value as f64 * 16384.0).round() as i64, 14)
+}
+
+fn axis_tag(text: &str) -> Result<Tag, TtxError> {
+    Tag::from_raw(format!("{:<4}", text))
+        .map_err(|_| TtxError::Invalid(format!("Bad axis tag {}", text)))
+}
+
+pub(crate) fn dump_fvar(element: &mut Element, fvar: &tables::fvar::fvar) {
+    for axis in &fvar.axes {
+        let mut axis_element = Element::new("Axis");
+        for (name, text) in [
+            ("AxisTag", axis.axisTag.to_string()),
+            ("Flags", format!("0x{:x}", axis.flags)),
+            ("MinValue", fixed(axis.minValue)),
+            ("DefaultValue", fixed(axis.defaultValue)),
+            ("MaxValue", fixed(axis.maxValue)),
+            ("AxisNameID", axis.axisNameID.to_string()),
+        ] {
+            axis_element.push(Element::with_text(name, &text));
+        }
+        element.push(axis_element);
+    }
+    for instance in &fvar.instances {
+        let mut instance_element = Element::new("NamedInstance")
+            .attr("flags", format!("0x{:x}", instance.flags))
+            .attr("subfamilyNameID", instance.subfamilyNameID);
+        if let Some(id) = instance.postscriptNameID {
+            instance_element = instance_element.attr("postscriptNameID", id);
+        }
+        for (axis, value) in fvar.axes.iter().zip(&instance.coordinates) {
+            instance_element.push(
+                Element::new("coord")
+                    .attr("axis", axis.axisTag.as_str())
+                    .attr("value", fixed(*value)),
+            );
+        }
+        element.push(instance_element);
+    }
+}
+
+pub(crate) fn compile_fvar(element: &Element) -> Result<tables::fvar::fvar, TtxError> {
+    let mut axes = vec![];
+    for axis in element.elements().filter(|e| e.name == "Axis") {
+        let field = |name: &str| {
+            axis.child(name)
+                .map(|child| child.content())
+                .ok_or_else(|| TtxError::Invalid(format!("<Axis> has no <{}>", name)))
+        };
+        axes.push(VariationAxisRecord {
+            axisTag: axis_tag(&field("AxisTag")?)?,
+            flags: parse_int(&field("Flags")?)? as u16,
+            minValue: parse_float(&field("MinValue")?)? as f32,
+            defaultValue: parse_float(&field("DefaultValue")?)? as f32,
+            maxValue: parse_float(&field("MaxValue")?)? as f32,
+            axisNameID: parse_int(&field("AxisNameID")?)? as u16,
+        });
+    }
+    let mut instances = vec![];
+    for instance in element.elements().filter(|e| e.name == "NamedInstance") {
+        // Axes missing from an instance are at their default
+        let mut coordinates: Vec<f32> = axes.iter().map(|axis| axis.defaultValue).collect();
+        for coord in instance.elements().filter(|e| e.name == "coord") {
+            let tag = axis_tag(coord.required("axis")?)?;
+            if let Some(index) = axes.iter().position(|axis| axis.axisTag == tag) {
+                coordinates[index] = parse_float(coord.required("value")?)? as f32;
+            }
+        }
+        instances.push(InstanceRecord {
+            subfamilyNameID: parse_int(instance.required("subfamilyNameID")?)? as u16,
+            flags: instance.get_attr("flags").map_or(Ok(0), parse_int)? as u16,
+            coordinates,
+            postscriptNameID: instance
+                .get_attr("postscriptNameID")
+                .map(parse_int)
+                .transpose()?
+                .map(|id| id as u16),
+        });
+    }
+    Ok(tables::fvar::fvar { axes, instances })
+}
+
+pub(crate) fn dump_avar(
+    element: &mut Element,
+    avar: &tables::avar::avar,
+    fvar: Option<&tables::fvar::fvar>,
+) {
+    for (index, map) in avar.maps.iter().enumerate() {
+        let tag = fvar
+            .and_then(|fvar| fvar.axes.get(index))
+            .map_or_else(|| format!("{:04}", index), |axis| axis.axisTag.to_string());
+        let mut segment = Element::new("segment").attr("axis", tag);
+        for (from, to) in &map.0 {
+            segment.push(
+                Element::new("mapping")
+                    .attr("from", f2dot14(*from))
+                    .attr("to", f2dot14(*to)),
+            );
+        }
+        element.push(segment);
+    }
+}
+
+pub(crate) fn compile_avar(
+    element: &Element,
+    fvar: Option<&tables::fvar::fvar>,
+) -> Result<tables::avar::avar, TtxError> {
+    let axes = fvar.map_or(&[][..], |fvar| &fvar.axes);
+    let mut maps = vec![tables::avar::SegmentMap(vec![]); axes.len()];
+    for segment in element.elements().filter(|e| e.name == "segment") {
+        let tag = axis_tag(segment.required("axis")?)?;
+        let index = axes
+            .iter()
+            .position(|axis| axis.axisTag == tag)
+            .ok_or_else(|| TtxError::Invalid(format!("avar segment for unknown axis {}", tag)))?;
+        for mapping in segment.elements().filter(|e| e.name == "mapping") {
+            maps[index].0.push((
+                parse_float(mapping.required("from")?)? as f32,
+                parse_float(mapping.required("to")?)? as f32,
+            ));
+        }
+    }
+    Ok(tables::avar::avar { maps })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag;
+
+    #[test]
+    fn test_fvar_avar_roundtrip() {
+        let fvar = tables::fvar::fvar {
+            axes: vec![VariationAxisRecord {
+                axisTag: tag!("wght"),
+                flags: 0,
+                minValue: 100.0,
+                defaultValue: 400.0,
+                maxValue: 900.0,
+                axisNameID: 256,
+            }],
+            instances: vec![InstanceRecord {
+                subfamilyNameID: 257,
+                flags: 0,
+                coordinates: vec![700.0],
+                postscriptNameID: Some(258),
+            }],
+        };
+        let mut element = Element::new("fvar");
+        dump_fvar(&mut element, &fvar);
+        let axis = element.child("Axis").unwrap();
+        assert_eq!(axis.child("MaxValue").unwrap().content(), "900.0");
+        assert_eq!(compile_fvar(&element).unwrap(), fvar);
+
+        let avar = tables::avar::avar {
+            maps: vec![tables::avar::SegmentMap(vec![
+                (-1.0, -1.0),
+                (0.0, 0.0),
+                (0.5, 0.25),
+                (1.0, 1.0),
+            ])],
+        };
+        let mut element = Element::new("avar");
+        dump_avar(&mut element, &avar, Some(&fvar));
+        let segment = element.child("segment").unwrap();
+        assert_eq!(segment.get_attr("axis"), Some("wght"));
+        assert_eq!(compile_avar(&element, Some(&fvar)).unwrap(), avar);
+    }
+}
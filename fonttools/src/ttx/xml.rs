@@ -0,0 +1,200 @@
+//! A minimal XML element tree, with a writer producing `ttx`-style layout
+use super::TtxError;
+use std::fmt::Write;
+use xml::reader::{EventReader, XmlEvent};
+
+/// A piece of an element's content
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+    Comment(String),
+}
+
+/// An XML element
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+    /// Whether text content is written on the same line as the tags
+    pub inline: bool,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Element {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds an attribute, builder-style
+    pub fn attr(mut self, name: &str, value: impl ToString) -> Self {
+        self.attributes.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// An element whose text is written between its tags on one line,
+    /// such as `<AxisTag>wght</AxisTag>`
+    pub fn with_text(name: &str, text: &str) -> Self {
+        let mut element = Element::new(name);
+        element.text(text);
+        element.inline = true;
+        element
+    }
+
+    /// An element with a single `value` attribute, as used for simple
+    /// fields
+    pub fn value(name: &str, value: impl ToString) -> Self {
+        Element::new(name).attr("value", value)
+    }
+
+    pub fn push(&mut self, element: Element) {
+        self.children.push(Node::Element(element));
+    }
+
+    pub fn comment(&mut self, comment: &str) {
+        self.children.push(Node::Comment(comment.to_string()));
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.children.push(Node::Text(text.to_string()));
+    }
+
+    pub fn get_attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns an attribute which must be present
+    pub fn required(&self, name: &str) -> Result<&str, TtxError> {
+        self.get_attr(name)
+            .ok_or_else(|| TtxError::Invalid(format!("<{}> has no {} attribute", self.name, name)))
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// The element's text content, with surrounding whitespace removed
+    pub fn content(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            if let Node::Text(t) = node {
+                text.push_str(t);
+            }
+        }
+        text.trim().to_string()
+    }
+
+    fn write(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        write!(out, "{}<{}", indent, self.name).unwrap();
+        for (name, value) in &self.attributes {
+            write!(out, " {}=\"{}\"", name, escape(value, true)).unwrap();
+        }
+        if self.children.is_empty() {
+            out.push_str("/>\n");
+            return;
+        }
+        if self.inline {
+            writeln!(out, ">{}</{}>", escape(&self.content(), false), self.name).unwrap();
+            return;
+        }
+        out.push_str(">\n");
+        for node in &self.children {
+            match node {
+                Node::Element(element) => element.write(out, depth + 1),
+                Node::Comment(comment) => {
+                    writeln!(out, "{}  <!-- {} -->", indent, comment).unwrap()
+                }
+                Node::Text(text) => {
+                    for line in text.lines() {
+                        writeln!(out, "{}  {}", indent, escape(line, false)).unwrap();
+                    }
+                }
+            }
+        }
+        writeln!(out, "{}</{}>", indent, self.name).unwrap();
+    }
+
+    /// Writes a `ttFont` element and its tables as a document, with a
+    /// blank line between tables as `ttx` does
+    pub fn to_document(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        write!(out, "<{}", self.name).unwrap();
+        for (name, value) in &self.attributes {
+            write!(out, " {}=\"{}\"", name, escape(value, true)).unwrap();
+        }
+        out.push_str(">\n");
+        for table in self.elements() {
+            out.push('\n');
+            table.write(&mut out, 1);
+        }
+        writeln!(out, "\n</{}>", self.name).unwrap();
+        out
+    }
+
+    /// Parses a document into its root element. Comments and whitespace
+    /// between elements are dropped.
+    pub fn parse(text: &str) -> Result<Element, TtxError> {
+        let mut stack: Vec<Element> = vec![];
+        for event in EventReader::from_str(text) {
+            match event.map_err(|e| TtxError::Xml(e.to_string()))? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => stack.push(Element {
+                    name: name.local_name,
+                    attributes: attributes
+                        .into_iter()
+                        .map(|a| (a.name.local_name, a.value))
+                        .collect(),
+                    ..Default::default()
+                }),
+                XmlEvent::EndElement { .. } => {
+                    let element = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        Some(parent) => parent.push(element),
+                        None => return Ok(element),
+                    }
+                }
+                XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.text(&text);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(TtxError::Xml("document has no root element".to_string()))
+    }
+}
+
+/// Escapes text for XML. Control characters which XML cannot represent
+/// are dropped.
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\r' => escaped.push_str("&#xD;"),
+            '\n' if attribute => escaped.push_str("&#xA;"),
+            c if (c as u32) < 0x20 && c != '\t' && c != '\n' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
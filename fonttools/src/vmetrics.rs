@@ -0,0 +1,332 @@
+//! Family-wide vertical metrics
+//!
+//! The fonts of a family should share their vertical metrics, or lines of
+//! text will change height when the style changes. [`FamilyMetrics`]
+//! gathers the extremes of a set of fonts (or of the masters of a source),
+//! and a [`VerticalMetricsPolicy`] turns them into the `hhea` and `OS/2`
+//! values which [`VerticalMetrics::apply`] writes into each font.
+//!
+//! ```no_run
+//! # use fonttools::font::Font;
+//! use fonttools::vmetrics::{apply_to_family, VerticalMetricsPolicy};
+//!
+//! let mut fonts: Vec<Font> = ["Test-Regular.ttf", "Test-Bold.ttf"]
+//!     .iter()
+//!     .map(|path| Font::load(path).expect("Could not load font"))
+//!     .collect();
+//! apply_to_family(&mut fonts, VerticalMetricsPolicy::GoogleFonts)
+//!     .expect("Could not set vertical metrics");
+//! ```
+use crate::font::Font;
+use crate::tables;
+use otspec::types::*;
+use otspec::DeserializationError;
+use std::str::FromStr;
+
+/// The `USE_TYPO_METRICS` bit of the OS/2 table's `fsSelection` field
+const USE_TYPO_METRICS: u16 = 1 << 7;
+
+/// An error produced while computing or applying vertical metrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerticalMetricsError {
+    /// A required table was not present in the font
+    MissingTable(Tag),
+    /// A table could not be read
+    Table(String),
+}
+
+impl std::fmt::Display for VerticalMetricsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerticalMetricsError::MissingTable(tag) => write!(f, "missing {} table", tag),
+            VerticalMetricsError::Table(e) => write!(f, "could not read table: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for VerticalMetricsError {}
+
+impl From<DeserializationError> for VerticalMetricsError {
+    fn from(e: DeserializationError) -> Self {
+        VerticalMetricsError::Table(e.0)
+    }
+}
+
+/// How the vertical metrics of a family are derived from its extremes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalMetricsPolicy {
+    /// The typo metrics keep the designer's ascender, descender and line
+    /// gap, while the `hhea` and win metrics both cover the family's bounding
+    /// box, with no line gap, so that no platform clips any glyph.
+    Bounds,
+    /// The Google Fonts rules: the `hhea` metrics repeat the typo metrics,
+    /// the win metrics cover the family's bounding box, and
+    /// `USE_TYPO_METRICS` is set so that Windows uses the typo metrics too.
+    GoogleFonts,
+}
+
+impl FromStr for VerticalMetricsPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bounds" => Ok(VerticalMetricsPolicy::Bounds),
+            "googlefonts" | "google-fonts" | "gf" => Ok(VerticalMetricsPolicy::GoogleFonts),
+            _ => Err(format!("Unknown vertical metrics policy {}", s)),
+        }
+    }
+}
+
+/// The extremes of a family, from which its vertical metrics are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FamilyMetrics {
+    /// The highest typographic ascender in the family
+    pub ascender: i16,
+    /// The lowest typographic descender in the family
+    pub descender: i16,
+    /// The largest typographic line gap in the family
+    pub line_gap: i16,
+    /// The highest point of any glyph in the family
+    pub y_max: i16,
+    /// The lowest point of any glyph in the family
+    pub y_min: i16,
+}
+
+impl FamilyMetrics {
+    /// Reads the typo metrics of a font from its `OS/2` table, and its
+    /// bounds from its `head` table. (For a variable font, these are the
+    /// bounds of the default instance.)
+    pub fn from_font(font: &Font) -> Result<Self, VerticalMetricsError> {
+        let head = font
+            .tables
+            .head()?
+            .ok_or(VerticalMetricsError::MissingTable(tables::head::TAG))?;
+        let os2 = font
+            .tables
+            .os2()?
+            .ok_or(VerticalMetricsError::MissingTable(tables::os2::TAG))?;
+        Ok(FamilyMetrics {
+            ascender: os2.sTypoAscender,
+            descender: os2.sTypoDescender,
+            line_gap: os2.sTypoLineGap,
+            y_max: head.yMax,
+            y_min: head.yMin,
+        })
+    }
+
+    /// Combines the extremes of two sets of metrics
+    pub fn union(&self, other: &FamilyMetrics) -> FamilyMetrics {
+        FamilyMetrics {
+            ascender: self.ascender.max(other.ascender),
+            descender: self.descender.min(other.descender),
+            line_gap: self.line_gap.max(other.line_gap),
+            y_max: self.y_max.max(other.y_max),
+            y_min: self.y_min.min(other.y_min),
+        }
+    }
+}
+
+/// The vertical metrics to be written to each font of a family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerticalMetrics {
+    /// The `OS/2` table's `sTypoAscender`
+    pub typo_ascender: i16,
+    /// The `OS/2` table's `sTypoDescender`
+    pub typo_descender: i16,
+    /// The `OS/2` table's `sTypoLineGap`
+    pub typo_line_gap: i16,
+    /// The `hhea` table's `ascender`
+    pub hhea_ascender: i16,
+    /// The `hhea` table's `descender`
+    pub hhea_descender: i16,
+    /// The `hhea` table's `lineGap`
+    pub hhea_line_gap: i16,
+    /// The `OS/2` table's `usWinAscent`
+    pub win_ascent: u16,
+    /// The `OS/2` table's `usWinDescent`
+    pub win_descent: u16,
+    /// Whether to set the `USE_TYPO_METRICS` bit of `fsSelection`
+    pub use_typo_metrics: bool,
+}
+
+impl VerticalMetricsPolicy {
+    /// Computes the vertical metrics for a family with the given extremes
+    pub fn compute(&self, family: &FamilyMetrics) -> VerticalMetrics {
+        // The win metrics clip anything outside them, so they must cover
+        // the typo metrics as well as the glyphs.
+        let win_ascent = family.y_max.max(family.ascender).max(0) as u16;
+        let win_descent = (-(family.y_min.min(family.descender) as i32)).max(0) as u16;
+        match self {
+            VerticalMetricsPolicy::Bounds => VerticalMetrics {
+                typo_ascender: family.ascender,
+                typo_descender: family.descender,
+                typo_line_gap: family.line_gap,
+                hhea_ascender: win_ascent.min(i16::MAX as u16) as i16,
+                hhea_descender: -(win_descent.min(i16::MAX as u16) as i16),
+                hhea_line_gap: 0,
+                win_ascent,
+                win_descent,
+                use_typo_metrics: false,
+            },
+            VerticalMetricsPolicy::GoogleFonts => VerticalMetrics {
+                typo_ascender: family.ascender,
+                typo_descender: family.descender,
+                typo_line_gap: family.line_gap,
+                hhea_ascender: family.ascender,
+                hhea_descender: family.descender,
+                hhea_line_gap: family.line_gap,
+                win_ascent,
+                win_descent,
+                use_typo_metrics: true,
+            },
+        }
+    }
+}
+
+impl VerticalMetrics {
+    /// Writes these metrics into the `hhea` and `OS/2` tables of a font
+    pub fn apply(&self, font: &mut Font) -> Result<(), VerticalMetricsError> {
+        let mut hhea = font
+            .tables
+            .hhea()?
+            .ok_or(VerticalMetricsError::MissingTable(tables::hhea::TAG))?;
+        let mut os2 = font
+            .tables
+            .os2()?
+            .ok_or(VerticalMetricsError::MissingTable(tables::os2::TAG))?;
+        hhea.ascender = self.hhea_ascender;
+        hhea.descender = self.hhea_descender;
+        hhea.lineGap = self.hhea_line_gap;
+        os2.sTypoAscender = self.typo_ascender;
+        os2.sTypoDescender = self.typo_descender;
+        os2.sTypoLineGap = self.typo_line_gap;
+        os2.usWinAscent = self.win_ascent;
+        os2.usWinDescent = self.win_descent;
+        if self.use_typo_metrics {
+            // USE_TYPO_METRICS was only defined in version 4
+            os2.version = os2.version.max(4);
+            os2.fsSelection |= USE_TYPO_METRICS;
+        } else {
+            os2.fsSelection &= !USE_TYPO_METRICS;
+        }
+        font.tables.insert(hhea);
+        font.tables.insert(os2);
+        Ok(())
+    }
+}
+
+/// Computes the vertical metrics of a family of fonts under the given
+/// policy, and writes them into every font
+pub fn apply_to_family(
+    fonts: &mut [Font],
+    policy: VerticalMetricsPolicy,
+) -> Result<VerticalMetrics, VerticalMetricsError> {
+    let mut family: Option<FamilyMetrics> = None;
+    for font in fonts.iter() {
+        let metrics = FamilyMetrics::from_font(font)?;
+        family = Some(family.map_or(metrics, |f| f.union(&metrics)));
+    }
+    let metrics = policy.compute(&family.unwrap_or_default());
+    for font in fonts.iter_mut() {
+        metrics.apply(font)?;
+    }
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family_font(typo: (i16, i16, i16), y_bounds: (i16, i16)) -> Font {
+        let mut font = crate::proof::tests::test_font();
+        font.tables
+            .insert(tables::head::new(1.0, 1000, 0, y_bounds.0, 500, y_bounds.1));
+        let mut os2 = crate::tables::os2::tests::blank_os2();
+        os2.version = 3;
+        os2.sTypoAscender = typo.0;
+        os2.sTypoDescender = typo.1;
+        os2.sTypoLineGap = typo.2;
+        font.tables.insert(os2);
+        font
+    }
+
+    fn family() -> Vec<Font> {
+        vec![
+            family_font((750, -250, 0), (-200, 900)),
+            family_font((800, -200, 100), (-300, 850)),
+        ]
+    }
+
+    #[test]
+    fn test_family_metrics() {
+        let fonts = family();
+        let regular = FamilyMetrics::from_font(&fonts[0]).unwrap();
+        let bold = FamilyMetrics::from_font(&fonts[1]).unwrap();
+        assert_eq!(
+            regular.union(&bold),
+            FamilyMetrics {
+                ascender: 800,
+                descender: -250,
+                line_gap: 100,
+                y_max: 900,
+                y_min: -300,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bounds_policy() {
+        let mut fonts = family();
+        let metrics = apply_to_family(&mut fonts, VerticalMetricsPolicy::Bounds).unwrap();
+        assert_eq!(metrics.win_ascent, 900);
+        assert_eq!(metrics.win_descent, 300);
+        for font in &fonts {
+            let hhea = font.tables.hhea().unwrap().unwrap();
+            let os2 = font.tables.os2().unwrap().unwrap();
+            assert_eq!(
+                (hhea.ascender, hhea.descender, hhea.lineGap),
+                (900, -300, 0)
+            );
+            assert_eq!(
+                (os2.sTypoAscender, os2.sTypoDescender, os2.sTypoLineGap),
+                (800, -250, 100)
+            );
+            assert_eq!(os2.fsSelection & USE_TYPO_METRICS, 0);
+        }
+    }
+
+    #[test]
+    fn test_google_fonts_policy() {
+        let mut fonts = family();
+        apply_to_family(&mut fonts, VerticalMetricsPolicy::GoogleFonts).unwrap();
+        for font in &fonts {
+            let hhea = font.tables.hhea().unwrap().unwrap();
+            let os2 = font.tables.os2().unwrap().unwrap();
+            assert_eq!(
+                (hhea.ascender, hhea.descender, hhea.lineGap),
+                (800, -250, 100)
+            );
+            assert_eq!((os2.usWinAscent, os2.usWinDescent), (900, 300));
+            assert_eq!(os2.fsSelection & USE_TYPO_METRICS, USE_TYPO_METRICS);
+            assert_eq!(os2.version, 4);
+        }
+    }
+
+    #[test]
+    fn test_missing_table() {
+        let mut fonts = vec![crate::proof::tests::test_font()];
+        assert_eq!(
+            apply_to_family(&mut fonts, VerticalMetricsPolicy::Bounds),
+            Err(VerticalMetricsError::MissingTable(tables::os2::TAG))
+        );
+    }
+
+    #[test]
+    fn test_policy_from_str() {
+        assert_eq!(
+            "GoogleFonts".parse::<VerticalMetricsPolicy>(),
+            Ok(VerticalMetricsPolicy::GoogleFonts)
+        );
+        assert!("nonsense".parse::<VerticalMetricsPolicy>().is_err());
+    }
+}
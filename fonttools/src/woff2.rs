@@ -0,0 +1,875 @@
+//! Compressing fonts to WOFF2
+//!
+//! [`compress`] turns the bytes of a TrueType or CFF font into a WOFF2 file,
+//! as described in the [W3C recommendation](https://www.w3.org/TR/WOFF2/).
+//! The `glyf` and `loca` tables are transformed, splitting the outlines into
+//! streams of like data which Brotli compresses much better than the tables
+//! themselves; every other table is stored as it is. There is no extended
+//! metadata or private data block.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use fonttools::font::Font;
+//!
+//! let mut font = Font::load("Test.ttf").unwrap();
+//! font.save_woff2("Test.woff2").unwrap();
+//! ```
+use std::convert::TryInto;
+
+/// An error compressing a font to WOFF2
+#[derive(Debug)]
+pub enum Woff2Error {
+    /// The font's table directory or outlines could not be read
+    Font(String),
+    /// The table data could not be compressed
+    Compression(std::io::Error),
+}
+
+impl std::fmt::Display for Woff2Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Woff2Error::Font(e) => write!(f, "Font error: {}", e),
+            Woff2Error::Compression(e) => write!(f, "Compression error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Woff2Error {}
+
+const SIGNATURE: u32 = 0x774F4632;
+const HEADER_SIZE: u32 = 48;
+
+/// Tags which the table directory refers to by their index in this list
+/// rather than spelling them out
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// The flag index of a table whose tag follows its flags
+const ARBITRARY_TAG: u8 = 63;
+/// The transform version of `glyf` and `loca` tables which are stored as
+/// they are. (For every other table it is version 0.)
+const NULL_GLYF_TRANSFORM: u8 = 3;
+/// Bit 11 of the `head` table's flags, which says the font has been
+/// through a lossless transform
+const HEAD_FLAG_TRANSFORMED: u16 = 1 << 11;
+
+// Simple glyph flags
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+// Component flags
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, Woff2Error> {
+    data.get(offset)
+        .copied()
+        .ok_or_else(|| Woff2Error::Font(format!("unexpected end of data at {}", offset)))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Woff2Error> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Woff2Error::Font(format!("unexpected end of data at {}", offset)))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Result<i16, Woff2Error> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Woff2Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| Woff2Error::Font(format!("unexpected end of data at {}", offset)))
+}
+
+fn slice(data: &[u8], start: usize, length: usize) -> Result<&[u8], Woff2Error> {
+    data.get(start..start + length)
+        .ok_or_else(|| Woff2Error::Font(format!("unexpected end of data at {}", start)))
+}
+
+/// Writes a number in the variable-length UIntBase128 encoding of the
+/// table directory
+fn push_base128(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Writes a number in the 255UInt16 encoding of the transformed `glyf` table
+fn push_255_u16(out: &mut Vec<u8>, value: u16) {
+    match value {
+        0..=252 => out.push(value as u8),
+        253..=505 => out.extend([255, (value - 253) as u8]),
+        506..=761 => out.extend([254, (value - 506) as u8]),
+        _ => {
+            out.push(253);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+/// Compresses an OpenType font, given as the bytes of its `sfnt` file, to
+/// WOFF2.
+pub fn compress(sfnt: &[u8]) -> Result<Vec<u8>, Woff2Error> {
+    let flavor = read_u32(sfnt, 0)?;
+    let num_tables = read_u16(sfnt, 4)?;
+    let mut tables: Vec<([u8; 4], &[u8])> = Vec::with_capacity(num_tables as usize);
+    for index in 0..num_tables as usize {
+        let record = 12 + 16 * index;
+        let tag: [u8; 4] = slice(sfnt, record, 4)?.try_into().unwrap();
+        let offset = read_u32(sfnt, record + 8)? as usize;
+        let length = read_u32(sfnt, record + 12)? as usize;
+        tables.push((tag, slice(sfnt, offset, length)?));
+    }
+    tables.sort_by_key(|&(tag, _)| tag);
+    let table = |tag: &[u8; 4]| tables.iter().find(|(t, _)| t == tag).map(|&(_, data)| data);
+
+    // The transformed glyf table carries loca too, so loca has to come
+    // straight after glyf
+    let transformed_glyf = match (table(b"glyf"), table(b"loca"), table(b"head")) {
+        (Some(glyf), Some(loca), Some(head)) => {
+            let maxp = table(b"maxp").ok_or_else(|| Woff2Error::Font("no maxp table".into()))?;
+            let num_glyphs = read_u16(maxp, 4)?;
+            let index_format = read_u16(head, 50)?;
+            Some(transform_glyf(glyf, loca, num_glyphs, index_format)?)
+        }
+        _ => None,
+    };
+    // The WOFF version is the font's version
+    let (major_version, minor_version) = match table(b"head") {
+        Some(head) => (read_u16(head, 4)?, read_u16(head, 6)?),
+        None => (0, 0),
+    };
+    if transformed_glyf.is_some() {
+        let loca = tables.iter().position(|(tag, _)| tag == b"loca").unwrap();
+        let loca = tables.remove(loca);
+        let glyf = tables.iter().position(|(tag, _)| tag == b"glyf").unwrap();
+        tables.insert(glyf + 1, loca);
+    }
+
+    let mut directory = vec![];
+    let mut stream = vec![];
+    let mut total_sfnt_size = 12 + 16 * tables.len() as u32;
+    for &(tag, data) in &tables {
+        total_sfnt_size += (data.len() as u32 + 3) & !3;
+        let index = KNOWN_TAGS
+            .iter()
+            .position(|known| **known == tag)
+            .map_or(ARBITRARY_TAG, |index| index as u8);
+        let is_glyf_or_loca = &tag == b"glyf" || &tag == b"loca";
+        let version = if is_glyf_or_loca && transformed_glyf.is_none() {
+            NULL_GLYF_TRANSFORM
+        } else {
+            0
+        };
+        directory.push(index | version << 6);
+        if index == ARBITRARY_TAG {
+            directory.extend(tag);
+        }
+        push_base128(&mut directory, data.len() as u32);
+        match (&tag, &transformed_glyf) {
+            (b"glyf", Some(transformed)) => {
+                push_base128(&mut directory, transformed.len() as u32);
+                stream.extend(transformed);
+            }
+            (b"loca", Some(_)) => push_base128(&mut directory, 0),
+            (b"head", Some(_)) => {
+                let mut head = data.to_vec();
+                let flags = read_u16(&head, 16)? | HEAD_FLAG_TRANSFORMED;
+                head[16..18].copy_from_slice(&flags.to_be_bytes());
+                stream.extend(head);
+            }
+            _ => stream.extend(data),
+        }
+    }
+
+    let mut compressed = vec![];
+    let params = brotli::enc::BrotliEncoderParams {
+        quality: 11,
+        mode: brotli::enc::backward_references::BrotliEncoderMode::BROTLI_MODE_FONT,
+        ..Default::default()
+    };
+    brotli::BrotliCompress(&mut &stream[..], &mut compressed, &params)
+        .map_err(Woff2Error::Compression)?;
+
+    let length = (HEADER_SIZE + directory.len() as u32 + compressed.len() as u32 + 3) & !3;
+    let mut woff2 = Vec::with_capacity(length as usize);
+    woff2.extend(SIGNATURE.to_be_bytes());
+    woff2.extend(flavor.to_be_bytes());
+    woff2.extend(length.to_be_bytes());
+    woff2.extend((tables.len() as u16).to_be_bytes());
+    woff2.extend(0u16.to_be_bytes()); // reserved
+    woff2.extend(total_sfnt_size.to_be_bytes());
+    woff2.extend((compressed.len() as u32).to_be_bytes());
+    woff2.extend(major_version.to_be_bytes());
+    woff2.extend(minor_version.to_be_bytes());
+    // No metadata or private data
+    woff2.extend([0; 20]);
+    woff2.extend(directory);
+    woff2.extend(compressed);
+    woff2.resize(length as usize, 0);
+    Ok(woff2)
+}
+
+/// The streams which a transformed `glyf` table is split into
+#[derive(Default)]
+struct GlyfStreams {
+    n_contours: Vec<u8>,
+    n_points: Vec<u8>,
+    flags: Vec<u8>,
+    glyphs: Vec<u8>,
+    composites: Vec<u8>,
+    bbox_bitmap: Vec<u8>,
+    bboxes: Vec<u8>,
+    instructions: Vec<u8>,
+    overlap_bitmap: Vec<u8>,
+}
+
+fn transform_glyf(
+    glyf: &[u8],
+    loca: &[u8],
+    num_glyphs: u16,
+    index_format: u16,
+) -> Result<Vec<u8>, Woff2Error> {
+    let offsets: Vec<usize> = (0..=num_glyphs as usize)
+        .map(|index| match index_format {
+            0 => read_u16(loca, index * 2).map(|offset| offset as usize * 2),
+            _ => read_u32(loca, index * 4).map(|offset| offset as usize),
+        })
+        .collect::<Result<_, _>>()?;
+    let mut streams = GlyfStreams {
+        bbox_bitmap: vec![0; 4 * (num_glyphs as usize).div_ceil(32)],
+        overlap_bitmap: vec![0; (num_glyphs as usize).div_ceil(8)],
+        ..Default::default()
+    };
+    let mut has_overlaps = false;
+    for (index, window) in offsets.windows(2).enumerate() {
+        let in_glyph = |e: Woff2Error| match e {
+            Woff2Error::Font(e) => Woff2Error::Font(format!("glyph {}: {}", index, e)),
+            e => e,
+        };
+        let glyph = glyf.get(window[0]..window[1]).ok_or_else(|| {
+            Woff2Error::Font(format!("glyph {} is outside the glyf table", index))
+        })?;
+        let n_contours = if glyph.is_empty() {
+            0
+        } else {
+            read_i16(glyph, 0)?
+        };
+        // Decoders work out the bounding boxes of simple glyphs from their
+        // points, so only the ones which differ are stored
+        let explicit_bbox = if n_contours > 0 {
+            let simple = read_simple_glyph(glyph, n_contours as usize).map_err(in_glyph)?;
+            transform_simple_glyph(glyph, &simple, &mut streams);
+            if simple.overlap {
+                streams.overlap_bitmap[index >> 3] |= 0x80 >> (index & 7);
+                has_overlaps = true;
+            }
+            simple.bounds() != read_bbox(glyph)?
+        } else if n_contours < 0 {
+            transform_composite_glyph(glyph, &mut streams).map_err(in_glyph)?;
+            true
+        } else {
+            false
+        };
+        streams.n_contours.extend(n_contours.max(-1).to_be_bytes());
+        if explicit_bbox {
+            streams.bbox_bitmap[index >> 3] |= 0x80 >> (index & 7);
+            streams.bboxes.extend(slice(glyph, 2, 8)?);
+        }
+    }
+
+    let mut out = vec![];
+    out.extend(0u16.to_be_bytes()); // reserved
+    out.extend(u16::from(has_overlaps).to_be_bytes());
+    out.extend(num_glyphs.to_be_bytes());
+    out.extend(index_format.to_be_bytes());
+    let bbox_size = streams.bbox_bitmap.len() + streams.bboxes.len();
+    for size in [
+        streams.n_contours.len(),
+        streams.n_points.len(),
+        streams.flags.len(),
+        streams.glyphs.len(),
+        streams.composites.len(),
+        bbox_size,
+        streams.instructions.len(),
+    ] {
+        out.extend((size as u32).to_be_bytes());
+    }
+    out.extend(streams.n_contours);
+    out.extend(streams.n_points);
+    out.extend(streams.flags);
+    out.extend(streams.glyphs);
+    out.extend(streams.composites);
+    out.extend(streams.bbox_bitmap);
+    out.extend(streams.bboxes);
+    out.extend(streams.instructions);
+    if has_overlaps {
+        out.extend(streams.overlap_bitmap);
+    }
+    Ok(out)
+}
+
+/// A simple glyph's contours and instructions
+struct SimpleGlyph {
+    end_points: Vec<u16>,
+    /// Where the instructions are in the glyph's data
+    instructions: std::ops::Range<usize>,
+    /// Each point's offset from the one before, and whether it is on the
+    /// curve
+    points: Vec<(i16, i16, bool)>,
+    overlap: bool,
+}
+
+impl SimpleGlyph {
+    /// The bounding box of the glyph's points
+    fn bounds(&self) -> [i32; 4] {
+        let (mut x, mut y) = (0, 0);
+        let mut bounds = [i32::MAX, i32::MAX, i32::MIN, i32::MIN];
+        for &(dx, dy, _) in &self.points {
+            x += dx as i32;
+            y += dy as i32;
+            bounds = [
+                bounds[0].min(x),
+                bounds[1].min(y),
+                bounds[2].max(x),
+                bounds[3].max(y),
+            ];
+        }
+        bounds
+    }
+}
+
+/// The bounding box in a glyph's header
+fn read_bbox(glyph: &[u8]) -> Result<[i32; 4], Woff2Error> {
+    Ok([
+        read_i16(glyph, 2)? as i32,
+        read_i16(glyph, 4)? as i32,
+        read_i16(glyph, 6)? as i32,
+        read_i16(glyph, 8)? as i32,
+    ])
+}
+
+fn read_simple_glyph(glyph: &[u8], n_contours: usize) -> Result<SimpleGlyph, Woff2Error> {
+    let end_points: Vec<u16> = (0..n_contours)
+        .map(|index| read_u16(glyph, 10 + 2 * index))
+        .collect::<Result<_, _>>()?;
+    if end_points.windows(2).any(|pair| pair[1] <= pair[0]) {
+        return Err(Woff2Error::Font("contour end points out of order".into()));
+    }
+    let num_points = end_points[n_contours - 1] as usize + 1;
+    let instructions_start = 12 + 2 * n_contours;
+    let instructions_length = read_u16(glyph, instructions_start - 2)? as usize;
+    let mut offset = instructions_start + instructions_length;
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = read_u8(glyph, offset)?;
+        offset += 1;
+        flags.push(flag);
+        if flag & REPEAT_FLAG != 0 {
+            let repeats = read_u8(glyph, offset)?;
+            offset += 1;
+            flags.extend(std::iter::repeat_n(flag, repeats as usize));
+        }
+    }
+    if flags.len() > num_points {
+        return Err(Woff2Error::Font("flags repeat past the last point".into()));
+    }
+
+    let mut read_coordinates = |short: u8, same_or_positive: u8| {
+        flags
+            .iter()
+            .map(|&flag| {
+                let delta = if flag & short != 0 {
+                    let value = read_u8(glyph, offset)? as i16;
+                    offset += 1;
+                    if flag & same_or_positive != 0 {
+                        value
+                    } else {
+                        -value
+                    }
+                } else if flag & same_or_positive != 0 {
+                    0
+                } else {
+                    let value = read_i16(glyph, offset)?;
+                    offset += 2;
+                    value
+                };
+                Ok(delta)
+            })
+            .collect::<Result<Vec<i16>, Woff2Error>>()
+    };
+    let xs = read_coordinates(X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE)?;
+    let ys = read_coordinates(Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE)?;
+    let points = flags
+        .iter()
+        .zip(xs.into_iter().zip(ys))
+        .map(|(&flag, (x, y))| (x, y, flag & ON_CURVE_POINT != 0))
+        .collect();
+    Ok(SimpleGlyph {
+        end_points,
+        instructions: instructions_start..instructions_start + instructions_length,
+        points,
+        overlap: flags[0] & OVERLAP_SIMPLE != 0,
+    })
+}
+
+/// Splits a simple glyph into the streams
+fn transform_simple_glyph(glyph: &[u8], simple: &SimpleGlyph, streams: &mut GlyfStreams) {
+    let mut start = 0;
+    for &end in &simple.end_points {
+        push_255_u16(&mut streams.n_points, end - start + 1);
+        start = end + 1;
+    }
+    for &(dx, dy, on_curve) in &simple.points {
+        push_triplet(streams, dx, dy, on_curve);
+    }
+    push_255_u16(&mut streams.glyphs, simple.instructions.len() as u16);
+    streams
+        .instructions
+        .extend(&glyph[simple.instructions.clone()]);
+}
+
+/// Writes a point's flag to the flag stream and its coordinates to the glyph
+/// stream, in as few bytes as will hold them
+fn push_triplet(streams: &mut GlyfStreams, dx: i16, dy: i16, on_curve: bool) {
+    let on_curve_bit = if on_curve { 0 } else { 0x80 };
+    let (abs_x, abs_y) = (dx.unsigned_abs(), dy.unsigned_abs());
+    let x_sign = u8::from(dx >= 0);
+    let y_sign = u8::from(dy >= 0);
+    let xy_signs = x_sign + 2 * y_sign;
+    let (flag, data): (u8, Vec<u8>) = if dx == 0 && abs_y < 1280 {
+        (((abs_y & 0xf00) >> 7) as u8 + y_sign, vec![abs_y as u8])
+    } else if dy == 0 && abs_x < 1280 {
+        (
+            10 + ((abs_x & 0xf00) >> 7) as u8 + x_sign,
+            vec![abs_x as u8],
+        )
+    } else if abs_x < 65 && abs_y < 65 {
+        let (x, y) = (abs_x - 1, abs_y - 1);
+        (
+            20 + (x & 0x30) as u8 + ((y & 0x30) >> 2) as u8 + xy_signs,
+            vec![((x & 0xf) << 4 | (y & 0xf)) as u8],
+        )
+    } else if abs_x < 769 && abs_y < 769 {
+        let (x, y) = (abs_x - 1, abs_y - 1);
+        (
+            84 + 12 * ((x & 0x300) >> 8) as u8 + ((y & 0x300) >> 6) as u8 + xy_signs,
+            vec![x as u8, y as u8],
+        )
+    } else if abs_x < 4096 && abs_y < 4096 {
+        (
+            120 + xy_signs,
+            vec![
+                (abs_x >> 4) as u8,
+                ((abs_x & 0xf) << 4 | abs_y >> 8) as u8,
+                abs_y as u8,
+            ],
+        )
+    } else {
+        let mut data = abs_x.to_be_bytes().to_vec();
+        data.extend(abs_y.to_be_bytes());
+        (124 + xy_signs, data)
+    };
+    streams.flags.push(on_curve_bit | flag);
+    streams.glyphs.extend(data);
+}
+
+/// Copies a composite glyph's components to the composite stream, and its
+/// instructions to the instruction stream
+fn transform_composite_glyph(glyph: &[u8], streams: &mut GlyfStreams) -> Result<(), Woff2Error> {
+    let mut offset = 10;
+    let mut has_instructions = false;
+    loop {
+        let flags = read_u16(glyph, offset)?;
+        // Flags, glyph index and arguments
+        offset += if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            8
+        } else {
+            6
+        };
+        offset += if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        has_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    streams.composites.extend(slice(glyph, 10, offset - 10)?);
+    if has_instructions {
+        let length = read_u16(glyph, offset)?;
+        push_255_u16(&mut streams.glyphs, length);
+        streams
+            .instructions
+            .extend(slice(glyph, offset + 2, length as usize)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Font;
+    use crate::tables::glyf::{Component, ComponentFlags, Glyph, Point};
+    use kurbo::Affine;
+    use otspec::{Deserializer, ReaderContext};
+
+    /// Reads the numbers of a WOFF2 file, as a decoder would
+    struct Reader<'a> {
+        data: &'a [u8],
+        offset: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Reader { data, offset: 0 }
+        }
+
+        fn bytes(&mut self, length: usize) -> &'a [u8] {
+            self.offset += length;
+            &self.data[self.offset - length..self.offset]
+        }
+
+        fn u8(&mut self) -> u8 {
+            self.bytes(1)[0]
+        }
+
+        fn u16(&mut self) -> u16 {
+            u16::from_be_bytes(self.bytes(2).try_into().unwrap())
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_be_bytes(self.bytes(4).try_into().unwrap())
+        }
+
+        fn base128(&mut self) -> u32 {
+            let mut value = 0;
+            loop {
+                let byte = self.u8();
+                value = value << 7 | (byte & 0x7f) as u32;
+                if byte & 0x80 == 0 {
+                    return value;
+                }
+            }
+        }
+
+        fn u255(&mut self) -> u16 {
+            match self.u8() {
+                253 => self.u16(),
+                254 => self.u8() as u16 + 506,
+                255 => self.u8() as u16 + 253,
+                value => value as u16,
+            }
+        }
+    }
+
+    fn decode_triplet(flag: u8, glyphs: &mut Reader) -> Point {
+        let on_curve = flag & 0x80 == 0;
+        let flag = (flag & 0x7f) as i32;
+        let sign = |bit: i32, value: i32| if flag >> bit & 1 == 1 { value } else { -value };
+        let (x, y) = if flag < 10 {
+            (0, sign(0, ((flag & 14) << 7) + glyphs.u8() as i32))
+        } else if flag < 20 {
+            (sign(0, (((flag - 10) & 14) << 7) + glyphs.u8() as i32), 0)
+        } else if flag < 84 {
+            let (b0, b1) = (flag - 20, glyphs.u8() as i32);
+            (
+                sign(0, 1 + (b0 & 0x30) + (b1 >> 4)),
+                sign(1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+            )
+        } else if flag < 120 {
+            let b0 = flag - 84;
+            let x = sign(0, 1 + ((b0 / 12) << 8) + glyphs.u8() as i32);
+            (x, sign(1, 1 + (((b0 % 12) >> 2) << 8) + glyphs.u8() as i32))
+        } else if flag < 124 {
+            let (b1, b2, b3) = (glyphs.u8() as i32, glyphs.u8() as i32, glyphs.u8() as i32);
+            (
+                sign(0, (b1 << 4) + (b2 >> 4)),
+                sign(1, ((b2 & 0x0f) << 8) + b3),
+            )
+        } else {
+            let x = sign(0, glyphs.u16() as i32);
+            (x, sign(1, glyphs.u16() as i32))
+        };
+        Point {
+            x: x as i16,
+            y: y as i16,
+            on_curve,
+        }
+    }
+
+    /// Rebuilds the glyphs of a transformed glyf table
+    fn decode_glyf(data: &[u8]) -> Vec<Glyph> {
+        let mut header = Reader::new(data);
+        assert_eq!(header.u16(), 0);
+        let option_flags = header.u16();
+        let num_glyphs = header.u16() as usize;
+        header.u16();
+        let mut streams = vec![];
+        let mut start = 36;
+        for _ in 0..7 {
+            let size = header.u32() as usize;
+            streams.push(Reader::new(&data[start..start + size]));
+            start += size;
+        }
+        let overlap_bitmap = &data[start..];
+        assert_eq!(overlap_bitmap.is_empty(), option_flags & 1 == 0);
+        let mut instructions = streams.pop().unwrap();
+        let mut bboxes = streams.pop().unwrap();
+        let composites = streams.pop().unwrap();
+        let mut composites = ReaderContext::new(composites.data.to_vec());
+        let mut glyphs = streams.pop().unwrap();
+        let mut flags = streams.pop().unwrap();
+        let mut n_points = streams.pop().unwrap();
+        let mut n_contours = streams.pop().unwrap();
+        let bbox_bitmap = bboxes.bytes(4 * num_glyphs.div_ceil(32));
+
+        let mut decoded = vec![];
+        for index in 0..num_glyphs {
+            let mut glyph = Glyph {
+                xMin: 0,
+                xMax: 0,
+                yMin: 0,
+                yMax: 0,
+                contours: vec![],
+                instructions: vec![],
+                components: vec![],
+                overlap: false,
+            };
+            let contours = n_contours.u16() as i16;
+            if contours > 0 {
+                let (mut x, mut y) = (0, 0);
+                for _ in 0..contours {
+                    let count = n_points.u255();
+                    let contour = (0..count)
+                        .map(|_| {
+                            let delta = decode_triplet(flags.u8(), &mut glyphs);
+                            x += delta.x;
+                            y += delta.y;
+                            Point { x, y, ..delta }
+                        })
+                        .collect();
+                    glyph.contours.push(contour);
+                }
+                let length = glyphs.u255() as usize;
+                glyph.instructions = instructions.bytes(length).to_vec();
+                glyph.overlap = overlap_bitmap
+                    .get(index >> 3)
+                    .is_some_and(|byte| byte & 0x80 >> (index & 7) != 0);
+                let points = glyph.contours.iter().flatten();
+                glyph.xMin = points.clone().map(|pt| pt.x).min().unwrap();
+                glyph.xMax = points.clone().map(|pt| pt.x).max().unwrap();
+                glyph.yMin = points.clone().map(|pt| pt.y).min().unwrap();
+                glyph.yMax = points.map(|pt| pt.y).max().unwrap();
+            } else if contours < 0 {
+                let mut has_instructions = false;
+                loop {
+                    let component: Component = composites.de().unwrap();
+                    let more = component.flags.contains(ComponentFlags::MORE_COMPONENTS);
+                    has_instructions |= component
+                        .flags
+                        .contains(ComponentFlags::WE_HAVE_INSTRUCTIONS);
+                    glyph.components.push(component);
+                    if !more {
+                        break;
+                    }
+                }
+                if has_instructions {
+                    let length = glyphs.u255() as usize;
+                    glyph.instructions = instructions.bytes(length).to_vec();
+                }
+            }
+            if bbox_bitmap[index >> 3] & 0x80 >> (index & 7) != 0 {
+                glyph.xMin = bboxes.u16() as i16;
+                glyph.yMin = bboxes.u16() as i16;
+                glyph.xMax = bboxes.u16() as i16;
+                glyph.yMax = bboxes.u16() as i16;
+            }
+            decoded.push(glyph);
+        }
+        decoded
+    }
+
+    fn sfnt_table_range(sfnt: &[u8], tag: &[u8; 4]) -> std::ops::Range<usize> {
+        let num_tables = read_u16(sfnt, 4).unwrap() as usize;
+        let record = (0..num_tables)
+            .map(|index| 12 + 16 * index)
+            .find(|&record| &sfnt[record..record + 4] == tag)
+            .unwrap();
+        let offset = read_u32(sfnt, record + 8).unwrap() as usize;
+        let length = read_u32(sfnt, record + 12).unwrap() as usize;
+        offset..offset + length
+    }
+
+    fn sfnt_table<'a>(sfnt: &'a [u8], tag: &[u8; 4]) -> &'a [u8] {
+        &sfnt[sfnt_table_range(sfnt, tag)]
+    }
+
+    fn point(x: i16, y: i16, on_curve: bool) -> Point {
+        Point { x, y, on_curve }
+    }
+
+    /// The test font, with outlines which need each kind of point encoding,
+    /// instructions, an overlap flag and a composite glyph
+    fn test_font() -> Font {
+        let mut font = crate::ttx::tests::test_font();
+        let mut glyf = font.tables.glyf().unwrap().unwrap().into_owned();
+        glyf.glyphs[1].contours = vec![
+            vec![
+                point(0, 0, true),
+                point(0, 1000, true),
+                point(1500, 1000, true),
+                point(1510, 1005, false),
+                point(2000, 1500, false),
+                point(7000, -3000, true),
+                point(6900, -3000, true),
+            ],
+            vec![point(-50, -60, true), point(-50, 1200, true)],
+        ];
+        glyf.glyphs[1].instructions = vec![0xb0, 0x01, 0x2c];
+        let component = |glyph_index, transformation| Component {
+            glyph_index,
+            transformation,
+            match_points: None,
+            flags: ComponentFlags::empty(),
+        };
+        glyf.glyphs[3].contours = vec![];
+        glyf.glyphs[3].components = vec![
+            component(1, Affine::IDENTITY),
+            component(2, Affine::new([0.5, 0.0, 0.0, 0.5, 600.0, 0.0])),
+        ];
+        glyf.glyphs[3].instructions = vec![0xb0, 0x00];
+        glyf.recalc_bounds();
+        font.tables.insert(glyf);
+        font
+    }
+
+    #[test]
+    fn test_compress() {
+        let mut sfnt = vec![];
+        test_font().write(&mut sfnt).unwrap();
+        // Glyphs aren't written with the overlap flag, so set it on the
+        // first point of glyph 2, which has one contour and no instructions
+        let loca = sfnt_table(&sfnt, b"loca");
+        let glyph = match read_u16(sfnt_table(&sfnt, b"head"), 50).unwrap() {
+            0 => read_u16(loca, 4).unwrap() as usize * 2,
+            _ => read_u32(loca, 8).unwrap() as usize,
+        };
+        let first_flag = sfnt_table_range(&sfnt, b"glyf").start + glyph + 14;
+        sfnt[first_flag] |= OVERLAP_SIMPLE;
+        let woff2 = compress(&sfnt).unwrap();
+
+        let mut header = Reader::new(&woff2);
+        assert_eq!(header.u32(), SIGNATURE);
+        assert_eq!(header.u32(), 0x00010000);
+        assert_eq!(header.u32() as usize, woff2.len());
+        assert_eq!(woff2.len() % 4, 0);
+        let num_tables = header.u16() as usize;
+        assert_eq!(num_tables, u16::from_be_bytes([sfnt[4], sfnt[5]]) as usize);
+        assert_eq!(header.u16(), 0);
+        let total_sfnt_size = header.u32() as usize;
+        assert_eq!(total_sfnt_size, sfnt.len());
+        let compressed_size = header.u32() as usize;
+        header.bytes(24);
+
+        let mut directory = vec![];
+        for _ in 0..num_tables {
+            let flags = header.u8();
+            let tag: [u8; 4] = if flags & 63 == ARBITRARY_TAG {
+                header.bytes(4).try_into().unwrap()
+            } else {
+                *KNOWN_TAGS[(flags & 63) as usize]
+            };
+            let length = header.base128() as usize;
+            let transform_length = match &tag {
+                b"glyf" | b"loca" => {
+                    assert_eq!(flags >> 6, 0);
+                    Some(header.base128() as usize)
+                }
+                _ => None,
+            };
+            directory.push((tag, length, transform_length));
+        }
+        let tags: Vec<&[u8; 4]> = directory.iter().map(|(tag, _, _)| tag).collect();
+        let glyf = tags.iter().position(|tag| tag == &b"glyf").unwrap();
+        assert_eq!(tags[glyf + 1], b"loca");
+        assert!(tags.contains(&b"DSIG"));
+
+        let mut stream = vec![];
+        let compressed = header.bytes(compressed_size);
+        brotli::BrotliDecompress(&mut &compressed[..], &mut stream).unwrap();
+        let original = Font::from_bytes(&sfnt).unwrap();
+        let mut stream = Reader::new(&stream);
+        for (tag, length, transform_length) in directory {
+            let data = stream.bytes(transform_length.unwrap_or(length));
+            let table = sfnt_table(&sfnt, &tag);
+            assert_eq!(length, table.len());
+            match &tag {
+                b"glyf" => {
+                    let glyf = original.tables.glyf().unwrap().unwrap();
+                    assert!(glyf.glyphs[2].overlap);
+                    assert_eq!(decode_glyf(data), glyf.glyphs);
+                }
+                b"loca" => assert!(data.is_empty()),
+                b"head" => {
+                    assert_eq!(
+                        data[16..18],
+                        (u16::from_be_bytes([table[16], table[17]]) | HEAD_FLAG_TRANSFORMED)
+                            .to_be_bytes()
+                    );
+                    assert_eq!(data[18..], table[18..]);
+                }
+                _ => assert_eq!(data, table),
+            }
+        }
+    }
+
+    #[test]
+    fn test_variable_length_numbers() {
+        let mut out = vec![];
+        for value in [0, 252, 253, 505, 506, 761, 762, 65535] {
+            push_255_u16(&mut out, value);
+        }
+        for value in [0, 127, 128, 0x1234_5678] {
+            push_base128(&mut out, value);
+        }
+        assert_eq!(out[out.len() - 5..], [0x81, 0x91, 0xd1, 0xac, 0x78]);
+        let mut reader = Reader::new(&out);
+        for value in [0, 252, 253, 505, 506, 761, 762, 65535] {
+            assert_eq!(reader.u255(), value);
+        }
+        for value in [0, 127, 128, 0x1234_5678] {
+            assert_eq!(reader.base128(), value);
+        }
+    }
+}
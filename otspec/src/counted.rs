@@ -9,6 +9,7 @@ use crate::SerializationError;
 use crate::Serialize;
 
 #[derive(Shrinkwrap, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Counted<T>(pub Vec<T>);
 
 impl<T> Serialize for Counted<T>
@@ -68,6 +69,7 @@ where
 }
 
 #[derive(Shrinkwrap, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Counted32<T>(pub Vec<T>);
 
 impl<T> Serialize for Counted32<T>
@@ -7,6 +7,7 @@ use crate::{
 // These things have to be serialized/deserialized by hand because of annoying
 // format switching things.
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct Anchor {
     pub xCoordinate: int16,
@@ -26,6 +26,7 @@ tables!(
 // XXX This is also still too clever
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A class definition table.
 ///
 /// Class definitions, used to define glyph contexts in the GSUB and GPOS tables,
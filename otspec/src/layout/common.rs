@@ -129,6 +129,7 @@ impl ScriptRecord {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Feature parameter data.
 ///
 /// Certain OpenType features may have various ancillary data attached to them.
@@ -151,6 +152,7 @@ pub enum FeatureParams {
 bitflags! {
     /// Lookup qualifiers
     #[derive(Serialize, Deserialize)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct LookupFlags: u16 {
         /// Position the last glyph of a cursive positioning sequence on the baseline
         const RIGHT_TO_LEFT = 0x0001;
@@ -196,7 +198,7 @@ pub struct Lookup {
 
 impl otspec::Serialize for Lookup {
     fn to_bytes(&self, data: &mut Vec<u8>) -> Result<(), otspec::SerializationError> {
-        let obj = otspec::offsetmanager::resolve_offsets(self);
+        let obj = otspec::offsetmanager::resolve_offsets(self)?;
         self.to_bytes_shallow(data)?;
         otspec::offsetmanager::resolve_offsets_and_serialize(obj, data, false)?;
         Ok(())
@@ -238,7 +240,7 @@ pub struct LookupList {
 
 impl Serialize for LookupList {
     fn to_bytes(&self, data: &mut Vec<u8>) -> Result<(), otspec::SerializationError> {
-        let obj = otspec::offsetmanager::resolve_offsets(self);
+        let obj = otspec::offsetmanager::resolve_offsets(self)?;
         self.to_bytes_shallow(data)?;
         otspec::offsetmanager::resolve_offsets_and_serialize(obj, data, false)?;
         Ok(())
@@ -65,6 +65,7 @@ tables!(
 // Needs to be handled manually because of awkward layout and [glyphcount-1] array
 #[allow(missing_docs, non_snake_case)]
 #[derive(Debug, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SequenceRule {
     pub glyphCount: uint16,
     pub seqLookupCount: uint16,
@@ -91,11 +92,12 @@ impl Deserialize for SequenceRule {
 // Needs to be handled manually because of awkward layout
 #[allow(missing_docs, non_snake_case)]
 #[derive(Debug, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SequenceContextFormat3 {
     pub format: uint16,
     pub glyphCount: uint16,
     pub seqLookupCount: uint16,
-    pub coverages: Vec<Offset16<Coverage>>,
+    pub coverages: VecOffset16<Coverage>,
     pub seqLookupRecords: Vec<SequenceLookupRecord>,
 }
 
@@ -113,7 +115,7 @@ impl Deserialize for SequenceContextFormat3 {
             format,
             glyphCount,
             seqLookupCount,
-            coverages,
+            coverages: coverages.into(),
             seqLookupRecords,
         })
     }
@@ -122,6 +124,7 @@ impl Deserialize for SequenceContextFormat3 {
 // Needs to be handled manually because of awkward layout and [glyphcount-1] array
 #[allow(missing_docs, non_snake_case)]
 #[derive(Debug, Serialize, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChainedSequenceRule {
     #[otspec(with = "Counted")]
     pub backtrackSequence: Vec<uint16>,
@@ -22,6 +22,7 @@ tables!(
 // XXX This is still clever
 
 #[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A coverage table.
 ///
 /// OpenType lookups store information about which glyphs are affected by the
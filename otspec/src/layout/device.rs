@@ -7,6 +7,7 @@ use crate::{
 // These have to be serialized/deserialized by hand because of annoying
 // bit-packing things.
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct Device {
     pub startSize: uint16,
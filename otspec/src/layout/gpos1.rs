@@ -6,6 +6,7 @@ use crate::Deserializer;
 use otspec_macros::Serialize;
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct SinglePosFormat1 {
     #[otspec(offset_base)]
@@ -17,6 +18,7 @@ pub struct SinglePosFormat1 {
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct SinglePosFormat2 {
     #[otspec(offset_base)]
@@ -7,6 +7,7 @@ use crate::Deserializer;
 use otspec_macros::Serialize;
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct PairPosFormat1 {
     #[otspec(offset_base)]
@@ -19,6 +20,7 @@ pub struct PairPosFormat1 {
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct PairSet {
     #[otspec(offset_base)]
@@ -27,6 +29,7 @@ pub struct PairSet {
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct PairValueRecord {
     pub secondGlyph: GlyphID,
@@ -37,6 +40,7 @@ pub struct PairValueRecord {
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct PairPosFormat2 {
     #[otspec(offset_base)]
@@ -52,12 +56,14 @@ pub struct PairPosFormat2 {
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct Class1Record {
     pub class2Records: Vec<Class2Record>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 pub struct Class2Record {
     #[otspec(embed)]
@@ -350,7 +356,7 @@ mod tests {
         let gpos_ser = otspec::ser::to_bytes(&gpos).unwrap();
         let root = Offset16::to(gpos);
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.dump_graph();
         assert_eq!(gpos_ser, binary_gpos);
     }
@@ -82,7 +82,7 @@ mod tests {
         let curs_ser = otspec::ser::to_bytes(&deserialized).unwrap();
         let root = Offset16::to(deserialized);
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.dump_graph();
 
         assert_eq!(curs_ser, binary_curs);
@@ -25,6 +25,7 @@ tables!(
 );
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_snake_case)]
 /// Information about anchor positioning on a base
 pub struct Mark2Record {
@@ -24,6 +24,7 @@ tables!(
 /// Internal representation of a ligature substitution for serialization/deserialization
 #[allow(non_camel_case_types, non_snake_case)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ligature {
     pub ligatureGlyph: uint16,
     pub componentGlyphIDs: Vec<uint16>,
@@ -13,6 +13,7 @@ use crate::utils::is_all_the_same;
 // responsibility to set the Options to reflect the flags they
 // have serialized elsewhere.
 #[derive(Debug, Clone, PartialEq, Serialize, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(missing_docs, non_snake_case, non_camel_case_types)]
 #[otspec(embedded)]
 pub struct ValueRecord {
@@ -34,6 +35,7 @@ bitflags! {
     ///
     /// These are computed automatically, so you don't need to worry about them.
     #[derive(Serialize, Deserialize)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct ValueRecordFlags: u16 {
             ///	Includes horizontal adjustment for placement
             const X_PLACEMENT = 0x0001;
@@ -7,9 +7,44 @@ use petgraph::dot::Dot;
 use petgraph::graph::{Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use petgraph::visit::Topo;
+use petgraph::Direction::{Incoming, Outgoing};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+
+/// How many times the repacker duplicates shared objects and lays the graph
+/// out again before giving up.
+const MAX_REPACK_ROUNDS: usize = 32;
+
+/// An edge of the object graph: the offset field through which a parent
+/// object refers to a child.
+#[derive(Clone, Copy)]
+struct Link<'a> {
+    field: &'a dyn OffsetMarkerTrait,
+}
+
+impl fmt::Debug for Link<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Offset{}", self.field.offset_size() * 8)
+    }
+}
+
+/// An offset which is too large for its field in a given layout.
+struct Overflow {
+    parent: NodeIndex,
+    child: NodeIndex,
+    offset: usize,
+    width: usize,
+}
 
 pub struct OffsetManager<'a> {
-    dag: Graph<&'a dyn OffsetMarkerTrait, Option<usize>>,
+    dag: Graph<&'a dyn OffsetMarkerTrait, Link<'a>>,
+    // Objects already in the graph, keyed by their serialized contents and
+    // their children, so that identical subtables are only written once.
+    objects: HashMap<(Vec<u8>, Vec<Option<NodeIndex>>), NodeIndex>,
+    sizes: Vec<usize>,
+    order: Vec<NodeIndex>,
+    positions: Vec<usize>,
     resolved: bool,
 }
 
@@ -20,79 +55,242 @@ impl<'a> OffsetManager<'a> {
     {
         let mut mgr = OffsetManager {
             dag: Graph::new(),
+            objects: HashMap::new(),
+            sizes: vec![],
+            order: vec![],
+            positions: vec![],
             resolved: false,
         };
-        mgr.add_object_graph(obj);
+        mgr.add_object_graph(obj, true);
+        mgr.sizes = mgr
+            .dag
+            .node_indices()
+            .map(|node| mgr.dag[node].object_size())
+            .collect();
         mgr
     }
-    fn add_object_graph(&mut self, obj: &'a dyn OffsetMarkerTrait) -> NodeIndex<u32> {
+    // Returns `None` for null offsets, which are left out of the graph.
+    fn add_object_graph(
+        &mut self,
+        obj: &'a dyn OffsetMarkerTrait,
+        is_root: bool,
+    ) -> Option<NodeIndex> {
+        if obj.is_explicitly_zero() {
+            return None;
+        }
         let mut children = vec![];
         for f in obj.children() {
-            children.push(self.add_object_graph(&*f));
+            children.push((self.add_object_graph(f, false), f));
         }
+
+        // Children are added first, so identical children have already been
+        // merged, and two objects are the same if they have the same contents
+        // and point to the same children. The offsets to the children are
+        // not known yet, so they are zeroed for the comparison.
+        for (child, field) in &children {
+            if child.is_some() {
+                field.set(0);
+            }
+        }
+        // (The root is unique, and is serialized by its caller.)
+        let mut contents = vec![];
+        let key = if is_root || obj.serialize_contents(&mut contents).is_err() {
+            None
+        } else {
+            Some((contents, children.iter().map(|(child, _)| *child).collect()))
+        };
+        if let Some(&node) = key.as_ref().and_then(|key| self.objects.get(key)) {
+            return Some(node);
+        }
+
         let node = self.dag.add_node(obj);
-        for child in children {
-            self.dag.add_edge(node, child, None);
+        for (child, field) in children {
+            if let Some(child) = child {
+                self.dag.add_edge(node, child, Link { field });
+            }
         }
-        node
+        if let Some(key) = key {
+            self.objects.insert(key, node);
+        }
+        Some(node)
     }
+
     pub fn dump_graph(&self) {
         println!("{:#?}", Dot::new(&self.dag));
     }
 
-    pub fn resolve(&mut self) {
-        // First pass over the graph works out where everything's going to go.
+    /// Works out where every object is going to go, and sets the offsets
+    /// to them.
+    ///
+    /// Objects are first laid out in plain topological order. If any offset
+    /// then overflows its field, the graph is repacked: objects are ordered
+    /// by their distance from the root, with each subgraph below a 32-bit
+    /// offset kept together in a space of its own after the 16-bit-addressed
+    /// objects, and shared objects which are still out of reach of one of
+    /// their parents are duplicated. An error is returned only if no layout
+    /// can be found.
+    pub fn resolve(&mut self) -> Result<(), SerializationError> {
+        let order = self.topological_order();
+        self.order = if self.overflows(&order).is_empty() {
+            order
+        } else {
+            self.repack()?
+        };
+        self.positions = self.positions(&self.order);
+        for &node in &self.order {
+            self.set_offsets(node);
+        }
+        self.resolved = true;
+        Ok(())
+    }
+
+    fn topological_order(&self) -> Vec<NodeIndex> {
         let mut topo = Topo::new(&self.dag);
-        let mut node = topo.next(&self.dag);
+        let mut order = vec![];
+        while let Some(node) = topo.next(&self.dag) {
+            order.push(node);
+        }
+        order
+    }
+
+    fn positions(&self, order: &[NodeIndex]) -> Vec<usize> {
+        let mut positions = vec![0; self.dag.node_count()];
         let mut offset_counter = 0;
+        for node in order {
+            positions[node.index()] = offset_counter;
+            offset_counter += self.sizes[node.index()];
+        }
+        positions
+    }
+
+    fn overflows(&self, order: &[NodeIndex]) -> Vec<Overflow> {
+        let positions = self.positions(order);
+        let mut overflows = vec![];
+        for &parent in order {
+            for edge in self.dag.edges_directed(parent, Outgoing) {
+                let offset = positions[edge.target().index()] - positions[parent.index()];
+                let width = edge.weight().field.offset_size();
+                if width < 8 && offset as u64 >= 1 << (8 * width) {
+                    overflows.push(Overflow {
+                        parent,
+                        child: edge.target(),
+                        offset,
+                        width,
+                    });
+                }
+            }
+        }
+        overflows
+    }
 
-        while node.is_some() {
-            let this_offset = self.dag.node_weight(node.unwrap()).unwrap();
-            // Set the edge to this
-            let size = this_offset.object_size();
-            if offset_counter > 0 {
-                let parent = self
-                    .dag
-                    .edges_directed(node.unwrap(), petgraph::Direction::Incoming)
-                    .next()
-                    .unwrap()
-                    .source();
-                self.dag
-                    .update_edge(parent, node.unwrap(), Some(offset_counter));
+    fn repack(&mut self) -> Result<Vec<NodeIndex>, SerializationError> {
+        let mut rounds = 0;
+        loop {
+            let order = self.shortest_distance_order();
+            let overflows = self.overflows(&order);
+            if overflows.is_empty() {
+                return Ok(order);
             }
-            offset_counter += size; // Pad to multiple of 4 or whatever
-            node = topo.next(&self.dag);
+            if rounds == MAX_REPACK_ROUNDS || !self.duplicate_shared(&overflows) {
+                let worst = overflows.iter().max_by_key(|o| o.offset).unwrap();
+                return Err(SerializationError(format!(
+                    "Offset overflow: {} offset(s) could not be packed, including a {}-bit offset of {} bytes",
+                    overflows.len(),
+                    worst.width * 8,
+                    worst.offset
+                )));
+            }
+            rounds += 1;
         }
+    }
 
-        // Second pass over the graph works out the offsets, resetting to zero
-        // at the top of each subtable.
-        let mut topo = Topo::new(&self.dag);
-        let mut node = topo.next(&self.dag);
-        let mut base = 0;
-        while node.is_some() {
-            let children_edges = self
-                .dag
-                .edges_directed(node.unwrap(), petgraph::Direction::Outgoing);
-            let mut parent_edges = self
-                .dag
-                .edges_directed(node.unwrap(), petgraph::Direction::Incoming);
-            if let Some(p) = parent_edges.next() {
-                base = p.weight().unwrap();
+    /// Orders the graph so that each object comes as soon as possible after
+    /// its parents, smaller objects first. Objects reached through a 32-bit
+    /// offset start a new space, which is placed after all the earlier ones.
+    fn shortest_distance_order(&self) -> Vec<NodeIndex> {
+        let mut keys: Vec<Option<(usize, usize)>> = vec![None; self.dag.node_count()];
+        let mut next_space = 1;
+        for node in self.topological_order() {
+            let (space, distance) = *keys[node.index()].get_or_insert((0, 0));
+            for edge in self.dag.edges_directed(node, Outgoing) {
+                let child = edge.target().index();
+                let candidate = if edge.weight().field.offset_size() > 2 {
+                    next_space += 1;
+                    (next_space - 1, self.sizes[child])
+                } else {
+                    (space, distance + self.sizes[child])
+                };
+                keys[child] = Some(match keys[child] {
+                    Some((s, d)) => (s.max(candidate.0), d.min(candidate.1)),
+                    None => candidate,
+                });
             }
+        }
 
-            for c in children_edges {
-                let offset = c.weight().unwrap() - base;
-                let target_id = c.target();
-                let target_node = self.dag.node_weight(target_id).unwrap();
-                if !target_node.is_explicitly_zero() {
-                    target_node.set(offset as u32);
+        // Kahn's algorithm, always taking the nearest object whose parents
+        // have all been placed.
+        let mut parents_left: Vec<usize> = self
+            .dag
+            .node_indices()
+            .map(|node| self.dag.edges_directed(node, Incoming).count())
+            .collect();
+        let mut heap: BinaryHeap<Reverse<((usize, usize), NodeIndex)>> = self
+            .dag
+            .node_indices()
+            .filter(|node| parents_left[node.index()] == 0)
+            .map(|node| Reverse((keys[node.index()].unwrap_or_default(), node)))
+            .collect();
+        let mut order = vec![];
+        while let Some(Reverse((_, node))) = heap.pop() {
+            order.push(node);
+            for child in self.dag.neighbors_directed(node, Outgoing) {
+                parents_left[child.index()] -= 1;
+                if parents_left[child.index()] == 0 {
+                    heap.push(Reverse((keys[child.index()].unwrap_or_default(), child)));
                 }
             }
-            node = topo.next(&self.dag);
         }
+        order
+    }
 
-        // self.dump_graph();
-        self.resolved = true;
+    /// Gives the parent of each overflowing offset to a shared object its
+    /// own copy of that object. Returns `false` if there was nothing to
+    /// duplicate.
+    fn duplicate_shared(&mut self, overflows: &[Overflow]) -> bool {
+        let mut duplicated = false;
+        for overflow in overflows {
+            if self.dag.edges_directed(overflow.child, Incoming).count() < 2 {
+                continue;
+            }
+            let edge = match self.dag.find_edge(overflow.parent, overflow.child) {
+                Some(edge) => edge,
+                None => continue,
+            };
+            let copy = self.dag.add_node(self.dag[overflow.child]);
+            self.sizes.push(self.sizes[overflow.child.index()]);
+            let grandchildren: Vec<_> = self
+                .dag
+                .edges_directed(overflow.child, Outgoing)
+                .map(|e| (e.target(), *e.weight()))
+                .collect();
+            for (grandchild, link) in grandchildren.into_iter().rev() {
+                self.dag.add_edge(copy, grandchild, link);
+            }
+            let link = self.dag.remove_edge(edge).unwrap();
+            self.dag.add_edge(overflow.parent, copy, link);
+            duplicated = true;
+        }
+        duplicated
+    }
+
+    /// Sets the offsets from an object to its children. Duplicated objects
+    /// share their offset fields, so this is done again just before each
+    /// copy is written.
+    fn set_offsets(&self, node: NodeIndex) {
+        for edge in self.dag.edges_directed(node, Outgoing) {
+            let offset = self.positions[edge.target().index()] - self.positions[node.index()];
+            edge.weight().field.set(offset as u32);
+        }
     }
 
     pub fn serialize(
@@ -101,18 +299,10 @@ impl<'a> OffsetManager<'a> {
         do_top: bool,
     ) -> Result<(), SerializationError> {
         assert!(self.resolved);
-        let mut topo = Topo::new(&self.dag);
-        let mut node = topo.next(&self.dag);
-        if !do_top {
-            if node.is_none() {
-                return Ok(());
-            }
-            node = topo.next(&self.dag);
-        }
-        while node.is_some() {
-            let this_offset = self.dag.node_weight(node.unwrap()).unwrap();
-            this_offset.serialize_contents(output)?;
-            node = topo.next(&self.dag);
+        let skip = if do_top { 0 } else { 1 };
+        for &node in self.order.iter().skip(skip) {
+            self.set_offsets(node);
+            self.dag[node].serialize_contents(output)?;
         }
         Ok(())
     }
@@ -134,14 +324,14 @@ where
     false
 }
 
-pub fn resolve_offsets<T>(obj: T) -> T
+pub fn resolve_offsets<T>(obj: T) -> Result<T, SerializationError>
 where
     T: Serialize,
 {
     let root = Offset16::to(obj);
     let mut mgr = OffsetManager::new(&root);
-    mgr.resolve();
-    root.link.unwrap()
+    mgr.resolve()?;
+    Ok(root.link.unwrap())
 }
 
 pub fn resolve_offsets_and_serialize<T>(
@@ -154,7 +344,7 @@ where
 {
     let root = Offset16::to(obj);
     let mut mgr = OffsetManager::new(&root);
-    mgr.resolve();
+    mgr.resolve()?;
     mgr.serialize(output, do_top)
 }
 
@@ -184,6 +374,7 @@ mod tests {
     }
 
     #[derive(Deserialize, Debug, PartialEq, Serialize, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Three {
         blah: uint16,
     }
@@ -213,7 +404,7 @@ mod tests {
                 .len(),
             0
         );
-        let one = resolve_offsets(one);
+        let one = resolve_offsets(one).unwrap();
         assert_eq!(one.anoffset.offset_value(), Some(8));
         assert_eq!(one.asecondoffset.offset_value(), Some(16));
 
@@ -313,7 +504,7 @@ mod tests {
         let mut output = vec![];
         let root = Offset16::to(has_embedding);
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.serialize(&mut output, true).unwrap();
         assert_eq!(
             output,
@@ -356,7 +547,7 @@ mod tests {
         let mut serialized = vec![];
         let root = Offset16::to(has_embedding_array.clone());
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.dump_graph();
         mgr.serialize(&mut serialized, true).unwrap();
         assert_eq!(
@@ -454,7 +645,7 @@ mod tests {
         let mut output = vec![];
         let root = Offset16::to(has_embedding_array);
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.dump_graph();
         mgr.serialize(&mut output, true).unwrap();
         assert_eq!(
@@ -530,7 +721,7 @@ mod tests {
         let mut output = vec![];
         let root = Offset16::to(has_offset_array);
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.dump_graph();
         mgr.serialize(&mut output, true).unwrap();
         assert_eq!(
@@ -631,7 +822,7 @@ mod tests {
         let mut output = vec![];
         let root = Offset16::to(deep);
         let mut mgr = OffsetManager::new(&root);
-        mgr.resolve();
+        mgr.resolve().unwrap();
         mgr.dump_graph();
         mgr.serialize(&mut output, true).unwrap();
         assert_eq!(
@@ -648,4 +839,161 @@ mod tests {
             ]
         );
     }
+
+    // Overflow handling
+    tables!(
+        Big {
+            Counted(uint16) values
+        }
+
+        BigThenSmall {
+            [offset_base]
+            Offset16(Big) big
+            Offset16(Three) small
+        }
+
+        FarAway {
+            [offset_base]
+            Offset32(BigThenSmall) far
+            Offset16(Three) near
+        }
+
+        Twice {
+            [offset_base]
+            Offset16(Three) one
+            Offset16(Three) two
+            Offset16(Three) three
+        }
+
+        Near {
+            [offset_base]
+            Offset16(Three) three
+        }
+
+        BigThenNear {
+            [offset_base]
+            Counted(uint16) values
+            Offset16(Near) near
+        }
+
+        Diamond {
+            [offset_base]
+            Offset16(Near) near
+            Offset16(Big) big
+            Offset16(BigThenNear) far
+        }
+
+        TooBig {
+            [offset_base]
+            Offset16(Big) one
+            Offset16(Big) two
+            Offset16(Three) three
+        }
+    );
+
+    fn big(value: uint16, count: usize) -> Big {
+        Big {
+            values: vec![value; count],
+        }
+    }
+
+    #[test]
+    fn test_overflow_reordering() {
+        let table = BigThenSmall {
+            big: Offset16::to(big(0xaaaa, 40000)),
+            small: Offset16::to(Three { blah: 0x1010 }),
+        };
+        let mut output = vec![];
+        table.to_bytes(&mut output).unwrap();
+        assert_eq!(output.len(), 4 + 2 + 80002);
+        // The small object is moved in front of the big one
+        assert_eq!(&output[0..6], &[0x00, 0x06, 0x00, 0x04, 0x10, 0x10]);
+        let rede: BigThenSmall = otspec::de::from_bytes(&output).unwrap();
+        assert_eq!(rede, table);
+    }
+
+    #[test]
+    fn test_overflow_spaces() {
+        let table = FarAway {
+            far: Offset32::to(BigThenSmall {
+                big: Offset16::to(big(0xaaaa, 40000)),
+                small: Offset16::to(Three { blah: 0x1010 }),
+            }),
+            near: Offset16::to(Three { blah: 0x2020 }),
+        };
+        let mut output = vec![];
+        table.to_bytes(&mut output).unwrap();
+        // Everything below the 32-bit offset comes after the 16-bit
+        // addressed object.
+        assert_eq!(
+            &output[0..14],
+            &[
+                0x00, 0x00, 0x00, 0x08, // far
+                0x00, 0x06, // near
+                0x20, 0x20, // near = Three
+                0x00, 0x06, // far.big
+                0x00, 0x04, // far.small
+                0x10, 0x10, // far.small = Three
+            ]
+        );
+        let rede: FarAway = otspec::de::from_bytes(&output).unwrap();
+        assert_eq!(rede, table);
+    }
+
+    #[test]
+    fn test_overflow_error() {
+        let table = TooBig {
+            one: Offset16::to(big(0xaaaa, 40000)),
+            two: Offset16::to(big(0xbbbb, 40000)),
+            three: Offset16::to(Three { blah: 0x1010 }),
+        };
+        let mut output = vec![];
+        let err = table.to_bytes(&mut output).unwrap_err();
+        assert!(err.0.starts_with("Offset overflow"));
+    }
+
+    #[test]
+    fn test_sharing() {
+        let table = Twice {
+            one: Offset16::to(Three { blah: 0x1010 }),
+            two: Offset16::to(Three { blah: 0x2020 }),
+            three: Offset16::to(Three { blah: 0x1010 }),
+        };
+        let mut output = vec![];
+        table.to_bytes(&mut output).unwrap();
+        assert_eq!(
+            output,
+            vec![
+                0x00, 0x06, // one
+                0x00, 0x08, // two
+                0x00, 0x06, // three, the same as one
+                0x10, 0x10, // one and three = Three
+                0x20, 0x20, // two = Three
+            ]
+        );
+        let rede: Twice = otspec::de::from_bytes(&output).unwrap();
+        assert_eq!(rede, table);
+    }
+
+    #[test]
+    fn test_overflow_duplication() {
+        // The `Near` table is shared between the root and the far table,
+        // but it can only be reached by both if it is duplicated.
+        let near = Near {
+            three: Offset16::to(Three { blah: 0x1010 }),
+        };
+        let table = Diamond {
+            near: Offset16::to(near.clone()),
+            big: Offset16::to(big(0xaaaa, 30000)),
+            far: Offset16::to(BigThenNear {
+                values: vec![0xbbbb; 30000],
+                near: Offset16::to(near),
+            }),
+        };
+        let mut output = vec![];
+        table.to_bytes(&mut output).unwrap();
+        assert_eq!(output.len(), 6 + 2 * 2 + 2 * 2 + 60002 + 60004);
+        let rede: Diamond = otspec::de::from_bytes(&output).unwrap();
+        assert_eq!(rede, table);
+    }
 }
@@ -61,6 +61,8 @@ pub trait OffsetMarkerTrait: Serialize + Debug {
     fn total_size_with_descendants(&self) -> usize;
     fn needs_resolving(&self) -> bool;
     fn is_explicitly_zero(&self) -> bool;
+    // The size in bytes of the offset field itself.
+    fn offset_size(&self) -> usize;
     // This is gross. Having polymorphic offset marker traits would make everything horrible,
     // so we have to specify the highest offset we need and cast downwards.
     fn set(&self, off: u32);
@@ -105,6 +107,10 @@ where
         false
     }
 
+    fn offset_size(&self) -> usize {
+        ::std::mem::size_of::<U>()
+    }
+
     fn is_explicitly_zero(&self) -> bool {
         self.link.is_none()
             && self.off.borrow().is_some()
@@ -235,6 +241,25 @@ where
     }
 }
 
+/// Offsets are serialized as the subtable they point to (or nothing); their
+/// values are recomputed when the table is compiled.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, U: OffsetType> serde::Serialize for Offset<T, U> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.link.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>, U: OffsetType> serde::Deserialize<'de> for Offset<T, U> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(thing) => Offset::to(thing),
+            None => Offset::to_nothing(),
+        })
+    }
+}
+
 // Vector of offsets
 
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -276,6 +301,22 @@ where
     }
 }
 
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, U: OffsetType> serde::Serialize for VecOffset<T, U> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.v.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>, U: OffsetType> serde::Deserialize<'de> for VecOffset<T, U> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(VecOffset {
+            v: Vec::deserialize(deserializer)?,
+        })
+    }
+}
+
 impl<T, U: OffsetType> From<VecOffset<T, U>> for Vec<Offset<T, U>> {
     fn from(v: VecOffset<T, U>) -> Self {
         v.v
@@ -377,6 +418,10 @@ where
         self.as_ref().map_or(true, |x| x.is_explicitly_zero())
     }
 
+    fn offset_size(&self) -> usize {
+        ::std::mem::size_of::<U>()
+    }
+
     fn set(&self, off: u32) {
         if let Some(x) = self {
             let new_off: Result<U, <u32 as std::convert::TryInto<U>>::Error> = off.try_into();
@@ -51,6 +51,7 @@ impl Default for GPOSLookupList {
     }
 }
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GPOSLookup {
     pub lookupType: uint16,
     pub lookupFlag: LookupFlags,
@@ -167,9 +168,9 @@ impl Deserialize for ExtensionPosFormat1 {
         c.push();
         let subst_format: uint16 = c.de()?;
         let extension_lookup_type: uint16 = c.de()?;
-        if !(0..=6).contains(&extension_lookup_type) {
+        if !(1..=8).contains(&extension_lookup_type) {
             return Err(crate::DeserializationError(format!(
-                "Bad GSUB extension lookup type {:?}",
+                "Bad GPOS extension lookup type {:?}",
                 extension_lookup_type
             )));
         }
@@ -190,6 +191,7 @@ impl Deserialize for ExtensionPosFormat1 {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GPOSSubtable {
     /// Contains a single positioning rule.
     GPOS1_1(SinglePosFormat1),
@@ -51,6 +51,7 @@ impl Default for GSUBLookupList {
     }
 }
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GSUBLookup {
     pub lookupType: uint16,
     pub lookupFlag: LookupFlags,
@@ -164,7 +165,7 @@ impl Deserialize for ExtensionSubstFormat1 {
         c.push();
         let subst_format: uint16 = c.de()?;
         let extension_lookup_type: uint16 = c.de()?;
-        if !(0..=6).contains(&extension_lookup_type) {
+        if !matches!(extension_lookup_type, 1..=6 | 8) {
             return Err(crate::DeserializationError(format!(
                 "Bad GSUB extension lookup type {:?}",
                 extension_lookup_type
@@ -187,6 +188,7 @@ impl Deserialize for ExtensionSubstFormat1 {
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GSUBSubtable {
     GSUB1_1(SingleSubstFormat1),
     GSUB1_2(SingleSubstFormat2),
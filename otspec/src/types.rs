@@ -21,6 +21,7 @@ pub type UFWORD = u16;
 pub type GlyphID = u16;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 pub struct uint24(u32);
 
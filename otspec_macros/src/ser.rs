@@ -31,7 +31,7 @@ pub fn expand_derive_serialize(
                     let obj = if self.offset_fields().is_empty() {
                         self
                     } else {
-                        otspec::offsetmanager::resolve_offsets(self)
+                        otspec::offsetmanager::resolve_offsets(self)?
                     };
                 }
             } else {
@@ -154,6 +154,7 @@ pub fn expand_tables(item: TokenStream) -> TokenStream {
             "/// Low-level structure used for serializing/deserializing table\n\
             #[allow(missing_docs, non_snake_case, non_camel_case_types)]\n\
             #[derive({} {} {} {} PartialEq, Clone)]\n\
+            #[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]\n\
             {}\n\
             pub struct {} {{",
             do_serialize, do_deserialize, do_debug, do_default, embed_attr, table_name,